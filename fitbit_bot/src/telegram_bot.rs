@@ -16,6 +16,7 @@ use tokio::{
 
 use fitbit_lib::scale_measurement::ScaleMeasurement;
 use garmin_lib::garmin_config::GarminConfig;
+use garmin_models::provenance::{DataProvider, Provenance};
 use garmin_utils::{garmin_util::get_list_of_telegram_userids, pgpool::PgPool};
 
 use super::failure_count::FailureCount;
@@ -169,7 +170,12 @@ impl TelegramBot {
         &self,
         mut meas: ScaleMeasurement,
     ) -> Result<ScaleMeasurement, Error> {
-        if meas.insert_into_db(&self.pool).await.is_ok() {
+        let provenance = Provenance::new(DataProvider::Telegram, "scale-measurement-bot", None);
+        if meas
+            .insert_into_db_with_provenance(&self.pool, &provenance)
+            .await
+            .is_ok()
+        {
             debug!("{:?}", meas);
             LAST_WEIGHT.store(Some(meas));
             FAILURE_COUNT.reset()?;
@@ -0,0 +1,311 @@
+use anyhow::{format_err, Error};
+use glob::glob;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tokio::task::spawn_blocking;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
+use garmin_models::{cache_store::CacheStore, garmin_sync::GarminSync};
+use garmin_utils::garmin_util::get_md5sum;
+
+/// Key of the manifest in the backup bucket
+pub const MANIFEST_KEY: &str = "manifest.json";
+
+/// Checksum and size of one archive partition (a monthly parquet file)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LocalPartition {
+    pub md5sum: StackString,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackupPartition {
+    pub md5sum: StackString,
+    pub size: u64,
+    pub backed_up: DateTimeWrapper,
+}
+
+/// What the backup bucket is supposed to hold, partitions are only uploaded
+/// when their checksum differs from the one recorded here
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ArchiveManifest {
+    pub updated: Option<DateTimeWrapper>,
+    pub partitions: BTreeMap<StackString, BackupPartition>,
+}
+
+impl ArchiveManifest {
+    /// Local partitions which are new or changed since the last backup
+    #[must_use]
+    pub fn changed<'a>(
+        &self,
+        local: &'a BTreeMap<StackString, LocalPartition>,
+    ) -> Vec<(&'a StackString, &'a LocalPartition)> {
+        local
+            .iter()
+            .filter(|(key, partition)| {
+                self.partitions
+                    .get(*key)
+                    .is_none_or(|p| p.md5sum != partition.md5sum)
+            })
+            .collect()
+    }
+}
+
+/// Checksums of the parquet files in `local_dir`
+fn local_partitions(local_dir: &Path) -> Result<BTreeMap<StackString, LocalPartition>, Error> {
+    let pattern = format_sstr!("{}/*.parquet", local_dir.to_string_lossy());
+    let mut partitions = BTreeMap::new();
+    for path in glob(&pattern)? {
+        let path = path?;
+        let key = path
+            .file_name()
+            .ok_or_else(|| format_err!("No filename {path:?}"))?
+            .to_string_lossy()
+            .into();
+        let partition = LocalPartition {
+            md5sum: get_md5sum(&path)?,
+            size: fs::metadata(&path)?.len(),
+        };
+        partitions.insert(key, partition);
+    }
+    Ok(partitions)
+}
+
+/// Replicates the heart rate parquet archive to
+/// `fitbit_archive_backup_bucket`, partitions are copied as they change and
+/// `manifest.json` records the checksum of each one so the backup can be
+/// verified and restored without listing the bucket
+pub struct ArchiveBackup {
+    gsync: GarminSync,
+    store: CacheStore,
+    local_dir: PathBuf,
+    bucket: StackString,
+}
+
+impl ArchiveBackup {
+    /// `None` unless `fitbit_archive_backup_bucket` is set and s3 is enabled
+    pub async fn new(config: &GarminConfig) -> Option<Self> {
+        let bucket = config.fitbit_archive_backup_bucket.clone()?;
+        let gsync = GarminSync::from_config(config).await?;
+        Some(Self {
+            gsync,
+            store: CacheStore::fitbit_archive(config).await,
+            local_dir: config.fitbit_archivedir.clone(),
+            bucket,
+        })
+    }
+
+    /// # Errors
+    /// Return error if the manifest can't be read or parsed
+    pub async fn read_manifest(&self) -> Result<ArchiveManifest, Error> {
+        match self.gsync.get_object(&self.bucket, MANIFEST_KEY).await? {
+            Some(body) => serde_json::from_slice(&body).map_err(Into::into),
+            None => Ok(ArchiveManifest::default()),
+        }
+    }
+
+    async fn write_manifest(&self, manifest: &ArchiveManifest) -> Result<(), Error> {
+        let body = serde_json::to_vec_pretty(manifest)?;
+        self.gsync
+            .put_object(&self.bucket, MANIFEST_KEY, body)
+            .await?;
+        Ok(())
+    }
+
+    async fn local_partitions(&self) -> Result<BTreeMap<StackString, LocalPartition>, Error> {
+        if !self.local_dir.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let local_dir = self.local_dir.clone();
+        spawn_blocking(move || local_partitions(&local_dir)).await?
+    }
+
+    /// Upload partitions which changed since the last backup, then the
+    /// updated manifest
+    /// # Errors
+    /// Return error if s3 api calls fail or an upload doesn't match its
+    /// local checksum
+    pub async fn backup(&self) -> Result<Vec<StackString>, Error> {
+        let mut manifest = self.read_manifest().await?;
+        let local = self.local_partitions().await?;
+        let changed = manifest.changed(&local);
+        if changed.is_empty() {
+            return Ok(vec![format_sstr!(
+                "Archive backup {} up to date, {} partitions",
+                self.bucket,
+                manifest.partitions.len()
+            )]);
+        }
+        let mut output = Vec::with_capacity(changed.len());
+        for (key, partition) in changed {
+            let etag = self
+                .gsync
+                .upload_file(&self.local_dir.join(key), &self.bucket, key)
+                .await?;
+            if etag != partition.md5sum {
+                return Err(format_err!(
+                    "Backup of {key} has etag {etag}, expected {}",
+                    partition.md5sum
+                ));
+            }
+            manifest.partitions.insert(
+                key.clone(),
+                BackupPartition {
+                    md5sum: partition.md5sum.clone(),
+                    size: partition.size,
+                    backed_up: DateTimeWrapper::now(),
+                },
+            );
+            output.push(format_sstr!("Backed up {key} to {}", self.bucket));
+        }
+        manifest.updated = Some(DateTimeWrapper::now());
+        self.write_manifest(&manifest).await?;
+        Ok(output)
+    }
+
+    /// Check every partition in the manifest is in the bucket with the
+    /// recorded checksum, and report local partitions which differ from
+    /// their backup
+    /// # Errors
+    /// Return error if s3 api calls fail
+    pub async fn verify(&self) -> Result<Vec<StackString>, Error> {
+        let manifest = self.read_manifest().await?;
+        let local = self.local_partitions().await?;
+        let mut output = Vec::new();
+        let mut problems = 0;
+        for (key, partition) in &manifest.partitions {
+            match self.gsync.get_etag(&self.bucket, key).await? {
+                None => {
+                    problems += 1;
+                    output.push(format_sstr!("{key} missing from {}", self.bucket));
+                }
+                Some(etag) if etag != partition.md5sum => {
+                    problems += 1;
+                    output.push(format_sstr!(
+                        "{key} has etag {etag}, manifest has {}",
+                        partition.md5sum
+                    ));
+                }
+                Some(_) => debug!("{key} verified"),
+            }
+            match local.get(key) {
+                Some(l) if l.md5sum != partition.md5sum => {
+                    output.push(format_sstr!("{key} changed locally since last backup"));
+                }
+                None => output.push(format_sstr!("{key} only in backup")),
+                Some(_) => (),
+            }
+        }
+        for key in local.keys() {
+            if !manifest.partitions.contains_key(key) {
+                output.push(format_sstr!("{key} not backed up"));
+            }
+        }
+        output.push(format_sstr!(
+            "Verified {} partitions in {}, {problems} problems",
+            manifest.partitions.len(),
+            self.bucket
+        ));
+        Ok(output)
+    }
+
+    /// Download partitions from the backup which are missing locally (or
+    /// all of them with `force`), checking each against the manifest before
+    /// it replaces the local partition; with s3 cache storage they're also
+    /// written back to `fitbit_archive_bucket`
+    /// # Errors
+    /// Return error if s3 api calls fail or a download doesn't match the
+    /// manifest
+    pub async fn restore(&self, force: bool) -> Result<Vec<StackString>, Error> {
+        let manifest = self.read_manifest().await?;
+        if manifest.partitions.is_empty() {
+            return Err(format_err!("No manifest in {}", self.bucket));
+        }
+        let local = self.local_partitions().await?;
+        if !self.local_dir.exists() {
+            tokio::fs::create_dir_all(&self.local_dir).await?;
+        }
+        let mut output = Vec::new();
+        for (key, partition) in &manifest.partitions {
+            if !force && local.contains_key(key) {
+                continue;
+            }
+            // download next to the partition and only replace it once the
+            // checksum matches, a bad download never clobbers a good file
+            let local_file = self.local_dir.join(key);
+            let tmp_file = self.local_dir.join(format_sstr!("{key}.restore"));
+            self.gsync
+                .download_file(&tmp_file, &self.bucket, key)
+                .await?;
+            let md5sum = {
+                let tmp_file = tmp_file.clone();
+                spawn_blocking(move || get_md5sum(&tmp_file)).await??
+            };
+            if md5sum != partition.md5sum {
+                tokio::fs::remove_file(&tmp_file).await?;
+                return Err(format_err!(
+                    "Restored {key} has md5sum {md5sum}, manifest has {}",
+                    partition.md5sum
+                ));
+            }
+            tokio::fs::rename(&tmp_file, &local_file).await?;
+            self.store.store(key).await?;
+            output.push(format_sstr!("Restored {key} from {}", self.bucket));
+        }
+        if output.is_empty() {
+            output.push(format_sstr!("Nothing to restore from {}", self.bucket));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::btreemap;
+
+    use garmin_lib::date_time_wrapper::DateTimeWrapper;
+
+    use crate::archive_backup::{ArchiveManifest, BackupPartition, LocalPartition};
+
+    #[test]
+    fn test_manifest_changed() {
+        let backed_up = |md5sum: &str| BackupPartition {
+            md5sum: md5sum.into(),
+            size: 100,
+            backed_up: DateTimeWrapper::now(),
+        };
+        let local = |md5sum: &str| LocalPartition {
+            md5sum: md5sum.into(),
+            size: 100,
+        };
+        let manifest = ArchiveManifest {
+            updated: Some(DateTimeWrapper::now()),
+            partitions: btreemap! {
+                "2024-01.parquet".into() => backed_up("aaa"),
+                "2024-02.parquet".into() => backed_up("bbb"),
+            },
+        };
+        let partitions = btreemap! {
+            "2024-01.parquet".into() => local("aaa"),
+            "2024-02.parquet".into() => local("ccc"),
+            "2024-03.parquet".into() => local("ddd"),
+        };
+        let changed: Vec<_> = manifest
+            .changed(&partitions)
+            .into_iter()
+            .map(|(k, _)| k.as_str())
+            .collect();
+        assert_eq!(changed, vec!["2024-02.parquet", "2024-03.parquet"]);
+        assert_eq!(ArchiveManifest::default().changed(&partitions).len(), 3);
+
+        let json = serde_json::to_string(&manifest).unwrap();
+        let parsed: ArchiveManifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.partitions.len(), 2);
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Error;
+use std::collections::BTreeSet;
+use time::{Date, OffsetDateTime};
+
+use garmin_lib::garmin_config::GarminConfig;
+
+use crate::fitbit_heartrate::FitbitHeartRate;
+
+/// Bluetooth SIG assigned number of the Heart Rate service
+pub const HEART_RATE_SERVICE: u16 = 0x180D;
+/// Bluetooth SIG assigned number of the Heart Rate Measurement characteristic
+pub const HEART_RATE_MEASUREMENT: u16 = 0x2A37;
+
+/// Parse a Heart Rate Measurement notification, bit 0 of the flags byte
+/// selects between a `u8` and a little endian `u16` value
+#[must_use]
+pub fn parse_heart_rate_measurement(data: &[u8]) -> Option<u16> {
+    let flags = *data.first()?;
+    if flags & 0x01 == 0 {
+        data.get(1).map(|v| u16::from(*v))
+    } else {
+        Some(u16::from_le_bytes([*data.get(1)?, *data.get(2)?]))
+    }
+}
+
+/// Buffer of samples received from a heart rate strap, flushed into the
+/// fitbit heartrate avro archive
+#[derive(Debug, Default)]
+pub struct HeartRateRecorder {
+    samples: Vec<FitbitHeartRate>,
+}
+
+impl HeartRateRecorder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the measurement in `data` received at `datetime`, zero values
+    /// and repeated samples within the same second are dropped
+    pub fn add_measurement(&mut self, datetime: OffsetDateTime, data: &[u8]) -> Option<i32> {
+        let value = parse_heart_rate_measurement(data).filter(|v| *v > 0)?;
+        if let Some(last) = self.samples.last() {
+            if last.datetime.unix_timestamp() == datetime.unix_timestamp() {
+                return None;
+            }
+        }
+        let value = i32::from(value);
+        self.samples.push(FitbitHeartRate {
+            datetime: datetime.into(),
+            value,
+        });
+        Some(value)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Merge buffered samples into the archive, returns the dates written
+    /// # Errors
+    /// Return error if `merge_slice_to_avro` fails
+    pub fn flush(&mut self, config: &GarminConfig) -> Result<BTreeSet<Date>, Error> {
+        if self.samples.is_empty() {
+            return Ok(BTreeSet::new());
+        }
+        let dates = FitbitHeartRate::merge_slice_to_avro(config, &self.samples)?;
+        self.samples.clear();
+        Ok(dates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+
+    use crate::ble_heartrate::{parse_heart_rate_measurement, HeartRateRecorder};
+
+    #[test]
+    fn test_parse_heart_rate_measurement() {
+        assert_eq!(parse_heart_rate_measurement(&[0x00, 72]), Some(72));
+        assert_eq!(
+            parse_heart_rate_measurement(&[0x16, 64, 0x20, 0x03]),
+            Some(64)
+        );
+        assert_eq!(parse_heart_rate_measurement(&[0x01, 0x2c, 0x01]), Some(300));
+        assert_eq!(parse_heart_rate_measurement(&[0x01, 0x2c]), None);
+        assert_eq!(parse_heart_rate_measurement(&[]), None);
+    }
+
+    #[test]
+    fn test_heart_rate_recorder() {
+        let mut recorder = HeartRateRecorder::new();
+        let t0 = datetime!(2024-01-01 12:00:00 UTC);
+        assert_eq!(recorder.add_measurement(t0, &[0x00, 90]), Some(90));
+        assert_eq!(
+            recorder.add_measurement(t0 + Duration::milliseconds(500), &[0x00, 91]),
+            None
+        );
+        assert_eq!(
+            recorder.add_measurement(t0 + Duration::seconds(1), &[0x00, 0]),
+            None
+        );
+        assert_eq!(
+            recorder.add_measurement(t0 + Duration::seconds(1), &[0x00, 92]),
+            Some(92)
+        );
+        assert_eq!(recorder.len(), 2);
+    }
+}
@@ -0,0 +1,355 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use time::{Date, Duration, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+
+use crate::scale_measurement::ScaleMeasurement;
+
+/// Energy content of a pound of body mass
+pub const KCAL_PER_LB: f64 = 3500.0;
+/// Weight of the latest measurement in the exponentially smoothed trend
+pub const TREND_ALPHA: f64 = 0.1;
+/// Two sided 95% confidence interval
+const CONFIDENCE_Z: f64 = 1.96;
+/// Days of measurements before the latest the goal projection is fitted to
+pub const PROJECTION_DAYS: i64 = 28;
+/// Goals further away than this many days at the fitted rate count as not
+/// being reached
+pub const PROJECTION_MAX_DAYS: f64 = 730.0;
+
+/// Exponentially smoothed weight trend, one value per measurement
+#[must_use]
+pub fn smooth_weights(measurements: &[ScaleMeasurement]) -> Vec<f64> {
+    let mut trend: Option<f64> = None;
+    measurements
+        .iter()
+        .map(|meas| {
+            let value = trend.map_or(meas.mass, |t| t + TREND_ALPHA * (meas.mass - t));
+            trend = Some(value);
+            value
+        })
+        .collect()
+}
+
+/// Median of each measurement and its neighbours, a single reading thrown
+/// off by hydration or a meal doesn't skew the fitted rate while a steady
+/// trend passes through unchanged
+#[must_use]
+pub fn median_filter(masses: &[f64]) -> Vec<f64> {
+    (0..masses.len())
+        .map(|idx| {
+            if idx == 0 || idx + 1 == masses.len() {
+                return masses[idx];
+            }
+            let mut window = [masses[idx - 1], masses[idx], masses[idx + 1]];
+            window.sort_by(f64::total_cmp);
+            window[1]
+        })
+        .collect()
+}
+
+/// Monday of the week containing `date`
+#[must_use]
+pub fn get_week_start(date: Date) -> Date {
+    date - Duration::days(date.weekday().number_days_from_monday().into())
+}
+
+/// Least squares slope of `points` and its standard error, needs at least
+/// three points spread over more than one instant
+fn linear_fit(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let n = points.len() as f64;
+    if points.len() < 3 {
+        return None;
+    }
+    let x_mean = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let y_mean = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - x_mean).powi(2)).sum();
+    if sxx <= 0.0 {
+        return None;
+    }
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - x_mean) * (y - y_mean))
+        .sum();
+    let slope = sxy / sxx;
+    let intercept = y_mean - slope * x_mean;
+    let sse: f64 = points
+        .iter()
+        .map(|(x, y)| (y - intercept - slope * x).powi(2))
+        .sum();
+    let stderr = (sse / (n - 2.0) / sxx).sqrt();
+    Some((slope, stderr))
+}
+
+/// Average daily calorie balance over one week implied by the change in
+/// weight, negative values are a deficit
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WeeklyCalorieEstimate {
+    pub week_start: Date,
+    pub number_of_measurements: usize,
+    /// Smoothed weight at the last measurement of the week in lbs
+    pub trend_weight: f64,
+    /// Fitted change in weight in lbs per week
+    pub weight_change: f64,
+    pub daily_balance: f64,
+    pub daily_balance_low: f64,
+    pub daily_balance_high: f64,
+}
+
+impl WeeklyCalorieEstimate {
+    /// Fit the median filtered weight of each week's measurements (sorted by
+    /// time) against time, weeks start on monday in local time and weeks with
+    /// fewer than three measurements are skipped
+    #[must_use]
+    pub fn from_measurements(measurements: &[ScaleMeasurement]) -> Vec<Self> {
+        let local = DateTimeWrapper::local_tz();
+        let trend = smooth_weights(measurements);
+        let masses: Vec<_> = measurements.iter().map(|m| m.mass).collect();
+        let filtered = median_filter(&masses);
+        let mut weeks: BTreeMap<Date, Vec<(OffsetDateTime, f64, f64)>> = BTreeMap::new();
+        for ((meas, mass), trend_weight) in measurements.iter().zip(filtered).zip(trend) {
+            let datetime = meas.datetime.to_offsetdatetime();
+            let week_start = get_week_start(datetime.to_timezone(local).date());
+            weeks
+                .entry(week_start)
+                .or_default()
+                .push((datetime, mass, trend_weight));
+        }
+        weeks
+            .into_iter()
+            .filter_map(|(week_start, values)| {
+                let (first, _, _) = values.first()?;
+                let (_, _, trend_weight) = values.last()?;
+                let points: Vec<_> = values
+                    .iter()
+                    .map(|(dt, mass, _)| ((*dt - *first).as_seconds_f64() / 86400.0, *mass))
+                    .collect();
+                let (slope, stderr) = linear_fit(&points)?;
+                let daily_balance = slope * KCAL_PER_LB;
+                let margin = CONFIDENCE_Z * stderr * KCAL_PER_LB;
+                Some(Self {
+                    week_start,
+                    number_of_measurements: values.len(),
+                    trend_weight: *trend_weight,
+                    weight_change: slope * 7.0,
+                    daily_balance,
+                    daily_balance_low: daily_balance - margin,
+                    daily_balance_high: daily_balance + margin,
+                })
+            })
+            .collect()
+    }
+}
+
+/// When the weight trend reaches `goal` at the rate fitted to the last
+/// `PROJECTION_DAYS` of measurements, with the dates at either end of the
+/// 95% range of the rate
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct GoalProjection {
+    pub goal: f64,
+    /// Time of the latest measurement, the projection starts from there
+    pub start: OffsetDateTime,
+    /// Smoothed weight at the latest measurement in lbs
+    pub trend_weight: f64,
+    /// Fitted change in weight in lbs per day
+    pub rate: f64,
+    pub rate_low: f64,
+    pub rate_high: f64,
+    /// Days until the goal at the fitted rate, `None` when moving away from
+    /// it or further than `PROJECTION_MAX_DAYS`
+    pub days: Option<f64>,
+    pub earliest_days: Option<f64>,
+    /// `None` when the slow end of the range never reaches the goal
+    pub latest_days: Option<f64>,
+}
+
+impl GoalProjection {
+    /// Projection from measurements sorted by time, `None` without enough
+    /// recent measurements to fit a rate
+    #[must_use]
+    pub fn from_measurements(measurements: &[ScaleMeasurement], goal: f64) -> Option<Self> {
+        let trend_weight = *smooth_weights(measurements).last()?;
+        let start = measurements.last()?.datetime.to_offsetdatetime();
+        let window = start - Duration::days(PROJECTION_DAYS);
+        let masses: Vec<_> = measurements.iter().map(|m| m.mass).collect();
+        let points: Vec<_> = measurements
+            .iter()
+            .zip(median_filter(&masses))
+            .filter(|(m, _)| m.datetime.to_offsetdatetime() >= window)
+            .map(|(m, mass)| {
+                let x = (m.datetime.to_offsetdatetime() - window).as_seconds_f64() / 86400.0;
+                (x, mass)
+            })
+            .collect();
+        let (rate, stderr) = linear_fit(&points)?;
+        let margin = CONFIDENCE_Z * stderr;
+        let remaining = goal - trend_weight;
+        let days_at = |rate: f64| {
+            let days = if remaining.abs() < f64::EPSILON {
+                0.0
+            } else {
+                remaining / rate
+            };
+            if days.is_finite() && (0.0..=PROJECTION_MAX_DAYS).contains(&days) {
+                Some(days)
+            } else {
+                None
+            }
+        };
+        let (fast, slow) = {
+            let low = days_at(rate - margin);
+            let high = days_at(rate + margin);
+            match (low, high) {
+                (Some(low), Some(high)) => (Some(low.min(high)), Some(low.max(high))),
+                (Some(days), None) | (None, Some(days)) => (Some(days), None),
+                (None, None) => (None, None),
+            }
+        };
+        Some(Self {
+            goal,
+            start,
+            trend_weight,
+            rate,
+            rate_low: rate - margin,
+            rate_high: rate + margin,
+            days: days_at(rate),
+            earliest_days: fast,
+            latest_days: slow,
+        })
+    }
+
+    /// Local date `days` after the latest measurement
+    #[must_use]
+    pub fn date_after(&self, days: f64) -> Date {
+        let local = DateTimeWrapper::local_tz();
+        (self.start + Duration::seconds_f64(days * 86400.0))
+            .to_timezone(local)
+            .date()
+    }
+
+    /// (time, fitted, low, high) weights from the latest measurement until
+    /// the goal is projected to be reached, empty when it isn't
+    #[must_use]
+    pub fn band(&self, steps: usize) -> Vec<(OffsetDateTime, f64, f64, f64)> {
+        let end = match self.days.or(self.earliest_days) {
+            Some(end) if end > 0.0 && steps > 0 => end,
+            _ => return Vec::new(),
+        };
+        (0..=steps)
+            .map(|idx| {
+                let days = end * idx as f64 / steps as f64;
+                let low = self.trend_weight + self.rate_low * days;
+                let high = self.trend_weight + self.rate_high * days;
+                (
+                    self.start + Duration::seconds_f64(days * 86400.0),
+                    self.trend_weight + self.rate * days,
+                    low.min(high),
+                    low.max(high),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{
+        macros::{date, datetime},
+        Duration,
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        calorie_estimate::{
+            get_week_start, median_filter, smooth_weights, GoalProjection, WeeklyCalorieEstimate,
+        },
+        scale_measurement::ScaleMeasurement,
+    };
+
+    #[test]
+    fn test_weekly_calorie_estimate() {
+        assert_eq!(get_week_start(date!(2024 - 03 - 07)), date!(2024 - 03 - 04));
+        assert_eq!(get_week_start(date!(2024 - 03 - 04)), date!(2024 - 03 - 04));
+
+        // lose one pound a week, measured at noon every day
+        let start = datetime!(2024-03-04 12:00:00 UTC);
+        let measurements: Vec<_> = (0..14)
+            .map(|i| ScaleMeasurement {
+                id: Uuid::new_v4(),
+                datetime: (start + Duration::days(i)).into(),
+                mass: 180.0 - i as f64 / 7.0,
+                fat_pct: 20.0,
+                water_pct: 60.0,
+                muscle_pct: 40.0,
+                bone_pct: 4.0,
+                connect_primary_key: None,
+            })
+            .collect();
+        let trend = smooth_weights(&measurements);
+        assert_eq!(trend[0], 180.0);
+        assert!(trend[13] > measurements[13].mass);
+
+        let estimates = WeeklyCalorieEstimate::from_measurements(&measurements);
+        assert_eq!(estimates.len(), 2);
+        let week = &estimates[0];
+        assert_eq!(week.number_of_measurements, 7);
+        assert!((week.weight_change + 1.0).abs() < 1e-6);
+        assert!((week.daily_balance + 500.0).abs() < 1e-6);
+        assert!(week.daily_balance_low <= week.daily_balance);
+        assert!(week.daily_balance_high >= week.daily_balance);
+
+        // a single heavy reading doesn't change the fitted rate
+        let mut spiked = measurements.clone();
+        spiked[3].mass += 4.0;
+        let estimates = WeeklyCalorieEstimate::from_measurements(&spiked);
+        assert!((estimates[0].daily_balance + 500.0).abs() < 1e-6);
+        assert_eq!(
+            median_filter(&[1.0, 5.0, 2.0, 3.0]),
+            vec![1.0, 2.0, 3.0, 3.0]
+        );
+
+        assert!(WeeklyCalorieEstimate::from_measurements(&measurements[..2]).is_empty());
+    }
+    #[test]
+    fn test_goal_projection() {
+        // lose one pound a week with a little noise, measured every day
+        let start = datetime!(2024-03-04 12:00:00 UTC);
+        let measurements: Vec<_> = (0..28)
+            .map(|i| ScaleMeasurement {
+                id: Uuid::new_v4(),
+                datetime: (start + Duration::days(i)).into(),
+                mass: 180.0 - i as f64 / 7.0 + if i % 2 == 0 { 0.3 } else { -0.3 },
+                fat_pct: 20.0,
+                water_pct: 60.0,
+                muscle_pct: 40.0,
+                bone_pct: 4.0,
+                connect_primary_key: None,
+            })
+            .collect();
+        let projection = GoalProjection::from_measurements(&measurements, 170.0).unwrap();
+        assert!((projection.rate * 7.0 + 1.0).abs() < 0.1);
+        assert!(projection.rate_low < projection.rate);
+        assert!(projection.rate_high > projection.rate);
+        let days = projection.days.unwrap();
+        let expected = (170.0 - projection.trend_weight) / projection.rate;
+        assert!((days - expected).abs() < 1e-6);
+        assert!(projection.earliest_days.unwrap() < days);
+        assert!(projection.latest_days.unwrap() > days);
+        assert!(projection.date_after(days) > date!(2024 - 04 - 01));
+
+        let band = projection.band(4);
+        assert_eq!(band.len(), 5);
+        assert_eq!(band[0].1, projection.trend_weight);
+        assert!((band[4].1 - 170.0).abs() < 1e-6);
+        assert!(band[4].2 < band[4].1 && band[4].3 > band[4].1);
+
+        // losing weight never reaches a higher goal
+        let projection = GoalProjection::from_measurements(&measurements, 190.0).unwrap();
+        assert!(projection.days.is_none());
+        assert!(projection.band(4).is_empty());
+
+        assert!(GoalProjection::from_measurements(&measurements[..2], 170.0).is_none());
+    }
+}
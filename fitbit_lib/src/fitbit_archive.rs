@@ -18,10 +18,12 @@ use time::{Date, Duration, Month, OffsetDateTime, Time};
 use tokio::task::spawn_blocking;
 
 use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
-use garmin_models::{garmin_file::GarminFile, garmin_summary::get_list_of_files_from_db};
+use garmin_models::{
+    cache_store::CacheStore, garmin_file::GarminFile, garmin_summary::get_list_of_files_from_db,
+};
 use garmin_utils::pgpool::PgPool;
 
-use crate::fitbit_heartrate::FitbitHeartRate;
+use crate::{fitbit_heartrate::FitbitHeartRate, fitbit_intraday::IntradayKind};
 
 #[derive(Default)]
 struct FitbitColumns {
@@ -68,6 +70,8 @@ async fn get_garmin_avro_file_map(
     let days = (end_date - start_date).whole_days();
     let mut days: Vec<_> = (0..=days).map(|i| start_date + Duration::days(i)).collect();
     days.shrink_to_fit();
+    let store = CacheStore::avro_cache(config).await;
+    let store = &store;
     let futures = days.iter().map(|date| async move {
         let constraint = format_sstr!(
             r#"
@@ -76,13 +80,9 @@ async fn get_garmin_avro_file_map(
             "#);
         let files: Vec<_> = get_list_of_files_from_db(&constraint, pool)
             .await?
+            .map_err(Into::<Error>::into)
             .try_filter_map(|filename| async move {
-                let avro_file = config.cache_dir.join(format_sstr!("{filename}.avro"));
-                if avro_file.exists() {
-                    Ok(Some(avro_file))
-                } else {
-                    Ok(None)
-                }
+                store.fetch(&format_sstr!("{filename}.avro")).await
             })
             .try_collect()
             .await?;
@@ -95,6 +95,45 @@ async fn get_garmin_avro_file_map(
     Ok(garmin_files)
 }
 
+/// Key of the monthly archive files (`YYYY-MM`) holding `date`
+#[must_use]
+pub fn get_month_key(date: Date) -> StackString {
+    let m: u8 = date.month().into();
+    format_sstr!("{:04}-{m:02}", date.year())
+}
+
+/// Keys of the monthly archive files covering `start_date` through
+/// `end_date`
+#[must_use]
+pub fn get_month_keys(start_date: Date, end_date: Date) -> BTreeSet<StackString> {
+    (0..=(end_date - start_date).whole_days())
+        .map(|i| get_month_key(start_date + Duration::days(i)))
+        .collect()
+}
+
+/// Download the heart rate and intraday archive files covering
+/// `start_date` through `end_date` which aren't kept locally, a no-op
+/// unless the archive is kept in s3
+/// # Errors
+/// Return error if s3 api calls fail
+pub async fn fetch_archive_files(
+    config: &GarminConfig,
+    start_date: Date,
+    end_date: Date,
+) -> Result<(), Error> {
+    let store = CacheStore::fitbit_archive(config).await;
+    if !store.is_remote() {
+        return Ok(());
+    }
+    for key in get_month_keys(start_date, end_date) {
+        store.fetch(&format_sstr!("{key}.parquet")).await?;
+        for kind in [IntradayKind::Steps, IntradayKind::Calories] {
+            store.fetch(&kind.get_parquet_key(&key)).await?;
+        }
+    }
+    Ok(())
+}
+
 fn get_start_date_end_date_for_key(key: &str) -> Result<(Date, Date), Error> {
     let year: i32 = (key[0..4]).parse()?;
     let month: u8 = (key[5..7]).parse()?;
@@ -117,11 +156,39 @@ pub async fn archive_fitbit_heartrates(
     pool: &PgPool,
     all: bool,
 ) -> Result<Vec<StackString>, Error> {
-    let mut output = Vec::new();
     let input_files = {
         let config = config.clone();
         spawn_blocking(move || get_fitbit_avro_file_map(&config, all)).await??
     };
+    archive_heartrate_files(config, pool, input_files).await
+}
+
+/// Rewrite the monthly archive files `months` (`YYYY-MM`) from every cached
+/// daily heart rate file of those months, e.g. after importing old data
+/// # Errors
+/// Returns error on db query failure
+pub async fn archive_fitbit_heartrate_months(
+    config: &GarminConfig,
+    pool: &PgPool,
+    months: &BTreeSet<StackString>,
+) -> Result<Vec<StackString>, Error> {
+    let input_files = {
+        let config = config.clone();
+        spawn_blocking(move || get_fitbit_avro_file_map(&config, true)).await??
+    };
+    let input_files = input_files
+        .into_iter()
+        .filter(|(key, _)| months.contains(key))
+        .collect();
+    archive_heartrate_files(config, pool, input_files).await
+}
+
+async fn archive_heartrate_files(
+    config: &GarminConfig,
+    pool: &PgPool,
+    input_files: BTreeMap<StackString, BTreeSet<PathBuf>>,
+) -> Result<Vec<StackString>, Error> {
+    let mut output = Vec::new();
     let mut garmin_input_files: BTreeMap<StackString, BTreeSet<_>> = BTreeMap::new();
     for key in input_files.keys() {
         let (start_date, end_date) = get_start_date_end_date_for_key(key)?;
@@ -130,6 +197,14 @@ pub async fn archive_fitbit_heartrates(
             .or_default()
             .extend(get_garmin_avro_file_map(config, pool, start_date, end_date).await?);
     }
+    let store = CacheStore::fitbit_archive(config).await;
+    let keys: Vec<_> = input_files
+        .keys()
+        .map(|key| format_sstr!("{key}.parquet"))
+        .collect();
+    for key in &keys {
+        store.fetch(key).await?;
+    }
     output.extend({
         let config = config.clone();
         spawn_blocking(move || {
@@ -137,6 +212,11 @@ pub async fn archive_fitbit_heartrates(
         })
         .await??
     });
+    for key in &keys {
+        if store.local_path(key).exists() {
+            store.store(key).await?;
+        }
+    }
     Ok(output)
 }
 
@@ -226,15 +306,7 @@ fn get_fitbit_parquet_files(
     start_date: Date,
     end_date: Date,
 ) -> Vec<PathBuf> {
-    let ndays = (end_date - start_date).whole_days();
-    let keys: BTreeSet<_> = (0..=ndays)
-        .map(|i| {
-            let d = start_date + Duration::days(i);
-            let m: u8 = d.month().into();
-            format_sstr!("{:04}-{:02}", d.year(), m)
-        })
-        .collect();
-    let mut fitbit_files: Vec<_> = keys
+    let mut fitbit_files: Vec<_> = get_month_keys(start_date, end_date)
         .iter()
         .filter_map(|key| {
             let input_filename = config.fitbit_archivedir.join(key).with_extension("parquet");
@@ -0,0 +1,234 @@
+use anyhow::{format_err, Error};
+use glob::glob;
+use log::{info, warn};
+use stack_string::{format_sstr, StackString};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use time::Date;
+use tokio::task::spawn_blocking;
+use zip::ZipArchive;
+
+use garmin_lib::garmin_config::GarminConfig;
+use garmin_models::provenance::{DataProvider, Provenance};
+use garmin_utils::pgpool::PgPool;
+
+use crate::{
+    fitbit_archive::{archive_fitbit_heartrate_months, get_month_key},
+    fitbit_heartrate::{FitbitHeartRate, JsonHeartRateEntry},
+};
+
+fn export_file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Whether `path` is one of the daily heart rate files of a fitbit data
+/// export, `heart_rate-YYYY-MM-DD.json`
+#[must_use]
+pub fn is_heartrate_export_file(path: &str) -> bool {
+    let name = export_file_name(path);
+    name.starts_with("heart_rate-") && name.ends_with(".json")
+}
+
+/// # Errors
+/// Returns error if deserialization fails
+pub fn parse_heartrate_export(reader: impl Read) -> Result<Vec<FitbitHeartRate>, Error> {
+    let entries: Vec<JsonHeartRateEntry> = serde_json::from_reader(reader)?;
+    Ok(entries
+        .into_iter()
+        .map(FitbitHeartRate::from_json_heartrate_entry)
+        .collect())
+}
+
+/// Fitbit account data export, either the zip as downloaded or the
+/// extracted directory, the heart rate files are nested a few directories
+/// down (e.g. `<user>/Physical Activity`)
+pub enum FitbitExport {
+    Zip(ZipArchive<File>),
+    Directory(Vec<PathBuf>),
+}
+
+impl FitbitExport {
+    /// # Errors
+    /// Returns error if the zip can't be read
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        if path.is_dir() {
+            let pattern = format_sstr!("{}/**/heart_rate-*.json", path.to_string_lossy());
+            let files: Result<Vec<_>, _> = glob(&pattern)?.collect();
+            let mut files = files?;
+            files.sort();
+            Ok(Self::Directory(files))
+        } else if path.exists() {
+            Ok(Self::Zip(ZipArchive::new(File::open(path)?)?))
+        } else {
+            Err(format_err!("{path:?} does not exist"))
+        }
+    }
+
+    /// Names of the heart rate files, sorted by date
+    #[must_use]
+    pub fn heartrate_files(&self) -> Vec<StackString> {
+        let mut names: Vec<StackString> = match self {
+            Self::Zip(zip) => zip
+                .file_names()
+                .filter(|name| is_heartrate_export_file(name))
+                .map(Into::into)
+                .collect(),
+            Self::Directory(files) => files
+                .iter()
+                .map(|f| f.to_string_lossy().as_ref().into())
+                .collect(),
+        };
+        names.sort_by(|a, b| export_file_name(a).cmp(export_file_name(b)));
+        names
+    }
+
+    /// # Errors
+    /// Returns error if the file can't be read or deserialized
+    pub fn read_heartrates(&mut self, name: &str) -> Result<Vec<FitbitHeartRate>, Error> {
+        match self {
+            Self::Zip(zip) => parse_heartrate_export(zip.by_name(name)?),
+            Self::Directory(_) => parse_heartrate_export(File::open(name)?),
+        }
+    }
+}
+
+/// Dates merged from a fitbit export and the files which couldn't be read
+#[derive(Debug, Default)]
+pub struct MergedExport {
+    pub dates: BTreeSet<Date>,
+    pub skipped: Vec<StackString>,
+}
+
+/// Merge every heart rate file of the export at `path` into the daily avro
+/// cache, `progress` is called with a line for each file. A file which can't
+/// be read or parsed is skipped and reported, the rest are still merged
+/// # Errors
+/// Returns error if the export can't be opened or merging into the cache
+/// fails
+pub fn merge_fitbit_export(
+    config: &GarminConfig,
+    path: &Path,
+    progress: &(dyn Fn(StackString) + Send + Sync),
+) -> Result<MergedExport, Error> {
+    let mut export = FitbitExport::open(path)?;
+    let names = export.heartrate_files();
+    let total = names.len();
+    progress(format_sstr!("found {total} heart rate files"));
+    let mut merged = MergedExport::default();
+    for (idx, name) in names.iter().enumerate() {
+        let heartrates = match export.read_heartrates(name) {
+            Ok(heartrates) => heartrates,
+            Err(e) => {
+                warn!("skipping {name} {e}");
+                progress(format_sstr!("{}/{total} {name} skipped {e}", idx + 1));
+                merged.skipped.push(name.clone());
+                continue;
+            }
+        };
+        merged
+            .dates
+            .extend(FitbitHeartRate::merge_slice_to_avro(config, &heartrates)?);
+        progress(format_sstr!(
+            "{}/{total} {name} {} values",
+            idx + 1,
+            heartrates.len()
+        ));
+    }
+    Ok(merged)
+}
+
+/// Import the heart rate files of a fitbit data export: merge them into
+/// the daily avro cache, rewrite the monthly parquet archive of each month
+/// touched and recompute the statistics summary of each day touched, files
+/// which can't be read are listed at the end of the output
+/// # Errors
+/// Returns error if reading the export, archiving or db queries fail
+pub async fn import_fitbit_export(
+    config: &GarminConfig,
+    pool: &PgPool,
+    path: &Path,
+    progress: impl Fn(StackString) + Send + Sync + 'static,
+) -> Result<Vec<StackString>, Error> {
+    let progress = Arc::new(progress);
+    let MergedExport { dates, skipped } = {
+        let config = config.clone();
+        let path = path.to_path_buf();
+        let progress = progress.clone();
+        spawn_blocking(move || merge_fitbit_export(&config, &path, progress.as_ref())).await??
+    };
+    let raw_file = path.to_string_lossy();
+    Provenance::new(DataProvider::Fitbit, "fitbit export", Some(&raw_file))
+        .record_heartrate_dates(pool, &dates)
+        .await?;
+    let progress = progress.as_ref();
+    let months: BTreeSet<_> = dates.iter().map(|d| get_month_key(*d)).collect();
+    progress(format_sstr!(
+        "archiving {} days in {} months",
+        dates.len(),
+        months.len()
+    ));
+    let mut output = archive_fitbit_heartrate_months(config, pool, &months).await?;
+    for (idx, date) in dates.iter().enumerate() {
+        FitbitHeartRate::calculate_summary_statistics(config, pool, *date).await?;
+        if (idx + 1) % 100 == 0 {
+            progress(format_sstr!(
+                "updated statistics {}/{}",
+                idx + 1,
+                dates.len()
+            ));
+        }
+    }
+    info!("updated statistics for {} days", dates.len());
+    output.push(format_sstr!("updated statistics for {} days", dates.len()));
+    if !skipped.is_empty() {
+        output.push(format_sstr!(
+            "skipped {} unreadable files: {}",
+            skipped.len(),
+            skipped.join(", ")
+        ));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::date;
+    use time_tz::{timezones::db::UTC, OffsetDateTimeExt};
+
+    use crate::fitbit_export::{is_heartrate_export_file, parse_heartrate_export};
+
+    #[test]
+    fn test_is_heartrate_export_file() {
+        assert!(is_heartrate_export_file(
+            "MyFitbitData/User/Physical Activity/heart_rate-2021-03-04.json"
+        ));
+        assert!(is_heartrate_export_file("heart_rate-2021-03-04.json"));
+        assert!(!is_heartrate_export_file(
+            "MyFitbitData/User/Physical Activity/resting_heart_rate-2021-03-04.json"
+        ));
+        assert!(!is_heartrate_export_file(
+            "MyFitbitData/User/Physical Activity/heart_rate-2021-03-04.csv"
+        ));
+    }
+
+    #[test]
+    fn test_parse_heartrate_export() -> Result<(), Error> {
+        let buf = br#"[
+            {"dateTime": "03/04/21 12:00:05", "value": {"bpm": 62, "confidence": 2}},
+            {"dateTime": "03/04/21 12:00:10", "value": {"bpm": 64, "confidence": 3}}
+        ]"#;
+        let heartrates = parse_heartrate_export(&buf[..])?;
+        assert_eq!(heartrates.len(), 2);
+        assert_eq!(heartrates[0].value, 62);
+        assert_eq!(heartrates[1].value, 64);
+        let d = heartrates[0].datetime.to_timezone(UTC).date();
+        assert!(d >= date!(2021 - 03 - 03) && d <= date!(2021 - 03 - 05));
+        Ok(())
+    }
+}
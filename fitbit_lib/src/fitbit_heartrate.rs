@@ -24,7 +24,9 @@ use time_tz::{timezones::db::UTC, OffsetDateTimeExt, PrimitiveDateTimeExt};
 use tokio::task::spawn_blocking;
 
 use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
-use garmin_models::{garmin_file::GarminFile, garmin_summary::get_list_of_files_from_db};
+use garmin_models::{
+    cache_store::CacheStore, garmin_file::GarminFile, garmin_summary::get_list_of_files_from_db,
+};
 use garmin_utils::pgpool::PgPool;
 
 use crate::{fitbit_statistics_summary::FitbitStatisticsSummary, GarminConnectHrData};
@@ -136,17 +138,15 @@ impl FitbitHeartRate {
             .collect();
         fitbit_files.shrink_to_fit();
         info!("fitbit_files {:?}", fitbit_files);
+        let store = CacheStore::avro_cache(config).await;
+        let store = &store;
         let futures = days.iter().map(|date| async move {
             let constraint = format_sstr!("date(begin_datetime at time zone 'utc') = '{date}'");
             let files: Vec<_> = get_list_of_files_from_db(&constraint, pool)
                 .await?
+                .map_err(Into::<Error>::into)
                 .try_filter_map(|filename| async move {
-                    let avro_file = config.cache_dir.join(format_sstr!("{filename}.avro"));
-                    if avro_file.exists() {
-                        Ok(Some(avro_file))
-                    } else {
-                        Ok(None)
-                    }
+                    store.fetch(&format_sstr!("{filename}.avro")).await
                 })
                 .try_collect()
                 .await?;
@@ -371,42 +371,6 @@ impl FitbitHeartRate {
     }
 }
 
-/// # Errors
-/// Returns error if deserialization fails
-pub fn process_fitbit_json_file(fname: &Path) -> Result<Vec<FitbitHeartRate>, Error> {
-    if !fname.exists() {
-        return Err(format_err!("file {fname:?} does not exist"));
-    }
-    let f = File::open(fname)?;
-    let result: Vec<JsonHeartRateEntry> = serde_json::from_reader(f)?;
-    let mut result: Vec<_> = result
-        .into_par_iter()
-        .map(FitbitHeartRate::from_json_heartrate_entry)
-        .collect();
-    result.shrink_to_fit();
-    Ok(result)
-}
-
-/// # Errors
-/// Returns error if deserialization fails
-pub fn import_fitbit_json_files(
-    config: &GarminConfig,
-    directory: &str,
-) -> Result<BTreeSet<Date>, Error> {
-    let mut filenames: Vec<_> = glob(&format_sstr!("{directory}/heart_rate-*.json"))?.collect();
-    filenames.shrink_to_fit();
-    let result: Result<Vec<BTreeSet<Date>>, Error> = filenames
-        .into_par_iter()
-        .map(|fname| {
-            let fname = fname?;
-            let heartrates = process_fitbit_json_file(&fname)?;
-
-            FitbitHeartRate::merge_slice_to_avro(config, &heartrates)
-        })
-        .collect();
-    result.map(|v| v.into_iter().flatten().collect())
-}
-
 /// # Errors
 /// Returns error if deserialization fails
 pub fn import_garmin_json_file(config: &GarminConfig, filename: &Path) -> Result<(), Error> {
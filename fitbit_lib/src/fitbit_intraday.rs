@@ -0,0 +1,382 @@
+use anyhow::{format_err, Error};
+use polars::{
+    df as dataframe,
+    prelude::{ParquetReader, ParquetWriter, SerReader, UniqueKeepStrategy},
+};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use time::{macros::format_description, Date, Duration, OffsetDateTime, Time};
+use time_tz::PrimitiveDateTimeExt;
+use tokio::task::spawn_blocking;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
+use garmin_models::cache_store::CacheStore;
+
+use crate::fitbit_archive::{get_heartrate_values, get_month_key, get_month_keys};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IntradayKind {
+    Steps,
+    Calories,
+}
+
+impl IntradayKind {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Steps => "steps",
+            Self::Calories => "calories",
+        }
+    }
+
+    /// Name of the monthly archive file, next to the `YYYY-MM.parquet`
+    /// heartrate files
+    #[must_use]
+    pub fn get_parquet_key(self, key: &str) -> StackString {
+        format_sstr!("{}_{key}.parquet", self.to_str())
+    }
+
+    fn get_parquet_file(self, archive_dir: &Path, key: &str) -> PathBuf {
+        archive_dir.join(self.get_parquet_key(key))
+    }
+}
+
+impl fmt::Display for IntradayKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for IntradayKind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "steps" => Ok(Self::Steps),
+            "calories" => Ok(Self::Calories),
+            _ => Err(format_err!("Invalid intraday kind {s}")),
+        }
+    }
+}
+
+/// Response of `1/user/-/activities/{steps,calories}/date/{date}/1d/1min.json`
+#[derive(Deserialize, Debug, Default)]
+pub struct FitbitIntradayResponse {
+    #[serde(rename = "activities-steps")]
+    pub steps: Option<Vec<FitbitDaySummary>>,
+    #[serde(rename = "activities-steps-intraday")]
+    pub steps_intraday: Option<FitbitIntradayDataset>,
+    #[serde(rename = "activities-calories")]
+    pub calories: Option<Vec<FitbitDaySummary>>,
+    #[serde(rename = "activities-calories-intraday")]
+    pub calories_intraday: Option<FitbitIntradayDataset>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FitbitDaySummary {
+    #[serde(rename = "dateTime")]
+    pub date: Date,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FitbitIntradayDataset {
+    pub dataset: Vec<FitbitIntradayEntry>,
+}
+
+/// `time` is local time of day, e.g. `07:31:00`
+#[derive(Deserialize, Debug)]
+pub struct FitbitIntradayEntry {
+    pub time: StackString,
+    pub value: f64,
+}
+
+impl FitbitIntradayResponse {
+    /// Values of each kind in the response as (unix timestamp, value)
+    /// # Errors
+    /// Return error if a time can't be parsed or converted to local time
+    pub fn to_values(&self) -> Result<Vec<(IntradayKind, Vec<(i64, f64)>)>, Error> {
+        let mut output = Vec::new();
+        for (kind, summary, intraday) in [
+            (IntradayKind::Steps, &self.steps, &self.steps_intraday),
+            (
+                IntradayKind::Calories,
+                &self.calories,
+                &self.calories_intraday,
+            ),
+        ] {
+            let (date, intraday) = match (summary.as_ref().and_then(|s| s.first()), intraday) {
+                (Some(summary), Some(intraday)) => (summary.date, intraday),
+                _ => continue,
+            };
+            let mut values = Vec::with_capacity(intraday.dataset.len());
+            for entry in &intraday.dataset {
+                let time =
+                    Time::parse(&entry.time, format_description!("[hour]:[minute]:[second]"))?;
+                let datetime = date
+                    .with_time(time)
+                    .assume_timezone(DateTimeWrapper::local_tz())
+                    .take_first()
+                    .ok_or_else(|| format_err!("Invalid local time {date} {time}"))?;
+                values.push((datetime.unix_timestamp(), entry.value));
+            }
+            output.push((kind, values));
+        }
+        Ok(output)
+    }
+}
+
+/// Merge values into the monthly parquet archive of `kind`, rows already
+/// in the archive are skipped
+/// # Errors
+/// Return error if reading or writing parquet files fails
+pub fn write_intraday_parquet(
+    config: &GarminConfig,
+    kind: IntradayKind,
+    values: &[(i64, f64)],
+) -> Result<Vec<StackString>, Error> {
+    write_parquet(&config.fitbit_archivedir, kind, values)
+}
+
+/// `write_intraday_parquet` keeping the monthly files in s3 when the
+/// archive is configured that way
+/// # Errors
+/// Return error if s3 api calls or writing parquet files fail
+pub async fn archive_intraday_values(
+    config: &GarminConfig,
+    kind: IntradayKind,
+    values: Vec<(i64, f64)>,
+) -> Result<Vec<StackString>, Error> {
+    let store = CacheStore::fitbit_archive(config).await;
+    let keys: BTreeSet<_> = values
+        .iter()
+        .filter_map(|(t, _)| OffsetDateTime::from_unix_timestamp(*t).ok())
+        .map(|d| kind.get_parquet_key(&get_month_key(d.date())))
+        .collect();
+    for key in &keys {
+        store.fetch(key).await?;
+    }
+    let output = {
+        let config = config.clone();
+        spawn_blocking(move || write_intraday_parquet(&config, kind, &values)).await??
+    };
+    for key in &keys {
+        store.store(key).await?;
+    }
+    Ok(output)
+}
+
+fn write_parquet(
+    archive_dir: &Path,
+    kind: IntradayKind,
+    values: &[(i64, f64)],
+) -> Result<Vec<StackString>, Error> {
+    let mut by_month: BTreeMap<StackString, BTreeMap<i64, f64>> = BTreeMap::new();
+    for (timestamp, value) in values {
+        let date = OffsetDateTime::from_unix_timestamp(*timestamp)?.date();
+        by_month
+            .entry(get_month_key(date))
+            .or_default()
+            .insert(*timestamp, *value);
+    }
+    let mut output = Vec::new();
+    for (key, values) in by_month {
+        let timestamps: Vec<_> = values.keys().copied().collect();
+        let values: Vec<_> = values.values().copied().collect();
+        let new_df = dataframe!(
+            "timestamp" => &timestamps,
+            "value" => &values,
+        )?;
+        let file = kind.get_parquet_file(archive_dir, &key);
+        let mut df = if file.exists() {
+            let df = ParquetReader::new(File::open(&file)?).finish()?;
+            let existing_entries = df.shape().0;
+            let updated_df =
+                df.vstack(&new_df)?
+                    .unique_stable(None, UniqueKeepStrategy::First, None)?;
+            let updated_count = updated_df.shape().0 - existing_entries;
+            if updated_count == 0 {
+                output.push(format_sstr!("No new {kind} entries for {key}, skipping"));
+                continue;
+            }
+            output.push(format_sstr!(
+                "New {kind} entries for {key}: {updated_count}"
+            ));
+            updated_df
+        } else {
+            new_df
+        };
+        output.push(format_sstr!("{} {:?}", file.to_string_lossy(), df.shape()));
+        ParquetWriter::new(File::create(&file)?).finish(&mut df)?;
+    }
+    Ok(output)
+}
+
+/// Archived values of `kind` between midnight utc of `start_date` and the
+/// end of `end_date`
+/// # Errors
+/// Return error if reading parquet files fails
+pub fn get_intraday_values(
+    config: &GarminConfig,
+    kind: IntradayKind,
+    start_date: Date,
+    end_date: Date,
+) -> Result<BTreeMap<i64, f64>, Error> {
+    read_parquet(&config.fitbit_archivedir, kind, start_date, end_date)
+}
+
+fn read_parquet(
+    archive_dir: &Path,
+    kind: IntradayKind,
+    start_date: Date,
+    end_date: Date,
+) -> Result<BTreeMap<i64, f64>, Error> {
+    let start_timestamp = start_date.midnight().assume_utc().unix_timestamp();
+    let end_timestamp = (end_date + Duration::days(1))
+        .midnight()
+        .assume_utc()
+        .unix_timestamp();
+    let mut output = BTreeMap::new();
+    for key in get_month_keys(start_date, end_date) {
+        let file = kind.get_parquet_file(archive_dir, &key);
+        if !file.exists() {
+            continue;
+        }
+        let df = ParquetReader::new(File::open(file)?).finish()?;
+        let timestamp_iter = df.column("timestamp")?.i64()?.into_iter();
+        let value_iter = df.column("value")?.f64()?.into_iter();
+        for (t, v) in timestamp_iter.zip(value_iter) {
+            if let (Some(t), Some(v)) = (t, v) {
+                if t >= start_timestamp && t < end_timestamp {
+                    output.insert(t, v);
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn get_minute(
+    minutes: &mut BTreeMap<i64, IntradayMinute>,
+    day_start: i64,
+    timestamp: i64,
+) -> Option<&mut IntradayMinute> {
+    if timestamp < day_start || timestamp >= day_start + 86400 {
+        return None;
+    }
+    let minute = (timestamp / 60) * 60;
+    let datetime = OffsetDateTime::from_unix_timestamp(minute).ok()?.into();
+    Some(minutes.entry(minute).or_insert(IntradayMinute {
+        datetime,
+        heartrate: None,
+        steps: None,
+        calories: None,
+    }))
+}
+
+/// One minute of heartrate, steps and calories
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct IntradayMinute {
+    pub datetime: DateTimeWrapper,
+    pub heartrate: Option<i32>,
+    pub steps: Option<f64>,
+    pub calories: Option<f64>,
+}
+
+/// Minute by minute heartrate (averaged), steps and calories for the local
+/// calendar day `date`
+/// # Errors
+/// Return error if reading parquet files fails
+pub fn get_intraday_day(config: &GarminConfig, date: Date) -> Result<Vec<IntradayMinute>, Error> {
+    let local = DateTimeWrapper::local_tz();
+    let start = date
+        .midnight()
+        .assume_timezone(local)
+        .take_first()
+        .ok_or_else(|| format_err!("Invalid local date {date}"))?
+        .unix_timestamp();
+    let (start_date, end_date) = (date - Duration::days(1), date + Duration::days(1));
+
+    let mut minutes: BTreeMap<i64, IntradayMinute> = BTreeMap::new();
+    for (datetime, value) in get_heartrate_values(config, start_date, end_date, Some(60))? {
+        if let Some(minute) = get_minute(&mut minutes, start, datetime.unix_timestamp()) {
+            minute.heartrate = Some(value);
+        }
+    }
+    for (timestamp, value) in
+        get_intraday_values(config, IntradayKind::Steps, start_date, end_date)?
+    {
+        if let Some(minute) = get_minute(&mut minutes, start, timestamp) {
+            minute.steps = Some(value);
+        }
+    }
+    for (timestamp, value) in
+        get_intraday_values(config, IntradayKind::Calories, start_date, end_date)?
+    {
+        if let Some(minute) = get_minute(&mut minutes, start, timestamp) {
+            minute.calories = Some(value);
+        }
+    }
+    Ok(minutes.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use tempfile::TempDir;
+    use time::macros::date;
+
+    use crate::fitbit_intraday::{
+        read_parquet, write_parquet, FitbitIntradayResponse, IntradayKind,
+    };
+
+    #[test]
+    fn test_fitbit_intraday() -> Result<(), Error> {
+        let buf = r#"{
+            "activities-steps": [{"dateTime": "2024-03-01", "value": "36"}],
+            "activities-steps-intraday": {
+                "dataset": [
+                    {"time": "12:00:00", "value": 12},
+                    {"time": "12:01:00", "value": 24}
+                ],
+                "datasetInterval": 1,
+                "datasetType": "minute"
+            }
+        }"#;
+        let response: FitbitIntradayResponse = serde_json::from_str(buf)?;
+        let values = response.to_values()?;
+        assert_eq!(values.len(), 1);
+        let (kind, values) = &values[0];
+        assert_eq!(*kind, IntradayKind::Steps);
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1].0 - values[0].0, 60);
+        assert_eq!(values[1].1, 24.0);
+
+        let dir = TempDir::with_prefix("fitbit_intraday")?;
+        let output = write_parquet(dir.path(), IntradayKind::Steps, values)?;
+        assert_eq!(output.len(), 1);
+        let output = write_parquet(dir.path(), IntradayKind::Steps, values)?;
+        assert!(output[0].starts_with("No new steps entries"));
+
+        let archived = read_parquet(
+            dir.path(),
+            IntradayKind::Steps,
+            date!(2024 - 02 - 29),
+            date!(2024 - 03 - 02),
+        )?;
+        assert_eq!(archived.len(), 2);
+        assert!(read_parquet(
+            dir.path(),
+            IntradayKind::Calories,
+            date!(2024 - 02 - 29),
+            date!(2024 - 03 - 02),
+        )?
+        .is_empty());
+        Ok(())
+    }
+}
@@ -9,6 +9,7 @@ use time::Date;
 use time_tz::OffsetDateTimeExt;
 
 use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_models::keyset::{KeysetRow, PageStart};
 use garmin_utils::pgpool::PgPool;
 
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, FromSqlRow)]
@@ -22,6 +23,12 @@ pub struct FitbitStatisticsSummary {
     pub number_of_entries: i32,
 }
 
+impl KeysetRow for FitbitStatisticsSummary {
+    fn page_after(&self) -> PageStart {
+        PageStart::after_date(self.date)
+    }
+}
+
 impl FitbitStatisticsSummary {
     #[must_use]
     pub fn from_heartrate_values(heartrate_values: &[(DateTimeWrapper, i32)]) -> Option<Self> {
@@ -71,7 +78,7 @@ impl FitbitStatisticsSummary {
         select_str: &'a str,
         start_date: Option<&'a Date>,
         end_date: Option<&'a Date>,
-        offset: Option<usize>,
+        start: Option<&'a PageStart>,
         limit: Option<usize>,
         order_str: &'a str,
     ) -> Result<Query<'a>, PqError> {
@@ -85,6 +92,10 @@ impl FitbitStatisticsSummary {
             conditions.push("date <= $end_date");
             query_bindings.push(("end_date", end_date as Parameter));
         }
+        if let Some(PageStart::After { datetime, .. }) = start {
+            conditions.push("date > CAST($after_datetime::timestamptz AT TIME ZONE 'UTC' AS DATE)");
+            query_bindings.push(("after_datetime", datetime as Parameter));
+        }
 
         let mut query = format_sstr!(
             "SELECT {select_str} FROM heartrate_statistics_summary {} {order_str}",
@@ -94,7 +105,7 @@ impl FitbitStatisticsSummary {
                 format_sstr!("WHERE {}", conditions.join(" AND "))
             }
         );
-        if let Some(offset) = &offset {
+        if let Some(offset) = start.and_then(PageStart::offset) {
             query.push_str(&format_sstr!(" OFFSET {offset}"));
         }
         if let Some(limit) = &limit {
@@ -109,14 +120,14 @@ impl FitbitStatisticsSummary {
         pool: &PgPool,
         start_date: Option<Date>,
         end_date: Option<Date>,
-        offset: Option<usize>,
+        start: Option<PageStart>,
         limit: Option<usize>,
     ) -> Result<impl Stream<Item = Result<Self, PqError>>, Error> {
         let query = Self::get_fitbit_statistics_query(
             "*",
             start_date.as_ref(),
             end_date.as_ref(),
-            offset,
+            start.as_ref(),
             limit,
             "ORDER BY date",
         )?;
@@ -0,0 +1,274 @@
+use anyhow::{format_err, Error};
+use serde::Deserialize;
+use time::Date;
+use time_tz::PrimitiveDateTimeExt;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_models::{
+    biomarker::{BiomarkerMeasurement, BiomarkerSeries},
+    garmin_connect_wellness::{SLEEP_RESPIRATION_SERIES, SPO2_SERIES},
+    provenance::Provenance,
+};
+use garmin_utils::pgpool::PgPool;
+
+/// Biomarker series names used for sleep logs imported from Fitbit, spo2
+/// and breathing rate share the series used for Garmin Connect
+pub const SLEEP_MINUTES_SERIES: &str = "sleep_minutes";
+pub const SLEEP_EFFICIENCY_SERIES: &str = "sleep_efficiency";
+pub const SLEEP_DEEP_SERIES: &str = "sleep_deep";
+pub const SLEEP_LIGHT_SERIES: &str = "sleep_light";
+pub const SLEEP_REM_SERIES: &str = "sleep_rem";
+pub const SLEEP_WAKE_SERIES: &str = "sleep_wake";
+
+/// Response of `1.2/user/-/sleep/date/{date}.json` (also the format of the
+/// `sleep-*.json` files in a data export, which hold a bare list of logs)
+#[derive(Deserialize, Debug)]
+pub struct FitbitSleep {
+    pub sleep: Vec<FitbitSleepLog>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FitbitSleepLog {
+    #[serde(rename = "dateOfSleep")]
+    pub date_of_sleep: Date,
+    #[serde(rename = "isMainSleep", default = "default_main_sleep")]
+    pub is_main_sleep: bool,
+    pub efficiency: Option<f64>,
+    #[serde(rename = "minutesAsleep")]
+    pub minutes_asleep: f64,
+    pub levels: Option<FitbitSleepLevels>,
+}
+
+fn default_main_sleep() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FitbitSleepLevels {
+    pub summary: FitbitSleepSummary,
+}
+
+/// Stages are only reported for logs of type `stages`, classic logs have
+/// asleep / restless / awake instead
+#[derive(Deserialize, Debug)]
+pub struct FitbitSleepSummary {
+    pub deep: Option<FitbitSleepStage>,
+    pub light: Option<FitbitSleepStage>,
+    pub rem: Option<FitbitSleepStage>,
+    pub wake: Option<FitbitSleepStage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FitbitSleepStage {
+    pub minutes: f64,
+}
+
+/// Response of `1/user/-/spo2/date/{date}.json`, the date range endpoint
+/// returns a list of these
+#[derive(Deserialize, Debug)]
+pub struct FitbitSpO2 {
+    #[serde(rename = "dateTime")]
+    pub date: Date,
+    pub value: FitbitSpO2Value,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FitbitSpO2Value {
+    pub avg: f64,
+}
+
+/// Response of `1/user/-/br/date/{date}.json`
+#[derive(Deserialize, Debug)]
+pub struct FitbitBreathingRate {
+    pub br: Vec<FitbitBreathingRateEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FitbitBreathingRateEntry {
+    #[serde(rename = "dateTime")]
+    pub date: Date,
+    pub value: FitbitBreathingRateValue,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FitbitBreathingRateValue {
+    #[serde(rename = "breathingRate")]
+    pub breathing_rate: f64,
+}
+
+/// Any of the sleep / spo2 / breathing rate responses
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum FitbitWellnessFile {
+    Sleep(FitbitSleep),
+    SleepLogs(Vec<FitbitSleepLog>),
+    BreathingRate(FitbitBreathingRate),
+    SpO2(FitbitSpO2),
+    SpO2Range(Vec<FitbitSpO2>),
+}
+
+impl FitbitWellnessFile {
+    /// # Errors
+    /// Return error if a date can't be converted to local time
+    pub fn to_measurements(&self) -> Result<Vec<BiomarkerMeasurement>, Error> {
+        let mut measurements = Vec::new();
+        match self {
+            Self::Sleep(FitbitSleep { sleep }) | Self::SleepLogs(sleep) => {
+                for log in sleep.iter().filter(|log| log.is_main_sleep) {
+                    measurements.extend(log.to_measurements()?);
+                }
+            }
+            Self::BreathingRate(FitbitBreathingRate { br }) => {
+                for entry in br {
+                    measurements.push(get_measurement(
+                        SLEEP_RESPIRATION_SERIES,
+                        entry.date,
+                        entry.value.breathing_rate,
+                    )?);
+                }
+            }
+            Self::SpO2(spo2) => {
+                measurements.push(get_measurement(SPO2_SERIES, spo2.date, spo2.value.avg)?);
+            }
+            Self::SpO2Range(entries) => {
+                for spo2 in entries {
+                    measurements.push(get_measurement(SPO2_SERIES, spo2.date, spo2.value.avg)?);
+                }
+            }
+        }
+        measurements.retain(|m| m.value > 0.0);
+        measurements.shrink_to_fit();
+        Ok(measurements)
+    }
+}
+
+impl FitbitSleepLog {
+    /// Minutes asleep, efficiency and minutes in each stage at local midnight
+    /// of the date the sleep ended
+    /// # Errors
+    /// Return error if the date can't be converted to local time
+    pub fn to_measurements(&self) -> Result<Vec<BiomarkerMeasurement>, Error> {
+        let mut measurements = vec![get_measurement(
+            SLEEP_MINUTES_SERIES,
+            self.date_of_sleep,
+            self.minutes_asleep,
+        )?];
+        if let Some(efficiency) = self.efficiency {
+            measurements.push(get_measurement(
+                SLEEP_EFFICIENCY_SERIES,
+                self.date_of_sleep,
+                efficiency,
+            )?);
+        }
+        if let Some(levels) = &self.levels {
+            for (series, stage) in [
+                (SLEEP_DEEP_SERIES, &levels.summary.deep),
+                (SLEEP_LIGHT_SERIES, &levels.summary.light),
+                (SLEEP_REM_SERIES, &levels.summary.rem),
+                (SLEEP_WAKE_SERIES, &levels.summary.wake),
+            ] {
+                if let Some(stage) = stage {
+                    measurements.push(get_measurement(series, self.date_of_sleep, stage.minutes)?);
+                }
+            }
+        }
+        Ok(measurements)
+    }
+}
+
+fn get_measurement(series: &str, date: Date, value: f64) -> Result<BiomarkerMeasurement, Error> {
+    let datetime = date
+        .midnight()
+        .assume_timezone(DateTimeWrapper::local_tz())
+        .take_first()
+        .ok_or_else(|| format_err!("Invalid local date {date}"))?;
+    Ok(BiomarkerMeasurement {
+        series: series.into(),
+        datetime: datetime.into(),
+        value,
+    })
+}
+
+/// Store fitbit measurements, creating the series if needed, returns the
+/// number of values stored
+/// # Errors
+/// Return error if db queries fail
+pub async fn merge_fitbit_measurements(
+    pool: &PgPool,
+    measurements: &[BiomarkerMeasurement],
+    provenance: &Provenance,
+) -> Result<usize, Error> {
+    for (name, units) in [
+        (SLEEP_MINUTES_SERIES, "min"),
+        (SLEEP_EFFICIENCY_SERIES, "%"),
+        (SLEEP_DEEP_SERIES, "min"),
+        (SLEEP_LIGHT_SERIES, "min"),
+        (SLEEP_REM_SERIES, "min"),
+        (SLEEP_WAKE_SERIES, "min"),
+        (SPO2_SERIES, "%"),
+        (SLEEP_RESPIRATION_SERIES, "brpm"),
+    ] {
+        if measurements.iter().any(|m| m.series == name)
+            && BiomarkerSeries::get_by_name(pool, name).await?.is_none()
+        {
+            BiomarkerSeries::new(name, units).upsert_db(pool).await?;
+        }
+    }
+    BiomarkerMeasurement::merge_updates(measurements, pool, provenance).await
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use garmin_models::garmin_connect_wellness::{SLEEP_RESPIRATION_SERIES, SPO2_SERIES};
+
+    use crate::fitbit_wellness::{
+        FitbitWellnessFile, SLEEP_DEEP_SERIES, SLEEP_EFFICIENCY_SERIES, SLEEP_MINUTES_SERIES,
+    };
+
+    #[test]
+    fn test_fitbit_sleep() -> Result<(), Error> {
+        let buf = r#"{"sleep": [
+            {"dateOfSleep": "2024-03-01", "isMainSleep": true, "efficiency": 92,
+             "minutesAsleep": 421, "type": "stages",
+             "levels": {"summary": {
+                "deep": {"count": 4, "minutes": 81, "thirtyDayAvgMinutes": 70},
+                "light": {"count": 30, "minutes": 220, "thirtyDayAvgMinutes": 210},
+                "rem": {"count": 6, "minutes": 120, "thirtyDayAvgMinutes": 100},
+                "wake": {"count": 28, "minutes": 52, "thirtyDayAvgMinutes": 50}}}},
+            {"dateOfSleep": "2024-03-01", "isMainSleep": false, "efficiency": 90,
+             "minutesAsleep": 35, "type": "classic",
+             "levels": {"summary": {"asleep": {"count": 0, "minutes": 35}}}}
+        ], "summary": {"totalMinutesAsleep": 456}}"#;
+        let sleep: FitbitWellnessFile = serde_json::from_str(buf)?;
+        let measurements = sleep.to_measurements()?;
+        assert_eq!(measurements.len(), 6);
+        assert_eq!(measurements[0].series, SLEEP_MINUTES_SERIES);
+        assert_eq!(measurements[0].value, 421.0);
+        assert_eq!(measurements[1].series, SLEEP_EFFICIENCY_SERIES);
+        assert_eq!(measurements[2].series, SLEEP_DEEP_SERIES);
+        assert_eq!(measurements[2].value, 81.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fitbit_spo2_breathing_rate() -> Result<(), Error> {
+        let buf = r#"[
+            {"dateTime": "2024-03-01", "value": {"avg": 95.7, "min": 93.0, "max": 98.1}},
+            {"dateTime": "2024-03-02", "value": {"avg": 96.1, "min": 94.0, "max": 98.5}}
+        ]"#;
+        let spo2: FitbitWellnessFile = serde_json::from_str(buf)?;
+        let measurements = spo2.to_measurements()?;
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(measurements[0].series, SPO2_SERIES);
+        assert_eq!(measurements[1].value, 96.1);
+
+        let buf = r#"{"br": [{"value": {"breathingRate": 15.4}, "dateTime": "2024-03-01"}]}"#;
+        let br: FitbitWellnessFile = serde_json::from_str(buf)?;
+        let measurements = br.to_measurements()?;
+        assert_eq!(measurements.len(), 1);
+        assert_eq!(measurements[0].series, SLEEP_RESPIRATION_SERIES);
+        Ok(())
+    }
+}
@@ -5,9 +5,15 @@
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_possible_wrap)]
 
+pub mod archive_backup;
+pub mod ble_heartrate;
+pub mod calorie_estimate;
 pub mod fitbit_archive;
+pub mod fitbit_export;
 pub mod fitbit_heartrate;
+pub mod fitbit_intraday;
 pub mod fitbit_statistics_summary;
+pub mod fitbit_wellness;
 pub mod scale_measurement;
 
 use derive_more::{Display, Into};
@@ -5,16 +5,25 @@ use postgres_query::{query, query_dyn, Error as PqError, FromSqlRow, Parameter,
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use stack_string::format_sstr;
+use stack_string::{format_sstr, StackString};
 use std::{collections::HashSet, convert::TryInto, fmt, sync::Arc};
-use time::{Date, OffsetDateTime};
+use time::{Date, Duration, OffsetDateTime};
 use uuid::Uuid;
 
 use garmin_lib::{
     date_time_wrapper::{iso8601::convert_datetime_to_str, DateTimeWrapper},
     garmin_config::GarminConfig,
 };
-use garmin_utils::pgpool::PgPool;
+use garmin_models::{
+    keyset::{KeysetRow, PageStart},
+    provenance::Provenance,
+};
+use garmin_utils::{
+    fit_encode::{
+        fit_definition, fit_file, FIT_BASE_ENUM, FIT_BASE_UINT16, FIT_BASE_UINT32, FIT_EPOCH_OFFSET,
+    },
+    pgpool::PgPool,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Copy, FromSqlRow, PartialEq)]
 pub struct ScaleMeasurement {
@@ -25,6 +34,7 @@ pub struct ScaleMeasurement {
     pub water_pct: f64,
     pub muscle_pct: f64,
     pub bone_pct: f64,
+    pub connect_primary_key: Option<i64>,
 }
 
 impl fmt::Display for ScaleMeasurement {
@@ -44,16 +54,98 @@ impl fmt::Display for ScaleMeasurement {
     }
 }
 
+const LBS_PER_KG: f64 = 2.204_623;
+
+/// Largest number of measurements written to one Garmin Connect import file,
+/// connect rejects very large weight imports
+pub const CONNECT_EXPORT_BATCH: usize = 100;
+
+/// Exported measurements connect hasn't linked after this many hours are
+/// offered for export again
+pub const CONNECT_EXPORT_RETRY_HOURS: i32 = 24;
+
+/// Metrics derived from a measurement and the `height` (inches) in the
+/// config, masses are in lbs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ScaleMetrics {
+    pub bmi: f64,
+    pub fat_mass: Option<f64>,
+    pub lean_mass: Option<f64>,
+    pub ffmi: Option<f64>,
+}
+
+/// A measurement along with its derived metrics, as returned by the json api
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ScaleMeasurementWithMetrics {
+    #[serde(flatten)]
+    pub measurement: ScaleMeasurement,
+    #[serde(flatten)]
+    pub metrics: ScaleMetrics,
+}
+
+impl ScaleMeasurementWithMetrics {
+    #[must_use]
+    pub fn new(measurement: ScaleMeasurement, config: &GarminConfig) -> Self {
+        Self {
+            measurement,
+            metrics: measurement.get_metrics(config),
+        }
+    }
+}
+
+impl KeysetRow for ScaleMeasurement {
+    fn page_after(&self) -> PageStart {
+        PageStart::After {
+            datetime: self.datetime.into(),
+            id: 0,
+        }
+    }
+}
+
 impl ScaleMeasurement {
     #[must_use]
     pub fn get_bmi(&self, config: &GarminConfig) -> f64 {
         // Mass in Kg
-        let mass = self.mass * (1.0 / 2.204_623);
-        // Height in cm
+        let mass = self.mass * (1.0 / LBS_PER_KG);
+        // Height in m
         let height = config.height * 0.0254;
         mass / (height * height)
     }
 
+    /// Fat mass, `None` if the measurement has no body fat reading (e.g. a
+    /// manual entry with only mass)
+    #[must_use]
+    pub fn get_fat_mass(&self) -> Option<f64> {
+        if self.fat_pct > 0.0 && self.fat_pct.is_finite() {
+            Some(self.mass * self.fat_pct / 100.0)
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub fn get_lean_mass(&self) -> Option<f64> {
+        self.get_fat_mass().map(|fat_mass| self.mass - fat_mass)
+    }
+
+    /// Fat free mass index, lean mass in kg over height in m squared
+    #[must_use]
+    pub fn get_ffmi(&self, config: &GarminConfig) -> Option<f64> {
+        let lean_mass = self.get_lean_mass()? * (1.0 / LBS_PER_KG);
+        let height = config.height * 0.0254;
+        Some(lean_mass / (height * height))
+    }
+
+    #[must_use]
+    pub fn get_metrics(&self, config: &GarminConfig) -> ScaleMetrics {
+        ScaleMetrics {
+            bmi: self.get_bmi(config),
+            fat_mass: self.get_fat_mass(),
+            lean_mass: self.get_lean_mass(),
+            ffmi: self.get_ffmi(config),
+        }
+    }
+
     /// # Errors
     /// Returns error parsing msg fails
     pub fn from_fit_plus(
@@ -95,6 +187,7 @@ impl ScaleMeasurement {
             water_pct: body_water_percent,
             muscle_pct,
             bone_pct,
+            connect_primary_key: None,
         })
     }
 
@@ -136,6 +229,7 @@ impl ScaleMeasurement {
             water_pct: values[2],
             muscle_pct: values[3],
             bone_pct: values[4],
+            connect_primary_key: None,
         })
     }
 
@@ -168,6 +262,49 @@ impl ScaleMeasurement {
         Ok(result)
     }
 
+    /// Latest measurement at or before `dt`, or the first one after it when
+    /// there are none before
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn get_nearest_before(
+        dt: OffsetDateTime,
+        pool: &PgPool,
+    ) -> Result<Option<Self>, Error> {
+        let before = query!(
+            "
+                SELECT * FROM scale_measurements
+                WHERE datetime <= $dt
+                ORDER BY datetime DESC
+                LIMIT 1
+            ",
+            dt = dt
+        );
+        let after = query!(
+            "
+                SELECT * FROM scale_measurements
+                WHERE datetime > $dt
+                ORDER BY datetime
+                LIMIT 1
+            ",
+            dt = dt
+        );
+        let conn = pool.get().await?;
+        // two queries which can each walk the datetime index, rather than
+        // sorting the whole table by distance from `dt`
+        for query in [before, after] {
+            if let Some(row) = conn.query_opt(query.sql(), query.parameters()).await? {
+                return Self::from_row(&row).map(Some).map_err(Into::into);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Mass in kilograms
+    #[must_use]
+    pub fn mass_kg(&self) -> f64 {
+        self.mass / LBS_PER_KG
+    }
+
     /// # Errors
     /// Returns error if db query fails
     pub async fn delete_from_db(self, pool: &PgPool) -> Result<(), Error> {
@@ -185,12 +322,36 @@ impl ScaleMeasurement {
     /// # Errors
     /// Returns error if db query fails
     pub async fn insert_into_db(&mut self, pool: &PgPool) -> Result<(), Error> {
+        self.insert(pool, None).await
+    }
+
+    /// Insert the measurement along with where it came from in one statement
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn insert_into_db_with_provenance(
+        &mut self,
+        pool: &PgPool,
+        provenance: &Provenance,
+    ) -> Result<(), Error> {
+        self.insert(pool, Some(provenance)).await
+    }
+
+    async fn insert(
+        &mut self,
+        pool: &PgPool,
+        provenance: Option<&Provenance>,
+    ) -> Result<(), Error> {
         let query = query!(
             "
                 INSERT INTO scale_measurements (
-                    datetime, mass, fat_pct, water_pct, muscle_pct, bone_pct
+                    datetime, mass, fat_pct, water_pct, muscle_pct, bone_pct,
+                    connect_primary_key, provider, import_path, raw_file, imported_at
+                )
+                VALUES (
+                    $datetime, $mass, $fat, $water, $muscle, $bone,
+                    $connect_primary_key, $provider, $import_path, $raw_file, $imported_at
                 )
-                VALUES ($datetime,$mass,$fat,$water,$muscle,$bone)
+                RETURNING id
             ",
             datetime = self.datetime,
             mass = self.mass,
@@ -198,30 +359,14 @@ impl ScaleMeasurement {
             water = self.water_pct,
             muscle = self.muscle_pct,
             bone = self.bone_pct,
+            connect_primary_key = self.connect_primary_key,
+            provider = provenance.map(|p| p.provider.to_str()),
+            import_path = provenance.map(|p| &p.import_path),
+            raw_file = provenance.and_then(|p| p.raw_file.as_ref()),
+            imported_at = provenance.map(|p| p.imported_at),
         );
 
         let conn = pool.get().await?;
-
-        conn.execute(query.sql(), query.parameters()).await?;
-
-        let query = query!(
-            "
-                SELECT id
-                FROM scale_measurements
-                WHERE datetime = $datetime
-                    AND mass = $mass
-                    AND fat_pct = $fat
-                    AND water_pct = $water
-                    AND muscle_pct = $muscle
-                    AND bone_pct = $bone
-            ",
-            datetime = self.datetime,
-            mass = self.mass,
-            fat = self.fat_pct,
-            water = self.water_pct,
-            muscle = self.muscle_pct,
-            bone = self.bone_pct,
-        );
         let result = conn.query_one(query.sql(), query.parameters()).await?;
         self.id = result.try_get("id")?;
 
@@ -249,7 +394,7 @@ impl ScaleMeasurement {
         select_str: &'a str,
         start_date: Option<&'a Date>,
         end_date: Option<&'a Date>,
-        offset: Option<usize>,
+        start: Option<&'a PageStart>,
         limit: Option<usize>,
         order_str: &'a str,
     ) -> Result<Query<'a>, PqError> {
@@ -263,6 +408,10 @@ impl ScaleMeasurement {
             conditions.push("date(datetime) <= $end_date");
             query_bindings.push(("end_date", d as Parameter));
         }
+        if let Some(PageStart::After { datetime, .. }) = start {
+            conditions.push("datetime > $after_datetime");
+            query_bindings.push(("after_datetime", datetime as Parameter));
+        }
         let mut query = format_sstr!(
             "SELECT {select_str} FROM scale_measurements {} {order_str}",
             if conditions.is_empty() {
@@ -271,7 +420,7 @@ impl ScaleMeasurement {
                 format_sstr!("WHERE {}", conditions.join(" AND "))
             }
         );
-        if let Some(offset) = offset {
+        if let Some(offset) = start.and_then(PageStart::offset) {
             query.push_str(&format_sstr!(" OFFSET {offset}"));
         }
         if let Some(limit) = limit {
@@ -288,14 +437,14 @@ impl ScaleMeasurement {
         pool: &PgPool,
         start_date: Option<Date>,
         end_date: Option<Date>,
-        offset: Option<usize>,
+        start: Option<PageStart>,
         limit: Option<usize>,
     ) -> Result<Vec<Self>, Error> {
         let query = Self::get_scale_measurement_query(
             "*",
             start_date.as_ref(),
             end_date.as_ref(),
-            offset,
+            start.as_ref(),
             limit,
             "ORDER BY datetime",
         )?;
@@ -329,9 +478,14 @@ impl ScaleMeasurement {
         Ok(count.count.try_into()?)
     }
 
+    /// Insert measurements not yet in the db, recording `provenance` on each
     /// # Errors
     /// Returns error if db query fails
-    pub async fn merge_updates<'a, T>(measurements: T, pool: &PgPool) -> Result<(), Error>
+    pub async fn merge_updates<'a, T>(
+        measurements: T,
+        pool: &PgPool,
+        provenance: &Provenance,
+    ) -> Result<(), Error>
     where
         T: IntoIterator<Item = &'a mut Self>,
     {
@@ -350,7 +504,8 @@ impl ScaleMeasurement {
                     if measurement_set.contains(&meas.datetime) {
                         debug!("measurement exists {:?}", meas);
                     } else {
-                        meas.insert_into_db(pool).await?;
+                        meas.insert_into_db_with_provenance(pool, provenance)
+                            .await?;
                         debug!("measurement inserted {:?}", meas);
                     }
                     Ok(())
@@ -359,19 +514,609 @@ impl ScaleMeasurement {
             .collect();
         futures.try_collect().await
     }
+
+    /// Groups of near duplicate measurements, ordered by datetime, where each
+    /// measurement is within `time_tolerance` and `mass_tolerance` lbs of the
+    /// first one of the group, so a slow drift doesn't chain readings together
+    #[must_use]
+    pub fn find_duplicates(
+        measurements: &[Self],
+        time_tolerance: Duration,
+        mass_tolerance: f64,
+    ) -> Vec<Vec<Self>> {
+        let mut measurements = measurements.to_vec();
+        measurements.sort_by_key(|m| m.datetime);
+        let mut groups = Vec::new();
+        let mut current: Vec<Self> = Vec::new();
+        for meas in measurements {
+            if let Some(anchor) = current.first() {
+                if meas.datetime.to_offsetdatetime() - anchor.datetime.to_offsetdatetime()
+                    > time_tolerance
+                    || (meas.mass - anchor.mass).abs() > mass_tolerance
+                {
+                    if current.len() > 1 {
+                        groups.push(current);
+                    }
+                    current = Vec::new();
+                }
+            }
+            current.push(meas);
+        }
+        if current.len() > 1 {
+            groups.push(current);
+        }
+        groups
+    }
+
+    /// Measurement of a duplicate group to keep, the one with the most body
+    /// composition values set (manual entries often only have mass),
+    /// earliest first
+    #[must_use]
+    pub fn preferred(group: &[Self]) -> Option<&Self> {
+        group.iter().max_by(|a, b| {
+            a.number_of_values()
+                .cmp(&b.number_of_values())
+                .then_with(|| b.datetime.cmp(&a.datetime))
+        })
+    }
+
+    fn number_of_values(&self) -> usize {
+        [self.fat_pct, self.water_pct, self.muscle_pct, self.bone_pct]
+            .iter()
+            .filter(|v| **v > 0.0)
+            .count()
+    }
+
+    /// Delete every measurement in `group` other than `keep`, returns the
+    /// number deleted
+    /// # Errors
+    /// Returns error if `keep` isn't in `group` or db query fails
+    pub async fn merge_duplicates(
+        group: &[Self],
+        keep: Uuid,
+        pool: &PgPool,
+    ) -> Result<usize, Error> {
+        if !group.iter().any(|m| m.id == keep) {
+            return Err(format_err!("{keep} is not part of the group"));
+        }
+        let ids: Vec<Uuid> = group
+            .iter()
+            .filter_map(|m| if m.id == keep { None } else { Some(m.id) })
+            .collect();
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        for id in &ids {
+            tran.execute("DELETE FROM scale_measurements WHERE id = $1", &[id])
+                .await?;
+        }
+        tran.commit().await?;
+        Ok(ids.len())
+    }
+
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn set_connect_primary_key(
+        &mut self,
+        connect_primary_key: i64,
+        pool: &PgPool,
+    ) -> Result<(), Error> {
+        let query = query!(
+            "
+                UPDATE scale_measurements
+                SET connect_primary_key = $connect_primary_key
+                WHERE id = $id
+            ",
+            id = self.id,
+            connect_primary_key = connect_primary_key,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        self.connect_primary_key.replace(connect_primary_key);
+        Ok(())
+    }
+
+    /// Measurements never matched with a Garmin Connect entry, oldest first,
+    /// leaving out those exported within the last
+    /// `CONNECT_EXPORT_RETRY_HOURS` so each export moves on to the next batch
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn read_unlinked_connect(
+        pool: &PgPool,
+        limit: Option<usize>,
+    ) -> Result<Vec<Self>, Error> {
+        let limit: Option<i64> = limit.map(TryInto::try_into).transpose()?;
+        let query = query!(
+            "
+                SELECT * FROM scale_measurements
+                WHERE connect_primary_key IS NULL
+                  AND (
+                    connect_exported_at IS NULL
+                    OR connect_exported_at < now() - make_interval(hours => $retry_hours)
+                  )
+                ORDER BY datetime
+                LIMIT $limit
+            ",
+            retry_hours = CONNECT_EXPORT_RETRY_HOURS,
+            limit = limit,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Record that `measurements` were written to a connect import file, the
+    /// connect key is set once the weights come back from connect
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn mark_connect_exported(measurements: &[Self], pool: &PgPool) -> Result<u64, Error> {
+        let ids: Vec<Uuid> = measurements.iter().map(|m| m.id).collect();
+        let query = query!(
+            "
+                UPDATE scale_measurements
+                SET connect_exported_at = now()
+                WHERE id = ANY($ids)
+            ",
+            ids = ids,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+
+    /// Encode `measurements` as a FIT weight file for the Garmin Connect
+    /// importer, body composition values which aren't set are left out
+    #[must_use]
+    pub fn to_connect_fit(measurements: &[Self]) -> Vec<u8> {
+        let fit_timestamp = |meas: &Self| {
+            (meas.datetime.unix_timestamp() - FIT_EPOCH_OFFSET).clamp(0, i64::from(u32::MAX)) as u32
+        };
+        let hundredths = |value: f64| {
+            if value > 0.0 {
+                ((value * 100.0).round() as u16).to_le_bytes()
+            } else {
+                u16::MAX.to_le_bytes()
+            }
+        };
+        let mut data = Vec::new();
+
+        // file_id: type=weight, manufacturer=development
+        fit_definition(
+            &mut data,
+            0,
+            0,
+            &[
+                (0, 1, FIT_BASE_ENUM),
+                (1, 2, FIT_BASE_UINT16),
+                (2, 2, FIT_BASE_UINT16),
+                (4, 4, FIT_BASE_UINT32),
+            ],
+        );
+        data.push(0);
+        data.push(9);
+        data.extend_from_slice(&255u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&measurements.first().map_or(0, fit_timestamp).to_le_bytes());
+
+        // weight_scale: weight, percent_fat, percent_hydration, bone_mass and
+        // muscle_mass, masses in kg, all values scaled by 100
+        fit_definition(
+            &mut data,
+            1,
+            30,
+            &[
+                (253, 4, FIT_BASE_UINT32),
+                (0, 2, FIT_BASE_UINT16),
+                (1, 2, FIT_BASE_UINT16),
+                (2, 2, FIT_BASE_UINT16),
+                (4, 2, FIT_BASE_UINT16),
+                (5, 2, FIT_BASE_UINT16),
+            ],
+        );
+        for meas in measurements {
+            let mass_kg = meas.mass / LBS_PER_KG;
+            data.push(1);
+            data.extend_from_slice(&fit_timestamp(meas).to_le_bytes());
+            data.extend_from_slice(&hundredths(mass_kg));
+            data.extend_from_slice(&hundredths(meas.fat_pct));
+            data.extend_from_slice(&hundredths(meas.water_pct));
+            data.extend_from_slice(&hundredths(mass_kg * meas.bone_pct / 100.0));
+            data.extend_from_slice(&hundredths(mass_kg * meas.muscle_pct / 100.0));
+        }
+        fit_file(&data)
+    }
+
+    /// Match each connect measurement against `local`: entries already
+    /// linked by `connect_primary_key` are skipped, otherwise the closest
+    /// unlinked local measurement within `time_tolerance` and
+    /// `mass_tolerance` lbs gets linked, and anything left is new
+    #[must_use]
+    pub fn reconcile_connect(
+        local: &[Self],
+        connect: &[Self],
+        time_tolerance: Duration,
+        mass_tolerance: f64,
+    ) -> Vec<ConnectReconciliation> {
+        let mut linked: HashSet<Uuid> = HashSet::new();
+        let mut result = Vec::new();
+        for meas in connect {
+            let connect_primary_key = match meas.connect_primary_key {
+                Some(k) => k,
+                None => continue,
+            };
+            if local
+                .iter()
+                .any(|m| m.connect_primary_key == Some(connect_primary_key))
+            {
+                continue;
+            }
+            let datetime = meas.datetime.to_offsetdatetime();
+            let closest = local
+                .iter()
+                .filter(|m| m.connect_primary_key.is_none() && !linked.contains(&m.id))
+                .filter(|m| (m.mass - meas.mass).abs() <= mass_tolerance)
+                .map(|m| ((m.datetime.to_offsetdatetime() - datetime).abs(), m))
+                .filter(|(diff, _)| *diff <= time_tolerance)
+                .min_by_key(|(diff, _)| *diff);
+            if let Some((_, m)) = closest {
+                linked.insert(m.id);
+                result.push(ConnectReconciliation::Link {
+                    id: m.id,
+                    connect_primary_key,
+                });
+            } else {
+                result.push(ConnectReconciliation::Insert(*meas));
+            }
+        }
+        result
+    }
+
+    /// Merge weights pulled from connect into `scale_measurements`, returns
+    /// the number of measurements linked and inserted, `provenance` is only
+    /// recorded on inserted measurements
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn merge_connect_weights(
+        connect: &[Self],
+        pool: &PgPool,
+        time_tolerance: Duration,
+        mass_tolerance: f64,
+        provenance: &Provenance,
+    ) -> Result<(usize, usize), Error> {
+        let (start_date, end_date) = match (
+            connect.iter().map(|m| m.datetime).min(),
+            connect.iter().map(|m| m.datetime).max(),
+        ) {
+            (Some(start), Some(end)) => (
+                (start.to_offsetdatetime() - time_tolerance).date(),
+                (end.to_offsetdatetime() + time_tolerance).date(),
+            ),
+            _ => return Ok((0, 0)),
+        };
+        let local = Self::read_from_db(pool, Some(start_date), Some(end_date), None, None).await?;
+        let (mut linked, mut inserted) = (0, 0);
+        for action in Self::reconcile_connect(&local, connect, time_tolerance, mass_tolerance) {
+            match action {
+                ConnectReconciliation::Link {
+                    id,
+                    connect_primary_key,
+                } => {
+                    if let Some(mut meas) = local.iter().find(|m| m.id == id).copied() {
+                        meas.set_connect_primary_key(connect_primary_key, pool)
+                            .await?;
+                        linked += 1;
+                    }
+                }
+                ConnectReconciliation::Insert(mut meas) => {
+                    meas.insert_into_db_with_provenance(pool, provenance)
+                        .await?;
+                    inserted += 1;
+                }
+            }
+        }
+        Ok((linked, inserted))
+    }
+
+    /// Split bulk import `entries` into measurements to insert, along with
+    /// the index of their entry, and skipped entries. An entry is skipped
+    /// when it failed to parse or validate, lies within `time_tolerance` and
+    /// `mass_tolerance` lbs of a measurement in `existing` or of an earlier
+    /// entry, or has the same time as one of them (`datetime` is unique)
+    #[must_use]
+    pub fn plan_bulk_import(
+        existing: &[Self],
+        entries: &[Result<ScaleMeasurementImport, StackString>],
+        time_tolerance: Duration,
+        mass_tolerance: f64,
+    ) -> (Vec<(usize, Self)>, Vec<ScaleImportSkipped>) {
+        let mut known: Vec<(OffsetDateTime, f64)> = existing
+            .iter()
+            .map(|m| (m.datetime.to_offsetdatetime(), m.mass))
+            .collect();
+        known.sort_by_key(|(datetime, _)| *datetime);
+        let mut inserts = Vec::new();
+        let mut skipped = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let meas = match entry
+                .as_ref()
+                .map_err(Clone::clone)
+                .and_then(|e| e.to_measurement().map_err(|e| format_sstr!("{e}")))
+            {
+                Ok(meas) => meas,
+                Err(reason) => {
+                    skipped.push(ScaleImportSkipped { index, reason });
+                    continue;
+                }
+            };
+            let datetime = meas.datetime.to_offsetdatetime();
+            let first = known.partition_point(|(d, _)| *d < datetime - time_tolerance);
+            let nearby = || {
+                known[first..]
+                    .iter()
+                    .take_while(|(d, _)| *d <= datetime + time_tolerance)
+            };
+            if let Some((d, _)) =
+                nearby().find(|(_, mass)| (mass - meas.mass).abs() <= mass_tolerance)
+            {
+                let reason = format_sstr!(
+                    "duplicate of measurement at {}",
+                    convert_datetime_to_str(*d)
+                );
+                skipped.push(ScaleImportSkipped { index, reason });
+                continue;
+            }
+            if nearby().any(|(d, _)| *d == datetime) {
+                let reason = format_sstr!(
+                    "another measurement exists at {}",
+                    convert_datetime_to_str(datetime)
+                );
+                skipped.push(ScaleImportSkipped { index, reason });
+                continue;
+            }
+            let idx = known.partition_point(|(d, _)| *d <= datetime);
+            known.insert(idx, (datetime, meas.mass));
+            inserts.push((index, meas));
+        }
+        (inserts, skipped)
+    }
+
+    /// Insert the entries of a bulk import which aren't duplicates of
+    /// measurements already in the db, recording `provenance` on each, all in
+    /// one transaction. Entries whose time was taken since the plan was made
+    /// are skipped rather than failing the import
+    /// # Errors
+    /// Returns error if db query fails
+    pub async fn bulk_import(
+        entries: &[Result<ScaleMeasurementImport, StackString>],
+        pool: &PgPool,
+        time_tolerance: Duration,
+        mass_tolerance: f64,
+        provenance: &Provenance,
+    ) -> Result<ScaleImportReport, Error> {
+        let datetimes = entries
+            .iter()
+            .filter_map(|e| e.as_ref().ok())
+            .map(|e| e.datetime.to_offsetdatetime());
+        let existing = match (datetimes.clone().min(), datetimes.max()) {
+            (Some(start), Some(end)) => {
+                let start_date = (start - time_tolerance).date();
+                let end_date = (end + time_tolerance).date();
+                Self::read_from_db(pool, Some(start_date), Some(end_date), None, None).await?
+            }
+            _ => Vec::new(),
+        };
+        let (inserts, mut skipped) =
+            Self::plan_bulk_import(&existing, entries, time_tolerance, mass_tolerance);
+        let mut inserted = 0;
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        for (index, meas) in inserts {
+            let query = query!(
+                "
+                    INSERT INTO scale_measurements (
+                        datetime, mass, fat_pct, water_pct, muscle_pct, bone_pct,
+                        provider, import_path, raw_file, imported_at
+                    )
+                    VALUES (
+                        $datetime, $mass, $fat, $water, $muscle, $bone,
+                        $provider, $import_path, $raw_file, $imported_at
+                    )
+                    ON CONFLICT (datetime) DO NOTHING
+                ",
+                datetime = meas.datetime,
+                mass = meas.mass,
+                fat = meas.fat_pct,
+                water = meas.water_pct,
+                muscle = meas.muscle_pct,
+                bone = meas.bone_pct,
+                provider = provenance.provider.to_str(),
+                import_path = &provenance.import_path,
+                raw_file = &provenance.raw_file,
+                imported_at = provenance.imported_at,
+            );
+            if tran.execute(query.sql(), query.parameters()).await? == 0 {
+                let reason = format_sstr!(
+                    "another measurement exists at {}",
+                    convert_datetime_to_str(meas.datetime.to_offsetdatetime())
+                );
+                skipped.push(ScaleImportSkipped { index, reason });
+            } else {
+                inserted += 1;
+            }
+        }
+        tran.commit().await?;
+        skipped.sort_by_key(|s| s.index);
+        Ok(ScaleImportReport { inserted, skipped })
+    }
+}
+
+/// Measurement imported in bulk from another app, `units` of `mass` are lbs
+/// (default) or kg and body composition values the app doesn't record are
+/// left out
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScaleMeasurementImport {
+    pub datetime: DateTimeWrapper,
+    pub mass: f64,
+    pub units: Option<StackString>,
+    #[serde(default)]
+    pub fat_pct: f64,
+    #[serde(default)]
+    pub water_pct: f64,
+    #[serde(default)]
+    pub muscle_pct: f64,
+    #[serde(default)]
+    pub bone_pct: f64,
+}
+
+impl ScaleMeasurementImport {
+    /// Entries of a json array or of newline delimited json, entries which
+    /// fail to parse carry the parse error
+    /// # Errors
+    /// Return error if `body` is neither a json array nor ndjson
+    pub fn parse_bulk(body: &[u8]) -> Result<Vec<Result<Self, StackString>>, Error> {
+        let parse = |value: serde_json::Value| {
+            serde_json::from_value::<Self>(value).map_err(|e| format_sstr!("{e}"))
+        };
+        let body = std::str::from_utf8(body)?.trim();
+        if body.starts_with('[') {
+            let values: Vec<serde_json::Value> = serde_json::from_str(body)?;
+            return Ok(values.into_iter().map(parse).collect());
+        }
+        Ok(body
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| format_sstr!("{e}"))
+                    .and_then(parse)
+            })
+            .collect())
+    }
+
+    /// # Errors
+    /// Return error if the mass, percentages, units or datetime are invalid
+    pub fn to_measurement(&self) -> Result<ScaleMeasurement, Error> {
+        let mass = match self.units.as_deref() {
+            None | Some("lbs" | "lb") => self.mass,
+            Some("kg") => self.mass * LBS_PER_KG,
+            Some(units) => return Err(format_err!("Invalid units {units}")),
+        };
+        if !mass.is_finite() || mass <= 0.0 || mass > 1e3 {
+            return Err(format_err!("Invalid mass {}", self.mass));
+        }
+        let pcts = [self.fat_pct, self.water_pct, self.muscle_pct, self.bone_pct];
+        if pcts
+            .iter()
+            .any(|p| !p.is_finite() || *p < 0.0 || *p > 100.0)
+        {
+            return Err(format_err!("Percentages must be between 0 and 100"));
+        }
+        if self.datetime.to_offsetdatetime() > OffsetDateTime::now_utc() {
+            return Err(format_err!("Measurement is in the future"));
+        }
+        Ok(ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: self.datetime,
+            mass,
+            fat_pct: self.fat_pct,
+            water_pct: self.water_pct,
+            muscle_pct: self.muscle_pct,
+            bone_pct: self.bone_pct,
+            connect_primary_key: None,
+        })
+    }
+}
+
+/// Entry of a bulk import which wasn't inserted, `index` is its position in
+/// the import
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScaleImportSkipped {
+    pub index: usize,
+    pub reason: StackString,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScaleImportReport {
+    pub inserted: usize,
+    pub skipped: Vec<ScaleImportSkipped>,
+}
+
+/// Outcome of matching a connect weight entry with local measurements
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectReconciliation {
+    Link { id: Uuid, connect_primary_key: i64 },
+    Insert(ScaleMeasurement),
+}
+
+const GRAMS_PER_LB: f64 = 453.592_37;
+
+/// Response of `weight-service/weight/dateRange`
+#[derive(Deserialize, Debug)]
+pub struct GarminConnectWeightRange {
+    #[serde(rename = "dateWeightList", default)]
+    pub date_weight_list: Vec<GarminConnectWeight>,
+}
+
+/// Masses are in grams
+#[derive(Deserialize, Debug)]
+pub struct GarminConnectWeight {
+    #[serde(rename = "samplePk")]
+    pub sample_pk: i64,
+    #[serde(rename = "timestampGMT")]
+    pub timestamp_gmt: i64,
+    pub weight: f64,
+    #[serde(rename = "bodyFat")]
+    pub body_fat: Option<f64>,
+    #[serde(rename = "bodyWater")]
+    pub body_water: Option<f64>,
+    #[serde(rename = "boneMass")]
+    pub bone_mass: Option<f64>,
+    #[serde(rename = "muscleMass")]
+    pub muscle_mass: Option<f64>,
+}
+
+impl GarminConnectWeightRange {
+    /// Entries as measurements, unset body composition values become 0
+    #[must_use]
+    pub fn to_measurements(&self) -> Vec<ScaleMeasurement> {
+        self.date_weight_list
+            .iter()
+            .filter(|w| w.weight > 0.0)
+            .filter_map(|w| {
+                let datetime = OffsetDateTime::from_unix_timestamp_nanos(
+                    i128::from(w.timestamp_gmt) * 1_000_000,
+                )
+                .ok()?;
+                let pct_of_weight = |mass: Option<f64>| mass.map_or(0.0, |m| m / w.weight * 100.0);
+                Some(ScaleMeasurement {
+                    id: Uuid::new_v4(),
+                    datetime: datetime.into(),
+                    mass: w.weight / GRAMS_PER_LB,
+                    fat_pct: w.body_fat.unwrap_or(0.0),
+                    water_pct: w.body_water.unwrap_or(0.0),
+                    muscle_pct: pct_of_weight(w.muscle_mass),
+                    bone_pct: pct_of_weight(w.bone_mass),
+                    connect_primary_key: Some(w.sample_pk),
+                })
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Error;
     use log::debug;
-    use time::{macros::datetime, OffsetDateTime};
+    use time::{macros::datetime, Duration, OffsetDateTime};
     use uuid::Uuid;
 
+    use fitparser::{profile::field_types::MesgNum, Value};
+
     use garmin_lib::garmin_config::GarminConfig;
-    use garmin_utils::pgpool::PgPool;
+    use garmin_models::keyset::KeysetRow;
+    use garmin_utils::{fit_encode::fit_crc, pgpool::PgPool};
 
-    use crate::scale_measurement::ScaleMeasurement;
+    use crate::scale_measurement::{
+        ConnectReconciliation, GarminConnectWeightRange, ScaleMeasurement, ScaleMeasurementImport,
+        ScaleMeasurementWithMetrics,
+    };
 
     #[test]
     fn test_from_telegram_text() -> Result<(), Error> {
@@ -385,6 +1130,7 @@ mod tests {
             water_pct: 59.6,
             muscle_pct: 40.4,
             bone_pct: 4.2,
+            connect_primary_key: None,
         };
         exp.datetime = obs.datetime;
         assert_eq!(obs, exp);
@@ -401,6 +1147,263 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_get_metrics() -> Result<(), Error> {
+        let config = GarminConfig::default();
+        let meas = ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: datetime!(2024-01-01 07:00:00 -05:00).into(),
+            mass: 180.0,
+            fat_pct: 20.0,
+            water_pct: 59.6,
+            muscle_pct: 40.4,
+            bone_pct: 4.2,
+            connect_primary_key: None,
+        };
+        let metrics = meas.get_metrics(&config);
+        assert!((metrics.fat_mass.unwrap() - 36.0).abs() < 1e-6);
+        assert!((metrics.lean_mass.unwrap() - 144.0).abs() < 1e-6);
+        // 71 inches
+        assert!((metrics.bmi - 25.1).abs() < 0.05);
+        assert!((metrics.ffmi.unwrap() - 20.1).abs() < 0.05);
+
+        // a manual entry without body fat has no lean mass
+        let manual = ScaleMeasurement {
+            fat_pct: 0.0,
+            ..meas
+        };
+        let metrics = manual.get_metrics(&config);
+        assert_eq!(metrics.fat_mass, None);
+        assert_eq!(metrics.lean_mass, None);
+        assert_eq!(metrics.ffmi, None);
+        assert!((metrics.bmi - 25.1).abs() < 0.05);
+
+        let js = serde_json::to_value(ScaleMeasurementWithMetrics::new(meas, &config))?;
+        assert_eq!(js["mass"], 180.0);
+        assert_eq!(js["fat_mass"], 36.0);
+        let obs: ScaleMeasurement = serde_json::from_value(js)?;
+        assert_eq!(obs, meas);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let first = ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: datetime!(2024-01-01 07:00:00 -05:00).into(),
+            mass: 188.0,
+            fat_pct: 0.0,
+            water_pct: 0.0,
+            muscle_pct: 0.0,
+            bone_pct: 0.0,
+            connect_primary_key: None,
+        };
+        let synced = ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: datetime!(2024-01-01 07:04:00 -05:00).into(),
+            mass: 188.2,
+            fat_pct: 20.6,
+            water_pct: 59.6,
+            muscle_pct: 40.4,
+            bone_pct: 4.2,
+            connect_primary_key: None,
+        };
+        let next_day = ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: datetime!(2024-01-02 07:00:00 -05:00).into(),
+            ..synced
+        };
+        let measurements = [next_day, synced, first];
+        let groups = ScaleMeasurement::find_duplicates(&measurements, Duration::minutes(10), 0.5);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 2);
+        assert_eq!(groups[0][0].id, first.id);
+        assert_eq!(
+            ScaleMeasurement::preferred(&groups[0]).map(|m| m.id),
+            Some(synced.id)
+        );
+        assert!(
+            ScaleMeasurement::find_duplicates(&measurements, Duration::minutes(10), 0.1).is_empty()
+        );
+
+        // readings 8 minutes apart each are compared to the first of the group
+        let chain: Vec<_> = (0..3)
+            .map(|i| ScaleMeasurement {
+                id: Uuid::new_v4(),
+                datetime: (datetime!(2024-01-03 07:00:00 -05:00) + Duration::minutes(8 * i)).into(),
+                mass: 188.0 + 0.3 * i as f64,
+                ..synced
+            })
+            .collect();
+        let groups = ScaleMeasurement::find_duplicates(&chain, Duration::minutes(10), 0.5);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 2);
+        assert_eq!(groups[0][0].id, chain[0].id);
+        assert_eq!(groups[0][1].id, chain[1].id);
+    }
+
+    #[test]
+    fn test_plan_bulk_import() -> Result<(), Error> {
+        let existing = ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: datetime!(2024-01-01 12:00:00 UTC).into(),
+            mass: 188.0,
+            fat_pct: 20.6,
+            water_pct: 59.6,
+            muscle_pct: 40.4,
+            bone_pct: 4.2,
+            connect_primary_key: None,
+        };
+        let ndjson = br#"
+            {"datetime": "2024-01-01T12:03:00Z", "mass": 188.2}
+            {"datetime": "2024-01-02T12:00:00Z", "mass": 85.0, "units": "kg", "fat_pct": 21.0}
+            {"datetime": "2024-01-02T12:05:00Z", "mass": 190.0}
+            {"datetime": "2024-01-03T12:00:00Z", "mass": -1.0}
+            {"datetime": "2024-01-04T12:00:00Z"}
+            {"datetime": "2024-01-05T12:00:00Z", "mass": 187.0, "units": "stone"}
+            {"datetime": "2024-01-01T12:00:00Z", "mass": 180.0}
+            {"datetime": "2024-01-02T12:05:00Z", "mass": 195.0}
+        "#;
+        let entries = ScaleMeasurementImport::parse_bulk(ndjson)?;
+        assert_eq!(entries.len(), 8);
+        assert!(entries[4].is_err());
+        let (inserts, skipped) =
+            ScaleMeasurement::plan_bulk_import(&[existing], &entries, Duration::minutes(10), 0.5);
+        assert_eq!(inserts.len(), 2);
+        assert_eq!(inserts[0].0, 1);
+        assert!((inserts[0].1.mass - 187.393).abs() < 1e-3);
+        assert!((inserts[0].1.fat_pct - 21.0).abs() < 1e-6);
+        assert_eq!(inserts[1].0, 2);
+        assert!((inserts[1].1.mass - 190.0).abs() < 1e-6);
+        let skipped: Vec<_> = skipped.iter().map(|s| s.index).collect();
+        assert_eq!(skipped, vec![0, 3, 4, 5, 6, 7]);
+
+        let array = br#"[{"datetime": "2024-01-02T12:00:00Z", "mass": 187.0}, {"mass": 1}]"#;
+        let entries = ScaleMeasurementImport::parse_bulk(array)?;
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_ok());
+        assert!(entries[1].is_err());
+        assert!(ScaleMeasurementImport::parse_bulk(b"[{]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconcile_connect() -> Result<(), Error> {
+        let buf = r#"{"dateWeightList": [
+            {"samplePk": 1001, "timestampGMT": 1704110520000, "weight": 85366.0,
+             "bodyFat": 20.6, "bodyWater": 59.6, "boneMass": 3585.0, "muscleMass": 34488.0},
+            {"samplePk": 1002, "timestampGMT": 1704196800000, "weight": 85275.0,
+             "bodyFat": null, "bodyWater": null, "boneMass": null, "muscleMass": null}
+        ]}"#;
+        let range: GarminConnectWeightRange = serde_json::from_str(buf)?;
+        let connect = range.to_measurements();
+        assert_eq!(connect.len(), 2);
+        assert!((connect[0].mass - 188.2).abs() < 0.05);
+        assert!((connect[0].bone_pct - 4.2).abs() < 0.05);
+        assert_eq!(connect[1].fat_pct, 0.0);
+
+        let local = ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: datetime!(2024-01-01 07:00:00 -05:00).into(),
+            mass: 188.0,
+            fat_pct: 20.6,
+            water_pct: 59.6,
+            muscle_pct: 40.4,
+            bone_pct: 4.2,
+            connect_primary_key: None,
+        };
+        let actions =
+            ScaleMeasurement::reconcile_connect(&[local], &connect, Duration::minutes(10), 0.5);
+        assert_eq!(
+            actions[0],
+            ConnectReconciliation::Link {
+                id: local.id,
+                connect_primary_key: 1001
+            }
+        );
+        assert_eq!(actions[1], ConnectReconciliation::Insert(connect[1]));
+
+        let linked = ScaleMeasurement {
+            connect_primary_key: Some(1001),
+            ..local
+        };
+        let actions =
+            ScaleMeasurement::reconcile_connect(&[linked], &connect, Duration::minutes(10), 0.5);
+        assert_eq!(actions.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_connect_fit() -> Result<(), Error> {
+        let meas = ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: datetime!(2024-01-01 07:00:00 -05:00).into(),
+            mass: 188.0,
+            fat_pct: 20.6,
+            water_pct: 59.6,
+            muscle_pct: 40.4,
+            bone_pct: 4.2,
+            connect_primary_key: None,
+        };
+        let mass_only = ScaleMeasurement {
+            id: Uuid::new_v4(),
+            datetime: datetime!(2024-01-02 07:00:00 -05:00).into(),
+            mass: 187.5,
+            fat_pct: 0.0,
+            water_pct: 0.0,
+            muscle_pct: 0.0,
+            bone_pct: 0.0,
+            connect_primary_key: None,
+        };
+        let fit = ScaleMeasurement::to_connect_fit(&[meas, mass_only]);
+        assert_eq!(fit_crc(&fit), 0);
+        let weights: Vec<_> = fitparser::from_bytes(&fit)?
+            .into_iter()
+            .filter(|r| r.kind() == MesgNum::WeightScale)
+            .collect();
+        assert_eq!(weights.len(), 2);
+        let field = |idx: usize, name: &str| {
+            weights[idx]
+                .fields()
+                .iter()
+                .find(|f| f.name() == name)
+                .and_then(|f| match f.value() {
+                    Value::Float64(w) => Some(*w),
+                    _ => None,
+                })
+        };
+        let timestamp = |idx: usize| {
+            weights[idx]
+                .fields()
+                .iter()
+                .find(|f| f.name() == "timestamp")
+                .and_then(|f| match f.value() {
+                    Value::Timestamp(t) => Some(t.timestamp()),
+                    _ => None,
+                })
+        };
+        let mass_kg = 188.0 / 2.204_623;
+        let expected = [
+            ("weight", mass_kg),
+            ("percent_fat", 20.6),
+            ("percent_hydration", 59.6),
+            ("bone_mass", mass_kg * 0.042),
+            ("muscle_mass", mass_kg * 0.404),
+        ];
+        for (name, value) in expected {
+            let obs = field(0, name).unwrap();
+            assert!((obs - value).abs() < 0.01, "{name} {obs} {value}");
+        }
+        assert_eq!(timestamp(0), Some(meas.datetime.unix_timestamp()));
+
+        assert!((field(1, "weight").unwrap() - 187.5 / 2.204_623).abs() < 0.01);
+        for (name, _) in &expected[1..] {
+            assert!(field(1, name).is_none(), "{name}");
+        }
+        assert_eq!(timestamp(1), Some(mass_only.datetime.unix_timestamp()));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_write_read_scale_measurement_from_db() -> Result<(), Error> {
         let first_date = datetime!(2010-01-01 04:00:00 -05:00).into();
@@ -412,6 +1415,7 @@ mod tests {
             water_pct: 59.6,
             muscle_pct: 40.4,
             bone_pct: 4.2,
+            connect_primary_key: None,
         };
 
         let config = GarminConfig::get_config(None)?;
@@ -436,6 +1440,11 @@ mod tests {
         assert_eq!(first, exp);
         assert_eq!(first.datetime, first_date);
 
+        let after =
+            ScaleMeasurement::read_from_db(&pool, None, None, Some(first.page_after()), Some(1))
+                .await?;
+        assert_eq!(after.first(), measurements.get(1));
+
         exp.delete_from_db(&pool).await?;
 
         Ok(())
@@ -0,0 +1,363 @@
+use anyhow::{format_err, Error};
+use futures::{stream::FuturesUnordered, TryStreamExt};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use time::{macros::time, Date, Duration, OffsetDateTime};
+use tokio::{
+    fs::{create_dir_all, write},
+    task::spawn_blocking,
+};
+use uuid::Uuid;
+
+use fitbit_lib::{fitbit_heartrate::FitbitHeartRate, scale_measurement::ScaleMeasurement};
+use garmin_lib::garmin_config::GarminConfig;
+use garmin_models::provenance::{DataProvider, Provenance};
+use garmin_parser::demo_data::DemoActivity;
+use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
+
+/// Minutes between generated heart rate values
+const HEARTRATE_INTERVAL: i64 = 5;
+/// Chance of stepping on the scale on any given morning
+const WEIGH_IN_PROBABILITY: f64 = 0.8;
+
+/// Kind of athlete the demo data imitates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DemoProfile {
+    #[default]
+    Runner,
+    Cyclist,
+    Casual,
+}
+
+impl DemoProfile {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Runner => "runner",
+            Self::Cyclist => "cyclist",
+            Self::Casual => "casual",
+        }
+    }
+
+    #[must_use]
+    pub fn settings(self) -> ProfileSettings {
+        match self {
+            Self::Runner => ProfileSettings {
+                activities_per_week: 5.0,
+                running_fraction: 0.85,
+                running_speed: (3.0, 4.0),
+                biking_speed: (6.0, 8.0),
+                resting_heart_rate: 50.0,
+                starting_weight: 160.0,
+                weight_change: -4.0,
+                fat_pct: 14.0,
+            },
+            Self::Cyclist => ProfileSettings {
+                activities_per_week: 4.0,
+                running_fraction: 0.2,
+                running_speed: (2.7, 3.4),
+                biking_speed: (7.0, 9.5),
+                resting_heart_rate: 54.0,
+                starting_weight: 170.0,
+                weight_change: -6.0,
+                fat_pct: 16.0,
+            },
+            Self::Casual => ProfileSettings {
+                activities_per_week: 2.0,
+                running_fraction: 0.6,
+                running_speed: (2.4, 3.0),
+                biking_speed: (5.0, 6.5),
+                resting_heart_rate: 64.0,
+                starting_weight: 190.0,
+                weight_change: -10.0,
+                fat_pct: 24.0,
+            },
+        }
+    }
+}
+
+impl fmt::Display for DemoProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for DemoProfile {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "runner" => Ok(Self::Runner),
+            "cyclist" => Ok(Self::Cyclist),
+            "casual" => Ok(Self::Casual),
+            _ => Err(format_err!("Invalid demo profile {s}")),
+        }
+    }
+}
+
+/// What a `DemoProfile` generates, speeds are in meters per second and
+/// masses in lbs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfileSettings {
+    pub activities_per_week: f64,
+    /// Fraction of activities which are runs, the rest are rides
+    pub running_fraction: f64,
+    pub running_speed: (f64, f64),
+    pub biking_speed: (f64, f64),
+    pub resting_heart_rate: f64,
+    pub starting_weight: f64,
+    /// Change in weight over the whole generated period
+    pub weight_change: f64,
+    pub fat_pct: f64,
+}
+
+impl ProfileSettings {
+    fn activity(&self, rng: &mut StdRng, midnight: OffsetDateTime) -> DemoActivity {
+        let begin_datetime =
+            midnight + Duration::hours(7) + Duration::minutes(rng.gen_range(0..120));
+        if rng.gen_bool(self.running_fraction) {
+            DemoActivity {
+                sport: SportTypes::Running,
+                begin_datetime,
+                number_of_laps: rng.gen_range(2..=10),
+                lap_distance: 1609.344,
+                speed: rng.gen_range(self.running_speed.0..self.running_speed.1),
+                avg_heart_rate: self.resting_heart_rate + rng.gen_range(80.0..105.0),
+            }
+        } else {
+            DemoActivity {
+                sport: SportTypes::Biking,
+                begin_datetime,
+                number_of_laps: rng.gen_range(2..=8),
+                lap_distance: 5000.0,
+                speed: rng.gen_range(self.biking_speed.0..self.biking_speed.1),
+                avg_heart_rate: self.resting_heart_rate + rng.gen_range(65.0..90.0),
+            }
+        }
+    }
+
+    /// Resting overnight, a little higher during the day and the activity's
+    /// average while it's going on
+    fn heartrate_day(
+        &self,
+        rng: &mut StdRng,
+        midnight: OffsetDateTime,
+        activity: Option<&DemoActivity>,
+    ) -> Vec<FitbitHeartRate> {
+        (0..24 * 60 / HEARTRATE_INTERVAL)
+            .map(|index| {
+                let datetime = midnight + Duration::minutes(index * HEARTRATE_INTERVAL);
+                let asleep = !(6..23).contains(&datetime.hour());
+                let value = match activity {
+                    Some(a) if a.begin_datetime <= datetime && datetime < a.end_datetime() => {
+                        a.avg_heart_rate
+                    }
+                    _ if asleep => self.resting_heart_rate - 4.0,
+                    _ => self.resting_heart_rate + 15.0,
+                } + rng.gen_range(-4.0..4.0);
+                FitbitHeartRate {
+                    datetime: datetime.into(),
+                    value: value.round() as i32,
+                }
+            })
+            .collect()
+    }
+
+    fn measurement(
+        &self,
+        rng: &mut StdRng,
+        midnight: OffsetDateTime,
+        trend: f64,
+    ) -> ScaleMeasurement {
+        fn round(x: f64) -> f64 {
+            (x * 10.0).round() / 10.0
+        }
+        let datetime = midnight + Duration::hours(6) + Duration::minutes(rng.gen_range(30..60));
+        let fat_pct = self.fat_pct + 0.3 * trend + rng.gen_range(-0.5..0.5);
+        ScaleMeasurement {
+            id: Uuid::from_u128(rng.gen()),
+            datetime: datetime.into(),
+            mass: round(self.starting_weight + trend + rng.gen_range(-1.0..1.0)),
+            fat_pct: round(fat_pct),
+            water_pct: round(65.0 - 0.5 * fat_pct + rng.gen_range(-0.5..0.5)),
+            muscle_pct: round(45.0 - 0.3 * fat_pct + rng.gen_range(-0.5..0.5)),
+            bone_pct: round(4.0 + rng.gen_range(-0.2..0.2)),
+            connect_primary_key: None,
+        }
+    }
+}
+
+/// Activities, heart rate and scale measurements for a fresh install or the
+/// public demo site, nothing here comes from a real person
+#[derive(Debug, Clone, Default)]
+pub struct DemoDataset {
+    pub activities: Vec<DemoActivity>,
+    pub heartrates: Vec<FitbitHeartRate>,
+    pub measurements: Vec<ScaleMeasurement>,
+}
+
+impl DemoDataset {
+    /// `days` of data ending the day before `end`, the same `profile` and
+    /// `seed` always give the same data
+    #[must_use]
+    pub fn generate(profile: DemoProfile, seed: u64, days: usize, end: OffsetDateTime) -> Self {
+        let settings = profile.settings();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let start = end.replace_time(time!(00:00)) - Duration::days(days as i64);
+        let activity_probability = (settings.activities_per_week / 7.0).min(1.0);
+        let mut dataset = Self::default();
+        for day in 0..days {
+            let midnight = start + Duration::days(day as i64);
+            let activity = if rng.gen_bool(activity_probability) {
+                Some(settings.activity(&mut rng, midnight))
+            } else {
+                None
+            };
+            dataset.heartrates.extend(settings.heartrate_day(
+                &mut rng,
+                midnight,
+                activity.as_ref(),
+            ));
+            if rng.gen_bool(WEIGH_IN_PROBABILITY) {
+                let trend = settings.weight_change * day as f64 / days as f64;
+                dataset
+                    .measurements
+                    .push(settings.measurement(&mut rng, midnight, trend));
+            }
+            dataset.activities.extend(activity);
+        }
+        dataset
+    }
+
+    /// Refuse to load demo data into a db that already holds activities, scale
+    /// measurements or heart rate summaries, demo rows would be
+    /// indistinguishable from real ones
+    /// # Errors
+    /// Return error if the db isn't empty or the query fails
+    pub async fn ensure_empty_database(pool: &PgPool) -> Result<(), Error> {
+        let conn = pool.get().await?;
+        let row = conn
+            .query_one(
+                "
+                    SELECT (SELECT count(*) FROM garmin_summary)
+                         + (SELECT count(*) FROM scale_measurements)
+                         + (SELECT count(*) FROM heartrate_statistics_summary)
+                ",
+                &[],
+            )
+            .await?;
+        let count: i64 = row.try_get(0)?;
+        if count > 0 {
+            return Err(format_err!(
+                "database already has {count} activity, measurement or heart rate \
+                 rows, demo data needs a separate empty database (or use \
+                 --files-only)"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Write a tcx file per activity into `directory`
+    /// # Errors
+    /// Return error if the files can't be written
+    pub async fn write_activities(&self, directory: &Path) -> Result<Vec<PathBuf>, Error> {
+        create_dir_all(directory).await?;
+        let mut paths = Vec::with_capacity(self.activities.len());
+        for activity in &self.activities {
+            let path = directory.join(activity.filename().as_str());
+            write(&path, activity.to_tcx()).await?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Merge the heart rates into the fitbit cache and compute each day's
+    /// statistics summary
+    /// # Errors
+    /// Return error if writing the avro files or db queries fail
+    pub async fn load_heartrates(
+        &self,
+        config: &GarminConfig,
+        pool: &PgPool,
+    ) -> Result<Vec<Date>, Error> {
+        let dates = {
+            let config = config.clone();
+            let heartrates = self.heartrates.clone();
+            spawn_blocking(move || FitbitHeartRate::merge_slice_to_avro(&config, &heartrates))
+                .await??
+        };
+        Provenance::new(DataProvider::Demo, "garmin-rust-cli demo", None)
+            .record_heartrate_dates(pool, &dates)
+            .await?;
+        let futures: FuturesUnordered<_> = dates
+            .iter()
+            .map(|date| async move {
+                FitbitHeartRate::calculate_summary_statistics(config, pool, *date).await?;
+                Ok::<_, Error>(())
+            })
+            .collect();
+        futures.try_collect::<()>().await?;
+        Ok(dates.into_iter().collect())
+    }
+
+    /// Insert the scale measurements, skipping any already in the db
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn load_measurements(&mut self, pool: &PgPool) -> Result<(), Error> {
+        let provenance = Provenance::new(DataProvider::Demo, "garmin-rust-cli demo", None);
+        ScaleMeasurement::merge_updates(self.measurements.iter_mut(), pool, &provenance).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::demo_data::{DemoDataset, DemoProfile};
+
+    #[test]
+    fn test_demo_dataset() {
+        let end = datetime!(2024-06-01 12:00 UTC);
+        let dataset = DemoDataset::generate(DemoProfile::Runner, 3, 365, end);
+        let again = DemoDataset::generate(DemoProfile::Runner, 3, 365, end);
+        assert_eq!(dataset.activities, again.activities);
+        assert_eq!(dataset.heartrates, again.heartrates);
+        assert_eq!(dataset.measurements, again.measurements);
+
+        // about five activities a week, mostly runs
+        assert!((200..320).contains(&dataset.activities.len()));
+        let runs = dataset
+            .activities
+            .iter()
+            .filter(|a| a.sport == SportTypes::Running)
+            .count();
+        assert!(runs > dataset.activities.len() / 2);
+        assert!(dataset.activities.iter().all(|a| a.end_datetime() < end));
+
+        assert_eq!(dataset.heartrates.len(), 365 * 24 * 12);
+        assert!(dataset
+            .heartrates
+            .iter()
+            .all(|h| (40..200).contains(&h.value)));
+        let first = dataset.measurements.first().unwrap();
+        let last = dataset.measurements.last().unwrap();
+        assert!(last.mass < first.mass);
+        assert!(dataset
+            .measurements
+            .iter()
+            .all(|m| (150.0..165.0).contains(&m.mass)));
+
+        let casual = DemoDataset::generate(DemoProfile::Casual, 3, 365, end);
+        assert!(casual.activities.len() < dataset.activities.len());
+        assert_eq!(
+            "cyclist".parse::<DemoProfile>().unwrap(),
+            DemoProfile::Cyclist
+        );
+        assert!("couch".parse::<DemoProfile>().is_err());
+    }
+}
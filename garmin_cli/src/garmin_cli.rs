@@ -1,43 +1,79 @@
 use anyhow::{format_err, Error};
 use futures::{future::try_join_all, TryStreamExt};
 use itertools::Itertools;
-use log::debug;
+use log::{debug, error};
 use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use reqwest::Client;
 use stack_string::{format_sstr, StackString};
 use std::{
     collections::{HashMap, HashSet},
     ffi::OsStr,
-    fs::{copy, rename},
+    fs::{copy, create_dir_all, remove_file, rename},
     path::{Path, PathBuf},
     sync::Arc,
 };
 use stdout_channel::StdoutChannel;
 use tempfile::TempDir;
 use time::Date;
+use time_tz::OffsetDateTimeExt;
 use tokio::task::spawn_blocking;
+use tracing::instrument;
+use url::Url;
 
-use fitbit_lib::fitbit_archive::archive_fitbit_heartrates;
-use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
+use fitbit_lib::{
+    archive_backup::ArchiveBackup, fitbit_archive::archive_fitbit_heartrates,
+    scale_measurement::ScaleMeasurement,
+};
+use garmin_lib::{
+    cache_storage::CacheStorage, date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig,
+    notification::NotifyEvent,
+};
 use garmin_models::{
+    activity_distribution::ActivityDistribution,
+    activity_location::{start_point, ActivityLocation, Location},
+    cache_store::CacheStore,
+    calorie_model::estimate_calories,
+    commute::ActivityCommute,
+    course_difficulty::course_difficulty,
+    elevation_profile::ElevationProfile,
     garmin_correction_lap::{GarminCorrectionLap, GarminCorrectionMap},
     garmin_file,
     garmin_summary::{get_list_of_files_from_db, GarminSummary},
     garmin_sync::GarminSync,
+    heart_rate_zones::{ActivityMaxHeartRate, HeartRateProfiles, HeartRateSuggestion},
+    heartrate_stream::HeartRateStream,
+    milestone::Milestone,
+    notifier::{Notification, Notifier},
+    osm_client::OsmClient,
+    power_curve::PowerCurve,
+    processing_lock::ProcessingLock,
+    provenance::{DataProvider, Provenance},
+    quarantined_file::QuarantinedFile,
+    race_forecast::refresh_race_forecasts,
+    ramp_rate::WeeklyRampRate,
+    route_match::ActivityRoute,
+    split_differential::split_differential,
+    surface_type::{infer_surface, ActivitySurface},
+    sync_status::SyncStatus,
+    threshold_heart_rate::ActivityThresholdEffort,
 };
 use garmin_parser::{
     garmin_parse::{GarminParse, GarminParseTrait},
     garmin_parse_fit::GarminParseFit,
     garmin_parse_gmn::GarminParseGmn,
+    garmin_parse_gpx::GarminParseGpx,
     garmin_parse_tcx::GarminParseTcx,
     garmin_parse_txt::GarminParseTxt,
+    parse_error::GarminParseError,
 };
 use garmin_reports::{
     garmin_constraints::GarminConstraints, garmin_file_report_txt::generate_txt_report,
     garmin_report_options::GarminReportOptions, garmin_summary_report_txt::create_report_query,
 };
 use garmin_utils::{
-    garmin_util::{extract_zip_from_garmin_connect, get_file_list},
+    garmin_util::{extract_zip_from_garmin_connect, get_file_list, get_md5sum},
     pgpool::PgPool,
+    sport_types::SportTypes,
 };
 
 #[derive(Debug, PartialEq, Clone, Eq)]
@@ -128,9 +164,17 @@ impl GarminCli {
     }
 
     /// # Errors
-    /// Return error if `read_corrections_from_db` fails or `get_summary_list`
-    /// fails
+    /// Return error if another process is syncing, `read_corrections_from_db`
+    /// fails or `get_summary_list` fails
+    #[instrument(skip_all)]
     pub async fn proc_everything(&self) -> Result<Vec<StackString>, Error> {
+        let lock = ProcessingLock::try_acquire(&self.pool).await?;
+        let result = self.proc_everything_locked().await;
+        lock.release().await?;
+        result
+    }
+
+    async fn proc_everything_locked(&self) -> Result<Vec<StackString>, Error> {
         let pool = self.get_pool();
         let mut corr_map = GarminCorrectionLap::read_corrections_from_db(&pool).await?;
         corr_map.shrink_to_fit();
@@ -140,14 +184,523 @@ impl GarminCli {
             Ok(Vec::new())
         } else {
             let pool = self.get_pool();
-            GarminSummary::write_summary_to_postgres(&summary_list, &pool)
-                .await
-                .map(|()| Vec::new())
+            let summary_ids =
+                GarminSummary::write_summary_to_postgres(&summary_list, &pool).await?;
+            HeartRateStream::apply_preferred(&pool, &summary_ids).await?;
+            PowerCurve::clear_analyzed(&pool, &summary_ids).await?;
+            ActivityDistribution::clear_analyzed(&pool, &summary_ids).await?;
+            self.sync_power_curves().await?;
+            self.sync_elevation_profiles().await?;
+            self.sync_activity_distributions().await?;
+            self.sync_route_matches().await?;
+            ActivityCommute::detect(&pool).await?;
+            self.sync_max_heart_rates().await?;
+            self.sync_threshold_efforts().await?;
+            let mut output = self.sync_missing_calories().await?;
+            for milestone in Milestone::check_milestones(&pool).await? {
+                let description = milestone.description();
+                let date = milestone.achieved_at.to_offsetdatetime().date();
+                output.push(format_sstr!("milestone: {description}"));
+                let notification = Notification::new(
+                    NotifyEvent::Milestone,
+                    format_sstr!("Milestone: {description}"),
+                    format_sstr!("{description} on {date}"),
+                );
+                output.extend(self.notify(&notification).await);
+            }
+            output.extend(self.check_ramp_rate().await?);
+            output.extend(self.check_heart_rate_zones().await?);
+            Ok(output)
+        }
+    }
+
+    /// Send `notification` through the channels set up in the config unless
+    /// it was sent before, failures are logged and don't fail the caller
+    pub async fn notify(&self, notification: &Notification) -> Vec<StackString> {
+        match Notifier::from_config(&self.config) {
+            Ok(notifier) => notifier.notify_once(&self.pool, notification).await,
+            Err(e) => {
+                error!("invalid notification config: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Alert once per week when the running mileage of the current week
+    /// exceeds `ramp_rate_threshold` times the recent average
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn check_ramp_rate(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let sport = SportTypes::Running;
+        let mut output = Vec::new();
+        for ramp_rate in WeeklyRampRate::get_warnings(
+            &pool,
+            sport,
+            self.config.week_start,
+            self.config.ramp_rate_threshold,
+            1,
+            self.config.exclude_commutes,
+        )
+        .await?
+        {
+            if !ramp_rate.record_alert(&pool, sport).await? {
+                continue;
+            }
+            let description = ramp_rate.description();
+            output.push(format_sstr!("ramp rate: {description}"));
+            let notification = Notification::new(
+                NotifyEvent::RampRate,
+                format_sstr!("Weekly {sport} mileage ramping up"),
+                description,
+            )
+            .with_dedupe_key(format_sstr!("{sport} {}", ramp_rate.week_start));
+            output.extend(self.notify(&notification).await);
+        }
+        Ok(output)
+    }
+
+    /// Alert about sync jobs which haven't succeeded in the last `hours`,
+    /// each job is alerted about at most once per `hours`
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn check_missing_sync(&self, hours: u32) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let mut output = Vec::new();
+        for status in SyncStatus::get_missing(&pool, hours).await? {
+            let last_success = status
+                .last_success
+                .map_or_else(|| "never".into(), StackString::from_display);
+            let mut body = format_sstr!("last success: {last_success}");
+            if let (Some(last_failure), Some(message)) = (status.last_failure, &status.message) {
+                body.push_str(&format_sstr!("\nlast failure: {last_failure} {message}"));
+            }
+            output.push(format_sstr!(
+                "{} sync missing for {hours} hours, {body}",
+                status.job
+            ));
+            let notification = Notification::new(
+                NotifyEvent::MissingSync,
+                format_sstr!("No {} sync in {hours} hours", status.job),
+                body,
+            )
+            .with_dedupe_key(format_sstr!("{} {last_success}", status.job));
+            output.extend(self.notify(&notification).await);
+            SyncStatus::record_alert(&pool, &status.job).await?;
+        }
+        Ok(output)
+    }
+
+    /// Compute and store mean-maximal power and pace curves for activities
+    /// which haven't been analyzed since they were imported
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn sync_power_curves(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (summary_id, filename) in PowerCurve::get_missing_summaries(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            for curve in PowerCurve::from_gfile(summary_id, &gfile) {
+                curve.upsert_db(&pool).await?;
+                output.push(format_sstr!("{filename} {}", curve.metric));
+            }
+            PowerCurve::mark_analyzed(&pool, summary_id).await?;
+        }
+        Ok(output)
+    }
+
+    /// Compute and store cadence and stride length distributions for
+    /// activities which haven't been analyzed yet
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn sync_activity_distributions(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (summary_id, filename) in ActivityDistribution::get_missing_summaries(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            for distribution in ActivityDistribution::from_gfile(summary_id, &gfile) {
+                distribution.upsert_db(&pool).await?;
+                output.push(format_sstr!("{filename} {}", distribution.metric));
+            }
+            ActivityDistribution::mark_analyzed(&pool, summary_id).await?;
+        }
+        Ok(output)
+    }
+
+    /// Compute and store elevation profile sparklines for activities which
+    /// don't have them yet
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn sync_elevation_profiles(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (summary_id, filename) in ElevationProfile::get_missing_summaries(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            ElevationProfile::from_gfile(summary_id, &gfile)
+                .unwrap_or_else(|| ElevationProfile::empty(summary_id))
+                .upsert_db(&pool)
+                .await?;
+            output.push(format_sstr!("{filename} elevation profile"));
+        }
+        Ok(output)
+    }
+
+    /// Match outdoor activities which haven't been matched yet against known
+    /// routes
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn sync_route_matches(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (summary_id, filename) in ActivityRoute::get_missing_summaries(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            ActivityRoute::match_gfile(&pool, summary_id, &gfile)
+                .await?
+                .upsert_db(&pool)
+                .await?;
+            output.push(format_sstr!("{filename} route match"));
+        }
+        Ok(output)
+    }
+
+    /// Store the sustained maximum heart rate of activities which don't have
+    /// one yet
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn sync_max_heart_rates(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (summary_id, filename) in ActivityMaxHeartRate::get_missing_summaries(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            ActivityMaxHeartRate::from_gfile(summary_id, &gfile)
+                .upsert_db(&pool)
+                .await?;
+            output.push(format_sstr!("{filename} max heart rate"));
+        }
+        Ok(output)
+    }
+
+    /// Estimate calories of activities recorded without them, from the
+    /// heart rate or the sport and the weight measured closest before it
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn sync_missing_calories(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let profiles =
+            HeartRateProfiles::read_from_db(&pool, self.config.heart_rate_profile()).await?;
+        let local = DateTimeWrapper::local_tz();
+        let mut output = Vec::new();
+        for summary in GarminSummary::get_missing_calories(&pool).await? {
+            let filename = &summary.filename;
+            let weight =
+                match ScaleMeasurement::get_nearest_before(summary.begin_datetime.into(), &pool)
+                    .await?
+                {
+                    Some(measurement) => measurement.mass_kg(),
+                    None => {
+                        // no weight has been recorded at all, so none of the
+                        // remaining activities can be estimated either
+                        debug!("no weight to estimate calories of {filename}");
+                        break;
+                    }
+                };
+            let date = summary.begin_datetime.to_timezone(local).date();
+            if let Some(calories) = estimate_calories(
+                summary.sport,
+                summary.total_duration,
+                summary.total_distance,
+                summary.avg_heart_rate(),
+                weight,
+                &profiles.profile_on(date),
+            ) {
+                GarminSummary::update_estimated_calories(&pool, summary.id, calories).await?;
+                output.push(format_sstr!("{filename} estimated {calories} kcal"));
+            } else {
+                // don't pick the activity up again on every sync
+                GarminSummary::mark_calories_attempted(&pool, summary.id).await?;
+            }
+        }
+        Ok(output)
+    }
+
+    /// Store the best 20 and 60 minute average heart rate of activities which
+    /// don't have them yet, used to estimate lactate threshold
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn sync_threshold_efforts(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (summary_id, filename) in ActivityThresholdEffort::get_missing_summaries(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            ActivityThresholdEffort::from_gfile(summary_id, &gfile)
+                .upsert_db(&pool)
+                .await?;
+            output.push(format_sstr!("{filename} threshold effort"));
         }
+        Ok(output)
+    }
+
+    /// Suggest new heart rate zones when the observed maximum heart rate
+    /// differs from the one in effect, the change has to be confirmed
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn check_heart_rate_zones(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let suggestion =
+            HeartRateSuggestion::detect(&pool, self.config.heart_rate_profile()).await?;
+        let mut output = Vec::new();
+        if let (Some(suggested), Some(observed)) = (&suggestion.suggested, &suggestion.observed) {
+            output.push(format_sstr!(
+                "suggest max heart rate {:.0} (current {:.0}) from {}, confirm at \
+                 /garmin/heart_rate_zones",
+                suggested.max_heart_rate,
+                suggestion.current.max_heart_rate,
+                observed.filename,
+            ));
+        }
+        Ok(output)
+    }
+
+    /// Compute course difficulty for activities imported before it was
+    /// stored with the summary
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn backfill_course_difficulty(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (id, filename) in GarminSummary::get_missing_course_difficulty(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            if let Some(difficulty) = course_difficulty(&gfile) {
+                GarminSummary::update_course_difficulty(&pool, id, difficulty).await?;
+                output.push(format_sstr!("{filename} {difficulty:0.3}"));
+            }
+        }
+        Ok(output)
+    }
+
+    /// Compute first vs second half split differential for activities
+    /// imported before it was stored with the summary
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn backfill_split_differential(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (id, filename) in GarminSummary::get_missing_split_differential(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            if let Some(differential) = split_differential(&gfile) {
+                GarminSummary::update_split_differential(&pool, id, differential).await?;
+                output.push(format_sstr!("{filename} {differential:0.3}"));
+            }
+        }
+        Ok(output)
+    }
+
+    /// Snapshot the forecast of upcoming races, at most once a day each
+    /// # Errors
+    /// Return error if no forecast endpoint is configured or db queries fail
+    pub async fn sync_race_forecasts(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let endpoint = self
+            .config
+            .forecast_endpoint
+            .as_ref()
+            .ok_or_else(|| format_err!("No forecast endpoint, set FORECAST_ENDPOINT"))?;
+        refresh_race_forecasts(&pool, &Client::new(), endpoint).await
+    }
+
+    /// Classify outdoor activities as road, trail or track from the osm ways
+    /// near their track points
+    /// # Errors
+    /// Return error if db queries or overpass requests fail
+    pub async fn sync_surfaces(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let endpoint = self
+            .config
+            .overpass_endpoint
+            .as_ref()
+            .ok_or_else(|| format_err!("No overpass endpoint"))?;
+        let client = OsmClient::new(self.config.osm_contact.as_deref())?;
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (summary_id, filename) in ActivitySurface::get_missing_summaries(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            let surface =
+                infer_surface(&client, endpoint, &gfile, self.config.surface_sample_points).await?;
+            ActivitySurface {
+                summary_id,
+                surface,
+            }
+            .upsert_db(&pool)
+            .await?;
+            output.push(format_sstr!("{filename} {surface}"));
+        }
+        Ok(output)
+    }
+
+    /// Store the start point and reverse geocoded city, region and country
+    /// of activities which haven't been geocoded yet
+    /// # Errors
+    /// Return error if db queries or geocoding requests fail
+    pub async fn sync_locations(&self) -> Result<Vec<StackString>, Error> {
+        let pool = self.get_pool();
+        let provider = self.config.geocode_provider;
+        let endpoint: Url = match &self.config.geocode_endpoint {
+            Some(endpoint) => endpoint.clone().into(),
+            None => provider.default_endpoint().parse()?,
+        };
+        let client = OsmClient::new(self.config.osm_contact.as_deref())?;
+        let store = CacheStore::avro_cache(&self.config).await;
+        let mut output = Vec::new();
+        for (summary_id, filename) in ActivityLocation::get_missing_summaries(&pool).await? {
+            let gfile = match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {filename} {e}");
+                    continue;
+                }
+            };
+            let start = start_point(&gfile);
+            let location = match start {
+                Some((lat, lon)) => {
+                    Location::get_location(&pool, &client, provider, &endpoint, lat, lon).await?
+                }
+                None => Location::default(),
+            };
+            if let Some(city) = &location.city {
+                output.push(format_sstr!("{filename} {city}"));
+            }
+            ActivityLocation {
+                summary_id,
+                start,
+                location,
+            }
+            .update_db(&pool)
+            .await?;
+        }
+        Ok(output)
+    }
+
+    /// Store the heart rate samples of `filename` (e.g. a chest strap fit
+    /// file) on the activity they overlap, along with the stream recorded by
+    /// the activity itself if it hasn't been stored yet
+    /// # Errors
+    /// Return error if parsing `filename` fails, no activity overlaps it, or
+    /// db queries fail
+    pub async fn import_heartrate_stream(
+        &self,
+        filename: &Path,
+        source: Option<&str>,
+        prefer: bool,
+    ) -> Result<StackString, Error> {
+        let pool = self.get_pool();
+        let stream_file = filename.to_path_buf();
+        let (detected_source, samples) =
+            spawn_blocking(move || GarminParseFit::read_heartrate_samples(&stream_file)).await??;
+        let source = source.unwrap_or(detected_source.as_str());
+        let begin = samples
+            .first()
+            .map(|(t, _)| *t)
+            .ok_or_else(|| format_err!("No heart rate samples in {filename:?}"))?;
+        let summary = GarminSummary::get_by_datetime(&pool, begin)
+            .await?
+            .ok_or_else(|| format_err!("No activity found at {begin}"))?;
+        let existing = HeartRateStream::read_by_summary_id(&pool, summary.id).await?;
+        if !existing.iter().any(|s| s.filename == summary.filename) {
+            let store = CacheStore::avro_cache(&self.config).await;
+            let gfile =
+                garmin_file::GarminFile::read_cached_avro(&store, &summary.filename).await?;
+            let primary = HeartRateStream::from_gfile(summary.id, "watch", &gfile);
+            if primary.source != source && !primary.heart_rates.is_empty() {
+                primary.upsert_db(&pool, None).await?;
+            }
+        }
+        let stream_filename = filename
+            .file_name()
+            .ok_or_else(|| format_err!("filename {filename:?} has no path"))?
+            .to_string_lossy();
+        let stream = HeartRateStream::new(summary.id, source, &stream_filename, &samples);
+        let provenance = Provenance::new(
+            DataProvider::FileImport,
+            "garmin-rust-cli heartrate-stream",
+            Some(&stream_filename),
+        );
+        stream.upsert_db(&pool, Some(&provenance)).await?;
+        if prefer {
+            HeartRateStream::set_preferred(&pool, summary.id, source).await?;
+        }
+        Ok(format_sstr!(
+            "{} {source} {} samples avg {:.1} bpm",
+            summary.filename,
+            stream.heart_rates.len(),
+            stream.avg_heart_rate,
+        ))
     }
 
     /// # Errors
     /// Return error if reading summary list fails
+    #[instrument(skip_all)]
     pub async fn get_summary_list(
         &self,
         corr_map: &HashMap<(DateTimeWrapper, i32), GarminCorrectionLap>,
@@ -164,7 +717,15 @@ impl GarminCli {
                 })
                 .collect::<Result<Vec<_>, Error>>()?,
             Some(GarminCliOptions::All) => {
-                GarminParse::process_all_gps_files(&config.gps_dir, &config.cache_dir, corr_map)?
+                let quarantined = QuarantinedFile::get_filenames(&pg_conn).await?;
+                let files: Vec<_> = get_file_list(&config.gps_dir)
+                    .into_iter()
+                    .filter(|f| {
+                        f.file_name()
+                            .is_none_or(|x| !quarantined.contains(x.to_string_lossy().as_ref()))
+                    })
+                    .collect();
+                self.process_or_quarantine(files, corr_map).await?
             }
             _ => {
                 let cacheset: HashSet<StackString> = get_file_list(&config.cache_dir)
@@ -183,13 +744,19 @@ impl GarminCli {
                     .await?
                     .try_collect()
                     .await?;
+                let quarantined = QuarantinedFile::get_filenames(&pg_conn).await?;
 
-                get_file_list(&config.gps_dir)
+                // avro files in s3 aren't all kept locally
+                let remote_cache = config.cache_storage == CacheStorage::S3;
+                let files: Vec<_> = get_file_list(&config.gps_dir)
                     .into_par_iter()
                     .filter_map(|f| f.file_name().map(|x| x.to_string_lossy().to_string()))
                     .filter_map(|f| {
                         let cachefile = format_sstr!("{f}.avro");
-                        if dbset.contains(f.as_str()) && cacheset.contains(cachefile.as_str()) {
+                        if (dbset.contains(f.as_str())
+                            && (remote_cache || cacheset.contains(cachefile.as_str())))
+                            || quarantined.contains(f.as_str())
+                        {
                             None
                         } else {
                             let gps_path = config.gps_dir.join(&f);
@@ -197,14 +764,136 @@ impl GarminCli {
                             Some(gps_path)
                         }
                     })
-                    .map(|f| GarminParse::process_single_gps_file(&f, &config.cache_dir, corr_map))
-                    .collect::<Result<Vec<_>, Error>>()?
+                    .collect();
+                self.process_or_quarantine(files, corr_map).await?
             }
         };
         gsum_list.shrink_to_fit();
+        self.store_cache_files(&gsum_list).await?;
         Ok(gsum_list)
     }
 
+    /// Upload the avro files of newly processed activities, a no-op unless
+    /// the cache is kept in s3
+    async fn store_cache_files(&self, gsum_list: &[GarminSummary]) -> Result<(), Error> {
+        let store = CacheStore::avro_cache(&self.config).await;
+        for gsum in gsum_list {
+            store.store(&format_sstr!("{}.avro", gsum.filename)).await?;
+        }
+        Ok(())
+    }
+
+    /// Parse `files`, files which fail are moved to `quarantine_dir` instead of
+    /// aborting the whole run
+    async fn process_or_quarantine(
+        &self,
+        files: Vec<PathBuf>,
+        corr_map: &HashMap<(DateTimeWrapper, i32), GarminCorrectionLap>,
+    ) -> Result<Vec<GarminSummary>, Error> {
+        let cache_dir = &self.config.cache_dir;
+        let results: Vec<_> = files
+            .into_par_iter()
+            .map(|f| {
+                let result = GarminParse::process_single_gps_file(&f, cache_dir, corr_map);
+                (f, result)
+            })
+            .collect();
+        let mut gsum_list = Vec::with_capacity(results.len());
+        for (path, result) in results {
+            match result {
+                Ok(gsum) => gsum_list.push(gsum),
+                Err(e) if GarminParseError::should_quarantine(&e) => {
+                    let message = self.quarantine_file(&path, &e).await?;
+                    self.stdout.send(message);
+                }
+                // not the file's fault (io, missing tool), leave it for the next sync
+                Err(e) => {
+                    error!("Failed to process {path:?}: {e}");
+                    self.stdout
+                        .send(format_sstr!("Failed to process {path:?}: {e}"));
+                }
+            }
+        }
+        Ok(gsum_list)
+    }
+
+    /// Move `path` into `quarantine_dir` and record `error` in
+    /// `quarantined_files`
+    /// # Errors
+    /// Return error if moving the file or db query fails
+    pub async fn quarantine_file(&self, path: &Path, error: &Error) -> Result<StackString, Error> {
+        let filename = path
+            .file_name()
+            .ok_or_else(|| format_err!("Failed to split filename {path:?}"))?
+            .to_string_lossy();
+        let quarantine_dir = &self.config.quarantine_dir;
+        if !quarantine_dir.exists() {
+            create_dir_all(quarantine_dir)?;
+        }
+        if let Some(corpus_dir) = &self.config.fuzz_corpus_dir {
+            if let Some(corpus_path) = capture_fuzz_input(corpus_dir, path, error)? {
+                debug!("added {path:?} to fuzz corpus {corpus_path:?}");
+            }
+        }
+        move_file(path, &quarantine_dir.join(filename.as_ref()))?;
+        let error_message = format_sstr!("{error}");
+        QuarantinedFile::new(&filename, &error_message)
+            .upsert_db(&self.pool)
+            .await?;
+        Ok(format_sstr!("Quarantined {filename}: {error_message}"))
+    }
+
+    /// Move a fixed file back from `quarantine_dir` to `gps_dir` and process
+    /// it again, it is quarantined again if it still fails to parse
+    /// # Errors
+    /// Return error if `filename` isn't quarantined, moving the file fails or
+    /// db queries fail
+    pub async fn retry_quarantined(&self, filename: &str) -> Result<StackString, Error> {
+        let entry = QuarantinedFile::get_by_filename(&self.pool, filename)
+            .await?
+            .ok_or_else(|| format_err!("{filename} is not quarantined"))?;
+        let quarantine_path = self.config.quarantine_dir.join(filename);
+        let gps_path = self.config.gps_dir.join(filename);
+        if quarantine_path.exists() {
+            move_file(&quarantine_path, &gps_path)?;
+        } else if !gps_path.exists() {
+            return Err(format_err!("{quarantine_path:?} does not exist"));
+        }
+        let mut corr_map = GarminCorrectionLap::read_corrections_from_db(&self.pool).await?;
+        corr_map.shrink_to_fit();
+        let cache_dir = self.config.cache_dir.clone();
+        let path = gps_path.clone();
+        let result = spawn_blocking(move || {
+            GarminParse::process_single_gps_file(&path, &cache_dir, &corr_map)
+        })
+        .await?;
+        match result {
+            Ok(gsum) => {
+                let gsum_list = [gsum];
+                self.store_cache_files(&gsum_list).await?;
+                GarminSummary::write_summary_to_postgres(&gsum_list, &self.pool).await?;
+                entry.delete_from_db(&self.pool).await?;
+                self.sync_power_curves().await?;
+                self.sync_elevation_profiles().await?;
+                self.sync_activity_distributions().await?;
+                self.sync_route_matches().await?;
+                self.sync_max_heart_rates().await?;
+                self.sync_threshold_efforts().await?;
+                Ok(format_sstr!("Processed {filename}"))
+            }
+            Err(e) if GarminParseError::should_quarantine(&e) => {
+                self.quarantine_file(&gps_path, &e).await
+            }
+            // keep it quarantined until a retry gets past the io / tool error
+            Err(e) => {
+                if gps_path.exists() {
+                    move_file(&gps_path, &quarantine_path)?;
+                }
+                Err(e)
+            }
+        }
+    }
+
     /// # Errors
     /// Return error if `sync_everything` fails
     pub async fn run_bootstrap(&self) -> Result<Vec<StackString>, Error> {
@@ -212,30 +901,54 @@ impl GarminCli {
     }
 
     /// # Errors
-    /// Return error if `sync_dir` fails
+    /// Return error if another process is syncing or `sync_dir` fails
+    #[instrument(skip_all)]
     pub async fn sync_everything(&self) -> Result<Vec<StackString>, Error> {
+        let lock = ProcessingLock::try_acquire(&self.pool).await?;
+        let result = self.sync_everything_locked().await;
+        lock.release().await?;
+        result
+    }
+
+    async fn sync_everything_locked(&self) -> Result<Vec<StackString>, Error> {
         let config = self.get_config();
-        let sdk_config = aws_config::load_from_env().await;
-        let gsync = GarminSync::new(&sdk_config);
+        let mut results = Vec::new();
+        if let Some(gsync) = GarminSync::from_config(config).await {
+            results.extend(self.sync_buckets(&gsync).await?);
+        }
+        results
+            .extend_from_slice(&archive_fitbit_heartrates(&self.config, &self.pool, false).await?);
+        if let Some(backup) = ArchiveBackup::new(&self.config).await {
+            results.extend(backup.backup().await?);
+        }
+        Ok(results)
+    }
 
-        let options = vec![
-            ("Syncing GPS files", &config.gps_dir, &config.gps_bucket),
-            (
+    async fn sync_buckets(&self, gsync: &GarminSync) -> Result<Vec<StackString>, Error> {
+        let config = self.get_config();
+        // with s3 cache storage files are uploaded as they're written and
+        // downloaded on demand, syncing would fill the local directories
+        let local_cache = config.cache_storage == CacheStorage::Local;
+        let mut options = vec![("Syncing GPS files", &config.gps_dir, &config.gps_bucket)];
+        if local_cache {
+            options.push((
                 "Syncing CACHE files",
                 &config.cache_dir,
                 &config.cache_bucket,
-            ),
-            (
-                "Syncing Fitbit Cache",
-                &config.fitbit_cachedir,
-                &config.fitbit_bucket,
-            ),
-            (
+            ));
+        }
+        options.push((
+            "Syncing Fitbit Cache",
+            &config.fitbit_cachedir,
+            &config.fitbit_bucket,
+        ));
+        if local_cache {
+            options.push((
                 "Syncing Fitbit Archive",
                 &config.fitbit_archivedir,
                 &config.fitbit_archive_bucket,
-            ),
-        ];
+            ));
+        }
 
         let futures = options.into_iter().map(|(title, local_dir, s3_bucket)| {
             debug!("{}", title);
@@ -243,10 +956,7 @@ impl GarminCli {
             let gsync = gsync.clone();
             async move { gsync.sync_dir(title, local_dir, s3_bucket, &pool).await }
         });
-        let mut results = try_join_all(futures).await?;
-        results
-            .extend_from_slice(&archive_fitbit_heartrates(&self.config, &self.pool, false).await?);
-        Ok(results)
+        try_join_all(futures).await
     }
 
     pub fn process_pattern<T, U>(config: &GarminConfig, patterns: T) -> GarminRequest
@@ -288,16 +998,12 @@ impl GarminCli {
                     .first()
                     .ok_or_else(|| format_err!("This shouldn't be happening..."))?;
                 debug!("{}", &file_name);
-                let avro_file = self
-                    .get_config()
-                    .cache_dir
-                    .join(file_name.as_str())
-                    .with_extension("avro");
+                let store = CacheStore::avro_cache(config).await;
 
                 let gfile = if let Ok(g) =
-                    garmin_file::GarminFile::read_avro_async(&avro_file).await
+                    garmin_file::GarminFile::read_cached_avro(&store, file_name).await
                 {
-                    debug!("Cached avro file read: {:?}", &avro_file);
+                    debug!("Cached avro file read: {file_name}");
                     g
                 } else {
                     let gps_file = config.gps_dir.join(file_name.as_str());
@@ -310,7 +1016,8 @@ impl GarminCli {
                 };
 
                 debug!("gfile {} {}", gfile.laps.len(), gfile.points.len());
-                self.stdout.send(generate_txt_report(&gfile)?.join("\n"));
+                self.stdout
+                    .send(generate_txt_report(&gfile, options.split_distance)?.join("\n"));
             }
             _ => {
                 debug!("{:?}", options);
@@ -341,31 +1048,66 @@ impl GarminCli {
         check_filename!("tcx", GarminParseTcx::new());
         check_filename!("txt", GarminParseTxt::new());
         check_filename!("gmn", GarminParseGmn::new());
+        check_filename!("gpx", GarminParseGpx::new());
 
         Err(format_err!("Bad filename {:?}", filename))
     }
 
+    /// Some exports bundle several activities into a single tcx, those are
+    /// split into one file per activity. Every input gets its own directory
+    /// under `tmpdir`, inputs sharing a name would otherwise overwrite each
+    /// other's activities
+    fn split_tcx_files(
+        filenames: Vec<(PathBuf, PathBuf)>,
+        tmpdir: &Path,
+        stdout: &StdoutChannel<StackString>,
+    ) -> Result<Vec<(PathBuf, PathBuf)>, Error> {
+        let mut output = Vec::new();
+        for (index, (raw_file, filename)) in filenames.into_iter().enumerate() {
+            if !is_tcx_file(&filename) {
+                output.push((raw_file, filename));
+                continue;
+            }
+            let outdir = tmpdir.join(format_sstr!("split_{index}").as_str());
+            create_dir_all(&outdir)?;
+            let files = GarminParseTcx::split_file(&filename, &outdir)?;
+            if files.len() > 1 {
+                stdout.send(format_sstr!(
+                    "{filename:?} contains {} activities",
+                    files.len()
+                ));
+            }
+            output.extend(files.into_iter().map(|f| (raw_file.clone(), f)));
+        }
+        Ok(output)
+    }
+
     fn process_filenames_sync(
         filenames: Vec<PathBuf>,
         stdout: &StdoutChannel<StackString>,
         config: &GarminConfig,
-    ) -> Result<Vec<DateTimeWrapper>, Error> {
+    ) -> Result<Vec<ImportedFile>, Error> {
         let tempdir = TempDir::with_prefix("garmin_cli")?;
         let ziptmpdir = tempdir.path();
 
-        let mut filenames = filenames
+        let filenames = filenames
             .into_par_iter()
-            .map(|filename| match filename.extension().map(OsStr::to_str) {
-                Some(Some("zip")) => extract_zip_from_garmin_connect(&filename, ziptmpdir),
-                Some(Some("fit" | "tcx" | "txt")) => Ok(filename),
-                _ => Self::transform_file_name(&filename),
+            .map(|raw_file| {
+                let filename = match raw_file.extension().map(OsStr::to_str) {
+                    Some(Some("zip")) => extract_zip_from_garmin_connect(&raw_file, ziptmpdir),
+                    Some(Some("fit" | "tcx" | "txt" | "gpx")) => Ok(raw_file.clone()),
+                    Some(Some("gz")) if is_tcx_file(&raw_file) => Ok(raw_file.clone()),
+                    _ => Self::transform_file_name(&raw_file),
+                }?;
+                Ok((raw_file, filename))
             })
             .collect::<Result<Vec<_>, Error>>()?;
+        let mut filenames = Self::split_tcx_files(filenames, ziptmpdir, stdout)?;
         filenames.shrink_to_fit();
 
         let mut result = filenames
             .into_par_iter()
-            .map(|filename| {
+            .map(|(raw_file, filename)| {
                 assert!(
                     filename.exists(),
                     "No such file {}",
@@ -374,15 +1116,16 @@ impl GarminCli {
                 let suffix = match filename.extension().and_then(OsStr::to_str) {
                     Some("fit") => "fit",
                     Some("tcx") => "tcx",
+                    Some("gz") if is_tcx_file(&filename) => "tcx.gz",
                     Some("txt") => "txt",
                     Some("gmn") => "gmn",
+                    Some("gpx") => "gpx",
                     _ => return Err(format_err!("Bad filename {:?}", filename)),
                 };
                 let gfile = GarminParse::new().with_file(&filename, &HashMap::new())?;
 
-                let outfile = config
-                    .gps_dir
-                    .join(gfile.get_standardized_name(suffix).as_str());
+                let standardized_name = gfile.get_standardized_name(suffix);
+                let outfile = config.gps_dir.join(standardized_name.as_str());
 
                 stdout.send(format_sstr!("{filename:?} {outfile:?}"));
 
@@ -391,7 +1134,11 @@ impl GarminCli {
                 }
 
                 rename(&filename, &outfile).or_else(|_| copy(&filename, &outfile).map(|_| ()))?;
-                Ok(Some(gfile.begin_datetime))
+                Ok(Some(ImportedFile {
+                    begin_datetime: gfile.begin_datetime,
+                    filename: standardized_name,
+                    raw_file,
+                }))
             })
             .filter_map(Result::transpose)
             .collect::<Result<Vec<_>, Error>>()?;
@@ -399,11 +1146,14 @@ impl GarminCli {
         Ok(result)
     }
 
+    /// Move `filenames` into `gps_dir`, recording `provenance` with the
+    /// original file name for each newly imported activity
     /// # Errors
     /// Return error if `process_filenames_sync` fails
     pub async fn process_filenames(
         &self,
         filenames: impl IntoIterator<Item = impl AsRef<Path>>,
+        provenance: &Provenance,
     ) -> Result<Vec<DateTimeWrapper>, Error> {
         let config = self.get_config().clone();
         let stdout = self.stdout.clone();
@@ -415,10 +1165,32 @@ impl GarminCli {
             .collect();
         filenames.shrink_to_fit();
 
-        spawn_blocking(move || Self::process_filenames_sync(filenames, &stdout, &config)).await?
+        let imported =
+            spawn_blocking(move || Self::process_filenames_sync(filenames, &stdout, &config))
+                .await??;
+        let mut result = Vec::with_capacity(imported.len());
+        for file in imported {
+            let raw_file = file.raw_file.file_name().map(OsStr::to_string_lossy);
+            let provenance = Provenance {
+                raw_file: raw_file.as_deref().map(Into::into),
+                ..provenance.clone()
+            };
+            provenance
+                .record_activity(&self.pool, &file.filename)
+                .await?;
+            result.push(file.begin_datetime);
+        }
+        Ok(result)
     }
 }
 
+/// Activity file moved into `gps_dir`
+struct ImportedFile {
+    begin_datetime: DateTimeWrapper,
+    filename: StackString,
+    raw_file: PathBuf,
+}
+
 #[derive(Debug, Default)]
 pub struct GarminRequest {
     pub filter: StackString,
@@ -426,3 +1198,203 @@ pub struct GarminRequest {
     pub options: GarminReportOptions,
     pub constraints: GarminConstraints,
 }
+
+fn move_file(from: &Path, to: &Path) -> Result<(), Error> {
+    if rename(from, to).is_err() {
+        copy(from, to)?;
+        remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// `.tcx` and gzipped `.tcx.gz` files
+fn is_tcx_file(path: &Path) -> bool {
+    let filename = path.to_string_lossy().to_lowercase();
+    filename.ends_with(".tcx") || filename.ends_with(".tcx.gz")
+}
+
+/// Copy `path` into the corpus of the fuzz target for its format when
+/// `error` says the file itself is malformed, gmn files are skipped since
+/// their target fuzzes the xml written by `garmin_dump`
+fn capture_fuzz_input(
+    corpus_dir: &Path,
+    path: &Path,
+    error: &Error,
+) -> Result<Option<PathBuf>, Error> {
+    match error.downcast_ref::<GarminParseError>() {
+        Some(e) if e.is_malformed_input() => (),
+        _ => return Ok(None),
+    }
+    let filename = path.to_string_lossy().to_lowercase();
+    let target = if filename.ends_with(".fit") {
+        "parse_fit"
+    } else if is_tcx_file(path) {
+        "parse_tcx"
+    } else if filename.ends_with(".gpx") {
+        "parse_gpx"
+    } else {
+        return Ok(None);
+    };
+    let target_dir = corpus_dir.join(target);
+    if !target_dir.exists() {
+        create_dir_all(&target_dir)?;
+    }
+    let corpus_path = target_dir.join(get_md5sum(path)?.as_str());
+    copy(path, &corpus_path)?;
+    Ok(Some(corpus_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use stack_string::StackString;
+    use std::{
+        collections::{HashMap, HashSet},
+        fs::{copy, create_dir_all, write},
+    };
+    use stdout_channel::StdoutChannel;
+    use time::macros::datetime;
+
+    use garmin_lib::{
+        date_time_wrapper::iso8601::convert_datetime_to_str, garmin_config::GarminConfig,
+    };
+    use garmin_models::{
+        garmin_correction_lap::GarminCorrectionLap, garmin_file::GarminFile,
+        garmin_summary::GarminSummary,
+    };
+    use garmin_parser::{demo_data::DemoActivity, garmin_parse::GarminParse};
+    use garmin_reports::garmin_summary_report_txt::create_report_query;
+    use garmin_utils::{garmin_util::get_file_list, pgpool::PgPool};
+
+    use crate::garmin_cli::{GarminCli, GarminCliOptions};
+
+    /// Combined effort column of the report for `pattern`
+    async fn combined_efforts(
+        config: &GarminConfig,
+        pool: &PgPool,
+        pattern: &[&str],
+    ) -> Result<HashSet<StackString>, Error> {
+        let req = GarminCli::process_pattern(config, pattern);
+        let report = create_report_query(pool, &req.options, &req.constraints).await?;
+        Ok(report
+            .get_text_entries()?
+            .into_iter()
+            .flatten()
+            .filter_map(|(text, _)| {
+                let text = text.trim().strip_suffix(" effort")?;
+                text.split(" / ").nth(1).map(Into::into)
+            })
+            .collect())
+    }
+
+    /// Needs the test database, see `make integration_test`; the fixtures
+    /// are left in the directories of `test.env` for the http tests
+    #[tokio::test]
+    #[ignore]
+    async fn test_pipeline_integration() -> Result<(), Error> {
+        let config = GarminConfig::get_config(Some("../tests/data/test.env"))?;
+        let pool = PgPool::new(&config.pgurl)?;
+        let gps_dir = &config.gps_dir;
+        let cache_dir = &config.cache_dir;
+        create_dir_all(gps_dir)?;
+        create_dir_all(cache_dir)?;
+
+        for fixture in ["test.fit", "test.tcx", "test.gpx"] {
+            copy(format!("../tests/data/{fixture}"), gps_dir.join(fixture))?;
+        }
+        let demo = DemoActivity::generate(7, 3, datetime!(2024-06-01 12:00 UTC));
+        for activity in &demo {
+            write(
+                gps_dir.join(activity.filename().as_str()),
+                activity.to_tcx(),
+            )?;
+        }
+        let gcli = GarminCli {
+            config: config.clone(),
+            opts: Some(GarminCliOptions::FileNames(get_file_list(gps_dir))),
+            pool: pool.clone(),
+            corr: HashMap::new(),
+            parser: GarminParse::new(),
+            stdout: StdoutChannel::new(),
+        };
+
+        // parse and summarize
+        gcli.proc_everything().await?;
+        for activity in &demo {
+            let filename = activity.filename();
+            assert!(cache_dir.join(format!("{filename}.avro")).exists());
+            let summary = GarminSummary::get_by_filename(&pool, &filename)
+                .await?
+                .expect("summary written");
+            assert_eq!(summary.sport, activity.sport);
+        }
+        let gpx = GarminFile::read_avro(&cache_dir.join("test.gpx.avro"))?;
+        assert_eq!(gpx.points.len(), 308);
+        let summary = GarminSummary::get_by_filename(&pool, "test.gpx")
+            .await?
+            .expect("summary written");
+        assert_eq!(
+            convert_datetime_to_str(summary.begin_datetime.into()),
+            "2014-01-12T16:00:05Z"
+        );
+        assert!(summary.total_distance > 0.0);
+        assert!((summary.total_distance - gpx.total_distance).abs() < 1e-6);
+
+        // correct the first lap and reprocess
+        let tcx = GarminSummary::get_by_filename(&pool, "test.tcx")
+            .await?
+            .expect("summary written");
+        let correction = GarminCorrectionLap::new()
+            .with_start_time(tcx.begin_datetime)
+            .with_lap_number(0)
+            .with_distance(10.0);
+        let corr_map = GarminCorrectionLap::map_from_vec(vec![correction]);
+        GarminCorrectionLap::dump_corrections_to_db(&corr_map, &pool).await?;
+        gcli.proc_everything().await?;
+        let corrected = GarminSummary::get_by_filename(&pool, "test.tcx")
+            .await?
+            .expect("summary written");
+        assert!(corrected.total_distance > tcx.total_distance);
+
+        // report over the demo activities
+        let req = GarminCli::process_pattern(&config, ["2024-05", "day"]);
+        let report = create_report_query(&pool, &req.options, &req.constraints).await?;
+        assert!(report.get_text_entries()?.len() >= demo.len());
+
+        // the combined effort of a sport's weekly row covers every sport
+        let all_sports = combined_efforts(&config, &pool, &["2024-05", "week"]).await?;
+        assert!(!all_sports.is_empty());
+        for activity in &demo {
+            let sport = activity.sport.to_str();
+            let single_sport =
+                combined_efforts(&config, &pool, &["2024-05", sport, "week"]).await?;
+            assert!(single_sport.is_subset(&all_sports));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_tcx_files_sharing_a_name() -> Result<(), Error> {
+        let tempdir = tempfile::TempDir::with_prefix("split_tcx")?;
+        let activities = "<TrainingCenterDatabase><Activities>\
+                          <Activity Sport=\"Running\"><Id>a</Id></Activity>\
+                          <Activity Sport=\"Biking\"><Id>b</Id></Activity>\
+                          </Activities></TrainingCenterDatabase>";
+        let mut inputs = Vec::new();
+        for dir in ["first", "second"] {
+            let dir = tempdir.path().join(dir);
+            create_dir_all(&dir)?;
+            let filename = dir.join("export.tcx");
+            write(&filename, activities)?;
+            inputs.push((filename.clone(), filename));
+        }
+        let outdir = tempdir.path().join("out");
+        create_dir_all(&outdir)?;
+        let files = GarminCli::split_tcx_files(inputs, &outdir, &StdoutChannel::new())?;
+        let outputs: HashSet<_> = files.iter().map(|(_, f)| f.clone()).collect();
+        assert_eq!(files.len(), 4);
+        assert_eq!(outputs.len(), 4);
+        assert!(outputs.iter().all(|f| f.exists()));
+        Ok(())
+    }
+}
@@ -1,12 +1,12 @@
-use anyhow::Error;
+use anyhow::{format_err, Error};
 use clap::Parser;
 use futures::{future::try_join_all, TryStreamExt};
 use itertools::Itertools;
-use log::info;
+use log::{debug, info, warn};
 use refinery::embed_migrations;
 use stack_string::{format_sstr, StackString};
 use std::{
-    collections::BTreeSet,
+    collections::{hash_map::Entry, BTreeSet, HashMap},
     ffi::OsStr,
     os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
@@ -15,36 +15,105 @@ use tempfile::TempDir;
 use time::{macros::format_description, Date, Duration, OffsetDateTime};
 use time_tz::OffsetDateTimeExt;
 use tokio::{
-    fs::{metadata, read_to_string, remove_file, write, File},
+    fs::{copy, metadata, read_to_string, remove_file, write, File},
     io::{stdin, stdout, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     task::spawn_blocking,
 };
 
 use derive_more::{From, Into};
 use fitbit_lib::{
+    archive_backup::ArchiveBackup,
     fitbit_archive::{
-        archive_fitbit_heartrates, get_heartrate_values, get_number_of_heartrate_values,
+        archive_fitbit_heartrates, fetch_archive_files, get_heartrate_values,
+        get_number_of_heartrate_values,
     },
     fitbit_heartrate::{import_garmin_heartrate_file, FitbitHeartRate},
+    fitbit_intraday::{archive_intraday_values, FitbitIntradayResponse},
     fitbit_statistics_summary::FitbitStatisticsSummary,
-    scale_measurement::ScaleMeasurement,
+    fitbit_wellness::{merge_fitbit_measurements, FitbitWellnessFile},
+    scale_measurement::{GarminConnectWeightRange, ScaleMeasurement, CONNECT_EXPORT_BATCH},
     GarminConnectHrData,
 };
-use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
+use garmin_lib::{
+    date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig, notification::NotifyEvent,
+    telemetry::Telemetry,
+};
 use garmin_models::{
-    fitbit_activity::FitbitActivity, garmin_connect_activity::GarminConnectActivity,
+    activity_cleanup::{delete_activities, CleanupFilter},
+    biomarker::{parse_datetime, BiomarkerMeasurement, BiomarkerSeries},
+    cache_store::CacheStore,
+    commute::ActivityCommute,
+    coverage_gap::{CoverageGap, GapKind, DEFAULT_MAX_GAP_DAYS, DEFAULT_STREAK_DAYS},
+    device_import::DeviceImport,
+    fitbit_activity::FitbitActivity,
+    garmin_connect_activity::GarminConnectActivity,
+    garmin_connect_activity_detail::GarminConnectActivityDetail,
     garmin_connect_har_file::GarminConnectHarFile,
-    strava_activities_har_file::StravaActivityHarFile, strava_activity::StravaActivity,
+    garmin_connect_wellness::{
+        merge_connect_measurements, GarminConnectBloodPressure, GarminConnectRespiration,
+        GarminConnectSpO2,
+    },
+    garmin_correction_lap::GarminCorrectionLap,
+    garmin_file::GarminFile,
+    garmin_summary::GarminSummary,
+    garmin_sync::GarminSync,
+    heart_rate_zones::{
+        current_heart_rate_profile, local_today, HeartRateProfiles, HeartRateSetting,
+    },
+    heartrate_stream::HeartRateStream,
+    legacy_corrections::{read_legacy_corrections, CorrectionConflict, LegacyImport},
+    notifier::Notification,
+    power_threshold::PowerThreshold,
+    processing_lock::LockHolder,
+    provenance::{DataProvider, Provenance},
+    strava_activities_har_file::StravaActivityHarFile,
+    strava_activity::StravaActivity,
+    strava_title::generate_title,
+    sync_status::{SyncStatus, ACTIVITY_JOB, HEARTRATE_JOB},
+};
+use garmin_parser::{
+    garmin_export_fit::garmin_file_to_fit,
+    garmin_file_diff::{GarminFileDiff, DEFAULT_DIFF_TOLERANCE, DEFAULT_MAX_ENTRY_DIFFS},
+    garmin_parse::{GarminParse, GarminParseTrait},
+};
+use garmin_reports::pace_planner::{
+    course_from_gfile, course_from_gpx, plan_splits, splits_to_fit_workout, splits_to_text,
+    SplitUnit,
+};
+use garmin_utils::{
+    custom_sport::{load_custom_sports, CustomSport, SportAlias, SportStyle},
+    garmin_util::{
+        convert_time_string, extract_zip_from_garmin_connect_multiple, parse_race_distance,
+        print_h_m_s, METERS_PER_MILE,
+    },
+    pgpool::PgPool,
+};
+use race_result_analysis::{
+    race_result_import::{import_race_results, parse_race_result_csv, RaceResultImportOptions},
+    race_results::RaceResults,
+    race_type::RaceType,
 };
-use garmin_utils::{garmin_util::extract_zip_from_garmin_connect_multiple, pgpool::PgPool};
-use race_result_analysis::{race_results::RaceResults, race_type::RaceType};
 use std::str::FromStr;
-use strava_lib::strava_client::StravaClient;
+use strava_lib::strava_client::{StravaClient, StravaSyncResult, DEFAULT_ACCOUNT};
 
-use crate::garmin_cli::{GarminCli, GarminCliOptions};
+use crate::{
+    demo_data::{DemoDataset, DemoProfile},
+    garmin_cli::{GarminCli, GarminCliOptions},
+    garmin_device::{get_import_filename, GarminDevice},
+    garmin_prune::prune_files,
+};
 
 embed_migrations!("../migrations");
 
+/// Apply the migrations embedded in the binary which haven't been run yet
+/// # Errors
+/// Return error if db connection or a migration fails
+pub async fn run_migrations(pool: &PgPool) -> Result<(), Error> {
+    let mut client = pool.get().await?;
+    migrations::runner().run_async(&mut **client).await?;
+    Ok(())
+}
+
 #[derive(Into, From, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct DateType(Date);
 
@@ -78,7 +147,16 @@ pub enum GarminCliOpts {
     /// `~/Downloads/garmin_connect/activities.json`, Next navigate to `<https://connect.garmin.com/modern/daily-summary/{date}>` where date is a date e.g. 2022-12-20,
     /// find the entry `<https://connect.garmin.com/wellness-service/wellness/dailyHeartRate/ddboline?date=2022-12-18>`,
     /// go to the response subtab and copy the output to
-    /// `~/Downloads/garmin_connect/heartrates.json`
+    /// `~/Downloads/garmin_connect/heartrates.json`.
+    /// Pulse ox, blood pressure and respiration are only read from a saved
+    /// `~/Downloads/connect.garmin.com.har` which includes the
+    /// `wellness-service/wellness/daily/spo2/{date}`,
+    /// `wellness-service/wellness/daily/respiration/{date}` and
+    /// `bloodpressure-service/bloodpressure/range/{start}/{end}` entries.
+    /// Weather, splits, device and gear of an activity come from the
+    /// `activity-service/activity/{id}`, `.../{id}/weather`, `.../{id}/splits`
+    /// and `gear-service/gear/filterGear?activityId={id}` entries, which
+    /// connect requests when the activity page is opened
     Connect {
         #[clap(short, long)]
         data_directory: Option<PathBuf>,
@@ -88,12 +166,22 @@ pub enum GarminCliOpts {
         end_date: Option<DateType>,
     },
     Sync,
-    Strava,
+    /// Pull activities from every linked Strava account with sync enabled,
+    /// with `dry_run` list the activities which would be created, updated
+    /// or linked without writing, with `verbose` also list the changed
+    /// fields
+    Strava {
+        #[clap(long)]
+        dry_run: bool,
+        #[clap(short, long)]
+        verbose: bool,
+    },
     Import {
         #[clap(short, long)]
         /// table: allowed values: ['scale_measurements', 'strava_activities',
         /// 'fitbit_activities', 'garmin_connect_activities',
-        /// 'race_results', 'heartrate_statistics_summary']
+        /// 'race_results', 'heartrate_statistics_summary', 'custom_sports',
+        /// 'sport_aliases', 'sport_styles', 'power_thresholds']
         table: StackString,
         #[clap(short, long)]
         filepath: Option<PathBuf>,
@@ -102,7 +190,8 @@ pub enum GarminCliOpts {
         #[clap(short, long)]
         /// table: allowed values: ['scale_measurements', 'strava_activities',
         /// 'fitbit_activities', 'garmin_connect_activities',
-        /// 'race_results', 'heartrate_statistics_summary']
+        /// 'race_results', 'heartrate_statistics_summary', 'custom_sports',
+        /// 'sport_aliases', 'sport_styles', 'power_thresholds']
         table: StackString,
         #[clap(short, long)]
         filepath: Option<PathBuf>,
@@ -115,6 +204,299 @@ pub enum GarminCliOpts {
         #[clap(short, long)]
         all: bool,
     },
+    /// Store the heart rate stream of a separate device (e.g. a chest strap
+    /// fit file) on the activity it overlaps
+    #[clap(alias = "hr-stream")]
+    HeartrateStream {
+        #[clap(short, long)]
+        filename: PathBuf,
+        /// Name of the stream, detected from the fit file if omitted
+        #[clap(short, long)]
+        source: Option<StackString>,
+        /// Use this stream for summary heart rate metrics
+        #[clap(short, long)]
+        prefer: bool,
+    },
+    /// Choose which stored heart rate stream of an activity feeds summary
+    /// heart rate metrics
+    #[clap(alias = "hr-prefer")]
+    PreferHeartrateStream {
+        /// Activity filename
+        #[clap(short, long)]
+        filename: StackString,
+        #[clap(short, long)]
+        source: StackString,
+    },
+    /// Flag an activity as a commute (or with `unset` as not one),
+    /// overriding the detection from Strava tags and repeated routes
+    Commute {
+        /// Activity filename
+        #[clap(short, long)]
+        filename: StackString,
+        #[clap(short, long)]
+        unset: bool,
+    },
+    /// Set FTP (watts) and W' (joules) used for W' balance from `date`
+    /// (default today) onwards
+    #[clap(alias = "ftp")]
+    PowerThreshold {
+        #[clap(short, long)]
+        ftp: u32,
+        #[clap(short, long)]
+        w_prime: u32,
+        #[clap(short, long)]
+        date: Option<DateType>,
+    },
+    /// Confirm the maximum (and optionally resting) heart rate used for heart
+    /// rate zones from `date` (default today) onwards
+    #[clap(alias = "max-hr")]
+    HeartRateMax {
+        #[clap(short, long)]
+        max_heart_rate: u32,
+        #[clap(short, long)]
+        resting_heart_rate: Option<u32>,
+        #[clap(short, long)]
+        date: Option<DateType>,
+    },
+    /// Parse a GPS file and print how it differs from the cached parse, from
+    /// another GPS file or from an avro dump written by another parser version
+    #[clap(alias = "diff")]
+    ParseDiff {
+        /// Path of the GPS file or its name in the gps directory
+        #[clap(short, long)]
+        filename: StackString,
+        /// GPS file or `.avro` dump to compare against instead of the cache
+        #[clap(short, long)]
+        against: Option<PathBuf>,
+        /// Parse without applying lap corrections
+        #[clap(short, long)]
+        no_corrections: bool,
+        /// Numbers closer than this are equal (default 1e-6)
+        #[clap(short, long)]
+        tolerance: Option<f64>,
+        /// Number of differing laps and points listed (default 10)
+        #[clap(short, long)]
+        max_entries: Option<usize>,
+    },
+    /// Plan grade adjusted race splits, optionally following the elevation
+    /// profile of a recorded activity or a GPX course
+    #[clap(alias = "pace")]
+    PacePlan {
+        /// Race distance, e.g. 10k, half, marathon, 13.1mi
+        #[clap(short, long)]
+        distance: StackString,
+        /// Target finish time as hh:mm:ss
+        #[clap(short, long)]
+        target_time: StackString,
+        /// Filename of a recorded activity to use as the course
+        #[clap(short, long)]
+        course: Option<StackString>,
+        #[clap(short, long)]
+        gpx: Option<PathBuf>,
+        /// Split length: mi (default) or km
+        #[clap(short, long)]
+        units: Option<StackString>,
+        /// Also write the plan as a FIT workout to this path
+        #[clap(short, long)]
+        fit_output: Option<PathBuf>,
+    },
+    /// Write a recorded activity, with its lap corrections applied, back out
+    /// as a FIT file
+    ExportFit {
+        #[clap(short, long)]
+        filename: StackString,
+        /// Output path (defaults to `{stem}_export.fit` in
+        /// `download_directory`)
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Record values of a user defined biomarker series (e.g. hrv, blood
+    /// pressure), either a single value or a csv of `datetime,value` lines
+    Biomarker {
+        #[clap(short, long)]
+        series: StackString,
+        /// Units of the series, stored when the series is created or updated
+        #[clap(short, long)]
+        units: Option<StackString>,
+        #[clap(short, long)]
+        value: Option<StackString>,
+        /// Timestamp (rfc3339) or date of `value`, default now
+        #[clap(short, long)]
+        datetime: Option<StackString>,
+        #[clap(short, long)]
+        csv: Option<PathBuf>,
+    },
+    /// Import fitbit sleep log, spo2 and breathing rate json (api responses
+    /// or data export files) into the wellness biomarker series
+    FitbitWellness {
+        #[clap(short, long)]
+        file: Vec<PathBuf>,
+    },
+    /// Archive fitbit intraday steps and calories json (api responses) in
+    /// monthly parquet files next to the heartrate archive
+    FitbitIntraday {
+        #[clap(short, long)]
+        file: Vec<PathBuf>,
+    },
+    /// Import new fit files from a Garmin watch mounted as usb mass storage
+    /// (detected under /media, /run/media, /mnt or /Volumes if `mount_point`
+    /// isn't given), only files newer than the last import from the same
+    /// device serial are copied
+    #[clap(alias = "device")]
+    DeviceImport {
+        #[clap(short, long)]
+        mount_point: Option<PathBuf>,
+        /// Remove imported files from the watch once they have been synced
+        #[clap(short, long)]
+        delete: bool,
+    },
+    /// List days missing heart rate data and missing activities during a
+    /// streak over the last `days` days (default 365), `backfill` re-runs
+    /// the garmin connect and strava syncs for each gap
+    Gaps {
+        #[clap(short, long)]
+        days: Option<i64>,
+        #[clap(short, long)]
+        backfill: bool,
+    },
+    /// List connect activities linked to a file which have no weather,
+    /// splits or gear yet, opening the pages listed before saving
+    /// `~/Downloads/connect.garmin.com.har` backfills them on the next
+    /// `connect`
+    ConnectDetails,
+    /// Remove near duplicate scale measurements, keeping the one with the
+    /// most body composition values from each group
+    ScaleDedup {
+        /// Only list the duplicates
+        #[clap(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Write scale measurements not yet in Garmin Connect to FIT weight files
+    /// of at most `CONNECT_EXPORT_BATCH` measurements for the connect
+    /// importer, the connect keys are recorded when the weights come back in
+    /// the next `connect` har import, exported measurements are skipped for
+    /// `CONNECT_EXPORT_RETRY_HOURS`
+    ScaleConnectExport {
+        /// Directory the files are written to (defaults to
+        /// `download_directory`)
+        #[clap(short, long)]
+        directory: Option<PathBuf>,
+        /// Only export the oldest `limit` measurements
+        #[clap(short, long)]
+        limit: Option<usize>,
+    },
+    /// Remove raw json, quarantined files and upload sessions past the
+    /// retention periods set in the config
+    Prune {
+        /// Only report which files would be removed
+        #[clap(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Import official results from an Athlinks csv export or a chip timing
+    /// results csv, linking each to the gps activity of the race
+    RaceImport {
+        #[clap(short, long)]
+        file: PathBuf,
+        /// Race name for files which don't have one per row
+        #[clap(short = 'n', long)]
+        race_name: Option<StackString>,
+        #[clap(short = 'd', long)]
+        race_date: Option<DateType>,
+        /// Race distance, e.g. 10K, 13.1mi or marathon
+        #[clap(short = 'k', long)]
+        distance: Option<StackString>,
+        /// Only import the row of this finisher
+        #[clap(short, long)]
+        athlete: Option<StackString>,
+    },
+    /// Send an alert through the configured notifiers for each sync job
+    /// without a success in the last `hours` (default `sync_alert_hours`),
+    /// meant to run from cron
+    SyncAlert {
+        #[clap(long)]
+        hours: Option<u32>,
+    },
+    /// Compute course difficulty for activities imported before it was
+    /// stored with the summary
+    CourseDifficulty,
+    /// Compute first vs second half split differential for activities
+    /// imported before it was stored with the summary
+    SplitDifferential,
+    /// Classify unclassified outdoor activities as road, trail or track
+    /// using the configured overpass endpoint
+    SurfaceSync,
+    /// Store today's weather forecast for upcoming races within the
+    /// forecast horizon, meant to run daily
+    RaceForecasts,
+    /// Reverse geocode the start point of activities which don't have a
+    /// location yet
+    Geocode,
+    /// Rename Strava activities from the last `days` (default 7) which still
+    /// have the default name using `strava_title_template`
+    StravaTitles {
+        #[clap(short, long)]
+        days: Option<i64>,
+    },
+    /// List activities matching the filter, with `delete_count` set to the
+    /// number listed by a previous dry run they are deleted along with their
+    /// files and s3 copies
+    Cleanup {
+        #[clap(short, long)]
+        start_date: Option<DateType>,
+        #[clap(short, long)]
+        end_date: Option<DateType>,
+        #[clap(long)]
+        sport: Option<StackString>,
+        #[clap(short, long)]
+        zero_distance: bool,
+        #[clap(long)]
+        delete_count: Option<usize>,
+    },
+    /// Import lap corrections from the python garmin-app (its json file or
+    /// sqlite database), laps with a different existing correction are
+    /// reported and only replaced with `overwrite`
+    ImportCorrections {
+        #[clap(short, long)]
+        file: PathBuf,
+        #[clap(long)]
+        overwrite: bool,
+    },
+    /// Show which process holds the sync lock, `force` terminates its db
+    /// connection to release it
+    SyncLock {
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Generate `days` (default 365) of synthetic activities, heart rate and
+    /// scale measurements for `profile` (runner, cyclist or casual) and load
+    /// them, so a fresh install or the demo site has something to show;
+    /// with `files_only` the tcx files are just written to `directory`
+    /// (default the gps directory)
+    /// Generate demo data, `directory` must be given explicitly and unless
+    /// `files_only` is set the database must be empty
+    DemoData {
+        #[clap(short, long)]
+        directory: PathBuf,
+        #[clap(short, long)]
+        profile: Option<StackString>,
+        #[clap(short = 'n', long)]
+        days: Option<usize>,
+        #[clap(short, long)]
+        seed: Option<u64>,
+        #[clap(long)]
+        files_only: bool,
+    },
+    /// Copy changed heart rate archive partitions to
+    /// `fitbit_archive_backup_bucket` (also done by `sync`)
+    ArchiveBackup,
+    /// Check the archive backup against its manifest
+    ArchiveVerify,
+    /// Download archive partitions missing locally from the backup, `force`
+    /// replaces every partition
+    ArchiveRestore {
+        #[clap(short, long)]
+        force: bool,
+    },
     #[clap(alias = "fit-archive-read")]
     FitbitArchiveRead {
         #[clap(short, long)]
@@ -127,11 +509,14 @@ pub enum GarminCliOpts {
 }
 
 impl GarminCliOpts {
+    /// Parse the arguments first so `--help` and usage errors don't need a
+    /// config, then start telemetry and run the command
     /// # Errors
-    /// Return error if config fails, or `process_opts` fails
+    /// Return error if config or telemetry fails, or `process_opts` fails
     pub async fn process_args() -> Result<(), Error> {
-        let config = GarminConfig::get_config(None)?;
         let opts = Self::parse();
+        let config = GarminConfig::get_config(None)?;
+        let _telemetry = Telemetry::init(&config, "garmin-rust-cli")?;
 
         if opts == Self::SyncAll {
             Self::Connect {
@@ -141,7 +526,12 @@ impl GarminCliOpts {
             }
             .process_opts(&config)
             .await?;
-            Self::Strava.process_opts(&config).await?;
+            Self::Strava {
+                dry_run: false,
+                verbose: false,
+            }
+            .process_opts(&config)
+            .await?;
             Self::Sync.process_opts(&config).await
         } else {
             opts.process_opts(&config).await
@@ -151,6 +541,10 @@ impl GarminCliOpts {
     async fn process_opts(self, config: &GarminConfig) -> Result<(), Error> {
         let pool = PgPool::new(&config.pgurl)?;
 
+        if self != Self::RunMigrations {
+            load_custom_sports(&pool).await?;
+        }
+
         let opts = match self {
             Self::Bootstrap => GarminCliOptions::Bootstrap,
             Self::Proc { filename } => GarminCliOptions::ImportFileNames(filename),
@@ -177,35 +571,50 @@ impl GarminCliOpts {
             Self::SyncAll => {
                 return Ok(());
             }
-            Self::Strava => {
+            Self::Strava { dry_run, verbose } => {
                 let cli = GarminCli::with_config()?;
-                let activity_names = Self::sync_with_strava(&cli)
-                    .await?
-                    .into_iter()
-                    .map(|a| a.name)
-                    .join("\n");
-                cli.stdout.send(activity_names);
+                let result = Self::sync_with_strava(&cli, dry_run).await?;
+                let mut output = if dry_run || verbose {
+                    result
+                        .changes
+                        .iter()
+                        .map(|change| change.describe(verbose))
+                        .join("\n")
+                } else {
+                    result.activities.into_iter().map(|a| a.name).join("\n")
+                };
+                for error in &result.errors {
+                    output.push_str(&format_sstr!("\nfailed {error}"));
+                }
+                cli.stdout.send(output);
                 return cli.stdout.close().await.map_err(Into::into);
             }
             Self::Import { table, filepath } => {
-                let data = if let Some(filepath) = filepath {
-                    read_to_string(&filepath).await?
+                let data = if let Some(filepath) = &filepath {
+                    read_to_string(filepath).await?
                 } else {
                     let mut stdin = stdin();
                     let mut buf = String::new();
                     stdin.read_to_string(&mut buf).await?;
                     buf
                 };
+                let raw_file = filepath.as_ref().map(|p| p.to_string_lossy());
+                let provenance = Provenance::new(
+                    DataProvider::FileImport,
+                    "garmin-rust-cli import",
+                    raw_file.as_deref(),
+                );
                 match table.as_str() {
                     "scale_measurements" => {
                         let mut measurements: Vec<ScaleMeasurement> = serde_json::from_str(&data)?;
-                        ScaleMeasurement::merge_updates(&mut measurements, &pool).await?;
+                        ScaleMeasurement::merge_updates(&mut measurements, &pool, &provenance)
+                            .await?;
                         let s = format_sstr!("scale_measurements {}\n", measurements.len());
                         stdout().write_all(s.as_bytes()).await?;
                     }
                     "strava_activities" => {
                         let activities: Vec<StravaActivity> = serde_json::from_str(&data)?;
-                        StravaActivity::upsert_activities(&activities, &pool).await?;
+                        StravaActivity::upsert_activities(&activities, &pool, &provenance).await?;
                         StravaActivity::fix_summary_id_in_db(&pool).await?;
                         let s = format_sstr!("strava_activities {}\n", activities.len());
                         stdout().write_all(s.as_bytes()).await?;
@@ -232,7 +641,8 @@ impl GarminCliOpts {
                     }
                     "garmin_connect_activities" => {
                         let activities: Vec<GarminConnectActivity> = serde_json::from_str(&data)?;
-                        GarminConnectActivity::upsert_activities(&activities, &pool).await?;
+                        GarminConnectActivity::upsert_activities(&activities, &pool, &provenance)
+                            .await?;
                         GarminConnectActivity::fix_summary_id_in_db(&pool).await?;
                         let s = format_sstr!("garmin_connect_activities {}\n", activities.len());
                         stdout().write_all(s.as_bytes()).await?;
@@ -250,6 +660,38 @@ impl GarminCliOpts {
                         let s = format_sstr!("race_results {}\n", results?.len());
                         stdout().write_all(s.as_bytes()).await?;
                     }
+                    "custom_sports" => {
+                        let sports: Vec<CustomSport> = serde_json::from_str(&data)?;
+                        for sport in &sports {
+                            sport.upsert_db(&pool).await?;
+                        }
+                        let s = format_sstr!("custom_sports {}\n", sports.len());
+                        stdout().write_all(s.as_bytes()).await?;
+                    }
+                    "sport_aliases" => {
+                        let aliases: Vec<SportAlias> = serde_json::from_str(&data)?;
+                        for alias in &aliases {
+                            alias.upsert_db(&pool).await?;
+                        }
+                        let s = format_sstr!("sport_aliases {}\n", aliases.len());
+                        stdout().write_all(s.as_bytes()).await?;
+                    }
+                    "sport_styles" => {
+                        let styles: Vec<SportStyle> = serde_json::from_str(&data)?;
+                        for style in &styles {
+                            style.upsert_db(&pool).await?;
+                        }
+                        let s = format_sstr!("sport_styles {}\n", styles.len());
+                        stdout().write_all(s.as_bytes()).await?;
+                    }
+                    "power_thresholds" => {
+                        let thresholds: Vec<PowerThreshold> = serde_json::from_str(&data)?;
+                        for threshold in &thresholds {
+                            threshold.upsert_db(&pool).await?;
+                        }
+                        let s = format_sstr!("power_thresholds {}\n", thresholds.len());
+                        stdout().write_all(s.as_bytes()).await?;
+                    }
                     _ => {}
                 }
                 return Ok(());
@@ -346,14 +788,33 @@ impl GarminCliOpts {
                         let v = serde_json::to_vec(&results)?;
                         file.write_all(&v).await?;
                     }
+                    "custom_sports" => {
+                        let sports = CustomSport::read_from_db(&pool).await?;
+                        let v = serde_json::to_vec(&sports)?;
+                        file.write_all(&v).await?;
+                    }
+                    "sport_aliases" => {
+                        let aliases = SportAlias::read_from_db(&pool).await?;
+                        let v = serde_json::to_vec(&aliases)?;
+                        file.write_all(&v).await?;
+                    }
+                    "sport_styles" => {
+                        let styles = SportStyle::read_from_db(&pool).await?;
+                        let v = serde_json::to_vec(&styles)?;
+                        file.write_all(&v).await?;
+                    }
+                    "power_thresholds" => {
+                        let thresholds = PowerThreshold::read_from_db(&pool).await?;
+                        let v = serde_json::to_vec(&thresholds)?;
+                        file.write_all(&v).await?;
+                    }
                     _ => {}
                 }
 
                 return Ok(());
             }
             Self::RunMigrations => {
-                let mut client = pool.get().await?;
-                migrations::runner().run_async(&mut **client).await?;
+                run_migrations(&pool).await?;
                 return Ok(());
             }
             Self::FitbitArchive { all } => {
@@ -362,6 +823,641 @@ impl GarminCliOpts {
                 stdout().write_all(b"\n").await?;
                 return Ok(());
             }
+            Self::HeartrateStream {
+                filename,
+                source,
+                prefer,
+            } => {
+                let cli = GarminCli::with_config()?;
+                let result = cli
+                    .import_heartrate_stream(&filename, source.as_deref(), prefer)
+                    .await?;
+                cli.stdout.send(result);
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::PreferHeartrateStream { filename, source } => {
+                let summary = GarminSummary::get_by_filename(&pool, &filename)
+                    .await?
+                    .ok_or_else(|| format_err!("No activity {filename}"))?;
+                if !HeartRateStream::set_preferred(&pool, summary.id, &source).await? {
+                    return Err(format_err!("No {source} heart rate stream for {filename}"));
+                }
+                return Ok(());
+            }
+            Self::Commute { filename, unset } => {
+                let summary = GarminSummary::get_by_filename(&pool, &filename)
+                    .await?
+                    .ok_or_else(|| format_err!("No activity {filename}"))?;
+                ActivityCommute::set_manual(&pool, summary.id, !unset).await?;
+                return Ok(());
+            }
+            Self::PowerThreshold { ftp, w_prime, date } => {
+                let effective_date =
+                    date.map_or_else(|| OffsetDateTime::now_utc().date(), Into::into);
+                let threshold = PowerThreshold {
+                    effective_date,
+                    ftp: f64::from(ftp),
+                    w_prime: f64::from(w_prime),
+                };
+                threshold.upsert_db(&pool).await?;
+                return Ok(());
+            }
+            Self::HeartRateMax {
+                max_heart_rate,
+                resting_heart_rate,
+                date,
+            } => {
+                let current =
+                    current_heart_rate_profile(&pool, config.heart_rate_profile()).await?;
+                let effective_date = date.map_or_else(local_today, Into::into);
+                let setting = HeartRateSetting {
+                    effective_date,
+                    max_heart_rate: f64::from(max_heart_rate),
+                    resting_heart_rate: resting_heart_rate
+                        .map_or(current.resting_heart_rate, f64::from),
+                };
+                setting.upsert_db(&pool).await?;
+                return Ok(());
+            }
+            Self::ParseDiff {
+                filename,
+                against,
+                no_corrections,
+                tolerance,
+                max_entries,
+            } => {
+                let gps_path = Path::new(filename.as_str());
+                let gps_path = if gps_path.exists() {
+                    gps_path.to_path_buf()
+                } else {
+                    config.gps_dir.join(filename.as_str())
+                };
+                let corr_map = if no_corrections {
+                    HashMap::new()
+                } else {
+                    GarminCorrectionLap::read_corrections_from_db(&pool).await?
+                };
+                let cached = if against.is_none() {
+                    let name = gps_path
+                        .file_name()
+                        .ok_or_else(|| format_err!("Invalid filename {filename}"))?
+                        .to_string_lossy();
+                    let store = CacheStore::avro_cache(config).await;
+                    Some(GarminFile::read_cached_avro(&store, &name).await?)
+                } else {
+                    None
+                };
+                let (reference, fresh) = spawn_blocking(move || {
+                    let fresh = GarminParse::new().with_file(&gps_path, &corr_map)?;
+                    let reference = match (cached, against) {
+                        (Some(cached), _) => cached,
+                        (None, Some(against))
+                            if against.extension() == Some(OsStr::new("avro")) =>
+                        {
+                            GarminFile::read_avro(&against)?
+                        }
+                        (None, Some(against)) => {
+                            GarminParse::new().with_file(&against, &corr_map)?
+                        }
+                        (None, None) => return Err(format_err!("Nothing to compare against")),
+                    };
+                    Ok::<_, Error>((reference, fresh))
+                })
+                .await??;
+                let diff = GarminFileDiff::compare(
+                    &reference,
+                    &fresh,
+                    tolerance.unwrap_or(DEFAULT_DIFF_TOLERANCE),
+                    max_entries.unwrap_or(DEFAULT_MAX_ENTRY_DIFFS),
+                )?;
+                stdout().write_all(diff.to_string().as_bytes()).await?;
+                return Ok(());
+            }
+            Self::PacePlan {
+                distance,
+                target_time,
+                course,
+                gpx,
+                units,
+                fit_output,
+            } => {
+                let race_distance = parse_race_distance(&distance)?;
+                let target_seconds = convert_time_string(&target_time)?;
+                let unit: SplitUnit = match &units {
+                    Some(units) => units.parse()?,
+                    None => SplitUnit::default(),
+                };
+                let course = if let Some(gpx) = gpx {
+                    course_from_gpx(&read_to_string(&gpx).await?)?
+                } else if let Some(course) = course {
+                    let store = CacheStore::avro_cache(config).await;
+                    course_from_gfile(&GarminFile::read_cached_avro(&store, &course).await?)
+                } else {
+                    Vec::new()
+                };
+                let splits = plan_splits(&course, race_distance, target_seconds, unit);
+                let text = splits_to_text(&splits, unit)?;
+                stdout().write_all(text.as_bytes()).await?;
+                stdout().write_all(b"\n").await?;
+                if let Some(fit_output) = fit_output {
+                    let name = format_sstr!("{distance} {target_time}");
+                    write(&fit_output, splits_to_fit_workout(&name, &splits)).await?;
+                }
+                return Ok(());
+            }
+            Self::ExportFit { filename, output } => {
+                let store = CacheStore::avro_cache(config).await;
+                let gfile = GarminFile::read_cached_avro(&store, &filename).await?;
+                let output = output.unwrap_or_else(|| {
+                    let stem = filename.split('.').next().unwrap_or(filename.as_str());
+                    config
+                        .download_directory
+                        .join(format_sstr!("{stem}_export.fit"))
+                });
+                let fit = spawn_blocking(move || garmin_file_to_fit(&gfile)).await?;
+                write(&output, fit).await?;
+                let s = format_sstr!("{}\n", output.display());
+                stdout().write_all(s.as_bytes()).await?;
+                return Ok(());
+            }
+            Self::Biomarker {
+                series,
+                units,
+                value,
+                datetime,
+                csv,
+            } => {
+                let series = match (BiomarkerSeries::get_by_name(&pool, &series).await?, units) {
+                    (Some(mut existing), Some(units)) => {
+                        existing.units = units;
+                        existing.upsert_db(&pool).await?;
+                        existing
+                    }
+                    (Some(existing), None) => existing,
+                    (None, units) => {
+                        let new_series = BiomarkerSeries::new(
+                            &series,
+                            units.as_ref().map_or("", StackString::as_str),
+                        );
+                        new_series.upsert_db(&pool).await?;
+                        new_series
+                    }
+                };
+                let mut measurements = if let Some(csv) = &csv {
+                    BiomarkerMeasurement::from_csv(&series.name, &read_to_string(&csv).await?)?
+                } else {
+                    Vec::new()
+                };
+                if let Some(value) = value {
+                    let datetime = match datetime {
+                        Some(d) => parse_datetime(&d)?,
+                        None => OffsetDateTime::now_utc(),
+                    };
+                    measurements.push(BiomarkerMeasurement {
+                        series: series.name.clone(),
+                        datetime: datetime.into(),
+                        value: value.parse()?,
+                    });
+                }
+                let raw_file = csv.as_ref().map(|p| p.to_string_lossy());
+                let provenance = Provenance::new(
+                    if raw_file.is_some() {
+                        DataProvider::FileImport
+                    } else {
+                        DataProvider::Manual
+                    },
+                    "garmin-rust-cli biomarker",
+                    raw_file.as_deref(),
+                );
+                let count =
+                    BiomarkerMeasurement::merge_updates(&measurements, &pool, &provenance).await?;
+                let s = format_sstr!("{} {count}\n", series.name);
+                stdout().write_all(s.as_bytes()).await?;
+                return Ok(());
+            }
+            Self::FitbitWellness { file } => {
+                let mut measurements = Vec::new();
+                for path in &file {
+                    let buf = read_to_string(path).await?;
+                    let wellness: FitbitWellnessFile = serde_json::from_str(buf.trim())
+                        .map_err(|e| format_err!("{} {e}", path.to_string_lossy()))?;
+                    measurements.extend(wellness.to_measurements()?);
+                }
+                let raw_file = file.iter().map(|p| p.to_string_lossy()).join(",");
+                let provenance = Provenance::new(
+                    DataProvider::Fitbit,
+                    "garmin-rust-cli fitbit-wellness",
+                    Some(&raw_file),
+                );
+                let count = merge_fitbit_measurements(&pool, &measurements, &provenance).await?;
+                let s = format_sstr!("stored {count} sleep / spo2 / breathing rate values\n");
+                stdout().write_all(s.as_bytes()).await?;
+                return Ok(());
+            }
+            Self::FitbitIntraday { file } => {
+                let mut output = Vec::new();
+                for path in file {
+                    let buf = read_to_string(&path).await?;
+                    let response: FitbitIntradayResponse = serde_json::from_str(buf.trim())
+                        .map_err(|e| format_err!("{} {e}", path.to_string_lossy()))?;
+                    for (kind, values) in response.to_values()? {
+                        output.extend(archive_intraday_values(config, kind, values).await?);
+                    }
+                }
+                stdout().write_all(output.join("\n").as_bytes()).await?;
+                return Ok(());
+            }
+            Self::DeviceImport {
+                mount_point,
+                delete,
+            } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let output = Self::import_from_device(&cli, mount_point.as_deref(), delete).await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::Gaps { days, backfill } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let start_date =
+                    (OffsetDateTime::now_utc() - Duration::days(days.unwrap_or(365))).date();
+                let gaps = CoverageGap::read_from_db(
+                    &cli.pool,
+                    start_date,
+                    DEFAULT_STREAK_DAYS,
+                    DEFAULT_MAX_GAP_DAYS,
+                )
+                .await?;
+                for gap in &gaps {
+                    cli.stdout.send(format_sstr!(
+                        "{} {} {} {} days",
+                        gap.kind,
+                        gap.gap_start,
+                        gap.gap_end,
+                        gap.days()
+                    ));
+                    if backfill {
+                        let output = Self::backfill_gap(&cli, gap).await?;
+                        cli.stdout.send(output.join("\n"));
+                    }
+                }
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::ConnectDetails => {
+                let cli = GarminCli::with_config()?;
+                let activities =
+                    GarminConnectActivityDetail::activities_missing_details(&pool).await?;
+                for activity in &activities {
+                    cli.stdout.send(format_sstr!(
+                        "{} https://connect.garmin.com/modern/activity/{}",
+                        activity.start_time_gmt,
+                        activity.activity_id
+                    ));
+                }
+                cli.stdout.send(format_sstr!(
+                    "{} activities missing details",
+                    activities.len()
+                ));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::ScaleDedup { dry_run } => {
+                let measurements =
+                    ScaleMeasurement::read_from_db(&pool, None, None, None, None).await?;
+                let groups = ScaleMeasurement::find_duplicates(
+                    &measurements,
+                    Duration::minutes(config.scale_duplicate_minutes.into()),
+                    config.scale_duplicate_lbs,
+                );
+                let mut deleted = 0;
+                for group in &groups {
+                    let keep = match ScaleMeasurement::preferred(group) {
+                        Some(keep) => keep.id,
+                        None => continue,
+                    };
+                    for meas in group {
+                        let action = if meas.id == keep { "keep" } else { "delete" };
+                        let s = format_sstr!(
+                            "{action} {} {} {:.1}\n",
+                            meas.id,
+                            meas.datetime,
+                            meas.mass
+                        );
+                        stdout().write_all(s.as_bytes()).await?;
+                    }
+                    if !dry_run {
+                        deleted += ScaleMeasurement::merge_duplicates(group, keep, &pool).await?;
+                    }
+                }
+                let s = format_sstr!("{} groups, deleted {deleted}\n", groups.len());
+                stdout().write_all(s.as_bytes()).await?;
+                return Ok(());
+            }
+            Self::ScaleConnectExport { directory, limit } => {
+                let directory = directory.unwrap_or_else(|| config.download_directory.clone());
+                let measurements = ScaleMeasurement::read_unlinked_connect(&pool, limit).await?;
+                for (idx, batch) in measurements.chunks(CONNECT_EXPORT_BATCH).enumerate() {
+                    let filename = directory.join(format_sstr!("connect_weights_{idx}.fit"));
+                    write(&filename, ScaleMeasurement::to_connect_fit(batch)).await?;
+                    ScaleMeasurement::mark_connect_exported(batch, &pool).await?;
+                    let s = format_sstr!("{} {} measurements\n", filename.display(), batch.len());
+                    stdout().write_all(s.as_bytes()).await?;
+                }
+                return Ok(());
+            }
+            Self::Prune { dry_run } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let output = prune_files(&cli.config, &cli.pool, dry_run).await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::RaceImport {
+                file,
+                race_name,
+                race_date,
+                distance,
+                athlete,
+            } => {
+                let race_distance = match distance {
+                    Some(distance) => Some(parse_race_distance(&distance)? as i32),
+                    None => None,
+                };
+                let options = RaceResultImportOptions {
+                    race_name,
+                    race_date: race_date.map(Into::into),
+                    race_distance,
+                    athlete,
+                };
+                let buf = read_to_string(&file).await?;
+                let results = parse_race_result_csv(&buf, &options)?;
+                for result in import_race_results(&pool, &results).await? {
+                    let linked = if result.race_summary_ids.is_empty() {
+                        "no activity"
+                    } else {
+                        "linked"
+                    };
+                    let s = format_sstr!(
+                        "{} {} {} {} {linked}\n",
+                        result
+                            .race_date
+                            .map_or_else(StackString::new, StackString::from_display),
+                        result.race_name.as_ref().map_or("", StackString::as_str),
+                        result.race_distance,
+                        print_h_m_s(result.race_time, true)?,
+                    );
+                    stdout().write_all(s.as_bytes()).await?;
+                }
+                return Ok(());
+            }
+            Self::SyncAlert { hours } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let hours = hours.unwrap_or(cli.config.sync_alert_hours);
+                let output = cli.check_missing_sync(hours).await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::CourseDifficulty => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let output = cli.backfill_course_difficulty().await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::SplitDifferential => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let output = cli.backfill_split_differential().await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::SurfaceSync => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let output = cli.sync_surfaces().await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::RaceForecasts => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let output = cli.sync_race_forecasts().await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::Geocode => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let output = cli.sync_locations().await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::Cleanup {
+                start_date,
+                end_date,
+                sport,
+                zero_distance,
+                delete_count,
+            } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let filter = CleanupFilter {
+                    start_date: start_date.map(Into::into),
+                    end_date: end_date.map(Into::into),
+                    sport: sport.map(|s| s.parse()).transpose()?,
+                    zero_distance,
+                };
+                let matches = filter.get_matching(&cli.pool).await?;
+                let mut output: Vec<StackString> = matches
+                    .iter()
+                    .map(|s| {
+                        format_sstr!(
+                            "{} {} {} {:.2} mi",
+                            s.filename,
+                            s.begin_datetime,
+                            s.sport,
+                            s.total_distance / METERS_PER_MILE
+                        )
+                    })
+                    .collect();
+                match delete_count {
+                    Some(count) if count == matches.len() => {
+                        let gsync = GarminSync::from_config(&cli.config).await;
+                        output.extend(
+                            delete_activities(&cli.pool, &cli.config, gsync.as_ref(), &matches)
+                                .await?,
+                        );
+                    }
+                    Some(count) => {
+                        return Err(format_err!(
+                            "Dry run listed {count} activities, filter now matches {}",
+                            matches.len()
+                        ));
+                    }
+                    None => output.push(format_sstr!(
+                        "dry run, rerun with --delete-count {} to delete",
+                        matches.len()
+                    )),
+                }
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::ImportCorrections { file, overwrite } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let imported = spawn_blocking(move || read_legacy_corrections(&file)).await??;
+                let existing = GarminCorrectionLap::read_corrections_from_db(&cli.pool).await?;
+                let result = LegacyImport::compare(imported, &existing);
+                let written = result.apply(&cli.pool, overwrite).await?;
+                let mut output: Vec<StackString> = result
+                    .conflicts
+                    .iter()
+                    .map(CorrectionConflict::describe)
+                    .collect();
+                output.push(format_sstr!(
+                    "wrote {written} corrections, {} unchanged, {} conflicts{}",
+                    result.unchanged,
+                    result.conflicts.len(),
+                    if overwrite || result.conflicts.is_empty() {
+                        ""
+                    } else {
+                        " (rerun with --overwrite to replace them)"
+                    }
+                ));
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::StravaTitles { days } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let start_date =
+                    (OffsetDateTime::now_utc() - Duration::days(days.unwrap_or(7))).date();
+                let output = Self::update_strava_titles(&cli, start_date).await?;
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::SyncLock { force } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let holder = if force {
+                    LockHolder::force_unlock(&cli.pool).await?
+                } else {
+                    LockHolder::get(&cli.pool).await?
+                };
+                let output = match holder {
+                    Some(holder) if force => {
+                        format_sstr!("released lock held by {}", holder.describe())
+                    }
+                    Some(holder) => format_sstr!("lock held by {}", holder.describe()),
+                    None => "no sync in progress".into(),
+                };
+                cli.stdout.send(output);
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::DemoData {
+                directory,
+                profile,
+                days,
+                seed,
+                files_only,
+            } => {
+                let profile: DemoProfile =
+                    profile.map(|p| p.parse()).transpose()?.unwrap_or_default();
+                if directory == config.gps_dir {
+                    return Err(format_err!(
+                        "refusing to write demo activities into gps_dir {directory:?}"
+                    ));
+                }
+                if !files_only {
+                    DemoDataset::ensure_empty_database(&pool).await?;
+                }
+                let mut dataset = DemoDataset::generate(
+                    profile,
+                    seed.unwrap_or(0),
+                    days.unwrap_or(365),
+                    OffsetDateTime::now_utc(),
+                );
+                let paths = dataset.write_activities(&directory).await?;
+                let mut output = vec![format_sstr!(
+                    "wrote {} {profile} activities to {directory:?}",
+                    paths.len()
+                )];
+                let cli = GarminCli {
+                    pool: pool.clone(),
+                    config: config.clone(),
+                    opts: Some(GarminCliOptions::FileNames(paths)),
+                    ..GarminCli::with_config()?
+                };
+                if !files_only {
+                    output.extend(cli.proc_everything().await?);
+                    let dates = dataset.load_heartrates(config, &pool).await?;
+                    output.push(format_sstr!("heartrate for {} days", dates.len()));
+                    dataset.load_measurements(&pool).await?;
+                    output.push(format_sstr!(
+                        "{} scale measurements",
+                        dataset.measurements.len()
+                    ));
+                }
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
+            Self::ArchiveBackup | Self::ArchiveVerify | Self::ArchiveRestore { .. } => {
+                let cli = GarminCli {
+                    pool,
+                    config: config.clone(),
+                    ..GarminCli::with_config()?
+                };
+                let backup = ArchiveBackup::new(config)
+                    .await
+                    .ok_or_else(|| format_err!("FITBIT_ARCHIVE_BACKUP_BUCKET not set"))?;
+                let output = match self {
+                    Self::ArchiveVerify => backup.verify().await?,
+                    Self::ArchiveRestore { force } => backup.restore(force).await?,
+                    _ => backup.backup().await?,
+                };
+                cli.stdout.send(output.join("\n"));
+                return cli.stdout.close().await.map_err(Into::into);
+            }
             Self::FitbitArchiveRead {
                 start_date,
                 end_date,
@@ -375,6 +1471,7 @@ impl GarminCliOpts {
                     || (OffsetDateTime::now_utc() + Duration::days(1)).date(),
                     Into::into,
                 );
+                fetch_archive_files(config, start_date, end_date).await?;
                 let count = {
                     let config = config.clone();
                     spawn_blocking(move || {
@@ -416,7 +1513,9 @@ impl GarminCliOpts {
         let results = match cli.get_opts() {
             Some(GarminCliOptions::ImportFileNames(filenames)) => {
                 let filenames = filenames.clone();
-                cli.process_filenames(&filenames).await?;
+                let provenance =
+                    Provenance::new(DataProvider::FileImport, "garmin-rust-cli proc", None);
+                cli.process_filenames(&filenames, &provenance).await?;
                 cli.proc_everything().await
             }
             Some(GarminCliOptions::Bootstrap) => cli.run_bootstrap().await,
@@ -425,27 +1524,166 @@ impl GarminCliOpts {
                 data_directory,
                 start_date,
                 end_date,
-            }) => {
-                let mut buf = cli.proc_everything().await?;
-                let (filenames, input_files, dates) = Self::sync_with_garmin_connect(
-                    cli,
-                    data_directory,
-                    *start_date,
-                    *end_date,
-                    true,
-                )
-                .await?;
-                if !filenames.is_empty() || !input_files.is_empty() || !dates.is_empty() {
-                    buf.extend_from_slice(&cli.sync_everything().await?);
+            }) => Self::connect_sync(cli, data_directory, *start_date, *end_date).await,
+            _ => cli.proc_everything().await,
+        };
+        let results = match results {
+            Ok(results) => results,
+            Err(e) => {
+                if let Some(GarminCliOptions::Sync | GarminCliOptions::Connect { .. }) =
+                    cli.get_opts()
+                {
+                    let message = format_sstr!("{e}");
+                    SyncStatus::record_failure(&cli.pool, ACTIVITY_JOB, &message).await?;
+                    let today = OffsetDateTime::now_utc().date();
+                    let notification = Notification::new(
+                        NotifyEvent::SyncFailure,
+                        "Garmin sync failed",
+                        message.clone(),
+                    )
+                    .with_dedupe_key(format_sstr!("{today} {message}"));
+                    for line in cli.notify(&notification).await {
+                        cli.stdout.send(line);
+                    }
                 }
-                Ok(buf)
+                return Err(e);
             }
-            _ => cli.proc_everything().await,
-        }?;
+        };
         cli.stdout.send(results.join("\n"));
         Ok(())
     }
 
+    /// Process new files and pull from garmin connect, records the sync
+    /// status of activities and (when new data arrived) heart rate
+    /// # Errors
+    /// Return error if processing or the connect sync fails
+    async fn connect_sync(
+        cli: &GarminCli,
+        data_directory: &Option<PathBuf>,
+        start_date: Option<Date>,
+        end_date: Option<Date>,
+    ) -> Result<Vec<StackString>, Error> {
+        let mut buf = cli.proc_everything().await?;
+        let (filenames, input_files, dates) =
+            Self::sync_with_garmin_connect(cli, data_directory, start_date, end_date, true).await?;
+        if !filenames.is_empty() || !input_files.is_empty() || !dates.is_empty() {
+            buf.extend_from_slice(&cli.sync_everything().await?);
+        }
+        SyncStatus::record_success(&cli.pool, ACTIVITY_JOB).await?;
+        if !dates.is_empty() {
+            SyncStatus::record_success(&cli.pool, HEARTRATE_JOB).await?;
+        }
+        Ok(buf)
+    }
+
+    /// Re-run the syncs which could fill `gap`, heart rate comes from the
+    /// garmin connect files for those dates, activities also from strava
+    /// # Errors
+    /// Return error if either sync fails
+    pub async fn backfill_gap(
+        cli: &GarminCli,
+        gap: &CoverageGap,
+    ) -> Result<Vec<StackString>, Error> {
+        let mut output = Vec::new();
+        let (filenames, _, dates) = Self::sync_with_garmin_connect(
+            cli,
+            &None,
+            Some(gap.gap_start),
+            Some(gap.gap_end),
+            true,
+        )
+        .await?;
+        output.push(format_sstr!(
+            "{} {} {} connect: {} activities, {} days of heartrate",
+            gap.kind,
+            gap.gap_start,
+            gap.gap_end,
+            filenames.len(),
+            dates.len()
+        ));
+        if gap.kind == GapKind::Activity {
+            let start_datetime = gap.gap_start.midnight().assume_utc();
+            let end_datetime = (gap.gap_end + Duration::days(1)).midnight().assume_utc();
+            let activities =
+                Self::sync_with_strava_range(cli, Some(start_datetime), Some(end_datetime), false)
+                    .await?
+                    .activities;
+            output.push(format_sstr!(
+                "{} {} {} strava: {} activities",
+                gap.kind,
+                gap.gap_start,
+                gap.gap_end,
+                activities.len()
+            ));
+        }
+        Ok(output)
+    }
+
+    /// Copy new fit files off mounted Garmin devices, import them and record
+    /// the newest file imported per device serial
+    /// # Errors
+    /// Return error if no device is found, copying files fails or importing
+    /// fails
+    pub async fn import_from_device(
+        cli: &GarminCli,
+        mount_point: Option<&Path>,
+        delete: bool,
+    ) -> Result<Vec<StackString>, Error> {
+        let devices = match mount_point {
+            Some(mount_point) => GarminDevice::from_mount_point(mount_point)
+                .into_iter()
+                .collect(),
+            None => GarminDevice::detect(),
+        };
+        if devices.is_empty() {
+            return Err(format_err!("No Garmin device found"));
+        }
+        let mut output = Vec::new();
+        for device in devices {
+            let last_import = DeviceImport::get_by_serial(&cli.pool, &device.serial).await?;
+            let since = last_import.map(|d| d.last_modified.into());
+            let files = device.new_activity_files(since)?;
+            let last_modified = match files.last() {
+                Some((_, modified)) => *modified,
+                None => {
+                    output.push(format_sstr!("{} no new activities", device.serial));
+                    continue;
+                }
+            };
+            let tempdir = TempDir::with_prefix("garmin_device")?;
+            let mut filenames = Vec::with_capacity(files.len());
+            for (path, _) in &files {
+                let filename = tempdir.path().join(get_import_filename(path)?.as_str());
+                copy(path, &filename).await?;
+                filenames.push(filename);
+            }
+            let import_path = format_sstr!("garmin-rust-cli device-import {}", device.serial);
+            let provenance = Provenance::new(DataProvider::Device, &import_path, None);
+            cli.process_filenames(&filenames, &provenance).await?;
+            output.extend(cli.proc_everything().await?);
+            DeviceImport::new(&device.serial, last_modified.into())
+                .upsert_db(&cli.pool)
+                .await?;
+            output.push(format_sstr!(
+                "{} imported {} activities",
+                device.serial,
+                files.len()
+            ));
+            if delete {
+                output.extend(cli.sync_everything().await?);
+                for (path, _) in &files {
+                    remove_file(path).await?;
+                }
+                output.push(format_sstr!(
+                    "{} removed {} activities",
+                    device.serial,
+                    files.len()
+                ));
+            }
+        }
+        Ok(output)
+    }
+
     /// # Errors
     /// Return error if various function fail
     pub async fn sync_with_garmin_connect(
@@ -471,22 +1709,78 @@ impl GarminCliOpts {
         let mut input_files = Vec::new();
         let mut filenames = Vec::new();
         let mut activities = Vec::new();
+        let mut activity_details = Vec::new();
+        let mut activities_provenance = None;
         let mut dates = BTreeSet::new();
         if exists_and_is_not_empty(&har_file).await {
             let buf = read_to_string(&har_file).await?;
             if !buf.is_empty() {
                 let har: GarminConnectHarFile = serde_json::from_str(buf.trim())?;
                 activities = har.get_activities()?;
+                activity_details = har.get_activity_details();
+                let har_path = har_file.to_string_lossy();
+                let provenance = Provenance::new(
+                    DataProvider::GarminConnect,
+                    "garmin-rust-cli connect",
+                    Some(&har_path),
+                );
+                activities_provenance = Some(provenance.clone());
                 for buf in har.get_heartrates() {
                     let hr_values: GarminConnectHrData = serde_json::from_str(buf)?;
                     let hr_values = FitbitHeartRate::from_garmin_connect_hr(&hr_values);
                     let config = cli.config.clone();
-                    dates.extend(
-                        spawn_blocking(move || {
-                            FitbitHeartRate::merge_slice_to_avro(&config, &hr_values)
-                        })
-                        .await??,
-                    );
+                    let new_dates = spawn_blocking(move || {
+                        FitbitHeartRate::merge_slice_to_avro(&config, &hr_values)
+                    })
+                    .await??;
+                    provenance
+                        .record_heartrate_dates(&cli.pool, &new_dates)
+                        .await?;
+                    dates.extend(new_dates);
+                }
+                let mut wellness = Vec::new();
+                // a truncated or unexpected response shouldn't lose the rest of the har
+                for buf in har.get_spo2() {
+                    match serde_json::from_str::<GarminConnectSpO2>(buf) {
+                        Ok(spo2) => wellness.extend(spo2.to_measurements()),
+                        Err(e) => warn!("skipping malformed spo2 entry: {e}"),
+                    }
+                }
+                for buf in har.get_blood_pressures() {
+                    match serde_json::from_str::<GarminConnectBloodPressure>(buf) {
+                        Ok(bp) => wellness.extend(bp.to_measurements()),
+                        Err(e) => warn!("skipping malformed blood pressure entry: {e}"),
+                    }
+                }
+                for buf in har.get_respiration() {
+                    let measurement = serde_json::from_str::<GarminConnectRespiration>(buf)
+                        .map_err(Error::from)
+                        .and_then(|resp| resp.to_measurement());
+                    match measurement {
+                        Ok(measurement) => wellness.extend(measurement),
+                        Err(e) => warn!("skipping malformed respiration entry: {e}"),
+                    }
+                }
+                if !wellness.is_empty() {
+                    let count =
+                        merge_connect_measurements(&cli.pool, &wellness, &provenance).await?;
+                    info!("stored {count} spo2 / blood pressure / respiration values");
+                }
+                let mut weights = Vec::new();
+                for buf in har.get_weights() {
+                    let range: GarminConnectWeightRange = serde_json::from_str(buf)?;
+                    weights.extend(range.to_measurements());
+                }
+                if !weights.is_empty() {
+                    let (linked, inserted) = ScaleMeasurement::merge_connect_weights(
+                        &weights,
+                        &cli.pool,
+                        Duration::minutes(cli.config.scale_duplicate_minutes.into()),
+                        cli.config.scale_duplicate_lbs,
+                        &provenance,
+                    )
+                    .await?;
+                    info!("linked {linked} and inserted {inserted} connect weights");
                 }
                 input_files.push(har_file);
             }
@@ -495,12 +1789,22 @@ impl GarminCliOpts {
             let buf = read_to_string(&activites_json).await?;
             if !buf.is_empty() {
                 activities = serde_json::from_str(buf.trim())?;
+                let json_path = activites_json.to_string_lossy();
+                activities_provenance = Some(Provenance::new(
+                    DataProvider::GarminConnect,
+                    "garmin-rust-cli connect",
+                    Some(&json_path),
+                ));
                 input_files.push(activites_json);
             }
         }
-        if !activities.is_empty() {
+        if let Some(provenance) = activities_provenance
+            .as_ref()
+            .filter(|_| !activities.is_empty())
+        {
             for activity in
-                GarminConnectActivity::merge_new_activities(activities, &cli.pool).await?
+                GarminConnectActivity::merge_new_activities(activities, &cli.pool, provenance)
+                    .await?
             {
                 let filename = cli
                     .config
@@ -511,8 +1815,26 @@ impl GarminCliOpts {
                 }
             }
         }
+        if !activity_details.is_empty() {
+            let mut stored = 0;
+            for (detail, splits) in &activity_details {
+                if detail.upsert_db(&cli.pool, splits).await? {
+                    stored += 1;
+                }
+            }
+            info!(
+                "stored details of {stored} of {} connect activities",
+                activity_details.len()
+            );
+        }
         if exists_and_is_not_empty(&heartrate_json).await {
             let buf = read_to_string(&heartrate_json).await?;
+            let json_path = heartrate_json.to_string_lossy();
+            let provenance = Provenance::new(
+                DataProvider::GarminConnect,
+                "garmin-rust-cli connect",
+                Some(&json_path),
+            );
             for line in buf.split('\n') {
                 if line.is_empty() {
                     continue;
@@ -520,12 +1842,14 @@ impl GarminCliOpts {
                 if let Ok(hr_values) = serde_json::from_str::<GarminConnectHrData>(line) {
                     let hr_values = FitbitHeartRate::from_garmin_connect_hr(&hr_values);
                     let config = cli.config.clone();
-                    dates.extend(
-                        spawn_blocking(move || {
-                            FitbitHeartRate::merge_slice_to_avro(&config, &hr_values)
-                        })
-                        .await??,
-                    );
+                    let new_dates = spawn_blocking(move || {
+                        FitbitHeartRate::merge_slice_to_avro(&config, &hr_values)
+                    })
+                    .await??;
+                    provenance
+                        .record_heartrate_dates(&cli.pool, &new_dates)
+                        .await?;
+                    dates.extend(new_dates);
                 }
             }
             if !buf.is_empty() {
@@ -551,6 +1875,14 @@ impl GarminCliOpts {
                         FitbitHeartRate::merge_slice_to_avro(&config, &hr_values)
                     })
                     .await??;
+                    let raw_file = heartrate_file.to_string_lossy();
+                    Provenance::new(
+                        DataProvider::GarminConnect,
+                        "garmin-rust-cli connect",
+                        Some(&raw_file),
+                    )
+                    .record_heartrate_dates(&cli.pool, &new_dates)
+                    .await?;
                     dates.append(&mut new_dates);
                     remove_file(&heartrate_file).await?;
                 }
@@ -561,6 +1893,12 @@ impl GarminCliOpts {
                     cli.config.download_directory.join(format_sstr!("{date}"))
                 };
                 if connect_wellness_file.exists() {
+                    let wellness_path = connect_wellness_file.to_string_lossy().into_owned();
+                    let provenance = Provenance::new(
+                        DataProvider::GarminConnect,
+                        "garmin-rust-cli connect",
+                        Some(&wellness_path),
+                    );
                     let tempdir = TempDir::with_prefix("garmin_cli_opts")?;
                     let ziptmpdir = tempdir.path().to_path_buf();
                     let wellness_files = spawn_blocking(move || {
@@ -574,6 +1912,9 @@ impl GarminCliOpts {
                         })
                         .await?
                         {
+                            provenance
+                                .record_heartrate_dates(&cli.pool, &new_dates)
+                                .await?;
                             dates.append(&mut new_dates);
                         }
                     }
@@ -589,7 +1930,9 @@ impl GarminCliOpts {
             }
         }
         if !filenames.is_empty() {
-            let datetimes = cli.process_filenames(&filenames).await?;
+            let provenance =
+                Provenance::new(DataProvider::GarminConnect, "garmin-rust-cli connect", None);
+            let datetimes = cli.process_filenames(&filenames, &provenance).await?;
             info!("number of files {}", datetimes.len());
         }
         if !filenames.is_empty() || !input_files.is_empty() {
@@ -612,7 +1955,14 @@ impl GarminCliOpts {
                         a.models.into_iter().map(Into::into).collect()
                     });
                 if !activities.is_empty() {
-                    StravaActivity::upsert_activities(&activities, &cli.pool).await?;
+                    let har_path = har_file.to_string_lossy();
+                    let provenance = Provenance::new(
+                        DataProvider::Strava,
+                        "garmin-rust-cli connect",
+                        Some(&har_path),
+                    );
+                    StravaActivity::upsert_har_activities(&activities, &cli.pool, &provenance)
+                        .await?;
                     StravaActivity::fix_summary_id_in_db(&cli.pool).await?;
                 }
                 input_files.push(har_file);
@@ -641,21 +1991,114 @@ impl GarminCliOpts {
 
     /// # Errors
     /// Return error if various function fail
-    pub async fn sync_with_strava(cli: &GarminCli) -> Result<Vec<StravaActivity>, Error> {
-        let config = cli.config.clone();
+    pub async fn sync_with_strava(
+        cli: &GarminCli,
+        dry_run: bool,
+    ) -> Result<StravaSyncResult, Error> {
         let start_datetime = Some(OffsetDateTime::now_utc() - Duration::days(30));
         let end_datetime = Some(OffsetDateTime::now_utc());
+        Self::sync_with_strava_range(cli, start_datetime, end_datetime, dry_run).await
+    }
 
-        let client = StravaClient::with_auth(config).await?;
-        let activities = client
-            .sync_with_client(start_datetime, end_datetime, &cli.pool)
-            .await?;
+    /// With `dry_run` nothing is written and processing and renaming are
+    /// skipped
+    /// # Errors
+    /// Return error if various function fail
+    pub async fn sync_with_strava_range(
+        cli: &GarminCli,
+        start_datetime: Option<OffsetDateTime>,
+        end_datetime: Option<OffsetDateTime>,
+        dry_run: bool,
+    ) -> Result<StravaSyncResult, Error> {
+        let config = cli.config.clone();
+        let result = StravaClient::sync_all_accounts(
+            config,
+            start_datetime,
+            end_datetime,
+            &cli.pool,
+            dry_run,
+        )
+        .await?;
+        if dry_run {
+            return Ok(result);
+        }
 
-        if !activities.is_empty() {
+        if !result.activities.is_empty() {
             cli.proc_everything().await?;
         }
+        if let Some(start_datetime) = start_datetime {
+            for line in Self::update_strava_titles(cli, start_datetime.date()).await? {
+                info!("{line}");
+            }
+        }
 
-        Ok(activities)
+        Ok(result)
+    }
+
+    /// Rename activities since `start_date` which still carry Strava's
+    /// default name, does nothing unless `strava_title_template` is set
+    /// # Errors
+    /// Return error if db queries or strava api calls fail
+    pub async fn update_strava_titles(
+        cli: &GarminCli,
+        start_date: Date,
+    ) -> Result<Vec<StackString>, Error> {
+        let template = match &cli.config.strava_title_template {
+            Some(template) => template,
+            None => return Ok(Vec::new()),
+        };
+        let activities = StravaActivity::get_default_named(&cli.pool, start_date).await?;
+        if activities.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut clients: HashMap<StackString, StravaClient> = HashMap::new();
+        let profiles =
+            HeartRateProfiles::read_from_db(&cli.pool, cli.config.heart_rate_profile()).await?;
+        let store = CacheStore::avro_cache(&cli.config).await;
+        let mut output = Vec::new();
+        for mut activity in activities {
+            let start_date = activity.start_date.to_offsetdatetime();
+            let summary = match GarminSummary::get_by_datetime(&cli.pool, start_date).await? {
+                Some(summary) => summary,
+                None => continue,
+            };
+            let gfile = match GarminFile::read_cached_avro(&store, &summary.filename).await {
+                Ok(gfile) => gfile,
+                Err(e) => {
+                    debug!("failed to read {} {e}", summary.filename);
+                    continue;
+                }
+            };
+            let begin = start_date.to_timezone(activity.timezone.tz());
+            let profile = profiles.profile_on(begin.date());
+            let title = generate_title(template, &gfile, begin, &profile);
+            // rename through the account the activity was synced from
+            let account = activity
+                .strava_account
+                .clone()
+                .unwrap_or_else(|| DEFAULT_ACCOUNT.into());
+            let client = match clients.entry(account) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let client =
+                        StravaClient::with_auth_account(cli.config.clone(), entry.key()).await?;
+                    entry.insert(client)
+                }
+            };
+            client
+                .update_strava_activity(
+                    activity.id.try_into()?,
+                    &title,
+                    None,
+                    activity.activity_type,
+                    Some(start_date),
+                )
+                .await?;
+            output.push(format_sstr!("{} {} -> {title}", activity.id, activity.name));
+            activity.name = title;
+            activity.update_db(&cli.pool).await?;
+        }
+        Ok(output)
     }
 }
 
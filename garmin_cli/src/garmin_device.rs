@@ -0,0 +1,150 @@
+use anyhow::{format_err, Error};
+use stack_string::{format_sstr, StackString};
+use std::{
+    env,
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+use time::OffsetDateTime;
+
+/// A Garmin watch mounted as usb mass storage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GarminDevice {
+    pub mount_point: PathBuf,
+    pub serial: StackString,
+}
+
+impl GarminDevice {
+    /// Return a device if `mount_point` contains a `GARMIN/Activity`
+    /// directory, the serial number is read from `GARMIN/GarminDevice.xml`
+    /// falling back to the name of the mount point
+    #[must_use]
+    pub fn from_mount_point(mount_point: &Path) -> Option<Self> {
+        if !mount_point.join("GARMIN").join("Activity").is_dir() {
+            return None;
+        }
+        let serial = read_to_string(mount_point.join("GARMIN").join("GarminDevice.xml"))
+            .ok()
+            .and_then(|xml| get_device_id(&xml))
+            .or_else(|| {
+                mount_point
+                    .file_name()
+                    .map(|f| f.to_string_lossy().as_ref().into())
+            })?;
+        Some(Self {
+            mount_point: mount_point.to_path_buf(),
+            serial,
+        })
+    }
+
+    /// Look for mounted devices in the usual removable media directories
+    #[must_use]
+    pub fn detect() -> Vec<Self> {
+        let mut roots = vec![
+            PathBuf::from("/media"),
+            PathBuf::from("/mnt"),
+            PathBuf::from("/Volumes"),
+        ];
+        if let Ok(user) = env::var("USER") {
+            roots.push(Path::new("/media").join(&user));
+            roots.push(Path::new("/run/media").join(&user));
+        }
+        Self::find_devices(&roots)
+    }
+
+    #[must_use]
+    pub fn find_devices(roots: &[PathBuf]) -> Vec<Self> {
+        let mut devices: Vec<_> = roots
+            .iter()
+            .filter_map(|root| read_dir(root).ok())
+            .flat_map(|entries| entries.filter_map(Result::ok))
+            .filter_map(|entry| Self::from_mount_point(&entry.path()))
+            .collect();
+        devices.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+        devices.dedup();
+        devices
+    }
+
+    /// Fit files in `GARMIN/Activity` modified after `since`, oldest first
+    /// # Errors
+    /// Return error if the activity directory can't be read
+    pub fn new_activity_files(
+        &self,
+        since: Option<OffsetDateTime>,
+    ) -> Result<Vec<(PathBuf, OffsetDateTime)>, Error> {
+        let activity_dir = self.mount_point.join("GARMIN").join("Activity");
+        let mut files = Vec::new();
+        for entry in read_dir(&activity_dir)? {
+            let path = entry?.path();
+            let is_fit = path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("fit"));
+            if !is_fit {
+                continue;
+            }
+            let modified: OffsetDateTime = path.metadata()?.modified()?.into();
+            if since.is_none_or(|since| modified > since) {
+                files.push((path, modified));
+            }
+        }
+        files.sort_by_key(|(_, modified)| *modified);
+        files.shrink_to_fit();
+        Ok(files)
+    }
+}
+
+fn get_device_id(xml: &str) -> Option<StackString> {
+    let start = xml.find("<Id>")? + "<Id>".len();
+    let end = start + xml[start..].find("</Id>")?;
+    let id = xml[start..end].trim();
+    if id.is_empty() {
+        None
+    } else {
+        Some(id.into())
+    }
+}
+
+/// Lower case `.fit` filename used when copying `path` off the device
+/// # Errors
+/// Return error if `path` has no filename
+pub fn get_import_filename(path: &Path) -> Result<StackString, Error> {
+    let stem = path
+        .file_stem()
+        .ok_or_else(|| format_err!("{path:?} has no filename"))?
+        .to_string_lossy();
+    Ok(format_sstr!("{stem}.fit"))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+
+    use crate::garmin_device::{get_import_filename, GarminDevice};
+
+    #[test]
+    fn test_find_devices() -> Result<(), Error> {
+        let root = TempDir::with_prefix("garmin_device")?;
+        let mount_point = root.path().join("GARMIN_WATCH");
+        let activity_dir = mount_point.join("GARMIN").join("Activity");
+        create_dir_all(&activity_dir)?;
+        create_dir_all(root.path().join("USB_STICK"))?;
+        write(
+            mount_point.join("GARMIN").join("GarminDevice.xml"),
+            "<Device><Model></Model><Id>3412345678</Id></Device>",
+        )?;
+        write(activity_dir.join("C3A81234.FIT"), b"fit")?;
+        write(activity_dir.join("notes.txt"), b"txt")?;
+
+        let devices = GarminDevice::find_devices(&[root.path().to_path_buf()]);
+        assert_eq!(devices.len(), 1);
+        assert_eq!(&devices[0].serial, "3412345678");
+
+        let files = devices[0].new_activity_files(None)?;
+        assert_eq!(files.len(), 1);
+        assert_eq!(get_import_filename(&files[0].0)?, "C3A81234.fit");
+        assert!(devices[0].new_activity_files(Some(files[0].1))?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::Error;
+use stack_string::{format_sstr, StackString};
+use std::{
+    fs::{read_dir, remove_file},
+    path::{Path, PathBuf},
+};
+use time::{macros::format_description, Date, Duration, OffsetDateTime};
+
+use fitbit_lib::fitbit_statistics_summary::FitbitStatisticsSummary;
+use garmin_lib::garmin_config::GarminConfig;
+use garmin_models::quarantined_file::QuarantinedFile;
+use garmin_utils::pgpool::PgPool;
+
+/// A file past the retention period of `policy`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiredFile {
+    pub policy: &'static str,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+fn get_cutoff(now: OffsetDateTime, retention_days: u32) -> Option<OffsetDateTime> {
+    if retention_days == 0 {
+        None
+    } else {
+        Some(now - Duration::days(retention_days.into()))
+    }
+}
+
+/// Date of a daily heartrate file named `{date}.json`
+fn get_raw_json_date(path: &Path) -> Option<Date> {
+    let stem = path.file_stem()?.to_str()?;
+    Date::parse(stem, format_description!("[year]-[month]-[day]")).ok()
+}
+
+/// Files in `directory` with one of `extensions` last modified before
+/// `cutoff`
+/// # Errors
+/// Return error if `directory` can't be read
+pub fn get_expired_files(
+    policy: &'static str,
+    directory: &Path,
+    extensions: &[&str],
+    cutoff: OffsetDateTime,
+) -> Result<Vec<ExpiredFile>, Error> {
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+        let matches = path
+            .extension()
+            .is_some_and(|e| extensions.iter().any(|ext| e.eq_ignore_ascii_case(ext)));
+        if !matches {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified: OffsetDateTime = metadata.modified()?.into();
+        if modified < cutoff {
+            files.push(ExpiredFile {
+                policy,
+                path,
+                size: metadata.len(),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files.shrink_to_fit();
+    Ok(files)
+}
+
+/// Raw connect json, quarantined files and abandoned upload sessions past
+/// the retention periods in `config`, a retention of 0 days (the default)
+/// keeps files forever
+/// # Errors
+/// Return error if reading directories or db query fails
+pub async fn get_expired(
+    config: &GarminConfig,
+    pool: &PgPool,
+    now: OffsetDateTime,
+) -> Result<Vec<ExpiredFile>, Error> {
+    let mut expired = Vec::new();
+    // only daily heartrate json which made it into the db, `activities.json`
+    // and `heartrates.json` are inputs of the next sync
+    if let Some(cutoff) = get_cutoff(now, config.raw_json_retention_days) {
+        for file in get_expired_files(
+            "raw_json",
+            &config.garmin_connect_import_directory,
+            &["json"],
+            cutoff,
+        )? {
+            let date = match get_raw_json_date(&file.path) {
+                Some(date) => date,
+                None => continue,
+            };
+            if FitbitStatisticsSummary::read_entry(date, pool)
+                .await?
+                .is_some()
+            {
+                expired.push(file);
+            }
+        }
+    }
+    if let Some(cutoff) = get_cutoff(now, config.upload_retention_days) {
+        expired.extend(get_expired_files(
+            "upload",
+            &config.upload_dir,
+            &["json", "part"],
+            cutoff,
+        )?);
+    }
+    // renaming a file keeps its mtime, so use the time it was quarantined
+    if let Some(cutoff) = get_cutoff(now, config.quarantine_retention_days) {
+        for entry in QuarantinedFile::read_from_db(pool).await? {
+            if entry.quarantined_at.to_offsetdatetime() >= cutoff {
+                continue;
+            }
+            let path = config.quarantine_dir.join(entry.filename.as_str());
+            if let Ok(metadata) = path.metadata() {
+                expired.push(ExpiredFile {
+                    policy: "quarantine",
+                    path,
+                    size: metadata.len(),
+                });
+            }
+        }
+    }
+    Ok(expired)
+}
+
+/// Remove expired files, or only report them if `dry_run` is set.
+/// The `quarantined_files` entries are kept so the file isn't imported again
+/// from the gps bucket.
+/// # Errors
+/// Return error if `get_expired` or removing a file fails
+pub async fn prune_files(
+    config: &GarminConfig,
+    pool: &PgPool,
+    dry_run: bool,
+) -> Result<Vec<StackString>, Error> {
+    let expired = get_expired(config, pool, OffsetDateTime::now_utc()).await?;
+    let mut output = Vec::with_capacity(expired.len() + 1);
+    let mut total_size = 0;
+    for file in &expired {
+        if !dry_run {
+            remove_file(&file.path)?;
+        }
+        total_size += file.size;
+        output.push(format_sstr!(
+            "{} {} {} bytes",
+            file.policy,
+            file.path.to_string_lossy(),
+            file.size
+        ));
+    }
+    let action = if dry_run { "would remove" } else { "removed" };
+    output.push(format_sstr!(
+        "{action} {} files, {total_size} bytes",
+        expired.len()
+    ));
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+    use time::{macros::date, Duration, OffsetDateTime};
+
+    use crate::garmin_prune::{get_cutoff, get_expired_files, get_raw_json_date};
+
+    #[test]
+    fn test_get_expired_files() -> Result<(), Error> {
+        let dir = TempDir::with_prefix("garmin_prune")?;
+        write(dir.path().join("2024-01-01.json"), b"{}")?;
+        write(dir.path().join("abc.part"), b"0123")?;
+        write(dir.path().join("notes.txt"), b"txt")?;
+        create_dir_all(dir.path().join("subdir.json"))?;
+
+        let now = OffsetDateTime::now_utc();
+        let expired = get_expired_files(
+            "upload",
+            dir.path(),
+            &["json", "part"],
+            now + Duration::hours(1),
+        )?;
+        assert_eq!(expired.len(), 2);
+        assert_eq!(expired[1].size, 4);
+        assert!(
+            get_expired_files("upload", dir.path(), &["json"], now - Duration::hours(1))?
+                .is_empty()
+        );
+        assert!(
+            get_expired_files("raw_json", &dir.path().join("missing"), &["json"], now)?.is_empty()
+        );
+
+        assert_eq!(
+            get_raw_json_date(&dir.path().join("2024-01-01.json")),
+            Some(date!(2024 - 01 - 01))
+        );
+        assert_eq!(get_raw_json_date(&dir.path().join("activities.json")), None);
+        assert_eq!(get_cutoff(now, 0), None);
+        assert_eq!(get_cutoff(now, 30), Some(now - Duration::days(30)));
+        Ok(())
+    }
+}
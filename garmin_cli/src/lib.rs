@@ -7,5 +7,8 @@
 #![allow(clippy::doc_link_with_quotes)]
 #![allow(clippy::doc_markdown)]
 
+pub mod demo_data;
 pub mod garmin_cli;
 pub mod garmin_cli_opts;
+pub mod garmin_device;
+pub mod garmin_prune;
@@ -21,6 +21,8 @@ use std::{
 use thiserror::Error;
 use tokio::task::JoinError;
 
+use garmin_models::processing_lock::is_processing_in_progress;
+
 use crate::logged_user::LOGIN_HTML;
 
 #[derive(Error, Debug)]
@@ -29,6 +31,8 @@ pub enum ServiceError {
     InternalServerError,
     #[error("BadRequest: {0}")]
     BadRequest(String),
+    #[error("NotFound: {0}")]
+    NotFound(String),
     #[error("Unauthorized")]
     Unauthorized,
     #[error("Anyhow error {0}")]
@@ -85,9 +89,17 @@ pub async fn error_response(err: Rejection) -> Result<Box<dyn Reply>, Infallible
                 code = StatusCode::BAD_REQUEST;
                 message = msg.as_str();
             }
+            ServiceError::NotFound(msg) => {
+                code = StatusCode::NOT_FOUND;
+                message = msg.as_str();
+            }
             ServiceError::Unauthorized => {
                 return Ok(Box::new(login_html()));
             }
+            ServiceError::AnyhowError(e) if is_processing_in_progress(e) => {
+                code = StatusCode::CONFLICT;
+                message = "Sync already in progress, try again once it finishes";
+            }
             _ => {
                 error!("Other error: {:?}", service_err);
                 code = StatusCode::INTERNAL_SERVER_ERROR;
@@ -163,6 +175,10 @@ mod test {
         let resp = error_response(err).await?.into_response();
         assert_eq!(resp.status().as_u16(), 400);
 
+        let err = ServiceError::NotFound("TEST ERROR".into()).into();
+        let resp = error_response(err).await?.into_response();
+        assert_eq!(resp.status().as_u16(), 404);
+
         let err = ServiceError::InternalServerError.into();
         let resp = error_response(err).await?.into_response();
         assert_eq!(resp.status().as_u16(), 500);
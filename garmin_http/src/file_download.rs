@@ -0,0 +1,104 @@
+use rweb::{
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderValue, StatusCode,
+    },
+    openapi::{
+        ComponentDescriptor, ComponentOrInlineSchema, Entity, MediaType, Response, ResponseEntity,
+        Responses,
+    },
+    Reply,
+};
+use stack_string::{format_sstr, StackString};
+use std::borrow::Cow;
+
+use crate::errors::ServiceError as Error;
+
+/// Binary file sent as an attachment so browsers save it as `filename`
+/// instead of displaying it
+pub struct FileDownload {
+    body: Vec<u8>,
+    content_type: &'static str,
+    filename: StackString,
+}
+
+impl FileDownload {
+    #[must_use]
+    pub fn new(body: Vec<u8>, content_type: &'static str, filename: &str) -> Self {
+        Self {
+            body,
+            content_type,
+            filename: filename.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn pdf(body: Vec<u8>, filename: &str) -> Self {
+        Self::new(body, "application/pdf", filename)
+    }
+
+    #[must_use]
+    pub fn png(body: Vec<u8>, filename: &str) -> Self {
+        Self::new(body, "image/png", filename)
+    }
+
+    #[must_use]
+    pub fn fit(body: Vec<u8>, filename: &str) -> Self {
+        Self::new(body, "application/vnd.ant.fit", filename)
+    }
+
+    #[must_use]
+    pub fn gpx(body: Vec<u8>, filename: &str) -> Self {
+        Self::new(body, "application/gpx+xml", filename)
+    }
+}
+
+impl Reply for FileDownload {
+    fn into_response(self) -> rweb::reply::Response {
+        let mut response = rweb::reply::Response::new(self.body.into());
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(self.content_type));
+        let disposition = format_sstr!("attachment; filename=\"{}\"", self.filename);
+        if let Ok(value) = HeaderValue::from_str(&disposition) {
+            headers.insert(CONTENT_DISPOSITION, value);
+        }
+        response
+    }
+}
+
+impl Entity for FileDownload {
+    fn type_name() -> Cow<'static, str> {
+        "binary".into()
+    }
+    fn describe(_: &mut ComponentDescriptor) -> ComponentOrInlineSchema {
+        use rweb::openapi::{self, Schema};
+        ComponentOrInlineSchema::Inline(Schema {
+            schema_type: Some(openapi::Type::String),
+            format: "binary".into(),
+            ..Schema::default()
+        })
+    }
+}
+
+impl ResponseEntity for FileDownload {
+    fn describe_responses(comp_d: &mut ComponentDescriptor) -> Responses {
+        let mut map = Error::describe_responses(comp_d);
+        let mut content = Response::default().content;
+        content.insert(
+            Cow::Borrowed("application/octet-stream"),
+            MediaType {
+                schema: Some(Self::describe(comp_d)),
+                ..MediaType::default()
+            },
+        );
+        map.insert(
+            Cow::Owned(StatusCode::OK.as_str().into()),
+            Response {
+                description: Cow::Borrowed("File Download"),
+                content,
+                ..Response::default()
+            },
+        );
+        map
+    }
+}
@@ -1,48 +1,184 @@
+use anyhow::Error as AnyhowError;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use dioxus::prelude::{
     component, dioxus_elements, rsx, Element, GlobalSignal, IntoDynNode, Props, Readable,
     VirtualDom,
 };
+use futures::{future::ready, stream, Stream, StreamExt};
 use itertools::Itertools;
+use log::error;
 use rweb_helper::DateType;
 use stack_string::{format_sstr, StackString};
 use std::{collections::HashMap, fmt::Write};
 use time::{macros::format_description, Date, Duration, OffsetDateTime};
 use time_tz::OffsetDateTimeExt;
 
-use fitbit_lib::{fitbit_heartrate::FitbitHeartRate, scale_measurement::ScaleMeasurement};
+use fitbit_lib::{
+    calorie_estimate::{GoalProjection, WeeklyCalorieEstimate},
+    fitbit_heartrate::FitbitHeartRate,
+    scale_measurement::ScaleMeasurement,
+};
 use garmin_lib::{
     date_time_wrapper::{iso8601::convert_datetime_to_str, DateTimeWrapper},
     garmin_config::GarminConfig,
+    language::Language,
+    split_distance::SplitDistance,
 };
 use garmin_models::{
-    garmin_connect_activity::GarminConnectActivity, garmin_file::GarminFile,
-    garmin_summary::GarminSummary, strava_activity::StravaActivity,
+    activity_cleanup::CleanupFilter,
+    activity_rpe::{ActivityRpe, RPE_RANGE},
+    admin_stats::AdminStats,
+    biomarker::{BiomarkerMeasurement, BiomarkerSeries},
+    clothing_log::{ClothingLogEntry, ClothingRecommendation, ComfortRating},
+    coverage_gap::CoverageGap,
+    demo_anonymizer::DemoAnonymizer,
+    garmin_connect_activity::GarminConnectActivity,
+    garmin_connect_activity_detail::{GarminConnectActivityDetail, GarminConnectSplit},
+    garmin_file::GarminFile,
+    garmin_summary::GarminSummary,
+    heart_rate_zones::HeartRateSuggestion,
+    heartrate_stream::HeartRateStream,
+    milestone::Milestone,
+    power_curve::{CurveMetric, CurvePeriod},
+    power_threshold::PowerThreshold,
+    quarantined_file::QuarantinedFile,
+    race_forecast::{RaceForecast, UpcomingRace, FORECAST_HORIZON_DAYS},
+    ramp_rate::WeeklyRampRate,
+    rest_days::RestDayStats,
+    route_match::MatchedRoute,
+    split_differential::{format_split_differential, split_differential},
+    stat_source::{ActivityStat, StatChoice, StatDiscrepancy},
+    strava_activity::StravaActivity,
+    strava_link::UnmatchedActivities,
+    sync_status::{SyncStatus, ACTIVITY_JOB, HEARTRATE_JOB, STRAVA_JOB},
+    threshold_heart_rate::ThresholdHistory,
+    training_pattern::TrainingPattern,
+    trip::{Trip, TripStats},
+    yearly_comparison::{ComparisonMetric, YearlyComparison},
 };
 use garmin_reports::{
-    garmin_file_report_txt::get_splits,
-    garmin_summary_report_txt::{GarminReportQuery, HtmlResult},
+    garmin_file_report_txt::get_distance_splits,
+    garmin_summary_report_txt::{GarminReportRow, HtmlResult},
+    interval_workout::{get_intervals, IntervalSource},
+    pace_band::splits_to_pace_band,
+    pace_planner::{splits_to_fit_workout, PlannedSplit, SplitUnit},
+    route_profile::RouteProfile,
+    route_progression::YearBest,
 };
 use garmin_utils::{
-    garmin_util::{print_h_m_s, titlecase, MARATHON_DISTANCE_MI, METERS_PER_MILE},
+    garmin_util::{print_h_m_s, MARATHON_DISTANCE_MI, METERS_PER_MILE},
     pgpool::PgPool,
     plot_graph::{generate_plot_data, ScatterPlotData},
-    sport_types::{get_sport_type_map, SportTypes},
+    plot_opts::{PlotAxis, PlotOpts, PlotSeries, PlotXAxis},
+    sport_types::{get_sport_names, SportTypes},
 };
 use race_result_analysis::{
+    race_attachment::RaceAttachment,
     race_result_analysis::{PlotData, RaceResultAnalysis},
     race_results::RaceResults,
     race_type::RaceType,
 };
-use strava_lib::strava_client::StravaAthlete;
+use strava_lib::strava_client::{StravaAccount, StravaAthlete};
 
 use crate::{
     errors::ServiceError as Error,
-    garmin_file_report_html::{extract_report_objects_from_file, get_plot_opts, ReportObjects},
+    garmin_file_report_html::{
+        add_w_prime_balance, extract_report_objects_from_file, get_plot_opts, ReportObjects,
+    },
+    i18n::tr,
+    logged_user::Session,
     FitbitStatisticsSummary,
 };
 
 const GRAMS_PER_OUNCE: f64 = 28.349_523_125;
 const LBS_PER_KG: f64 = 1_000.0 / (16.0 * GRAMS_PER_OUNCE);
+/// Milestones reached in this many days are shown above the reports
+const MILESTONE_BANNER_DAYS: i64 = 14;
+/// Ramp rate warnings of the current and previous week are shown on the
+/// index page
+const RAMP_RATE_BANNER_WEEKS: i64 = 2;
+/// Report table rows rendered per chunk of the streamed report page
+const REPORT_STREAM_ROWS: usize = 200;
+/// Stands in for the report table rows in the page rendered around them
+const REPORT_ROWS_MARKER: &str = "<!-- garmin report rows -->";
+/// Ends the report table when reading or rendering its rows fails part way
+const REPORT_ERROR_ROW: &str =
+    "<tr><td class=\"report-error\">The report is incomplete, loading it failed</td></tr>";
+
+/// Mean-maximal curves of the current and previous period for
+/// `/garmin/power_curve`
+#[derive(PartialEq, Clone)]
+pub struct PowerCurveOpts {
+    pub metric: CurveMetric,
+    pub sport: SportTypes,
+    pub period: CurvePeriod,
+    pub current: Vec<(i32, f64)>,
+    pub previous: Vec<(i32, f64)>,
+}
+
+/// Most points drawn per route on the trip map
+const TRIP_ROUTE_MAX_POINTS: usize = 500;
+
+/// Gps track of one activity on the trip map
+#[derive(PartialEq, Clone)]
+pub struct TripRoute {
+    pub filename: StackString,
+    pub color: StackString,
+    pub coordinates: Vec<(f64, f64)>,
+}
+
+impl TripRoute {
+    /// Track of `gfile` thinned to at most `TRIP_ROUTE_MAX_POINTS` points,
+    /// `None` without gps data
+    #[must_use]
+    pub fn from_gfile(gfile: &GarminFile) -> Option<Self> {
+        let points: Vec<_> = gfile
+            .points
+            .iter()
+            .filter_map(|p| Some((p.latitude?, p.longitude?)))
+            .collect();
+        if points.is_empty() {
+            return None;
+        }
+        let step = (points.len() + TRIP_ROUTE_MAX_POINTS - 1) / TRIP_ROUTE_MAX_POINTS;
+        let coordinates = points.into_iter().step_by(step.max(1)).collect();
+        Some(Self {
+            filename: gfile.filename.clone(),
+            color: gfile.sport.color(),
+            coordinates,
+        })
+    }
+}
+
+/// Activities, routes and photos of a trip for `/garmin/trip`
+#[derive(PartialEq, Clone)]
+pub struct TripOpts {
+    pub trip: Trip,
+    pub stats: TripStats,
+    pub summaries: Vec<GarminSummary>,
+    pub routes: Vec<TripRoute>,
+    pub photo_urls: Vec<StackString>,
+}
+
+/// Biomarker series which can be overlaid on the scale and heart rate
+/// statistics plots, along with the values of the selected one
+#[derive(PartialEq, Clone, Default)]
+pub struct BiomarkerOverlay {
+    pub series: Vec<BiomarkerSeries>,
+    pub selected: Option<BiomarkerSeries>,
+    pub measurements: Vec<BiomarkerMeasurement>,
+}
+
+/// Daily SpO2, blood pressure and nightly respiration averages shown with the
+/// heart rate statistics
+#[derive(PartialEq, Clone, Default)]
+pub struct WellnessOpts {
+    pub spo2: Vec<(Date, f64)>,
+    pub systolic: Vec<(Date, f64)>,
+    pub diastolic: Vec<(Date, f64)>,
+    pub sleep_respiration: Vec<(Date, f64)>,
+    pub sleep_hours: Vec<(Date, f64)>,
+}
 
 #[derive(PartialEq, Clone)]
 struct HeartrateOpts {
@@ -51,23 +187,25 @@ struct HeartrateOpts {
 }
 
 pub enum IndexConfig {
-    Report {
-        reports: GarminReportQuery,
-    },
     File {
         gfile: GarminFile,
+        xaxis: Option<PlotXAxis>,
+        split_distance: SplitDistance,
     },
     Scale {
         measurements: Vec<ScaleMeasurement>,
         offset: usize,
         start_date: DateType,
         end_date: DateType,
+        overlay: BiomarkerOverlay,
     },
     HearRateSummary {
         stats: Vec<FitbitStatisticsSummary>,
         offset: Option<usize>,
         start_date: Option<DateType>,
         end_date: Option<DateType>,
+        overlay: BiomarkerOverlay,
+        wellness: WellnessOpts,
     },
     HeartRate {
         heartrate: Vec<(DateTimeWrapper, i32)>,
@@ -78,6 +216,15 @@ pub enum IndexConfig {
     RaceResult {
         model: RaceResultAnalysis,
     },
+    PowerCurve {
+        power_curve: PowerCurveOpts,
+    },
+    Trip {
+        trip: TripOpts,
+    },
+    YearlyComparison {
+        comparison: YearlyComparison,
+    },
 }
 
 /// # Errors
@@ -87,52 +234,59 @@ pub async fn index_new_body(
     pool: &PgPool,
     title: StackString,
     is_demo: bool,
-    history: Vec<StackString>,
+    session: Session,
     index_config: IndexConfig,
 ) -> Result<String, Error> {
     let map_api_key = config.maps_api_key.clone();
+    let Session {
+        history,
+        pinned,
+        language,
+    } = session;
     match index_config {
-        IndexConfig::Report { reports } => {
-            let mut url_strings = reports.get_url_strings();
-            url_strings.shrink_to_fit();
-            let mut reports = reports.get_text_entries().map_err(Into::<Error>::into)?;
-            reports.shrink_to_fit();
-            let mut app = VirtualDom::new_with_props(
-                IndexElement,
-                IndexElementProps {
-                    title,
-                    reports,
-                    url_strings,
-                    plot_reports: None,
-                    gfile: None,
-                    strava_activity: None,
-                    connect_activity: None,
-                    race_result: None,
-                    is_demo,
-                    map_api_key,
-                    history,
-                    measurements: Vec::new(),
-                    offset: None,
-                    start_date: None,
-                    end_date: None,
-                    heartrate_stats: Vec::new(),
-                    heartrate_opts: None,
-                    model: None,
-                    config: config.clone(),
-                },
-            );
-            app.rebuild_in_place();
-            let mut renderer = dioxus_ssr::Renderer::default();
-            let mut buffer = String::new();
-            renderer
-                .render_to(&mut buffer, &app)
-                .map_err(Into::<Error>::into)?;
-            Ok(buffer)
-        }
-        IndexConfig::File { gfile } => {
-            let report_objs = extract_report_objects_from_file(&gfile);
+        IndexConfig::File {
+            mut gfile,
+            xaxis,
+            split_distance,
+        } => {
+            // look things up by the real start before the demo shifts it
+            let begin: OffsetDateTime = gfile.begin_datetime.into();
+            let threshold = if gfile.points.iter().any(|p| p.power.is_some()) {
+                let date = begin.to_timezone(DateTimeWrapper::local_tz()).date();
+                PowerThreshold::get_for_date(pool, date).await?
+            } else {
+                None
+            };
+            // the demo renames the file, so keep its real name for the lookups
+            let filename = gfile.filename.clone();
+            let origin = DemoAnonymizer::origin(&gfile);
+            let anonymizer = DemoAnonymizer::from_config(config).filter(|_| is_demo);
+            if let Some(anonymizer) = &anonymizer {
+                anonymizer.anonymize_file(&mut gfile);
+            }
+
+            let mut report_objs = extract_report_objects_from_file(&gfile, xaxis);
+            if let Some(threshold) = &threshold {
+                add_w_prime_balance(&mut report_objs, &gfile, threshold);
+            }
 
-            let summary = GarminSummary::get_by_filename(pool, &gfile.filename).await?;
+            let summary = GarminSummary::get_by_filename(pool, &filename).await?;
+            if let Some(s) = &summary {
+                let streams = HeartRateStream::read_by_summary_id(pool, s.id).await?;
+                if streams.len() > 1 {
+                    report_objs.hr_streams = streams
+                        .iter()
+                        .map(|stream| {
+                            let name = if stream.preferred {
+                                format_sstr!("{} (preferred)", stream.source)
+                            } else {
+                                stream.source.clone()
+                            };
+                            (name, stream.plot_values(begin))
+                        })
+                        .collect();
+                }
+            }
             let strava_activity = if let Some(s) = &summary {
                 StravaActivity::get_from_summary_id(pool, s.id).await?
             } else {
@@ -148,29 +302,100 @@ pub async fn index_new_body(
             } else {
                 None
             };
+            let stat_choices = if let Some(s) = &summary {
+                StatChoice::get_by_summary_id(pool, s.id).await?
+            } else {
+                Vec::new()
+            };
+            let stat_discrepancies = StatDiscrepancy::from_sources(
+                gfile.total_distance,
+                gfile.total_calories,
+                strava_activity.as_ref(),
+                connect_activity.as_ref(),
+                &stat_choices,
+            );
+            let mut route = if let Some(s) = &summary {
+                MatchedRoute::get_by_summary_id(pool, s.id).await?
+            } else {
+                None
+            };
+            let rpe = if let Some(s) = &summary {
+                ActivityRpe::get_by_summary_id(pool, s.id)
+                    .await?
+                    .map(|r| r.rpe)
+            } else {
+                None
+            };
+            let (connect_detail, connect_splits) = if let Some(a) = &connect_activity {
+                (
+                    GarminConnectActivityDetail::get_by_activity_id(pool, a.activity_id).await?,
+                    GarminConnectSplit::read_from_db(pool, a.activity_id).await?,
+                )
+            } else {
+                (None, Vec::new())
+            };
+            let (strava_activity, connect_activity, race_result, connect_detail, connect_splits) =
+                if let Some(anonymizer) = &anonymizer {
+                    if let Some(route) = &mut route {
+                        anonymizer.anonymize_route(&filename, origin, route);
+                    }
+                    // names and ids of linked activities would give away the real ones
+                    let race_result = race_result.map(|mut r| {
+                        r.race_name = None;
+                        r.bib_number = None;
+                        r.race_report = None;
+                        r.race_date = None;
+                        r
+                    });
+                    // as would device serial numbers, gear names and split times
+                    (None, None, race_result, None, Vec::new())
+                } else {
+                    (
+                        strava_activity,
+                        connect_activity,
+                        race_result,
+                        connect_detail,
+                        connect_splits,
+                    )
+                };
 
             let mut app = VirtualDom::new_with_props(
                 IndexElement,
                 IndexElementProps {
                     title,
-                    reports: Vec::new(),
-                    url_strings: Vec::new(),
+                    report_rows: false,
                     plot_reports: Some(report_objs),
                     gfile: Some(gfile),
                     strava_activity,
                     connect_activity,
+                    connect_detail,
+                    connect_splits,
                     race_result,
+                    stat_discrepancies,
+                    route,
+                    rpe,
                     is_demo,
+                    language,
                     map_api_key,
                     history,
+                    pinned,
                     measurements: Vec::new(),
+                    calorie_estimates: Vec::new(),
+                    milestones: Vec::new(),
+                    ramp_warnings: Vec::new(),
                     offset: None,
                     start_date: None,
                     end_date: None,
                     heartrate_stats: Vec::new(),
                     heartrate_opts: None,
                     model: None,
+                    power_curve: None,
+                    trip: None,
+                    yearly_comparison: None,
+                    overlay: None,
+                    wellness: None,
                     config: config.clone(),
+                    split_distance,
                 },
             );
             app.rebuild_in_place();
@@ -186,29 +411,46 @@ pub async fn index_new_body(
             offset,
             start_date,
             end_date,
+            overlay,
         } => {
+            let calorie_estimates = WeeklyCalorieEstimate::from_measurements(&measurements);
             let mut app = VirtualDom::new_with_props(
                 IndexElement,
                 IndexElementProps {
                     title,
-                    reports: Vec::new(),
-                    url_strings: Vec::new(),
+                    report_rows: false,
                     plot_reports: None,
                     gfile: None,
                     strava_activity: None,
                     connect_activity: None,
+                    connect_detail: None,
+                    connect_splits: Vec::new(),
                     race_result: None,
+                    stat_discrepancies: Vec::new(),
+                    route: None,
+                    rpe: None,
                     is_demo,
+                    language,
                     map_api_key,
                     history,
+                    pinned,
                     measurements,
+                    calorie_estimates,
+                    milestones: Vec::new(),
+                    ramp_warnings: Vec::new(),
                     offset: Some(offset),
                     start_date: Some(start_date),
                     end_date: Some(end_date),
                     heartrate_stats: Vec::new(),
                     heartrate_opts: None,
                     model: None,
+                    power_curve: None,
+                    trip: None,
+                    yearly_comparison: None,
+                    overlay: Some(overlay),
+                    wellness: None,
                     config: config.clone(),
+                    split_distance: config.split_distance,
                 },
             );
             app.rebuild_in_place();
@@ -224,29 +466,47 @@ pub async fn index_new_body(
             offset,
             start_date,
             end_date,
+            overlay,
+            wellness,
         } => {
             let mut app = VirtualDom::new_with_props(
                 IndexElement,
                 IndexElementProps {
                     title,
-                    reports: Vec::new(),
-                    url_strings: Vec::new(),
+                    report_rows: false,
                     plot_reports: None,
                     gfile: None,
                     strava_activity: None,
                     connect_activity: None,
+                    connect_detail: None,
+                    connect_splits: Vec::new(),
                     race_result: None,
+                    stat_discrepancies: Vec::new(),
+                    route: None,
+                    rpe: None,
                     is_demo,
+                    language,
                     map_api_key,
                     history,
+                    pinned,
                     measurements: Vec::new(),
+                    calorie_estimates: Vec::new(),
+                    milestones: Vec::new(),
+                    ramp_warnings: Vec::new(),
                     offset,
                     start_date,
                     end_date,
                     heartrate_stats: stats,
                     heartrate_opts: None,
                     model: None,
+                    power_curve: None,
+                    trip: None,
+                    yearly_comparison: None,
+                    overlay: Some(overlay),
+                    // sleep and wellness series are never rendered on demo pages
+                    wellness: if is_demo { None } else { Some(wellness) },
                     config: config.clone(),
+                    split_distance: config.split_distance,
                 },
             );
             app.rebuild_in_place();
@@ -267,17 +527,26 @@ pub async fn index_new_body(
                 IndexElement,
                 IndexElementProps {
                     title,
-                    reports: Vec::new(),
-                    url_strings: Vec::new(),
+                    report_rows: false,
                     plot_reports: None,
                     gfile: None,
                     strava_activity: None,
                     connect_activity: None,
+                    connect_detail: None,
+                    connect_splits: Vec::new(),
                     race_result: None,
+                    stat_discrepancies: Vec::new(),
+                    route: None,
+                    rpe: None,
                     is_demo,
+                    language,
                     map_api_key,
                     history,
+                    pinned,
                     measurements: Vec::new(),
+                    calorie_estimates: Vec::new(),
+                    milestones: Vec::new(),
+                    ramp_warnings: Vec::new(),
                     offset: None,
                     start_date: Some(start_date),
                     end_date: Some(end_date),
@@ -287,7 +556,13 @@ pub async fn index_new_body(
                         button_date,
                     }),
                     model: None,
+                    power_curve: None,
+                    trip: None,
+                    yearly_comparison: None,
+                    overlay: None,
+                    wellness: None,
                     config: config.clone(),
+                    split_distance: config.split_distance,
                 },
             );
             app.rebuild_in_place();
@@ -303,24 +578,183 @@ pub async fn index_new_body(
                 IndexElement,
                 IndexElementProps {
                     title,
-                    reports: Vec::new(),
-                    url_strings: Vec::new(),
+                    report_rows: false,
                     plot_reports: None,
                     gfile: None,
                     strava_activity: None,
                     connect_activity: None,
+                    connect_detail: None,
+                    connect_splits: Vec::new(),
                     race_result: None,
+                    stat_discrepancies: Vec::new(),
+                    route: None,
+                    rpe: None,
                     is_demo,
+                    language,
                     map_api_key,
                     history,
+                    pinned,
                     measurements: Vec::new(),
+                    calorie_estimates: Vec::new(),
+                    milestones: Vec::new(),
+                    ramp_warnings: Vec::new(),
                     offset: None,
                     start_date: None,
                     end_date: None,
                     heartrate_stats: Vec::new(),
                     heartrate_opts: None,
                     model: Some(model),
+                    power_curve: None,
+                    trip: None,
+                    yearly_comparison: None,
+                    overlay: None,
+                    wellness: None,
+                    config: config.clone(),
+                    split_distance: config.split_distance,
+                },
+            );
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer
+                .render_to(&mut buffer, &app)
+                .map_err(Into::<Error>::into)?;
+            Ok(buffer)
+        }
+        IndexConfig::PowerCurve { power_curve } => {
+            let mut app = VirtualDom::new_with_props(
+                IndexElement,
+                IndexElementProps {
+                    title,
+                    report_rows: false,
+                    plot_reports: None,
+                    gfile: None,
+                    strava_activity: None,
+                    connect_activity: None,
+                    connect_detail: None,
+                    connect_splits: Vec::new(),
+                    race_result: None,
+                    stat_discrepancies: Vec::new(),
+                    route: None,
+                    rpe: None,
+                    is_demo,
+                    language,
+                    map_api_key,
+                    history,
+                    pinned,
+                    measurements: Vec::new(),
+                    calorie_estimates: Vec::new(),
+                    milestones: Vec::new(),
+                    ramp_warnings: Vec::new(),
+                    offset: None,
+                    start_date: None,
+                    end_date: None,
+                    heartrate_stats: Vec::new(),
+                    heartrate_opts: None,
+                    model: None,
+                    power_curve: Some(power_curve),
+                    trip: None,
+                    yearly_comparison: None,
+                    overlay: None,
+                    wellness: None,
+                    config: config.clone(),
+                    split_distance: config.split_distance,
+                },
+            );
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer
+                .render_to(&mut buffer, &app)
+                .map_err(Into::<Error>::into)?;
+            Ok(buffer)
+        }
+        IndexConfig::Trip { trip } => {
+            let mut app = VirtualDom::new_with_props(
+                IndexElement,
+                IndexElementProps {
+                    title,
+                    report_rows: false,
+                    plot_reports: None,
+                    gfile: None,
+                    strava_activity: None,
+                    connect_activity: None,
+                    connect_detail: None,
+                    connect_splits: Vec::new(),
+                    race_result: None,
+                    stat_discrepancies: Vec::new(),
+                    route: None,
+                    rpe: None,
+                    is_demo,
+                    language,
+                    map_api_key,
+                    history,
+                    pinned,
+                    measurements: Vec::new(),
+                    calorie_estimates: Vec::new(),
+                    milestones: Vec::new(),
+                    ramp_warnings: Vec::new(),
+                    offset: None,
+                    start_date: None,
+                    end_date: None,
+                    heartrate_stats: Vec::new(),
+                    heartrate_opts: None,
+                    model: None,
+                    power_curve: None,
+                    trip: Some(trip),
+                    yearly_comparison: None,
+                    overlay: None,
+                    wellness: None,
+                    config: config.clone(),
+                    split_distance: config.split_distance,
+                },
+            );
+            app.rebuild_in_place();
+            let mut renderer = dioxus_ssr::Renderer::default();
+            let mut buffer = String::new();
+            renderer
+                .render_to(&mut buffer, &app)
+                .map_err(Into::<Error>::into)?;
+            Ok(buffer)
+        }
+        IndexConfig::YearlyComparison { comparison } => {
+            let mut app = VirtualDom::new_with_props(
+                IndexElement,
+                IndexElementProps {
+                    title,
+                    report_rows: false,
+                    plot_reports: None,
+                    gfile: None,
+                    strava_activity: None,
+                    connect_activity: None,
+                    connect_detail: None,
+                    connect_splits: Vec::new(),
+                    race_result: None,
+                    stat_discrepancies: Vec::new(),
+                    route: None,
+                    rpe: None,
+                    is_demo,
+                    language,
+                    map_api_key,
+                    history,
+                    pinned,
+                    measurements: Vec::new(),
+                    calorie_estimates: Vec::new(),
+                    milestones: Vec::new(),
+                    ramp_warnings: Vec::new(),
+                    offset: None,
+                    start_date: None,
+                    end_date: None,
+                    heartrate_stats: Vec::new(),
+                    heartrate_opts: None,
+                    model: None,
+                    power_curve: None,
+                    trip: None,
+                    yearly_comparison: Some(comparison),
+                    overlay: None,
+                    wellness: None,
                     config: config.clone(),
+                    split_distance: config.split_distance,
                 },
             );
             app.rebuild_in_place();
@@ -334,61 +768,317 @@ pub async fn index_new_body(
     }
 }
 
-#[component]
-fn IndexElement(
+/// Report page as a stream of html, the page around the report table is
+/// rendered first and then the table rows `REPORT_STREAM_ROWS` at a time as
+/// they come in so large reports are never held in memory as a whole. The
+/// headers are already sent once rows fail, so the table then ends with
+/// `REPORT_ERROR_ROW` instead
+/// # Errors
+/// Return error if db queries or rendering the page fail
+pub async fn index_report_stream(
+    config: &GarminConfig,
+    pool: &PgPool,
     title: StackString,
-    reports: Vec<Vec<(StackString, Option<HtmlResult>)>>,
-    url_strings: Vec<StackString>,
-    plot_reports: Option<ReportObjects>,
-    gfile: Option<GarminFile>,
-    strava_activity: Option<StravaActivity>,
-    connect_activity: Option<GarminConnectActivity>,
-    race_result: Option<RaceResults>,
     is_demo: bool,
-    map_api_key: StackString,
-    history: Vec<StackString>,
-    measurements: Vec<ScaleMeasurement>,
-    offset: Option<usize>,
-    start_date: Option<DateType>,
-    end_date: Option<DateType>,
-    heartrate_stats: Vec<FitbitStatisticsSummary>,
-    heartrate_opts: Option<HeartrateOpts>,
-    model: Option<RaceResultAnalysis>,
-    config: GarminConfig,
-) -> Element {
-    struct PlotData {
-        data: Vec<(String, f64)>,
-        title: &'static str,
-        xaxis: &'static str,
-        yaxis: &'static str,
-        units: &'static str,
-    }
+    session: Session,
+    rows: impl Stream<Item = Result<GarminReportRow, AnyhowError>> + Send + 'static,
+) -> Result<impl Stream<Item = Result<String, Error>> + Send + 'static, Error> {
+    let map_api_key = config.maps_api_key.clone();
+    let Session {
+        history,
+        pinned,
+        language,
+    } = session;
+    // the banners carry the real dates of recent activities
+    let (milestones, ramp_warnings) = if is_demo {
+        (Vec::new(), Vec::new())
+    } else {
+        let since = OffsetDateTime::now_utc().date() - Duration::days(MILESTONE_BANNER_DAYS);
+        let milestones = Milestone::read_from_db(pool, Some(since)).await?;
+        let ramp_warnings = WeeklyRampRate::get_warnings(
+            pool,
+            SportTypes::Running,
+            config.week_start,
+            config.ramp_rate_threshold,
+            RAMP_RATE_BANNER_WEEKS,
+            config.exclude_commutes,
+        )
+        .await?;
+        (milestones, ramp_warnings)
+    };
+    let mut app = VirtualDom::new_with_props(
+        IndexElement,
+        IndexElementProps {
+            title,
+            report_rows: true,
+            plot_reports: None,
+            gfile: None,
+            strava_activity: None,
+            connect_activity: None,
+            connect_detail: None,
+            connect_splits: Vec::new(),
+            race_result: None,
+            stat_discrepancies: Vec::new(),
+            route: None,
+            rpe: None,
+            is_demo,
+            language,
+            map_api_key,
+            history,
+            pinned,
+            measurements: Vec::new(),
+            calorie_estimates: Vec::new(),
+            milestones,
+            ramp_warnings,
+            offset: None,
+            start_date: None,
+            end_date: None,
+            heartrate_stats: Vec::new(),
+            heartrate_opts: None,
+            model: None,
+            power_curve: None,
+            trip: None,
+            yearly_comparison: None,
+            overlay: None,
+            wellness: None,
+            config: config.clone(),
+            split_distance: config.split_distance,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    let (head, tail) = match buffer.split_once(REPORT_ROWS_MARKER) {
+        Some((head, tail)) => (head.to_string(), tail.to_string()),
+        None => return Err(Error::InternalServerError),
+    };
+    drop(buffer);
 
-    let offset = offset.unwrap_or(0);
-    let history_buttons = generate_history_buttons(&history);
-    let buttons = get_buttons(is_demo);
-    let mut sport_title: Option<Element> = None;
-    let mut button_str: Option<Element> = None;
-    let mut script_box: Option<Element> = None;
-    let mut text_box: Option<Element> = None;
-    let mut table_box: Option<Element> = None;
-    let mut image_box: Option<Element> = None;
-    let local = DateTimeWrapper::local_tz();
+    let rows = rows
+        .chunks(REPORT_STREAM_ROWS)
+        .enumerate()
+        .scan(false, |failed, (idx, chunk)| {
+            if *failed {
+                return ready(None);
+            }
+            let mut reports = Vec::with_capacity(chunk.len());
+            let mut url_strings = Vec::with_capacity(chunk.len());
+            for row in chunk {
+                match row {
+                    Ok((report, url_string)) => {
+                        reports.push(report);
+                        url_strings.push(url_string);
+                    }
+                    Err(e) => {
+                        error!("Reading report rows failed {e}");
+                        *failed = true;
+                        break;
+                    }
+                }
+            }
+            let mut body = match report_rows_body(reports, url_strings, idx * REPORT_STREAM_ROWS) {
+                Ok(body) => body,
+                Err(e) => {
+                    error!("Rendering report rows failed {e}");
+                    *failed = true;
+                    String::new()
+                }
+            };
+            if *failed {
+                body.push_str(REPORT_ERROR_ROW);
+            }
+            ready(Some(Ok(body)))
+        });
+    Ok(stream::once(ready(Ok(head)))
+        .chain(rows)
+        .chain(stream::once(ready(Ok(tail)))))
+}
 
-    if let Some(model) = model {
-        script_box.replace(create_analysis_plot(&model, is_demo));
-    }
-    if let Some(HeartrateOpts {
-        heartrate,
-        button_date,
-    }) = heartrate_opts
-    {
-        let start_date: Date = start_date.map_or_else(
-            || {
-                (OffsetDateTime::now_utc() - Duration::days(3))
-                    .to_timezone(local)
-                    .date()
-            },
+fn report_rows_body(
+    reports: Vec<Vec<(StackString, Option<HtmlResult>)>>,
+    url_strings: Vec<StackString>,
+    offset: usize,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        ReportRowsElement,
+        ReportRowsElementProps {
+            reports,
+            url_strings,
+            offset,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn ReportRowsElement(
+    reports: Vec<Vec<(StackString, Option<HtmlResult>)>>,
+    url_strings: Vec<StackString>,
+    offset: usize,
+) -> Element {
+    let rows =
+        reports
+            .iter()
+            .zip(url_strings.iter())
+            .enumerate()
+            .map(|(idx, (text_entries, cmd))| {
+                let idx = idx + offset;
+                let entries = text_entries.iter().enumerate().map(|(jdx, (s, u))| {
+                    let entry = u.as_ref().map_or(rsx! {"{s}"}, |u| match u {
+                        HtmlResult {
+                            text: Some(t),
+                            url: Some(u),
+                        } => rsx! {
+                            a {href: "{u}", target: "_blank", "{t}"},
+                        },
+                        HtmlResult {
+                            text: Some(t),
+                            url: None,
+                        } => rsx! {
+                            div {
+                                dangerous_inner_html: "{t}",
+                            }
+                        },
+                        HtmlResult {
+                            text: None,
+                            url: Some(u),
+                        } => rsx! {
+                            a {href: "{u}", target: "_blank", "link"},
+                        },
+                        _ => rsx! {""},
+                    });
+                    rsx! {
+                        td {
+                            key: "report-entry-{jdx}",
+                            {entry}
+                        }
+                    }
+                });
+                let sport: Option<SportTypes> = cmd.split(',').next().and_then(|s| s.parse().ok());
+                let sport_style = sport.map_or_else(StackString::new, |s| {
+                    format_sstr!("border-left: 6px solid {};", s.color())
+                });
+                let sport_icon = sport.map_or("", SportTypes::icon);
+                rsx! {
+                    tr {
+                        key: "report-key-{idx}",
+                        td {
+                            style: "{sport_style}",
+                            button {
+                                "type": "submit",
+                                "onclick": "send_command('filter={cmd}')",
+                                "{sport_icon} {cmd}",
+                            }
+                        },
+                        {entries}
+                    },
+                }
+            });
+    rsx! {
+        {rows}
+    }
+}
+
+#[component]
+fn IndexElement(
+    title: StackString,
+    report_rows: bool,
+    plot_reports: Option<ReportObjects>,
+    gfile: Option<GarminFile>,
+    strava_activity: Option<StravaActivity>,
+    connect_activity: Option<GarminConnectActivity>,
+    connect_detail: Option<GarminConnectActivityDetail>,
+    connect_splits: Vec<GarminConnectSplit>,
+    race_result: Option<RaceResults>,
+    stat_discrepancies: Vec<StatDiscrepancy>,
+    route: Option<MatchedRoute>,
+    rpe: Option<i16>,
+    is_demo: bool,
+    language: Language,
+    map_api_key: StackString,
+    history: Vec<StackString>,
+    pinned: Vec<StackString>,
+    measurements: Vec<ScaleMeasurement>,
+    calorie_estimates: Vec<WeeklyCalorieEstimate>,
+    milestones: Vec<Milestone>,
+    ramp_warnings: Vec<WeeklyRampRate>,
+    offset: Option<usize>,
+    start_date: Option<DateType>,
+    end_date: Option<DateType>,
+    heartrate_stats: Vec<FitbitStatisticsSummary>,
+    heartrate_opts: Option<HeartrateOpts>,
+    model: Option<RaceResultAnalysis>,
+    power_curve: Option<PowerCurveOpts>,
+    trip: Option<TripOpts>,
+    yearly_comparison: Option<YearlyComparison>,
+    overlay: Option<BiomarkerOverlay>,
+    wellness: Option<WellnessOpts>,
+    config: GarminConfig,
+    split_distance: SplitDistance,
+) -> Element {
+    struct PlotData {
+        data: Vec<(String, f64)>,
+        title: &'static str,
+        xaxis: &'static str,
+        yaxis: &'static str,
+        units: &'static str,
+        goal: Option<f64>,
+        projection: Option<GoalProjection>,
+    }
+
+    let offset = offset.unwrap_or(0);
+    let overlay = overlay.unwrap_or_default();
+    let overlay_js = biomarker_overlay_js(&overlay);
+    let history_buttons = generate_history_buttons(&history, &pinned, is_demo);
+    let buttons = get_buttons(is_demo, language);
+    let mut sport_title: Option<Element> = None;
+    let mut button_str: Option<Element> = None;
+    let mut script_box: Option<Element> = None;
+    let mut text_box: Option<Element> = None;
+    let mut table_box: Option<Element> = None;
+    let mut image_box: Option<Element> = None;
+    let local = DateTimeWrapper::local_tz();
+
+    if let Some(model) = model {
+        script_box.replace(create_analysis_plot(&model, is_demo));
+    }
+    if let Some(power_curve) = power_curve {
+        script_box.replace(create_power_curve_plot(&power_curve));
+    }
+    if let Some(comparison) = yearly_comparison {
+        script_box.replace(create_yearly_comparison_plot(&comparison));
+    }
+    if let Some(trip) = trip {
+        let Trip {
+            name,
+            start_date,
+            end_date,
+            ..
+        } = &trip.trip;
+        sport_title.replace(rsx! {"{name} {start_date} to {end_date}"});
+        script_box.replace(create_trip_map(&trip.routes));
+        table_box.replace(trip_table(&trip));
+    }
+    if let Some(HeartrateOpts {
+        heartrate,
+        button_date,
+    }) = heartrate_opts
+    {
+        let start_date: Date = start_date.map_or_else(
+            || {
+                (OffsetDateTime::now_utc() - Duration::days(3))
+                    .to_timezone(local)
+                    .date()
+            },
             Into::into,
         );
         let end_date: Date = end_date.map_or_else(
@@ -446,12 +1136,14 @@ fn IndexElement(
             rsx! {
                 input {
                     "type": "date",
+                    "aria-label": "Start date",
                     name: "start-date",
                     id: "start_date_selector_heart",
                     value: "{start_date}",
                 }
                 input {
                     "type": "date",
+                    "aria-label": "End date",
                     name: "end-date",
                     id: "end_date_selector_heart",
                     value: "{end_date}",
@@ -553,6 +1245,8 @@ fn IndexElement(
             xaxis: "Date",
             yaxis: "Heatrate [bpm]",
             units: "bpm",
+            goal: None,
+            projection: None,
         });
         let mut max_heartrate: Vec<(String, f64)> = heartrate_stats
             .iter()
@@ -568,6 +1262,8 @@ fn IndexElement(
             xaxis: "Date",
             yaxis: "Heatrate [bpm]",
             units: "bpm",
+            goal: None,
+            projection: None,
         });
         let mut mean_heartrate: Vec<(String, f64)> = heartrate_stats
             .iter()
@@ -583,19 +1279,76 @@ fn IndexElement(
             xaxis: "Date",
             yaxis: "Heatrate [bpm]",
             units: "bpm",
+            goal: None,
+            projection: None,
         });
+        let wellness = wellness.unwrap_or_default();
+        for (values, title, yaxis, units) in [
+            (&wellness.spo2, "SpO2", "SpO2 [%]", "%"),
+            (
+                &wellness.systolic,
+                "Systolic Blood Pressure",
+                "Systolic [mmHg]",
+                "mmHg",
+            ),
+            (
+                &wellness.diastolic,
+                "Diastolic Blood Pressure",
+                "Diastolic [mmHg]",
+                "mmHg",
+            ),
+            (
+                &wellness.sleep_respiration,
+                "Nightly Respiration",
+                "Respiration [brpm]",
+                "brpm",
+            ),
+            (&wellness.sleep_hours, "Sleep", "Sleep [hours]", "hours"),
+        ] {
+            if values.is_empty() {
+                continue;
+            }
+            let mut data: Vec<(String, f64)> = values
+                .iter()
+                .map(|(date, value)| {
+                    let key = date.format(dformat).unwrap_or_else(|_| String::new());
+                    (key, *value)
+                })
+                .collect();
+            data.shrink_to_fit();
+            plots.push(PlotData {
+                data,
+                title,
+                xaxis: "Date",
+                yaxis,
+                units,
+                goal: None,
+                projection: None,
+            });
+        }
+        let spo2: HashMap<Date, f64> = wellness.spo2.iter().copied().collect();
+        let diastolic: HashMap<Date, f64> = wellness.diastolic.iter().copied().collect();
+        let blood_pressure: HashMap<Date, (f64, f64)> = wellness
+            .systolic
+            .iter()
+            .filter_map(|(date, sys)| diastolic.get(date).map(|dia| (*date, (*sys, *dia))))
+            .collect();
         let graphs = plots.into_iter().enumerate().map(|(idx, plot)| {
             let data = serde_json::to_string(&plot.data).unwrap_or_else(|_| String::new());
             let title = plot.title;
             let xaxis = plot.xaxis;
             let yaxis = plot.yaxis;
             let units = plot.units;
+            let goal_js = plot
+                .goal
+                .map_or_else(|| "null".into(), |goal| format_sstr!("{goal}"));
             let mut script_body = String::new();
             script_body.push_str("\n!function(){\n");
             writeln!(&mut script_body, "\tlet data = {data};").unwrap();
             writeln!(
                 &mut script_body,
-                "\ttime_series(data, '{title}', '{xaxis}', '{yaxis}', '{units}');"
+                "\ttime_series(data, '{title}', '{xaxis}', '{yaxis}', '{units}', {overlay_js}, \
+                 {goal_js});"
             )
             .unwrap();
             script_body.push_str("}();\n");
@@ -618,6 +1371,12 @@ fn IndexElement(
                 let max = stat.max_heartrate;
                 let mnh = stat.mean_heartrate;
                 let mdh = stat.median_heartrate;
+                let spo2 = spo2
+                    .get(&date)
+                    .map_or_else(String::new, |v| format!("{v:2.1}"));
+                let bp = blood_pressure
+                    .get(&date)
+                    .map_or_else(String::new, |(sys, dia)| format!("{sys:.0}/{dia:.0}"));
                 rsx! {
                     tr {
                         key: "heartrate-stat-key-{idx}",
@@ -626,6 +1385,8 @@ fn IndexElement(
                         td {"{max:2.1}"},
                         td {"{mnh:2.1}"},
                         td {"{mdh:2.1}"},
+                        td {"{spo2}"},
+                        td {"{bp}"},
                     }
                 }
             });
@@ -653,16 +1414,19 @@ fn IndexElement(
             rsx! {
                 input {
                     "type": "date",
+                    "aria-label": "Start date",
                     name: "start-date",
                     id: "start_date_selector_stat",
                     value: "{start_date}",
                 }
                 input {
                     "type": "date",
+                    "aria-label": "End date",
                     name: "end-date",
                     id: "end_date_selector_stat",
                     value: "{end_date}",
                 }
+                {biomarker_selector(&overlay)},
                 button {
                     "type": "submit",
                     "onclick": "heartrate_stat_plot({offset}, '{start_date}', '{end_date}')",
@@ -673,12 +1437,15 @@ fn IndexElement(
         script_box.replace(rsx! {
             table {
                 "border": "1",
+                caption {class: "visually-hidden", "Heart rate statistics"},
                 thead {
-                    th {"Date"},
-                    th {"Min"}
-                    th {"Max"},
-                    th {"Mean"},
-                    th {"Median"},
+                    th {"scope": "col", "Date"},
+                    th {"scope": "col", "Min"}
+                    th {"scope": "col", "Max"},
+                    th {"scope": "col", "Mean"},
+                    th {"scope": "col", "Median"},
+                    th {"scope": "col", "SpO2 %"},
+                    th {"scope": "col", "Blood Pressure"},
                 },
                 tbody {
                     {entries},
@@ -714,6 +1481,9 @@ fn IndexElement(
 
         let mut plots = Vec::new();
 
+        let weight_projection = config
+            .goal_mass
+            .and_then(|goal| GoalProjection::from_measurements(&measurements, goal));
         let mut mass: Vec<(String, f64)> = measurements
             .iter()
             .map(|meas| {
@@ -731,6 +1501,8 @@ fn IndexElement(
             xaxis: "Date",
             yaxis: "Weight [lbs]",
             units: "lbs",
+            goal: config.goal_mass,
+            projection: weight_projection,
         });
         let mut fat: Vec<(String, f64)> = measurements
             .iter()
@@ -749,6 +1521,8 @@ fn IndexElement(
             xaxis: "Date",
             yaxis: "Fat %",
             units: "%",
+            goal: config.goal_fat_pct,
+            projection: None,
         });
         let mut water: Vec<(String, f64)> = measurements
             .iter()
@@ -767,6 +1541,8 @@ fn IndexElement(
             xaxis: "Date",
             yaxis: "Water %",
             units: "%",
+            goal: None,
+            projection: None,
         });
         let mut muscle: Vec<(String, f64)> = measurements
             .iter()
@@ -785,6 +1561,8 @@ fn IndexElement(
             xaxis: "Date",
             yaxis: "Muscle %",
             units: "%",
+            goal: None,
+            projection: None,
         });
         let mut bone: Vec<(String, f64)> = measurements
             .iter()
@@ -803,6 +1581,70 @@ fn IndexElement(
             xaxis: "Date",
             yaxis: "Bone %",
             units: "%",
+            goal: None,
+            projection: None,
+        });
+        let mut bmi: Vec<(String, f64)> = measurements
+            .iter()
+            .map(|meas| {
+                let key = meas
+                    .datetime
+                    .format(tformat)
+                    .unwrap_or_else(|_| String::new());
+                (key, meas.get_bmi(&config))
+            })
+            .collect();
+        bmi.shrink_to_fit();
+        plots.push(PlotData {
+            data: bmi,
+            title: "BMI",
+            xaxis: "Date",
+            yaxis: "BMI [kg/m^2]",
+            units: "kg/m^2",
+            goal: None,
+            projection: None,
+        });
+        let mut lean_mass: Vec<(String, f64)> = measurements
+            .iter()
+            .filter_map(|meas| {
+                let value = meas.get_lean_mass()?;
+                let key = meas
+                    .datetime
+                    .format(tformat)
+                    .unwrap_or_else(|_| String::new());
+                Some((key, value))
+            })
+            .collect();
+        lean_mass.shrink_to_fit();
+        plots.push(PlotData {
+            data: lean_mass,
+            title: "Lean Mass",
+            xaxis: "Date",
+            yaxis: "Lean Mass [lbs]",
+            units: "lbs",
+            goal: None,
+            projection: None,
+        });
+        let mut ffmi: Vec<(String, f64)> = measurements
+            .iter()
+            .filter_map(|meas| {
+                let value = meas.get_ffmi(&config)?;
+                let key = meas
+                    .datetime
+                    .format(tformat)
+                    .unwrap_or_else(|_| String::new());
+                Some((key, value))
+            })
+            .collect();
+        ffmi.shrink_to_fit();
+        plots.push(PlotData {
+            data: ffmi,
+            title: "FFMI",
+            xaxis: "Date",
+            yaxis: "FFMI [kg/m^2]",
+            units: "kg/m^2",
+            goal: config.goal_ffmi,
+            projection: None,
         });
         let graphs = plots.into_iter().enumerate().map(|(idx, plot)| {
             let data = serde_json::to_string(&plot.data).unwrap_or_else(|_| String::new());
@@ -810,12 +1652,20 @@ fn IndexElement(
             let xaxis = plot.xaxis;
             let yaxis = plot.yaxis;
             let units = plot.units;
+            let goal_js = plot
+                .goal
+                .map_or_else(|| "null".into(), |goal| format_sstr!("{goal}"));
+            let projection_js = plot
+                .projection
+                .as_ref()
+                .map_or_else(|| "null".into(), goal_projection_js);
             let mut script_body = String::new();
             script_body.push_str("\n!function(){\n");
             writeln!(&mut script_body, "\tlet data = {data};").unwrap();
             writeln!(
                 &mut script_body,
-                "\ttime_series(data, '{title}', '{xaxis}', '{yaxis}', '{units}');"
+                "\ttime_series(data, '{title}', '{xaxis}', '{yaxis}', '{units}', {overlay_js}, \
+                 {goal_js}, {projection_js});"
             )
             .unwrap();
             script_body.push_str("}();\n");
@@ -840,6 +1690,12 @@ fn IndexElement(
                 let ms = meas.muscle_pct;
                 let b = meas.bone_pct;
                 let bmi = meas.get_bmi(&config);
+                let lean_mass = meas
+                    .get_lean_mass()
+                    .map_or_else(StackString::new, |l| format_sstr!("{l:3.1}"));
+                let ffmi = meas
+                    .get_ffmi(&config)
+                    .map_or_else(StackString::new, |f| format_sstr!("{f:2.1}"));
                 rsx! {
                     tr {
                         key: "measurement-key-{idx}",
@@ -850,6 +1706,8 @@ fn IndexElement(
                         td {"{ms:2.1}"},
                         td {"{b:2.1}"},
                         td {"{bmi:2.1}"},
+                        td {"{lean_mass}"},
+                        td {"{ffmi}"},
                     }
                 }
             });
@@ -873,20 +1731,91 @@ fn IndexElement(
                 "Next",
             }
         };
+        let calorie_rows = calorie_estimates
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(idx, est)| {
+                let week = est.week_start;
+                let n = est.number_of_measurements;
+                let trend = est.trend_weight;
+                let change = est.weight_change;
+                let balance = est.daily_balance;
+                let low = est.daily_balance_low;
+                let high = est.daily_balance_high;
+                rsx! {
+                    tr {
+                        key: "calorie-estimate-key-{idx}",
+                        td {"{week}"},
+                        td {"{n}"},
+                        td {"{trend:3.1}"},
+                        td {"{change:+2.2}"},
+                        td {"{balance:+4.0}"},
+                        td {"{low:+4.0} to {high:+4.0}"},
+                    }
+                }
+            });
+        let calorie_table = if calorie_estimates.is_empty() {
+            None
+        } else {
+            Some(rsx! {
+                h4 {"Estimated Calorie Balance (negative is a deficit)"},
+                table {
+                    "border": "1",
+                    caption {class: "visually-hidden", "Estimated calorie balance"},
+                    thead {
+                        th {"scope": "col", "Week"},
+                        th {"scope": "col", "Measurements"},
+                        th {"scope": "col", "Trend Weight"},
+                        th {"scope": "col", "Change lbs/week"},
+                        th {"scope": "col", "kcal/day"},
+                        th {"scope": "col", "95% Range kcal/day"},
+                    },
+                    tbody {
+                        {calorie_rows},
+                    },
+                },
+            })
+        };
+        let projection_summary = weight_projection.map(|projection| {
+            let goal = projection.goal;
+            let rate = projection.rate * 7.0;
+            let date_or = |days: Option<f64>, default: &str| {
+                days.map_or_else(
+                    || default.into(),
+                    |d| StackString::from_display(projection.date_after(d)),
+                )
+            };
+            let projected = projection.days.map_or_else(
+                || "not reached".into(),
+                |d| format_sstr!("reached around {}", projection.date_after(d)),
+            );
+            let earliest = date_or(projection.earliest_days, "never");
+            let latest = date_or(projection.latest_days, "never");
+            rsx! {
+                p {
+                    "Goal {goal:3.1} lbs {projected} at the current {rate:+2.2} lbs/week \
+                     (95% range {earliest} to {latest})",
+                }
+            }
+        });
         let date_input = {
             rsx! {
                 input {
                     "type": "date",
+                    "aria-label": "Start date",
                     name: "start-date",
                     id: "start_date_selector_scale",
                     value: "{start_date}",
                 }
                 input {
                     "type": "date",
+                    "aria-label": "End date",
                     name: "end-date",
                     id: "end_date_selector_scale",
                     value: "{end_date}",
                 }
+                {biomarker_selector(&overlay)},
                 button {
                     "type": "submit",
                     "onclick": "scale_measurement_plots({offset}, '{start_date}', '{end_date}')",
@@ -900,24 +1829,38 @@ fn IndexElement(
                 "onclick": "manualScaleMeasurement();",
                 "Manual Scale Measurement Input",
             }
+            button {
+                "type": "submit",
+                "onclick": "scaleDuplicates();",
+                "Find Duplicates",
+            }
+            a {
+                href: "/garmin/scale_measurements/connect_export.fit",
+                download: "connect_weights.fit",
+                "Export to Garmin Connect",
+            }
             div {
                 id: "scale_measurement_box",
                 table {
                     "border": "1",
+                    caption {class: "visually-hidden", "Scale measurements"},
                     thead {
-                        th {"Date"},
+                        th {"scope": "col", "Date"},
                         th {
+                            "scope": "col",
                             a {
                                 href: "https://www.fitbit.com/weight",
                                 target: "_blank",
                                 "Weight",
                             }
                         }
-                        th {"Fat %"},
-                        th {"Water %"},
-                        th {"Muscle %"},
-                        th {"Bone %"},
-                        th {"BMI kg/m^2"},
+                        th {"scope": "col", "Fat %"},
+                        th {"scope": "col", "Water %"},
+                        th {"scope": "col", "Muscle %"},
+                        th {"scope": "col", "Bone %"},
+                        th {"scope": "col", "BMI kg/m^2"},
+                        th {"scope": "col", "Lean Mass"},
+                        th {"scope": "col", "FFMI kg/m^2"},
                     },
                     tbody {
                         {entries},
@@ -927,6 +1870,8 @@ fn IndexElement(
                     {prev_button},
                     {next_button},
                 },
+                {projection_summary},
+                {calorie_table},
                 div {
                     {date_input}
                 },
@@ -934,114 +1879,32 @@ fn IndexElement(
             }
         });
     }
-    let report_str =
-        reports
-            .iter()
-            .zip(url_strings.iter())
-            .enumerate()
-            .map(|(idx, (text_entries, cmd))| {
-                let entries = text_entries.iter().enumerate().map(|(jdx, (s, u))| {
-                    let entry = u.as_ref().map_or(rsx! {"{s}"}, |u| match u {
-                        HtmlResult {
-                            text: Some(t),
-                            url: Some(u),
-                        } => rsx! {
-                            a {href: "{u}", target: "_blank", "{t}"},
-                        },
-                        HtmlResult {
-                            text: Some(t),
-                            url: None,
-                        } => rsx! {
-                            div {
-                                dangerous_inner_html: "{t}",
-                            }
-                        },
-                        HtmlResult {
-                            text: None,
-                            url: Some(u),
-                        } => rsx! {
-                            a {href: "{u}", target: "_blank", "link"},
-                        },
-                        _ => rsx! {""},
-                    });
-                    rsx! {
-                        td {
-                            key: "report-entry-{jdx}",
-                            {entry}
-                        }
-                    }
-                });
-                rsx! {
-                    tr {
-                        key: "report-key-{idx}",
-                        td {
-                            button {
-                                "type": "submit",
-                                "onclick": "send_command('filter={cmd}')",
-                                "{cmd}",
-                            }
-                        },
-                        {entries}
-                    },
-                }
-            });
-    if let Some(report_objs) = plot_reports {
-        if !report_objs.lat_vals.is_empty()
-            & !report_objs.lon_vals.is_empty()
-            & (report_objs.lat_vals.len() == report_objs.lon_vals.len())
-        {
-            if let Some(gfile) = gfile {
-                let plot_opts = get_plot_opts(&report_objs);
-                let graphs = plot_opts.into_iter().enumerate().filter_map(|(idx, opts)| {
-                    let data = opts.data.as_ref()?;
-                    if data.is_empty() {
-                        return None;
-                    }
-                    let title = &opts.title;
-                    let xlabel = &opts.xlabel;
-                    let ylabel = &opts.ylabel;
-                    if let Some(ScatterPlotData { data, xstep, ystep }) =
-                        generate_plot_data(&opts, data)
-                    {
-                        let data = serde_json::to_string(&data).unwrap_or_else(|_| String::new());
-                        let mut script_body = String::new();
-                        script_body.push_str("\n!function(){\n");
-                        writeln!(&mut script_body, "\tlet data = {data};").unwrap();
-                        writeln!(
-                            &mut script_body,
-                            "\tscatter_plot(data, '{title}', '{xlabel}', '{ylabel}', {xstep}, \
-                             {ystep});"
-                        )
-                        .unwrap();
-                        script_body.push_str("}();\n");
-                        Some(rsx! {
-                            script {
-                                key: "plot-key-{idx}",
-                                dangerous_inner_html: "{script_body}",
-                            }
-                        })
-                    } else {
-                        let mut script_body = String::new();
-                        script_body.push_str("\n!function(){\n");
-                        let data = serde_json::to_string(&data).unwrap_or_else(|_| String::new());
-                        writeln!(&mut script_body, "\tlet data = {data};").unwrap();
-                        writeln!(
-                            &mut script_body,
-                            "\tline_plot(data, '{title}', '{xlabel}', '{ylabel}');"
-                        )
-                        .unwrap();
-                        script_body.push_str("}();\n");
-                        Some(rsx! {
-                            script {
-                                key: "plot-key-{idx}",
-                                dangerous_inner_html: "{script_body}",
-                            }
-                        })
-                    }
-                });
-                image_box.replace(rsx! {
-                    {graphs}
-                });
+    let share_button = gfile.as_ref().filter(|_| !is_demo).map(|gfile| {
+        let f = &gfile.filename;
+        rsx! {
+            a {
+                href: "/garmin/share_image.png?filename={f}",
+                target: "_blank",
+                button { "Share image" },
+            }
+            a {
+                href: "/garmin/export.fit?filename={f}",
+                button { "Export FIT" },
+            }
+            a {
+                href: "/garmin/activity/{f}/gpx",
+                button { "Export GPX" },
+            }
+        }
+    });
+    if let Some(report_objs) = plot_reports {
+        if !report_objs.lat_vals.is_empty()
+            & !report_objs.lon_vals.is_empty()
+            & (report_objs.lat_vals.len() == report_objs.lon_vals.len())
+        {
+            if let Some(gfile) = gfile {
+                let color = gfile.sport.color();
+                image_box.replace(get_file_plots(&report_objs, &color));
 
                 let minlat = report_objs
                     .lat_vals
@@ -1078,7 +1941,6 @@ fn IndexElement(
                     (11, 0.20),
                     (10, 0.4),
                 ];
-                let sport_str = gfile.sport.to_str();
                 let sport_title_link = if let Some(strava_activity) = &strava_activity {
                     let id = strava_activity.id;
                     let name = &strava_activity.name;
@@ -1091,9 +1953,10 @@ fn IndexElement(
                         }
                     }
                 } else {
-                    let s = titlecase(sport_str);
+                    let s = gfile.sport.display_name();
+                    let icon = gfile.sport.icon();
                     let dt = gfile.begin_datetime;
-                    rsx! {"Garmin Event {s} on {dt}"}
+                    rsx! {"{icon} Garmin Event {s} on {dt}"}
                 };
                 sport_title.replace(sport_title_link);
                 if !is_demo {
@@ -1105,6 +1968,7 @@ fn IndexElement(
                             form {
                                 input {
                                     "type": "text",
+                                    "aria-label": "Strava activity title",
                                     name: "cmd",
                                     id: "strava_upload",
                                 },
@@ -1123,6 +1987,7 @@ fn IndexElement(
                             form {
                                 input {
                                     "type": "text",
+                                    "aria-label": "Strava activity title",
                                     name: "cmd",
                                     id: "strava_upload",
                                 },
@@ -1156,10 +2021,13 @@ fn IndexElement(
                     "\tlet runningRouteCoordinates = [{map_segment}];"
                 )
                 .unwrap();
+                let lap_pins =
+                    serde_json::to_string(&report_objs.lap_pins).unwrap_or_else(|_| String::new());
+                writeln!(&mut script_body, "\tlet lapPins = {lap_pins};").unwrap();
                 writeln!(
                     &mut script_body,
                     "\tinitialize({central_lat}, {central_lon}, {zoom_value}, \
-                     runningRouteCoordinates);"
+                     runningRouteCoordinates, '{color}', lapPins);"
                 )
                 .unwrap();
                 script_body.push_str("}();\n");
@@ -1172,43 +2040,109 @@ fn IndexElement(
                     &gfile,
                     strava_activity.as_ref(),
                     connect_activity.as_ref(),
+                    connect_detail.as_ref(),
                     race_result.as_ref(),
+                    &stat_discrepancies,
+                    route.as_ref(),
+                    rpe,
+                    language,
                 ));
-                let splits_mi = Some(get_html_splits(&gfile, METERS_PER_MILE, "mi"));
-                let splits_5k = Some(get_html_splits(&gfile, 5000.0, "km"));
+                let intervals = get_html_intervals(&gfile);
+                let connect_splits = get_html_connect_splits(&connect_splits, language);
+                let splits = Some(get_html_splits(&gfile, split_distance, language));
+                let splits_5k = (split_distance != SplitDistance::FIVE_K)
+                    .then(|| get_html_splits(&gfile, SplitDistance::FIVE_K, language));
                 table_box.replace(rsx! {
                     div {
                         {file_html},
-                        {splits_mi},
+                        {intervals},
+                        {connect_splits},
+                        {splits},
                         {splits_5k},
                     }
                 });
             }
         } else if let Some(gfile) = gfile {
+            image_box.replace(get_file_plots(&report_objs, &gfile.sport.color()));
             let file_html = Some(get_file_html(
                 &gfile,
                 strava_activity.as_ref(),
                 connect_activity.as_ref(),
+                connect_detail.as_ref(),
                 race_result.as_ref(),
+                &stat_discrepancies,
+                route.as_ref(),
+                rpe,
+                language,
             ));
-            let splits_mi = Some(get_html_splits(&gfile, METERS_PER_MILE, "mi"));
-            let splits_5k = Some(get_html_splits(&gfile, 5000.0, "km"));
+            let intervals = get_html_intervals(&gfile);
+            let connect_splits = get_html_connect_splits(&connect_splits, language);
+            let splits = Some(get_html_splits(&gfile, split_distance, language));
+            let splits_5k = (split_distance != SplitDistance::FIVE_K)
+                .then(|| get_html_splits(&gfile, SplitDistance::FIVE_K, language));
             text_box.replace(rsx! {
                 div {
                     {file_html},
-                    {splits_mi},
+                    {intervals},
+                    {connect_splits},
+                    {splits},
                     {splits_5k},
                 }
             });
         }
-    } else if !reports.is_empty() {
+    } else if report_rows {
         text_box.replace(rsx! {
             table {
+                "aria-label": "Activity reports",
                 "border": "0",
-                {report_str},
+                dangerous_inner_html: REPORT_ROWS_MARKER,
             }
         });
     }
+    let milestone_banner = if milestones.is_empty() {
+        None
+    } else {
+        let items = milestones.iter().enumerate().map(|(idx, m)| {
+            let description = m.description();
+            let date = m.achieved_at.to_offsetdatetime().date();
+            rsx! {
+                li {
+                    key: "milestone-banner-key-{idx}",
+                    b {"{description}"},
+                    " on {date}",
+                }
+            }
+        });
+        Some(rsx! {
+            div {
+                class: "milestones",
+                ul {
+                    {items}
+                }
+            }
+        })
+    };
+    let ramp_banner = if ramp_warnings.is_empty() {
+        None
+    } else {
+        let items = ramp_warnings.iter().enumerate().map(|(idx, r)| {
+            let description = r.description();
+            rsx! {
+                li {
+                    key: "ramp-banner-key-{idx}",
+                    "Mileage ramping up too fast, {description}",
+                }
+            }
+        });
+        Some(rsx! {
+            div {
+                class: "ramp-warning",
+                ul {
+                    {items}
+                }
+            }
+        })
+    };
     let upload_button = if is_demo {
         None
     } else {
@@ -1217,8 +2151,10 @@ fn IndexElement(
                 action: "/garmin/upload_file",
                 method: "post",
                 enctype: "multipart/form-data",
+                "onsubmit": "return uploadFileSubmit(this);",
                 input {
                     "type": "file",
+                    "aria-label": "Activity file",
                     name: "filename",
                 },
                 input {"type": "submit"},
@@ -1245,6 +2181,11 @@ fn IndexElement(
             }
         },
         body {
+            a {
+                class: "skip-link",
+                href: "#garmin_text_box",
+                "Skip to content",
+            },
             h3 {
                 {buttons},
             },
@@ -1253,6 +2194,7 @@ fn IndexElement(
                 method: "get",
                 input {
                     "type": "text",
+                    "aria-label": "Filter",
                     name: "cmd",
                     id: "garmin_filter",
                 },
@@ -1264,9 +2206,12 @@ fn IndexElement(
                 }
             }
             {history_buttons},
+            {milestone_banner},
+            {ramp_banner},
             br {
                 {upload_button},
                 {button_str},
+                {share_button},
             },
             h1 {
                 style: "text-align: center",
@@ -1276,6 +2221,7 @@ fn IndexElement(
             script {src: "/garmin/scripts/garmin_scripts.js"},
             script {src: "/garmin/scripts/line_plot.js"},
             script {src: "/garmin/scripts/scatter_plot.js"},
+            script {src: "/garmin/scripts/combined_plot.js"},
             script {src: "/garmin/scripts/time_series.js"},
             script {
                 "type": "text/javascript",
@@ -1285,6 +2231,7 @@ fn IndexElement(
             {script_box},
             div {
                 id: "garmin_text_box",
+                "tabindex": "-1",
                 {text_box},
             },
             div {
@@ -1299,21 +2246,181 @@ fn IndexElement(
     }
 }
 
+/// Argument for the optional overlay of `time_series`, `null` without a
+/// selected series
+fn biomarker_overlay_js(overlay: &BiomarkerOverlay) -> String {
+    let tformat = format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second][offset_hour \
+         sign:mandatory]:[offset_minute]"
+    );
+    let series = match &overlay.selected {
+        Some(series) => series,
+        None => return "null".into(),
+    };
+    let mut data: Vec<(String, f64)> = overlay
+        .measurements
+        .iter()
+        .map(|m| {
+            let key = m.datetime.format(tformat).unwrap_or_else(|_| String::new());
+            (key, m.value)
+        })
+        .collect();
+    data.shrink_to_fit();
+    serde_json::to_string(&serde_json::json!({
+        "name": series.name,
+        "units": series.units,
+        "data": data,
+    }))
+    .unwrap_or_else(|_| "null".into())
+}
+
+/// Projected weight band drawn after the last measurement of the weight plot
+fn goal_projection_js(projection: &GoalProjection) -> StackString {
+    let tformat = format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second][offset_hour \
+         sign:mandatory]:[offset_minute]"
+    );
+    let data: Vec<_> = projection
+        .band(10)
+        .into_iter()
+        .map(|(datetime, weight, low, high)| {
+            let key = datetime.format(tformat).unwrap_or_else(|_| String::new());
+            (key, weight, low, high)
+        })
+        .collect();
+    serde_json::to_string(&serde_json::json!({ "data": data }))
+        .map_or_else(|_| "null".into(), Into::into)
+}
+
+fn biomarker_selector(overlay: &BiomarkerOverlay) -> Element {
+    if overlay.series.is_empty() {
+        return rsx! {""};
+    }
+    let current = overlay.selected.as_ref().map_or("", |s| s.name.as_str());
+    let mut names: Vec<&str> = overlay
+        .series
+        .iter()
+        .map(|s| s.name.as_str())
+        .filter(|s| *s != current)
+        .collect();
+    if current.is_empty() {
+        names.insert(0, "");
+    } else {
+        names.insert(0, current);
+        names.insert(1, "");
+    }
+    let options = names.into_iter().enumerate().map(|(idx, name)| {
+        let label = if name.is_empty() { "no overlay" } else { name };
+        rsx! {
+            option {
+                key: "overlay-option-key-{idx}",
+                value: "{name}",
+                "{label}",
+            }
+        }
+    });
+    rsx! {
+        select {
+            "aria-label": "Biomarker overlay",
+            id: "overlay_selector",
+            {options},
+        }
+    }
+}
+
+fn get_file_plots(report_objs: &ReportObjects, color: &str) -> Element {
+    let plot_opts = get_plot_opts(report_objs);
+    let graphs = plot_opts.into_iter().enumerate().filter_map(|(idx, opts)| {
+        let markers =
+            serde_json::to_string(&opts.markers.unwrap_or(&[])).unwrap_or_else(|_| String::new());
+        let summary = opts.summary();
+        if opts.is_combined() {
+            let title = &opts.title;
+            let xlabel = &opts.xlabel;
+            let series = serde_json::to_string(&opts.series).unwrap_or_else(|_| String::new());
+            let mut script_body = String::new();
+            script_body.push_str("\n!function(){\n");
+            writeln!(&mut script_body, "\tlet series = {series};").unwrap();
+            writeln!(
+                &mut script_body,
+                "\tcombined_plot(series, '{title}', '{xlabel}', '{color}', {markers}, \
+                 '{summary}');"
+            )
+            .unwrap();
+            script_body.push_str("}();\n");
+            return Some(rsx! {
+                script {
+                    key: "plot-key-{idx}",
+                    dangerous_inner_html: "{script_body}",
+                }
+            });
+        }
+        let data = opts.data.as_ref()?;
+        if data.is_empty() {
+            return None;
+        }
+        let title = &opts.title;
+        let xlabel = &opts.xlabel;
+        let ylabel = &opts.ylabel;
+        if let Some(ScatterPlotData { data, xstep, ystep }) = generate_plot_data(&opts, data) {
+            let data = serde_json::to_string(&data).unwrap_or_else(|_| String::new());
+            let mut script_body = String::new();
+            script_body.push_str("\n!function(){\n");
+            writeln!(&mut script_body, "\tlet data = {data};").unwrap();
+            writeln!(
+                &mut script_body,
+                "\tscatter_plot(data, '{title}', '{xlabel}', '{ylabel}', {xstep}, \
+                 {ystep}, '{color}', '{summary}');"
+            )
+            .unwrap();
+            script_body.push_str("}();\n");
+            Some(rsx! {
+                script {
+                    key: "plot-key-{idx}",
+                    dangerous_inner_html: "{script_body}",
+                }
+            })
+        } else {
+            let mut script_body = String::new();
+            script_body.push_str("\n!function(){\n");
+            let data = serde_json::to_string(&data).unwrap_or_else(|_| String::new());
+            writeln!(&mut script_body, "\tlet data = {data};").unwrap();
+            writeln!(
+                &mut script_body,
+                "\tline_plot(data, '{title}', '{xlabel}', '{ylabel}', '{color}', {markers}, \
+                 '{summary}');"
+            )
+            .unwrap();
+            script_body.push_str("}();\n");
+            Some(rsx! {
+                script {
+                    key: "plot-key-{idx}",
+                    dangerous_inner_html: "{script_body}",
+                }
+            })
+        }
+    });
+    rsx! {
+        {graphs}
+    }
+}
+
 fn get_file_html(
     gfile: &GarminFile,
     strava_activity: Option<&StravaActivity>,
     connect_activity: Option<&GarminConnectActivity>,
+    connect_detail: Option<&GarminConnectActivityDetail>,
     race_result: Option<&RaceResults>,
+    stat_discrepancies: &[StatDiscrepancy],
+    route: Option<&MatchedRoute>,
+    rpe: Option<i16>,
+    language: Language,
 ) -> Element {
     let dt = gfile.begin_datetime;
     let sp = {
         let current_sport = gfile.sport.to_str();
-        let mut sport_types: Vec<_> = get_sport_type_map()
-            .keys()
-            .filter_map(|s| if *s == current_sport { None } else { Some(*s) })
-            .collect();
-        sport_types.shrink_to_fit();
-        sport_types.sort_unstable();
+        let mut sport_types = get_sport_names();
+        sport_types.retain(|s| *s != current_sport);
         sport_types.insert(0, current_sport);
         let sport_types = sport_types.into_iter().enumerate().map(|(idx, s)| {
             rsx! {
@@ -1326,6 +2433,7 @@ fn get_file_html(
         });
         rsx! {
             select {
+                "aria-label": "Sport",
                 id: "sport_select",
                 {sport_types},
             }
@@ -1351,15 +2459,33 @@ fn get_file_html(
     } else {
         let filename = &gfile.filename;
         rsx! {
-            a {
-                button {
-                    "type": "submit",
-                    "onclick": "createStravaActivity('{filename}');",
-                    "create",
-                }
+            button {
+                "type": "submit",
+                "onclick": "createStravaActivity('{filename}');",
+                "create",
             }
         }
     };
+    let (kudos, comments) = strava_activity.map_or((0, 0), |a| (a.kudos_count, a.comment_count));
+    let photos = strava_activity
+        .filter(|a| !a.photo_urls.is_empty())
+        .map(|a| {
+            rsx! {
+                div {
+                    {a.photo_urls.iter().enumerate().map(|(idx, url)| rsx! {
+                        a {
+                            key: "photo-key-{idx}",
+                            href: "{url}",
+                            target: "_blank",
+                            img {
+                                src: "{url}",
+                                height: "150",
+                            }
+                        }
+                    })}
+                }
+            }
+        });
     let gid = connect_activity.as_ref().map(|connect_activity| {
         let activity_id = connect_activity.activity_id;
         rsx! {
@@ -1373,6 +2499,33 @@ fn get_file_html(
     let gstep = connect_activity
         .as_ref()
         .map_or(0, |x| x.steps.unwrap_or(0));
+    // the reported weather beats the device sensor, which reads warm from
+    // body heat
+    let avg_temperature = connect_detail
+        .and_then(|d| d.temperature)
+        .or_else(|| gfile.avg_temperature())
+        .map_or_else(StackString::new, |t| format_sstr!("{t:.1} C"));
+    let connect_details = connect_detail.and_then(|detail| {
+        let values: Vec<_> = [
+            detail
+                .weather_string()
+                .map(|w| format_sstr!("Weather: {w}")),
+            detail.gear.as_ref().map(|g| format_sstr!("Gear: {g}")),
+            detail.device_string().map(|d| format_sstr!("Device: {d}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if values.is_empty() {
+            None
+        } else {
+            let values = values.join(", ");
+            Some(rsx! {
+                div {"{values}"}
+            })
+        }
+    });
+    let split = split_differential(gfile).map_or_else(StackString::new, format_split_differential);
     let import_button = if race_result.is_none() && gfile.sport == SportTypes::Running {
         let filename = &gfile.filename;
         Some(rsx! {
@@ -1385,32 +2538,227 @@ fn get_file_html(
     } else {
         None
     };
+    let pool_length_form = if gfile.sport == SportTypes::Swimming {
+        Some(rsx! {
+            div {
+                "Pool length (m) ",
+                input {
+                    "type": "number",
+                    "aria-label": "Pool length",
+                    id: "pool_length",
+                    step: "any",
+                    value: "25",
+                    title: "Leave empty to remove the correction",
+                },
+                " set on watch (m) ",
+                input {
+                    "type": "number",
+                    "aria-label": "Recorded pool length",
+                    id: "recorded_pool_length",
+                    step: "any",
+                    value: "25",
+                },
+                button {
+                    "type": "submit",
+                    "onclick": "addGarminCorrectionPoolLength('{begin_datetime}')",
+                    "Correct Pool Length",
+                },
+            }
+        })
+    } else {
+        None
+    };
 
-    let labels = [
-        "Sport",
-        "Lap",
-        "Distance",
-        "Duration",
-        "Calories",
-        "Time",
-        "Pace / mi",
-        "Pace / km",
-        "Heart Rate",
+    let clothing_form = {
+        let filename = &gfile.filename;
+        let comfort = [
+            ComfortRating::JustRight,
+            ComfortRating::TooCold,
+            ComfortRating::TooHot,
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(idx, c)| {
+            let value = c.to_str();
+            let name = c.display_name();
+            rsx! {
+                option {
+                    key: "comfort-key-{idx}",
+                    value: "{value}",
+                    "{name}",
+                }
+            }
+        });
+        rsx! {
+            div {
+                "Clothing ",
+                input {
+                    "type": "text",
+                    "aria-label": "Clothing",
+                    id: "clothing_text",
+                },
+                select {
+                    id: "comfort_select",
+                    {comfort},
+                },
+                button {
+                    "type": "submit",
+                    "onclick": "clothingLog('{filename}');",
+                    "Log Clothing",
+                },
+            }
+        }
+    };
+
+    let rpe_form = {
+        let filename = &gfile.filename;
+        let (low, high) = RPE_RANGE;
+        let value = rpe.map_or_else(StackString::new, StackString::from_display);
+        rsx! {
+            div {
+                "Perceived exertion ",
+                input {
+                    "type": "number",
+                    "aria-label": "Rating of perceived exertion",
+                    id: "rpe_input",
+                    min: "{low}",
+                    max: "{high}",
+                    step: "1",
+                    value: "{value}",
+                },
+                button {
+                    "type": "submit",
+                    "onclick": "activityRpe('{filename}');",
+                    "Rate RPE",
+                },
+            }
+        }
+    };
+
+    let stat_sources = if stat_discrepancies.is_empty() {
+        None
+    } else {
+        let filename = &gfile.filename;
+        let rows = stat_discrepancies.iter().enumerate().map(|(idx, d)| {
+            let name = d.stat.display_name();
+            let stat = d.stat.to_str();
+            let values = d.values.iter().enumerate().map(|(jdx, (source, value))| {
+                let value = match d.stat {
+                    ActivityStat::Distance => format_sstr!("{:.2} mi", value / METERS_PER_MILE),
+                    ActivityStat::Calories => format_sstr!("{value:.0} cal"),
+                };
+                let source_name = source.display_name();
+                let choice = if d.chosen == Some(*source) {
+                    rsx! { b {" (chosen)"} }
+                } else {
+                    let source = source.to_str();
+                    rsx! {
+                        button {
+                            "type": "submit",
+                            "onclick": "chooseStatSource('{filename}', '{stat}', '{source}');",
+                            "Use",
+                        }
+                    }
+                };
+                rsx! {
+                    td {
+                        key: "stat-source-value-key-{jdx}",
+                        "{source_name}: {value} ",
+                        {choice},
+                    }
+                }
+            });
+            rsx! {
+                tr {
+                    key: "stat-source-key-{idx}",
+                    td {"{name}"},
+                    {values},
+                }
+            }
+        });
+        Some(rsx! {
+            div {
+                "Sources disagree, choose the value used in reports",
+                table {
+                    "border": "1",
+                    class: "dataframe",
+                    caption {class: "visually-hidden", "Stat sources"},
+                    tbody { {rows} },
+                }
+            }
+        })
+    };
+
+    let route_link = route.map(|route| {
+        let id = route.id;
+        let name = &route.name;
+        rsx! {
+            div {
+                a {
+                    href: "/garmin/routes/progression?route_id={id}",
+                    target: "_blank",
+                    "Route best times ({name})",
+                }
+            }
+        }
+    });
+
+    let summary_caption = tr(language, "Activity summary");
+    let laps_caption = tr(language, "Laps");
+    let header_labels = [
+        tr(language, "Start Time"),
+        tr(language, "Sport"),
+        "",
+        "GarminConnectID",
+        tr(language, "Garmin Steps"),
+        "StravaID",
+        tr(language, "Kudos"),
+        tr(language, "Comments"),
+        tr(language, "Avg Temp"),
+        tr(language, "Split"),
     ];
+    let labels: &[&'static str] = if gfile.sport == SportTypes::Swimming {
+        &[
+            "Sport",
+            "Lap",
+            "Distance",
+            "Duration",
+            "Calories",
+            "Time",
+            "Pace / 100m",
+            "Heart Rate",
+        ]
+    } else {
+        &[
+            "Sport",
+            "Lap",
+            "Distance",
+            "Duration",
+            "Calories",
+            "Time",
+            "Pace / mi",
+            "Pace / km",
+            "Heart Rate",
+        ]
+    };
 
     rsx! {
         table {
             "border": "1",
             class: "dataframe",
+            caption {class: "visually-hidden", "{summary_caption}"},
             thead {
                 tr {
                     "style": "text-align: center;",
-                    th {"Start Time"},
-                    th {"Sport"},
-                    th {},
-                    th {"GarminConnectID"},
-                    th {"Garmin Steps"},
-                    th {"StravaID"},
+                    {header_labels.iter().enumerate().map(|(idx, label)| {
+                        rsx! {
+                            th {
+                                key: "header-key-{idx}",
+                                "scope": "col",
+                                "{label}"
+                            },
+                        }
+                    })}
                 }
             },
             tbody {
@@ -1422,21 +2770,35 @@ fn get_file_html(
                     td { {gid} },
                     td {"{gstep}"},
                     td { {sid} },
+                    td {"{kudos}"},
+                    td {"{comments}"},
+                    td {"{avg_temperature}"},
+                    td {"{split}"},
                 }
             }
         },
+        {connect_details},
+        {photos},
         {import_button},
+        {pool_length_form},
+        {clothing_form},
+        {rpe_form},
+        {stat_sources},
+        {route_link},
         br {
             table {
                 "border": "1",
                 class: "dataframe",
+                caption {class: "visually-hidden", "{laps_caption}"},
                 thead {
                     tr {
                         "type": "text-align: center;",
                         {labels.iter().enumerate().map(|(idx, label)| {
+                            let label = tr(language, *label);
                             rsx! {
                                 th {
                                     key: "label-key-{idx}",
+                                    "scope": "col",
                                     "{label}"
                                 },
                             }
@@ -1453,7 +2815,14 @@ fn get_file_html(
                             format_sstr!("{}", lap.lap_calories),
                             format_sstr!("{:.2} min", lap.lap_duration / 60.),
                         ];
-                        if lap.lap_distance > 0.0 {
+                        if gfile.sport == SportTypes::Swimming {
+                            if let Some(pace) = lap.pace_per_100m() {
+                                values.push(format_sstr!(
+                                    "{} / 100m",
+                                    print_h_m_s(pace, false).unwrap_or_else(|_| "".into())
+                                ));
+                            }
+                        } else if lap.lap_distance > 0.0 {
                             values.push(format_sstr!(
                                 "{} / mi",
                                 print_h_m_s(
@@ -1471,6 +2840,15 @@ fn get_file_html(
                         if let Some(lap_avg_hr) = lap.lap_avg_hr {
                             values.push(format_sstr!("{lap_avg_hr} bpm"));
                         }
+                        if let Some(cadence) = lap.avg_cadence_string(gfile.sport) {
+                            values.push(cadence);
+                        }
+                        if let Some(lap_avg_power) = lap.lap_avg_power {
+                            values.push(format_sstr!("{lap_avg_power:.0} W"));
+                        }
+                        if let Some(lap_normalized_power) = lap.lap_normalized_power {
+                            values.push(format_sstr!("{lap_normalized_power:.0} W NP"));
+                        }
                         rsx! {
                             tr {
                                 key: "lap-key-{idx}",
@@ -1490,8 +2868,168 @@ fn get_file_html(
     }
 }
 
-fn get_html_splits(gfile: &GarminFile, split_distance_in_meters: f64, label: &str) -> Element {
-    let labels = [
+/// Work and recovery table for interval workouts, `None` for anything else
+fn get_html_intervals(gfile: &GarminFile) -> Option<Element> {
+    let (source, intervals) = get_intervals(gfile)?;
+    let caption = match source {
+        IntervalSource::Laps => "Intervals (from laps)",
+        IntervalSource::Detected => "Intervals (detected from pace)",
+    };
+    let labels = ["Rep", "Type", "Distance", "Time", "Heart Rate", "Pace / mi"];
+    let rows = intervals.into_iter().enumerate().map(|(idx, interval)| {
+        let rep = interval
+            .rep
+            .map_or_else(StackString::new, StackString::from_display);
+        let kind = interval.kind;
+        let distance = interval.distance / METERS_PER_MILE;
+        let time = print_h_m_s(interval.duration, false).unwrap_or_else(|_| "".into());
+        let heart_rate = interval
+            .avg_heart_rate
+            .map_or_else(StackString::new, |hr| format_sstr!("{hr:.0} bpm"));
+        let pace = interval
+            .pace()
+            .and_then(|pace| print_h_m_s(pace, false).ok())
+            .unwrap_or_default();
+        let style = if interval.rep.is_some() {
+            "text-align: center; font-weight: bold;"
+        } else {
+            "text-align: center;"
+        };
+        rsx! {
+            tr {
+                key: "interval-key-{idx}",
+                "style": "{style}",
+                td {"{rep}"},
+                td {"{kind}"},
+                td {"{distance:0.2} mi"},
+                td {"{time}"},
+                td {"{heart_rate}"},
+                td {"{pace}"},
+            }
+        }
+    });
+
+    Some(rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {"{caption}"},
+            thead {
+                tr {
+                    "style": "text-align: center;",
+                    {labels.iter().enumerate().map(|(idx, label)| {
+                        rsx! {
+                            th {
+                                key: "label-key-{idx}",
+                                "scope": "col",
+                                "{label}",
+                            }
+                        }
+                    })},
+                }
+            },
+            tbody {
+                {rows},
+            }
+        }
+    })
+}
+
+/// Splits recorded by Garmin Connect, `None` if none were captured
+fn get_html_connect_splits(splits: &[GarminConnectSplit], language: Language) -> Option<Element> {
+    if splits.is_empty() {
+        return None;
+    }
+    let labels = [
+        "Lap",
+        "Distance",
+        "Duration",
+        "Pace / mi",
+        "Calories",
+        "Heart Rate",
+        "Cadence",
+        "Power",
+    ];
+    let rows = splits.iter().enumerate().map(|(idx, split)| {
+        let mut values = vec![
+            format_sstr!("{}", split.split_index),
+            format_sstr!("{:.2} mi", split.distance / METERS_PER_MILE),
+            print_h_m_s(split.duration, true).unwrap_or_else(|_| "".into()),
+        ];
+        values.push(if split.distance > 0.0 {
+            print_h_m_s(split.duration / (split.distance / METERS_PER_MILE), false)
+                .unwrap_or_else(|_| "".into())
+        } else {
+            StackString::new()
+        });
+        values.push(
+            split
+                .calories
+                .map_or_else(StackString::new, |c| format_sstr!("{c:.0}")),
+        );
+        values.push(
+            split
+                .average_hr
+                .map_or_else(StackString::new, |h| format_sstr!("{h:.0} bpm")),
+        );
+        values.push(
+            split
+                .average_cadence
+                .map_or_else(StackString::new, |c| format_sstr!("{c:.0} /min")),
+        );
+        values.push(
+            split
+                .average_power
+                .map_or_else(StackString::new, |p| format_sstr!("{p:.0} W")),
+        );
+        rsx! {
+            tr {
+                key: "connect-split-key-{idx}",
+                "style": "text-align: center;",
+                {values.iter().enumerate().map(|(i, v)| rsx! {
+                    td {
+                        key: "v-key-{i}",
+                        "{v}"
+                    }
+                })},
+            }
+        }
+    });
+    Some(rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {"Garmin Connect splits"},
+            thead {
+                tr {
+                    "style": "text-align: center;",
+                    {labels.iter().enumerate().map(|(idx, label)| {
+                        let label = tr(language, *label);
+                        rsx! {
+                            th {
+                                key: "label-key-{idx}",
+                                "scope": "col",
+                                "{label}",
+                            }
+                        }
+                    })},
+                }
+            },
+            tbody {
+                {rows},
+            }
+        }
+    })
+}
+
+fn get_html_splits(
+    gfile: &GarminFile,
+    split_distance: SplitDistance,
+    language: Language,
+) -> Element {
+    let split_distance_in_meters = split_distance.meters();
+    let splits_caption = tr(language, "Splits");
+    let labels = [
         "Split",
         "Time",
         "Pace / mi",
@@ -1499,11 +3037,11 @@ fn get_html_splits(gfile: &GarminFile, split_distance_in_meters: f64, label: &st
         "Marathon Time",
         "Heart Rate",
     ];
-    let values = get_splits(gfile, split_distance_in_meters, label, true)
+    let values = get_distance_splits(gfile, split_distance, true)
         .into_iter()
         .enumerate()
         .map(move |(idx, val)| {
-            let dis = val.split_distance as i32;
+            let dis = split_distance.marker(val.split_distance as usize);
             let tim = val.time_value;
             let hrt = val.avg_heart_rate.unwrap_or(0.0) as i32;
             let tim0 = print_h_m_s(tim, true).unwrap_or_else(|_| "".into());
@@ -1519,7 +3057,7 @@ fn get_html_splits(gfile: &GarminFile, split_distance_in_meters: f64, label: &st
             rsx! {
                 tr {
                     key: "split-key-{idx}",
-                    td {"{dis} {label}"},
+                    td {"{dis}"},
                     td {"{tim0}"},
                     td {"{tim1}"},
                     td {"{tim2}"},
@@ -1533,13 +3071,16 @@ fn get_html_splits(gfile: &GarminFile, split_distance_in_meters: f64, label: &st
         table {
             "border": "1",
             class: "dataframe",
+            caption {class: "visually-hidden", "{splits_caption}"},
             thead {
                 tr {
                     "style": "text-align: center;",
                     {labels.iter().enumerate().map(|(idx, label)| {
+                        let label = tr(language, *label);
                         rsx! {
                             th {
                                 key: "label-key-{idx}",
+                                "scope": "col",
                                 "{label}",
                             }
                         }
@@ -1553,7 +3094,11 @@ fn get_html_splits(gfile: &GarminFile, split_distance_in_meters: f64, label: &st
     }
 }
 
-fn generate_history_buttons(history_vec: &[StackString]) -> Element {
+fn generate_history_buttons(
+    history_vec: &[StackString],
+    pinned: &[StackString],
+    is_demo: bool,
+) -> Element {
     let local = DateTimeWrapper::local_tz();
     let local = OffsetDateTime::now_utc().to_timezone(local).date();
     let year = local.year();
@@ -1564,84 +3109,160 @@ fn generate_history_buttons(history_vec: &[StackString]) -> Element {
         (year - 1, 12)
     };
     let default_string = format_sstr!("{prev_year:04}-{prev_month:02},{year:04}-{month:02},week");
-    let mut history = history_vec.to_vec();
-    if !history.contains(&default_string) {
-        history.insert(0, default_string);
+    let (mut history, unpinned): (Vec<_>, Vec<_>) = history_vec
+        .iter()
+        .cloned()
+        .partition(|filter| pinned.contains(filter));
+    if !history_vec.contains(&default_string) {
+        history.push(default_string);
     }
+    history.extend(unpinned);
     rsx! {
         {history.into_iter().enumerate().map(move |(idx, filter)| {
+            let is_pinned = pinned.contains(&filter);
+            let label = if is_pinned {
+                format_sstr!("\u{1f4cc} {filter}")
+            } else {
+                filter.clone()
+            };
+            let controls = if is_demo {
+                None
+            } else {
+                let pin_label = if is_pinned { "unpin" } else { "pin" };
+                let pin = !is_pinned;
+                Some(rsx! {
+                    button {
+                        "type": "submit",
+                        "aria-label": "{pin_label} {filter}",
+                        "data-filter": "{filter}",
+                        "onclick": "pinHistory(this.dataset.filter, {pin})",
+                        "{pin_label}",
+                    },
+                    button {
+                        "type": "submit",
+                        "aria-label": "delete {filter}",
+                        "data-filter": "{filter}",
+                        "onclick": "deleteHistory(this.dataset.filter)",
+                        "x",
+                    },
+                })
+            };
             rsx! {
-                button {
+                span {
                     key: "history-key-{idx}",
-                    "type": "submit",
-                    "onclick": "send_command('filter={filter}')",
-                    "{filter}",
+                    button {
+                        "type": "submit",
+                        "onclick": "send_command('filter={filter}')",
+                        "{label}",
+                    },
+                    {controls},
                 }
             }
         })}
     }
 }
 
-fn get_buttons(demo: bool) -> Element {
+fn get_buttons(demo: bool, language: Language) -> Element {
+    let command_buttons = |buttons: &'static [(&'static str, &'static str)]| {
+        buttons
+            .iter()
+            .enumerate()
+            .map(move |(idx, (onclick, label))| {
+                let label = tr(language, *label);
+                rsx! {
+                    button {
+                        key: "command-button-key-{idx}",
+                        "type": "submit",
+                        "onclick": "{onclick}",
+                        "{label}",
+                    }
+                }
+            })
+    };
     let top_buttons: Option<Element> = if demo {
         None
     } else {
+        let buttons = command_buttons(&[
+            ("garmin_sync();", "Sync with S3"),
+            ("stravaAthlete();", "Strava Athlete"),
+            ("heartrateSync();", "Scale sync"),
+            ("quarantinedFiles();", "Quarantine"),
+            ("adminStats();", "Admin Stats"),
+            ("coverageGaps();", "Coverage Gaps"),
+            ("cleanupDryRun();", "Cleanup"),
+            ("milestones();", "Milestones"),
+            ("travelMap();", "Travel Map"),
+            ("regionMap(false);", "Region Map"),
+            ("mostKudoed();", "Most Kudos"),
+            ("trips();", "Trips"),
+            (
+                "yearly_comparison_plot('distance', 'all');",
+                "Yearly Comparison",
+            ),
+            ("trainingPattern('running');", "Training Pattern"),
+            ("heartRateZones();", "Heart Rate Zones"),
+            ("clothingReport('');", "Clothing"),
+            ("integrations();", "Integrations"),
+            ("linkReview();", "Link Review"),
+        ]);
         Some(rsx! {
-            button {
-                "type": "submit",
-                "onclick": "garmin_sync();",
-                "Sync with S3",
-            },
-            button {
-                "type": "submit",
-                "onclick": "stravaAthlete();",
-                "Strava Athlete",
-            },
-            button {
-                "type": "submit",
-                "onclick": "heartrateSync();",
-                "Scale sync",
-            },
+            {buttons}
         })
     };
+    let plot_buttons = command_buttons(&[
+        ("scale_measurement_plots(0);", "Scale Plots"),
+        ("heartrate_stat_plot(0);", "Heart Rate Stats"),
+        ("heartrate_plot();", "Heart Rate Plots"),
+        ("race_result_plot_personal();", "Race Result Plot"),
+        (
+            "power_curve_plot('pace', 'running', 'six_weeks');",
+            "Power Curve",
+        ),
+    ]);
+    let filter_buttons = command_buttons(&[
+        ("send_command('filter=latest');", "latest"),
+        ("send_command('filter=sport');", "sport"),
+    ]);
+    let language_label = tr(language, "Language");
+    // the current language is listed first so it shows as selected
+    let mut languages = Language::all().to_vec();
+    languages.retain(|l| *l != language);
+    languages.insert(0, language);
+    let language_options = languages.into_iter().enumerate().map(|(idx, l)| {
+        let code = l.to_str();
+        let name = l.native_name();
+        rsx! {
+            option {
+                key: "language-key-{idx}",
+                value: "{code}",
+                "{name}",
+            }
+        }
+    });
+    let on_language = if demo {
+        "setLanguageDemo(this.value);"
+    } else {
+        "setLanguage(this.value);"
+    };
     rsx! {
         br {
             {top_buttons},
         }
-        button {
-            "type": "submit",
-            "onclick": "scale_measurement_plots(0);",
-            "Scale Plots",
-        },
-        button {
-            "type": "submit",
-            "onclick": "heartrate_stat_plot(0);",
-            "Heart Rate Stats",
-        },
-        button {
-            "type": "submit",
-            "onclick": "heartrate_plot();",
-            "Heart Rate Plots",
-        },
-        button {
-            "type": "submit",
-            "onclick": "race_result_plot_personal();",
-            "Race Result Plot",
-        },
+        {plot_buttons},
         button {
             name: "garminconnectoutput",
             id: "garminconnectoutput",
+            "aria-live": "polite",
             dangerous_inner_html: "&nbsp;",
         },
-        button {
-            "type": "submit",
-            "onclick": "send_command('filter=latest');",
-            "latest",
-        },
-        button {
-            "type": "submit",
-            "onclick": "send_command('filter=sport');",
-            "sport",
+        {filter_buttons},
+        label {
+            " {language_label} ",
+            select {
+                id: "language_select",
+                "onchange": "{on_language}",
+                {language_options},
+            }
         }
     }
 }
@@ -1728,6 +3349,9 @@ fn create_analysis_plot(model: &RaceResultAnalysis, is_demo: bool) -> Element {
                 .as_ref()
                 .map_or("", StackString::as_str)
                 .into();
+            let split = model
+                .split_differential(result)
+                .map_or_else(StackString::new, |d| format_sstr!("{:+.1}%", d * 100.0));
             let flag = result.race_flag;
             let flag = if is_demo {
                 rsx! {"{flag}"}
@@ -1739,6 +3363,11 @@ fn create_analysis_plot(model: &RaceResultAnalysis, is_demo: bool) -> Element {
                         id: "race_flag_{id}",
                         "onclick": "flipRaceResultFlag({id});",
                         "{flag}"
+                    },
+                    button {
+                        "type": "button",
+                        "onclick": "raceResultNotes('{id}');",
+                        "Notes"
                     }
                 }
             };
@@ -1759,11 +3388,62 @@ fn create_analysis_plot(model: &RaceResultAnalysis, is_demo: bool) -> Element {
                         {date},
                     },
                     td {"{name}"},
+                    td {
+                        "align": "center",
+                        "{split}",
+                    },
                     td { {flag} },
                 }
             }
         });
 
+    let split_tendency = model.split_tendency();
+    let split_tendency = if split_tendency.is_empty() {
+        None
+    } else {
+        let rows = split_tendency
+            .into_iter()
+            .enumerate()
+            .map(|(idx, tendency)| {
+                let distance = f64::from(tendency.race_distance) / METERS_PER_MILE;
+                let race_count = tendency.race_count;
+                let negative_count = tendency.negative_count;
+                let positive_count = tendency.positive_count;
+                let mean = tendency.mean_differential * 100.0;
+                rsx! {
+                    tr {
+                        key: "split-tendency-key-{idx}",
+                        td {
+                            "align": "right",
+                            "{distance:0.1}",
+                        },
+                        td {"{race_count}"},
+                        td {"{negative_count}"},
+                        td {"{positive_count}"},
+                        td {"{mean:+.1}%"},
+                    }
+                }
+            });
+        Some(rsx! {
+            br {
+                table {
+                    "border": "1",
+                    caption {class: "visually-hidden", "Split summary by distance"},
+                    thead {
+                        th {"scope": "col", "Distance (mi)"},
+                        th {"scope": "col", "Races"},
+                        th {"scope": "col", "Negative Splits"},
+                        th {"scope": "col", "Positive Splits"},
+                        th {"scope": "col", "Mean Second Half"},
+                    },
+                    tbody {
+                        {rows},
+                    }
+                }
+            }
+        })
+    };
+
     let x_vals: Vec<f64> = x_vals.map(|x| x * METERS_PER_MILE).to_vec();
     let mut y_nom: Vec<(f64, f64)> = y_nom
         .iter()
@@ -1845,10 +3525,11 @@ fn create_analysis_plot(model: &RaceResultAnalysis, is_demo: bool) -> Element {
     let tables = rsx! {
         table {
             "border": "1",
+            caption {class: "visually-hidden", "Pace by distance"},
             thead {
-                th {"Distance (mi)"},
-                th {"Pace (min/mi)"},
-                th {"Time"},
+                th {"scope": "col", "Distance (mi)"},
+                th {"scope": "col", "Pace (min/mi)"},
+                th {"scope": "col", "Time"},
             },
             tbody {
                 {pace_results},
@@ -1857,19 +3538,22 @@ fn create_analysis_plot(model: &RaceResultAnalysis, is_demo: bool) -> Element {
         br {
             table {
                 "border": "1",
+                caption {class: "visually-hidden", "Race results"},
                 thead {
-                    th {"Distance (mi)"},
-                    th {"Time"},
-                    th {"Pace (min/mi)"},
-                    th {"Date"},
-                    th {"Name"},
-                    th {"Flag"},
+                    th {"scope": "col", "Distance (mi)"},
+                    th {"scope": "col", "Time"},
+                    th {"scope": "col", "Pace (min/mi)"},
+                    th {"scope": "col", "Date"},
+                    th {"scope": "col", "Name"},
+                    th {"scope": "col", "Split"},
+                    th {"scope": "col", "Flag"},
                 },
                 tbody {
                     {race_results},
                 }
             }
-        }
+        },
+        {split_tendency},
     };
 
     let scripts = rsx! {
@@ -1888,90 +3572,726 @@ fn create_analysis_plot(model: &RaceResultAnalysis, is_demo: bool) -> Element {
     }
 }
 
-/// # Errors
-/// Returns error if formatting fails
-pub fn create_fitbit_table(heartrate_values: Vec<FitbitHeartRate>) -> Result<String, Error> {
-    let mut app = VirtualDom::new_with_props(
-        FitbitTableElement,
-        FitbitTableElementProps { heartrate_values },
-    );
-    app.rebuild_in_place();
-    let mut renderer = dioxus_ssr::Renderer::default();
-    let mut buffer = String::new();
-    renderer
-        .render_to(&mut buffer, &app)
-        .map_err(Into::<Error>::into)?;
-    Ok(buffer)
-}
-
-#[component]
-fn FitbitTableElement(heartrate_values: Vec<FitbitHeartRate>) -> Element {
-    rsx! {
-        table {
-            "border": "1",
-            thead {
-                th {"Datetime"},
-                th {"Heart Rate"},
-            },
-            tbody {
-                {heartrate_values.iter().enumerate().map(|(idx, entry)| {
-                    let datetime = entry.datetime;
-                    let heartrate = entry.value;
-                    rsx! {
-                        tr {
-                            key: "heartrate-values-key-{idx}",
-                            td {"{datetime}"},
-                            td {"{heartrate}"},
-                        }
-                    }
-                })},
-            }
-        }
+fn create_trip_map(routes: &[TripRoute]) -> Element {
+    if routes.is_empty() {
+        return rsx! {""};
     }
-}
-
-/// # Errors
-/// Returns error if formatting fails
-pub fn table_body(body: StackString) -> Result<String, Error> {
-    let mut app = VirtualDom::new_with_props(TableElement, TableElementProps { body });
-    app.rebuild_in_place();
-    let mut renderer = dioxus_ssr::Renderer::default();
-    let mut buffer = String::new();
-    renderer
-        .render_to(&mut buffer, &app)
-        .map_err(Into::<Error>::into)?;
-    Ok(buffer)
-}
-
-#[component]
-fn TableElement(body: StackString) -> Element {
+    let routes: Vec<_> = routes
+        .iter()
+        .map(|route| {
+            serde_json::json!({
+                "filename": route.filename,
+                "color": route.color,
+                "coordinates": route.coordinates,
+            })
+        })
+        .collect();
+    let routes = serde_json::to_string(&routes).unwrap_or_else(|_| "[]".into());
+    let mut script_body = String::new();
+    script_body.push_str("\n!function(){\n");
+    writeln!(&mut script_body, "\tinitialize_routes({routes});").unwrap();
+    script_body.push_str("}();\n");
     rsx! {
-        textarea {
-            cols: "100",
-            rows: "40",
-            "{body}"
+        script {
+            dangerous_inner_html: "{script_body}",
         }
     }
 }
 
-/// # Errors
-/// Returns error if formatting fails
-pub fn strava_body(athlete: StravaAthlete) -> Result<String, Error> {
-    let mut app = VirtualDom::new_with_props(StravaElement, StravaElementProps { athlete });
-    app.rebuild_in_place();
-    let mut renderer = dioxus_ssr::Renderer::default();
-    let mut buffer = String::new();
-    renderer
-        .render_to(&mut buffer, &app)
-        .map_err(Into::<Error>::into)?;
-    Ok(buffer)
-}
-
-#[component]
-fn StravaElement(athlete: StravaAthlete) -> Element {
-    let id = athlete.id;
-    let username = &athlete.username;
-    let firstname = &athlete.firstname;
+fn trip_table(trip: &TripOpts) -> Element {
+    let TripOpts {
+        trip,
+        stats,
+        summaries,
+        photo_urls,
+        ..
+    } = trip;
+    let local = DateTimeWrapper::local_tz();
+    let days = trip.days();
+    let format_duration = |duration: f64| print_h_m_s(duration, true).unwrap_or_else(|_| "".into());
+    let sport_rows = stats.sports.iter().enumerate().map(|(idx, s)| {
+        let sport = s.sport.display_name();
+        let activities = s.activities;
+        let distance = s.total_distance / METERS_PER_MILE;
+        let duration = format_duration(s.total_duration);
+        let calories = s.total_calories;
+        rsx! {
+            tr {
+                key: "trip-sport-key-{idx}",
+                td {"{sport}"},
+                td {"{activities}"},
+                td {"{distance:0.2} mi"},
+                td {"{duration}"},
+                td {"{calories}"},
+            }
+        }
+    });
+    let total_activities = stats.activities;
+    let total_distance = stats.total_distance / METERS_PER_MILE;
+    let total_duration = format_duration(stats.total_duration);
+    let total_calories = stats.total_calories;
+    let activity_rows = summaries.iter().enumerate().map(|(idx, s)| {
+        let filename = &s.filename;
+        let begin = s.begin_datetime.to_timezone(local);
+        let date = begin.date();
+        let time = begin.time();
+        let sport = s.sport.display_name();
+        let distance = s.total_distance / METERS_PER_MILE;
+        let duration = format_duration(s.total_duration);
+        rsx! {
+            tr {
+                key: "trip-activity-key-{idx}",
+                td {
+                    button {
+                        "type": "submit",
+                        "onclick": "send_command('filter={filename}')",
+                        "{date} {time}",
+                    }
+                },
+                td {"{sport}"},
+                td {"{distance:0.2} mi"},
+                td {"{duration}"},
+            }
+        }
+    });
+    let photos = photo_urls.iter().enumerate().map(|(idx, url)| {
+        rsx! {
+            a {
+                key: "trip-photo-key-{idx}",
+                href: "{url}",
+                target: "_blank",
+                img {
+                    src: "{url}",
+                    loading: "lazy",
+                },
+            }
+        }
+    });
+    let gallery = if photo_urls.is_empty() {
+        None
+    } else {
+        Some(rsx! {
+            div {
+                class: "trip-gallery",
+                {photos},
+            }
+        })
+    };
+    rsx! {
+        div {
+            table {
+                "border": "1",
+                class: "dataframe",
+                caption {class: "visually-hidden", "Totals by sport"},
+                thead {
+                    tr {
+                        th {"scope": "col", "Sport"},
+                        th {"scope": "col", "Activities"},
+                        th {"scope": "col", "Distance"},
+                        th {"scope": "col", "Duration"},
+                        th {"scope": "col", "Calories"},
+                    }
+                },
+                tbody {
+                    {sport_rows},
+                    tr {
+                        td {b {"Total ({days} days)"}},
+                        td {b {"{total_activities}"}},
+                        td {b {"{total_distance:0.2} mi"}},
+                        td {b {"{total_duration}"}},
+                        td {b {"{total_calories}"}},
+                    }
+                }
+            },
+            br {},
+            table {
+                "border": "1",
+                class: "dataframe",
+                caption {class: "visually-hidden", "Activities"},
+                thead {
+                    tr {
+                        th {"scope": "col", "Start"},
+                        th {"scope": "col", "Sport"},
+                        th {"scope": "col", "Distance"},
+                        th {"scope": "col", "Duration"},
+                    }
+                },
+                tbody {
+                    {activity_rows}
+                }
+            },
+            {gallery},
+        }
+    }
+}
+
+fn create_power_curve_plot(power_curve: &PowerCurveOpts) -> Element {
+    let PowerCurveOpts {
+        metric,
+        sport,
+        period,
+        current,
+        previous,
+    } = power_curve;
+    // pace curves are stored as speed, display them as min/mi
+    let convert = |value: f64| match metric {
+        CurveMetric::Power => value,
+        CurveMetric::Pace => {
+            if value > 0.0 {
+                METERS_PER_MILE / value / 60.0
+            } else {
+                0.0
+            }
+        }
+    };
+    let ylabel = match metric {
+        CurveMetric::Power => "W",
+        CurveMetric::Pace => "min/mi",
+    };
+    let to_plot = |values: &[(i32, f64)]| -> Vec<(f64, f64)> {
+        values
+            .iter()
+            .map(|(d, v)| (f64::from(*d).log10(), convert(*v)))
+            .collect()
+    };
+    let current_values = to_plot(current);
+    let previous_values = to_plot(previous);
+    let mut plot = PlotOpts::new()
+        .with_name("power_curve")
+        .with_title(&format_sstr!("Mean Maximal {metric} {sport}"))
+        .with_labels("log10 duration (s)", ylabel);
+    if !current_values.is_empty() {
+        plot = plot.with_series(
+            PlotSeries::new(period.to_str(), ylabel, &current_values, PlotAxis::Left)
+                .with_color("#d62728"),
+        );
+    }
+    if !previous_values.is_empty() {
+        plot = plot.with_series(
+            PlotSeries::new("previous", ylabel, &previous_values, PlotAxis::Left)
+                .with_color("#7f7f7f"),
+        );
+    }
+    let title = &plot.title;
+    let xlabel = &plot.xlabel;
+    let series = serde_json::to_string(&plot.series).unwrap_or_else(|_| String::new());
+    let mut script_body = String::new();
+    script_body.push_str("\n!function(){\n");
+    writeln!(&mut script_body, "\tlet series = {series};").unwrap();
+    writeln!(
+        &mut script_body,
+        "\tcombined_plot(series, '{title}', '{xlabel}', '{}', []);",
+        sport.color(),
+    )
+    .unwrap();
+    script_body.push_str("}();\n");
+
+    let previous_map: HashMap<i32, f64> = previous.iter().copied().collect();
+    let rows = current.iter().enumerate().map(|(idx, (duration, value))| {
+        let duration_str = print_h_m_s(f64::from(*duration), true).unwrap_or_else(|_| "".into());
+        let value = convert(*value);
+        let previous = previous_map
+            .get(duration)
+            .map_or_else(StackString::new, |v| format_sstr!("{:0.2}", convert(*v)));
+        rsx! {
+            tr {
+                key: "power-curve-key-{idx}",
+                td {"{duration_str}"},
+                td {"{value:0.2}"},
+                td {"{previous}"},
+            }
+        }
+    });
+
+    let buttons = [
+        (CurveMetric::Power, SportTypes::Biking),
+        (CurveMetric::Pace, SportTypes::Running),
+        (CurveMetric::Pace, SportTypes::Biking),
+    ]
+    .into_iter()
+    .flat_map(|(m, s)| [(m, s, CurvePeriod::SixWeeks), (m, s, CurvePeriod::Season)])
+    .enumerate()
+    .map(move |(idx, (m, s, p))| {
+        rsx! {
+            button {
+                key: "power-curve-button-{idx}",
+                "type": "submit",
+                "onclick": "power_curve_plot('{m}', '{s}', '{p}');",
+                "{m} {s} {p}",
+            }
+        }
+    });
+
+    rsx! {
+        br {
+            {buttons},
+        }
+        script {src: "/garmin/scripts/combined_plot.js"},
+        script {
+            dangerous_inner_html: "{script_body}"
+        },
+        table {
+            "border": "1",
+            caption {class: "visually-hidden", "Power curve"},
+            thead {
+                th {"scope": "col", "Duration"},
+                th {"scope": "col", "{period} ({ylabel})"},
+                th {"scope": "col", "Previous ({ylabel})"},
+            },
+            tbody {
+                {rows},
+            }
+        },
+    }
+}
+
+/// Line colors of past years in the yearly comparison, the current year is
+/// always red
+const COMPARISON_COLORS: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f", "#17becf",
+];
+
+fn create_yearly_comparison_plot(comparison: &YearlyComparison) -> Element {
+    let YearlyComparison {
+        metric,
+        sport,
+        today,
+        years,
+    } = comparison;
+    let units = metric.units();
+    let sport_name = sport.map_or_else(|| "All Sports".into(), SportTypes::display_name);
+    let current_year = years.last().map(|y| y.year);
+    let mut plot = PlotOpts::new()
+        .with_name("yearly_comparison")
+        .with_title(&format_sstr!("Cumulative {metric} by Year {sport_name}"))
+        .with_labels("day of year", units);
+    // newest year first in the legend
+    let year_values: Vec<(i32, Vec<(f64, f64)>)> = years
+        .iter()
+        .rev()
+        .map(|year| {
+            let values = year
+                .values
+                .iter()
+                .map(|(day, value)| (f64::from(*day), *value))
+                .collect();
+            (year.year, values)
+        })
+        .collect();
+    for (idx, (year, values)) in year_values.iter().enumerate() {
+        let color = if Some(*year) == current_year {
+            "#d62728"
+        } else {
+            COMPARISON_COLORS[idx % COMPARISON_COLORS.len()]
+        };
+        plot = plot.with_series(
+            PlotSeries::new(&format_sstr!("{year}"), units, values, PlotAxis::Left)
+                .with_color(color),
+        );
+    }
+    let title = &plot.title;
+    let xlabel = &plot.xlabel;
+    let series = serde_json::to_string(&plot.series).unwrap_or_else(|_| String::new());
+    let color = sport.map_or("#d62728", SportTypes::color);
+    let mut script_body = String::new();
+    script_body.push_str("\n!function(){\n");
+    writeln!(&mut script_body, "\tlet series = {series};").unwrap();
+    writeln!(
+        &mut script_body,
+        "\tcombined_plot(series, '{title}', '{xlabel}', '{color}', []);",
+    )
+    .unwrap();
+    script_body.push_str("}();\n");
+
+    let versus = comparison.versus_last_year().map(|(current, previous)| {
+        let difference = current - previous;
+        let status = if difference >= 0.0 { "ahead of" } else { "behind" };
+        let difference = difference.abs();
+        rsx! {
+            p {"{current:0.1} {units} through day {today}, {difference:0.1} {units} {status} last year ({previous:0.1} {units})"}
+        }
+    });
+    let rows = years.iter().rev().enumerate().map(|(idx, year)| {
+        let y = year.year;
+        let to_date = year.value_at(*today);
+        let total = year.total();
+        rsx! {
+            tr {
+                key: "yearly-comparison-key-{idx}",
+                td {"{y}"},
+                td {"{to_date:0.1}"},
+                td {"{total:0.1}"},
+            }
+        }
+    });
+    let buttons = [ComparisonMetric::Distance, ComparisonMetric::Duration]
+        .into_iter()
+        .flat_map(|m| {
+            [
+                (m, "all"),
+                (m, SportTypes::Running.to_str()),
+                (m, SportTypes::Biking.to_str()),
+            ]
+        })
+        .enumerate()
+        .map(|(idx, (m, s))| {
+            rsx! {
+                button {
+                    key: "yearly-comparison-button-{idx}",
+                    "type": "submit",
+                    "onclick": "yearly_comparison_plot('{m}', '{s}');",
+                    "{m} {s}",
+                }
+            }
+        });
+
+    rsx! {
+        br {
+            {buttons},
+        }
+        script {src: "/garmin/scripts/combined_plot.js"},
+        script {
+            dangerous_inner_html: "{script_body}"
+        },
+        {versus},
+        table {
+            "border": "1",
+            caption {class: "visually-hidden", "Yearly comparison"},
+            thead {
+                th {"scope": "col", "Year"},
+                th {"scope": "col", "Through Day {today} ({units})"},
+                th {"scope": "col", "Total ({units})"},
+            },
+            tbody {
+                {rows},
+            }
+        },
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn create_fitbit_table(heartrate_values: Vec<FitbitHeartRate>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        FitbitTableElement,
+        FitbitTableElementProps { heartrate_values },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn FitbitTableElement(heartrate_values: Vec<FitbitHeartRate>) -> Element {
+    rsx! {
+        table {
+            "border": "1",
+            caption {class: "visually-hidden", "Heart rate"},
+            thead {
+                th {"scope": "col", "Datetime"},
+                th {"scope": "col", "Heart Rate"},
+            },
+            tbody {
+                {heartrate_values.iter().enumerate().map(|(idx, entry)| {
+                    let datetime = entry.datetime;
+                    let heartrate = entry.value;
+                    rsx! {
+                        tr {
+                            key: "heartrate-values-key-{idx}",
+                            td {"{datetime}"},
+                            td {"{heartrate}"},
+                        }
+                    }
+                })},
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn table_body(body: StackString) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(TableElement, TableElementProps { body });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn TableElement(body: StackString) -> Element {
+    rsx! {
+        textarea {
+            "aria-label": "Output",
+            cols: "100",
+            rows: "40",
+            "{body}"
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn pace_planner_body(
+    distance: StackString,
+    target_time: StackString,
+    course: Option<StackString>,
+    unit: SplitUnit,
+    splits: Vec<PlannedSplit>,
+    races: Vec<(UpcomingRace, Vec<RaceForecast>)>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        PacePlannerElement,
+        PacePlannerElementProps {
+            distance,
+            target_time,
+            course,
+            unit,
+            splits,
+            races,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn PacePlannerElement(
+    distance: StackString,
+    target_time: StackString,
+    course: Option<StackString>,
+    unit: SplitUnit,
+    splits: Vec<PlannedSplit>,
+    races: Vec<(UpcomingRace, Vec<RaceForecast>)>,
+) -> Element {
+    let label = unit.label();
+    let course = course.unwrap_or_default();
+    let local = DateTimeWrapper::local_tz();
+    let race_entries = races.iter().map(|(race, forecasts)| {
+        let id = race.id;
+        let name = &race.name;
+        let race_datetime = race.race_datetime.to_timezone(local);
+        let race_distance = race.distance / unit.meters();
+        let target = race
+            .target_time
+            .and_then(|t| print_h_m_s(t, true).ok())
+            .unwrap_or_else(|| "".into());
+        let suggestion = match forecasts.last() {
+            Some(latest) => {
+                let adjustment = latest.pace_adjustment();
+                let percent = adjustment * 100.0;
+                let adjusted = race
+                    .target_time
+                    .and_then(|t| print_h_m_s(t * (1.0 + adjustment), true).ok())
+                    .map(|t| format_sstr!(", adjusted target {t}"))
+                    .unwrap_or_default();
+                if adjustment > 0.0 {
+                    format_sstr!("Expect to run {percent:0.1}% slower in the heat{adjusted}")
+                } else {
+                    "No heat adjustment needed".into()
+                }
+            }
+            None => {
+                format_sstr!("Forecast is fetched daily starting {FORECAST_HORIZON_DAYS} days out")
+            }
+        };
+        let forecast_rows = forecasts.iter().map(|forecast| {
+            let forecast_id = forecast.id;
+            let fetched_at = forecast.fetched_at.to_timezone(local).date();
+            let temperature = forecast.temperature;
+            let dew_point = forecast.dew_point;
+            let humidity = forecast
+                .humidity
+                .map_or_else(StackString::new, |h| format_sstr!("{h:0.0}"));
+            let wind_speed = forecast
+                .wind_speed
+                .map_or_else(StackString::new, |w| format_sstr!("{w:0.0}"));
+            let precipitation = forecast
+                .precipitation_probability
+                .map_or_else(StackString::new, |p| format_sstr!("{p:0.0}"));
+            let slowdown = forecast.pace_adjustment() * 100.0;
+            rsx! {
+                tr {
+                    key: "race-forecast-key-{forecast_id}",
+                    td {"{fetched_at}"},
+                    td {"{temperature:0.1}"},
+                    td {"{dew_point:0.1}"},
+                    td {"{humidity}"},
+                    td {"{wind_speed}"},
+                    td {"{precipitation}"},
+                    td {"{slowdown:0.1}"},
+                }
+            }
+        });
+        rsx! {
+            div {
+                key: "upcoming-race-key-{id}",
+                h4 {"{name} {race_datetime} {race_distance:0.2} {label} {target}"},
+                form {
+                    action: "/garmin/pace_planner/races/delete",
+                    method: "post",
+                    enctype: "multipart/form-data",
+                    input {"type": "hidden", name: "id", value: "{id}"},
+                    input {"type": "submit", value: "Delete"},
+                },
+                "{suggestion}",
+                table {
+                    "border": "1",
+                    caption {class: "visually-hidden", "Weather forecast"},
+                    thead {
+                        th {"scope": "col", "Fetched"},
+                        th {"scope": "col", "Temperature (C)"},
+                        th {"scope": "col", "Dew Point (C)"},
+                        th {"scope": "col", "Humidity (%)"},
+                        th {"scope": "col", "Wind (km/h)"},
+                        th {"scope": "col", "Precipitation (%)"},
+                        th {"scope": "col", "Slowdown (%)"},
+                    },
+                    tbody {
+                        {forecast_rows},
+                    }
+                },
+            }
+        }
+    });
+    let rows = splits.iter().map(|split| {
+        let idx = split.split;
+        let distance = split.distance / unit.meters();
+        let gain = split.elevation_gain;
+        let loss = split.elevation_loss;
+        let grade = split.grade * 100.0;
+        let pace = print_h_m_s(split.pace(unit), false).unwrap_or_else(|_| "".into());
+        let split_time = print_h_m_s(split.split_time, false).unwrap_or_else(|_| "".into());
+        let elapsed = print_h_m_s(split.elapsed_time, true).unwrap_or_else(|_| "".into());
+        rsx! {
+            tr {
+                key: "pace-plan-key-{idx}",
+                td {"{idx}"},
+                td {"{distance:0.2}"},
+                td {"{gain:0.0}"},
+                td {"{loss:0.0}"},
+                td {"{grade:0.1}"},
+                td {"{pace}"},
+                td {"{split_time}"},
+                td {"{elapsed}"},
+            }
+        }
+    });
+    let download = if splits.is_empty() {
+        None
+    } else {
+        let name = format_sstr!("{distance} {target_time}");
+        let fit_data = STANDARD.encode(splits_to_fit_workout(&name, &splits));
+        let pace_band = splits_to_pace_band(&name, &splits, unit).ok().map(|pdf| {
+            let pdf_data = STANDARD.encode(pdf);
+            rsx! {
+                " ",
+                a {
+                    href: "data:application/pdf;base64,{pdf_data}",
+                    download: "pace_band.pdf",
+                    "Download pace band",
+                }
+            }
+        });
+        Some(rsx! {
+            a {
+                href: "data:application/octet-stream;base64,{fit_data}",
+                download: "pace_plan.fit",
+                "Download FIT workout",
+            },
+            {pace_band},
+        })
+    };
+    rsx! {
+        form {
+            action: "/garmin/pace_planner",
+            method: "post",
+            enctype: "multipart/form-data",
+            "Distance ",
+            input {"type": "text", "aria-label": "Distance", name: "distance", value: "{distance}"},
+            " Target Time ",
+            input {"type": "text", "aria-label": "Target time", name: "target_time", value: "{target_time}"},
+            " Units ",
+            input {"type": "text", "aria-label": "Units", name: "units", value: "{label}"},
+            " Course ",
+            input {"type": "text", "aria-label": "Course", name: "course", value: "{course}"},
+            " GPX ",
+            input {"type": "file", "aria-label": "GPX file", name: "gpx"},
+            input {"type": "submit", value: "Plan"},
+        },
+        br {
+            {download},
+        },
+        table {
+            "border": "1",
+            caption {class: "visually-hidden", "Pace plan"},
+            thead {
+                th {"scope": "col", "Split"},
+                th {"scope": "col", "Distance ({label})"},
+                th {"scope": "col", "Gain (m)"},
+                th {"scope": "col", "Loss (m)"},
+                th {"scope": "col", "Grade (%)"},
+                th {"scope": "col", "Pace (min/{label})"},
+                th {"scope": "col", "Split Time"},
+                th {"scope": "col", "Elapsed"},
+            },
+            tbody {
+                {rows},
+            }
+        },
+        h3 {"Upcoming Races"},
+        {race_entries},
+        form {
+            action: "/garmin/pace_planner/races",
+            method: "post",
+            enctype: "multipart/form-data",
+            "Name ",
+            input {"type": "text", "aria-label": "Race name", name: "name"},
+            " Start ",
+            input {"type": "datetime-local", "aria-label": "Race start", name: "race_datetime"},
+            " Latitude ",
+            input {"type": "text", "aria-label": "Latitude", name: "latitude"},
+            " Longitude ",
+            input {"type": "text", "aria-label": "Longitude", name: "longitude"},
+            " Distance ",
+            input {"type": "text", "aria-label": "Distance", name: "distance"},
+            " Target Time ",
+            input {"type": "text", "aria-label": "Target time", name: "target_time"},
+            input {"type": "submit", value: "Add Race"},
+        },
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn strava_body(athlete: StravaAthlete) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(StravaElement, StravaElementProps { athlete });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn StravaElement(athlete: StravaAthlete) -> Element {
+    let id = athlete.id;
+    let username = &athlete.username;
+    let firstname = &athlete.firstname;
     let lastname = &athlete.lastname;
     let city = &athlete.city;
     let state = &athlete.state;
@@ -1981,130 +4301,1642 @@ fn StravaElement(athlete: StravaAthlete) -> Element {
     let updated_at = athlete.updated_at;
     let follower_count = athlete.follower_count.map(|follower_count| {
         rsx! {
-            td {"Follower Count"},
-            td {"{follower_count}"},
+            td {"Follower Count"},
+            td {"{follower_count}"},
+        }
+    });
+    let friend_count = athlete.friend_count.map(|friend_count| {
+        rsx! {
+            td {"Friend Count"},
+            td {"{friend_count}"},
+        }
+    });
+    let measurement_preference =
+        athlete
+            .measurement_preference
+            .as_ref()
+            .map(|measurement_preference| {
+                rsx! {
+                    td {"Measurement Preference"},
+                    td {"{measurement_preference}"},
+                }
+            });
+    let clubs = athlete.clubs.as_ref().map(|clubs| {
+        let lines = clubs.iter().enumerate().map(|(idx, c)| {
+            let id = c.id;
+            let name = &c.name;
+            let sport_type = &c.sport_type;
+            let city = &c.city;
+            let state = &c.state;
+            let country = &c.country;
+            let private = c.private;
+            let member_count = c.member_count;
+            let url = &c.url;
+
+            rsx! {
+                tr {
+                    key: "club-key-{idx}",
+                    td {"{id}"},
+                    td {"{name}"},
+                    td {"{sport_type}"},
+                    td {"{city}"},
+                    td {"{state}"},
+                    td {"{country}"},
+                    td {"{private}"},
+                    td {"{member_count}"},
+                    td {"{url}"},
+                }
+            }
+        });
+        rsx! {
+            br {"Clubs"},
+            table {
+                "border": "1",
+                caption {class: "visually-hidden", "Clubs"},
+                thead {
+                    th {"scope": "col", "ID"},
+                    th {"scope": "col", "Name"},
+                    th {"scope": "col", "Sport Type"},
+                    th {"scope": "col", "City"},
+                    th {"scope": "col", "State"},
+                    th {"scope": "col", "Country"},
+                    th {"scope": "col", "Private"},
+                    th {"scope": "col", "Member Count"},
+                    th {"scope": "col", "Url"},
+                },
+                tbody {
+                    {lines},
+                }
+            }
+        }
+    });
+    let shoes = athlete.shoes.as_ref().map(|shoes| {
+        let lines = shoes.iter().enumerate().map(|(idx, s)| {
+            let id = &s.id;
+            let resource_state = s.resource_state;
+            let primary = s.primary;
+            let name = &s.name;
+            let distance = s.distance / METERS_PER_MILE;
+            rsx! {
+                tr {
+                    key: "shoes-key-{idx}",
+                    td {"{id}"},
+                    td {"{resource_state}"},
+                    td {"{primary}"},
+                    td {"{name}"},
+                    td {"{distance:0.2}"},
+                }
+            }
+        });
+        rsx! {
+            br {"Shoes"},
+            table {
+                "border": "1",
+                caption {class: "visually-hidden", "Shoes"},
+                thead {
+                    th {"scope": "col", "ID"},
+                    th {"scope": "col", "Resource State"},
+                    th {"scope": "col", "Primary"},
+                    th {"scope": "col", "Name"},
+                    th {"scope": "col", "Distance (mi)"},
+                },
+                tbody {
+                    {lines},
+                }
+            }
+        }
+    });
+
+    rsx! {
+        table {
+            "border": "1",
+            caption {class: "visually-hidden", "Athlete"},
+            tbody {
+                tr {td {"ID"}, td {"{id}"}},
+                tr {td {"Username"}, td {"{username}"}},
+                tr {td {"First Name"}, td {"{firstname}"}},
+                tr {td {"Last Name"}, td {"{lastname}"}},
+                tr {td {"City"}, td {"{city}"}},
+                tr {td {"State"}, td {"{state}"}},
+                tr {td {"Sex"}, td {"{sex}"}},
+                tr {td {"Weight"}, td {"{weight} lbs"}},
+                tr {td {"Created At"}, td {"{created_at}"}},
+                tr {td {"Updated At"}, td {"{updated_at}"}},
+                tr { {follower_count} },
+                tr { {friend_count} },
+                tr { {measurement_preference} },
+            },
+            {clubs},
+            {shoes},
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn quarantine_body(
+    message: Option<StackString>,
+    files: Vec<QuarantinedFile>,
+) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(QuarantineElement, QuarantineElementProps { message, files });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn QuarantineElement(message: Option<StackString>, files: Vec<QuarantinedFile>) -> Element {
+    let message = message.map(|message| {
+        rsx! {
+            p {"{message}"}
+        }
+    });
+    let rows = files.iter().enumerate().map(|(idx, f)| {
+        let filename = &f.filename;
+        let error_message = &f.error_message;
+        let quarantined_at = f.quarantined_at;
+        rsx! {
+            tr {
+                key: "quarantine-key-{idx}",
+                td {"{filename}"},
+                td {"{quarantined_at}"},
+                td {"{error_message}"},
+                td {
+                    button {
+                        "type": "submit",
+                        "data-filename": "{filename}",
+                        "onclick": "quarantineRetry(this.dataset.filename);",
+                        "Retry",
+                    }
+                },
+            }
+        }
+    });
+    rsx! {
+        {message},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Quarantined files"},
+            thead {
+                tr {
+                    th {"scope": "col", "File"},
+                    th {"scope": "col", "Quarantined"},
+                    th {"scope": "col", "Error"},
+                    th {"scope": "col"},
+                }
+            },
+            tbody {
+                {rows}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn cleanup_body(
+    filter: CleanupFilter,
+    message: Option<StackString>,
+    matches: Vec<GarminSummary>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        CleanupElement,
+        CleanupElementProps {
+            filter,
+            message,
+            matches,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn CleanupElement(
+    filter: CleanupFilter,
+    message: Option<StackString>,
+    matches: Vec<GarminSummary>,
+) -> Element {
+    let start_date = filter
+        .start_date
+        .map_or_else(StackString::new, StackString::from_display);
+    let end_date = filter
+        .end_date
+        .map_or_else(StackString::new, StackString::from_display);
+    let sport = filter
+        .sport
+        .map_or_else(StackString::new, StackString::from_display);
+    let zero_distance = filter.zero_distance;
+    let message = message.map(|message| {
+        rsx! {
+            p {"{message}"}
+        }
+    });
+    let count = matches.len();
+    let delete_button = if matches.is_empty() {
+        None
+    } else {
+        // delete exactly what the dry run listed
+        let ids = matches
+            .iter()
+            .map(|s| StackString::from_display(s.id))
+            .join(",");
+        Some(rsx! {
+            button {
+                "type": "submit",
+                "data-ids": "{ids}",
+                "onclick": "cleanupDelete(this);",
+                "Delete {count} activities",
+            }
+        })
+    };
+    let rows = matches.iter().enumerate().map(|(idx, s)| {
+        let filename = &s.filename;
+        let begin_datetime = s.begin_datetime;
+        let sport = s.sport;
+        let distance = s.total_distance / METERS_PER_MILE;
+        let duration = print_h_m_s(s.total_duration, true).unwrap_or_else(|_| "".into());
+        rsx! {
+            tr {
+                key: "cleanup-key-{idx}",
+                td {"{filename}"},
+                td {"{begin_datetime}"},
+                td {"{sport}"},
+                td {"{distance:0.2} mi"},
+                td {"{duration}"},
+            }
+        }
+    });
+    rsx! {
+        form {
+            "Start Date",
+            input {"type": "date", "aria-label": "Start date", id: "cleanup_start_date", value: "{start_date}"},
+            "End Date",
+            input {"type": "date", "aria-label": "End date", id: "cleanup_end_date", value: "{end_date}"},
+            "Sport",
+            input {"type": "text", "aria-label": "Sport", id: "cleanup_sport", value: "{sport}"},
+            "Zero Distance",
+            input {"type": "checkbox", "aria-label": "Zero distance", id: "cleanup_zero_distance", checked: zero_distance},
+            button {
+                "type": "button",
+                "onclick": "cleanupDryRun();",
+                "Dry Run",
+            },
+        },
+        {message},
+        {delete_button},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Cleanup candidates"},
+            thead {
+                tr {
+                    th {"scope": "col", "File"},
+                    th {"scope": "col", "Start"},
+                    th {"scope": "col", "Sport"},
+                    th {"scope": "col", "Distance"},
+                    th {"scope": "col", "Duration"},
+                }
+            },
+            tbody {
+                {rows}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn admin_stats_body(stats: AdminStats) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(AdminStatsElement, AdminStatsElementProps { stats });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+fn format_size(size: u64) -> StackString {
+    format_sstr!("{:.1} MB", size as f64 / 1e6)
+}
+
+#[component]
+fn AdminStatsElement(stats: AdminStats) -> Element {
+    let missing = stats.missing_links;
+    let sync_lock = match &stats.sync_lock {
+        Some(holder) => {
+            let description = holder.describe();
+            rsx! {
+                "Held by {description} ",
+                button {
+                    "type": "submit",
+                    "onclick": "syncLockRelease()",
+                    "Force Unlock",
+                }
+            }
+        }
+        None => rsx! {"No sync in progress"},
+    };
+    let table_rows = stats.table_counts.iter().enumerate().map(|(idx, t)| {
+        let table_name = &t.table_name;
+        let row_count = t.row_count;
+        rsx! {
+            tr {
+                key: "table-count-key-{idx}",
+                td {"{table_name}"},
+                td {"{row_count}"},
+            }
+        }
+    });
+    let directory_rows = stats.directory_sizes.iter().enumerate().map(|(idx, d)| {
+        let name = &d.name;
+        let number_of_files = d.number_of_files;
+        let size = format_size(d.total_size);
+        rsx! {
+            tr {
+                key: "directory-size-key-{idx}",
+                td {"{name}"},
+                td {"{number_of_files}"},
+                td {"{size}"},
+            }
+        }
+    });
+    let bucket_rows = stats.bucket_sizes.iter().enumerate().map(|(idx, b)| {
+        let s3_bucket = &b.s3_bucket;
+        let number_of_files = b.number_of_files;
+        let size = format_size(b.total_size.max(0) as u64);
+        rsx! {
+            tr {
+                key: "bucket-size-key-{idx}",
+                td {"{s3_bucket}"},
+                td {"{number_of_files}"},
+                td {"{size}"},
+            }
+        }
+    });
+    let gap_rows = stats.heartrate_gaps.iter().enumerate().map(|(idx, g)| {
+        let gap_start = g.gap_start;
+        let gap_end = g.gap_end;
+        let days = g.days();
+        rsx! {
+            tr {
+                key: "heartrate-gap-key-{idx}",
+                td {"{gap_start}"},
+                td {"{gap_end}"},
+                td {"{days}"},
+            }
+        }
+    });
+    rsx! {
+        h3 {"Activities"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Activities"},
+            thead {
+                tr {
+                    th {"scope": "col", "Total"},
+                    th {"scope": "col", "Missing Strava"},
+                    th {"scope": "col", "Missing Connect"},
+                }
+            },
+            tbody {
+                tr {
+                    td {"{missing.total_activities}"},
+                    td {"{missing.missing_strava}"},
+                    td {"{missing.missing_connect}"},
+                }
+            }
+        },
+        h3 {"Sync Lock"},
+        p {{sync_lock}},
+        h3 {"Tables"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Tables"},
+            thead {
+                tr {
+                    th {"scope": "col", "Table"},
+                    th {"scope": "col", "Rows (estimated)"},
+                }
+            },
+            tbody {
+                {table_rows}
+            }
+        },
+        h3 {"Local Directories"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Local directories"},
+            thead {
+                tr {
+                    th {"scope": "col", "Directory"},
+                    th {"scope": "col", "Files"},
+                    th {"scope": "col", "Size"},
+                }
+            },
+            tbody {
+                {directory_rows}
+            }
+        },
+        h3 {"S3 Buckets"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "S3 buckets"},
+            thead {
+                tr {
+                    th {"scope": "col", "Bucket"},
+                    th {"scope": "col", "Files"},
+                    th {"scope": "col", "Size"},
+                }
+            },
+            tbody {
+                {bucket_rows}
+            }
+        },
+        h3 {"Heart Rate Gaps"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Heart rate gaps"},
+            thead {
+                tr {
+                    th {"scope": "col", "Start"},
+                    th {"scope": "col", "End"},
+                    th {"scope": "col", "Days"},
+                }
+            },
+            tbody {
+                {gap_rows}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn integrations_body(
+    strava_accounts: Vec<StravaAccount>,
+    statuses: Vec<SyncStatus>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        IntegrationsElement,
+        IntegrationsElementProps {
+            strava_accounts,
+            statuses,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn IntegrationsElement(strava_accounts: Vec<StravaAccount>, statuses: Vec<SyncStatus>) -> Element {
+    let now = OffsetDateTime::now_utc();
+    let connected = strava_accounts
+        .iter()
+        .filter(|a| a.refresh_token.is_some())
+        .count();
+    let strava_connection =
+        format_sstr!("{connected} of {} accounts linked", strava_accounts.len());
+    // (name, how it's connected, sync job)
+    let integrations = [
+        ("Strava", strava_connection, STRAVA_JOB),
+        ("Fitbit", "Archive import".into(), HEARTRATE_JOB),
+        (
+            "Garmin Connect",
+            "Data directory import".into(),
+            ACTIVITY_JOB,
+        ),
+    ];
+    let status_rows = integrations
+        .iter()
+        .enumerate()
+        .map(|(idx, (name, connection, job))| {
+            let status = statuses.iter().find(|s| s.job == *job);
+            let last_success = status
+                .and_then(|s| s.last_success)
+                .map_or_else(|| "never".into(), StackString::from_display);
+            let last_failure = status
+                .and_then(|s| s.last_failure)
+                .map_or_else(StackString::new, StackString::from_display);
+            let failure_count = status.map_or(0, |s| s.failure_count);
+            let message = status.and_then(|s| s.message.clone()).unwrap_or_default();
+            rsx! {
+                tr {
+                    key: "integration-key-{idx}",
+                    td {"{name}"},
+                    td {"{connection}"},
+                    td {"{last_success}"},
+                    td {"{last_failure}"},
+                    td {"{failure_count}"},
+                    td {"{message}"},
+                }
+            }
+        });
+    let account_rows = strava_accounts.iter().enumerate().map(|(idx, account)| {
+        let name = &account.name;
+        let connection = if account.refresh_token.is_some() {
+            "connected"
+        } else {
+            "not connected"
+        };
+        let expiry = match account.expires_at() {
+            Some(expires_at) if expires_at < now => {
+                format_sstr!(
+                    "expired {}, refreshed on the next sync",
+                    DateTimeWrapper::from(expires_at)
+                )
+            }
+            Some(expires_at) => StackString::from_display(DateTimeWrapper::from(expires_at)),
+            None => StackString::new(),
+        };
+        let sync_enabled = account.sync_enabled;
+        let sync_label = if sync_enabled {
+            "Disable sync"
+        } else {
+            "Enable sync"
+        };
+        let toggle = !sync_enabled;
+        rsx! {
+            tr {
+                key: "strava-account-key-{idx}",
+                td {"{name}"},
+                td {"{connection}"},
+                td {"{expiry}"},
+                td {"{sync_enabled}"},
+                td {
+                    button {
+                        "type": "submit",
+                        "onclick": "stravaConnect('{name}');",
+                        "Re-auth",
+                    },
+                    button {
+                        "type": "submit",
+                        "onclick": "stravaAccountSync('{name}', {toggle});",
+                        "{sync_label}",
+                    },
+                    button {
+                        "type": "submit",
+                        "onclick": "stravaDisconnect('{name}');",
+                        "Disconnect",
+                    },
+                }
+            }
+        }
+    });
+    rsx! {
+        h3 {"Integrations"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Integrations"},
+            thead {
+                tr {
+                    th {"scope": "col", "Integration"},
+                    th {"scope": "col", "Connection"},
+                    th {"scope": "col", "Last Success"},
+                    th {"scope": "col", "Last Failure"},
+                    th {"scope": "col", "Failures"},
+                    th {"scope": "col", "Message"},
+                }
+            },
+            tbody {
+                {status_rows}
+            }
+        },
+        h3 {"Strava Accounts"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Strava accounts"},
+            thead {
+                tr {
+                    th {"scope": "col", "Account"},
+                    th {"scope": "col", "Status"},
+                    th {"scope": "col", "Token Expires"},
+                    th {"scope": "col", "Sync"},
+                    th {"scope": "col"},
+                }
+            },
+            tbody {
+                {account_rows}
+            }
+        },
+        form {
+            "Account",
+            input {"type": "text", "aria-label": "Strava account name", id: "strava_account_name"},
+            button {
+                "type": "submit",
+                "onclick": "stravaConnect(document.getElementById('strava_account_name').value); return false;",
+                "Connect",
+            },
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn link_review_body(
+    message: Option<StackString>,
+    unmatched: UnmatchedActivities,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        LinkReviewElement,
+        LinkReviewElementProps { message, unmatched },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn LinkReviewElement(message: Option<StackString>, unmatched: UnmatchedActivities) -> Element {
+    let message = message.map(|message| {
+        rsx! {
+            p {"{message}"}
+        }
+    });
+    let candidates = unmatched.candidates();
+    let strava_rows = unmatched
+        .strava_activities
+        .iter()
+        .enumerate()
+        .map(|(idx, activity)| {
+            let strava_id = activity.id;
+            let start_date = activity.start_date;
+            let name = &activity.name;
+            let sport = activity.activity_type.to_str();
+            let distance = format_sstr!(
+                "{:.2} mi",
+                activity.distance.unwrap_or(0.0) / METERS_PER_MILE
+            );
+            let options = candidates
+                .get(&strava_id)
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .enumerate()
+                .map(|(jdx, summary)| {
+                    let summary_id = summary.id;
+                    let label = format_sstr!(
+                        "{} {} {:.2} mi",
+                        summary.begin_datetime,
+                        summary.sport.to_str(),
+                        summary.total_distance / METERS_PER_MILE,
+                    );
+                    rsx! {
+                        option {
+                            key: "link-option-key-{idx}-{jdx}",
+                            value: "{summary_id}",
+                            "{label}"
+                        }
+                    }
+                });
+            rsx! {
+                tr {
+                    key: "link-strava-key-{idx}",
+                    td {
+                        a {
+                            href: "https://www.strava.com/activities/{strava_id}",
+                            target: "_blank",
+                            "{start_date}",
+                        }
+                    },
+                    td {"{name}"},
+                    td {"{sport}"},
+                    td {"{distance}"},
+                    td {
+                        select {
+                            "aria-label": "Strava activity",
+                            id: "link_summary_{strava_id}",
+                            {options}
+                        }
+                    },
+                    td {
+                        button {
+                            "type": "submit",
+                            "onclick": "linkActivity({strava_id});",
+                            "Link",
+                        }
+                    },
+                }
+            }
+        });
+    let summary_rows = unmatched
+        .summaries
+        .iter()
+        .enumerate()
+        .map(|(idx, summary)| {
+            let filename = &summary.filename;
+            let begin_datetime = summary.begin_datetime;
+            let sport = summary.sport.to_str();
+            let distance = format_sstr!("{:.2} mi", summary.total_distance / METERS_PER_MILE);
+            rsx! {
+                tr {
+                    key: "link-summary-key-{idx}",
+                    td {
+                        button {
+                            "type": "submit",
+                            id: "{filename}",
+                            "onclick": "send_command('filter={filename}');",
+                            "{begin_datetime}",
+                        }
+                    },
+                    td {"{sport}"},
+                    td {"{distance}"},
+                }
+            }
+        });
+    rsx! {
+        h3 {"Unlinked Strava Activities"},
+        {message},
+        button {
+            "type": "submit",
+            "onclick": "linkAuto();",
+            "Auto link",
+        },
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Unlinked Strava activities"},
+            thead {
+                tr {
+                    th {"scope": "col", "Start"},
+                    th {"scope": "col", "Name"},
+                    th {"scope": "col", "Sport"},
+                    th {"scope": "col", "Distance"},
+                    th {"scope": "col", "Garmin Activity"},
+                    th {"scope": "col"},
+                }
+            },
+            tbody {
+                {strava_rows}
+            }
+        },
+        h3 {"Garmin Activities Without a Strava Activity"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Garmin activities without a Strava activity"},
+            thead {
+                tr {
+                    th {"scope": "col", "Start"},
+                    th {"scope": "col", "Sport"},
+                    th {"scope": "col", "Distance"},
+                }
+            },
+            tbody {
+                {summary_rows}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn training_pattern_body(pattern: TrainingPattern) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        TrainingPatternElement,
+        TrainingPatternElementProps { pattern },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn TrainingPatternElement(pattern: TrainingPattern) -> Element {
+    let sport_name = pattern
+        .sport
+        .map_or_else(|| "All Sports".into(), SportTypes::display_name);
+    let max_count = pattern.max_count().max(1) as f64;
+    let hours = (0..24).map(|hour| {
+        rsx! {
+            th {
+                key: "training-pattern-hour-{hour}",
+                "scope": "col",
+                "{hour:02}"
+            }
+        }
+    });
+    let rows = pattern.cells.iter().enumerate().map(|(row, cells)| {
+        let weekday = pattern.weekday(row);
+        let total: usize = cells.iter().map(|c| c.count).sum();
+        let cells = cells.iter().enumerate().map(|(hour, cell)| {
+            let count = cell.count;
+            // shade by the share of the busiest cell
+            let alpha = count as f64 / max_count;
+            let style = format_sstr!("background-color: rgba(214, 39, 40, {alpha:0.2})");
+            let pace = cell
+                .pace()
+                .and_then(|p| print_h_m_s(p, false).ok())
+                .unwrap_or_default();
+            if count == 0 {
+                rsx! {
+                    td {key: "training-pattern-cell-{row}-{hour}"}
+                }
+            } else {
+                rsx! {
+                    td {
+                        key: "training-pattern-cell-{row}-{hour}",
+                        style: "{style}",
+                        "{count}",
+                        br {},
+                        "{pace}",
+                    }
+                }
+            }
+        });
+        rsx! {
+            tr {
+                key: "training-pattern-row-{row}",
+                th {"scope": "col", "{weekday}"},
+                {cells},
+                td {"{total}"},
+            }
+        }
+    });
+    let buttons = ["running", "biking", "walking", "all"]
+        .iter()
+        .enumerate()
+        .map(|(idx, sport)| {
+            rsx! {
+                button {
+                    key: "training-pattern-button-{idx}",
+                    "type": "submit",
+                    "onclick": "trainingPattern('{sport}');",
+                    "{sport}",
+                }
+            }
+        });
+    rsx! {
+        h3 {"Training Pattern {sport_name}"},
+        {buttons},
+        p {"Activity count and average pace (min/mi) by local start time"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Activity count and average pace by weekday and hour"},
+            thead {
+                tr {
+                    th {"scope": "col"},
+                    {hours},
+                    th {"scope": "col", "Total"},
+                }
+            },
+            tbody {
+                {rows}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn rest_days_body(
+    stats: RestDayStats,
+    threshold: ThresholdHistory,
+    threshold_heart_rate: Option<f64>,
+) -> Result<String, Error> {
+    let svg = threshold.history_svg()?.unwrap_or_default();
+    let mut app = VirtualDom::new_with_props(
+        RestDaysElement,
+        RestDaysElementProps {
+            stats,
+            threshold,
+            threshold_heart_rate,
+            threshold_svg: svg,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn RestDaysElement(
+    stats: RestDayStats,
+    threshold: ThresholdHistory,
+    threshold_heart_rate: Option<f64>,
+    threshold_svg: StackString,
+) -> Element {
+    let start_date = stats.start_date;
+    let end_date = stats.end_date;
+    let days_trained = stats.days_trained;
+    let rest_days = stats.rest_days;
+    let hard_efforts = stats.hard_efforts;
+    let avg_rest = stats
+        .avg_rest_between_hard
+        .map_or_else(|| "n/a".into(), |r| format_sstr!("{r:0.1} days"));
+    let longest_break = stats.longest_break.map_or_else(
+        || "none".into(),
+        |(start, length)| format_sstr!("{length} days from {start}"),
+    );
+    let plan = stats
+        .planned_training_days
+        .map_or_else(|| "every day".into(), |d| format_sstr!("{d} days per week"));
+    let consistency = stats.consistency();
+    let svg = stats.consistency_svg().unwrap_or_default();
+    let threshold_heart_rate = threshold_heart_rate.map_or_else(
+        || "none".into(),
+        |t| format_sstr!("{t:0.0} bpm, training impulse and zones are based on it"),
+    );
+    let estimates = threshold
+        .estimates
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(idx, e)| {
+            let date = e.date;
+            let filename = &e.filename;
+            let effort = if e.race { "race" } else { "hard effort" };
+            let minutes = e.effort_seconds / 60.0;
+            let heart_rate = e.heart_rate;
+            let threshold_heart_rate = e.threshold_heart_rate;
+            rsx! {
+                tr {
+                    key: "estimate-{idx}",
+                    td {
+                        button {
+                            "type": "submit",
+                            "onclick": "send_command('filter={filename}');",
+                            "{date}",
+                        }
+                    },
+                    td {"{effort}"},
+                    td {"{heart_rate:0.0} over {minutes:0.0} min"},
+                    td {"{threshold_heart_rate:0.0}"},
+                }
+            }
+        });
+    rsx! {
+        h3 {"Rest Days {start_date} to {end_date}"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Rest days"},
+            tbody {
+                tr { th {"scope": "row", "Days trained"}, td {"{days_trained}"} },
+                tr { th {"scope": "row", "Rest days"}, td {"{rest_days}"} },
+                tr { th {"scope": "row", "Hard efforts"}, td {"{hard_efforts}"} },
+                tr { th {"scope": "row", "Average rest between hard efforts"}, td {"{avg_rest}"} },
+                tr { th {"scope": "row", "Longest break"}, td {"{longest_break}"} },
+                tr { th {"scope": "row", "Consistency (plan: {plan})"}, td {"{consistency:0.1}%"} },
+            }
+        },
+        p {"Percentage of planned days trained by week"},
+        div {
+            "role": "img",
+            "aria-label": "Percentage of planned days trained by week",
+            dangerous_inner_html: "{svg}",
+        },
+        h3 {"Lactate Threshold"},
+        p {"Current estimate: {threshold_heart_rate}"},
+        div {
+            "role": "img",
+            "aria-label": "Lactate threshold heart rate estimates, current {threshold_heart_rate}",
+            dangerous_inner_html: "{threshold_svg}",
+        },
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Lactate threshold efforts"},
+            thead {
+                tr {
+                    th {"scope": "col", "Date"},
+                    th {"scope": "col", "Effort"},
+                    th {"scope": "col", "Best Average"},
+                    th {"scope": "col", "Threshold"},
+                }
+            },
+            tbody {
+                {estimates}
+            }
+        },
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn heart_rate_zones_body(suggestion: HeartRateSuggestion) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        HeartRateZonesElement,
+        HeartRateZonesElementProps { suggestion },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn HeartRateZonesElement(suggestion: HeartRateSuggestion) -> Element {
+    let current = suggestion.current;
+    let threshold_heart_rate = current.threshold_heart_rate.map_or_else(
+        || "none".into(),
+        |t| format_sstr!("{t:0.0} bpm, zones are based on it"),
+    );
+    let current_zones = current.zones();
+    let suggested_zones = suggestion.suggested.map(|s| s.zones());
+    let rows = current_zones.iter().enumerate().map(|(idx, (low, high))| {
+        let zone = idx + 1;
+        let suggested = suggested_zones.as_ref().map(|zones| {
+            let (low, high) = zones[idx];
+            rsx! {
+                td {"{low:0.0} - {high:0.0}"}
+            }
+        });
+        rsx! {
+            tr {
+                key: "zone-{zone}",
+                td {"Zone {zone}"},
+                td {"{low:0.0} - {high:0.0}"},
+                {suggested},
+            }
+        }
+    });
+    let activities = suggestion.activities;
+    let observed = match &suggestion.observed {
+        Some(observed) => {
+            let max_heart_rate = observed.max_heart_rate;
+            let filename = &observed.filename;
+            let date = observed
+                .begin_datetime
+                .to_timezone(DateTimeWrapper::local_tz())
+                .date();
+            format_sstr!(
+                "Observed max {max_heart_rate:0.0} bpm on {date} ({filename}) over {activities} \
+                 activities"
+            )
+        }
+        None => format_sstr!("No observed max heart rate over {activities} activities"),
+    };
+    let max_heart_rate = current.max_heart_rate;
+    let resting_heart_rate = current.resting_heart_rate;
+    let suggested_header = suggestion.suggested.map(|s| {
+        let max_heart_rate = s.max_heart_rate;
+        rsx! {
+            th {"scope": "col", "Suggested (max {max_heart_rate:0.0})"}
+        }
+    });
+    let confirm = suggestion.suggested.map(|s| {
+        let max_heart_rate = s.max_heart_rate;
+        let resting_heart_rate = s.resting_heart_rate;
+        rsx! {
+            button {
+                "type": "submit",
+                "onclick": "heartRateZonesUpdate({max_heart_rate}, {resting_heart_rate});",
+                "Confirm max heart rate {max_heart_rate:0.0}",
+            }
+        }
+    });
+    rsx! {
+        h3 {"Heart Rate Zones"},
+        p {"{observed}"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Heart rate zones"},
+            thead {
+                tr {
+                    th {"scope": "col"},
+                    th {"scope": "col", "Current (max {max_heart_rate:0.0} rest {resting_heart_rate:0.0})"},
+                    {suggested_header},
+                }
+            },
+            tbody {
+                {rows}
+            }
+        },
+        {confirm},
+        p {
+            "Lactate threshold: {threshold_heart_rate} ",
+            button {
+                "type": "submit",
+                "onclick": "restDays();",
+                "Threshold history and training load",
+            },
+        },
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn coverage_gaps_body(
+    message: Option<StackString>,
+    gaps: Vec<CoverageGap>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        CoverageGapsElement,
+        CoverageGapsElementProps { message, gaps },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn CoverageGapsElement(message: Option<StackString>, gaps: Vec<CoverageGap>) -> Element {
+    let message = message.map(|message| {
+        rsx! {
+            pre {"{message}"}
         }
     });
-    let friend_count = athlete.friend_count.map(|friend_count| {
+    let rows = gaps.iter().enumerate().map(|(idx, g)| {
+        let kind = g.kind;
+        let gap_start = g.gap_start;
+        let gap_end = g.gap_end;
+        let days = g.days();
         rsx! {
-            td {"Friend Count"},
-            td {"{friend_count}"},
+            tr {
+                key: "coverage-gap-key-{idx}",
+                td {"{kind}"},
+                td {"{gap_start}"},
+                td {"{gap_end}"},
+                td {"{days}"},
+                td {
+                    button {
+                        "type": "submit",
+                        "onclick": "coverageGapBackfill('{kind}', '{gap_start}', '{gap_end}');",
+                        "Backfill",
+                    }
+                },
+            }
         }
     });
-    let measurement_preference =
-        athlete
-            .measurement_preference
-            .as_ref()
-            .map(|measurement_preference| {
-                rsx! {
-                    td {"Measurement Preference"},
-                    td {"{measurement_preference}"},
+    rsx! {
+        {message},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Coverage gaps"},
+            thead {
+                tr {
+                    th {"scope": "col", "Kind"},
+                    th {"scope": "col", "Start"},
+                    th {"scope": "col", "End"},
+                    th {"scope": "col", "Days"},
+                    th {"scope": "col"},
                 }
-            });
-    let clubs = athlete.clubs.as_ref().map(|clubs| {
-        let lines = clubs.iter().enumerate().map(|(idx, c)| {
-            let id = c.id;
-            let name = &c.name;
-            let sport_type = &c.sport_type;
-            let city = &c.city;
-            let state = &c.state;
-            let country = &c.country;
-            let private = c.private;
-            let member_count = c.member_count;
-            let url = &c.url;
+            },
+            tbody {
+                {rows}
+            }
+        }
+    }
+}
 
-            rsx! {
+/// # Errors
+/// Returns error if formatting fails
+pub fn milestones_body(milestones: Vec<Milestone>) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(MilestonesElement, MilestonesElementProps { milestones });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn MilestonesElement(milestones: Vec<Milestone>) -> Element {
+    let rows = milestones.iter().enumerate().map(|(idx, m)| {
+        let achieved_at = m.achieved_at.to_offsetdatetime().date();
+        let description = m.description();
+        let activity = m
+            .summary_id
+            .map_or_else(StackString::new, StackString::from_display);
+        rsx! {
+            tr {
+                key: "milestone-key-{idx}",
+                td {"{achieved_at}"},
+                td {"{description}"},
+                td {"{activity}"},
+            }
+        }
+    });
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Milestones"},
+            thead {
                 tr {
-                    key: "club-key-{idx}",
-                    td {"{id}"},
-                    td {"{name}"},
-                    td {"{sport_type}"},
-                    td {"{city}"},
-                    td {"{state}"},
-                    td {"{country}"},
-                    td {"{private}"},
-                    td {"{member_count}"},
-                    td {"{url}"},
+                    th {"scope": "col", "Date"},
+                    th {"scope": "col", "Milestone"},
+                    th {"scope": "col", "Activity"},
                 }
+            },
+            tbody {
+                {rows}
             }
-        });
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn trips_body(trips: Vec<Trip>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(TripsElement, TripsElementProps { trips });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn TripsElement(trips: Vec<Trip>) -> Element {
+    let rows = trips.iter().enumerate().map(|(idx, t)| {
+        let id = t.id;
+        let name = &t.name;
+        let start_date = t.start_date;
+        let end_date = t.end_date;
         rsx! {
-            br {"Clubs"},
-            table {
-                "border": "1",
-                thead {
-                    th {"ID"},
-                    th {"Name"},
-                    th {"Sport Type"},
-                    th {"City"},
-                    th {"State"},
-                    th {"Country"},
-                    th {"Private"},
-                    th {"Member Count"},
-                    th {"Url"},
+            tr {
+                key: "trip-key-{idx}",
+                td {
+                    a {
+                        href: "/garmin/trip?id={id}",
+                        "{name}",
+                    }
                 },
-                tbody {
-                    {lines},
+                td {"{start_date}"},
+                td {"{end_date}"},
+                td {
+                    button {
+                        "type": "submit",
+                        "data-id": "{id}",
+                        "data-name": "{name}",
+                        "onclick": "tripDelete(this);",
+                        "Delete",
+                    }
+                },
+            }
+        }
+    });
+    rsx! {
+        form {
+            input {
+                "type": "text",
+                "aria-label": "Trip name",
+                id: "trip_name",
+                placeholder: "Alps 2024",
+            },
+            input {
+                "type": "date",
+                "aria-label": "Start date",
+                id: "trip_start_date",
+            },
+            input {
+                "type": "date",
+                "aria-label": "End date",
+                id: "trip_end_date",
+            },
+            input {
+                "type": "button",
+                value: "Add Trip",
+                "onclick": "tripCreate();",
+            },
+        },
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Trips"},
+            thead {
+                tr {
+                    th {"scope": "col", "Trip"},
+                    th {"scope": "col", "Start"},
+                    th {"scope": "col", "End"},
+                    th {"scope": "col"},
                 }
+            },
+            tbody {
+                {rows}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn most_kudoed_body(year: i32, activities: Vec<StravaActivity>) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        MostKudoedElement,
+        MostKudoedElementProps { year, activities },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn MostKudoedElement(year: i32, activities: Vec<StravaActivity>) -> Element {
+    let rows = activities.iter().enumerate().map(|(idx, a)| {
+        let id = a.id;
+        let date = a.start_date.to_offsetdatetime().date();
+        let name = &a.name;
+        let kudos = a.kudos_count;
+        let comments = a.comment_count;
+        let photos = a.total_photo_count;
+        rsx! {
+            tr {
+                key: "kudos-key-{idx}",
+                td {"{date}"},
+                td {
+                    a {
+                        href: "https://www.strava.com/activities/{id}",
+                        target: "_blank",
+                        "{name}",
+                    }
+                },
+                td {"{kudos}"},
+                td {"{comments}"},
+                td {"{photos}"},
             }
         }
     });
-    let shoes = athlete.shoes.as_ref().map(|shoes| {
-        let lines = shoes.iter().enumerate().map(|(idx, s)| {
-            let id = &s.id;
-            let resource_state = s.resource_state;
-            let primary = s.primary;
-            let name = &s.name;
-            let distance = s.distance / METERS_PER_MILE;
-            rsx! {
+    let prev_year = year - 1;
+    let next_year = year + 1;
+    rsx! {
+        button {
+            "type": "submit",
+            "onclick": "mostKudoed({prev_year});",
+            "{prev_year}",
+        },
+        button {
+            "type": "submit",
+            "onclick": "mostKudoed({next_year});",
+            "{next_year}",
+        },
+        h3 {"Most kudoed activities of {year}"},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Most kudoed activities"},
+            thead {
                 tr {
-                    key: "shoes-key-{idx}",
-                    td {"{id}"},
-                    td {"{resource_state}"},
-                    td {"{primary}"},
-                    td {"{name}"},
-                    td {"{distance:0.2}"},
+                    th {"scope": "col", "Date"},
+                    th {"scope": "col", "Activity"},
+                    th {"scope": "col", "Kudos"},
+                    th {"scope": "col", "Comments"},
+                    th {"scope": "col", "Photos"},
                 }
+            },
+            tbody {
+                {rows}
             }
-        });
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn race_result_notes_body(
+    result: RaceResults,
+    attachments: Vec<(RaceAttachment, StackString)>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        RaceResultNotesElement,
+        RaceResultNotesElementProps {
+            result,
+            attachments,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn RaceResultNotesElement(
+    result: RaceResults,
+    attachments: Vec<(RaceAttachment, StackString)>,
+) -> Element {
+    let id = result.id;
+    let name = result.race_name.as_ref().map_or("", StackString::as_str);
+    let date = result
+        .race_date
+        .map_or_else(StackString::new, StackString::from_display);
+    let bib_number = result.bib_number.as_ref().map_or("", StackString::as_str);
+    let race_report = result.race_report.as_ref().map_or("", StackString::as_str);
+    let rows = attachments.iter().enumerate().map(|(idx, (a, url))| {
+        let attachment_id = a.id;
+        let kind = &a.kind;
+        let filename = &a.filename;
         rsx! {
-            br {"Shoes"},
-            table {
-                "border": "1",
-                thead {
-                    th {"ID"},
-                    th {"Resource State"},
-                    th {"Primary"},
-                    th {"Name"},
-                    th {"Distance (mi)"},
+            tr {
+                key: "race-attachment-key-{idx}",
+                td {"{kind}"},
+                td {
+                    a {
+                        href: "{url}",
+                        target: "_blank",
+                        "{filename}",
+                    }
                 },
-                tbody {
-                    {lines},
+                td {
+                    button {
+                        "type": "button",
+                        "onclick": "raceAttachmentDelete('{attachment_id}', '{id}');",
+                        "Delete",
+                    }
+                },
+            }
+        }
+    });
+    rsx! {
+        h3 {"{date} {name}"},
+        "Bib ",
+        input {
+            "type": "text",
+            "aria-label": "Bib number",
+            id: "race_bib_number",
+            value: "{bib_number}",
+        },
+        br {},
+        textarea {
+            "aria-label": "Race report",
+            id: "race_report",
+            cols: "100",
+            rows: "20",
+            "{race_report}"
+        },
+        br {},
+        button {
+            "type": "button",
+            "onclick": "raceResultNotesSave('{id}');",
+            "Save",
+        },
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Attachments"},
+            thead {
+                tr {
+                    th {"scope": "col", "Kind"},
+                    th {"scope": "col", "File"},
+                    th {"scope": "col"},
+                }
+            },
+            tbody {
+                {rows}
+            }
+        },
+        form {
+            action: "/garmin/race_result_attachment?id={id}",
+            method: "post",
+            enctype: "multipart/form-data",
+            "onsubmit": "return raceAttachmentUpload(this, '{id}');",
+            input {
+                "type": "file",
+                "aria-label": "Activity file",
+                name: "filename",
+            },
+            input {"type": "submit", value: "Upload"},
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn scale_duplicates_body(
+    message: Option<StackString>,
+    groups: Vec<Vec<ScaleMeasurement>>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        ScaleDuplicatesElement,
+        ScaleDuplicatesElementProps { message, groups },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn ScaleDuplicatesElement(
+    message: Option<StackString>,
+    groups: Vec<Vec<ScaleMeasurement>>,
+) -> Element {
+    let message = message.map(|message| {
+        rsx! {
+            p {"{message}"}
+        }
+    });
+    let ngroups = groups.len();
+    let rows = groups.iter().enumerate().flat_map(|(gidx, group)| {
+        let preferred = ScaleMeasurement::preferred(group).map(|m| m.id);
+        group.iter().enumerate().map(move |(idx, meas)| {
+            let id = meas.id;
+            let datetime = convert_datetime_to_str(meas.datetime.into());
+            let mass = meas.mass;
+            let fat_pct = meas.fat_pct;
+            let water_pct = meas.water_pct;
+            let muscle_pct = meas.muscle_pct;
+            let bone_pct = meas.bone_pct;
+            let suggested = if preferred == Some(id) { "*" } else { "" };
+            rsx! {
+                tr {
+                    key: "scale-duplicate-key-{gidx}-{idx}",
+                    td {"{gidx}"},
+                    td {"{datetime}"},
+                    td {"{mass:.1}"},
+                    td {"{fat_pct:.1}"},
+                    td {"{water_pct:.1}"},
+                    td {"{muscle_pct:.1}"},
+                    td {"{bone_pct:.1}"},
+                    td {"{suggested}"},
+                    td {
+                        button {
+                            "type": "submit",
+                            "onclick": "scaleDuplicateMerge('{id}');",
+                            "Keep",
+                        }
+                    },
                 }
             }
-        }
+        })
     });
-
     rsx! {
+        {message},
+        p {"{ngroups} groups of duplicates, keeping one deletes the rest of its group"},
         table {
             "border": "1",
-            tbody {
-                tr {td {"ID"}, td {"{id}"}},
-                tr {td {"Username"}, td {"{username}"}},
-                tr {td {"First Name"}, td {"{firstname}"}},
-                tr {td {"Last Name"}, td {"{lastname}"}},
-                tr {td {"City"}, td {"{city}"}},
-                tr {td {"State"}, td {"{state}"}},
-                tr {td {"Sex"}, td {"{sex}"}},
-                tr {td {"Weight"}, td {"{weight} lbs"}},
-                tr {td {"Created At"}, td {"{created_at}"}},
-                tr {td {"Updated At"}, td {"{updated_at}"}},
-                tr { {follower_count} },
-                tr { {friend_count} },
-                tr { {measurement_preference} },
+            class: "dataframe",
+            caption {class: "visually-hidden", "Duplicate scale measurements"},
+            thead {
+                tr {
+                    th {"scope": "col", "Group"},
+                    th {"scope": "col", "Date"},
+                    th {"scope": "col", "Weight"},
+                    th {"scope": "col", "Fat %"},
+                    th {"scope": "col", "Water %"},
+                    th {"scope": "col", "Muscle %"},
+                    th {"scope": "col", "Bone %"},
+                    th {"scope": "col", "Suggested"},
+                    th {"scope": "col"},
+                }
             },
-            {clubs},
-            {shoes},
+            tbody {
+                {rows}
+            }
         }
     }
 }
@@ -2126,12 +5958,14 @@ fn scale_measurement_manual_input_element() -> Element {
     rsx! {
         form {
             table {
+                "role": "presentation",
                 tbody {
                     tr {
                         td {"Weight (lbs)"}
                         td {
                             input {
                                 "type": "text",
+                                "aria-label": "Weight (lbs)",
                                 name: "weight_in_lbs",
                                 id: "weight_in_lbs",
                             }
@@ -2142,6 +5976,7 @@ fn scale_measurement_manual_input_element() -> Element {
                         td {
                             input {
                                 "type": "text",
+                                "aria-label": "Body fat %",
                                 name: "body_fat_percent",
                                 id: "body_fat_percent",
                             }
@@ -2152,6 +5987,7 @@ fn scale_measurement_manual_input_element() -> Element {
                         td {
                             input {
                                 "type": "text",
+                                "aria-label": "Muscle mass (lbs)",
                                 name: "muscle_mass_lbs",
                                 id: "muscle_mass_lbs",
                             }
@@ -2162,6 +5998,7 @@ fn scale_measurement_manual_input_element() -> Element {
                         td {
                             input {
                                 "type": "text",
+                                "aria-label": "Body water %",
                                 name: "body_water_percent",
                                 id: "body_water_percent",
                             }
@@ -2172,6 +6009,7 @@ fn scale_measurement_manual_input_element() -> Element {
                         td {
                             input {
                                 "type": "text",
+                                "aria-label": "Bone mass (lbs)",
                                 name: "bone_mass_lbs",
                                 id: "bone_mass_lbs",
                             }
@@ -2192,3 +6030,640 @@ fn scale_measurement_manual_input_element() -> Element {
         }
     }
 }
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn clothing_body(
+    message: Option<StackString>,
+    entries: Vec<ClothingLogEntry>,
+    recommendation: Option<ClothingRecommendation>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        ClothingElement,
+        ClothingElementProps {
+            message,
+            entries,
+            recommendation,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn ClothingElement(
+    message: Option<StackString>,
+    entries: Vec<ClothingLogEntry>,
+    recommendation: Option<ClothingRecommendation>,
+) -> Element {
+    let message = message.map(|message| rsx! {p{"{message}"}});
+    let recommendation = recommendation.map(|recommendation| {
+        let temperature = recommendation.temperature;
+        let best = match recommendation.best() {
+            Some(best) => format_sstr!("Wear {} at {temperature:.1} C", best.clothing),
+            None => format_sstr!("Nothing logged was just right at {temperature:.1} C"),
+        };
+        let rows = recommendation.outfits.iter().enumerate().map(|(idx, o)| {
+            let clothing = &o.clothing;
+            let too_cold = o.too_cold;
+            let just_right = o.just_right;
+            let too_hot = o.too_hot;
+            rsx! {
+                tr {
+                    key: "clothing-outfit-key-{idx}",
+                    td {"{clothing}"},
+                    td {"{too_cold}"},
+                    td {"{just_right}"},
+                    td {"{too_hot}"},
+                }
+            }
+        });
+        rsx! {
+            h4 {"{best}"},
+            table {
+                "border": "1",
+                class: "dataframe",
+                caption {class: "visually-hidden", "Clothing comfort"},
+                thead {
+                    tr {
+                        th {"scope": "col", "Outfit"},
+                        th {"scope": "col", "Too Cold"},
+                        th {"scope": "col", "Just Right"},
+                        th {"scope": "col", "Too Hot"},
+                    }
+                },
+                tbody {
+                    {rows}
+                }
+            }
+        }
+    });
+    let rows = entries.iter().enumerate().map(|(idx, entry)| {
+        let filename = &entry.filename;
+        let begin_datetime = entry.begin_datetime;
+        let sport = entry.sport.display_name();
+        let clothing = &entry.clothing;
+        let comfort = entry.comfort.display_name();
+        let temperature = match entry.temperature {
+            Some(t) if entry.device_temperature => format_sstr!("{t:.1} C (device)"),
+            Some(t) => format_sstr!("{t:.1} C"),
+            None => StackString::new(),
+        };
+        rsx! {
+            tr {
+                key: "clothing-entry-key-{idx}",
+                td {
+                    button {
+                        "type": "submit",
+                        "onclick": "send_command('filter={filename}');",
+                        "{begin_datetime}",
+                    }
+                },
+                td {"{sport}"},
+                td {"{temperature}"},
+                td {"{clothing}"},
+                td {"{comfort}"},
+            }
+        }
+    });
+    rsx! {
+        {message},
+        h3 {"Clothing Log"},
+        "Forecast temperature (C) ",
+        input {
+            "type": "number",
+            "aria-label": "Forecast temperature",
+            id: "forecast_temperature",
+            step: "any",
+        },
+        button {
+            "type": "submit",
+            "onclick": "clothingReport(document.getElementById('forecast_temperature').value);",
+            "Recommend",
+        },
+        {recommendation},
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Clothing log"},
+            thead {
+                tr {
+                    th {"scope": "col", "Start Time"},
+                    th {"scope": "col", "Sport"},
+                    th {"scope": "col", "Temperature"},
+                    th {"scope": "col", "Clothing"},
+                    th {"scope": "col", "Comfort"},
+                }
+            },
+            tbody {
+                {rows}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn route_profile_body(
+    unit: SplitUnit,
+    pace: StackString,
+    profile: Option<RouteProfile>,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        RouteProfileElement,
+        RouteProfileElementProps {
+            unit,
+            pace,
+            profile,
+        },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn RouteProfileElement(
+    unit: SplitUnit,
+    pace: StackString,
+    profile: Option<RouteProfile>,
+) -> Element {
+    let label = unit.label();
+    let profile = profile.map(|profile| {
+        let distance = profile.distance / unit.meters();
+        let adjusted = profile.grade_adjusted_distance / unit.meters();
+        let gain = profile.elevation_gain;
+        let loss = profile.elevation_loss;
+        let flat_pace =
+            print_h_m_s(profile.pace * unit.meters(), false).unwrap_or_else(|_| "".into());
+        let estimated_time =
+            print_h_m_s(profile.estimated_time, true).unwrap_or_else(|_| "".into());
+        let svg = profile.to_svg().unwrap_or_default();
+        let rows = profile.splits.iter().map(|split| {
+            let idx = split.split;
+            let distance = split.distance / unit.meters();
+            let gain = split.elevation_gain;
+            let loss = split.elevation_loss;
+            let grade = split.grade * 100.0;
+            let pace = print_h_m_s(split.pace(unit), false).unwrap_or_else(|_| "".into());
+            let elapsed = print_h_m_s(split.elapsed_time, true).unwrap_or_else(|_| "".into());
+            rsx! {
+                tr {
+                    key: "route-profile-key-{idx}",
+                    td {"{idx}"},
+                    td {"{distance:0.2}"},
+                    td {"{gain:0.0}"},
+                    td {"{loss:0.0}"},
+                    td {"{grade:0.1}"},
+                    td {"{pace}"},
+                    td {"{elapsed}"},
+                }
+            }
+        });
+        rsx! {
+            p {
+                "Distance {distance:0.2} {label}, gain {gain:0.0} m, loss {loss:0.0} m",
+                br {},
+                "Grade adjusted distance {adjusted:0.2} {label}",
+            },
+            p {"Estimated time {estimated_time} at {flat_pace} /{label} on the flat"},
+            div {
+                "role": "img",
+                "aria-label": "Elevation profile",
+                dangerous_inner_html: "{svg}",
+            },
+            table {
+                "border": "1",
+                caption {class: "visually-hidden", "Splits"},
+                thead {
+                    th {"scope": "col", "Split"},
+                    th {"scope": "col", "Distance ({label})"},
+                    th {"scope": "col", "Gain (m)"},
+                    th {"scope": "col", "Loss (m)"},
+                    th {"scope": "col", "Grade (%)"},
+                    th {"scope": "col", "Pace (min/{label})"},
+                    th {"scope": "col", "Elapsed"},
+                },
+                tbody {
+                    {rows},
+                }
+            },
+        }
+    });
+    rsx! {
+        form {
+            action: "/garmin/route_profile",
+            method: "post",
+            enctype: "multipart/form-data",
+            " GPX ",
+            input {"type": "file", "aria-label": "GPX file", name: "gpx"},
+            " Units ",
+            input {"type": "text", "aria-label": "Units", name: "units", value: "{label}"},
+            " Flat Pace (min/{label}, default from recent runs) ",
+            input {"type": "text", "aria-label": "Flat pace", name: "pace", value: "{pace}"},
+            input {"type": "submit", value: "Profile"},
+        },
+        {profile},
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn matched_routes_body(routes: Vec<(MatchedRoute, i64)>) -> Result<String, Error> {
+    let mut app =
+        VirtualDom::new_with_props(MatchedRoutesElement, MatchedRoutesElementProps { routes });
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn MatchedRoutesElement(routes: Vec<(MatchedRoute, i64)>) -> Element {
+    let rows = routes.iter().enumerate().map(|(idx, (route, attempts))| {
+        let id = route.id;
+        let name = &route.name;
+        let sport = route.sport;
+        let distance = route.distance / METERS_PER_MILE;
+        rsx! {
+            tr {
+                key: "matched-route-key-{idx}",
+                td {
+                    a {
+                        href: "/garmin/routes/progression?route_id={id}",
+                        "{name}",
+                    }
+                },
+                td {"{sport}"},
+                td {"{distance:0.2}"},
+                td {"{attempts}"},
+            }
+        }
+    });
+    rsx! {
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Matched routes"},
+            thead {
+                th {"scope": "col", "Route"},
+                th {"scope": "col", "Sport"},
+                th {"scope": "col", "Distance (mi)"},
+                th {"scope": "col", "Attempts"},
+            },
+            tbody {
+                {rows}
+            }
+        }
+    }
+}
+
+/// # Errors
+/// Returns error if formatting fails
+pub fn route_progression_body(
+    route: MatchedRoute,
+    bests: Vec<YearBest>,
+    svg: StackString,
+) -> Result<String, Error> {
+    let mut app = VirtualDom::new_with_props(
+        RouteProgressionElement,
+        RouteProgressionElementProps { route, bests, svg },
+    );
+    app.rebuild_in_place();
+    let mut renderer = dioxus_ssr::Renderer::default();
+    let mut buffer = String::new();
+    renderer
+        .render_to(&mut buffer, &app)
+        .map_err(Into::<Error>::into)?;
+    Ok(buffer)
+}
+
+#[component]
+fn RouteProgressionElement(route: MatchedRoute, bests: Vec<YearBest>, svg: StackString) -> Element {
+    let name = &route.name;
+    let distance = route.distance / METERS_PER_MILE;
+    let rows = bests.iter().enumerate().map(|(idx, best)| {
+        let year = best.year;
+        let filename = &best.attempt.filename;
+        let date = best.attempt.begin_datetime;
+        let time = print_h_m_s(best.attempt.total_duration, true).unwrap_or_else(|_| "".into());
+        let attempts = best.attempts;
+        let conditions = best.conditions();
+        let pb = if best.personal_best { "PB" } else { "" };
+        rsx! {
+            tr {
+                key: "route-progression-key-{idx}",
+                td {"{year}"},
+                td {
+                    a {
+                        href: "/garmin/index.html?filter={filename}",
+                        "{date}",
+                    }
+                },
+                td {"{time}"},
+                td {"{pb}"},
+                td {"{conditions}"},
+                td {"{attempts}"},
+            }
+        }
+    });
+    rsx! {
+        h3 {"{name} ({distance:0.2} mi)"},
+        div {
+            "role": "img",
+            "aria-label": "Best time by year for {name}",
+            dangerous_inner_html: "{svg}",
+        },
+        table {
+            "border": "1",
+            class: "dataframe",
+            caption {class: "visually-hidden", "Best attempt by year"},
+            thead {
+                th {"scope": "col", "Year"},
+                th {"scope": "col", "Best Attempt"},
+                th {"scope": "col", "Time"},
+                th {"scope": "col"},
+                th {"scope": "col", "Conditions"},
+                th {"scope": "col", "Attempts"},
+            },
+            tbody {
+                {rows}
+            }
+        },
+        a {
+            href: "/garmin/routes",
+            "All routes",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dioxus::prelude::Element;
+    use stack_string::StackString;
+    use time::macros::datetime;
+
+    use fitbit_lib::fitbit_heartrate::FitbitHeartRate;
+    use garmin_lib::{language::Language, split_distance::SplitDistance};
+    use garmin_models::{
+        garmin_connect_activity_detail::{GarminConnectActivityDetail, GarminConnectSplit},
+        garmin_file::GarminFile,
+        garmin_lap::GarminLap,
+        garmin_point::GarminPoint,
+        quarantined_file::QuarantinedFile,
+    };
+
+    use crate::garmin_elements::{
+        create_fitbit_table, generate_history_buttons, get_buttons, get_file_html,
+        get_html_connect_splits, get_html_splits, quarantine_body,
+        scale_measurement_manual_input_body,
+    };
+
+    /// Opening tags `<name ...>` in `html` with the offset just past each tag
+    fn opening_tags<'a>(html: &'a str, name: &str) -> Vec<(&'a str, usize)> {
+        let prefix = format!("<{name}");
+        html.match_indices(&prefix)
+            .filter_map(|(start, _)| {
+                let rest = &html[start + prefix.len()..];
+                if !rest.starts_with([' ', '>', '/']) {
+                    return None;
+                }
+                let end = start + html[start..].find('>')? + 1;
+                Some((&html[start..end], end))
+            })
+            .collect()
+    }
+
+    fn has_attr(tag: &str, attr: &str) -> bool {
+        tag.contains(&format!(" {attr}="))
+    }
+
+    /// Problems an assistive technology user would run into in `html`
+    fn accessibility_issues(html: &str) -> Vec<String> {
+        let mut issues = Vec::new();
+        for (tag, end) in opening_tags(html, "table") {
+            if !has_attr(tag, "aria-label")
+                && !tag.contains(r#"role="presentation""#)
+                && !html[end..].starts_with("<caption")
+            {
+                issues.push(format!("table without caption: {tag}"));
+            }
+        }
+        for (tag, _) in opening_tags(html, "th") {
+            if !has_attr(tag, "scope") {
+                issues.push(format!("header cell without scope: {tag}"));
+            }
+        }
+        for name in ["input", "select", "textarea"] {
+            for (tag, end) in opening_tags(html, name) {
+                let is_control = !["hidden", "submit", "button"]
+                    .iter()
+                    .any(|t| tag.contains(&format!(r#"type="{t}""#)));
+                let before = &html[..end];
+                let in_label = before.rfind("<label") > before.rfind("</label>");
+                if is_control && !in_label && !has_attr(tag, "aria-label") {
+                    issues.push(format!("unlabelled form control: {tag}"));
+                }
+            }
+        }
+        for (tag, end) in opening_tags(html, "button") {
+            let content = html[end..].split("</button>").next().unwrap_or("");
+            let text = content.replace("&nbsp;", "");
+            // the status output is a live region, it only gets text later
+            if text.trim().is_empty() && !has_attr(tag, "aria-label") && !has_attr(tag, "aria-live")
+            {
+                issues.push(format!("button without a name: {tag}"));
+            }
+        }
+        for (start, _) in html.match_indices(" onclick=") {
+            let tag_start = html[..start].rfind('<').unwrap_or(0);
+            let tag = &html[tag_start..start];
+            let focusable = ["<button", "<input", "<select", "<a "]
+                .iter()
+                .any(|t| tag.starts_with(t))
+                || has_attr(tag, "tabindex");
+            if !focusable {
+                issues.push(format!("click handler not reachable by keyboard: {tag}"));
+            }
+        }
+        for (start, _) in html.match_indices(r#" role="img""#) {
+            let tag_start = html[..start].rfind('<').unwrap_or(0);
+            let tag_end = start + html[start..].find('>').unwrap_or(0);
+            if !has_attr(&html[tag_start..tag_end], "aria-label") {
+                issues.push("image without text alternative".into());
+            }
+        }
+        issues
+    }
+
+    fn assert_accessible(element: Element) {
+        let html = dioxus_ssr::render_element(element);
+        let issues = accessibility_issues(&html);
+        assert!(issues.is_empty(), "{issues:#?}\n{html}");
+    }
+
+    fn test_file() -> GarminFile {
+        let begin = datetime!(2024-05-04 13:00:00 UTC);
+        let mut gfile = GarminFile::new();
+        gfile.filename = "2024-05-04_13-00-00_1_1.fit".into();
+        gfile.begin_datetime = begin.into();
+        gfile.laps = vec![GarminLap {
+            lap_start: begin.into(),
+            lap_distance: 3218.7,
+            lap_duration: 1200.0,
+            ..GarminLap::new()
+        }];
+        gfile.points = (0..=32_i32)
+            .map(|idx| GarminPoint {
+                time: (begin + time::Duration::seconds((idx * 37).into())).into(),
+                distance: Some(f64::from(idx) * 100.0),
+                heart_rate: Some(150.0),
+                ..GarminPoint::default()
+            })
+            .collect();
+        gfile.total_distance = 3200.0;
+        gfile.total_duration = 1184.0;
+        gfile
+    }
+
+    #[test]
+    fn test_accessibility_checker() {
+        let issues = accessibility_issues(
+            r#"<table><thead><tr><th>A</th></tr></thead></table><div onclick="f()">x</div><button></button><input type="text">"#,
+        );
+        assert_eq!(issues.len(), 5, "{issues:#?}");
+    }
+
+    #[test]
+    fn test_buttons_accessible() {
+        assert_accessible(get_buttons(false, Language::English));
+        assert_accessible(get_buttons(true, Language::German));
+        let history: Vec<StackString> = vec!["2024-05,run".into(), "latest".into()];
+        let pinned: Vec<StackString> = vec!["latest".into()];
+        assert_accessible(generate_history_buttons(&history, &pinned, false));
+    }
+
+    #[test]
+    fn test_history_buttons_escape_filter() {
+        let history: Vec<StackString> = vec!["');alert(1);('".into()];
+        let html = dioxus_ssr::render_element(generate_history_buttons(&history, &history, false));
+        assert!(html.contains(r#"onclick="pinHistory(this.dataset.filter, false)""#));
+        assert!(html.contains(r#"onclick="deleteHistory(this.dataset.filter)""#));
+        assert!(!html.contains("pinHistory('"));
+        assert!(!html.contains("deleteHistory('"));
+    }
+
+    #[test]
+    fn test_file_page_accessible() {
+        let gfile = test_file();
+        for language in Language::all() {
+            assert_accessible(get_file_html(
+                &gfile,
+                None,
+                None,
+                None,
+                None,
+                &[],
+                None,
+                Some(6),
+                language,
+            ));
+            assert_accessible(get_html_splits(&gfile, SplitDistance::default(), language));
+        }
+    }
+
+    #[test]
+    fn test_file_page_connect_detail() {
+        let gfile = test_file();
+        let detail = GarminConnectActivityDetail {
+            temperature: Some(12.5),
+            weather: Some("Cloudy".into()),
+            gear: Some("Pegasus".into()),
+            ..GarminConnectActivityDetail::new(1)
+        };
+        let html = dioxus_ssr::render_element(get_file_html(
+            &gfile,
+            None,
+            None,
+            Some(&detail),
+            None,
+            &[],
+            None,
+            None,
+            Language::English,
+        ));
+        // once in the summary table and once in the weather
+        assert_eq!(html.matches("12.5 C").count(), 2, "{html}");
+        assert!(
+            html.contains("Weather: Cloudy 12.5 C, Gear: Pegasus"),
+            "{html}"
+        );
+        assert!(accessibility_issues(&html).is_empty(), "{html}");
+
+        assert!(get_html_connect_splits(&[], Language::English).is_none());
+        let split = GarminConnectSplit {
+            activity_id: 1,
+            split_index: 1,
+            start_time_gmt: gfile.begin_datetime,
+            distance: 1609.344,
+            duration: 480.0,
+            moving_duration: None,
+            average_hr: Some(150.0),
+            max_hr: None,
+            calories: None,
+            average_cadence: Some(172.0),
+            average_power: None,
+            elevation_gain: None,
+        };
+        let splits = get_html_connect_splits(&[split], Language::German).unwrap();
+        let html = dioxus_ssr::render_element(splits);
+        assert!(html.contains("00:08:00"), "{html}");
+        assert!(html.contains("172 /min"), "{html}");
+    }
+
+    #[test]
+    fn test_tables_accessible() {
+        let values = vec![FitbitHeartRate {
+            datetime: datetime!(2024-05-04 13:00:00 UTC).into(),
+            value: 150,
+        }];
+        for html in [
+            create_fitbit_table(values).unwrap(),
+            quarantine_body(
+                None,
+                vec![QuarantinedFile::new("bad.fit", "Invalid extension")],
+            )
+            .unwrap(),
+            scale_measurement_manual_input_body().unwrap(),
+        ] {
+            let issues = accessibility_issues(&html);
+            assert!(issues.is_empty(), "{issues:#?}\n{html}");
+        }
+    }
+
+    #[test]
+    fn test_quarantine_retry_escapes_filename() {
+        let html = quarantine_body(
+            None,
+            vec![QuarantinedFile::new(
+                "x');alert(1);('.fit",
+                "Invalid extension",
+            )],
+        )
+        .unwrap();
+        assert!(html.contains(r#"onclick="quarantineRetry(this.dataset.filename);""#));
+        assert!(!html.contains("quarantineRetry('"));
+    }
+}
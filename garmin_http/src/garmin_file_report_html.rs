@@ -1,21 +1,33 @@
-use stack_string::format_sstr;
+use stack_string::{format_sstr, StackString};
+use uuid::Uuid;
 
-use garmin_models::garmin_file::GarminFile;
-use garmin_utils::{garmin_util::METERS_PER_MILE, plot_opts::PlotOpts};
+use garmin_models::{
+    activity_distribution::{cadence_per_minute, ActivityDistribution, DistributionMetric},
+    garmin_file::GarminFile,
+    power_threshold::PowerThreshold,
+};
+use garmin_utils::{
+    garmin_util::METERS_PER_MILE,
+    plot_opts::{PlotAxis, PlotOpts, PlotSeries, PlotXAxis},
+};
 
 use garmin_reports::garmin_file_report_txt::get_splits;
 
+const HR_STREAM_COLORS: [&str; 4] = ["#d62728", "#1f77b4", "#2ca02c", "#9467bd"];
+
 #[derive(Default, PartialEq, Clone)]
 pub struct ReportObjects {
     pub avg_hr: f64,
     pub sum_time: f64,
     pub max_hr: f64,
+    pub xaxis: PlotXAxis,
 
     pub hr_vals: Vec<f64>,
     pub hr_values: Vec<(f64, f64)>,
     pub alt_vals: Vec<f64>,
     pub alt_values: Vec<(f64, f64)>,
     pub mph_speed_values: Vec<(f64, f64)>,
+    pub pace_values: Vec<(f64, f64)>,
     pub avg_speed_values: Vec<(f64, f64)>,
     pub avg_mph_speed_values: Vec<(f64, f64)>,
     pub lat_vals: Vec<f64>,
@@ -23,10 +35,44 @@ pub struct ReportObjects {
     pub mile_split_vals: Vec<(f64, f64)>,
     pub speed_values: Vec<(f64, f64)>,
     pub heart_rate_speed: Vec<(f64, f64)>,
+    pub lap_markers: Vec<(f64, i32)>,
+    pub lap_pins: Vec<(f64, f64, i32)>,
+    /// Heart rate per recording device as (minutes since start, bpm)
+    pub hr_streams: Vec<(StackString, Vec<(f64, f64)>)>,
+    /// W' balance in kJ along the activity, empty without power data
+    pub w_prime_balance: Vec<(f64, f64)>,
+    /// W' in kJ used for `w_prime_balance`
+    pub w_prime: f64,
+    /// Respiration rate in breaths per minute, empty if not recorded
+    pub respiration_values: Vec<(f64, f64)>,
+    /// Ambient temperature in degrees Celsius, empty if not recorded
+    pub temperature_values: Vec<(f64, f64)>,
+    /// Cadence in `cadence_units`, empty if not recorded
+    pub cadence_values: Vec<(f64, f64)>,
+    /// Minutes in each cadence bucket, empty if not recorded
+    pub cadence_distribution: Vec<(f64, f64)>,
+    /// spm for running, rpm otherwise
+    pub cadence_units: StackString,
+    /// Minutes in each stride length bucket (m), running only
+    pub stride_distribution: Vec<(f64, f64)>,
 }
 
+/// Extract plot data from `gfile`, point based plots use `xaxis` if given,
+/// falling back to elapsed time when the file has no distance data
 #[must_use]
-pub fn extract_report_objects_from_file(gfile: &GarminFile) -> ReportObjects {
+pub fn extract_report_objects_from_file(
+    gfile: &GarminFile,
+    xaxis: Option<PlotXAxis>,
+) -> ReportObjects {
+    let has_distance = gfile
+        .points
+        .iter()
+        .any(|p| p.distance.is_some_and(|d| d > 0.0));
+    let xaxis = match xaxis {
+        Some(PlotXAxis::Time) => PlotXAxis::Time,
+        _ if !has_distance => PlotXAxis::Time,
+        _ => PlotXAxis::Distance,
+    };
     let speed_values = get_splits(gfile, 400., "lap", true);
     let mut heart_rate_speed: Vec<_> = speed_values
         .iter()
@@ -62,14 +108,31 @@ pub fn extract_report_objects_from_file(gfile: &GarminFile) -> ReportObjects {
         mile_split_vals,
         speed_values,
         heart_rate_speed,
+        xaxis,
+        cadence_units: DistributionMetric::Cadence.units(gfile.sport).into(),
         ..ReportObjects::default()
     };
 
-    for point in &gfile.points {
-        if point.distance.is_none() {
-            continue;
+    for distribution in ActivityDistribution::from_gfile(Uuid::nil(), gfile) {
+        let minutes = distribution
+            .buckets
+            .iter()
+            .map(|(bucket, seconds)| (*bucket, seconds / 60.0))
+            .collect();
+        match distribution.metric {
+            DistributionMetric::Cadence => report_objs.cadence_distribution = minutes,
+            DistributionMetric::StrideLength => report_objs.stride_distribution = minutes,
         }
-        let xval = point.distance.unwrap_or(0.0) / METERS_PER_MILE;
+    }
+
+    for point in &gfile.points {
+        let xval = match xaxis {
+            PlotXAxis::Distance => match point.distance {
+                Some(d) => d / METERS_PER_MILE,
+                None => continue,
+            },
+            PlotXAxis::Time => point.duration_from_begin / 60.0,
+        };
         if xval > 0.0 {
             if let Some(hr) = point.heart_rate {
                 if hr > 0.0 {
@@ -79,7 +142,22 @@ pub fn extract_report_objects_from_file(gfile: &GarminFile) -> ReportObjects {
                     report_objs.hr_values.push((xval, hr));
                 }
             }
+            if let Some(rr) = point.respiration_rate {
+                if rr > 0.0 {
+                    report_objs.respiration_values.push((xval, rr));
+                }
+            }
+            if let Some(cadence) = point.cadence {
+                if cadence > 0.0 {
+                    report_objs
+                        .cadence_values
+                        .push((xval, cadence_per_minute(cadence, gfile.sport)));
+                }
+            }
         };
+        if let Some(temperature) = point.temperature {
+            report_objs.temperature_values.push((xval, temperature));
+        }
         if let Some(alt) = point.altitude {
             if (alt > 0.0) & (alt < 10000.0) {
                 report_objs.alt_vals.push(alt);
@@ -88,6 +166,10 @@ pub fn extract_report_objects_from_file(gfile: &GarminFile) -> ReportObjects {
         };
         if (point.speed_mph > 0.0) & (point.speed_mph < 20.0) {
             report_objs.mph_speed_values.push((xval, point.speed_mph));
+            let pace = 60.0 / point.speed_mph;
+            if pace < 20.0 {
+                report_objs.pace_values.push((xval, pace));
+            }
         };
         if (point.avg_speed_value_permi > 0.0) & (point.avg_speed_value_permi < 20.0) {
             report_objs
@@ -107,6 +189,30 @@ pub fn extract_report_objects_from_file(gfile: &GarminFile) -> ReportObjects {
         };
     }
 
+    if gfile.laps.len() > 1 {
+        let mut lap_start = 0.0;
+        for (idx, lap) in gfile.laps.iter().enumerate() {
+            let lap_number = idx as i32 + 1;
+            if idx > 0 {
+                report_objs.lap_markers.push((lap_start, lap_number));
+            }
+            lap_start += match xaxis {
+                PlotXAxis::Distance => lap.lap_distance / METERS_PER_MILE,
+                PlotXAxis::Time => lap.lap_duration / 60.0,
+            };
+            let pin = gfile
+                .points
+                .iter()
+                .filter(|p| p.time >= lap.lap_start)
+                .find_map(|p| Some((p.latitude?, p.longitude?)));
+            if let Some((lat, lon)) = pin {
+                report_objs.lap_pins.push((lat, lon, lap_number));
+            }
+        }
+        report_objs.lap_markers.shrink_to_fit();
+        report_objs.lap_pins.shrink_to_fit();
+    }
+
     if report_objs.sum_time > 0.0 {
         report_objs.avg_hr /= report_objs.sum_time;
         report_objs.max_hr = *report_objs
@@ -119,9 +225,43 @@ pub fn extract_report_objects_from_file(gfile: &GarminFile) -> ReportObjects {
     report_objs
 }
 
+/// Fill `report_objs.w_prime_balance` from the power data of `gfile`
+pub fn add_w_prime_balance(
+    report_objs: &mut ReportObjects,
+    gfile: &GarminFile,
+    threshold: &PowerThreshold,
+) {
+    let points: Vec<_> = gfile.points.iter().filter(|p| p.power.is_some()).collect();
+    let samples: Vec<_> = points
+        .iter()
+        .filter_map(|p| p.power.map(|w| (p.duration_from_begin, w)))
+        .collect();
+    let balance = threshold.w_prime_balance(&samples);
+    let mut w_prime_balance: Vec<_> = points
+        .iter()
+        .zip(balance)
+        .filter_map(|(point, balance)| {
+            let xval = match report_objs.xaxis {
+                PlotXAxis::Distance => point.distance? / METERS_PER_MILE,
+                PlotXAxis::Time => point.duration_from_begin / 60.0,
+            };
+            Some((xval, balance / 1000.0))
+        })
+        .collect();
+    w_prime_balance.shrink_to_fit();
+    report_objs.w_prime_balance = w_prime_balance;
+    report_objs.w_prime = threshold.w_prime / 1000.0;
+}
+
 #[must_use]
 pub fn get_plot_opts(report_objs: &ReportObjects) -> Vec<PlotOpts> {
     let mut plot_opts = Vec::new();
+    let xlabel = report_objs.xaxis.label();
+    // split based plots are always against distance
+    let distance_markers: &[(f64, i32)] = match report_objs.xaxis {
+        PlotXAxis::Distance => &report_objs.lap_markers,
+        PlotXAxis::Time => &[],
+    };
 
     if !report_objs.mile_split_vals.is_empty() {
         plot_opts.push(
@@ -129,6 +269,7 @@ pub fn get_plot_opts(report_objs: &ReportObjects) -> Vec<PlotOpts> {
                 .with_name("mile_splits")
                 .with_title("Pace per Mile every mi")
                 .with_data(&report_objs.mile_split_vals)
+                .with_markers(distance_markers)
                 .with_marker("o")
                 .with_labels("mi", "min/mi"),
         );
@@ -144,7 +285,60 @@ pub fn get_plot_opts(report_objs: &ReportObjects) -> Vec<PlotOpts> {
                     report_objs.max_hr
                 ))
                 .with_data(&report_objs.hr_values)
-                .with_labels("mi", "bpm"),
+                .with_markers(&report_objs.lap_markers)
+                .with_labels(xlabel, "bpm"),
+        );
+    };
+
+    if !report_objs.respiration_values.is_empty() {
+        let avg_rr = report_objs
+            .respiration_values
+            .iter()
+            .map(|(_, rr)| rr)
+            .sum::<f64>()
+            / report_objs.respiration_values.len() as f64;
+        plot_opts.push(
+            PlotOpts::new()
+                .with_name("respiration_rate")
+                .with_title(&format_sstr!("Respiration Rate {avg_rr:2.1} avg brpm"))
+                .with_data(&report_objs.respiration_values)
+                .with_markers(&report_objs.lap_markers)
+                .with_labels(xlabel, "brpm"),
+        );
+    };
+
+    if !report_objs.cadence_values.is_empty() {
+        let units = &report_objs.cadence_units;
+        let avg_cadence = report_objs
+            .cadence_values
+            .iter()
+            .map(|(_, c)| c)
+            .sum::<f64>()
+            / report_objs.cadence_values.len() as f64;
+        plot_opts.push(
+            PlotOpts::new()
+                .with_name("cadence")
+                .with_title(&format_sstr!("Cadence {avg_cadence:3.0} avg {units}"))
+                .with_data(&report_objs.cadence_values)
+                .with_markers(&report_objs.lap_markers)
+                .with_labels(xlabel, units),
+        );
+    };
+
+    if !report_objs.temperature_values.is_empty() {
+        let avg_temperature = report_objs
+            .temperature_values
+            .iter()
+            .map(|(_, t)| t)
+            .sum::<f64>()
+            / report_objs.temperature_values.len() as f64;
+        plot_opts.push(
+            PlotOpts::new()
+                .with_name("temperature")
+                .with_title(&format_sstr!("Temperature {avg_temperature:2.1} avg C"))
+                .with_data(&report_objs.temperature_values)
+                .with_markers(&report_objs.lap_markers)
+                .with_labels(xlabel, "temperature [C]"),
         );
     };
 
@@ -154,7 +348,7 @@ pub fn get_plot_opts(report_objs: &ReportObjects) -> Vec<PlotOpts> {
                 .with_name("altitude")
                 .with_title("Altitude")
                 .with_data(&report_objs.alt_values)
-                .with_labels("mi", "height [m]"),
+                .with_labels(xlabel, "height [m]"),
         );
     };
 
@@ -164,6 +358,7 @@ pub fn get_plot_opts(report_objs: &ReportObjects) -> Vec<PlotOpts> {
                 .with_name("speed_minpermi")
                 .with_title("Speed min/mi every 1/4 mi")
                 .with_data(&report_objs.speed_values)
+                .with_markers(distance_markers)
                 .with_labels("mi", "min/mi"),
         );
 
@@ -172,7 +367,8 @@ pub fn get_plot_opts(report_objs: &ReportObjects) -> Vec<PlotOpts> {
                 .with_name("speed_mph")
                 .with_title("Speed mph")
                 .with_data(&report_objs.mph_speed_values)
-                .with_labels("mi", "mph"),
+                .with_markers(&report_objs.lap_markers)
+                .with_labels(xlabel, "mph"),
         );
     };
 
@@ -207,6 +403,107 @@ pub fn get_plot_opts(report_objs: &ReportObjects) -> Vec<PlotOpts> {
         );
     };
 
+    if !report_objs.hr_values.is_empty()
+        && !(report_objs.pace_values.is_empty() && report_objs.alt_values.is_empty())
+    {
+        let mut combined = PlotOpts::new()
+            .with_name("combined")
+            .with_title("Heart Rate, Pace and Altitude")
+            .with_labels(xlabel, "bpm")
+            .with_markers(&report_objs.lap_markers)
+            .with_series(
+                PlotSeries::new("Heart Rate", "bpm", &report_objs.hr_values, PlotAxis::Left)
+                    .with_color("#d62728"),
+            );
+        if !report_objs.pace_values.is_empty() {
+            combined = combined.with_series(PlotSeries::new(
+                "Pace",
+                "min/mi",
+                &report_objs.pace_values,
+                PlotAxis::Right,
+            ));
+        }
+        if !report_objs.alt_values.is_empty() {
+            combined = combined.with_series(
+                PlotSeries::new(
+                    "Altitude",
+                    "m",
+                    &report_objs.alt_values,
+                    PlotAxis::Background,
+                )
+                .with_color("#bbbbbb"),
+            );
+        }
+        plot_opts.push(combined);
+    };
+
+    if !report_objs.cadence_distribution.is_empty() {
+        let units = &report_objs.cadence_units;
+        plot_opts.push(
+            PlotOpts::new()
+                .with_name("cadence_distribution")
+                .with_title(&format_sstr!("Time in Cadence Zone ({units})"))
+                .with_data(&report_objs.cadence_distribution)
+                .with_marker("o")
+                .with_labels(units, "min"),
+        );
+    };
+
+    if !report_objs.stride_distribution.is_empty() {
+        plot_opts.push(
+            PlotOpts::new()
+                .with_name("stride_distribution")
+                .with_title("Time by Stride Length")
+                .with_data(&report_objs.stride_distribution)
+                .with_marker("o")
+                .with_labels("m", "min"),
+        );
+    };
+
+    if !report_objs.w_prime_balance.is_empty() {
+        let min_balance = report_objs
+            .w_prime_balance
+            .iter()
+            .map(|(_, b)| *b)
+            .fold(f64::INFINITY, f64::min);
+        plot_opts.push(
+            PlotOpts::new()
+                .with_name("w_prime_balance")
+                .with_title(&format_sstr!(
+                    "W' Balance {min_balance:0.1} kJ min of {:0.1} kJ",
+                    report_objs.w_prime
+                ))
+                .with_labels(xlabel, "kJ")
+                .with_markers(&report_objs.lap_markers)
+                .with_series(
+                    PlotSeries::new(
+                        "W' balance",
+                        "kJ",
+                        &report_objs.w_prime_balance,
+                        PlotAxis::Left,
+                    )
+                    .with_color("#d62728"),
+                ),
+        );
+    };
+
+    if report_objs.hr_streams.len() > 1 {
+        let mut comparison = PlotOpts::new()
+            .with_name("heart_rate_sources")
+            .with_title("Heart Rate by Device")
+            .with_labels("min", "bpm");
+        for ((source, values), color) in report_objs
+            .hr_streams
+            .iter()
+            .zip(HR_STREAM_COLORS.iter().cycle())
+        {
+            comparison = comparison.with_series(
+                PlotSeries::new(source, "bpm", values, PlotAxis::Left).with_color(color),
+            );
+        }
+        plot_opts.push(comparison);
+    };
+
     if !report_objs.avg_mph_speed_values.is_empty() {
         let (_, avg_mph_speed_value) = report_objs
             .avg_mph_speed_values
@@ -219,7 +516,7 @@ pub fn get_plot_opts(report_objs: &ReportObjects) -> Vec<PlotOpts> {
                 .with_title(&format_sstr!("Avg Speed {avg_mph_speed_value:.2} mph"))
                 .with_data(&report_objs.avg_mph_speed_values)
                 .with_scatter()
-                .with_labels("mi", "min/mi"),
+                .with_labels(xlabel, "min/mi"),
         );
     };
 
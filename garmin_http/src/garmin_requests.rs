@@ -15,12 +15,15 @@ use fitbit_lib::{
 use garmin_cli::garmin_cli::{GarminCli, GarminRequest};
 use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
 use garmin_models::{
-    garmin_correction_lap::GarminCorrectionLap, garmin_summary::GarminSummary,
+    demo_anonymizer::DemoAnonymizer,
+    garmin_correction_lap::GarminCorrectionLap,
+    garmin_summary::GarminSummary,
+    provenance::{DataProvider, Provenance},
     strava_activity::StravaActivity,
 };
 use garmin_reports::garmin_constraints::GarminConstraints;
 use garmin_utils::pgpool::PgPool;
-use strava_lib::strava_client::StravaClient;
+use strava_lib::strava_client::{StravaClient, StravaSyncResult, DEFAULT_ACCOUNT};
 
 use crate::{
     errors::ServiceError as Error, sport_types_wrapper::SportTypesWrapper, FitbitHeartRateWrapper,
@@ -49,6 +52,10 @@ impl From<GarminHtmlRequest> for GarminListRequest {
 pub struct StravaSyncRequest {
     pub start_datetime: Option<DateTimeType>,
     pub end_datetime: Option<DateTimeType>,
+    #[schema(description = "List the changes without writing anything")]
+    pub dry_run: Option<bool>,
+    #[schema(description = "Report the changed fields of each activity")]
+    pub verbose: Option<bool>,
 }
 
 impl StravaSyncRequest {
@@ -58,7 +65,7 @@ impl StravaSyncRequest {
         &self,
         pool: &PgPool,
         config: &GarminConfig,
-    ) -> Result<Vec<StravaActivity>, Error> {
+    ) -> Result<StravaSyncResult, Error> {
         let gcli = GarminCli::from_pool(pool)?;
 
         let start_datetime = self
@@ -69,25 +76,39 @@ impl StravaSyncRequest {
             .end_datetime
             .map(Into::into)
             .or_else(|| Some(OffsetDateTime::now_utc()));
+        let dry_run = self.dry_run.unwrap_or(false);
+
+        let result = StravaClient::sync_all_accounts(
+            config.clone(),
+            start_datetime,
+            end_datetime,
+            pool,
+            dry_run,
+        )
+        .await?;
+        if dry_run {
+            return Ok(result);
+        }
 
-        let client = StravaClient::with_auth(config.clone()).await?;
-        let activities = client
-            .sync_with_client(start_datetime, end_datetime, pool)
-            .await?;
-
-        if !activities.is_empty() {
+        if !result.activities.is_empty() {
             gcli.sync_everything().await?;
             gcli.proc_everything().await?;
         }
         StravaActivity::fix_summary_id_in_db(pool).await?;
 
-        Ok(activities)
+        Ok(result)
     }
 }
 
 #[derive(Serialize, Deserialize, Schema)]
 pub struct FitbitHeartrateCacheRequest {
-    date: DateType,
+    pub date: DateType,
+    #[schema(description = "Offset")]
+    pub offset: Option<usize>,
+    #[schema(description = "Limit")]
+    pub limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    pub cursor: Option<StackString>,
 }
 
 impl FitbitHeartrateCacheRequest {
@@ -126,7 +147,7 @@ pub struct FitbitTcxSyncRequest {
     pub start_date: Option<DateType>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, Schema)]
+#[derive(Serialize, Deserialize, Debug, Clone, Schema)]
 pub struct ScaleMeasurementRequest {
     #[schema(description = "Start Date")]
     pub start_date: Option<DateType>,
@@ -138,9 +159,26 @@ pub struct ScaleMeasurementRequest {
     pub offset: Option<usize>,
     #[schema(description = "Limit")]
     pub limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    pub cursor: Option<StackString>,
+    #[schema(description = "Biomarker series to overlay on plots")]
+    pub overlay: Option<StackString>,
 }
 
 impl ScaleMeasurementRequest {
+    /// Real dates of a request from the shifted demo pages, missing dates
+    /// keep defaulting to the latest real data
+    #[must_use]
+    pub fn unshift_demo(self, anonymizer: &DemoAnonymizer) -> Self {
+        let unshift = |d: Option<DateType>| d.map(|d| anonymizer.unshift_date(d.into()).into());
+        Self {
+            start_date: unshift(self.start_date),
+            end_date: unshift(self.end_date),
+            button_date: unshift(self.button_date),
+            ..self
+        }
+    }
+
     fn add_default(&self, ndays: i64) -> Self {
         let local = DateTimeWrapper::local_tz();
         Self {
@@ -163,6 +201,8 @@ impl ScaleMeasurementRequest {
             },
             offset: self.offset,
             limit: self.limit,
+            cursor: self.cursor.clone(),
+            overlay: self.overlay.clone(),
         }
     }
 }
@@ -172,6 +212,7 @@ pub struct FitbitStatisticsPlotRequest {
     pub end_date: DateType,
     pub offset: usize,
     pub is_demo: bool,
+    pub overlay: Option<StackString>,
 }
 
 impl From<ScaleMeasurementRequest> for FitbitStatisticsPlotRequest {
@@ -182,6 +223,7 @@ impl From<ScaleMeasurementRequest> for FitbitStatisticsPlotRequest {
             end_date: item.end_date.expect("this should be impossible"),
             offset: item.offset.unwrap_or(0),
             is_demo: false,
+            overlay: item.overlay,
         }
     }
 }
@@ -191,6 +233,7 @@ pub struct ScaleMeasurementPlotRequest {
     pub end_date: DateType,
     pub offset: usize,
     pub is_demo: bool,
+    pub overlay: Option<StackString>,
 }
 
 impl From<ScaleMeasurementRequest> for ScaleMeasurementPlotRequest {
@@ -201,6 +244,7 @@ impl From<ScaleMeasurementRequest> for ScaleMeasurementPlotRequest {
             end_date: item.end_date.expect("this should be impossible"),
             offset: item.offset.unwrap_or(0),
             is_demo: false,
+            overlay: item.overlay,
         }
     }
 }
@@ -240,6 +284,21 @@ pub struct StravaActivitiesRequest {
     pub offset: Option<usize>,
     #[schema(description = "Limit")]
     pub limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    pub cursor: Option<StackString>,
+    #[schema(description = "Strava Account, defaults to the default account")]
+    pub account: Option<StackString>,
+}
+
+/// Authorized client for `account`, or the default account
+async fn strava_client(
+    config: &GarminConfig,
+    account: Option<&StackString>,
+) -> Result<StravaClient, Error> {
+    let account = account.map_or(DEFAULT_ACCOUNT, StackString::as_str);
+    StravaClient::with_auth_account(config.clone(), account)
+        .await
+        .map_err(Into::into)
 }
 
 impl StravaActivitiesRequest {
@@ -249,7 +308,7 @@ impl StravaActivitiesRequest {
         &self,
         config: &GarminConfig,
     ) -> Result<Vec<StravaActivity>, Error> {
-        let client = StravaClient::with_auth(config.clone()).await?;
+        let client = strava_client(config, self.account.as_ref()).await?;
         let start_date = self.start_date.map(|s| {
             let d: Date = s.into();
             d.with_time(time!(00:00:00)).assume_utc()
@@ -277,6 +336,8 @@ pub struct StravaUploadRequest {
     pub description: Option<StackString>,
     #[schema(description = "Privacy Flag")]
     pub is_private: Option<bool>,
+    #[schema(description = "Strava Account, defaults to the default account")]
+    pub account: Option<StackString>,
 }
 
 impl StravaUploadRequest {
@@ -287,8 +348,7 @@ impl StravaUploadRequest {
         if !filename.exists() {
             return Ok(format_sstr!("File {} does not exist", self.filename));
         }
-        let config = config.clone();
-        let client = StravaClient::with_auth(config).await?;
+        let client = strava_client(config, self.account.as_ref()).await?;
         client
             .upload_strava_activity(
                 &filename,
@@ -314,16 +374,27 @@ pub struct StravaUpdateRequest {
     pub is_private: Option<bool>,
     #[schema(description = "Start DateTime")]
     pub start_time: Option<DateTimeType>,
+    #[schema(description = "Strava Account, defaults to the account the activity was synced from")]
+    pub account: Option<StackString>,
 }
 
 impl StravaUpdateRequest {
     /// # Errors
     /// Returns error if db query fails
-    pub async fn run_update(&self, config: &GarminConfig) -> Result<Url, Error> {
+    pub async fn run_update(&self, pool: &PgPool, config: &GarminConfig) -> Result<Url, Error> {
         let sport = self.activity_type.parse()?;
 
-        let config = config.clone();
-        let client = StravaClient::with_auth(config).await?;
+        let account = match &self.account {
+            Some(account) => Some(account.clone()),
+            None => {
+                let activity_id = i64::try_from(self.activity_id)
+                    .map_err(|e| Error::BadRequest(format!("{e}")))?;
+                StravaActivity::get_by_id(pool, activity_id)
+                    .await?
+                    .and_then(|a| a.strava_account)
+            }
+        };
+        let client = strava_client(config, account.as_ref()).await?;
         let body = client
             .update_strava_activity(
                 self.activity_id,
@@ -340,6 +411,8 @@ impl StravaUpdateRequest {
 #[derive(Debug, Serialize, Deserialize, Schema)]
 pub struct StravaCreateRequest {
     pub filename: StackString,
+    #[schema(description = "Strava Account, defaults to the default account")]
+    pub account: Option<StackString>,
 }
 
 impl StravaCreateRequest {
@@ -352,11 +425,16 @@ impl StravaCreateRequest {
     ) -> Result<Option<i64>, Error> {
         if let Some(gfile) = GarminSummary::get_by_filename(pool, self.filename.as_str()).await? {
             let mut strava_activity: StravaActivity = gfile.into();
-            let config = config.clone();
-            let client = StravaClient::with_auth(config).await?;
+            let client = strava_client(config, self.account.as_ref()).await?;
             let activity_id = client.create_strava_activity(&strava_activity).await?;
             strava_activity.id = activity_id;
-            strava_activity.insert_into_db(pool).await?;
+            strava_activity.strava_account = Some(client.account.clone());
+            let provenance = Provenance::new(
+                DataProvider::Upload,
+                "/garmin/strava/create",
+                Some(self.filename.as_str()),
+            );
+            strava_activity.insert_into_db(pool, &provenance).await?;
             StravaActivity::fix_summary_id_in_db(pool).await?;
             Ok(Some(activity_id))
         } else {
@@ -377,6 +455,12 @@ pub struct AddGarminCorrectionRequest {
     pub duration: Option<f64>,
     #[schema(description = "Sport")]
     pub sport: Option<SportTypesWrapper>,
+    #[schema(description = "Actual Pool Length (m)")]
+    pub pool_length: Option<f64>,
+    #[schema(description = "Pool Length Set On Watch (m)")]
+    pub recorded_pool_length: Option<f64>,
+    #[schema(description = "Remove The Pool Length Correction")]
+    pub clear_pool_length: Option<bool>,
 }
 
 impl AddGarminCorrectionRequest {
@@ -407,6 +491,27 @@ impl AddGarminCorrectionRequest {
         if self.sport.is_some() {
             new_corr.sport = self.sport.map(Into::into);
         }
+        if self.clear_pool_length == Some(true) {
+            new_corr = new_corr.without_pool_length();
+        } else {
+            match (self.pool_length, self.recorded_pool_length) {
+                (Some(pool_length), Some(recorded_pool_length)) => {
+                    let is_valid = |l: f64| l.is_finite() && l > 0.0;
+                    if !is_valid(pool_length) || !is_valid(recorded_pool_length) {
+                        return Err(Error::BadRequest(format!(
+                            "Invalid pool length {pool_length} {recorded_pool_length}"
+                        )));
+                    }
+                    new_corr = new_corr.with_pool_length(pool_length, recorded_pool_length);
+                }
+                (None, None) => (),
+                _ => {
+                    return Err(Error::BadRequest(
+                        "Both pool length and the length set on the watch are needed".into(),
+                    ));
+                }
+            }
+        }
 
         corr_map.insert(unique_key, new_corr);
 
@@ -1,7 +1,7 @@
 #![allow(clippy::needless_pass_by_value)]
 
 use anyhow::Error;
-use log::{error, info};
+use log::{error, info, warn};
 use maplit::hashset;
 use notify::{
     recommended_watcher, Event, EventHandler, EventKind, INotifyWatcher, RecursiveMode,
@@ -14,41 +14,70 @@ use rweb::{
     openapi::{self, Info},
     Filter, Reply,
 };
-use stack_string::format_sstr;
+use stack_string::{format_sstr, StackString};
 use std::{
     collections::HashSet,
     net::SocketAddr,
     path::{Path, PathBuf},
     sync::Arc,
 };
+use time::OffsetDateTime;
 use tokio::{
+    fs::{create_dir_all, write},
     sync::watch::{channel, Receiver, Sender},
     task::spawn,
     time::{interval, sleep, Duration},
 };
 
-use garmin_cli::{garmin_cli::GarminCli, garmin_cli_opts::GarminCliOpts};
-use garmin_lib::garmin_config::GarminConfig;
-use garmin_models::garmin_correction_lap::GarminCorrectionMap;
-use garmin_utils::pgpool::PgPool;
+use garmin_cli::{
+    garmin_cli::GarminCli,
+    garmin_cli_opts::{run_migrations, GarminCliOpts},
+    garmin_prune::prune_files,
+};
+use garmin_lib::{garmin_config::GarminConfig, notification::NotifyEvent};
+use garmin_models::{
+    cache_store::CacheStore,
+    garmin_correction_lap::GarminCorrectionMap,
+    notifier::Notification,
+    sync_status::{SyncStatus, ACTIVITY_JOB, HEARTRATE_JOB},
+};
+use garmin_utils::{custom_sport::load_custom_sports, pgpool::PgPool};
 
 use crate::{
     errors::error_response,
     garmin_rust_routes::{
-        add_garmin_correction, fitbit_activities_db, fitbit_activities_db_update,
-        fitbit_heartrate_cache, fitbit_heartrate_cache_update, fitbit_plots, fitbit_plots_demo,
-        garmin, garmin_connect_activities_db, garmin_connect_activities_db_update, garmin_demo,
-        garmin_scripts_demo_js, garmin_scripts_js, garmin_sync, garmin_upload, heartrate_plots,
+        activity_gpx, activity_rpe_update, add_garmin_correction, admin_stats, biomarker_update,
+        cleanup_delete, cleanup_dry_run, clothing, clothing_log, combined_plot_js,
+        coverage_gap_backfill, coverage_gaps, export_fit, filter_history, filter_history_delete,
+        filter_history_pin, fitbit_activities_db, fitbit_activities_db_update,
+        fitbit_heartrate_cache, fitbit_heartrate_cache_update, fitbit_intraday, fitbit_plots,
+        fitbit_plots_demo, garmin, garmin_connect_activities_db,
+        garmin_connect_activities_db_update, garmin_demo, garmin_distribution,
+        garmin_distribution_monthly, garmin_file_splits, garmin_scripts_demo_js, garmin_scripts_js,
+        garmin_sync, garmin_upload, heart_rate_zones, heart_rate_zones_update, heartrate_plots,
         heartrate_plots_demo, heartrate_statistics_plots, heartrate_statistics_plots_demo,
         heartrate_statistics_summary_db, heartrate_statistics_summary_db_update, initialize_map_js,
-        line_plot_js, race_result_flag, race_result_import, race_result_plot,
-        race_result_plot_demo, race_results_db, race_results_db_update, scale_measurement,
-        scale_measurement_manual, scale_measurement_manual_input, scale_measurement_update,
-        scatter_plot_js, scatter_plot_with_lines_js, strava_activities, strava_activities_db,
+        integrations, language_demo, language_update, line_plot_js, link_activity, link_auto,
+        link_unmatched, matched_routes, meta_activity_types, meta_race_types, meta_sports,
+        milestones, pace_band, pace_planner, pace_planner_upload, power_curve, power_curve_demo,
+        provenance, quarantine, quarantine_retry, race_result_attachment_delete,
+        race_result_attachment_upload, race_result_flag, race_result_import, race_result_notes,
+        race_result_notes_update, race_result_plot, race_result_plot_demo, race_results_db,
+        race_results_db_update, region_map, rest_days, route_profile, route_profile_upload,
+        route_progression, scale_measurement, scale_measurement_bulk,
+        scale_measurement_connect_export, scale_measurement_duplicates,
+        scale_measurement_duplicates_merge, scale_measurement_manual,
+        scale_measurement_manual_input, scale_measurement_update, scatter_plot_js,
+        scatter_plot_with_lines_js, share_image, stat_source_update, strava_account_delete,
+        strava_account_sync, strava_accounts, strava_activities, strava_activities_db,
         strava_activities_db_update, strava_athlete, strava_auth, strava_callback, strava_create,
-        strava_refresh, strava_sync, strava_update, strava_upload, time_series_js, user,
+        strava_most_kudoed, strava_refresh, strava_sync, strava_update, strava_upload, sync_lock,
+        sync_lock_release, sync_status, sync_status_update, time_series_js, training_pattern,
+        travel_map, trip, trip_create, trip_delete, trips, upcoming_race_create,
+        upcoming_race_delete, upload_session_chunk, upload_session_complete, upload_session_create,
+        upload_session_status, user, yearly_comparison,
     },
-    logged_user::{fill_from_db, get_secrets},
+    logged_user::{fill_from_db, get_random_key, get_secrets},
 };
 
 /// `AppState` is the application state shared between all the handlers
@@ -60,6 +89,8 @@ pub struct AppState {
     pub config: GarminConfig,
     pub db: PgPool,
     pub client: Arc<Client>,
+    /// Activity avro cache, built once so requests share its s3 client
+    pub avro_cache: CacheStore,
 }
 
 #[derive(Clone)]
@@ -88,6 +119,13 @@ impl Notifier {
     }
 
     fn set_watcher(mut self, directory: &Path) -> Result<Self, Error> {
+        if !directory.exists() {
+            warn!(
+                "{} does not exist, not watching for har files",
+                directory.display()
+            );
+            return Ok(self);
+        }
         let watcher = recommended_watcher(self.clone())
             .and_then(|mut w| w.watch(directory, RecursiveMode::Recursive).map(|()| w))?;
         self.watcher = Some(Arc::new(watcher));
@@ -117,6 +155,30 @@ impl EventHandler for Notifier {
     }
 }
 
+/// Create the data directories and secret keys which don't exist yet and
+/// apply the embedded migrations, a fresh install only needs `PGURL`
+/// # Errors
+/// Returns error if a directory or key can't be written or a migration fails
+pub async fn init_app(config: &GarminConfig) -> Result<Vec<StackString>, Error> {
+    let mut output = Vec::new();
+    for directory in config.data_directories() {
+        if !directory.exists() {
+            create_dir_all(directory).await?;
+            output.push(format_sstr!("created {}", directory.display()));
+        }
+    }
+    for path in [&config.secret_path, &config.jwt_secret_path] {
+        if !path.exists() {
+            write(path, get_random_key()).await?;
+            output.push(format_sstr!("created key {}", path.display()));
+        }
+    }
+    let pool = PgPool::new(&config.pgurl)?;
+    run_migrations(&pool).await?;
+    output.push("migrations applied".into());
+    Ok(output)
+}
+
 /// Create the server.
 /// Configuration is done through environment variables, see `GarminConfig` for
 /// more information. `PgPool` is a wrapper around a connection pool.
@@ -131,19 +193,66 @@ pub async fn start_app() -> Result<(), Error> {
         let mut i = interval(std::time::Duration::from_secs(60));
         loop {
             fill_from_db(&pool).await.unwrap_or(());
+            load_custom_sports(&pool).await.unwrap_or(());
+            i.tick().await;
+        }
+    }
+    async fn prune_daily(config: GarminConfig, pool: PgPool) {
+        let mut i = interval(std::time::Duration::from_secs(24 * 3600));
+        // the first tick completes immediately, don't prune on every restart
+        i.tick().await;
+        loop {
             i.tick().await;
+            match prune_files(&config, &pool, false).await {
+                Ok(lines) => {
+                    for line in lines {
+                        info!("{line}");
+                    }
+                }
+                Err(e) => error!("prune failed {e}"),
+            }
         }
     }
     async fn run_connect_sync(cli: &GarminCli) {
-        if let Ok((filenames, input_files, dates)) =
-            GarminCliOpts::sync_with_garmin_connect(cli, &None, None, None, false).await
-        {
+        async fn _run_connect_sync(cli: &GarminCli) -> Result<bool, Error> {
+            let (filenames, input_files, dates) =
+                GarminCliOpts::sync_with_garmin_connect(cli, &None, None, None, false).await?;
             if !filenames.is_empty() || !input_files.is_empty() || !dates.is_empty() {
                 info!("processed filenames {filenames:?} from {input_files:?} and dates {dates:?}");
-                for line in cli.sync_everything().await.unwrap_or(Vec::new()) {
+                for line in cli.sync_everything().await? {
                     info!("{line}");
                 }
             }
+            Ok(!dates.is_empty())
+        }
+        // record the outcome like the cli does so missing sync alerts and the
+        // sync status panel see syncs triggered by the download watcher
+        let result = match _run_connect_sync(cli).await {
+            Ok(new_heartrates) => {
+                let mut result = SyncStatus::record_success(&cli.pool, ACTIVITY_JOB).await;
+                if result.is_ok() && new_heartrates {
+                    result = SyncStatus::record_success(&cli.pool, HEARTRATE_JOB).await;
+                }
+                result
+            }
+            Err(e) => {
+                error!("connect sync failed {e}");
+                let message = format_sstr!("{e}");
+                let today = OffsetDateTime::now_utc().date();
+                let notification = Notification::new(
+                    NotifyEvent::SyncFailure,
+                    "Garmin sync failed",
+                    message.clone(),
+                )
+                .with_dedupe_key(format_sstr!("{today} {message}"));
+                for line in cli.notify(&notification).await {
+                    info!("{line}");
+                }
+                SyncStatus::record_failure(&cli.pool, ACTIVITY_JOB, &message).await
+            }
+        };
+        if let Err(e) = result {
+            error!("failed to record sync status {e}");
         }
     }
     async fn check_downloads(cli: GarminCli, mut notifier: Notifier) {
@@ -169,6 +278,13 @@ pub async fn start_app() -> Result<(), Error> {
             update_db(pool).await;
         }
     });
+    spawn({
+        let config = config.clone();
+        let pool = pool.clone();
+        async move {
+            prune_daily(config, pool).await;
+        }
+    });
     spawn({
         let pool = pool.clone();
         let corr = GarminCorrectionMap::new();
@@ -194,7 +310,13 @@ pub async fn start_app() -> Result<(), Error> {
 fn get_garmin_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     let index_path = garmin(app.clone()).boxed();
     let garmin_demo_path = garmin_demo(app.clone()).boxed();
+    let garmin_file_splits_path = garmin_file_splits(app.clone()).boxed();
     let garmin_upload_path = garmin_upload(app.clone()).boxed();
+    let upload_session_path = upload_session_create(app.clone())
+        .or(upload_session_status(app.clone()))
+        .or(upload_session_chunk(app.clone()))
+        .or(upload_session_complete(app.clone()))
+        .boxed();
     let add_garmin_correction_path = add_garmin_correction(app.clone()).boxed();
     let garmin_connect_activities_db_get = garmin_connect_activities_db(app.clone()).boxed();
     let garmin_connect_activities_db_post =
@@ -203,10 +325,29 @@ fn get_garmin_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
         .or(garmin_connect_activities_db_post)
         .boxed();
     let garmin_sync_path = garmin_sync(app.clone()).boxed();
+    let quarantine_get = quarantine(app.clone()).boxed();
+    let quarantine_post = quarantine_retry(app.clone()).boxed();
+    let quarantine_path = quarantine_get.or(quarantine_post).boxed();
+    let admin_stats_path = admin_stats(app.clone()).boxed();
+    let sync_lock_path = sync_lock(app.clone())
+        .or(sync_lock_release(app.clone()))
+        .boxed();
+    let scale_duplicates_path = scale_measurement_duplicates(app.clone())
+        .or(scale_measurement_duplicates_merge(app.clone()))
+        .or(scale_measurement_connect_export(app.clone()))
+        .boxed();
+    let coverage_gaps_path = coverage_gaps(app.clone())
+        .or(coverage_gap_backfill(app.clone()))
+        .boxed();
+    let milestones_path = milestones(app.clone()).boxed();
+    let sync_status_path = sync_status(app.clone())
+        .or(sync_status_update(app.clone()))
+        .boxed();
     let strava_sync_path = strava_sync(app.clone()).boxed();
     let heartrate_cache_get = fitbit_heartrate_cache(app.clone()).boxed();
     let heartrate_cache_post = fitbit_heartrate_cache_update(app.clone()).boxed();
     let heartrate_cache_path = heartrate_cache_get.or(heartrate_cache_post).boxed();
+    let fitbit_intraday_path = fitbit_intraday(app.clone()).boxed();
     let fitbit_plots_path = fitbit_plots(app.clone()).boxed();
     let fitbit_plots_demo_path = fitbit_plots_demo(app.clone()).boxed();
     let heartrate_statistics_plots_path = heartrate_statistics_plots(app.clone()).boxed();
@@ -225,6 +366,7 @@ fn get_garmin_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
         .or(heartrate_statistics_summary_db_post)
         .boxed();
     let fitbit_path = heartrate_cache_path
+        .or(fitbit_intraday_path)
         .or(fitbit_plots_path)
         .or(fitbit_plots_demo_path)
         .or(heartrate_statistics_plots_path)
@@ -238,7 +380,11 @@ fn get_garmin_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     let scale_measurements_post = scale_measurement_update(app.clone()).boxed();
     let scale_measurement_manual_path = scale_measurement_manual(app.clone()).boxed();
     let scale_measurement_manual_input_path = scale_measurement_manual_input().boxed();
-    let scale_measurements_path = scale_measurements_get.or(scale_measurements_post).boxed();
+    let scale_measurements_bulk_path = scale_measurement_bulk(app.clone()).boxed();
+    let scale_measurements_path = scale_measurements_get
+        .or(scale_measurements_post)
+        .or(scale_measurements_bulk_path)
+        .boxed();
     let strava_auth_path = strava_auth(app.clone()).boxed();
     let strava_refresh_path = strava_refresh(app.clone()).boxed();
     let strava_callback_path = strava_callback(app.clone()).boxed();
@@ -252,6 +398,18 @@ fn get_garmin_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     let strava_upload_path = strava_upload(app.clone()).boxed();
     let strava_update_path = strava_update(app.clone()).boxed();
     let strava_create_path = strava_create(app.clone()).boxed();
+    let strava_accounts_get = strava_accounts(app.clone()).boxed();
+    let strava_account_sync_path = strava_account_sync(app.clone()).boxed();
+    let strava_account_delete_path = strava_account_delete(app.clone()).boxed();
+    let strava_accounts_path = strava_accounts_get
+        .or(strava_account_sync_path)
+        .or(strava_account_delete_path)
+        .boxed();
+    let integrations_path = integrations(app.clone()).boxed();
+    let link_path = link_activity(app.clone())
+        .or(link_auto(app.clone()))
+        .or(link_unmatched(app.clone()))
+        .boxed();
 
     let strava_path = strava_auth_path
         .or(strava_refresh_path)
@@ -262,13 +420,81 @@ fn get_garmin_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
         .or(strava_upload_path)
         .or(strava_update_path)
         .or(strava_create_path)
+        .or(strava_accounts_path)
+        .or(integrations_path)
+        .or(link_path)
         .boxed();
 
     let user_path = user().boxed();
+    let filter_history_get = filter_history(app.clone()).boxed();
+    let filter_history_pin_path = filter_history_pin(app.clone()).boxed();
+    let filter_history_delete_path = filter_history_delete(app.clone()).boxed();
+    let filter_history_path = filter_history_get
+        .or(filter_history_pin_path)
+        .or(filter_history_delete_path)
+        .boxed();
     let race_result_plot_path = race_result_plot(app.clone()).boxed();
     let race_result_flag_path = race_result_flag(app.clone()).boxed();
+    let travel_map_path = travel_map(app.clone()).boxed();
+    let region_map_path = region_map(app.clone()).boxed();
+    let provenance_path = provenance(app.clone()).boxed();
+    let cleanup_dry_run_path = cleanup_dry_run(app.clone()).boxed();
+    let cleanup_delete_path = cleanup_delete(app.clone()).boxed();
+    let strava_most_kudoed_path = strava_most_kudoed(app.clone()).boxed();
+    let race_result_notes_path = race_result_notes(app.clone()).boxed();
+    let race_result_notes_update_path = race_result_notes_update(app.clone()).boxed();
+    let race_result_attachment_upload_path = race_result_attachment_upload(app.clone()).boxed();
+    let race_result_attachment_delete_path = race_result_attachment_delete(app.clone()).boxed();
     let race_result_import_path = race_result_import(app.clone()).boxed();
     let race_result_plot_demo_path = race_result_plot_demo(app.clone()).boxed();
+    let distribution_path = garmin_distribution(app.clone()).boxed();
+    let distribution_monthly_path = garmin_distribution_monthly(app.clone()).boxed();
+    let trips_path = trips(app.clone())
+        .or(trip_create(app.clone()))
+        .or(trip_delete(app.clone()))
+        .boxed();
+    let trip_path = trip(app.clone()).boxed();
+    let power_curve_path = power_curve(app.clone()).boxed();
+    let yearly_comparison_path = yearly_comparison(app.clone()).boxed();
+    let training_pattern_path = training_pattern(app.clone()).boxed();
+    let rest_days_path = rest_days(app.clone()).boxed();
+    let heart_rate_zones_get = heart_rate_zones(app.clone()).boxed();
+    let heart_rate_zones_post = heart_rate_zones_update(app.clone()).boxed();
+    let heart_rate_zones_path = heart_rate_zones_get.or(heart_rate_zones_post).boxed();
+    let language_update_path = language_update(app.clone()).boxed();
+    let language_demo_path = language_demo(app.clone()).boxed();
+    let clothing_get = clothing(app.clone()).boxed();
+    let clothing_post = clothing_log(app.clone()).boxed();
+    let clothing_path = clothing_get.or(clothing_post).boxed();
+    let stat_source_path = stat_source_update(app.clone()).boxed();
+    let activity_rpe_path = activity_rpe_update(app.clone()).boxed();
+    let power_curve_demo_path = power_curve_demo(app.clone()).boxed();
+    let pace_planner_get = pace_planner(app.clone()).boxed();
+    let pace_planner_post = pace_planner_upload(app.clone()).boxed();
+    let pace_band_path = pace_band(app.clone()).boxed();
+    let share_image_path = share_image(app.clone()).boxed();
+    let export_fit_path = export_fit(app.clone()).boxed();
+    let activity_gpx_path = activity_gpx(app.clone()).boxed();
+    let route_profile_get = route_profile(app.clone()).boxed();
+    let route_profile_post = route_profile_upload(app.clone()).boxed();
+    let matched_routes_path = matched_routes(app.clone()).boxed();
+    let route_progression_path = route_progression(app.clone()).boxed();
+    let upcoming_race_create_path = upcoming_race_create(app.clone()).boxed();
+    let upcoming_race_delete_path = upcoming_race_delete(app.clone()).boxed();
+    let pace_planner_path = pace_planner_get
+        .or(pace_planner_post)
+        .or(pace_band_path)
+        .or(share_image_path)
+        .or(export_fit_path)
+        .or(activity_gpx_path)
+        .or(route_profile_get)
+        .or(route_profile_post)
+        .or(matched_routes_path)
+        .or(route_progression_path)
+        .or(upcoming_race_create_path)
+        .or(upcoming_race_delete_path)
+        .boxed();
+    let biomarker_path = biomarker_update(app.clone()).boxed();
     let race_results_db_get = race_results_db(app.clone()).boxed();
     let race_results_db_post = race_results_db_update(app.clone()).boxed();
     let race_results_db_path = race_results_db_get.or(race_results_db_post).boxed();
@@ -277,16 +503,30 @@ fn get_garmin_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
     let garmin_scripts_demo_js_path = garmin_scripts_demo_js().boxed();
     let line_plot_js_path = line_plot_js().boxed();
     let scatter_plot_js_path = scatter_plot_js().boxed();
+    let combined_plot_js_path = combined_plot_js().boxed();
     let scatter_plot_with_lines_js_path = scatter_plot_with_lines_js().boxed();
     let time_series_js_path = time_series_js().boxed();
     let initialize_map_js_path = initialize_map_js().boxed();
+    let meta_path = meta_sports()
+        .or(meta_race_types())
+        .or(meta_activity_types())
+        .boxed();
 
     index_path
         .or(garmin_demo_path)
+        .or(garmin_file_splits_path)
         .or(garmin_upload_path)
+        .or(upload_session_path)
         .or(add_garmin_correction_path)
         .or(garmin_connect_activities_db_path)
         .or(garmin_sync_path)
+        .or(quarantine_path)
+        .or(admin_stats_path)
+        .or(sync_lock_path)
+        .or(scale_duplicates_path)
+        .or(coverage_gaps_path)
+        .or(milestones_path)
+        .or(sync_status_path)
         .or(strava_sync_path)
         .or(fitbit_path)
         .or(scale_measurement_manual_path)
@@ -294,18 +534,48 @@ fn get_garmin_path(app: &AppState) -> BoxedFilter<(impl Reply,)> {
         .or(scale_measurements_path)
         .or(strava_path)
         .or(user_path)
+        .or(filter_history_path)
         .or(race_result_plot_path)
         .or(race_result_flag_path)
+        .or(travel_map_path)
+        .or(region_map_path)
+        .or(provenance_path)
+        .or(cleanup_dry_run_path)
+        .or(cleanup_delete_path)
+        .or(strava_most_kudoed_path)
+        .or(race_result_notes_path)
+        .or(race_result_notes_update_path)
+        .or(race_result_attachment_upload_path)
+        .or(race_result_attachment_delete_path)
         .or(race_result_import_path)
         .or(race_result_plot_demo_path)
+        .or(distribution_monthly_path)
+        .or(distribution_path)
+        .or(trips_path)
+        .or(trip_path)
+        .or(power_curve_path)
+        .or(yearly_comparison_path)
+        .or(training_pattern_path)
+        .or(rest_days_path)
+        .or(heart_rate_zones_path)
+        .or(language_update_path)
+        .or(language_demo_path)
+        .or(clothing_path)
+        .or(stat_source_path)
+        .or(activity_rpe_path)
+        .or(power_curve_demo_path)
+        .or(pace_planner_path)
+        .or(biomarker_path)
         .or(race_results_db_path)
         .or(garmin_scripts_js_path)
         .or(garmin_scripts_demo_js_path)
         .or(line_plot_js_path)
         .or(scatter_plot_js_path)
+        .or(combined_plot_js_path)
         .or(scatter_plot_with_lines_js_path)
         .or(time_series_js_path)
         .or(initialize_map_js_path)
+        .or(meta_path)
         .boxed()
 }
 
@@ -314,6 +584,7 @@ async fn run_app(config: &GarminConfig, pool: &PgPool) -> Result<(), Error> {
         config: config.clone(),
         db: pool.clone(),
         client: Arc::new(ClientBuilder::new().build()?),
+        avro_cache: CacheStore::avro_cache(config).await,
     };
 
     let (spec, garmin_path) = openapi::spec()
@@ -343,8 +614,54 @@ async fn run_app(config: &GarminConfig, pool: &PgPool) -> Result<(), Error> {
     let routes = garmin_path
         .or(spec_json_path)
         .or(spec_yaml_path)
-        .recover(error_response);
+        .recover(error_response)
+        .with(rweb::filters::trace::request());
     let addr: SocketAddr = format_sstr!("{}:{}", config.host, config.port).parse()?;
     rweb::serve(routes).bind(addr).await;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use reqwest::ClientBuilder;
+    use rweb::{openapi, test::request, Filter};
+    use std::sync::Arc;
+
+    use garmin_lib::garmin_config::GarminConfig;
+    use garmin_models::cache_store::CacheStore;
+    use garmin_utils::pgpool::PgPool;
+
+    use crate::{
+        errors::error_response,
+        garmin_rust_app::{get_garmin_path, AppState},
+    };
+
+    /// Needs the test database with the fixtures processed by
+    /// `test_pipeline_integration`, see `make integration_test`
+    #[tokio::test]
+    #[ignore]
+    async fn test_demo_endpoints_integration() -> Result<(), Error> {
+        let config = GarminConfig::get_config(Some("../tests/data/test.env"))?;
+        let pool = PgPool::new(&config.pgurl)?;
+        let app = AppState {
+            avro_cache: CacheStore::avro_cache(&config).await,
+            config,
+            db: pool,
+            client: Arc::new(ClientBuilder::new().build()?),
+        };
+        let (_, garmin_path) = openapi::spec().build(|| get_garmin_path(&app));
+        let routes = garmin_path.recover(error_response);
+
+        for (path, expected) in [
+            ("/garmin/demo.html?filter=2012-11-05", "Garmin Event"),
+            ("/garmin/demo.html?filter=2024-05,day", "Garmin Summary"),
+        ] {
+            let response = request().method("GET").path(path).reply(&routes).await;
+            assert_eq!(response.status(), 200, "{path}");
+            let body = String::from_utf8_lossy(response.body());
+            assert!(body.contains(expected), "{path}");
+        }
+        Ok(())
+    }
+}
@@ -2,11 +2,13 @@
 use anyhow::format_err;
 use futures::{future::try_join_all, TryStreamExt};
 use itertools::Itertools;
-use log::debug;
+use log::{debug, error};
+use reqwest::Client;
 use rweb::{
-    get,
+    delete, get,
+    hyper::body::Bytes,
     multipart::{FormData, Part},
-    post, Buf, Filter, Json, Query, Rejection, Schema,
+    post, put, Buf, Filter, Json, Query, Rejection, Schema,
 };
 use rweb_helper::{
     html_response::HtmlResponse as HtmlBase, json_response::JsonResponse as JsonBase, DateType,
@@ -14,39 +16,125 @@ use rweb_helper::{
 };
 use serde::{Deserialize, Serialize};
 use stack_string::{format_sstr, StackString};
-use std::convert::Infallible;
+use std::{
+    collections::HashMap,
+    convert::{Infallible, TryFrom},
+    path::Path,
+};
 use tempfile::TempDir;
+use time::{Date, Duration, Month, OffsetDateTime};
 use tokio::{fs::File, io::AsyncWriteExt, task::spawn_blocking};
 use tokio_stream::StreamExt;
+use uuid::Uuid;
 
 use fitbit_lib::{
-    fitbit_archive, fitbit_heartrate::FitbitHeartRate,
-    fitbit_statistics_summary::FitbitStatisticsSummary, scale_measurement::ScaleMeasurement,
+    fitbit_archive,
+    fitbit_heartrate::FitbitHeartRate,
+    fitbit_intraday::get_intraday_day,
+    fitbit_statistics_summary::FitbitStatisticsSummary,
+    fitbit_wellness::SLEEP_MINUTES_SERIES,
+    scale_measurement::{
+        ScaleImportReport, ScaleMeasurement, ScaleMeasurementImport, ScaleMeasurementWithMetrics,
+        CONNECT_EXPORT_BATCH,
+    },
+};
+use garmin_cli::{
+    garmin_cli::{GarminCli, GarminRequest},
+    garmin_cli_opts::GarminCliOpts,
 };
-use garmin_cli::garmin_cli::{GarminCli, GarminRequest};
 use garmin_lib::{
-    date_time_wrapper::iso8601::convert_datetime_to_str, garmin_config::GarminConfig,
+    date_time_wrapper::{iso8601::convert_datetime_to_str, DateTimeWrapper},
+    garmin_config::GarminConfig,
+    language::Language,
+    split_distance::SplitDistance,
 };
 use garmin_models::{
+    activity_cleanup::{delete_activities, CleanupFilter},
+    activity_distribution::{ActivityDistribution, DistributionMetric},
+    activity_location::{CityVisit, RegionVisit},
+    activity_rpe::ActivityRpe,
+    admin_stats::AdminStats,
+    biomarker::{parse_datetime, BiomarkerMeasurement, BiomarkerSeries},
+    cache_store::CacheStore,
+    clothing_log::{ClothingLogEntry, ClothingRecommendation},
+    coverage_gap::{CoverageGap, GapKind, DEFAULT_MAX_GAP_DAYS, DEFAULT_STREAK_DAYS},
+    demo_anonymizer::DemoAnonymizer,
+    filter_history::FilterHistory,
     fitbit_activity::FitbitActivity,
     garmin_connect_activity::GarminConnectActivity,
+    garmin_connect_activity_detail::GarminConnectActivityDetail,
+    garmin_connect_wellness::{
+        DIASTOLIC_SERIES, SLEEP_RESPIRATION_SERIES, SPO2_SERIES, SYSTOLIC_SERIES,
+    },
     garmin_correction_lap::GarminCorrectionLap,
     garmin_file,
     garmin_summary::{get_list_of_files_from_db, GarminSummary},
+    garmin_sync::GarminSync,
+    heart_rate_zones::{
+        current_heart_rate_profile, local_today, HeartRateProfiles, HeartRateSetting,
+        HeartRateSuggestion,
+    },
+    milestone::Milestone,
+    power_curve::{CurveMetric, CurvePeriod, PowerCurve},
+    processing_lock::{is_processing_in_progress, LockHolder},
+    provenance::{DataProvider, Provenance, ProvenanceEntry, ProvenanceKind},
+    quarantined_file::QuarantinedFile,
+    race_forecast::{parse_race_datetime, RaceForecast, UpcomingRace},
+    rest_days::{RestDayStats, DEFAULT_REST_DAYS},
+    route_match::{MatchedRoute, RouteAttempt},
+    stat_source::{source_values, ActivityStat, StatChoice, StatSource},
     strava_activity::StravaActivity,
+    strava_link::{link_manual, link_tolerant, UnmatchedActivities},
+    sync_status::SyncStatus,
+    threshold_heart_rate::{ThresholdHistory, THRESHOLD_HISTORY_DAYS},
+    training_pattern::{TrainingPattern, DEFAULT_PATTERN_DAYS, MAX_PATTERN_DAYS},
+    trip::{Trip, TripStats},
+    user_preferences::UserPreferences,
+    yearly_comparison::{ComparisonMetric, YearlyComparison, DEFAULT_COMPARISON_YEARS},
+};
+use garmin_parser::{
+    garmin_export_fit::garmin_file_to_fit,
+    garmin_export_gpx::garmin_file_to_gpx,
+    garmin_parse::{GarminParse, GarminParseTrait},
+};
+use garmin_reports::{
+    garmin_file_report_txt::get_distance_splits,
+    garmin_summary_report_txt::create_report_stream,
+    pace_band::splits_to_pace_band,
+    pace_planner::{
+        course_from_gfile, course_from_gpx, grade_adjusted_pace, plan_splits, PlannedSplit,
+        SplitUnit,
+    },
+    route_profile::{typical_pace, RouteProfile, ROUTE_PACE_ACTIVITIES},
+    route_progression::{best_by_year, progression_svg},
+    share_card::share_card_png,
+};
+use garmin_utils::{
+    garmin_util::{convert_time_string, parse_race_distance, METERS_PER_MILE},
+    pgpool::PgPool,
+    sport_types::{
+        get_fitbit_activity_ids, get_sport_names, get_strava_activity_types, SportTypes,
+    },
 };
-use garmin_parser::garmin_parse::{GarminParse, GarminParseTrait};
-use garmin_reports::garmin_summary_report_txt::create_report_query;
-use garmin_utils::{garmin_util::titlecase, pgpool::PgPool};
 use race_result_analysis::{
-    race_result_analysis::RaceResultAnalysis, race_results::RaceResults, race_type::RaceType,
+    race_attachment::{AttachmentKind, RaceAttachment},
+    race_result_analysis::RaceResultAnalysis,
+    race_results::RaceResults,
+    race_type::RaceType,
 };
-use strava_lib::strava_client::StravaClient;
+use strava_lib::strava_client::{StravaAccount, StravaClient, DEFAULT_ACCOUNT};
 
 use crate::{
     errors::ServiceError as Error,
+    file_download::FileDownload,
     garmin_elements::{
-        index_new_body, scale_measurement_manual_input_body, strava_body, table_body, IndexConfig,
+        admin_stats_body, cleanup_body, clothing_body, coverage_gaps_body, heart_rate_zones_body,
+        index_new_body, index_report_stream, integrations_body, link_review_body,
+        matched_routes_body, milestones_body, most_kudoed_body, pace_planner_body, quarantine_body,
+        race_result_notes_body, rest_days_body, route_profile_body, route_progression_body,
+        scale_duplicates_body, scale_measurement_manual_input_body, strava_body, table_body,
+        training_pattern_body, trips_body, BiomarkerOverlay, IndexConfig, PowerCurveOpts, TripOpts,
+        TripRoute, WellnessOpts,
     },
     garmin_requests::{
         AddGarminCorrectionRequest, FitbitHeartrateCacheRequest, FitbitHeartratePlotRequest,
@@ -57,10 +145,16 @@ use crate::{
         StravaCreateRequest, StravaSyncRequest, StravaUpdateRequest, StravaUploadRequest,
     },
     garmin_rust_app::AppState,
+    html_stream::HtmlStream,
     logged_user::{LoggedUser, Session},
-    FitbitActivityTypesWrapper, FitbitActivityWrapper, FitbitHeartRateWrapper,
-    FitbitStatisticsSummaryWrapper, GarminConnectActivityWrapper, RaceResultsWrapper,
-    RaceTypeWrapper, ScaleMeasurementWrapper, StravaActivityWrapper,
+    pagination::{PageQuery, PageRequest, PaginatedBody, PaginatedJson, Pagination},
+    resumable_upload::UploadSession,
+    sport_types_wrapper::SportTypesWrapper,
+    CityVisitWrapper, FilterHistoryWrapper, FitbitActivityTypesWrapper, FitbitActivityWrapper,
+    FitbitHeartRateWrapper, FitbitStatisticsSummaryWrapper, GarminConnectActivityWrapper,
+    IntradayMinuteWrapper, ProvenanceEntryWrapper, RaceResultsWrapper, RaceTypeWrapper,
+    RegionVisitWrapper, ScaleMeasurementWithMetricsWrapper, ScaleMeasurementWrapper,
+    StravaActivityWrapper, SyncStatusWrapper,
 };
 
 pub type WarpResult<T> = Result<T, Rejection>;
@@ -69,6 +163,11 @@ pub type HttpResult<T> = Result<T, Error>;
 #[derive(Deserialize, Schema)]
 struct FilterRequest {
     filter: Option<StackString>,
+    #[schema(
+        description = "Split Distance for File Reports (defaults to config)",
+        example = r#""1km""#
+    )]
+    split: Option<StackString>,
 }
 
 fn proc_pattern_wrapper<T: AsRef<str>>(
@@ -84,7 +183,15 @@ fn proc_pattern_wrapper<T: AsRef<str>>(
 
     let filter_iter = filter.split(',');
 
-    let req = GarminCli::process_pattern(config, filter_iter);
+    let mut req = GarminCli::process_pattern(config, filter_iter);
+    // demo pages show shifted dates, so their filters are moved back
+    if let Some(anonymizer) = DemoAnonymizer::from_config(config).filter(|_| is_demo) {
+        req.constraints.unshift_demo(&anonymizer);
+        req.options.anonymizer = Some(anonymizer);
+    }
+    if let Some(split) = request.split.as_ref().and_then(|s| s.parse().ok()) {
+        req.options.split_distance = split;
+    }
     let mut history: Vec<_> = history.iter().map(|s| s.as_ref().into()).collect();
     history.shrink_to_fit();
 
@@ -98,54 +205,99 @@ fn proc_pattern_wrapper<T: AsRef<str>>(
     }
 }
 
+/// Client for the race attachment bucket, a bad request when s3 is turned
+/// off
+async fn require_s3(config: &GarminConfig) -> HttpResult<GarminSync> {
+    GarminSync::from_config(config)
+        .await
+        .ok_or_else(|| Error::BadRequest("S3 is not enabled (S3_ENABLED=false)".into()))
+}
+
+/// Strava routes need the api credentials in `strava_tokenfile`
+fn require_strava(config: &GarminConfig) -> HttpResult<()> {
+    if config.strava_configured() {
+        Ok(())
+    } else {
+        Err(Error::BadRequest(format!(
+            "Strava is not configured, {:?} does not exist",
+            config.strava_tokenfile
+        )))
+    }
+}
+
 fn optional_session() -> impl Filter<Extract = (Option<Session>,), Error = Infallible> + Copy {
     rweb::cookie::optional("session")
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Main Page", content = "html")]
-struct IndexResponse(HtmlBase<StackString, Error>);
-
 #[get("/garmin/index.html")]
 #[openapi(description = "Main Page")]
 pub async fn garmin(
     query: Query<FilterRequest>,
     #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<IndexResponse> {
+) -> WarpResult<HtmlStream> {
     let query = query.into_inner();
 
     let mut session = user
-        .get_session(&state.client, &state.config)
+        .get_session(&state.client, &state.config, &state.db)
         .await
         .map_err(Into::<Error>::into)?;
 
     let grec = proc_pattern_wrapper(&state.config, query, &session.history, false);
+    FilterHistory::add(&state.db, &user.email, &grec.request.filter)
+        .await
+        .map_err(Into::<Error>::into)?;
     if !session.history.contains(&grec.request.filter) {
-        if session.history.len() > 5 {
-            session.history.remove(0);
-        }
         session.history.push(grec.request.filter.clone());
     }
-
-    let body = get_index_body(&state.db, &state.config, &grec.request, false)
-        .await
-        .map_err(Into::<Error>::into)?
-        .into();
-
     user.set_session(&state.client, &state.config, &session)
         .await
         .map_err(Into::<Error>::into)?;
 
-    Ok(HtmlBase::new(body).into())
+    let body = get_index_body(
+        &state.db,
+        &state.config,
+        &state.avro_cache,
+        &grec.request,
+        session,
+        false,
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+
+    Ok(body)
+}
+
+/// Read the cached avro of `filename`, parsing the original file when the
+/// cache is missing
+async fn read_or_parse_gfile(
+    config: &GarminConfig,
+    pool: &PgPool,
+    store: &CacheStore,
+    filename: &str,
+) -> HttpResult<garmin_file::GarminFile> {
+    if let Ok(gfile) = garmin_file::GarminFile::read_cached_avro(store, filename).await {
+        debug!("Cached avro file read: {filename}");
+        return Ok(gfile);
+    }
+    let gps_file = config.gps_dir.join(filename);
+    let mut corr_map = GarminCorrectionLap::read_corrections_from_db(pool).await?;
+    corr_map.shrink_to_fit();
+
+    debug!("Reading gps_file: {:?}", &gps_file);
+    let gfile =
+        spawn_blocking(move || GarminParse::new().with_file(&gps_file, &corr_map)).await??;
+    Ok(gfile)
 }
 
 async fn get_index_body(
     pool: &PgPool,
     config: &GarminConfig,
+    store: &CacheStore,
     req: &GarminRequest,
+    session: Session,
     is_demo: bool,
-) -> HttpResult<String> {
+) -> HttpResult<HtmlStream> {
     let mut file_list: Vec<StackString> =
         get_list_of_files_from_db(&req.constraints.to_query_string(), pool)
             .await?
@@ -154,52 +306,49 @@ async fn get_index_body(
     file_list.shrink_to_fit();
 
     match file_list.len() {
-        0 => Ok(String::new()),
+        0 => Ok(HtmlStream::new(String::new())),
         1 => {
             let file_name = file_list
                 .first()
                 .ok_or_else(|| format_err!("This shouldn't be happening..."))?;
             debug!("{}", &file_name);
-            let avro_file = config.cache_dir.join(file_name.as_str());
-
-            let gfile = if let Ok(g) = garmin_file::GarminFile::read_avro_async(&avro_file).await {
-                debug!("Cached avro file read: {:?}", &avro_file);
-                g
-            } else {
-                let gps_file = config.gps_dir.join(file_name.as_str());
-                let mut corr_map = GarminCorrectionLap::read_corrections_from_db(pool).await?;
-                corr_map.shrink_to_fit();
-
-                debug!("Reading gps_file: {:?}", &gps_file);
-                spawn_blocking(move || GarminParse::new().with_file(&gps_file, &corr_map)).await??
+            let gfile = read_or_parse_gfile(config, pool, store, file_name).await?;
+            let sport = gfile.sport.display_name();
+            let dt: DateTimeWrapper = match DemoAnonymizer::from_config(config).filter(|_| is_demo)
+            {
+                Some(anonymizer) => anonymizer
+                    .shift_datetime(gfile.begin_datetime.into())
+                    .into(),
+                None => gfile.begin_datetime,
             };
-            let sport: StackString = gfile.sport.into();
-            let sport = titlecase(&sport);
-            let dt = gfile.begin_datetime;
             let title = format_sstr!("Garmin Event {sport} at {dt}");
             let body = index_new_body(
                 config,
                 pool,
                 title,
                 is_demo,
-                req.history.clone(),
-                IndexConfig::File { gfile },
+                session,
+                IndexConfig::File {
+                    gfile,
+                    xaxis: req.options.xaxis,
+                    split_distance: req.options.split_distance,
+                },
             )
             .await?;
-            Ok(body)
+            Ok(HtmlStream::new(body))
         }
         _ => {
-            let reports = create_report_query(pool, &req.options, &req.constraints).await?;
-            let body = index_new_body(
+            let rows = create_report_stream(pool, &req.options, &req.constraints).await?;
+            let rows = index_report_stream(
                 config,
                 pool,
                 "Garmin Summary".into(),
                 is_demo,
-                req.history.clone(),
-                IndexConfig::Report { reports },
+                session,
+                rows,
             )
             .await?;
-            Ok(body)
+            Ok(HtmlStream::from_stream(rows))
         }
     }
 }
@@ -210,12 +359,12 @@ pub async fn garmin_demo(
     query: Query<FilterRequest>,
     #[data] state: AppState,
     #[filter = "optional_session"] session: Option<Session>,
-) -> WarpResult<IndexResponse> {
+) -> WarpResult<HtmlStream> {
     let query = query.into_inner();
 
     let mut session = session.unwrap_or_default();
 
-    let grec = proc_pattern_wrapper(&state.config, query, &session.history, false);
+    let grec = proc_pattern_wrapper(&state.config, query, &session.history, true);
     if !session.history.contains(&grec.request.filter) {
         if session.history.len() > 5 {
             session.history.remove(0);
@@ -223,14 +372,499 @@ pub async fn garmin_demo(
         session.history.push(grec.request.filter.clone());
     }
 
-    let body = get_index_body(&state.db, &state.config, &grec.request, true)
+    let jwt = session.get_jwt_cookie(&state.config.domain);
+    let body = get_index_body(
+        &state.db,
+        &state.config,
+        &state.avro_cache,
+        &grec.request,
+        session,
+        true,
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+
+    let jwt_str = StackString::from_display(jwt.encoded());
+    Ok(body.with_cookie(&jwt_str))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct FileSplitsRequest {
+    #[schema(
+        description = "Activity Filename",
+        example = r#""2024-01-07_12-30-00_1_1.fit""#
+    )]
+    filename: StackString,
+    #[schema(
+        description = "Split Distance, e.g. 1mi, 1km or 400m (defaults to config)",
+        example = r#""1km""#
+    )]
+    split: Option<StackString>,
+    #[schema(description = "Offset")]
+    offset: Option<usize>,
+    #[schema(description = "Limit")]
+    limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    cursor: Option<StackString>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "FileSplit")]
+struct FileSplit {
+    #[schema(description = "Split Number", example = r#""1""#)]
+    split: i32,
+    #[schema(description = "Distance at End of Split", example = r#""1 km""#)]
+    distance: StackString,
+    #[schema(description = "Distance at End of Split (m)", example = r#""1000.0""#)]
+    distance_meters: f64,
+    #[schema(description = "Split Duration (s)", example = r#""300.0""#)]
+    duration: f64,
+    #[schema(description = "Pace (s/mi)", example = r#""482.8""#)]
+    pace_per_mile: f64,
+    #[schema(description = "Pace (s/km)", example = r#""300.0""#)]
+    pace_per_km: f64,
+    #[schema(description = "Average Heart Rate (bpm)", example = r#""150.0""#)]
+    avg_heart_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedFileSplit")]
+struct PaginatedFileSplit {
+    pagination: Pagination,
+    data: Vec<FileSplit>,
+}
+
+impl PaginatedBody for PaginatedFileSplit {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/splits")]
+#[openapi(description = "Splits of an Activity every Split Distance")]
+pub async fn garmin_file_splits(
+    query: Query<FileSplitsRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedFileSplit>> {
+    let query = query.into_inner();
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let split_distance: SplitDistance = match &query.split {
+        Some(split) => split
+            .parse()
+            .map_err(|e| Error::BadRequest(format!("{e}")))?,
+        None => state.config.split_distance,
+    };
+    if GarminSummary::get_by_filename(&state.db, &query.filename)
         .await
         .map_err(Into::<Error>::into)?
-        .into();
+        .is_none()
+    {
+        return Err(Error::BadRequest(format!("No activity {}", query.filename)).into());
+    }
+    let store = state.avro_cache.clone();
+    let gfile = garmin_file::GarminFile::read_cached_avro(&store, &query.filename)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let meters = split_distance.meters();
+    let splits = get_distance_splits(&gfile, split_distance, true)
+        .into_iter()
+        .map(|val| FileSplit {
+            split: val.split_distance as i32,
+            distance: split_distance.marker(val.split_distance as usize),
+            distance_meters: val.split_distance * meters,
+            duration: val.time_value,
+            pace_per_mile: val.time_value / (meters / METERS_PER_MILE),
+            pace_per_km: val.time_value / (meters / 1000.0),
+            avg_heart_rate: val.avg_heart_rate,
+        })
+        .collect();
+    let mut params = vec![("filename", query.filename)];
+    params.extend(query.split.map(|split| ("split", split)));
+    let (pagination, data) = page.paginate_list(splits, "/garmin/splits", &params)?;
+    Ok(PaginatedJson(PaginatedFileSplit { pagination, data }))
+}
 
-    let jwt = session.get_jwt_cookie(&state.config.domain);
-    let jwt_str = StackString::from_display(jwt.encoded());
-    Ok(HtmlBase::new(body).with_cookie(&jwt_str).into())
+#[derive(Serialize, Deserialize, Schema)]
+struct ShareImageRequest {
+    #[schema(
+        description = "Activity Filename",
+        example = r#""2024-01-07_12-30-00_1_1.fit""#
+    )]
+    filename: StackString,
+}
+
+#[get("/garmin/share_image.png")]
+#[openapi(description = "Share Card Image of an Activity: Route, Distance, Pace and Elevation")]
+pub async fn share_image(
+    query: Query<ShareImageRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FileDownload> {
+    let query = query.into_inner();
+    if GarminSummary::get_by_filename(&state.db, &query.filename)
+        .await
+        .map_err(Into::<Error>::into)?
+        .is_none()
+    {
+        return Err(Error::BadRequest(format!("No activity {}", query.filename)).into());
+    }
+    let store = state.avro_cache.clone();
+    let gfile = garmin_file::GarminFile::read_cached_avro(&store, &query.filename)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let png = spawn_blocking(move || share_card_png(&gfile))
+        .await
+        .map_err(Into::<Error>::into)?
+        .map_err(Into::<Error>::into)?;
+    let filename = format_sstr!(
+        "{}.png",
+        query.filename.split('.').next().unwrap_or("share_image")
+    );
+    Ok(FileDownload::png(png, &filename))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct ExportFitRequest {
+    #[schema(
+        description = "Activity Filename",
+        example = r#""2024-01-07_12-30-00_1_1.fit""#
+    )]
+    filename: StackString,
+}
+
+#[get("/garmin/export.fit")]
+#[openapi(description = "Download an Activity, with Lap Corrections Applied, as a FIT File")]
+pub async fn export_fit(
+    query: Query<ExportFitRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FileDownload> {
+    let query = query.into_inner();
+    if GarminSummary::get_by_filename(&state.db, &query.filename)
+        .await
+        .map_err(Into::<Error>::into)?
+        .is_none()
+    {
+        return Err(Error::BadRequest(format!("No activity {}", query.filename)).into());
+    }
+    let store = state.avro_cache.clone();
+    let gfile = garmin_file::GarminFile::read_cached_avro(&store, &query.filename)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let fit = spawn_blocking(move || garmin_file_to_fit(&gfile))
+        .await
+        .map_err(Into::<Error>::into)?;
+    let filename = format_sstr!(
+        "{}_export.fit",
+        query.filename.split('.').next().unwrap_or("activity")
+    );
+    Ok(FileDownload::fit(fit, &filename))
+}
+
+#[get("/garmin/activity/{filename}/gpx")]
+#[openapi(description = "Download the GPS Track of an Activity as GPX")]
+pub async fn activity_gpx(
+    filename: String,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FileDownload> {
+    if GarminSummary::get_by_filename(&state.db, &filename)
+        .await
+        .map_err(Into::<Error>::into)?
+        .is_none()
+    {
+        return Err(Error::BadRequest(format!("No activity {filename}")).into());
+    }
+    let gfile = read_or_parse_gfile(&state.config, &state.db, &state.avro_cache, &filename).await?;
+    let gpx = spawn_blocking(move || garmin_file_to_gpx(&gfile))
+        .await
+        .map_err(Into::<Error>::into)?;
+    let gpx_filename = format_sstr!("{}.gpx", filename.split('.').next().unwrap_or("activity"));
+    Ok(FileDownload::gpx(gpx.as_bytes().to_vec(), &gpx_filename))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct DistributionRequest {
+    #[schema(
+        description = "Activity Filename",
+        example = r#""2024-01-07_12-30-00_1_1.fit""#
+    )]
+    filename: StackString,
+    #[schema(
+        description = "Distribution Metric: cadence or stride_length (default cadence)",
+        example = r#""cadence""#
+    )]
+    metric: Option<StackString>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "DistributionBucket")]
+struct DistributionBucket {
+    #[schema(
+        description = "Bucket Lower Edge (spm, rpm or m)",
+        example = r#""170.0""#
+    )]
+    bucket: f64,
+    #[schema(description = "Time in Bucket (s)", example = r#""600.0""#)]
+    seconds: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "Distribution")]
+struct Distribution {
+    #[schema(description = "Distribution Metric", example = r#""cadence""#)]
+    metric: StackString,
+    #[schema(description = "Bucket Units", example = r#""spm""#)]
+    units: StackString,
+    #[schema(description = "Bucket Width", example = r#""5.0""#)]
+    bucket_width: f64,
+    #[schema(description = "Time in each Bucket")]
+    buckets: Vec<DistributionBucket>,
+}
+
+impl Distribution {
+    fn new(metric: DistributionMetric, sport: SportTypes, buckets: &[(f64, f64)]) -> Self {
+        Self {
+            metric: metric.to_str().into(),
+            units: metric.units(sport).into(),
+            bucket_width: metric.bucket_width(),
+            buckets: buckets
+                .iter()
+                .map(|(bucket, seconds)| DistributionBucket {
+                    bucket: *bucket,
+                    seconds: *seconds,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn parse_distribution_metric(metric: Option<&StackString>) -> Result<DistributionMetric, Error> {
+    match metric {
+        Some(m) => m.parse().map_err(|e| Error::BadRequest(format!("{e}"))),
+        None => Ok(DistributionMetric::default()),
+    }
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Activity Distribution")]
+struct DistributionResponse(JsonBase<Distribution, Error>);
+
+#[get("/garmin/distribution")]
+#[openapi(description = "Time spent in each cadence or stride length bucket of an Activity")]
+pub async fn garmin_distribution(
+    query: Query<DistributionRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<DistributionResponse> {
+    let query = query.into_inner();
+    let metric = parse_distribution_metric(query.metric.as_ref())?;
+    let summary = GarminSummary::get_by_filename(&state.db, &query.filename)
+        .await
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest(format!("No activity {}", query.filename)))?;
+    let stored = ActivityDistribution::read_by_summary_id(&state.db, summary.id, metric)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let buckets = if let Some(stored) = stored {
+        stored.buckets
+    } else {
+        let store = state.avro_cache.clone();
+        let gfile = garmin_file::GarminFile::read_cached_avro(&store, &query.filename)
+            .await
+            .map_err(Into::<Error>::into)?;
+        ActivityDistribution::from_gfile(summary.id, &gfile)
+            .into_iter()
+            .find(|d| d.metric == metric)
+            .map(|d| d.buckets)
+            .unwrap_or_default()
+    };
+    Ok(JsonBase::new(Distribution::new(metric, summary.sport, &buckets)).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct MonthlyDistributionRequest {
+    #[schema(description = "Month (YYYY-MM)", example = r#""2024-01""#)]
+    month: StackString,
+    #[schema(description = "Sport (default running)")]
+    sport: Option<SportTypesWrapper>,
+    #[schema(
+        description = "Distribution Metric: cadence or stride_length (default cadence)",
+        example = r#""cadence""#
+    )]
+    metric: Option<StackString>,
+}
+
+/// First day of `month` (`YYYY-MM`) and of the month after
+fn month_range(month: &str) -> Result<(Date, Date), Error> {
+    let bad_month = || Error::BadRequest(format!("{month} is not a valid month (YYYY-MM)"));
+    let (year, month_number) = month.split_once('-').ok_or_else(bad_month)?;
+    let year: i32 = year.parse().map_err(|_| bad_month())?;
+    let month_number: u8 = month_number.parse().map_err(|_| bad_month())?;
+    let month = Month::try_from(month_number).map_err(|_| bad_month())?;
+    let start = Date::from_calendar_date(year, month, 1).map_err(|_| bad_month())?;
+    let next_year = if month == Month::December {
+        year + 1
+    } else {
+        year
+    };
+    let end = Date::from_calendar_date(next_year, month.next(), 1).map_err(|_| bad_month())?;
+    Ok((start, end))
+}
+
+#[get("/garmin/distribution/monthly")]
+#[openapi(description = "Time in each cadence or stride length bucket for a sport over a month")]
+pub async fn garmin_distribution_monthly(
+    query: Query<MonthlyDistributionRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<DistributionResponse> {
+    let query = query.into_inner();
+    let metric = parse_distribution_metric(query.metric.as_ref())?;
+    let sport = query.sport.map_or(SportTypes::Running, Into::into);
+    let (start, end) = month_range(&query.month)?;
+    let buckets = ActivityDistribution::get_totals(
+        &state.db,
+        metric,
+        sport,
+        start.midnight().assume_utc(),
+        end.midnight().assume_utc(),
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    Ok(JsonBase::new(Distribution::new(metric, sport, &buckets)).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "SportMeta")]
+struct SportMeta {
+    #[schema(description = "Sport", example = r#""running""#)]
+    name: SportTypesWrapper,
+    #[schema(description = "Display Name", example = r#""Running""#)]
+    display_name: StackString,
+    #[schema(
+        description = "Other Names Accepted for the Sport",
+        example = r#"["run"]"#
+    )]
+    aliases: Vec<StackString>,
+    #[schema(description = "Strava Activity Type", example = r#""Run""#)]
+    strava_activity: StackString,
+    #[schema(description = "Fitbit Activity Type ID", example = r#""90009""#)]
+    fitbit_activity_id: Option<i64>,
+    #[schema(description = "User Defined Sport")]
+    custom: bool,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Sport Types")]
+struct SportMetaResponse(JsonBase<Vec<SportMeta>, Error>);
+
+#[get("/garmin/meta/sports")]
+#[openapi(description = "Valid values of the sport schema, with display names and aliases")]
+pub async fn meta_sports() -> WarpResult<SportMetaResponse> {
+    let sport_names = get_sport_names();
+    let sports = SportTypes::all()
+        .into_iter()
+        .map(|sport| {
+            let aliases = sport_names
+                .iter()
+                .filter(|name| **name != sport.to_str())
+                .filter(|name| name.parse::<SportTypes>().ok() == Some(sport))
+                .map(|name| (*name).into())
+                .collect();
+            SportMeta {
+                name: sport.into(),
+                display_name: sport.display_name(),
+                aliases,
+                strava_activity: sport.to_strava_activity(),
+                fitbit_activity_id: sport.to_fitbit_activity_id().map(|id| id as i64),
+                custom: matches!(sport, SportTypes::Custom(_)),
+            }
+        })
+        .collect();
+    Ok(JsonBase::new(sports).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "RaceTypeMeta")]
+struct RaceTypeMeta {
+    #[schema(description = "Race Type", example = r#""personal""#)]
+    name: RaceTypeWrapper,
+    #[schema(description = "Description", example = r#""Personal Results""#)]
+    description: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Race Types")]
+struct RaceTypeMetaResponse(JsonBase<Vec<RaceTypeMeta>, Error>);
+
+#[get("/garmin/meta/race_types")]
+#[openapi(description = "Valid values of race_type")]
+pub async fn meta_race_types() -> WarpResult<RaceTypeMetaResponse> {
+    let race_types = [
+        (RaceType::Personal, "Personal Results"),
+        (RaceType::WorldRecordMen, "Men's World Records"),
+        (RaceType::WorldRecordWomen, "Women's World Records"),
+    ]
+    .iter()
+    .map(|(race_type, description)| RaceTypeMeta {
+        name: (*race_type).into(),
+        description: (*description).into(),
+    })
+    .collect();
+    Ok(JsonBase::new(race_types).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "FitbitActivityTypeMeta")]
+struct FitbitActivityTypeMeta {
+    #[schema(description = "Fitbit Activity Type ID", example = r#""90009""#)]
+    id: i64,
+    #[schema(description = "Sport", example = r#""running""#)]
+    sport: SportTypesWrapper,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "StravaActivityTypeMeta")]
+struct StravaActivityTypeMeta {
+    #[schema(description = "Strava Activity Type", example = r#""Run""#)]
+    activity_type: StackString,
+    #[schema(description = "Sport", example = r#""running""#)]
+    sport: SportTypesWrapper,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "ActivityTypeMeta")]
+struct ActivityTypeMeta {
+    #[schema(description = "Fitbit Activity Type IDs")]
+    fitbit: Vec<FitbitActivityTypeMeta>,
+    #[schema(description = "Strava Activity Types")]
+    strava: Vec<StravaActivityTypeMeta>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Activity Type Mappings")]
+struct ActivityTypeMetaResponse(JsonBase<ActivityTypeMeta, Error>);
+
+#[get("/garmin/meta/activity_types")]
+#[openapi(description = "Fitbit activity ids and Strava activity types mapped to sports")]
+pub async fn meta_activity_types() -> WarpResult<ActivityTypeMetaResponse> {
+    let fitbit = get_fitbit_activity_ids()
+        .into_iter()
+        .map(|(id, sport)| FitbitActivityTypeMeta {
+            id: id as i64,
+            sport: sport.into(),
+        })
+        .collect();
+    let strava = get_strava_activity_types()
+        .into_iter()
+        .map(|(activity_type, sport)| StravaActivityTypeMeta {
+            activity_type,
+            sport: sport.into(),
+        })
+        .collect();
+    Ok(JsonBase::new(ActivityTypeMeta { fitbit, strava }).into())
 }
 
 #[derive(RwebResponse)]
@@ -259,6 +893,11 @@ pub async fn scatter_plot_js() -> WarpResult<JsResponse> {
     Ok(HtmlBase::new(include_str!("../../templates/scatter_plot.js")).into())
 }
 
+#[get("/garmin/scripts/combined_plot.js")]
+pub async fn combined_plot_js() -> WarpResult<JsResponse> {
+    Ok(HtmlBase::new(include_str!("../../templates/combined_plot.js")).into())
+}
+
 #[get("/garmin/scripts/scatter_plot_with_lines.js")]
 pub async fn scatter_plot_with_lines_js() -> WarpResult<JsResponse> {
     Ok(HtmlBase::new(include_str!("../../templates/scatter_plot_with_lines.js")).into())
@@ -285,7 +924,7 @@ pub async fn garmin_upload(
     #[data] state: AppState,
 ) -> WarpResult<UploadResponse> {
     let session = user
-        .get_session(&state.client, &state.config)
+        .get_session(&state.client, &state.config, &state.db)
         .await
         .map_err(Into::<Error>::into)?;
     let body = garmin_upload_body(form, state, session).await?;
@@ -314,10 +953,19 @@ async fn garmin_upload_body(
         }
     }
 
-    let filename = fname.as_str();
+    process_uploaded_file(fname.as_str(), "/garmin/upload_file", &state, session).await
+}
+
+async fn process_uploaded_file(
+    filename: &str,
+    import_path: &str,
+    state: &AppState,
+    session: Session,
+) -> HttpResult<StackString> {
     let gcli = GarminCli::from_pool(&state.db)?;
     let filenames = vec![filename];
-    let datetimes = gcli.process_filenames(&filenames).await?;
+    let provenance = Provenance::new(DataProvider::Upload, import_path, None);
+    let datetimes = gcli.process_filenames(&filenames, &provenance).await?;
     gcli.sync_everything().await?;
     gcli.proc_everything().await?;
 
@@ -325,405 +973,596 @@ async fn garmin_upload_body(
         filter: datetimes
             .first()
             .map(|dt| convert_datetime_to_str((*dt).into())),
+        split: None,
     };
 
     let grec = proc_pattern_wrapper(&state.config, query, &session.history, false);
-    let body = get_index_body(&state.db, &state.config, &grec.request, false)
-        .await?
-        .into();
+    let body = get_index_body(
+        &state.db,
+        &state.config,
+        &state.avro_cache,
+        &grec.request,
+        session,
+        false,
+    )
+    .await?
+    .into_string()
+    .await?
+    .into();
     Ok(body)
 }
 
-async fn save_file(file_path: &str, field: Part) -> Result<u64, anyhow::Error> {
-    let mut file = File::create(file_path).await?;
-    let mut stream = field.stream();
-    let mut buf_size = 0usize;
+#[derive(Serialize, Deserialize, Schema)]
+struct UploadSessionRequest {
+    filename: StackString,
+    #[schema(description = "File size in bytes")]
+    size: u64,
+    #[schema(description = "Hex encoded sha256 of the complete file")]
+    sha256: StackString,
+}
 
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        let chunk = chunk.chunk();
-        buf_size += chunk.len();
-        file.write_all(chunk).await?;
-    }
-    let file_size = file.metadata().await?.len();
-    debug_assert!(buf_size as u64 == file_size);
-    Ok(file_size)
+#[derive(Serialize, Deserialize, Schema)]
+struct UploadSessionQuery {
+    session_id: UuidWrapper,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+#[schema(component = "UploadSessionStatus")]
+struct UploadSessionStatus {
+    session_id: UuidWrapper,
+    filename: StackString,
+    size: u64,
+    #[schema(description = "Number of bytes received, the next chunk starts here")]
+    offset: u64,
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Garmin Sync", content = "html")]
-struct GarminSyncResponse(HtmlBase<StackString, Error>);
+#[response(description = "Upload Session", status = "CREATED")]
+struct UploadSessionResponse(JsonBase<UploadSessionStatus, Error>);
 
-#[post("/garmin/garmin_sync")]
-pub async fn garmin_sync(
+#[post("/garmin/upload_session")]
+pub async fn upload_session_create(
+    payload: Json<UploadSessionRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<GarminSyncResponse> {
-    let gcli = GarminCli::from_pool(&state.db).map_err(Into::<Error>::into)?;
-    let mut body = gcli.sync_everything().await.map_err(Into::<Error>::into)?;
-    body.extend_from_slice(&gcli.proc_everything().await.map_err(Into::<Error>::into)?);
-    let body = body.join("\n").into();
-    let body = table_body(body)?.into();
-    Ok(HtmlBase::new(body).into())
+) -> WarpResult<UploadSessionResponse> {
+    let payload = payload.into_inner();
+    let upload = UploadSession::new(&payload.filename, payload.size, &payload.sha256)
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    upload
+        .create(&state.config.upload_dir)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let status = UploadSessionStatus {
+        session_id: upload.session_id.into(),
+        filename: upload.filename,
+        size: upload.size,
+        offset: 0,
+    };
+    Ok(JsonBase::new(status).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Strava Sync", content = "html")]
-struct StravaSyncResponse(HtmlBase<StackString, Error>);
+#[response(description = "Upload Session Status")]
+struct UploadSessionStatusResponse(JsonBase<UploadSessionStatus, Error>);
 
-#[post("/garmin/strava_sync")]
-pub async fn strava_sync(
-    query: Query<StravaSyncRequest>,
+#[get("/garmin/upload_session")]
+pub async fn upload_session_status(
+    query: Query<UploadSessionQuery>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaSyncResponse> {
-    let body = query
-        .into_inner()
-        .run_sync(&state.db, &state.config)
-        .await?
-        .into_iter()
-        .map(|a| a.name)
-        .join("\n")
-        .into();
-    let body = table_body(body)?.into();
-    Ok(HtmlBase::new(body).into())
+) -> WarpResult<UploadSessionStatusResponse> {
+    let upload_dir = &state.config.upload_dir;
+    let upload = UploadSession::get(upload_dir, query.into_inner().session_id.into())
+        .await
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let offset = upload
+        .offset(upload_dir)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let status = UploadSessionStatus {
+        session_id: upload.session_id.into(),
+        filename: upload.filename,
+        size: upload.size,
+        offset,
+    };
+    Ok(JsonBase::new(status).into())
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Strava Auth", content = "html")]
-struct StravaAuthResponse(HtmlBase<StackString, Error>);
-
-#[get("/garmin/strava/auth")]
-pub async fn strava_auth(
+#[put("/garmin/upload_session/chunk")]
+pub async fn upload_session_chunk(
+    query: Query<UploadSessionQuery>,
+    #[header = "content-range"] content_range: String,
+    #[body] body: Bytes,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaAuthResponse> {
-    let client = StravaClient::from_file(state.config.clone())
+) -> WarpResult<UploadSessionStatusResponse> {
+    let upload_dir = &state.config.upload_dir;
+    let upload = UploadSession::get(upload_dir, query.into_inner().session_id.into())
+        .await
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let offset = upload
+        .append_chunk(upload_dir, &content_range, &body)
+        .await
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let status = UploadSessionStatus {
+        session_id: upload.session_id.into(),
+        filename: upload.filename,
+        size: upload.size,
+        offset,
+    };
+    Ok(JsonBase::new(status).into())
+}
+
+#[post("/garmin/upload_session/complete")]
+pub async fn upload_session_complete(
+    query: Query<UploadSessionQuery>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<UploadResponse> {
+    let session = user
+        .get_session(&state.client, &state.config, &state.db)
         .await
         .map_err(Into::<Error>::into)?;
-    let body: StackString = client
-        .get_authorization_url_api()
-        .map_err(Into::<Error>::into)
-        .map(|u| u.as_str().into())?;
+    let upload_dir = &state.config.upload_dir;
+    let upload = UploadSession::get(upload_dir, query.into_inner().session_id.into())
+        .await
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let tempdir = TempDir::with_prefix("garmin_rust").map_err(Into::<Error>::into)?;
+    let fname = upload
+        .finish(upload_dir, tempdir.path())
+        .await
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let body = process_uploaded_file(
+        &fname.to_string_lossy(),
+        "/garmin/upload_session/complete",
+        &state,
+        session,
+    )
+    .await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+async fn save_file(file_path: &str, field: Part) -> Result<u64, anyhow::Error> {
+    let mut file = File::create(file_path).await?;
+    let mut stream = field.stream();
+    let mut buf_size = 0usize;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        let chunk = chunk.chunk();
+        buf_size += chunk.len();
+        file.write_all(chunk).await?;
+    }
+    let file_size = file.metadata().await?.len();
+    debug_assert!(buf_size as u64 == file_size);
+    Ok(file_size)
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Garmin Sync", content = "html")]
+struct GarminSyncResponse(HtmlBase<StackString, Error>);
 
+#[post("/garmin/garmin_sync")]
+pub async fn garmin_sync(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<GarminSyncResponse> {
+    let gcli = GarminCli::from_pool(&state.db).map_err(Into::<Error>::into)?;
+    let result = async {
+        let mut body = gcli.sync_everything().await?;
+        body.extend_from_slice(&gcli.proc_everything().await?);
+        Ok::<_, anyhow::Error>(body)
+    }
+    .await;
+    let body = match result {
+        Ok(body) => body,
+        Err(e) if is_processing_in_progress(&e) => {
+            vec![format_sstr!("{e}, try again once it finishes")]
+        }
+        Err(e) => return Err(Into::<Error>::into(e).into()),
+    };
+    let body = body.join("\n").into();
+    let body = table_body(body)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Strava Refresh Auth", content = "html")]
-struct StravaRefreshResponse(HtmlBase<StackString, Error>);
+#[response(description = "Quarantined Files", content = "html")]
+struct QuarantineResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/strava/refresh_auth")]
-pub async fn strava_refresh(
+#[get("/garmin/quarantine")]
+pub async fn quarantine(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaRefreshResponse> {
-    let mut client = StravaClient::from_file(state.config.clone())
-        .await
-        .map_err(Into::<Error>::into)?;
-    client
-        .refresh_access_token()
+) -> WarpResult<QuarantineResponse> {
+    let files = QuarantinedFile::read_from_db(&state.db)
         .await
         .map_err(Into::<Error>::into)?;
-    client.to_file().await.map_err(Into::<Error>::into)?;
-    let body: StackString = r#"
-        <title>Strava auth code received!</title>
-        This window can be closed.
-        <script language="JavaScript" type="text/javascript">window.close()</script>"#
-        .into();
-
+    let body = quarantine_body(None, files)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "StravaCallbackRequest")]
-struct StravaCallbackRequest {
-    #[schema(description = "Authorization Code")]
-    code: StackString,
-    #[schema(description = "CSRF State")]
-    state: StackString,
+#[derive(Serialize, Deserialize, Schema)]
+struct QuarantineRetryRequest {
+    filename: StackString,
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Strava Callback", content = "html")]
-struct StravaCallbackResponse(HtmlBase<StackString, Error>);
-
-#[get("/garmin/strava/callback")]
-pub async fn strava_callback(
-    query: Query<StravaCallbackRequest>,
+#[post("/garmin/quarantine/retry")]
+pub async fn quarantine_retry(
+    query: Query<QuarantineRetryRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaCallbackResponse> {
+) -> WarpResult<QuarantineResponse> {
     let query = query.into_inner();
-    let mut client = StravaClient::from_file(state.config.clone())
+    let gcli = GarminCli::from_pool(&state.db).map_err(Into::<Error>::into)?;
+    let message = gcli
+        .retry_quarantined(&query.filename)
         .await
-        .map_err(Into::<Error>::into)?;
-    client
-        .process_callback(&query.code, &query.state)
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let files = QuarantinedFile::read_from_db(&state.db)
         .await
         .map_err(Into::<Error>::into)?;
-    client.to_file().await.map_err(Into::<Error>::into)?;
-    let body: StackString = r#"
-        <title>Strava auth code received!</title>
-        This window can be closed.
-        <script language="JavaScript" type="text/javascript">window.close()</script>"#
-        .into();
+    let body = quarantine_body(Some(message), files)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Strava Activities")]
-struct StravaActivitiesResponse(JsonBase<Vec<StravaActivityWrapper>, Error>);
+#[response(description = "Activity Cleanup", content = "html")]
+struct CleanupResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/strava/activities")]
-pub async fn strava_activities(
-    query: Query<StravaActivitiesRequest>,
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
-    #[data] state: AppState,
-) -> WarpResult<StravaActivitiesResponse> {
-    let mut alist: Vec<_> = query
-        .into_inner()
-        .get_activities(&state.config)
-        .await?
-        .into_iter()
-        .map(Into::into)
-        .collect();
-    alist.shrink_to_fit();
-    Ok(JsonBase::new(alist).into())
+#[derive(Serialize, Deserialize, Schema)]
+struct CleanupRequest {
+    #[schema(description = "Start Date")]
+    start_date: Option<DateType>,
+    #[schema(description = "End Date")]
+    end_date: Option<DateType>,
+    #[schema(description = "Sport")]
+    sport: Option<StackString>,
+    #[schema(description = "Only activities without distance")]
+    zero_distance: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "Pagination")]
-struct Pagination {
-    #[schema(description = "Total Number of Entries")]
-    total: usize,
-    #[schema(description = "Number of Entries to Skip")]
-    offset: usize,
-    #[schema(description = "Number of Entries Returned")]
-    limit: usize,
+#[derive(Serialize, Deserialize, Schema)]
+struct CleanupDeleteRequest {
+    #[schema(description = "Summary IDs listed by the dry run")]
+    ids: Vec<UuidWrapper>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "PaginatedStravaActivity")]
-struct PaginatedStravaActivity {
-    pagination: Pagination,
-    data: Vec<StravaActivityWrapper>,
+impl CleanupRequest {
+    fn filter(&self) -> Result<CleanupFilter, Error> {
+        let sport = self
+            .sport
+            .as_ref()
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e: anyhow::Error| Error::BadRequest(e.to_string()))?;
+        Ok(CleanupFilter {
+            start_date: self.start_date.map(Into::into),
+            end_date: self.end_date.map(Into::into),
+            sport,
+            zero_distance: self.zero_distance.unwrap_or(false),
+        })
+    }
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Strava DB Activities")]
-struct StravaActivitiesDBResponse(JsonBase<PaginatedStravaActivity, Error>);
-
-#[get("/garmin/strava/activities_db")]
-pub async fn strava_activities_db(
-    query: Query<StravaActivitiesRequest>,
+#[get("/garmin/admin/cleanup")]
+#[openapi(description = "Dry run listing of the activities a cleanup would delete")]
+pub async fn cleanup_dry_run(
+    query: Query<CleanupRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaActivitiesDBResponse> {
-    let query = query.into_inner();
-
-    let offset = query.offset.unwrap_or(0);
-    let limit = query.limit.unwrap_or(10);
-    let start_date = query.start_date.map(Into::into);
-    let end_date = query.end_date.map(Into::into);
-
-    let total = StravaActivity::get_total(&state.db, start_date, end_date)
-        .await
-        .map_err(Into::<Error>::into)?;
-    let pagination = Pagination {
-        total,
-        offset,
-        limit,
-    };
-
-    let mut data: Vec<_> =
-        StravaActivity::read_from_db(&state.db, start_date, end_date, Some(offset), Some(limit))
+) -> WarpResult<CleanupResponse> {
+    let filter = query.into_inner().filter()?;
+    // an empty filter just shows the form
+    let matches = if filter.is_empty() {
+        Vec::new()
+    } else {
+        filter
+            .get_matching(&state.db)
             .await
             .map_err(Into::<Error>::into)?
-            .map_ok(Into::into)
-            .try_collect()
-            .await
-            .map_err(Into::<Error>::into)?;
-    data.shrink_to_fit();
-
-    Ok(JsonBase::new(PaginatedStravaActivity { pagination, data }).into())
-}
-
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "StravaActiviesDBUpdateRequest")]
-struct StravaActiviesDBUpdateRequest {
-    updates: Vec<StravaActivityWrapper>,
+    };
+    let body = cleanup_body(filter, None, matches)?.into();
+    Ok(HtmlBase::new(body).into())
 }
 
-#[derive(RwebResponse)]
-#[response(
-    description = "Strava Activities Update",
-    status = "CREATED",
-    content = "html"
-)]
-struct StravaActivitiesUpdateResponse(HtmlBase<StackString, Error>);
-
-#[post("/garmin/strava/activities_db")]
-pub async fn strava_activities_db_update(
-    payload: Json<StravaActiviesDBUpdateRequest>,
+#[delete("/garmin/admin/cleanup")]
+#[openapi(description = "Delete the activities listed by a cleanup dry run")]
+pub async fn cleanup_delete(
+    payload: Json<CleanupDeleteRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaActivitiesUpdateResponse> {
-    let payload = payload.into_inner();
-    let mut updates: Vec<_> = payload.updates.into_iter().map(Into::into).collect();
-    updates.shrink_to_fit();
-    let body = StravaActivity::upsert_activities(&updates, &state.db)
+) -> WarpResult<CleanupResponse> {
+    let ids: Vec<Uuid> = payload
+        .into_inner()
+        .ids
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    if ids.is_empty() {
+        return Err(Error::BadRequest("No activities to delete".into()).into());
+    }
+    let matches = GarminSummary::get_by_ids(&state.db, &ids)
         .await
         .map_err(Into::<Error>::into)?;
-    StravaActivity::fix_summary_id_in_db(&state.db)
+    if matches.len() != ids.len() {
+        return Err(Error::BadRequest(
+            format_sstr!(
+                "Dry run listed {} activities, only {} still exist",
+                ids.len(),
+                matches.len()
+            )
+            .to_string(),
+        )
+        .into());
+    }
+    let gsync = GarminSync::from_config(&state.config).await;
+    let output = delete_activities(&state.db, &state.config, gsync.as_ref(), &matches)
         .await
         .map_err(Into::<Error>::into)?;
-
-    let body = body.join("\n").into();
+    let body = cleanup_body(
+        CleanupFilter::default(),
+        Some(output.join("\n").into()),
+        Vec::new(),
+    )?
+    .into();
     Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Strava Upload", status = "CREATED", content = "html")]
-struct StravaUploadResponse(HtmlBase<StackString, Error>);
+#[response(description = "Admin Statistics", content = "html")]
+struct AdminStatsResponse(HtmlBase<StackString, Error>);
 
-#[post("/garmin/strava/upload")]
-pub async fn strava_upload(
-    payload: Json<StravaUploadRequest>,
+#[get("/garmin/admin/stats")]
+pub async fn admin_stats(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaUploadResponse> {
-    let body = payload.into_inner().run_upload(&state.config).await?;
+) -> WarpResult<AdminStatsResponse> {
+    let stats = AdminStats::get(&state.config, &state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = admin_stats_body(stats)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Strava Update", status = "CREATED", content = "html")]
-struct StravaUpdateResponse(HtmlBase<StackString, Error>);
+#[response(description = "Sync Lock", content = "html")]
+struct SyncLockResponse(HtmlBase<StackString, Error>);
 
-#[post("/garmin/strava/update")]
-pub async fn strava_update(
-    payload: Json<StravaUpdateRequest>,
+#[get("/garmin/admin/sync_lock")]
+#[openapi(description = "Show which process holds the sync lock")]
+pub async fn sync_lock(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaUpdateResponse> {
-    let body = payload.into_inner().run_update(&state.config).await?;
-    Ok(HtmlBase::new(body.as_str().into()).into())
+) -> WarpResult<SyncLockResponse> {
+    let holder = LockHolder::get(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = holder.map_or_else(
+        || "no sync in progress".into(),
+        |h| format_sstr!("lock held by {}", h.describe()),
+    );
+    let body = table_body(body)?.into();
+    Ok(HtmlBase::new(body).into())
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Strava Create", status = "CREATED", content = "html")]
-struct StravaCreateResponse(HtmlBase<StackString, Error>);
-
-#[post("/garmin/strava/create")]
-pub async fn strava_create(
-    query: Query<StravaCreateRequest>,
+#[delete("/garmin/admin/sync_lock")]
+#[openapi(description = "Terminate the db connection holding the sync lock")]
+pub async fn sync_lock_release(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaCreateResponse> {
-    let activity_id = query
-        .into_inner()
-        .create_activity(&state.db, &state.config)
-        .await?;
-    let body = activity_id.map_or_else(|| "".into(), StackString::from_display);
+) -> WarpResult<SyncLockResponse> {
+    let holder = LockHolder::force_unlock(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = holder.map_or_else(
+        || "no sync in progress".into(),
+        |h| format_sstr!("released lock held by {}", h.describe()),
+    );
+    let body = table_body(body)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Fitbit Heartrate")]
-struct FitbitHeartRateResponse(JsonBase<Vec<FitbitHeartRateWrapper>, Error>);
+#[response(description = "Coverage Gaps", content = "html")]
+struct CoverageGapsResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/fitbit/heartrate_cache")]
-pub async fn fitbit_heartrate_cache(
-    query: Query<FitbitHeartrateCacheRequest>,
+#[derive(Serialize, Deserialize, Schema)]
+struct CoverageGapsRequest {
+    #[schema(description = "Number of days to look back, default 365")]
+    days: Option<i64>,
+}
+
+#[get("/garmin/admin/gaps")]
+pub async fn coverage_gaps(
+    query: Query<CoverageGapsRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<FitbitHeartRateResponse> {
-    let mut hlist: Vec<_> = query
-        .into_inner()
-        .get_cache(&state.config)
-        .await?
-        .into_iter()
-        .map(Into::into)
-        .collect();
-    hlist.shrink_to_fit();
-    Ok(JsonBase::new(hlist).into())
+) -> WarpResult<CoverageGapsResponse> {
+    let query = query.into_inner();
+    let start_date = (OffsetDateTime::now_utc() - Duration::days(query.days.unwrap_or(365))).date();
+    let gaps = CoverageGap::read_from_db(
+        &state.db,
+        start_date,
+        DEFAULT_STREAK_DAYS,
+        DEFAULT_MAX_GAP_DAYS,
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    let body = coverage_gaps_body(None, gaps)?.into();
+    Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(
-    description = "Fitbit Heartrate Update",
-    content = "html",
-    status = "CREATED"
-)]
-struct FitbitHeartrateUpdateResponse(HtmlBase<StackString, Error>);
+#[response(description = "Milestones", content = "html")]
+struct MilestonesResponse(HtmlBase<StackString, Error>);
 
-#[post("/garmin/fitbit/heartrate_cache")]
-pub async fn fitbit_heartrate_cache_update(
-    payload: Json<FitbitHeartrateUpdateRequest>,
+#[get("/garmin/milestones")]
+pub async fn milestones(
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<FitbitHeartrateUpdateResponse> {
-    let dates = payload.into_inner().merge_data(&state.config).await?;
-    Ok(HtmlBase::new(format_sstr!("Finished {dates:?}")).into())
+) -> WarpResult<MilestonesResponse> {
+    let milestones = Milestone::read_from_db(&state.db, None)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = milestones_body(milestones)?.into();
+    Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Fitbit Activities")]
-struct FitbitActivitiesResponse(JsonBase<Vec<FitbitActivityWrapper>, Error>);
+#[response(description = "Most Kudoed Activities", content = "html")]
+struct MostKudoedResponse(HtmlBase<StackString, Error>);
+
+#[derive(Serialize, Deserialize, Schema)]
+struct MostKudoedRequest {
+    #[schema(description = "Year, defaults to the current year")]
+    year: Option<i32>,
+}
+
+#[get("/garmin/strava/most_kudoed")]
+pub async fn strava_most_kudoed(
+    query: Query<MostKudoedRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<MostKudoedResponse> {
+    let year = query
+        .into_inner()
+        .year
+        .unwrap_or_else(|| OffsetDateTime::now_utc().year());
+    let activities = StravaActivity::get_most_kudoed(&state.db, year, 10)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = most_kudoed_body(year, activities)?.into();
+    Ok(HtmlBase::new(body).into())
+}
 
 #[derive(RwebResponse)]
-#[response(description = "Fitbit Callback", content = "html")]
-struct FitbitCallbackResponse(HtmlBase<StackString, Error>);
+#[response(description = "Trips", content = "html")]
+struct TripsResponse(HtmlBase<StackString, Error>);
+
+async fn get_trips_body(state: &AppState) -> HttpResult<StackString> {
+    let trips = Trip::get_all(&state.db).await?;
+    let body = trips_body(trips)?.into();
+    Ok(body)
+}
+
+#[get("/garmin/trips")]
+pub async fn trips(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<TripsResponse> {
+    let body = get_trips_body(&state).await?;
+    Ok(HtmlBase::new(body).into())
+}
 
 #[derive(Serialize, Deserialize, Schema)]
-struct FitbitSyncRequest {
-    date: DateType,
+struct TripCreateRequest {
+    #[schema(description = "Trip Name", example = r#""Alps 2024""#)]
+    name: StackString,
+    #[schema(description = "First Day of the Trip")]
+    start_date: DateType,
+    #[schema(description = "Last Day of the Trip")]
+    end_date: DateType,
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Fitbit Sync", content = "html")]
-struct FitbitSyncResponse(HtmlBase<StackString, Error>);
+#[post("/garmin/trips")]
+pub async fn trip_create(
+    payload: Json<TripCreateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<TripsResponse> {
+    let payload = payload.into_inner();
+    let trip = Trip::new(
+        &payload.name,
+        payload.start_date.into(),
+        payload.end_date.into(),
+    )
+    .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let inserted = trip
+        .insert_into_db(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    if !inserted {
+        return Err(Error::BadRequest(format!("Trip {} already exists", trip.name)).into());
+    }
+    let body = get_trips_body(&state).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct TripRequest {
+    #[schema(description = "Trip ID")]
+    id: UuidWrapper,
+}
+
+#[delete("/garmin/trips")]
+pub async fn trip_delete(
+    query: Query<TripRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<TripsResponse> {
+    let id = query.into_inner().id.into();
+    let trip = Trip::get_by_id(&state.db, id)
+        .await
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest(format!("No trip {id}")))?;
+    trip.delete_from_db(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = get_trips_body(&state).await?;
+    Ok(HtmlBase::new(body).into())
+}
 
 #[derive(RwebResponse)]
-#[response(description = "Fitbit Heartrate Statistics Plots", content = "html")]
-struct FitbitStatisticsPlotResponse(HtmlBase<StackString, Error>);
+#[response(description = "Trip", content = "html")]
+struct TripResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/fitbit/heartrate_statistics_plots")]
-pub async fn heartrate_statistics_plots(
-    query: Query<ScaleMeasurementRequest>,
+#[get("/garmin/trip")]
+pub async fn trip(
+    query: Query<TripRequest>,
     #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<FitbitStatisticsPlotResponse> {
-    let query: FitbitStatisticsPlotRequest = query.into_inner().into();
+) -> WarpResult<TripResponse> {
+    let id = query.into_inner().id.into();
     let session = user
-        .get_session(&state.client, &state.config)
+        .get_session(&state.client, &state.config, &state.db)
         .await
         .map_err(Into::<Error>::into)?;
-    let mut stats: Vec<FitbitStatisticsSummary> = FitbitStatisticsSummary::read_from_db(
-        &state.db,
-        Some(query.start_date.into()),
-        Some(query.end_date.into()),
-        None,
-        None,
-    )
-    .await
-    .map_err(Into::<Error>::into)?
-    .try_collect()
-    .await
-    .map_err(Into::<Error>::into)?;
-    stats.shrink_to_fit();
+    let trip = Trip::get_by_id(&state.db, id)
+        .await
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest(format!("No trip {id}")))?;
+    let summaries = trip
+        .get_summaries(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let store = state.avro_cache.clone();
+    let mut routes = Vec::new();
+    for summary in &summaries {
+        match garmin_file::GarminFile::read_cached_avro(&store, &summary.filename).await {
+            Ok(gfile) => routes.extend(TripRoute::from_gfile(&gfile)),
+            Err(e) => debug!("failed to read {} {e}", summary.filename),
+        }
+    }
+    let photo_urls = Trip::get_photo_urls(&state.db, &summaries)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let title = trip.name.clone();
     let body = index_new_body(
         &state.config,
         &state.db,
-        "".into(),
+        title,
         false,
-        session.history,
-        IndexConfig::HearRateSummary {
-            stats,
-            offset: Some(query.offset),
-            start_date: Some(query.start_date),
-            end_date: Some(query.end_date),
+        session,
+        IndexConfig::Trip {
+            trip: TripOpts {
+                stats: TripStats::from_summaries(&summaries),
+                trip,
+                summaries,
+                routes,
+                photo_urls,
+            },
         },
     )
     .await?
@@ -731,722 +1570,3177 @@ pub async fn heartrate_statistics_plots(
     Ok(HtmlBase::new(body).into())
 }
 
-#[get("/garmin/fitbit/heartrate_statistics_plots_demo")]
-pub async fn heartrate_statistics_plots_demo(
-    query: Query<ScaleMeasurementRequest>,
-    #[data] state: AppState,
-    #[filter = "optional_session"] session: Option<Session>,
-) -> WarpResult<FitbitStatisticsPlotResponse> {
-    let mut query: FitbitStatisticsPlotRequest = query.into_inner().into();
-    query.is_demo = true;
-    let session = session.unwrap_or_default();
+#[derive(Serialize, Deserialize, Schema)]
+struct CoverageGapBackfillRequest {
+    kind: StackString,
+    start_date: DateType,
+    end_date: DateType,
+}
+
+#[post("/garmin/admin/gaps/backfill")]
+pub async fn coverage_gap_backfill(
+    query: Query<CoverageGapBackfillRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<CoverageGapsResponse> {
+    let query = query.into_inner();
+    let gap = CoverageGap {
+        kind: query
+            .kind
+            .parse::<GapKind>()
+            .map_err(|e| Error::BadRequest(e.to_string()))?,
+        gap_start: query.start_date.into(),
+        gap_end: query.end_date.into(),
+    };
+    let gcli = GarminCli::from_pool(&state.db).map_err(Into::<Error>::into)?;
+    let output = GarminCliOpts::backfill_gap(&gcli, &gap)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let start_date = (OffsetDateTime::now_utc() - Duration::days(365)).date();
+    let gaps = CoverageGap::read_from_db(
+        &state.db,
+        start_date,
+        DEFAULT_STREAK_DAYS,
+        DEFAULT_MAX_GAP_DAYS,
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    let body = coverage_gaps_body(Some(output.join("\n").into()), gaps)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Strava Sync", content = "html")]
+struct StravaSyncResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/strava_sync")]
+pub async fn strava_sync(
+    query: Query<StravaSyncRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaSyncResponse> {
+    let query = query.into_inner();
+    let verbose = query.verbose.unwrap_or(false);
+    let list_changes = verbose || query.dry_run.unwrap_or(false);
+    let result = query.run_sync(&state.db, &state.config).await?;
+    let mut body = if list_changes {
+        result
+            .changes
+            .iter()
+            .map(|change| change.describe(verbose))
+            .join("\n")
+    } else {
+        result.activities.into_iter().map(|a| a.name).join("\n")
+    };
+    for error in &result.errors {
+        body.push_str(&format_sstr!("\nfailed {error}"));
+    }
+    let body = table_body(body.into())?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Strava Auth", content = "html")]
+struct StravaAuthResponse(HtmlBase<StackString, Error>);
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "StravaAuthRequest")]
+struct StravaAuthRequest {
+    #[schema(description = "Account to link, defaults to the default account")]
+    account: Option<StackString>,
+}
+
+#[get("/garmin/strava/auth")]
+pub async fn strava_auth(
+    query: Query<StravaAuthRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaAuthResponse> {
+    require_strava(&state.config)?;
+    let mut client = StravaClient::from_file(state.config.clone())
+        .await
+        .map_err(Into::<Error>::into)?;
+    if let Some(account) = query.into_inner().account {
+        if account != DEFAULT_ACCOUNT {
+            StravaAccount::validate_name(&account)
+                .map_err(|e| Error::BadRequest(format!("{e}")))?;
+            client.account = account;
+        }
+    }
+    let body: StackString = client
+        .get_authorization_url_api()
+        .map_err(Into::<Error>::into)
+        .map(|u| u.as_str().into())?;
+
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Strava Refresh Auth", content = "html")]
+struct StravaRefreshResponse(HtmlBase<StackString, Error>);
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "StravaAccountRequest")]
+struct StravaAccountRequest {
+    #[schema(description = "Strava Account, defaults to the default account")]
+    account: Option<StackString>,
+}
+
+impl StravaAccountRequest {
+    fn account(&self) -> &str {
+        self.account.as_ref().map_or(DEFAULT_ACCOUNT, StackString::as_str)
+    }
+}
+
+#[get("/garmin/strava/refresh_auth")]
+pub async fn strava_refresh(
+    query: Query<StravaAccountRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaRefreshResponse> {
+    require_strava(&state.config)?;
+    let query = query.into_inner();
+    let mut client = StravaClient::from_file_account(state.config.clone(), query.account())
+        .await
+        .map_err(Into::<Error>::into)?;
+    client
+        .refresh_access_token()
+        .await
+        .map_err(Into::<Error>::into)?;
+    client.to_file().await.map_err(Into::<Error>::into)?;
+    let body: StackString = r#"
+        <title>Strava auth code received!</title>
+        This window can be closed.
+        <script language="JavaScript" type="text/javascript">window.close()</script>"#
+        .into();
+
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "StravaCallbackRequest")]
+struct StravaCallbackRequest {
+    #[schema(description = "Authorization Code")]
+    code: StackString,
+    #[schema(description = "CSRF State")]
+    state: StackString,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Strava Callback", content = "html")]
+struct StravaCallbackResponse(HtmlBase<StackString, Error>);
+
+#[get("/garmin/strava/callback")]
+pub async fn strava_callback(
+    query: Query<StravaCallbackRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaCallbackResponse> {
+    let query = query.into_inner();
+    require_strava(&state.config)?;
+    let mut client = StravaClient::from_file(state.config.clone())
+        .await
+        .map_err(Into::<Error>::into)?;
+    client
+        .process_callback(&query.code, &query.state)
+        .await
+        .map_err(Into::<Error>::into)?;
+    client.to_file().await.map_err(Into::<Error>::into)?;
+    let body: StackString = r#"
+        <title>Strava auth code received!</title>
+        This window can be closed.
+        <script language="JavaScript" type="text/javascript">window.close()</script>"#
+        .into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "StravaAccount")]
+struct StravaAccountInfo {
+    #[schema(description = "Account Name")]
+    name: StackString,
+    #[schema(description = "Whether the account is synced")]
+    sync_enabled: bool,
+    #[schema(description = "Whether the account has a refresh token")]
+    authorized: bool,
+}
+
+impl From<StravaAccount> for StravaAccountInfo {
+    fn from(account: StravaAccount) -> Self {
+        Self {
+            name: account.name,
+            sync_enabled: account.sync_enabled,
+            authorized: account.refresh_token.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedStravaAccount")]
+struct PaginatedStravaAccount {
+    pagination: Pagination,
+    data: Vec<StravaAccountInfo>,
+}
+
+impl PaginatedBody for PaginatedStravaAccount {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/strava/accounts")]
+#[openapi(description = "List Linked Strava Accounts")]
+pub async fn strava_accounts(
+    query: Query<PageQuery>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedStravaAccount>> {
+    require_strava(&state.config)?;
+    let page = query.into_inner().page()?;
+    let accounts = StravaAccount::read_tokenfile(&state.config)
+        .await
+        .map_err(Into::<Error>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let (pagination, data) = page.paginate_list(accounts, "/garmin/strava/accounts", &[])?;
+    Ok(PaginatedJson(PaginatedStravaAccount { pagination, data }))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct StravaAccountSyncRequest {
+    #[schema(description = "Account Name")]
+    account: StackString,
+    #[schema(description = "Whether to sync the account")]
+    sync_enabled: bool,
+}
+
+#[post("/garmin/strava/accounts/sync")]
+#[openapi(description = "Enable or Disable Syncing a Linked Strava Account")]
+pub async fn strava_account_sync(
+    query: Query<StravaAccountSyncRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaAccountsResponse> {
+    require_strava(&state.config)?;
+    let query = query.into_inner();
+    StravaAccount::set_sync_enabled(&state.config, &query.account, query.sync_enabled)
+        .await
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let accounts = StravaAccount::read_tokenfile(&state.config)
+        .await
+        .map_err(Into::<Error>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(JsonBase::new(accounts).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct StravaAccountDeleteRequest {
+    #[schema(description = "Account Name")]
+    account: StackString,
+}
+
+#[delete("/garmin/strava/accounts")]
+#[openapi(description = "Disconnect a Linked Strava Account")]
+pub async fn strava_account_delete(
+    query: Query<StravaAccountDeleteRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<IntegrationsResponse> {
+    let query = query.into_inner();
+    StravaAccount::disconnect(&state.config, &query.account)
+        .await
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let body = integrations_impl(&state).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Integrations", content = "html")]
+struct IntegrationsResponse(HtmlBase<StackString, Error>);
+
+async fn integrations_impl(state: &AppState) -> HttpResult<StackString> {
+    let strava_accounts = if state.config.strava_configured() {
+        StravaAccount::read_tokenfile(&state.config).await?
+    } else {
+        Vec::new()
+    };
+    let statuses = SyncStatus::get_all(&state.db).await?;
+    Ok(integrations_body(strava_accounts, statuses)?.into())
+}
+
+#[get("/garmin/integrations")]
+#[openapi(description = "Connection and Sync Status of Strava, Fitbit and Garmin Connect")]
+pub async fn integrations(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<IntegrationsResponse> {
+    let body = integrations_impl(&state).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Unlinked Activities", content = "html")]
+struct LinkReviewResponse(HtmlBase<StackString, Error>);
+
+/// Unlinked activities of the last 90 days are reviewed by default
+const DEFAULT_LINK_REVIEW_DAYS: i64 = 90;
+
+async fn link_review_impl(
+    state: &AppState,
+    days: Option<i64>,
+    message: Option<StackString>,
+) -> HttpResult<StackString> {
+    let start =
+        OffsetDateTime::now_utc() - Duration::days(days.unwrap_or(DEFAULT_LINK_REVIEW_DAYS));
+    let unmatched = UnmatchedActivities::get(&state.db, start).await?;
+    Ok(link_review_body(message, unmatched)?.into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct LinkReviewRequest {
+    #[schema(description = "Number of Days to Review (default 90)")]
+    days: Option<i64>,
+}
+
+#[get("/garmin/link/unmatched")]
+#[openapi(description = "Strava and Garmin Activities Which Aren't Linked")]
+pub async fn link_unmatched(
+    query: Query<LinkReviewRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<LinkReviewResponse> {
+    let body = link_review_impl(&state, query.into_inner().days, None).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct LinkRequest {
+    #[schema(description = "Garmin Summary ID")]
+    summary_id: UuidWrapper,
+    #[schema(description = "Strava Activity ID")]
+    strava_id: i64,
+}
+
+#[post("/garmin/link")]
+#[openapi(description = "Link a Strava Activity to a Garmin Activity")]
+pub async fn link_activity(
+    query: Query<LinkRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<LinkReviewResponse> {
+    let query = query.into_inner();
+    let summary_id: Uuid = query.summary_id.into();
+    link_manual(&state.db, summary_id, query.strava_id)
+        .await
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let message = format_sstr!("Linked {} to {summary_id}", query.strava_id);
+    let body = link_review_impl(&state, None, Some(message)).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[post("/garmin/link/auto")]
+#[openapi(description = "Link Strava Activities to Garmin Activities With Nearby Start Times")]
+pub async fn link_auto(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<LinkReviewResponse> {
+    let links = link_tolerant(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let message = format_sstr!("Linked {} activities", links.len());
+    let body = link_review_impl(&state, None, Some(message)).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[get("/garmin/strava/activities")]
+pub async fn strava_activities(
+    query: Query<StravaActivitiesRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedStravaActivity>> {
+    require_strava(&state.config)?;
+    let query = query.into_inner();
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let mut params = date_params(
+        query.start_date.map(Into::into),
+        query.end_date.map(Into::into),
+    );
+    params.extend(query.account.clone().map(|account| ("account", account)));
+    let alist: Vec<_> = query
+        .get_activities(&state.config)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let (pagination, data) = page.paginate_list(alist, "/garmin/strava/activities", &params)?;
+    Ok(PaginatedJson(PaginatedStravaActivity { pagination, data }))
+}
+
+fn date_params(
+    start_date: Option<Date>,
+    end_date: Option<Date>,
+) -> Vec<(&'static str, StackString)> {
+    [("start_date", start_date), ("end_date", end_date)]
+        .into_iter()
+        .filter_map(|(key, date)| date.map(|d| (key, format_sstr!("{d}"))))
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedStravaActivity")]
+struct PaginatedStravaActivity {
+    pagination: Pagination,
+    data: Vec<StravaActivityWrapper>,
+}
+
+impl PaginatedBody for PaginatedStravaActivity {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/strava/activities_db")]
+pub async fn strava_activities_db(
+    query: Query<StravaActivitiesRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedStravaActivity>> {
+    let query = query.into_inner();
+
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let start_date = query.start_date.map(Into::into);
+    let end_date = query.end_date.map(Into::into);
+
+    let total = StravaActivity::get_total(&state.db, start_date, end_date)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let mut rows: Vec<StravaActivity> = StravaActivity::read_from_db(
+        &state.db,
+        start_date,
+        end_date,
+        Some(page.start),
+        Some(page.limit),
+    )
+    .await
+    .map_err(Into::<Error>::into)?
+    .try_collect()
+    .await
+    .map_err(Into::<Error>::into)?;
+    rows.shrink_to_fit();
+    let pagination = page.paginate(
+        total,
+        &rows,
+        "/garmin/strava/activities_db",
+        &date_params(start_date, end_date),
+    );
+    let data = rows.into_iter().map(Into::into).collect();
+
+    Ok(PaginatedJson(PaginatedStravaActivity { pagination, data }))
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "StravaActiviesDBUpdateRequest")]
+struct StravaActiviesDBUpdateRequest {
+    updates: Vec<StravaActivityWrapper>,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Strava Activities Update",
+    status = "CREATED",
+    content = "html"
+)]
+struct StravaActivitiesUpdateResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/strava/activities_db")]
+pub async fn strava_activities_db_update(
+    payload: Json<StravaActiviesDBUpdateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaActivitiesUpdateResponse> {
+    let payload = payload.into_inner();
+    let mut updates: Vec<_> = payload.updates.into_iter().map(Into::into).collect();
+    updates.shrink_to_fit();
+    let provenance = Provenance::new(DataProvider::Upload, "/garmin/strava/activities_db", None);
+    let body = StravaActivity::upsert_activities(&updates, &state.db, &provenance)
+        .await
+        .map_err(Into::<Error>::into)?;
+    StravaActivity::fix_summary_id_in_db(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+
+    let body = body.join("\n").into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Strava Upload", status = "CREATED", content = "html")]
+struct StravaUploadResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/strava/upload")]
+pub async fn strava_upload(
+    payload: Json<StravaUploadRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaUploadResponse> {
+    require_strava(&state.config)?;
+    let body = payload.into_inner().run_upload(&state.config).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Strava Update", status = "CREATED", content = "html")]
+struct StravaUpdateResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/strava/update")]
+pub async fn strava_update(
+    payload: Json<StravaUpdateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaUpdateResponse> {
+    require_strava(&state.config)?;
+    let body = payload
+        .into_inner()
+        .run_update(&state.db, &state.config)
+        .await?;
+    Ok(HtmlBase::new(body.as_str().into()).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Strava Create", status = "CREATED", content = "html")]
+struct StravaCreateResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/strava/create")]
+pub async fn strava_create(
+    query: Query<StravaCreateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaCreateResponse> {
+    require_strava(&state.config)?;
+    let activity_id = query
+        .into_inner()
+        .create_activity(&state.db, &state.config)
+        .await?;
+    let body = activity_id.map_or_else(|| "".into(), StackString::from_display);
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedFitbitHeartRate")]
+struct PaginatedFitbitHeartRate {
+    pagination: Pagination,
+    data: Vec<FitbitHeartRateWrapper>,
+}
+
+impl PaginatedBody for PaginatedFitbitHeartRate {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/fitbit/heartrate_cache")]
+pub async fn fitbit_heartrate_cache(
+    query: Query<FitbitHeartrateCacheRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedFitbitHeartRate>> {
+    let query = query.into_inner();
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let date: Date = query.date.into();
+    let hlist: Vec<_> = query
+        .get_cache(&state.config)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let params = [("date", format_sstr!("{date}"))];
+    let (pagination, data) =
+        page.paginate_list(hlist, "/garmin/fitbit/heartrate_cache", &params)?;
+    Ok(PaginatedJson(PaginatedFitbitHeartRate { pagination, data }))
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Fitbit Heartrate Update",
+    content = "html",
+    status = "CREATED"
+)]
+struct FitbitHeartrateUpdateResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/fitbit/heartrate_cache")]
+pub async fn fitbit_heartrate_cache_update(
+    payload: Json<FitbitHeartrateUpdateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FitbitHeartrateUpdateResponse> {
+    let dates = payload.into_inner().merge_data(&state.config).await?;
+    Provenance::new(DataProvider::Upload, "/garmin/fitbit/heartrate_cache", None)
+        .record_heartrate_dates(&state.db, &dates)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new(format_sstr!("Finished {dates:?}")).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct FitbitIntradayRequest {
+    #[schema(description = "Local Date")]
+    date: DateType,
+    #[schema(description = "Offset")]
+    offset: Option<usize>,
+    #[schema(description = "Limit")]
+    limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    cursor: Option<StackString>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedIntradayMinute")]
+struct PaginatedIntradayMinute {
+    pagination: Pagination,
+    data: Vec<IntradayMinuteWrapper>,
+}
+
+impl PaginatedBody for PaginatedIntradayMinute {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/fitbit/intraday")]
+#[openapi(description = "Minute by minute heartrate, steps and calories for one day")]
+pub async fn fitbit_intraday(
+    query: Query<FitbitIntradayRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedIntradayMinute>> {
+    let query = query.into_inner();
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let date: Date = query.date.into();
+    fitbit_archive::fetch_archive_files(
+        &state.config,
+        date - Duration::days(1),
+        date + Duration::days(1),
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    let config = state.config.clone();
+    let minutes: Vec<IntradayMinuteWrapper> =
+        spawn_blocking(move || get_intraday_day(&config, date))
+            .await??
+            .into_iter()
+            .map(Into::into)
+            .collect();
+    let params = [("date", format_sstr!("{date}"))];
+    let (pagination, data) = page.paginate_list(minutes, "/garmin/fitbit/intraday", &params)?;
+    Ok(PaginatedJson(PaginatedIntradayMinute { pagination, data }))
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedSyncStatus")]
+struct PaginatedSyncStatus {
+    pagination: Pagination,
+    data: Vec<SyncStatusWrapper>,
+}
+
+impl PaginatedBody for PaginatedSyncStatus {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/sync_status")]
+#[openapi(description = "Last success and failure of each sync job")]
+pub async fn sync_status(
+    query: Query<PageQuery>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedSyncStatus>> {
+    let page = query.into_inner().page()?;
+    let statuses: Vec<SyncStatusWrapper> = SyncStatus::get_all(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let (pagination, data) = page.paginate_list(statuses, "/garmin/sync_status", &[])?;
+    Ok(PaginatedJson(PaginatedSyncStatus { pagination, data }))
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedCityVisit")]
+struct PaginatedCityVisit {
+    pagination: Pagination,
+    data: Vec<CityVisitWrapper>,
+}
+
+impl PaginatedBody for PaginatedCityVisit {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/travel_map")]
+#[openapi(description = "Every city with a geocoded activity start")]
+pub async fn travel_map(
+    query: Query<PageQuery>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedCityVisit>> {
+    let page = query.into_inner().page()?;
+    let cities: Vec<CityVisitWrapper> = CityVisit::get_all(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let (pagination, data) = page.paginate_list(cities, "/garmin/travel_map", &[])?;
+    Ok(PaginatedJson(PaginatedCityVisit { pagination, data }))
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedRegionVisit")]
+struct PaginatedRegionVisit {
+    pagination: Pagination,
+    data: Vec<RegionVisitWrapper>,
+}
+
+impl PaginatedBody for PaginatedRegionVisit {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct RegionMapRequest {
+    #[schema(description = "Aggregate by US state instead of by country")]
+    us_states: Option<bool>,
+    #[schema(description = "Offset")]
+    offset: Option<usize>,
+    #[schema(description = "Limit")]
+    limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    cursor: Option<StackString>,
+}
+
+#[get("/garmin/region_map")]
+#[openapi(description = "Activity counts and first visits by country or US state")]
+pub async fn region_map(
+    query: Query<RegionMapRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedRegionVisit>> {
+    let query = query.into_inner();
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let us_states = query.us_states.unwrap_or(false);
+    let regions = if us_states {
+        RegionVisit::get_us_states(&state.db).await
+    } else {
+        RegionVisit::get_countries(&state.db).await
+    };
+    let regions: Vec<RegionVisitWrapper> = regions
+        .map_err(Into::<Error>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let params = [("us_states", format_sstr!("{us_states}"))];
+    let (pagination, data) = page.paginate_list(regions, "/garmin/region_map", &params)?;
+    Ok(PaginatedJson(PaginatedRegionVisit { pagination, data }))
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedProvenanceEntry")]
+struct PaginatedProvenanceEntry {
+    pagination: Pagination,
+    data: Vec<ProvenanceEntryWrapper>,
+}
+
+impl PaginatedBody for PaginatedProvenanceEntry {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct ProvenanceRequest {
+    #[schema(
+        description = "activity, heartrate_stream, heartrate_batch, strava_activity, \
+                       garmin_connect_activity, scale_measurement or biomarker"
+    )]
+    kind: StackString,
+    #[schema(
+        description = "Activity Filename, Date, Strava or Connect Activity Id, Measurement Id or \
+                       Biomarker Series"
+    )]
+    key: StackString,
+    #[schema(description = "Offset")]
+    offset: Option<usize>,
+    #[schema(description = "Limit")]
+    limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    cursor: Option<StackString>,
+}
+
+#[get("/garmin/provenance")]
+#[openapi(description = "Provider, import path, raw file and import time of records")]
+pub async fn provenance(
+    query: Query<ProvenanceRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedProvenanceEntry>> {
+    let query = query.into_inner();
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let kind: ProvenanceKind = query
+        .kind
+        .parse()
+        .map_err(|e: anyhow::Error| Error::BadRequest(e.to_string()))?;
+    let entries: Vec<ProvenanceEntryWrapper> =
+        ProvenanceEntry::get_entries(&state.db, kind, &query.key)
+            .await
+            .map_err(Into::<Error>::into)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+    let params = [("kind", query.kind), ("key", query.key)];
+    let (pagination, data) = page.paginate_list(entries, "/garmin/provenance", &params)?;
+    Ok(PaginatedJson(PaginatedProvenanceEntry { pagination, data }))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct SyncStatusUpdateRequest {
+    #[schema(description = "Sync Job")]
+    job: StackString,
+    #[schema(description = "Whether the job succeeded")]
+    success: bool,
+    #[schema(description = "Failure Message")]
+    message: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Sync Status Update", status = "CREATED")]
+struct SyncStatusUpdateResponse(JsonBase<Vec<SyncStatusWrapper>, Error>);
+
+#[post("/garmin/sync_status")]
+#[openapi(description = "Record the outcome of a scheduled sync job")]
+pub async fn sync_status_update(
+    query: Query<SyncStatusUpdateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<SyncStatusUpdateResponse> {
+    let query = query.into_inner();
+    if query.success {
+        SyncStatus::record_success(&state.db, &query.job).await
+    } else {
+        let message = query.message.as_ref().map_or("", StackString::as_str);
+        SyncStatus::record_failure(&state.db, &query.job, message).await
+    }
+    .map_err(Into::<Error>::into)?;
+    let mut statuses: Vec<SyncStatusWrapper> = SyncStatus::get_all(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    statuses.shrink_to_fit();
+    Ok(JsonBase::new(statuses).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Fitbit Activities")]
+struct FitbitActivitiesResponse(JsonBase<Vec<FitbitActivityWrapper>, Error>);
+
+#[derive(RwebResponse)]
+#[response(description = "Fitbit Callback", content = "html")]
+struct FitbitCallbackResponse(HtmlBase<StackString, Error>);
+
+#[derive(Serialize, Deserialize, Schema)]
+struct FitbitSyncRequest {
+    date: DateType,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Fitbit Sync", content = "html")]
+struct FitbitSyncResponse(HtmlBase<StackString, Error>);
+
+async fn get_biomarker_overlay(
+    pool: &PgPool,
+    overlay: Option<&str>,
+    start_date: DateType,
+    end_date: DateType,
+) -> HttpResult<BiomarkerOverlay> {
+    let mut series = BiomarkerSeries::read_from_db(pool).await?;
+    series.shrink_to_fit();
+    let selected = overlay.and_then(|name| series.iter().find(|s| s.name == name).cloned());
+    let measurements = if let Some(selected) = &selected {
+        BiomarkerMeasurement::read_from_db(
+            pool,
+            &selected.name,
+            Some(start_date.into()),
+            Some(end_date.into()),
+        )
+        .await?
+    } else {
+        Vec::new()
+    };
+    Ok(BiomarkerOverlay {
+        series,
+        selected,
+        measurements,
+    })
+}
+
+async fn get_wellness(
+    pool: &PgPool,
+    start_date: DateType,
+    end_date: DateType,
+) -> HttpResult<WellnessOpts> {
+    let start_date = Some(start_date.into());
+    let end_date = Some(end_date.into());
+    let spo2 =
+        BiomarkerMeasurement::daily_averages(pool, SPO2_SERIES, start_date, end_date).await?;
+    let systolic =
+        BiomarkerMeasurement::daily_averages(pool, SYSTOLIC_SERIES, start_date, end_date).await?;
+    let diastolic =
+        BiomarkerMeasurement::daily_averages(pool, DIASTOLIC_SERIES, start_date, end_date).await?;
+    let sleep_respiration =
+        BiomarkerMeasurement::daily_averages(pool, SLEEP_RESPIRATION_SERIES, start_date, end_date)
+            .await?;
+    let mut sleep_hours =
+        BiomarkerMeasurement::daily_averages(pool, SLEEP_MINUTES_SERIES, start_date, end_date)
+            .await?;
+    for (_, value) in &mut sleep_hours {
+        *value /= 60.0;
+    }
+    Ok(WellnessOpts {
+        spo2,
+        systolic,
+        diastolic,
+        sleep_respiration,
+        sleep_hours,
+    })
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Fitbit Heartrate Statistics Plots", content = "html")]
+struct FitbitStatisticsPlotResponse(HtmlBase<StackString, Error>);
+
+#[get("/garmin/fitbit/heartrate_statistics_plots")]
+pub async fn heartrate_statistics_plots(
+    query: Query<ScaleMeasurementRequest>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FitbitStatisticsPlotResponse> {
+    let query: FitbitStatisticsPlotRequest = query.into_inner().into();
+    let session = user
+        .get_session(&state.client, &state.config, &state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let mut stats: Vec<FitbitStatisticsSummary> = FitbitStatisticsSummary::read_from_db(
+        &state.db,
+        Some(query.start_date.into()),
+        Some(query.end_date.into()),
+        None,
+        None,
+    )
+    .await
+    .map_err(Into::<Error>::into)?
+    .try_collect()
+    .await
+    .map_err(Into::<Error>::into)?;
+    stats.shrink_to_fit();
+    let overlay = get_biomarker_overlay(
+        &state.db,
+        query.overlay.as_deref(),
+        query.start_date,
+        query.end_date,
+    )
+    .await?;
+    let wellness = get_wellness(&state.db, query.start_date, query.end_date).await?;
+    let body = index_new_body(
+        &state.config,
+        &state.db,
+        "".into(),
+        false,
+        session,
+        IndexConfig::HearRateSummary {
+            stats,
+            offset: Some(query.offset),
+            start_date: Some(query.start_date),
+            end_date: Some(query.end_date),
+            overlay,
+            wellness,
+        },
+    )
+    .await?
+    .into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[get("/garmin/fitbit/heartrate_statistics_plots_demo")]
+pub async fn heartrate_statistics_plots_demo(
+    query: Query<ScaleMeasurementRequest>,
+    #[data] state: AppState,
+    #[filter = "optional_session"] session: Option<Session>,
+) -> WarpResult<FitbitStatisticsPlotResponse> {
+    let anonymizer = DemoAnonymizer::from_config(&state.config);
+    let mut query = query.into_inner();
+    if let Some(anonymizer) = &anonymizer {
+        query = query.unshift_demo(anonymizer);
+    }
+    let mut query: FitbitStatisticsPlotRequest = query.into();
+    query.is_demo = true;
+    let session = session.unwrap_or_default();
+
+    let mut stats: Vec<FitbitStatisticsSummary> = FitbitStatisticsSummary::read_from_db(
+        &state.db,
+        Some(query.start_date.into()),
+        Some(query.end_date.into()),
+        None,
+        None,
+    )
+    .await
+    .map_err(Into::<Error>::into)?
+    .try_collect()
+    .await
+    .map_err(Into::<Error>::into)?;
+    stats.shrink_to_fit();
+    if let Some(anonymizer) = &anonymizer {
+        for stat in &mut stats {
+            stat.date = anonymizer.shift_date(stat.date);
+        }
+        query.start_date = anonymizer.shift_date(query.start_date.into()).into();
+        query.end_date = anonymizer.shift_date(query.end_date.into()).into();
+    }
+    // biomarkers and wellness series are personal health data, the public
+    // demo pages never show them
+    let overlay = BiomarkerOverlay::default();
+    let wellness = WellnessOpts::default();
+    let body = index_new_body(
+        &state.config,
+        &state.db,
+        "".into(),
+        true,
+        session,
+        IndexConfig::HearRateSummary {
+            stats,
+            offset: Some(query.offset),
+            start_date: Some(query.start_date),
+            end_date: Some(query.end_date),
+            overlay,
+            wellness,
+        },
+    )
+    .await?
+    .into();
+
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Scale Measurement Plots", content = "html")]
+struct ScaleMeasurementResponse(HtmlBase<StackString, Error>);
+
+#[get("/garmin/fitbit/plots")]
+pub async fn fitbit_plots(
+    query: Query<ScaleMeasurementRequest>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<ScaleMeasurementResponse> {
+    let session = user
+        .get_session(&state.client, &state.config, &state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let query: ScaleMeasurementPlotRequest = query.into_inner().into();
+
+    let measurements = ScaleMeasurement::read_from_db(
+        &state.db,
+        Some(query.start_date.into()),
+        Some(query.end_date.into()),
+        None,
+        None,
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+
+    let overlay = get_biomarker_overlay(
+        &state.db,
+        query.overlay.as_deref(),
+        query.start_date,
+        query.end_date,
+    )
+    .await?;
+    let body = index_new_body(
+        &state.config,
+        &state.db,
+        "".into(),
+        false,
+        session,
+        IndexConfig::Scale {
+            measurements,
+            offset: query.offset,
+            start_date: query.start_date,
+            end_date: query.end_date,
+            overlay,
+        },
+    )
+    .await?
+    .into();
+
+    Ok(HtmlBase::new(body).into())
+}
+
+#[get("/garmin/fitbit/plots_demo")]
+pub async fn fitbit_plots_demo(
+    query: Query<ScaleMeasurementRequest>,
+    #[data] state: AppState,
+    #[filter = "optional_session"] session: Option<Session>,
+) -> WarpResult<ScaleMeasurementResponse> {
+    let session = session.unwrap_or_default();
+    let anonymizer = DemoAnonymizer::from_config(&state.config);
+    let mut query = query.into_inner();
+    if let Some(anonymizer) = &anonymizer {
+        query = query.unshift_demo(anonymizer);
+    }
+    let mut query: ScaleMeasurementPlotRequest = query.into();
+    query.is_demo = true;
+
+    let mut measurements = ScaleMeasurement::read_from_db(
+        &state.db,
+        Some(query.start_date.into()),
+        Some(query.end_date.into()),
+        None,
+        None,
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    if let Some(anonymizer) = &anonymizer {
+        for measurement in &mut measurements {
+            measurement.datetime = anonymizer
+                .shift_datetime(measurement.datetime.into())
+                .into();
+        }
+        query.start_date = anonymizer.shift_date(query.start_date.into()).into();
+        query.end_date = anonymizer.shift_date(query.end_date.into()).into();
+    }
+
+    // biomarkers are personal health data, the public demo pages never show them
+    let overlay = BiomarkerOverlay::default();
+    let body = index_new_body(
+        &state.config,
+        &state.db,
+        "".into(),
+        true,
+        session,
+        IndexConfig::Scale {
+            measurements,
+            offset: query.offset,
+            start_date: query.start_date,
+            end_date: query.end_date,
+            overlay,
+        },
+    )
+    .await?
+    .into();
+
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Fitbit Heartrate Plots", content = "html")]
+struct FitbitHeartratePlotResponse(HtmlBase<StackString, Error>);
+
+#[get("/garmin/fitbit/heartrate_plots")]
+pub async fn heartrate_plots(
+    query: Query<ScaleMeasurementRequest>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FitbitHeartratePlotResponse> {
+    let query: FitbitHeartratePlotRequest = query.into_inner().into();
+    let session = user
+        .get_session(&state.client, &state.config, &state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+
+    fitbit_archive::fetch_archive_files(
+        &state.config,
+        query.start_date.into(),
+        query.end_date.into(),
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    let parquet_values = fitbit_archive::get_number_of_heartrate_values(
+        &state.config,
+        query.start_date.into(),
+        query.end_date.into(),
+    )
+    .map_err(Into::<Error>::into)?;
+    let step_size = if parquet_values < 40_000 {
+        1
+    } else {
+        parquet_values / 40_000
+    };
+    debug!("parquet_values {parquet_values} step size {step_size}");
+
+    let heartrate = if parquet_values == 0 {
+        FitbitHeartRate::get_heartrate_values(
+            &state.config,
+            &state.db,
+            query.start_date.into(),
+            query.end_date.into(),
+        )
+        .await
+    } else {
+        let config = state.config.clone();
+        spawn_blocking(move || {
+            fitbit_archive::get_heartrate_values(
+                &config,
+                query.start_date.into(),
+                query.end_date.into(),
+                Some(step_size),
+            )
+        })
+        .await
+        .map_err(Into::<Error>::into)?
+    }
+    .map_err(Into::<Error>::into)?;
+
+    let body = index_new_body(
+        &state.config,
+        &state.db,
+        "".into(),
+        false,
+        session,
+        IndexConfig::HeartRate {
+            heartrate,
+            start_date: query.start_date,
+            end_date: query.end_date,
+            button_date: query.button_date,
+        },
+    )
+    .await
+    .map_err(Into::<Error>::into)?
+    .into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[get("/garmin/fitbit/heartrate_plots_demo")]
+pub async fn heartrate_plots_demo(
+    query: Query<ScaleMeasurementRequest>,
+    #[data] state: AppState,
+    #[filter = "optional_session"] session: Option<Session>,
+) -> WarpResult<FitbitHeartratePlotResponse> {
+    let anonymizer = DemoAnonymizer::from_config(&state.config);
+    let mut query = query.into_inner();
+    if let Some(anonymizer) = &anonymizer {
+        query = query.unshift_demo(anonymizer);
+    }
+    let mut query: FitbitHeartratePlotRequest = query.into();
+    query.is_demo = true;
+    let session = session.unwrap_or_default();
+
+    let mut heartrate = FitbitHeartRate::get_heartrate_values(
+        &state.config,
+        &state.db,
+        query.start_date.into(),
+        query.end_date.into(),
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    if let Some(anonymizer) = &anonymizer {
+        for (datetime, _) in &mut heartrate {
+            *datetime = anonymizer.shift_datetime((*datetime).into()).into();
+        }
+        query.start_date = anonymizer.shift_date(query.start_date.into()).into();
+        query.end_date = anonymizer.shift_date(query.end_date.into()).into();
+        query.button_date = query
+            .button_date
+            .map(|d| anonymizer.shift_date(d.into()).into());
+    }
+    let body = index_new_body(
+        &state.config,
+        &state.db,
+        "".into(),
+        true,
+        session,
+        IndexConfig::HeartRate {
+            heartrate,
+            start_date: query.start_date,
+            end_date: query.end_date,
+            button_date: query.button_date,
+        },
+    )
+    .await
+    .map_err(Into::<Error>::into)?
+    .into();
+
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Scale Measurement Duplicates", content = "html")]
+struct ScaleDuplicatesResponse(HtmlBase<StackString, Error>);
+
+#[derive(Serialize, Deserialize, Schema)]
+struct ScaleDuplicatesRequest {
+    #[schema(description = "Time tolerance in minutes")]
+    minutes: Option<u32>,
+    #[schema(description = "Mass tolerance in lbs")]
+    mass: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct ScaleDuplicateMergeRequest {
+    #[schema(description = "Measurement to keep, the rest of its group is deleted")]
+    keep: UuidWrapper,
+    #[schema(description = "Time tolerance in minutes")]
+    minutes: Option<u32>,
+    #[schema(description = "Mass tolerance in lbs")]
+    mass: Option<f64>,
+}
+
+async fn get_scale_duplicates(
+    state: &AppState,
+    minutes: Option<u32>,
+    mass: Option<f64>,
+) -> Result<Vec<Vec<ScaleMeasurement>>, Error> {
+    let time_tolerance = Duration::minutes(
+        minutes
+            .unwrap_or(state.config.scale_duplicate_minutes)
+            .into(),
+    );
+    let mass_tolerance = mass.unwrap_or(state.config.scale_duplicate_lbs);
+    let measurements = ScaleMeasurement::read_from_db(&state.db, None, None, None, None).await?;
+    Ok(ScaleMeasurement::find_duplicates(
+        &measurements,
+        time_tolerance,
+        mass_tolerance,
+    ))
+}
+
+#[get("/garmin/scale_measurements/duplicates")]
+pub async fn scale_measurement_duplicates(
+    query: Query<ScaleDuplicatesRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<ScaleDuplicatesResponse> {
+    let query = query.into_inner();
+    let groups = get_scale_duplicates(&state, query.minutes, query.mass).await?;
+    let body = scale_duplicates_body(None, groups)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[post("/garmin/scale_measurements/duplicates/merge")]
+pub async fn scale_measurement_duplicates_merge(
+    query: Query<ScaleDuplicateMergeRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<ScaleDuplicatesResponse> {
+    let query = query.into_inner();
+    let keep = query.keep.into();
+    let groups = get_scale_duplicates(&state, query.minutes, query.mass).await?;
+    let group = groups
+        .iter()
+        .find(|group| group.iter().any(|m| m.id == keep))
+        .ok_or_else(|| Error::BadRequest(format!("{keep} has no duplicates")))?;
+    let deleted = ScaleMeasurement::merge_duplicates(group, keep, &state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let message = format_sstr!("kept {keep}, deleted {deleted}");
+    let groups = get_scale_duplicates(&state, query.minutes, query.mass).await?;
+    let body = scale_duplicates_body(Some(message), groups)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[get("/garmin/scale_measurements/connect_export.fit")]
+#[openapi(
+    description = "FIT Weight File of the Oldest Scale Measurements not yet in Garmin Connect, \
+                   each call moves on to the next batch"
+)]
+pub async fn scale_measurement_connect_export(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FileDownload> {
+    let measurements =
+        ScaleMeasurement::read_unlinked_connect(&state.db, Some(CONNECT_EXPORT_BATCH))
+            .await
+            .map_err(Into::<Error>::into)?;
+    if measurements.is_empty() {
+        return Err(Error::BadRequest("Every measurement is already in connect".into()).into());
+    }
+    let fit = ScaleMeasurement::to_connect_fit(&measurements);
+    ScaleMeasurement::mark_connect_exported(&measurements, &state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(FileDownload::fit(fit, "connect_weights.fit"))
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Fitbit Tcx Sync")]
+struct FitbitTcxSyncResponse(JsonBase<Vec<String>, Error>);
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedScaleMeasurement")]
+struct PaginatedScaleMeasurement {
+    pagination: Pagination,
+    data: Vec<ScaleMeasurementWithMetricsWrapper>,
+}
+
+impl PaginatedBody for PaginatedScaleMeasurement {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/scale_measurements")]
+pub async fn scale_measurement(
+    query: Query<ScaleMeasurementRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedScaleMeasurement>> {
+    let query = query.into_inner();
+
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let start_date = query.start_date.map(Into::into);
+    let end_date = query.end_date.map(Into::into);
+
+    let total = ScaleMeasurement::get_total(&state.db, start_date, end_date)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let mut rows: Vec<ScaleMeasurement> = ScaleMeasurement::read_from_db(
+        &state.db,
+        start_date,
+        end_date,
+        Some(page.start),
+        Some(page.limit),
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    rows.shrink_to_fit();
+    let pagination = page.paginate(
+        total,
+        &rows,
+        "/garmin/scale_measurements",
+        &date_params(start_date, end_date),
+    );
+    let data = rows
+        .into_iter()
+        .map(|meas| ScaleMeasurementWithMetrics::new(meas, &state.config).into())
+        .collect();
+
+    Ok(PaginatedJson(PaginatedScaleMeasurement {
+        pagination,
+        data,
+    }))
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Scale Measurements Update",
+    content = "html",
+    status = "CREATED"
+)]
+struct ScaleMeasurementsUpdateResponse(HtmlBase<&'static str, Error>);
+
+#[post("/garmin/scale_measurements")]
+pub async fn scale_measurement_update(
+    measurements: Json<ScaleMeasurementUpdateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<ScaleMeasurementsUpdateResponse> {
+    let measurements = measurements.into_inner();
+    let mut measurements: Vec<_> = measurements
+        .measurements
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    measurements.shrink_to_fit();
+    let provenance = Provenance::new(DataProvider::Upload, "/garmin/scale_measurements", None);
+    ScaleMeasurement::merge_updates(&mut measurements, &state.db, &provenance)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(HtmlBase::new("Finished").into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+#[schema(component = "ScaleImportSkipped")]
+struct ScaleImportSkippedWrapper {
+    #[schema(description = "Position of the Entry in the Import")]
+    index: usize,
+    #[schema(description = "Why the Entry wasn't Inserted")]
+    reason: StackString,
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+#[schema(component = "ScaleImportReport")]
+struct ScaleImportReportWrapper {
+    #[schema(description = "Number of Measurements Inserted")]
+    inserted: usize,
+    #[schema(description = "Invalid and Duplicate Entries")]
+    skipped: Vec<ScaleImportSkippedWrapper>,
+}
+
+impl From<ScaleImportReport> for ScaleImportReportWrapper {
+    fn from(report: ScaleImportReport) -> Self {
+        Self {
+            inserted: report.inserted,
+            skipped: report
+                .skipped
+                .into_iter()
+                .map(|s| ScaleImportSkippedWrapper {
+                    index: s.index,
+                    reason: s.reason,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Scale Measurements Bulk Import", status = "CREATED")]
+struct ScaleMeasurementBulkResponse(JsonBase<ScaleImportReportWrapper, Error>);
+
+#[post("/garmin/scale_measurements/bulk")]
+#[openapi(
+    description = "Import Historical Scale Measurements from a Json Array or Newline Delimited \
+                   Json, skipping Invalid Entries and Duplicates of Existing Measurements"
+)]
+pub async fn scale_measurement_bulk(
+    #[body] body: Bytes,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<ScaleMeasurementBulkResponse> {
+    let entries = ScaleMeasurementImport::parse_bulk(&body)
+        .map_err(|e| Error::BadRequest(format!("Invalid import: {e}")))?;
+    let provenance = Provenance::new(
+        DataProvider::Upload,
+        "/garmin/scale_measurements/bulk",
+        None,
+    );
+    let report = ScaleMeasurement::bulk_import(
+        &entries,
+        &state.db,
+        Duration::minutes(state.config.scale_duplicate_minutes.into()),
+        state.config.scale_duplicate_lbs,
+        &provenance,
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    Ok(JsonBase::new(report.into()).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "BiomarkerRequest")]
+struct BiomarkerRequest {
+    #[schema(description = "Series Name", example = r#""hrv""#)]
+    series: StackString,
+    #[schema(
+        description = "Series Units, updates an existing series",
+        example = r#""ms""#
+    )]
+    units: Option<StackString>,
+    #[schema(description = "Single Value")]
+    value: Option<f64>,
+    #[schema(description = "Timestamp (rfc3339) or date of value, default now")]
+    datetime: Option<StackString>,
+    #[schema(description = "Csv text of datetime,value lines")]
+    csv: Option<StackString>,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Biomarker Post", content = "html", status = "CREATED")]
+struct BiomarkerResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/biomarker")]
+pub async fn biomarker_update(
+    payload: Json<BiomarkerRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<BiomarkerResponse> {
+    let payload = payload.into_inner();
+    let body = biomarker_update_body(payload, &state.db).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+async fn biomarker_update_body(
+    payload: BiomarkerRequest,
+    pool: &PgPool,
+) -> HttpResult<StackString> {
+    let series = match (
+        BiomarkerSeries::get_by_name(pool, &payload.series).await?,
+        payload.units,
+    ) {
+        (Some(existing), None) => existing,
+        (Some(mut existing), Some(units)) => {
+            existing.units = units;
+            existing.upsert_db(pool).await?;
+            existing
+        }
+        (None, units) => {
+            let series = BiomarkerSeries::new(
+                &payload.series,
+                units.as_ref().map_or("", StackString::as_str),
+            );
+            series
+                .upsert_db(pool)
+                .await
+                .map_err(|e| Error::BadRequest(e.to_string()))?;
+            series
+        }
+    };
+    let mut measurements = match &payload.csv {
+        Some(csv) => BiomarkerMeasurement::from_csv(&series.name, csv)
+            .map_err(|e| Error::BadRequest(e.to_string()))?,
+        None => Vec::new(),
+    };
+    if let Some(value) = payload.value {
+        let datetime = match &payload.datetime {
+            Some(d) => parse_datetime(d).map_err(|e| Error::BadRequest(e.to_string()))?,
+            None => OffsetDateTime::now_utc(),
+        };
+        measurements.push(BiomarkerMeasurement {
+            series: series.name.clone(),
+            datetime: datetime.into(),
+            value,
+        });
+    }
+    let provenance = Provenance::new(
+        if payload.csv.is_some() {
+            DataProvider::Upload
+        } else {
+            DataProvider::Manual
+        },
+        "/garmin/biomarker",
+        None,
+    );
+    let count = BiomarkerMeasurement::merge_updates(&measurements, pool, &provenance).await?;
+    Ok(format_sstr!("{} {count}", series.name))
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "ScaleMeasurementManualRequest")]
+struct ScaleMeasurementManualRequest {
+    #[schema(description = "Weight in lbs", example = r#""189.0""#)]
+    weight_in_lbs: f64,
+    #[schema(description = "Body fat percent", example = r#""20.3""#)]
+    body_fat_percent: f64,
+    #[schema(description = "Muscle mass in lbs", example = r#""153.0""#)]
+    muscle_mass_lbs: f64,
+    #[schema(description = "Body water percent", example = r#""63.0""#)]
+    body_water_percent: f64,
+    #[schema(description = "Bone mass in lbs", example = r#""63.0""#)]
+    bone_mass_lbs: f64,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Scale Measurement Manual Input Post",
+    status = "CREATED"
+)]
+struct ScaleMeasurementManualResponse(JsonBase<ScaleMeasurementWrapper, Error>);
+
+#[post("/garmin/scale_measurements/manual")]
+pub async fn scale_measurement_manual(
+    payload: Json<ScaleMeasurementManualRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<ScaleMeasurementManualResponse> {
+    let payload = payload.into_inner();
+    let mut measurement = ScaleMeasurement::from_fit_plus(
+        payload.weight_in_lbs,
+        payload.body_fat_percent,
+        payload.muscle_mass_lbs,
+        payload.body_water_percent,
+        payload.bone_mass_lbs,
+    )
+    .map_err(Into::<Error>::into)?;
+    let provenance = Provenance::new(
+        DataProvider::Manual,
+        "/garmin/scale_measurements/manual",
+        None,
+    );
+    measurement
+        .insert_into_db_with_provenance(&state.db, &provenance)
+        .await
+        .map_err(Into::<Error>::into)?;
+    Ok(JsonBase::new(measurement.into()).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Scale Measurement Manual Input")]
+struct ScaleMeasurementManualInputResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/scale_measurements/manual/input")]
+pub async fn scale_measurement_manual_input(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+) -> WarpResult<ScaleMeasurementManualInputResponse> {
+    let body = scale_measurement_manual_input_body()?;
+    Ok(HtmlBase::new(body.into()).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Logged in User")]
+struct UserResponse(JsonBase<LoggedUser, Error>);
+
+#[allow(clippy::unused_async)]
+#[get("/garmin/user")]
+pub async fn user(#[filter = "LoggedUser::filter"] user: LoggedUser) -> WarpResult<UserResponse> {
+    Ok(JsonBase::new(user).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedFilterHistory")]
+struct PaginatedFilterHistory {
+    pagination: Pagination,
+    data: Vec<FilterHistoryWrapper>,
+}
+
+impl PaginatedBody for PaginatedFilterHistory {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/history")]
+#[openapi(description = "Filter history of the logged in user, pinned entries first")]
+pub async fn filter_history(
+    query: Query<PageQuery>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedFilterHistory>> {
+    let page = query.into_inner().page()?;
+    let history: Vec<FilterHistoryWrapper> = FilterHistory::get_by_email(&state.db, &user.email)
+        .await
+        .map_err(Into::<Error>::into)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let (pagination, data) = page.paginate_list(history, "/garmin/history", &[])?;
+    Ok(PaginatedJson(PaginatedFilterHistory { pagination, data }))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct FilterHistoryPinRequest {
+    #[schema(description = "Filter")]
+    filter: StackString,
+    #[schema(description = "Pin (true) or unpin (false) the filter")]
+    pinned: bool,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Filter History Update", content = "html")]
+struct FilterHistoryUpdateResponse(HtmlBase<&'static str, Error>);
+
+#[post("/garmin/history/pin")]
+#[openapi(description = "Pin or unpin a filter history entry")]
+pub async fn filter_history_pin(
+    payload: Json<FilterHistoryPinRequest>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FilterHistoryUpdateResponse> {
+    let payload = payload.into_inner();
+    if FilterHistory::set_pinned(&state.db, &user.email, &payload.filter, payload.pinned)
+        .await
+        .map_err(Into::<Error>::into)?
+    {
+        Ok(HtmlBase::new("Finished").into())
+    } else {
+        Err(Error::BadRequest(format!("No history entry {}", payload.filter)).into())
+    }
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct FilterHistoryDeleteRequest {
+    #[schema(description = "Filter")]
+    filter: StackString,
+}
+
+#[delete("/garmin/history")]
+#[openapi(description = "Delete a filter history entry")]
+pub async fn filter_history_delete(
+    query: Query<FilterHistoryDeleteRequest>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FilterHistoryUpdateResponse> {
+    let query = query.into_inner();
+    if FilterHistory::delete(&state.db, &user.email, &query.filter)
+        .await
+        .map_err(Into::<Error>::into)?
+    {
+        Ok(HtmlBase::new("Finished").into())
+    } else {
+        Err(Error::BadRequest(format!("No history entry {}", query.filter)).into())
+    }
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Add correction", content = "html", status = "CREATED")]
+struct AddGarminCorrectionResponse(HtmlBase<&'static str, Error>);
+
+#[post("/garmin/add_garmin_correction")]
+pub async fn add_garmin_correction(
+    payload: Json<AddGarminCorrectionRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<AddGarminCorrectionResponse> {
+    payload.into_inner().add_corrections(&state.db).await?;
+    Ok(HtmlBase::new("finised").into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Fitbit Activity Types")]
+struct FitbitActivityTypesResponse(JsonBase<FitbitActivityTypesWrapper, Error>);
+
+#[derive(RwebResponse)]
+#[response(description = "Strava Athlete")]
+struct StravaAthleteResponse(HtmlBase<StackString, Error>);
+
+#[get("/garmin/strava/athlete")]
+pub async fn strava_athlete(
+    query: Query<StravaAccountRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StravaAthleteResponse> {
+    require_strava(&state.config)?;
+    let query = query.into_inner();
+    let client = StravaClient::with_auth_account(state.config, query.account())
+        .await
+        .map_err(Into::<Error>::into)?;
+    let result = client
+        .get_strava_athlete()
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = strava_body(result)?.into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Fitbit Profile")]
+struct FitbitProfileResponse(HtmlBase<StackString, Error>);
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedGarminConnectActivity")]
+struct PaginatedGarminConnectActivity {
+    pagination: Pagination,
+    data: Vec<GarminConnectActivityWrapper>,
+}
+
+impl PaginatedBody for PaginatedGarminConnectActivity {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/garmin_connect_activities_db")]
+pub async fn garmin_connect_activities_db(
+    query: Query<StravaActivitiesRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedGarminConnectActivity>> {
+    let query = query.into_inner();
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let start_date = query.start_date.map(Into::into);
+    let end_date = query.end_date.map(Into::into);
+
+    let total = GarminConnectActivity::get_total(&state.db, start_date, end_date)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let mut rows: Vec<GarminConnectActivity> = GarminConnectActivity::read_from_db(
+        &state.db,
+        start_date,
+        end_date,
+        Some(page.start),
+        Some(page.limit),
+    )
+    .await
+    .map_err(Into::<Error>::into)?
+    .try_collect()
+    .await
+    .map_err(Into::<Error>::into)?;
+    rows.shrink_to_fit();
+    let pagination = page.paginate(
+        total,
+        &rows,
+        "/garmin/garmin_connect_activities_db",
+        &date_params(start_date, end_date),
+    );
+    let data = rows.into_iter().map(Into::into).collect();
+
+    Ok(PaginatedJson(PaginatedGarminConnectActivity {
+        pagination,
+        data,
+    }))
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Garmin Connect Activities",
+    content = "html",
+    status = "CREATED"
+)]
+struct GarminConnectActivitiesUpdateResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/garmin_connect_activities_db")]
+pub async fn garmin_connect_activities_db_update(
+    payload: Json<GarminConnectActivitiesDBUpdateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<GarminConnectActivitiesUpdateResponse> {
+    let payload = payload.into_inner();
+    let mut updates: Vec<_> = payload.updates.into_iter().map(Into::into).collect();
+    updates.shrink_to_fit();
+    let provenance = Provenance::new(
+        DataProvider::Upload,
+        "/garmin/garmin_connect_activities_db",
+        None,
+    );
+    let body: StackString =
+        GarminConnectActivity::upsert_activities(&updates, &state.db, &provenance)
+            .await
+            .map_err(Into::<Error>::into)?
+            .join("\n")
+            .into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedFitbitActivity")]
+struct PaginatedFitbitActivity {
+    pagination: Pagination,
+    data: Vec<FitbitActivityWrapper>,
+}
+
+impl PaginatedBody for PaginatedFitbitActivity {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/fitbit/fitbit_activities_db")]
+pub async fn fitbit_activities_db(
+    query: Query<StravaActivitiesRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedFitbitActivity>> {
+    let query = query.into_inner();
+
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let start_date = query.start_date.map(Into::into);
+    let end_date = query.end_date.map(Into::into);
+
+    let total = FitbitActivity::get_total(&state.db, start_date, end_date)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let mut rows: Vec<FitbitActivity> = FitbitActivity::read_from_db(
+        &state.db,
+        start_date,
+        end_date,
+        Some(page.start),
+        Some(page.limit),
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    rows.shrink_to_fit();
+    let pagination = page.paginate(
+        total,
+        &rows,
+        "/garmin/fitbit/fitbit_activities_db",
+        &date_params(start_date, end_date),
+    );
+    let data = rows.into_iter().map(Into::into).collect();
+
+    Ok(PaginatedJson(PaginatedFitbitActivity { pagination, data }))
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+struct FitbitActivitiesDBUpdateRequest {
+    updates: Vec<FitbitActivityWrapper>,
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Fitbit Activities Update",
+    content = "html",
+    status = "CREATED"
+)]
+struct FitbitActivitiesDBUpdateResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/fitbit/fitbit_activities_db")]
+pub async fn fitbit_activities_db_update(
+    payload: Json<FitbitActivitiesDBUpdateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<FitbitActivitiesDBUpdateResponse> {
+    let payload = payload.into_inner();
+    let mut updates: Vec<_> = payload.updates.into_iter().map(Into::into).collect();
+    updates.shrink_to_fit();
+    let body = FitbitActivity::upsert_activities(&updates, &state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    FitbitActivity::fix_summary_id_in_db(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+
+    let body = body.join("\n").into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedFitbitStatisticsSummary")]
+struct PaginatedFitbitStatisticsSummary {
+    pagination: Pagination,
+    data: Vec<FitbitStatisticsSummaryWrapper>,
+}
+
+impl PaginatedBody for PaginatedFitbitStatisticsSummary {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
+
+#[get("/garmin/fitbit/heartrate_statistics_summary_db")]
+pub async fn heartrate_statistics_summary_db(
+    query: Query<StravaActivitiesRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PaginatedJson<PaginatedFitbitStatisticsSummary>> {
+    let query = query.into_inner();
+
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
+    let start_date = query.start_date.map(Into::into);
+    let end_date = query.end_date.map(Into::into);
+
+    let total = FitbitStatisticsSummary::get_total(&state.db, start_date, end_date)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let mut rows: Vec<FitbitStatisticsSummary> = FitbitStatisticsSummary::read_from_db(
+        &state.db,
+        start_date,
+        end_date,
+        Some(page.start),
+        Some(page.limit),
+    )
+    .await
+    .map_err(Into::<Error>::into)?
+    .try_collect()
+    .await
+    .map_err(Into::<Error>::into)?;
+    rows.shrink_to_fit();
+    let pagination = page.paginate(
+        total,
+        &rows,
+        "/garmin/fitbit/heartrate_statistics_summary_db",
+        &date_params(start_date, end_date),
+    );
+    let data = rows.into_iter().map(Into::into).collect();
+
+    Ok(PaginatedJson(PaginatedFitbitStatisticsSummary {
+        pagination,
+        data,
+    }))
+}
+
+#[derive(RwebResponse)]
+#[response(
+    description = "Heartrate Statistics Update",
+    content = "html",
+    status = "CREATED"
+)]
+struct HeartrateStatisticsUpdateResponse(HtmlBase<StackString, Error>);
+
+#[post("/garmin/fitbit/heartrate_statistics_summary_db")]
+pub async fn heartrate_statistics_summary_db_update(
+    payload: Json<HeartrateStatisticsSummaryDBUpdateRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<HeartrateStatisticsUpdateResponse> {
+    let body = payload
+        .into_inner()
+        .process_updates(&state.db)
+        .await?
+        .join("\n")
+        .into();
+    Ok(HtmlBase::new(body).into())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+#[schema(component = "RaceResultPlotRequest")]
+struct RaceResultPlotRequest {
+    #[schema(description = "Race Type")]
+    race_type: RaceTypeWrapper,
+    #[schema(description = "Demo Flag")]
+    demo: Option<bool>,
+}
+
+async fn race_result_plot_impl(
+    req: RaceResultPlotRequest,
+    state: AppState,
+    session: Session,
+) -> Result<StackString, Error> {
+    let mut model = RaceResultAnalysis::run_analysis(req.race_type.into(), &state.db).await?;
+    let demo = req.demo.unwrap_or(true);
+    if demo && DemoAnonymizer::from_config(&state.config).is_some() {
+        for result in &mut model.data {
+            result.race_name = None;
+            result.bib_number = None;
+            result.race_report = None;
+        }
+    }
 
-    let mut stats: Vec<FitbitStatisticsSummary> = FitbitStatisticsSummary::read_from_db(
-        &state.db,
-        Some(query.start_date.into()),
-        Some(query.end_date.into()),
-        None,
-        None,
-    )
-    .await
-    .map_err(Into::<Error>::into)?
-    .try_collect()
-    .await
-    .map_err(Into::<Error>::into)?;
-    stats.shrink_to_fit();
     let body = index_new_body(
         &state.config,
         &state.db,
         "".into(),
-        true,
-        session.history,
-        IndexConfig::HearRateSummary {
-            stats,
-            offset: Some(query.offset),
-            start_date: Some(query.start_date),
-            end_date: Some(query.end_date),
-        },
+        demo,
+        session,
+        IndexConfig::RaceResult { model },
     )
     .await?
     .into();
-
-    Ok(HtmlBase::new(body).into())
+    Ok(body)
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Scale Measurement Plots", content = "html")]
-struct ScaleMeasurementResponse(HtmlBase<StackString, Error>);
+#[response(description = "Race Result Plot", content = "html")]
+struct RaceResultPlotResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/fitbit/plots")]
-pub async fn fitbit_plots(
-    query: Query<ScaleMeasurementRequest>,
+#[get("/garmin/race_result_plot")]
+pub async fn race_result_plot(
+    query: Query<RaceResultPlotRequest>,
     #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<ScaleMeasurementResponse> {
+) -> WarpResult<RaceResultPlotResponse> {
+    let mut query = query.into_inner();
+    query.demo = Some(false);
     let session = user
-        .get_session(&state.client, &state.config)
+        .get_session(&state.client, &state.config, &state.db)
         .await
         .map_err(Into::<Error>::into)?;
-    let query: ScaleMeasurementPlotRequest = query.into_inner().into();
-
-    let measurements = ScaleMeasurement::read_from_db(
-        &state.db,
-        Some(query.start_date.into()),
-        Some(query.end_date.into()),
-        None,
-        None,
-    )
-    .await
-    .map_err(Into::<Error>::into)?;
-
-    let body = index_new_body(
-        &state.config,
-        &state.db,
-        "".into(),
-        false,
-        session.history,
-        IndexConfig::Scale {
-            measurements,
-            offset: query.offset,
-            start_date: query.start_date,
-            end_date: query.end_date,
-        },
-    )
-    .await?
-    .into();
-
+    let body = race_result_plot_impl(query, state, session).await?;
     Ok(HtmlBase::new(body).into())
 }
 
-#[get("/garmin/fitbit/plots_demo")]
-pub async fn fitbit_plots_demo(
-    query: Query<ScaleMeasurementRequest>,
+#[get("/garmin/race_result_plot_demo")]
+pub async fn race_result_plot_demo(
+    query: Query<RaceResultPlotRequest>,
     #[data] state: AppState,
     #[filter = "optional_session"] session: Option<Session>,
-) -> WarpResult<ScaleMeasurementResponse> {
+) -> WarpResult<RaceResultPlotResponse> {
+    let mut query = query.into_inner();
+    query.demo = Some(true);
     let session = session.unwrap_or_default();
-    let mut query: ScaleMeasurementPlotRequest = query.into_inner().into();
-    query.is_demo = true;
+    let body = race_result_plot_impl(query, state, session).await?;
+    Ok(HtmlBase::new(body).into())
+}
 
-    let measurements = ScaleMeasurement::read_from_db(
-        &state.db,
-        Some(query.start_date.into()),
-        Some(query.end_date.into()),
-        None,
-        None,
-    )
-    .await
-    .map_err(Into::<Error>::into)?;
+#[derive(Serialize, Deserialize, Schema)]
+#[schema(component = "PowerCurveRequest")]
+struct PowerCurveRequest {
+    #[schema(description = "Curve metric: power or pace (default pace)")]
+    metric: Option<StackString>,
+    #[schema(description = "Sport (default running)")]
+    sport: Option<SportTypesWrapper>,
+    #[schema(description = "Aggregation period: six_weeks or season (default six_weeks)")]
+    period: Option<StackString>,
+}
+
+async fn power_curve_impl(
+    req: PowerCurveRequest,
+    state: AppState,
+    session: Session,
+    is_demo: bool,
+) -> Result<StackString, Error> {
+    let metric: CurveMetric = match &req.metric {
+        Some(m) => m.parse().map_err(|e| Error::BadRequest(e.to_string()))?,
+        None => CurveMetric::default(),
+    };
+    let period: CurvePeriod = match &req.period {
+        Some(p) => p.parse().map_err(|e| Error::BadRequest(e.to_string()))?,
+        None => CurvePeriod::default(),
+    };
+    let sport = req.sport.map_or(SportTypes::Running, Into::into);
+    // demo curves end a date shift back, so they don't give away current form
+    let now = match DemoAnonymizer::from_config(&state.config).filter(|_| is_demo) {
+        Some(anonymizer) => anonymizer.shift_datetime(OffsetDateTime::now_utc()),
+        None => OffsetDateTime::now_utc(),
+    };
+    let ((start, end), (prev_start, prev_end)) = period.ranges(now);
+    let current = PowerCurve::get_best(&state.db, metric, sport, start, end).await?;
+    let previous = PowerCurve::get_best(&state.db, metric, sport, prev_start, prev_end).await?;
 
     let body = index_new_body(
         &state.config,
         &state.db,
         "".into(),
-        true,
-        session.history,
-        IndexConfig::Scale {
-            measurements,
-            offset: query.offset,
-            start_date: query.start_date,
-            end_date: query.end_date,
+        is_demo,
+        session,
+        IndexConfig::PowerCurve {
+            power_curve: PowerCurveOpts {
+                metric,
+                sport,
+                period,
+                current,
+                previous,
+            },
         },
     )
     .await?
     .into();
-
-    Ok(HtmlBase::new(body).into())
+    Ok(body)
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Fitbit Heartrate Plots", content = "html")]
-struct FitbitHeartratePlotResponse(HtmlBase<StackString, Error>);
+#[response(description = "Power Curve", content = "html")]
+struct PowerCurveResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/fitbit/heartrate_plots")]
-pub async fn heartrate_plots(
-    query: Query<ScaleMeasurementRequest>,
+#[get("/garmin/power_curve")]
+pub async fn power_curve(
+    query: Query<PowerCurveRequest>,
     #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<FitbitHeartratePlotResponse> {
-    let query: FitbitHeartratePlotRequest = query.into_inner().into();
+) -> WarpResult<PowerCurveResponse> {
+    let query = query.into_inner();
     let session = user
-        .get_session(&state.client, &state.config)
+        .get_session(&state.client, &state.config, &state.db)
         .await
         .map_err(Into::<Error>::into)?;
-
-    let parquet_values = fitbit_archive::get_number_of_heartrate_values(
-        &state.config,
-        query.start_date.into(),
-        query.end_date.into(),
-    )
-    .map_err(Into::<Error>::into)?;
-    let step_size = if parquet_values < 40_000 {
-        1
-    } else {
-        parquet_values / 40_000
-    };
-    debug!("parquet_values {parquet_values} step size {step_size}");
-
-    let heartrate = if parquet_values == 0 {
-        FitbitHeartRate::get_heartrate_values(
-            &state.config,
-            &state.db,
-            query.start_date.into(),
-            query.end_date.into(),
-        )
-        .await
-    } else {
-        let config = state.config.clone();
-        spawn_blocking(move || {
-            fitbit_archive::get_heartrate_values(
-                &config,
-                query.start_date.into(),
-                query.end_date.into(),
-                Some(step_size),
-            )
-        })
-        .await
-        .map_err(Into::<Error>::into)?
-    }
-    .map_err(Into::<Error>::into)?;
-
-    let body = index_new_body(
-        &state.config,
-        &state.db,
-        "".into(),
-        false,
-        session.history,
-        IndexConfig::HeartRate {
-            heartrate,
-            start_date: query.start_date,
-            end_date: query.end_date,
-            button_date: query.button_date,
-        },
-    )
-    .await
-    .map_err(Into::<Error>::into)?
-    .into();
+    let body = power_curve_impl(query, state, session, false).await?;
     Ok(HtmlBase::new(body).into())
 }
 
-#[get("/garmin/fitbit/heartrate_plots_demo")]
-pub async fn heartrate_plots_demo(
-    query: Query<ScaleMeasurementRequest>,
+#[get("/garmin/power_curve_demo")]
+pub async fn power_curve_demo(
+    query: Query<PowerCurveRequest>,
     #[data] state: AppState,
     #[filter = "optional_session"] session: Option<Session>,
-) -> WarpResult<FitbitHeartratePlotResponse> {
-    let mut query: FitbitHeartratePlotRequest = query.into_inner().into();
-    query.is_demo = true;
+) -> WarpResult<PowerCurveResponse> {
+    let query = query.into_inner();
     let session = session.unwrap_or_default();
-
-    let heartrate = FitbitHeartRate::get_heartrate_values(
-        &state.config,
-        &state.db,
-        query.start_date.into(),
-        query.end_date.into(),
-    )
-    .await
-    .map_err(Into::<Error>::into)?;
-    let body = index_new_body(
-        &state.config,
-        &state.db,
-        "".into(),
-        true,
-        session.history,
-        IndexConfig::HeartRate {
-            heartrate,
-            start_date: query.start_date,
-            end_date: query.end_date,
-            button_date: query.button_date,
-        },
-    )
-    .await
-    .map_err(Into::<Error>::into)?
-    .into();
-
+    let body = power_curve_impl(query, state, session, true).await?;
     Ok(HtmlBase::new(body).into())
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Fitbit Tcx Sync")]
-struct FitbitTcxSyncResponse(JsonBase<Vec<String>, Error>);
-
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "PaginatedScaleMeasurement")]
-struct PaginatedScaleMeasurement {
-    pagination: Pagination,
-    data: Vec<ScaleMeasurementWrapper>,
+#[derive(Serialize, Deserialize, Schema)]
+struct YearlyComparisonRequest {
+    #[schema(description = "Compared total: distance or duration (default distance)")]
+    metric: Option<StackString>,
+    #[schema(description = "Sport, or all (default all)")]
+    sport: Option<StackString>,
+    #[schema(description = "Number of years compared (default 5)")]
+    years: Option<i32>,
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Scale Measurements")]
-struct ScaleMeasurementsResponse(JsonBase<PaginatedScaleMeasurement, Error>);
+#[response(description = "Yearly Comparison", content = "html")]
+struct YearlyComparisonResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/scale_measurements")]
-pub async fn scale_measurement(
-    query: Query<ScaleMeasurementRequest>,
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+#[get("/garmin/stats/yearly_comparison")]
+#[openapi(description = "Cumulative Distance or Time of Each Year Overlaid on One Chart")]
+pub async fn yearly_comparison(
+    query: Query<YearlyComparisonRequest>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<ScaleMeasurementsResponse> {
+) -> WarpResult<YearlyComparisonResponse> {
     let query = query.into_inner();
-
-    let offset = query.offset.unwrap_or(0);
-    let limit = query.limit.unwrap_or(10);
-    let start_date = query.start_date.map(Into::into);
-    let end_date = query.end_date.map(Into::into);
-
-    let total = ScaleMeasurement::get_total(&state.db, start_date, end_date)
+    let session = user
+        .get_session(&state.client, &state.config, &state.db)
         .await
         .map_err(Into::<Error>::into)?;
-    let pagination = Pagination {
-        total,
-        offset,
-        limit,
+    let metric: ComparisonMetric = match &query.metric {
+        Some(m) => m.parse().map_err(|e| Error::BadRequest(format!("{e}")))?,
+        None => ComparisonMetric::default(),
     };
-
-    let mut data: Vec<_> = ScaleMeasurement::read_from_db(
+    let sport: Option<SportTypes> = match &query.sport {
+        Some(s) if s.as_str() != "all" => {
+            Some(s.parse().map_err(|e| Error::BadRequest(format!("{e}")))?)
+        }
+        _ => None,
+    };
+    let years = query.years.unwrap_or(DEFAULT_COMPARISON_YEARS);
+    if !(1..=50).contains(&years) {
+        return Err(Error::BadRequest(format!("Invalid number of years {years}")).into());
+    }
+    let comparison = YearlyComparison::read_from_db(&state.db, metric, sport, years)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = index_new_body(
+        &state.config,
         &state.db,
-        query.start_date.map(Into::into),
-        query.end_date.map(Into::into),
-        Some(offset),
-        Some(limit),
-    )
-    .await
-    .map_err(Into::<Error>::into)?
-    .into_iter()
-    .map(Into::into)
-    .collect();
-    data.shrink_to_fit();
+        "".into(),
+        false,
+        session,
+        IndexConfig::YearlyComparison { comparison },
+    )
+    .await?
+    .into();
+    Ok(HtmlBase::new(body).into())
+}
 
-    Ok(JsonBase::new(PaginatedScaleMeasurement { pagination, data }).into())
+#[derive(Serialize, Deserialize, Schema)]
+struct TrainingPatternRequest {
+    #[schema(description = "Sport, or all (default running)")]
+    sport: Option<StackString>,
+    #[schema(description = "Number of days analyzed (default 365)")]
+    days: Option<i64>,
 }
 
 #[derive(RwebResponse)]
-#[response(
-    description = "Scale Measurements Update",
-    content = "html",
-    status = "CREATED"
-)]
-struct ScaleMeasurementsUpdateResponse(HtmlBase<&'static str, Error>);
+#[response(description = "Training Pattern", content = "html")]
+struct TrainingPatternResponse(HtmlBase<StackString, Error>);
 
-#[post("/garmin/scale_measurements")]
-pub async fn scale_measurement_update(
-    measurements: Json<ScaleMeasurementUpdateRequest>,
+#[get("/garmin/training_pattern")]
+#[openapi(description = "Activity Count and Pace by Day of Week and Hour of Day")]
+pub async fn training_pattern(
+    query: Query<TrainingPatternRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<ScaleMeasurementsUpdateResponse> {
-    let measurements = measurements.into_inner();
-    let mut measurements: Vec<_> = measurements
-        .measurements
-        .into_iter()
-        .map(Into::into)
-        .collect();
-    measurements.shrink_to_fit();
-    ScaleMeasurement::merge_updates(&mut measurements, &state.db)
+) -> WarpResult<TrainingPatternResponse> {
+    let query = query.into_inner();
+    let sport: Option<SportTypes> = match &query.sport {
+        Some(s) if s.as_str() == "all" => None,
+        Some(s) => Some(s.parse().map_err(|e| Error::BadRequest(format!("{e}")))?),
+        None => Some(SportTypes::Running),
+    };
+    let days = query.days.unwrap_or(DEFAULT_PATTERN_DAYS);
+    if !(1..=MAX_PATTERN_DAYS).contains(&days) {
+        return Err(Error::BadRequest(format!("Invalid number of days {days}")).into());
+    }
+    let pattern = TrainingPattern::read_from_db(&state.db, sport, state.config.week_start, days)
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(HtmlBase::new("Finished").into())
+    let body = training_pattern_body(pattern)?.into();
+    Ok(HtmlBase::new(body).into())
 }
 
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "ScaleMeasurementManualRequest")]
-struct ScaleMeasurementManualRequest {
-    #[schema(description = "Weight in lbs", example = r#""189.0""#)]
-    weight_in_lbs: f64,
-    #[schema(description = "Body fat percent", example = r#""20.3""#)]
-    body_fat_percent: f64,
-    #[schema(description = "Muscle mass in lbs", example = r#""153.0""#)]
-    muscle_mass_lbs: f64,
-    #[schema(description = "Body water percent", example = r#""63.0""#)]
-    body_water_percent: f64,
-    #[schema(description = "Bone mass in lbs", example = r#""63.0""#)]
-    bone_mass_lbs: f64,
+#[derive(Serialize, Deserialize, Schema)]
+struct RestDaysRequest {
+    #[schema(description = "Number of days analyzed (default 365)")]
+    days: Option<i64>,
 }
 
 #[derive(RwebResponse)]
-#[response(
-    description = "Scale Measurement Manual Input Post",
-    status = "CREATED"
-)]
-struct ScaleMeasurementManualResponse(JsonBase<ScaleMeasurementWrapper, Error>);
+#[response(description = "Rest Days", content = "html")]
+struct RestDaysResponse(HtmlBase<StackString, Error>);
 
-#[post("/garmin/scale_measurements/manual")]
-pub async fn scale_measurement_manual(
-    payload: Json<ScaleMeasurementManualRequest>,
+#[get("/garmin/rest_days")]
+#[openapi(
+    description = "Rest Days Between Hard Efforts, Training Consistency and Lactate Threshold \
+                   History"
+)]
+pub async fn rest_days(
+    query: Query<RestDaysRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<ScaleMeasurementManualResponse> {
-    let payload = payload.into_inner();
-    let mut measurement = ScaleMeasurement::from_fit_plus(
-        payload.weight_in_lbs,
-        payload.body_fat_percent,
-        payload.muscle_mass_lbs,
-        payload.body_water_percent,
-        payload.bone_mass_lbs,
-    )
-    .map_err(Into::<Error>::into)?;
-    measurement
-        .insert_into_db(&state.db)
+) -> WarpResult<RestDaysResponse> {
+    let days = query.into_inner().days.unwrap_or(DEFAULT_REST_DAYS);
+    if days < 1 {
+        return Err(Error::BadRequest("days must be positive".into()).into());
+    }
+    let profiles = HeartRateProfiles::read_from_db(&state.db, state.config.heart_rate_profile())
         .await
         .map_err(Into::<Error>::into)?;
-    Ok(JsonBase::new(measurement.into()).into())
+    let current = profiles.profile_on(local_today());
+    let threshold = ThresholdHistory::read_from_db(&state.db, &current, THRESHOLD_HISTORY_DAYS)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let stats = RestDayStats::read_from_db(
+        &state.db,
+        &profiles,
+        state.config.hard_effort_trimp,
+        state.config.planned_training_days,
+        state.config.week_start,
+        days,
+        state.config.exclude_commutes,
+    )
+    .await
+    .map_err(Into::<Error>::into)?;
+    let body = rest_days_body(stats, threshold, current.threshold_heart_rate)?.into();
+    Ok(HtmlBase::new(body).into())
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Scale Measurement Manual Input")]
-struct ScaleMeasurementManualInputResponse(HtmlBase<StackString, Error>);
-
-#[post("/garmin/scale_measurements/manual/input")]
-pub async fn scale_measurement_manual_input(
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
-) -> WarpResult<ScaleMeasurementManualInputResponse> {
-    let body = scale_measurement_manual_input_body()?;
-    Ok(HtmlBase::new(body.into()).into())
+#[derive(Serialize, Deserialize, Schema)]
+struct ClothingRequest {
+    #[schema(description = "Forecast temperature (C) to recommend an outfit for")]
+    temperature: Option<f64>,
+    #[schema(description = "Sport, or all (default running)")]
+    sport: Option<StackString>,
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Logged in User")]
-struct UserResponse(JsonBase<LoggedUser, Error>);
-
-#[allow(clippy::unused_async)]
-#[get("/garmin/user")]
-pub async fn user(#[filter = "LoggedUser::filter"] user: LoggedUser) -> WarpResult<UserResponse> {
-    Ok(JsonBase::new(user).into())
+#[response(description = "Clothing Log", content = "html")]
+struct ClothingResponse(HtmlBase<StackString, Error>);
+
+/// Temperature of the weather garmin connect reported for the activity
+async fn connect_temperature(pool: &PgPool, summary_id: Uuid) -> HttpResult<Option<f64>> {
+    let activity = match GarminConnectActivity::get_from_summary_id(pool, summary_id).await? {
+        Some(activity) => activity,
+        None => return Ok(None),
+    };
+    let detail =
+        GarminConnectActivityDetail::get_by_activity_id(pool, activity.activity_id).await?;
+    Ok(detail.and_then(|d| d.temperature))
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Add correction", content = "html", status = "CREATED")]
-struct AddGarminCorrectionResponse(HtmlBase<&'static str, Error>);
+async fn clothing_impl(
+    pool: &PgPool,
+    sport: Option<&str>,
+    temperature: Option<f64>,
+    message: Option<StackString>,
+) -> HttpResult<String> {
+    let sport: Option<SportTypes> = match sport {
+        Some("all") => None,
+        Some(s) => Some(s.parse().map_err(|e| Error::BadRequest(format!("{e}")))?),
+        None => Some(SportTypes::Running),
+    };
+    let entries = ClothingLogEntry::get_entries(pool, sport)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let recommendation = temperature.map(|t| ClothingRecommendation::from_entries(&entries, t));
+    clothing_body(message, entries, recommendation).map_err(Into::into)
+}
 
-#[post("/garmin/add_garmin_correction")]
-pub async fn add_garmin_correction(
-    payload: Json<AddGarminCorrectionRequest>,
+#[get("/garmin/clothing")]
+#[openapi(description = "Clothing Log and Outfit Recommendation for a Forecast Temperature")]
+pub async fn clothing(
+    query: Query<ClothingRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<AddGarminCorrectionResponse> {
-    payload.into_inner().add_corrections(&state.db).await?;
-    Ok(HtmlBase::new("finised").into())
+) -> WarpResult<ClothingResponse> {
+    let query = query.into_inner();
+    let body = clothing_impl(&state.db, query.sport.as_deref(), query.temperature, None)
+        .await?
+        .into();
+    Ok(HtmlBase::new(body).into())
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Fitbit Activity Types")]
-struct FitbitActivityTypesResponse(JsonBase<FitbitActivityTypesWrapper, Error>);
-
-#[derive(RwebResponse)]
-#[response(description = "Strava Athlete")]
-struct StravaAthleteResponse(HtmlBase<StackString, Error>);
+#[derive(Serialize, Deserialize, Schema)]
+struct ClothingLogRequest {
+    #[schema(
+        description = "Activity Filename",
+        example = r#""2024-01-07_12-30-00_1_1.fit""#
+    )]
+    filename: StackString,
+    #[schema(description = "What was worn")]
+    clothing: StackString,
+    #[schema(description = "Comfort: too_cold, just_right or too_hot")]
+    comfort: StackString,
+    #[schema(
+        description = "Temperature (C), defaults to the reported weather, then the device sensor"
+    )]
+    temperature: Option<f64>,
+}
 
-#[get("/garmin/strava/athlete")]
-pub async fn strava_athlete(
+#[post("/garmin/clothing")]
+#[openapi(description = "Log Clothing Worn for an Activity")]
+pub async fn clothing_log(
+    payload: Json<ClothingLogRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<StravaAthleteResponse> {
-    let client = StravaClient::with_auth(state.config)
+) -> WarpResult<ClothingResponse> {
+    let payload = payload.into_inner();
+    let comfort = payload
+        .comfort
+        .parse()
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    if payload.clothing.trim().is_empty() {
+        return Err(Error::BadRequest("No clothing given".into()).into());
+    }
+    let summary = GarminSummary::get_by_filename(&state.db, &payload.filename)
         .await
-        .map_err(Into::<Error>::into)?;
-    let result = client
-        .get_strava_athlete()
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest(format!("No activity {}", payload.filename)))?;
+    let (temperature, device_temperature) = match payload.temperature {
+        Some(t) => (Some(t), false),
+        None => match connect_temperature(&state.db, summary.id).await? {
+            Some(t) => (Some(t), false),
+            None => match summary.avg_temperature {
+                Some(t) => (Some(t), true),
+                None => {
+                    let store = state.avro_cache.clone();
+                    let filename = &payload.filename;
+                    match garmin_file::GarminFile::read_cached_avro(&store, filename).await {
+                        Ok(gfile) => {
+                            let t = gfile.avg_temperature();
+                            (t, t.is_some())
+                        }
+                        Err(e) => {
+                            debug!("failed to read {filename} {e}");
+                            (None, false)
+                        }
+                    }
+                }
+            },
+        },
+    };
+    let entry = ClothingLogEntry {
+        summary_id: summary.id,
+        filename: summary.filename,
+        begin_datetime: summary.begin_datetime,
+        sport: summary.sport,
+        clothing: payload.clothing.trim().into(),
+        comfort,
+        temperature,
+        device_temperature,
+    };
+    entry
+        .upsert_db(&state.db)
         .await
         .map_err(Into::<Error>::into)?;
-    let body = strava_body(result)?.into();
+    let message = format_sstr!("Logged {} for {}", entry.clothing, entry.filename);
+    let body = clothing_impl(&state.db, Some(entry.sport.to_str()), None, Some(message))
+        .await?
+        .into();
     Ok(HtmlBase::new(body).into())
 }
 
+#[derive(Serialize, Deserialize, Schema)]
+struct StatSourceRequest {
+    #[schema(
+        description = "Activity Filename",
+        example = r#""2024-01-07_12-30-00_1_1.fit""#
+    )]
+    filename: StackString,
+    #[schema(description = "Stat: distance or calories")]
+    stat: StackString,
+    #[schema(description = "Source: file, strava or garmin_connect")]
+    source: StackString,
+}
+
 #[derive(RwebResponse)]
-#[response(description = "Fitbit Profile")]
-struct FitbitProfileResponse(HtmlBase<StackString, Error>);
+#[response(description = "Stat Source", content = "html", status = "CREATED")]
+struct StatSourceResponse(HtmlBase<StackString, Error>);
 
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "PaginatedGarminConnectActivity")]
-struct PaginatedGarminConnectActivity {
-    pagination: Pagination,
-    data: Vec<GarminConnectActivityWrapper>,
+#[post("/garmin/stat_source")]
+#[openapi(description = "Choose the Source of an Activity Total Used in Reports")]
+pub async fn stat_source_update(
+    payload: Json<StatSourceRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<StatSourceResponse> {
+    let payload = payload.into_inner();
+    let stat: ActivityStat = payload
+        .stat
+        .parse()
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let source: StatSource = payload
+        .source
+        .parse()
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let body = stat_source_body(&state, &payload.filename, stat, source).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+async fn stat_source_body(
+    state: &AppState,
+    filename: &str,
+    stat: ActivityStat,
+    source: StatSource,
+) -> HttpResult<StackString> {
+    let summary = GarminSummary::get_by_filename(&state.db, filename)
+        .await?
+        .ok_or_else(|| Error::BadRequest(format!("No activity {filename}")))?;
+    let store = state.avro_cache.clone();
+    let gfile = garmin_file::GarminFile::read_cached_avro(&store, filename).await?;
+    let strava_activity = StravaActivity::get_from_summary_id(&state.db, summary.id).await?;
+    let connect_activity =
+        GarminConnectActivity::get_from_summary_id(&state.db, summary.id).await?;
+    let value = source_values(
+        stat,
+        gfile.total_distance,
+        gfile.total_calories,
+        strava_activity.as_ref(),
+        connect_activity.as_ref(),
+    )
+    .into_iter()
+    .find_map(|(s, v)| (s == source).then_some(v))
+    .ok_or_else(|| {
+        Error::BadRequest(format!(
+            "{} has no {} for {filename}",
+            source.display_name(),
+            stat.to_str()
+        ))
+    })?;
+    let choice = StatChoice {
+        summary_id: summary.id,
+        stat,
+        source,
+        value,
+    };
+    choice.upsert_db(&state.db).await?;
+    Ok(format_sstr!(
+        "{} of {filename} set from {}",
+        stat.display_name(),
+        source.display_name()
+    ))
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct ActivityRpeRequest {
+    #[schema(
+        description = "Activity Filename",
+        example = r#""2024-01-07_12-30-00_1_1.fit""#
+    )]
+    filename: StackString,
+    #[schema(description = "Rating of Perceived Exertion 1-10, clears the rating when absent")]
+    rpe: Option<i16>,
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Garmin Connect Activities")]
-struct GarminConnectActivitiesResponse(JsonBase<PaginatedGarminConnectActivity, Error>);
+#[response(description = "Activity RPE", content = "html", status = "CREATED")]
+struct ActivityRpeResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/garmin_connect_activities_db")]
-pub async fn garmin_connect_activities_db(
-    query: Query<StravaActivitiesRequest>,
+#[post("/garmin/activity_rpe")]
+#[openapi(description = "Rate the Perceived Exertion of an Activity")]
+pub async fn activity_rpe_update(
+    payload: Json<ActivityRpeRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<GarminConnectActivitiesResponse> {
-    let query = query.into_inner();
-    let offset = query.offset.unwrap_or(0);
-    let limit = query.limit.unwrap_or(10);
-    let start_date = query.start_date.map(Into::into);
-    let end_date = query.end_date.map(Into::into);
+) -> WarpResult<ActivityRpeResponse> {
+    let payload = payload.into_inner();
+    let summary = GarminSummary::get_by_filename(&state.db, &payload.filename)
+        .await
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest(format!("No activity {}", payload.filename)))?;
+    let body = match payload.rpe {
+        Some(rpe) => {
+            let rpe =
+                ActivityRpe::new(summary.id, rpe).map_err(|e| Error::BadRequest(format!("{e}")))?;
+            rpe.upsert_db(&state.db)
+                .await
+                .map_err(Into::<Error>::into)?;
+            format_sstr!("RPE of {} set to {}", summary.filename, rpe.rpe)
+        }
+        None => {
+            ActivityRpe::delete_by_summary_id(&state.db, summary.id)
+                .await
+                .map_err(Into::<Error>::into)?;
+            format_sstr!("RPE of {} cleared", summary.filename)
+        }
+    };
+    Ok(HtmlBase::new(body).into())
+}
 
-    let total = GarminConnectActivity::get_total(&state.db, start_date, end_date)
+#[derive(RwebResponse)]
+#[response(description = "Heart Rate Zones", content = "html")]
+struct HeartRateZonesResponse(HtmlBase<StackString, Error>);
+
+#[get("/garmin/heart_rate_zones")]
+#[openapi(description = "Heart Rate Zones and Suggested Update from Observed Max Heart Rate")]
+pub async fn heart_rate_zones(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<HeartRateZonesResponse> {
+    let suggestion = HeartRateSuggestion::detect(&state.db, state.config.heart_rate_profile())
         .await
         .map_err(Into::<Error>::into)?;
+    let body = heart_rate_zones_body(suggestion)?.into();
+    Ok(HtmlBase::new(body).into())
+}
 
-    let pagination = Pagination {
-        total,
-        offset,
-        limit,
-    };
-
-    let mut data: Vec<_> = GarminConnectActivity::read_from_db(
-        &state.db,
-        start_date,
-        end_date,
-        Some(offset),
-        Some(limit),
-    )
-    .await
-    .map_err(Into::<Error>::into)?
-    .map_ok(Into::into)
-    .try_collect()
-    .await
-    .map_err(Into::<Error>::into)?;
-    data.shrink_to_fit();
-    Ok(JsonBase::new(PaginatedGarminConnectActivity { pagination, data }).into())
+#[derive(Serialize, Deserialize, Schema)]
+struct HeartRateZonesRequest {
+    #[schema(description = "Max Heart Rate (bpm)")]
+    max_heart_rate: f64,
+    #[schema(description = "Resting Heart Rate (bpm), default current")]
+    resting_heart_rate: Option<f64>,
 }
 
 #[derive(RwebResponse)]
 #[response(
-    description = "Garmin Connect Activities",
+    description = "Heart Rate Zones Update",
     content = "html",
     status = "CREATED"
 )]
-struct GarminConnectActivitiesUpdateResponse(HtmlBase<StackString, Error>);
+struct HeartRateZonesUpdateResponse(HtmlBase<StackString, Error>);
 
-#[post("/garmin/garmin_connect_activities_db")]
-pub async fn garmin_connect_activities_db_update(
-    payload: Json<GarminConnectActivitiesDBUpdateRequest>,
+#[post("/garmin/heart_rate_zones")]
+#[openapi(description = "Confirm Max and Resting Heart Rate Used for Heart Rate Zones")]
+pub async fn heart_rate_zones_update(
+    payload: Json<HeartRateZonesRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<GarminConnectActivitiesUpdateResponse> {
+) -> WarpResult<HeartRateZonesUpdateResponse> {
     let payload = payload.into_inner();
-    let mut updates: Vec<_> = payload.updates.into_iter().map(Into::into).collect();
-    updates.shrink_to_fit();
-    let body: StackString = GarminConnectActivity::upsert_activities(&updates, &state.db)
+    let current = current_heart_rate_profile(&state.db, state.config.heart_rate_profile())
         .await
-        .map_err(Into::<Error>::into)?
-        .join("\n")
-        .into();
+        .map_err(Into::<Error>::into)?;
+    let setting = HeartRateSetting {
+        effective_date: local_today(),
+        max_heart_rate: payload.max_heart_rate,
+        resting_heart_rate: payload
+            .resting_heart_rate
+            .unwrap_or(current.resting_heart_rate),
+    };
+    setting
+        .validate()
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    setting
+        .upsert_db(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = format_sstr!(
+        "Max heart rate {:.0} resting {:.0} from {}",
+        setting.max_heart_rate,
+        setting.resting_heart_rate,
+        setting.effective_date
+    );
     Ok(HtmlBase::new(body).into())
 }
 
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "PaginatedFitbitActivity")]
-struct PaginatedFitbitActivity {
-    pagination: Pagination,
-    data: Vec<FitbitActivityWrapper>,
+#[derive(Serialize, Deserialize, Schema)]
+struct LanguageRequest {
+    #[schema(description = "Language code", example = r#""de""#)]
+    language: StackString,
+}
+
+impl LanguageRequest {
+    fn language(&self) -> Result<Language, Error> {
+        self.language
+            .parse()
+            .map_err(|e| Error::BadRequest(format!("{e}")))
+    }
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Fitbit Activities")]
-struct FitbitActivitiesDBResponse(JsonBase<PaginatedFitbitActivity, Error>);
+#[response(description = "Language Update", content = "html", status = "CREATED")]
+struct LanguageUpdateResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/fitbit/fitbit_activities_db")]
-pub async fn fitbit_activities_db(
-    query: Query<StravaActivitiesRequest>,
-    #[filter = "LoggedUser::filter"] _: LoggedUser,
+#[post("/garmin/language")]
+#[openapi(description = "Set the Language of the Web UI")]
+pub async fn language_update(
+    payload: Json<LanguageRequest>,
+    #[filter = "LoggedUser::filter"] user: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<FitbitActivitiesDBResponse> {
-    let query = query.into_inner();
-
-    let offset = query.offset.unwrap_or(0);
-    let limit = query.limit.unwrap_or(10);
-    let start_date = query.start_date.map(Into::into);
-    let end_date = query.end_date.map(Into::into);
-
-    let total = FitbitActivity::get_total(&state.db, start_date, end_date)
+) -> WarpResult<LanguageUpdateResponse> {
+    let language = payload.into_inner().language()?;
+    UserPreferences::set_language(&state.db, &user.email, language)
         .await
         .map_err(Into::<Error>::into)?;
-    let pagination = Pagination {
-        total,
-        offset,
-        limit,
+    let body = format_sstr!("Language set to {}", language.native_name());
+    Ok(HtmlBase::new(body).into())
+}
+
+#[get("/garmin/language_demo")]
+#[openapi(description = "Set the Language of the Demo Web UI")]
+pub async fn language_demo(
+    query: Query<LanguageRequest>,
+    #[data] state: AppState,
+    #[filter = "optional_session"] session: Option<Session>,
+) -> WarpResult<HtmlStream> {
+    let language = query.into_inner().language()?;
+    let session = Session {
+        language,
+        ..session.unwrap_or_default()
     };
+    let jwt = session.get_jwt_cookie(&state.config.domain);
+    let jwt_str = StackString::from_display(jwt.encoded());
+    let body = format!("Language set to {}", language.native_name());
+    Ok(HtmlStream::new(body).with_cookie(&jwt_str))
+}
 
-    let mut data: Vec<_> =
-        FitbitActivity::read_from_db(&state.db, start_date, end_date, Some(offset), Some(limit))
-            .await
-            .map_err(Into::<Error>::into)?
-            .into_iter()
-            .map(Into::into)
-            .collect();
-    data.shrink_to_fit();
-    Ok(JsonBase::new(PaginatedFitbitActivity { pagination, data }).into())
+#[derive(Serialize, Deserialize, Schema, Default)]
+#[schema(component = "PacePlannerRequest")]
+struct PacePlannerRequest {
+    #[schema(description = "Race distance, e.g. 10k, half, marathon, 13.1mi")]
+    distance: Option<StackString>,
+    #[schema(description = "Target finish time hh:mm:ss")]
+    target_time: Option<StackString>,
+    #[schema(description = "Split length: mi or km (default mi)")]
+    units: Option<StackString>,
+    #[schema(description = "Filename of a recorded activity to use as the course")]
+    course: Option<StackString>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Schema)]
-struct FitbitActivitiesDBUpdateRequest {
-    updates: Vec<FitbitActivityWrapper>,
+struct PacePlan {
+    distance: StackString,
+    target_time: StackString,
+    course: Option<StackString>,
+    unit: SplitUnit,
+    splits: Vec<PlannedSplit>,
+}
+
+async fn get_pace_plan(
+    req: PacePlannerRequest,
+    state: &AppState,
+    gpx: Option<&str>,
+) -> HttpResult<PacePlan> {
+    let unit: SplitUnit = match &req.units {
+        Some(u) if !u.is_empty() => u.parse().map_err(|e| Error::BadRequest(format!("{e}")))?,
+        _ => SplitUnit::default(),
+    };
+    let distance = req.distance.unwrap_or_default();
+    let target_time = req.target_time.unwrap_or_default();
+    let course_name = req.course.filter(|c| !c.is_empty());
+    let splits = if distance.is_empty() || target_time.is_empty() {
+        Vec::new()
+    } else {
+        let race_distance =
+            parse_race_distance(&distance).map_err(|e| Error::BadRequest(format!("{e}")))?;
+        let target_seconds =
+            convert_time_string(&target_time).map_err(|e| Error::BadRequest(format!("{e}")))?;
+        let course = if let Some(gpx) = gpx {
+            course_from_gpx(gpx).map_err(|e| Error::BadRequest(format!("{e}")))?
+        } else if let Some(course) = &course_name {
+            if GarminSummary::get_by_filename(&state.db, course)
+                .await?
+                .is_none()
+            {
+                return Err(Error::NotFound(format!("No activity {course}")));
+            }
+            let store = state.avro_cache.clone();
+            let gfile = garmin_file::GarminFile::read_cached_avro(&store, course)
+                .await
+                .map_err(|e| Error::NotFound(format!("No track for {course}: {e}")))?;
+            course_from_gfile(&gfile)
+        } else {
+            Vec::new()
+        };
+        plan_splits(&course, race_distance, target_seconds, unit)
+    };
+    Ok(PacePlan {
+        distance,
+        target_time,
+        course: course_name,
+        unit,
+        splits,
+    })
+}
+
+async fn pace_planner_impl(
+    req: PacePlannerRequest,
+    state: &AppState,
+    gpx: Option<&str>,
+) -> HttpResult<StackString> {
+    let plan = get_pace_plan(req, state, gpx).await?;
+    let mut races = Vec::new();
+    for race in UpcomingRace::get_upcoming(&state.db).await? {
+        let forecasts = RaceForecast::get_by_race(&state.db, race.id).await?;
+        races.push((race, forecasts));
+    }
+    let body = pace_planner_body(
+        plan.distance,
+        plan.target_time,
+        plan.course,
+        plan.unit,
+        plan.splits,
+        races,
+    )?
+    .into();
+    Ok(body)
 }
 
 #[derive(RwebResponse)]
-#[response(
-    description = "Fitbit Activities Update",
-    content = "html",
-    status = "CREATED"
-)]
-struct FitbitActivitiesDBUpdateResponse(HtmlBase<StackString, Error>);
+#[response(description = "Pace Planner", content = "html")]
+struct PacePlannerResponse(HtmlBase<StackString, Error>);
 
-#[post("/garmin/fitbit/fitbit_activities_db")]
-pub async fn fitbit_activities_db_update(
-    payload: Json<FitbitActivitiesDBUpdateRequest>,
+#[get("/garmin/pace_planner")]
+pub async fn pace_planner(
+    query: Query<PacePlannerRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<FitbitActivitiesDBUpdateResponse> {
-    let payload = payload.into_inner();
-    let mut updates: Vec<_> = payload.updates.into_iter().map(Into::into).collect();
-    updates.shrink_to_fit();
-    let body = FitbitActivity::upsert_activities(&updates, &state.db)
-        .await
-        .map_err(Into::<Error>::into)?;
-    FitbitActivity::fix_summary_id_in_db(&state.db)
-        .await
-        .map_err(Into::<Error>::into)?;
-
-    let body = body.join("\n").into();
+) -> WarpResult<PacePlannerResponse> {
+    let body = pace_planner_impl(query.into_inner(), &state, None).await?;
     Ok(HtmlBase::new(body).into())
 }
 
-#[derive(Debug, Serialize, Deserialize, Schema)]
-#[schema(component = "PaginatedFitbitStatisticsSummary")]
-struct PaginatedFitbitStatisticsSummary {
-    pagination: Pagination,
-    data: Vec<FitbitStatisticsSummaryWrapper>,
+#[post("/garmin/pace_planner")]
+pub async fn pace_planner_upload(
+    #[filter = "rweb::multipart::form"] mut form: FormData,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PacePlannerResponse> {
+    let mut req = PacePlannerRequest::default();
+    let mut gpx: Option<StackString> = None;
+    while let Some(item) = form.next().await {
+        let item = item.map_err(Into::<Error>::into)?;
+        let name: StackString = item.name().into();
+        let value = read_part(item).await.map_err(Into::<Error>::into)?;
+        let value = Some(value).filter(|v| !v.is_empty());
+        match name.as_str() {
+            "distance" => req.distance = value,
+            "target_time" => req.target_time = value,
+            "units" => req.units = value,
+            "course" => req.course = value,
+            "gpx" => gpx = value,
+            _ => {}
+        }
+    }
+    let body = pace_planner_impl(req, &state, gpx.as_deref()).await?;
+    Ok(HtmlBase::new(body).into())
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Heartrate Statistics")]
-struct HeartrateStatisticsResponse(JsonBase<PaginatedFitbitStatisticsSummary, Error>);
-
-#[get("/garmin/fitbit/heartrate_statistics_summary_db")]
-pub async fn heartrate_statistics_summary_db(
-    query: Query<StravaActivitiesRequest>,
+#[get("/garmin/pace_planner/pace_band.pdf")]
+#[openapi(description = "Printable pace band for a race distance and target time")]
+pub async fn pace_band(
+    query: Query<PacePlannerRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<HeartrateStatisticsResponse> {
-    let query = query.into_inner();
+) -> WarpResult<FileDownload> {
+    let plan = get_pace_plan(query.into_inner(), &state, None).await?;
+    if plan.splits.is_empty() {
+        return Err(Error::BadRequest("distance and target_time are required".into()).into());
+    }
+    let title = format_sstr!("{} {}", plan.distance, plan.target_time);
+    let pdf = splits_to_pace_band(&title, &plan.splits, plan.unit).map_err(Into::<Error>::into)?;
+    Ok(FileDownload::pdf(pdf, "pace_band.pdf"))
+}
 
-    let offset = query.offset.unwrap_or(0);
-    let limit = query.limit.unwrap_or(10);
-    let start_date = query.start_date.map(Into::into);
-    let end_date = query.end_date.map(Into::into);
+#[post("/garmin/pace_planner/races")]
+pub async fn upcoming_race_create(
+    #[filter = "rweb::multipart::form"] mut form: FormData,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PacePlannerResponse> {
+    let mut fields: HashMap<StackString, StackString> = HashMap::new();
+    while let Some(item) = form.next().await {
+        let item = item.map_err(Into::<Error>::into)?;
+        let name: StackString = item.name().into();
+        let value = read_part(item).await.map_err(Into::<Error>::into)?;
+        fields.insert(name, value);
+    }
+    let field = |name: &str| {
+        fields
+            .get(name)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| Error::BadRequest(format!("{name} is required")))
+    };
+    let race_datetime = parse_race_datetime(field("race_datetime")?)
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let latitude: f64 = field("latitude")?
+        .parse()
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let longitude: f64 = field("longitude")?
+        .parse()
+        .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let distance =
+        parse_race_distance(field("distance")?).map_err(|e| Error::BadRequest(format!("{e}")))?;
+    let target_time = match field("target_time") {
+        Ok(t) => Some(convert_time_string(t).map_err(|e| Error::BadRequest(format!("{e}")))?),
+        Err(_) => None,
+    };
+    let race = UpcomingRace::new(
+        field("name")?,
+        race_datetime,
+        latitude,
+        longitude,
+        distance,
+        target_time,
+    )
+    .map_err(|e| Error::BadRequest(format!("{e}")))?;
+    race.insert_into_db(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    // don't make a race already in range wait for the daily refresh, the
+    // race is stored so a failed forecast is left to that refresh
+    if let Some(endpoint) = &state.config.forecast_endpoint {
+        if race.in_forecast_range(OffsetDateTime::now_utc()) {
+            match RaceForecast::fetch(&Client::new(), endpoint, &race).await {
+                Ok(Some(forecast)) => {
+                    if let Err(e) = forecast.insert_into_db(&state.db).await {
+                        error!("failed to store forecast for {} {e}", race.name);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => error!("failed to fetch forecast for {} {e}", race.name),
+            }
+        }
+    }
+    let body = pace_planner_impl(PacePlannerRequest::default(), &state, None).await?;
+    Ok(HtmlBase::new(body).into())
+}
 
-    let total = FitbitStatisticsSummary::get_total(&state.db, start_date, end_date)
+#[post("/garmin/pace_planner/races/delete")]
+pub async fn upcoming_race_delete(
+    #[filter = "rweb::multipart::form"] mut form: FormData,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<PacePlannerResponse> {
+    let mut id: Option<Uuid> = None;
+    while let Some(item) = form.next().await {
+        let item = item.map_err(Into::<Error>::into)?;
+        let name: StackString = item.name().into();
+        let value = read_part(item).await.map_err(Into::<Error>::into)?;
+        if name == "id" {
+            id = value.trim().parse().ok();
+        }
+    }
+    let id = id.ok_or_else(|| Error::BadRequest("Invalid race id".into()))?;
+    let race = UpcomingRace::get_by_id(&state.db, id)
+        .await
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest(format!("No race {id}")))?;
+    race.delete_from_db(&state.db)
         .await
         .map_err(Into::<Error>::into)?;
+    let body = pace_planner_impl(PacePlannerRequest::default(), &state, None).await?;
+    Ok(HtmlBase::new(body).into())
+}
 
-    let pagination = Pagination {
-        total,
-        offset,
-        limit,
-    };
+#[derive(Serialize, Deserialize, Schema, Default)]
+#[schema(component = "RouteProfileRequest")]
+struct RouteProfileRequest {
+    #[schema(description = "Split length: mi or km (default mi)")]
+    units: Option<StackString>,
+    #[schema(description = "Pace on the flat mm:ss per split unit (default from recent runs)")]
+    pace: Option<StackString>,
+}
 
-    let mut data: Vec<_> = FitbitStatisticsSummary::read_from_db(
+/// Median grade adjusted pace of the most recent runs in seconds per meter
+async fn recent_grade_adjusted_pace(state: &AppState) -> HttpResult<Option<f64>> {
+    let filenames = GarminSummary::get_recent_filenames(
         &state.db,
-        start_date,
-        end_date,
-        Some(offset),
-        Some(limit),
+        SportTypes::Running,
+        ROUTE_PACE_ACTIVITIES as i64,
     )
-    .await
-    .map_err(Into::<Error>::into)?
-    .map_ok(|x| {
-        let x: FitbitStatisticsSummaryWrapper = x.into();
-        x
-    })
-    .try_collect()
-    .await
-    .map_err(Into::<Error>::into)?;
-    data.shrink_to_fit();
-    Ok(JsonBase::new(PaginatedFitbitStatisticsSummary { pagination, data }).into())
+    .await?;
+    let store = state.avro_cache.clone();
+    let mut paces = Vec::new();
+    for filename in filenames {
+        match garmin_file::GarminFile::read_cached_avro(&store, &filename).await {
+            Ok(gfile) => paces.extend(grade_adjusted_pace(&gfile)),
+            Err(e) => debug!("failed to read {filename} {e}"),
+        }
+    }
+    Ok(typical_pace(&paces))
+}
+
+async fn route_profile_impl(
+    req: RouteProfileRequest,
+    state: &AppState,
+    gpx: Option<&str>,
+) -> HttpResult<StackString> {
+    let unit: SplitUnit = match &req.units {
+        Some(u) if !u.is_empty() => u.parse().map_err(|e| Error::BadRequest(format!("{e}")))?,
+        _ => SplitUnit::default(),
+    };
+    let pace = req.pace.unwrap_or_default();
+    let profile = if let Some(gpx) = gpx {
+        let course = course_from_gpx(gpx).map_err(|e| Error::BadRequest(format!("{e}")))?;
+        let seconds_per_meter = if pace.is_empty() {
+            recent_grade_adjusted_pace(state).await?.ok_or_else(|| {
+                Error::BadRequest("No recent runs to take a pace from, give a pace".into())
+            })?
+        } else {
+            // a pace is minutes and seconds, not hours and minutes
+            let pace = if pace.matches(':').count() < 2 {
+                format_sstr!("0:{pace}")
+            } else {
+                pace.clone()
+            };
+            convert_time_string(&pace).map_err(|e| Error::BadRequest(format!("{e}")))?
+                / unit.meters()
+        };
+        Some(
+            RouteProfile::from_course(&course, seconds_per_meter, unit)
+                .ok_or_else(|| Error::BadRequest("Route has no length".into()))?,
+        )
+    } else {
+        None
+    };
+    let body = route_profile_body(unit, pace, profile)?.into();
+    Ok(body)
 }
 
 #[derive(RwebResponse)]
-#[response(
-    description = "Heartrate Statistics Update",
-    content = "html",
-    status = "CREATED"
-)]
-struct HeartrateStatisticsUpdateResponse(HtmlBase<StackString, Error>);
+#[response(description = "Route Elevation Profile", content = "html")]
+struct RouteProfileResponse(HtmlBase<StackString, Error>);
 
-#[post("/garmin/fitbit/heartrate_statistics_summary_db")]
-pub async fn heartrate_statistics_summary_db_update(
-    payload: Json<HeartrateStatisticsSummaryDBUpdateRequest>,
+#[get("/garmin/route_profile")]
+pub async fn route_profile(
+    query: Query<RouteProfileRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<HeartrateStatisticsUpdateResponse> {
-    let body = payload
-        .into_inner()
-        .process_updates(&state.db)
-        .await?
-        .join("\n")
-        .into();
+) -> WarpResult<RouteProfileResponse> {
+    let body = route_profile_impl(query.into_inner(), &state, None).await?;
     Ok(HtmlBase::new(body).into())
 }
 
-#[derive(Serialize, Deserialize, Schema)]
-#[schema(component = "RaceResultPlotRequest")]
-struct RaceResultPlotRequest {
-    #[schema(description = "Race Type")]
-    race_type: RaceTypeWrapper,
-    #[schema(description = "Demo Flag")]
-    demo: Option<bool>,
-}
-
-async fn race_result_plot_impl(
-    req: RaceResultPlotRequest,
-    state: AppState,
-    session: Session,
-) -> Result<StackString, Error> {
-    let model = RaceResultAnalysis::run_analysis(req.race_type.into(), &state.db).await?;
-    let demo = req.demo.unwrap_or(true);
-
-    let body = index_new_body(
-        &state.config,
-        &state.db,
-        "".into(),
-        demo,
-        session.history,
-        IndexConfig::RaceResult { model },
-    )
-    .await?
-    .into();
-    Ok(body)
+#[post("/garmin/route_profile")]
+pub async fn route_profile_upload(
+    #[filter = "rweb::multipart::form"] mut form: FormData,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<RouteProfileResponse> {
+    let mut req = RouteProfileRequest::default();
+    let mut gpx: Option<StackString> = None;
+    while let Some(item) = form.next().await {
+        let item = item.map_err(Into::<Error>::into)?;
+        let name: StackString = item.name().into();
+        let value = read_part(item).await.map_err(Into::<Error>::into)?;
+        let value = Some(value).filter(|v| !v.is_empty());
+        match name.as_str() {
+            "units" => req.units = value,
+            "pace" => req.pace = value,
+            "gpx" => gpx = value,
+            _ => {}
+        }
+    }
+    let body = route_profile_impl(req, &state, gpx.as_deref()).await?;
+    Ok(HtmlBase::new(body).into())
 }
 
 #[derive(RwebResponse)]
-#[response(description = "Race Result Plot", content = "html")]
-struct RaceResultPlotResponse(HtmlBase<StackString, Error>);
+#[response(description = "Matched Routes", content = "html")]
+struct MatchedRoutesResponse(HtmlBase<StackString, Error>);
 
-#[get("/garmin/race_result_plot")]
-pub async fn race_result_plot(
-    query: Query<RaceResultPlotRequest>,
-    #[filter = "LoggedUser::filter"] user: LoggedUser,
+#[get("/garmin/routes")]
+#[openapi(description = "Routes Run More Than Once")]
+pub async fn matched_routes(
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<RaceResultPlotResponse> {
-    let mut query = query.into_inner();
-    query.demo = Some(false);
-    let session = user
-        .get_session(&state.client, &state.config)
+) -> WarpResult<MatchedRoutesResponse> {
+    let routes = MatchedRoute::get_all(&state.db)
         .await
         .map_err(Into::<Error>::into)?;
-    let body = race_result_plot_impl(query, state, session).await?;
+    let body = matched_routes_body(routes)?.into();
     Ok(HtmlBase::new(body).into())
 }
 
-#[get("/garmin/race_result_plot_demo")]
-pub async fn race_result_plot_demo(
-    query: Query<RaceResultPlotRequest>,
+#[derive(Serialize, Deserialize, Schema)]
+struct RouteProgressionRequest {
+    #[schema(description = "Matched Route ID")]
+    route_id: UuidWrapper,
+}
+
+#[derive(RwebResponse)]
+#[response(description = "Route Best Time Progression", content = "html")]
+struct RouteProgressionResponse(HtmlBase<StackString, Error>);
+
+#[get("/garmin/routes/progression")]
+#[openapi(description = "Best Time on a Route by Year")]
+pub async fn route_progression(
+    query: Query<RouteProgressionRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-    #[filter = "optional_session"] session: Option<Session>,
-) -> WarpResult<RaceResultPlotResponse> {
-    let mut query = query.into_inner();
-    query.demo = Some(true);
-    let session = session.unwrap_or_default();
-    let body = race_result_plot_impl(query, state, session).await?;
+) -> WarpResult<RouteProgressionResponse> {
+    let body = route_progression_impl(&state, query.into_inner().route_id.into()).await?;
     Ok(HtmlBase::new(body).into())
 }
 
+async fn route_progression_impl(state: &AppState, route_id: Uuid) -> HttpResult<StackString> {
+    let route = MatchedRoute::get_by_id(&state.db, route_id)
+        .await?
+        .ok_or_else(|| Error::BadRequest(format!("No route {route_id}")))?;
+    let attempts = RouteAttempt::get_by_route_id(&state.db, route_id).await?;
+    let bests = best_by_year(&attempts);
+    let svg = progression_svg(&bests)?.unwrap_or_default();
+    let body = route_progression_body(route, bests, svg)?.into();
+    Ok(body)
+}
+
+async fn read_part(field: Part) -> Result<StackString, anyhow::Error> {
+    let mut stream = field.stream();
+    let mut buf = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        buf.extend_from_slice(chunk.chunk());
+    }
+    let s = String::from_utf8(buf)?;
+    Ok(s.into())
+}
+
 #[derive(Serialize, Deserialize, Schema)]
 struct RaceResultFlagRequest {
     id: UuidWrapper,
@@ -1481,6 +4775,138 @@ pub async fn race_result_flag(
     Ok(HtmlBase::new(result).into())
 }
 
+#[derive(RwebResponse)]
+#[response(description = "Race Result Notes", content = "html")]
+struct RaceResultNotesResponse(HtmlBase<StackString, Error>);
+
+#[get("/garmin/race_result_notes")]
+pub async fn race_result_notes(
+    query: Query<RaceResultFlagRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<RaceResultNotesResponse> {
+    let query = query.into_inner();
+    let body = get_race_result_notes_body(&state, query.id.into()).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+async fn get_race_result_notes_body(state: &AppState, id: Uuid) -> HttpResult<StackString> {
+    let result = RaceResults::get_result_by_id(id, &state.db)
+        .await?
+        .ok_or_else(|| Error::BadRequest(format!("No race result {id}")))?;
+    let mut attachments = Vec::new();
+    // attachments are stored in s3, without it only the notes are shown
+    if let Some(gsync) = GarminSync::from_config(&state.config).await {
+        for attachment in RaceAttachment::get_by_race_id(&state.db, id).await? {
+            let url = attachment.get_url(&gsync, &state.config).await?;
+            attachments.push((attachment, url));
+        }
+    }
+    let body = race_result_notes_body(result, attachments)?.into();
+    Ok(body)
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct RaceResultNotesRequest {
+    id: UuidWrapper,
+    bib_number: Option<StackString>,
+    race_report: Option<StackString>,
+}
+
+#[post("/garmin/race_result_notes")]
+pub async fn race_result_notes_update(
+    payload: Json<RaceResultNotesRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<RaceResultNotesResponse> {
+    let payload = payload.into_inner();
+    let id = payload.id.into();
+    let mut result = RaceResults::get_result_by_id(id, &state.db)
+        .await
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest(format!("No race result {id}")))?;
+    result.bib_number = payload.bib_number.filter(|s| !s.trim().is_empty());
+    result.race_report = payload.race_report.filter(|s| !s.trim().is_empty());
+    result
+        .update_db(&state.db)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = get_race_result_notes_body(&state, id).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+#[post("/garmin/race_result_attachment")]
+pub async fn race_result_attachment_upload(
+    query: Query<RaceResultFlagRequest>,
+    #[filter = "rweb::multipart::form"] form: FormData,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<RaceResultNotesResponse> {
+    let id = query.into_inner().id.into();
+    race_result_attachment_upload_body(form, &state, id).await?;
+    let body = get_race_result_notes_body(&state, id).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
+async fn race_result_attachment_upload_body(
+    mut form: FormData,
+    state: &AppState,
+    id: Uuid,
+) -> HttpResult<()> {
+    if RaceResults::get_result_by_id(id, &state.db)
+        .await?
+        .is_none()
+    {
+        return Err(Error::BadRequest(format!("No race result {id}")));
+    }
+    let gsync = require_s3(&state.config).await?;
+    let tempdir = TempDir::with_prefix("garmin_rust")?;
+    let tempdir_str = tempdir.path().to_string_lossy();
+
+    while let Some(item) = form.next().await {
+        let item = item?;
+        let filename: StackString = item.filename().unwrap_or("").into();
+        if filename.is_empty() {
+            return Err(Error::BadRequest("Empty Filename".into()));
+        }
+        let fname = format_sstr!("{tempdir_str}/{filename}");
+        let file_size = save_file(fname.as_str(), item).await?;
+        if file_size == 0 {
+            return Err(Error::BadRequest("Empty File".into()));
+        }
+        let kind = AttachmentKind::from_filename(&filename);
+        RaceAttachment::new(id, kind, &filename)
+            .upload(&state.db, &gsync, &state.config, Path::new(fname.as_str()))
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Schema)]
+struct RaceAttachmentDeleteRequest {
+    id: UuidWrapper,
+}
+
+#[delete("/garmin/race_result_attachment")]
+pub async fn race_result_attachment_delete(
+    query: Query<RaceAttachmentDeleteRequest>,
+    #[filter = "LoggedUser::filter"] _: LoggedUser,
+    #[data] state: AppState,
+) -> WarpResult<RaceResultNotesResponse> {
+    let id = query.into_inner().id.into();
+    let attachment = RaceAttachment::get_by_id(&state.db, id)
+        .await
+        .map_err(Into::<Error>::into)?
+        .ok_or_else(|| Error::BadRequest(format!("No attachment {id}")))?;
+    let gsync = require_s3(&state.config).await?;
+    attachment
+        .delete(&state.db, &gsync, &state.config)
+        .await
+        .map_err(Into::<Error>::into)?;
+    let body = get_race_result_notes_body(&state, attachment.race_id).await?;
+    Ok(HtmlBase::new(body).into())
+}
+
 #[derive(Serialize, Deserialize, Schema)]
 struct RaceResultImportRequest {
     filename: StackString,
@@ -1530,31 +4956,48 @@ pub async fn race_result_import(
 struct RaceResultsDBRequest {
     #[schema(description = "Race Type")]
     race_type: Option<RaceTypeWrapper>,
+    #[schema(description = "Offset")]
+    offset: Option<usize>,
+    #[schema(description = "Limit")]
+    limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    cursor: Option<StackString>,
 }
 
-#[derive(RwebResponse)]
-#[response(description = "Race Results")]
-struct RaceResultsResponse(JsonBase<Vec<RaceResultsWrapper>, Error>);
+#[derive(Debug, Serialize, Deserialize, Schema)]
+#[schema(component = "PaginatedRaceResults")]
+struct PaginatedRaceResults {
+    pagination: Pagination,
+    data: Vec<RaceResultsWrapper>,
+}
+
+impl PaginatedBody for PaginatedRaceResults {
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+}
 
 #[get("/garmin/race_results_db")]
 pub async fn race_results_db(
     query: Query<RaceResultsDBRequest>,
     #[filter = "LoggedUser::filter"] _: LoggedUser,
     #[data] state: AppState,
-) -> WarpResult<RaceResultsResponse> {
+) -> WarpResult<PaginatedJson<PaginatedRaceResults>> {
     let query = query.into_inner();
+    let page = PageRequest::new(query.offset, query.limit, query.cursor.as_deref())?;
 
     let race_type = query.race_type.map_or(RaceType::Personal, Into::into);
-    let mut results: Vec<_> = RaceResults::get_results_by_type(race_type, &state.db)
+    let results: Vec<_> = RaceResults::get_results_by_type(race_type, &state.db)
         .await
         .map_err(Into::<Error>::into)?
         .map_ok(Into::into)
         .try_collect()
         .await
         .map_err(Into::<Error>::into)?;
-    results.shrink_to_fit();
+    let params = [("race_type", format_sstr!("{race_type}"))];
+    let (pagination, data) = page.paginate_list(results, "/garmin/race_results_db", &params)?;
 
-    Ok(JsonBase::new(results).into())
+    Ok(PaginatedJson(PaginatedRaceResults { pagination, data }))
 }
 
 #[derive(Serialize, Deserialize, Schema)]
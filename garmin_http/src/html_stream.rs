@@ -0,0 +1,107 @@
+use anyhow::Error as AnyhowError;
+use futures::Stream;
+use rweb::{
+    http::{
+        header::{CONTENT_TYPE, SET_COOKIE},
+        HeaderValue, StatusCode,
+    },
+    hyper::{body::to_bytes, Body},
+    openapi::{
+        ComponentDescriptor, ComponentOrInlineSchema, Entity, MediaType, Response, ResponseEntity,
+        Responses,
+    },
+    Reply,
+};
+use stack_string::StackString;
+use std::borrow::Cow;
+
+use crate::errors::ServiceError as Error;
+
+/// Html response sent with chunked transfer encoding, pages built from a
+/// stream are written out as they're rendered instead of being held in
+/// memory in full
+pub struct HtmlStream {
+    body: Body,
+    cookie: Option<StackString>,
+}
+
+impl HtmlStream {
+    #[must_use]
+    pub fn new(body: impl Into<Body>) -> Self {
+        Self {
+            body: body.into(),
+            cookie: None,
+        }
+    }
+
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<String, Error>> + Send + 'static,
+    {
+        Self::new(Body::wrap_stream(stream))
+    }
+
+    #[must_use]
+    pub fn with_cookie(mut self, cookie: &str) -> Self {
+        self.cookie = Some(cookie.into());
+        self
+    }
+
+    /// Render the whole page, for callers which embed it in another
+    /// response
+    /// # Errors
+    /// Return error if rendering fails or the page isn't utf8
+    pub async fn into_string(self) -> Result<String, Error> {
+        let bytes = to_bytes(self.body).await.map_err(AnyhowError::from)?;
+        String::from_utf8(bytes.to_vec()).map_err(Into::into)
+    }
+}
+
+impl Reply for HtmlStream {
+    fn into_response(self) -> rweb::reply::Response {
+        let mut response = rweb::reply::Response::new(self.body);
+        let headers = response.headers_mut();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        if let Some(cookie) = self.cookie {
+            if let Ok(value) = HeaderValue::from_str(&cookie) {
+                headers.insert(SET_COOKIE, value);
+            }
+        }
+        response
+    }
+}
+
+impl Entity for HtmlStream {
+    fn type_name() -> Cow<'static, str> {
+        String::type_name()
+    }
+    fn describe(comp_d: &mut ComponentDescriptor) -> ComponentOrInlineSchema {
+        String::describe(comp_d)
+    }
+}
+
+impl ResponseEntity for HtmlStream {
+    fn describe_responses(comp_d: &mut ComponentDescriptor) -> Responses {
+        let mut map = Error::describe_responses(comp_d);
+        let mut content = Response::default().content;
+        content.insert(
+            Cow::Borrowed("text/html"),
+            MediaType {
+                schema: Some(String::describe(comp_d)),
+                ..MediaType::default()
+            },
+        );
+        map.insert(
+            Cow::Owned(StatusCode::OK.as_str().into()),
+            Response {
+                description: Cow::Borrowed("Main Page"),
+                content,
+                ..Response::default()
+            },
+        );
+        map
+    }
+}
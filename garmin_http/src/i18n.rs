@@ -0,0 +1,136 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use garmin_lib::language::Language;
+
+/// Every translatable UI string, in English, catalogs in `templates/i18n`
+/// map these to their translation
+pub const MESSAGES: &[&str] = &[
+    // buttons
+    "Sync with S3",
+    "Strava Athlete",
+    "Scale sync",
+    "Quarantine",
+    "Admin Stats",
+    "Coverage Gaps",
+    "Cleanup",
+    "Milestones",
+    "Travel Map",
+    "Region Map",
+    "Most Kudos",
+    "Trips",
+    "Yearly Comparison",
+    "Training Pattern",
+    "Heart Rate Zones",
+    "Clothing",
+    "Integrations",
+    "Link Review",
+    "Scale Plots",
+    "Heart Rate Stats",
+    "Heart Rate Plots",
+    "Race Result Plot",
+    "Power Curve",
+    "latest",
+    "sport",
+    "Language",
+    // report table headers
+    "Start Time",
+    "Sport",
+    "Garmin Steps",
+    "Kudos",
+    "Comments",
+    "Avg Temp",
+    "Split",
+    "Lap",
+    "Distance",
+    "Duration",
+    "Calories",
+    "Time",
+    "Heart Rate",
+    "Marathon Time",
+    // units labels
+    "Pace / 100m",
+    "Pace / mi",
+    "Pace / km",
+    // table captions
+    "Activity summary",
+    "Laps",
+    "Splits",
+];
+
+type Catalog = HashMap<&'static str, &'static str>;
+
+fn catalog_source(language: Language) -> &'static str {
+    match language {
+        Language::English => "",
+        Language::German => include_str!("../../templates/i18n/de.txt"),
+        Language::Spanish => include_str!("../../templates/i18n/es.txt"),
+    }
+}
+
+/// Parse `english = translation` lines, blank lines and lines starting with
+/// `#` are skipped
+fn parse_catalog(source: &'static str) -> Catalog {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (message, translation) = line.split_once(" = ")?;
+            Some((message.trim(), translation.trim()))
+        })
+        .collect()
+}
+
+fn catalogs() -> &'static HashMap<Language, Catalog> {
+    static CATALOGS: OnceLock<HashMap<Language, Catalog>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        Language::all()
+            .iter()
+            .map(|&language| (language, parse_catalog(catalog_source(language))))
+            .collect()
+    })
+}
+
+/// `message` in `language`, untranslated strings fall back to English
+#[must_use]
+pub fn tr(language: Language, message: &'static str) -> &'static str {
+    catalogs()
+        .get(&language)
+        .and_then(|catalog| catalog.get(message))
+        .copied()
+        .unwrap_or(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use garmin_lib::language::Language;
+
+    use crate::i18n::{catalog_source, parse_catalog, tr, MESSAGES};
+
+    #[test]
+    fn test_catalogs_complete() {
+        let messages: BTreeSet<_> = MESSAGES.iter().copied().collect();
+        assert_eq!(messages.len(), MESSAGES.len());
+        for language in Language::all() {
+            if language == Language::English {
+                continue;
+            }
+            let catalog = parse_catalog(catalog_source(language));
+            let translated: BTreeSet<_> = catalog.keys().copied().collect();
+            let missing: Vec<_> = messages.difference(&translated).collect();
+            let unknown: Vec<_> = translated.difference(&messages).collect();
+            assert!(missing.is_empty(), "{language} missing {missing:?}");
+            assert!(unknown.is_empty(), "{language} unknown {unknown:?}");
+        }
+    }
+
+    #[test]
+    fn test_tr() {
+        assert_eq!(tr(Language::English, "Distance"), "Distance");
+        assert_eq!(tr(Language::German, "Distance"), "Distanz");
+        assert_eq!(tr(Language::Spanish, "Heart Rate"), "Frecuencia cardíaca");
+        assert_eq!(tr(Language::German, "not translated"), "not translated");
+    }
+}
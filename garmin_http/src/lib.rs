@@ -11,12 +11,17 @@
 #![allow(clippy::ignored_unit_patterns)]
 
 pub mod errors;
+pub mod file_download;
 pub mod garmin_elements;
 pub mod garmin_file_report_html;
 pub mod garmin_requests;
 pub mod garmin_rust_app;
 pub mod garmin_rust_routes;
+pub mod html_stream;
+pub mod i18n;
 pub mod logged_user;
+pub mod pagination;
+pub mod resumable_upload;
 pub mod sport_types_wrapper;
 
 use derive_more::{From, Into};
@@ -31,13 +36,19 @@ use std::{borrow::Cow, collections::HashMap};
 
 use fitbit_lib::{
     fitbit_heartrate::{FitbitBodyWeightFat, FitbitHeartRate},
+    fitbit_intraday::IntradayMinute,
     fitbit_statistics_summary::FitbitStatisticsSummary,
-    scale_measurement::ScaleMeasurement,
+    scale_measurement::{ScaleMeasurement, ScaleMeasurementWithMetrics},
 };
 use garmin_lib::strava_timezone::StravaTimeZone;
 use garmin_models::{
-    fitbit_activity::FitbitActivity, garmin_connect_activity::GarminConnectActivity,
+    activity_location::{CityVisit, RegionVisit},
+    filter_history::FilterHistory,
+    fitbit_activity::FitbitActivity,
+    garmin_connect_activity::GarminConnectActivity,
+    provenance::ProvenanceEntry,
     strava_activity::StravaActivity,
+    sync_status::SyncStatus,
 };
 use race_result_analysis::{race_results::RaceResults, race_type::RaceType};
 
@@ -75,6 +86,25 @@ struct _FitbitHeartRateWrapper {
     value: i32,
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Into, From)]
+pub struct IntradayMinuteWrapper(IntradayMinute);
+
+derive_rweb_schema!(IntradayMinuteWrapper, _IntradayMinuteWrapper);
+
+#[allow(dead_code)]
+#[derive(Schema)]
+#[schema(component = "IntradayMinute")]
+struct _IntradayMinuteWrapper {
+    #[schema(description = "DateTime")]
+    datetime: DateTimeType,
+    #[schema(description = "Mean Heartrate (bpm)")]
+    heartrate: Option<i32>,
+    #[schema(description = "Steps")]
+    steps: Option<f64>,
+    #[schema(description = "Calories")]
+    calories: Option<f64>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Into, From)]
 pub struct StravaActivityWrapper(StravaActivity);
 
@@ -84,17 +114,17 @@ derive_rweb_schema!(StravaActivityWrapper, _StravaActivityWrapper);
 #[derive(Schema)]
 #[schema(component = "StravaActivity")]
 struct _StravaActivityWrapper {
-    #[schema(description = "Activity Name")]
+    #[schema(description = "Activity Name", example = r#""Morning Run""#)]
     name: StackString,
     #[schema(description = "Start Date")]
     start_date: DateTimeType,
-    #[schema(description = "Activity ID")]
+    #[schema(description = "Activity ID", example = r#""10234567890""#)]
     id: i64,
-    #[schema(description = "Distance (m)")]
+    #[schema(description = "Distance (m)", example = r#""8046.7""#)]
     distance: Option<f64>,
-    #[schema(description = "Moving Time (s)")]
+    #[schema(description = "Moving Time (s)", example = r#""2520""#)]
     moving_time: Option<i64>,
-    #[schema(description = "Elapsed Time (s)")]
+    #[schema(description = "Elapsed Time (s)", example = r#""2580""#)]
     elapsed_time: i64,
     #[schema(description = "Total Elevation Gain (m)")]
     total_elevation_gain: Option<f64>,
@@ -106,6 +136,16 @@ struct _StravaActivityWrapper {
     activity_type: SportTypesWrapper,
     #[schema(description = "Time Zone")]
     timezone: StravaTimeZoneWrapper,
+    #[schema(description = "Number of Kudos")]
+    kudos_count: i32,
+    #[schema(description = "Number of Comments")]
+    comment_count: i32,
+    #[schema(description = "Number of Photos")]
+    total_photo_count: i32,
+    #[schema(description = "Photo Urls")]
+    photo_urls: Vec<StackString>,
+    #[schema(description = "Linked Strava Account")]
+    strava_account: Option<StackString>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Into, From)]
@@ -148,6 +188,46 @@ struct _ScaleMeasurementWrapper {
     muscle_pct: f64,
     #[schema(description = "Bone %")]
     bone_pct: f64,
+    #[schema(description = "Garmin Connect Primary Key")]
+    connect_primary_key: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq, Into, From)]
+pub struct ScaleMeasurementWithMetricsWrapper(ScaleMeasurementWithMetrics);
+
+derive_rweb_schema!(
+    ScaleMeasurementWithMetricsWrapper,
+    _ScaleMeasurementWithMetricsWrapper
+);
+
+#[allow(dead_code)]
+#[derive(Schema)]
+#[schema(component = "ScaleMeasurementWithMetrics")]
+struct _ScaleMeasurementWithMetricsWrapper {
+    #[schema(description = "Scale Measurement ID")]
+    id: UuidWrapper,
+    #[schema(description = "DateTime")]
+    datetime: DateTimeType,
+    #[schema(description = "Mass (lbs)")]
+    mass: f64,
+    #[schema(description = "Fat %")]
+    fat_pct: f64,
+    #[schema(description = "Water %")]
+    water_pct: f64,
+    #[schema(description = "Muscle %")]
+    muscle_pct: f64,
+    #[schema(description = "Bone %")]
+    bone_pct: f64,
+    #[schema(description = "Garmin Connect Primary Key")]
+    connect_primary_key: Option<i64>,
+    #[schema(description = "BMI (kg/m^2)")]
+    bmi: f64,
+    #[schema(description = "Fat Mass (lbs)")]
+    fat_mass: Option<f64>,
+    #[schema(description = "Lean Mass (lbs)")]
+    lean_mass: Option<f64>,
+    #[schema(description = "Fat Free Mass Index (kg/m^2)")]
+    ffmi: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Into, From)]
@@ -164,13 +244,16 @@ struct _FitbitActivityWrapper {
     start_time: DateTimeType,
     #[schema(description = "TCX Link")]
     tcx_link: Option<StackString>,
-    #[schema(description = "Activity Type ID")]
+    #[schema(
+        description = "Activity Type ID, see /garmin/meta/activity_types",
+        example = r#""90009""#
+    )]
     activity_type_id: Option<i64>,
-    #[schema(description = "Activity Name")]
+    #[schema(description = "Activity Name", example = r#""Run""#)]
     activity_name: Option<StackString>,
-    #[schema(description = "Duration (ms)")]
+    #[schema(description = "Duration (ms)", example = r#""2520000""#)]
     duration: i64,
-    #[schema(description = "Distance (mi)")]
+    #[schema(description = "Distance (mi)", example = r#""5.0""#)]
     distance: Option<f64>,
     #[schema(description = "Distance Unit")]
     distance_unit: Option<StackString>,
@@ -189,9 +272,9 @@ derive_rweb_schema!(GarminConnectActivityWrapper, _GarminConnectActivityWrapper)
 #[derive(Schema)]
 #[schema(component = "GarminConnectActivity")]
 struct _GarminConnectActivityWrapper {
-    #[schema(description = "Activity ID")]
+    #[schema(description = "Activity ID", example = r#""12345678901""#)]
     activity_id: i64,
-    #[schema(description = "Activity Name")]
+    #[schema(description = "Activity Name", example = r#""Morning Run""#)]
     activity_name: Option<StackString>,
     #[schema(description = "Description")]
     description: Option<StackString>,
@@ -284,6 +367,10 @@ struct _RaceResultsWrapper {
     race_flag: bool,
     #[schema(description = "Race Summary IDs")]
     race_summary_ids: Vec<Option<UuidWrapper>>,
+    #[schema(description = "Bib Number")]
+    bib_number: Option<StackString>,
+    #[schema(description = "Race Report")]
+    race_report: Option<StackString>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Into, From, Eq)]
@@ -295,25 +382,147 @@ derive_rweb_schema!(FitbitActivityTypesWrapper, _FitbitActivityTypesWrapper);
 #[derive(Schema)]
 struct _FitbitActivityTypesWrapper(HashMap<String, StackString>);
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Into, From, Eq)]
+pub struct FilterHistoryWrapper(FilterHistory);
+
+derive_rweb_schema!(FilterHistoryWrapper, _FilterHistoryWrapper);
+
+#[allow(dead_code)]
+#[derive(Schema)]
+#[schema(component = "FilterHistory")]
+struct _FilterHistoryWrapper {
+    #[schema(description = "Email Address")]
+    email: StackString,
+    #[schema(description = "Filter")]
+    filter: StackString,
+    #[schema(description = "Pinned")]
+    pinned: bool,
+    #[schema(description = "Last Used")]
+    last_used: DateTimeType,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Into, From, Eq)]
+pub struct SyncStatusWrapper(SyncStatus);
+
+derive_rweb_schema!(SyncStatusWrapper, _SyncStatusWrapper);
+
+#[allow(dead_code)]
+#[derive(Schema)]
+#[schema(component = "SyncStatus")]
+struct _SyncStatusWrapper {
+    #[schema(description = "Sync Job")]
+    job: StackString,
+    #[schema(description = "Last Success")]
+    last_success: Option<DateTimeType>,
+    #[schema(description = "Last Failure")]
+    last_failure: Option<DateTimeType>,
+    #[schema(description = "Last Failure Message")]
+    message: Option<StackString>,
+    #[schema(description = "Last Missing Sync Alert")]
+    last_alert: Option<DateTimeType>,
+    #[schema(description = "Failures Since the Last Success")]
+    failure_count: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Into, From)]
+pub struct CityVisitWrapper(CityVisit);
+
+derive_rweb_schema!(CityVisitWrapper, _CityVisitWrapper);
+
+#[allow(dead_code)]
+#[derive(Schema)]
+#[schema(component = "CityVisit")]
+struct _CityVisitWrapper {
+    #[schema(description = "City")]
+    city: StackString,
+    #[schema(description = "Region")]
+    region: Option<StackString>,
+    #[schema(description = "Country")]
+    country: Option<StackString>,
+    #[schema(description = "Average Start Latitude")]
+    latitude: f64,
+    #[schema(description = "Average Start Longitude")]
+    longitude: f64,
+    #[schema(description = "Number of Activities")]
+    activities: i64,
+    #[schema(description = "First Activity")]
+    first_visit: DateTimeType,
+    #[schema(description = "Last Activity")]
+    last_visit: DateTimeType,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Into, From)]
+pub struct RegionVisitWrapper(RegionVisit);
+
+derive_rweb_schema!(RegionVisitWrapper, _RegionVisitWrapper);
+
+#[allow(dead_code)]
+#[derive(Schema)]
+#[schema(component = "RegionVisit")]
+struct _RegionVisitWrapper {
+    #[schema(description = "Country or State")]
+    name: StackString,
+    #[schema(description = "Number of Activities")]
+    activities: i64,
+    #[schema(description = "First Activity")]
+    first_visit: DateTimeType,
+    #[schema(description = "Last Activity")]
+    last_visit: DateTimeType,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Into, From)]
+pub struct ProvenanceEntryWrapper(ProvenanceEntry);
+
+derive_rweb_schema!(ProvenanceEntryWrapper, _ProvenanceEntryWrapper);
+
+#[allow(dead_code)]
+#[derive(Schema)]
+#[schema(component = "ProvenanceEntry")]
+struct _ProvenanceEntryWrapper {
+    #[schema(description = "Record Kind")]
+    kind: StackString,
+    #[schema(
+        description = "Activity Filename or Id, Stream Source, Date, Measurement Id or Datetime"
+    )]
+    record_key: StackString,
+    #[schema(description = "Provider")]
+    provider: StackString,
+    #[schema(description = "Import Command or Endpoint")]
+    import_path: StackString,
+    #[schema(description = "Raw File")]
+    raw_file: Option<StackString>,
+    #[schema(description = "Imported At")]
+    imported_at: DateTimeType,
+}
+
 #[cfg(test)]
 mod test {
     use rweb_helper::derive_rweb_test;
 
     use crate::{
-        FitbitActivityWrapper, FitbitBodyWeightFatWrapper, FitbitHeartRateWrapper,
-        FitbitStatisticsSummaryWrapper, GarminConnectActivityWrapper, RaceResultsWrapper,
-        RaceTypeWrapper, ScaleMeasurementWrapper, StravaActivityWrapper, _FitbitActivityWrapper,
-        _FitbitBodyWeightFatWrapper, _FitbitHeartRateWrapper, _FitbitStatisticsSummaryWrapper,
-        _GarminConnectActivityWrapper, _RaceResultsWrapper, _RaceTypeWrapper,
-        _ScaleMeasurementWrapper, _StravaActivityWrapper,
+        CityVisitWrapper, FilterHistoryWrapper, FitbitActivityWrapper, FitbitBodyWeightFatWrapper,
+        FitbitHeartRateWrapper, FitbitStatisticsSummaryWrapper, GarminConnectActivityWrapper,
+        IntradayMinuteWrapper, ProvenanceEntryWrapper, RaceResultsWrapper, RaceTypeWrapper,
+        RegionVisitWrapper, ScaleMeasurementWithMetricsWrapper, ScaleMeasurementWrapper,
+        StravaActivityWrapper, SyncStatusWrapper, _CityVisitWrapper, _FilterHistoryWrapper,
+        _FitbitActivityWrapper, _FitbitBodyWeightFatWrapper, _FitbitHeartRateWrapper,
+        _FitbitStatisticsSummaryWrapper, _GarminConnectActivityWrapper, _IntradayMinuteWrapper,
+        _ProvenanceEntryWrapper, _RaceResultsWrapper, _RaceTypeWrapper, _RegionVisitWrapper,
+        _ScaleMeasurementWithMetricsWrapper, _ScaleMeasurementWrapper, _StravaActivityWrapper,
+        _SyncStatusWrapper,
     };
 
     #[test]
     fn test_types() {
         derive_rweb_test!(FitbitHeartRateWrapper, _FitbitHeartRateWrapper);
+        derive_rweb_test!(IntradayMinuteWrapper, _IntradayMinuteWrapper);
         derive_rweb_test!(StravaActivityWrapper, _StravaActivityWrapper);
         derive_rweb_test!(FitbitBodyWeightFatWrapper, _FitbitBodyWeightFatWrapper);
         derive_rweb_test!(ScaleMeasurementWrapper, _ScaleMeasurementWrapper);
+        derive_rweb_test!(
+            ScaleMeasurementWithMetricsWrapper,
+            _ScaleMeasurementWithMetricsWrapper
+        );
         derive_rweb_test!(FitbitActivityWrapper, _FitbitActivityWrapper);
         derive_rweb_test!(GarminConnectActivityWrapper, _GarminConnectActivityWrapper);
         derive_rweb_test!(
@@ -322,5 +531,10 @@ mod test {
         );
         derive_rweb_test!(RaceTypeWrapper, _RaceTypeWrapper);
         derive_rweb_test!(RaceResultsWrapper, _RaceResultsWrapper);
+        derive_rweb_test!(FilterHistoryWrapper, _FilterHistoryWrapper);
+        derive_rweb_test!(SyncStatusWrapper, _SyncStatusWrapper);
+        derive_rweb_test!(CityVisitWrapper, _CityVisitWrapper);
+        derive_rweb_test!(RegionVisitWrapper, _RegionVisitWrapper);
+        derive_rweb_test!(ProvenanceEntryWrapper, _ProvenanceEntryWrapper);
     }
 }
@@ -22,7 +22,8 @@ use time::OffsetDateTime;
 use url::Url;
 use uuid::Uuid;
 
-use garmin_lib::garmin_config::GarminConfig;
+use garmin_lib::{garmin_config::GarminConfig, language::Language};
+use garmin_models::{filter_history::FilterHistory, user_preferences::UserPreferences};
 use garmin_utils::{garmin_util::AuthorizedUsers, pgpool::PgPool};
 
 use crate::errors::ServiceError as Error;
@@ -62,18 +63,32 @@ impl LoggedUser {
             })
     }
 
+    /// History is read from the `filter_history` table, the session service
+    /// is only consulted (and its history imported) when the table has no
+    /// entries for this user
     /// # Errors
-    /// Returns error if api call fails
+    /// Returns error if api call or db query fails
     pub async fn get_session(
         &self,
         client: &Client,
         config: &GarminConfig,
+        pool: &PgPool,
     ) -> Result<Session, anyhow::Error> {
         #[derive(Deserialize, Debug)]
         struct SessionResponse {
             history: Option<Vec<StackString>>,
         }
 
+        let language = UserPreferences::get_language(pool, &self.email).await?;
+        let history = FilterHistory::get_by_email(pool, &self.email).await?;
+        if !history.is_empty() {
+            let session: Session = history.into();
+            return Ok(Session {
+                language,
+                ..session
+            });
+        }
+
         let base_url: Url = format_sstr!("https://{}", config.domain).parse()?;
         let session: Option<SessionResponse> = ExternalUser::get_session_data(
             &base_url,
@@ -85,12 +100,15 @@ impl LoggedUser {
         .await?;
 
         debug!("Got session {:?}", session);
-        match session {
-            Some(session) => Ok(Session {
-                history: session.history.unwrap_or_default(),
-            }),
-            None => Ok(Session::default()),
+        let history = session.and_then(|s| s.history).unwrap_or_default();
+        for filter in &history {
+            FilterHistory::add(pool, &self.email, filter).await?;
         }
+        Ok(Session {
+            history,
+            pinned: Vec::new(),
+            language,
+        })
     }
 
     /// # Errors
@@ -149,9 +167,34 @@ impl FromStr for LoggedUser {
     }
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
+#[derive(Default, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Session {
     pub history: Vec<StackString>,
+    #[serde(default)]
+    pub pinned: Vec<StackString>,
+    #[serde(default)]
+    pub language: Language,
+}
+
+/// Prefix of the language entry of the session cookie
+const LANGUAGE_PREFIX: &str = "lang:";
+
+impl From<Vec<FilterHistory>> for Session {
+    fn from(entries: Vec<FilterHistory>) -> Self {
+        let mut history = Vec::with_capacity(entries.len());
+        let mut pinned = Vec::new();
+        for entry in entries {
+            if entry.pinned {
+                pinned.push(entry.filter.clone());
+            }
+            history.push(entry.filter);
+        }
+        Self {
+            history,
+            pinned,
+            language: Language::default(),
+        }
+    }
 }
 
 impl FromStr for Session {
@@ -159,16 +202,29 @@ impl FromStr for Session {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let data = STANDARD.decode(s)?;
         let history_str = String::from_utf8(data)?;
-        let mut history: Vec<_> = history_str.split(';').map(Into::into).collect();
+        let mut entries = history_str.split(';').peekable();
+        let language = entries
+            .next_if(|entry| entry.starts_with(LANGUAGE_PREFIX))
+            .and_then(|entry| entry.strip_prefix(LANGUAGE_PREFIX)?.parse().ok())
+            .unwrap_or_default();
+        let mut history: Vec<_> = entries.map(Into::into).collect();
         history.shrink_to_fit();
-        Ok(Session { history })
+        Ok(Session {
+            history,
+            pinned: Vec::new(),
+            language,
+        })
     }
 }
 
 impl Session {
     #[must_use]
     pub fn get_jwt_cookie(&self, domain: &str) -> Cookie<'static> {
-        let history_str = self.history.join(";");
+        let mut history_str = self.history.join(";");
+        // cookies of english sessions keep the format from before languages
+        if self.language != Language::default() {
+            history_str = format!("{LANGUAGE_PREFIX}{};{history_str}", self.language);
+        }
         let token = STANDARD.encode(history_str);
         Cookie::build(("session", token))
             .http_only(true)
@@ -224,3 +280,33 @@ pub async fn fill_from_db(pool: &PgPool) -> Result<(), Error> {
     debug!("AUTHORIZED_USERS {:?}", *AUTHORIZED_USERS);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use garmin_lib::language::Language;
+
+    use crate::logged_user::Session;
+
+    #[test]
+    fn test_session_cookie_language() -> Result<(), Error> {
+        let session = Session {
+            history: vec!["latest".into(), "2024-05,week".into()],
+            pinned: Vec::new(),
+            language: Language::German,
+        };
+        let cookie = session.get_jwt_cookie("localhost");
+        let session_from_cookie: Session = cookie.value().parse()?;
+        assert_eq!(session_from_cookie, session);
+
+        let session = Session {
+            language: Language::English,
+            ..session
+        };
+        let cookie = session.get_jwt_cookie("localhost");
+        let session_from_cookie: Session = cookie.value().parse()?;
+        assert_eq!(session_from_cookie, session);
+        Ok(())
+    }
+}
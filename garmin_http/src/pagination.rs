@@ -0,0 +1,442 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rweb::{
+    http::{
+        header::{CONTENT_TYPE, LINK},
+        HeaderValue, StatusCode,
+    },
+    openapi::{
+        ComponentDescriptor, ComponentOrInlineSchema, Entity, MediaType, Response, ResponseEntity,
+        Responses,
+    },
+    Reply, Schema,
+};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::borrow::Cow;
+use time::OffsetDateTime;
+use url::form_urlencoded::Serializer;
+
+use garmin_models::keyset::{KeysetRow, PageStart};
+
+use crate::errors::ServiceError as Error;
+
+pub const DEFAULT_LIMIT: usize = 10;
+pub const MAX_LIMIT: usize = 1000;
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Paging parameters of list endpoints which take no filters
+#[derive(Debug, Serialize, Deserialize, Schema)]
+pub struct PageQuery {
+    #[schema(description = "Offset")]
+    pub offset: Option<usize>,
+    #[schema(description = "Limit")]
+    pub limit: Option<usize>,
+    #[schema(description = "Page Cursor, takes precedence over offset")]
+    pub cursor: Option<StackString>,
+}
+
+impl PageQuery {
+    /// # Errors
+    /// Return error if the parameters are invalid, see `PageRequest::new`
+    pub fn page(&self) -> Result<PageRequest, Error> {
+        PageRequest::new(self.offset, self.limit, self.cursor.as_deref())
+    }
+}
+
+/// Start and limit of the page requested from a list endpoint, the start
+/// comes either from `offset` or from the opaque `cursor` handed out in the
+/// links of a previous page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRequest {
+    pub start: PageStart,
+    pub limit: usize,
+}
+
+impl PageRequest {
+    /// Limits above `MAX_LIMIT` are clamped to it
+    /// # Errors
+    /// Return error if `limit` is zero or if `cursor` wasn't produced by
+    /// `encode_cursor`
+    pub fn new(
+        offset: Option<usize>,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Self, Error> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+        if limit == 0 {
+            return Err(Error::BadRequest("limit must be at least 1".into()));
+        }
+        let start = match cursor {
+            Some(cursor) => decode_cursor(cursor)?,
+            None => PageStart::Offset(offset.unwrap_or(0)),
+        };
+        Ok(Self { start, limit })
+    }
+
+    /// Pagination of the page `rows` out of `total` entries, links point at
+    /// `path` and repeat the filters in `params` so following them keeps the
+    /// same selection.  The next page continues after the keyset of the last
+    /// row, pages reached that way don't know their offset so they only link
+    /// forward
+    #[must_use]
+    pub fn paginate<R: KeysetRow>(
+        self,
+        total: usize,
+        rows: &[R],
+        path: &str,
+        params: &[(&str, StackString)],
+    ) -> Pagination {
+        let Self { start, limit } = self;
+        let offset = start.offset();
+        let link = |start: PageStart| page_url(path, params, start, limit);
+        let last_offset = total.saturating_sub(1) / limit * limit;
+        let has_next = rows.len() == limit
+            && match offset {
+                Some(o) => o + limit < total,
+                None => true,
+            };
+        Pagination {
+            total,
+            offset,
+            limit,
+            first: link(PageStart::Offset(0)),
+            last: link(PageStart::Offset(last_offset)),
+            prev: offset
+                .filter(|o| *o > 0)
+                .map(|o| link(PageStart::Offset(o.saturating_sub(limit)))),
+            next: rows
+                .last()
+                .filter(|_| has_next)
+                .map(|row| link(row.page_after())),
+        }
+    }
+
+    /// Page of `rows` for endpoints which load their whole list at once, such
+    /// pages are only addressed by offset
+    /// # Errors
+    /// Return error if the request continues after a keyset
+    pub fn paginate_list<T>(
+        self,
+        rows: Vec<T>,
+        path: &str,
+        params: &[(&str, StackString)],
+    ) -> Result<(Pagination, Vec<T>), Error> {
+        let Self { start, limit } = self;
+        let offset = start
+            .offset()
+            .ok_or_else(|| Error::BadRequest(format!("{path} doesn't support keyset cursors")))?;
+        let total = rows.len();
+        let link = |offset: usize| page_url(path, params, PageStart::Offset(offset), limit);
+        let pagination = Pagination {
+            total,
+            offset: Some(offset),
+            limit,
+            first: link(0),
+            last: link(total.saturating_sub(1) / limit * limit),
+            prev: (offset > 0).then(|| link(offset.saturating_sub(limit))),
+            next: (offset + limit < total).then(|| link(offset + limit)),
+        };
+        let rows = rows.into_iter().skip(offset).take(limit).collect();
+        Ok((pagination, rows))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Schema)]
+#[schema(component = "Pagination")]
+pub struct Pagination {
+    #[schema(description = "Total Number of Entries")]
+    pub total: usize,
+    #[schema(description = "Number of Entries Skipped, absent for pages reached by keyset")]
+    pub offset: Option<usize>,
+    #[schema(description = "Number of Entries Returned")]
+    pub limit: usize,
+    #[schema(description = "Link to the First Page")]
+    pub first: StackString,
+    #[schema(description = "Link to the Last Page")]
+    pub last: StackString,
+    #[schema(description = "Link to the Previous Page")]
+    pub prev: Option<StackString>,
+    #[schema(description = "Link to the Next Page")]
+    pub next: Option<StackString>,
+}
+
+impl Pagination {
+    /// RFC5988 `Link` header value
+    #[must_use]
+    pub fn link_header(&self) -> StackString {
+        let links = [
+            Some((&self.first, "first")),
+            self.prev.as_ref().map(|l| (l, "prev")),
+            self.next.as_ref().map(|l| (l, "next")),
+            Some((&self.last, "last")),
+        ];
+        let links: Vec<_> = links
+            .iter()
+            .filter_map(|link| link.map(|(url, rel)| format_sstr!("<{url}>; rel=\"{rel}\"")))
+            .collect();
+        links.join(", ").into()
+    }
+}
+
+/// Opaque cursor of `start`, an offset or a `datetime:id` keyset
+#[must_use]
+pub fn encode_cursor(start: PageStart) -> StackString {
+    let cursor = match start {
+        PageStart::Offset(offset) => format_sstr!("{offset}"),
+        PageStart::After { datetime, id } => {
+            format_sstr!("{}:{id}", datetime.unix_timestamp_nanos())
+        }
+    };
+    URL_SAFE_NO_PAD.encode(cursor).into()
+}
+
+/// # Errors
+/// Return error if `cursor` wasn't produced by `encode_cursor`
+pub fn decode_cursor(cursor: &str) -> Result<PageStart, Error> {
+    let bad_cursor = || Error::BadRequest(format!("invalid cursor {cursor}"));
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| bad_cursor())?;
+    let cursor = std::str::from_utf8(&bytes).map_err(|_| bad_cursor())?;
+    match cursor.split_once(':') {
+        None => cursor.parse().map(PageStart::Offset).ok(),
+        Some((nanos, id)) => nanos
+            .parse()
+            .ok()
+            .and_then(|nanos| OffsetDateTime::from_unix_timestamp_nanos(nanos).ok())
+            .zip(id.parse().ok())
+            .map(|(datetime, id)| PageStart::After { datetime, id }),
+    }
+    .ok_or_else(bad_cursor)
+}
+
+fn page_url(
+    path: &str,
+    params: &[(&str, StackString)],
+    start: PageStart,
+    limit: usize,
+) -> StackString {
+    let mut query = Serializer::new(String::new());
+    for (key, value) in params {
+        query.append_pair(key, value);
+    }
+    query.append_pair("cursor", &encode_cursor(start));
+    query.append_pair("limit", &format_sstr!("{limit}"));
+    format_sstr!("{path}?{}", query.finish())
+}
+
+/// Body of a paginated list endpoint
+pub trait PaginatedBody: Serialize + Entity {
+    fn pagination(&self) -> &Pagination;
+}
+
+/// Json response of a list endpoint, the pagination of the body is repeated
+/// in the `Link` and `X-Total-Count` headers
+pub struct PaginatedJson<T>(pub T);
+
+impl<T: PaginatedBody> Reply for PaginatedJson<T> {
+    fn into_response(self) -> rweb::reply::Response {
+        let body = match serde_json::to_vec(&self.0) {
+            Ok(body) => body,
+            Err(_) => {
+                let mut response = rweb::reply::Response::new(Vec::new().into());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                return response;
+            }
+        };
+        let pagination = self.0.pagination();
+        let mut response = rweb::reply::Response::new(body.into());
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(pagination.total));
+        if let Ok(value) = HeaderValue::from_str(&pagination.link_header()) {
+            headers.insert(LINK, value);
+        }
+        response
+    }
+}
+
+impl<T: Entity> Entity for PaginatedJson<T> {
+    fn type_name() -> Cow<'static, str> {
+        T::type_name()
+    }
+    fn describe(comp_d: &mut ComponentDescriptor) -> ComponentOrInlineSchema {
+        T::describe(comp_d)
+    }
+}
+
+impl<T: Entity> ResponseEntity for PaginatedJson<T> {
+    fn describe_responses(comp_d: &mut ComponentDescriptor) -> Responses {
+        let mut map = Error::describe_responses(comp_d);
+        let mut content = Response::default().content;
+        content.insert(
+            Cow::Borrowed("application/json"),
+            MediaType {
+                schema: Some(Self::describe(comp_d)),
+                ..MediaType::default()
+            },
+        );
+        map.insert(
+            Cow::Owned(StatusCode::OK.as_str().into()),
+            Response {
+                description: Cow::Borrowed("Paginated List"),
+                content,
+                ..Response::default()
+            },
+        );
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stack_string::StackString;
+    use time::macros::datetime;
+
+    use garmin_models::keyset::{KeysetRow, PageStart};
+
+    use crate::pagination::{decode_cursor, encode_cursor, PageRequest, MAX_LIMIT};
+
+    struct Row(i64);
+
+    impl KeysetRow for Row {
+        fn page_after(&self) -> PageStart {
+            PageStart::After {
+                datetime: datetime!(2024-01-01 00:00:00 UTC),
+                id: self.0,
+            }
+        }
+    }
+
+    #[test]
+    fn test_cursor() {
+        for start in [
+            PageStart::Offset(0),
+            PageStart::Offset(1234),
+            Row(98765).page_after(),
+            PageStart::After {
+                datetime: datetime!(1969-07-20 20:17:40.5 UTC),
+                id: -1,
+            },
+        ] {
+            assert_eq!(decode_cursor(&encode_cursor(start)).unwrap(), start);
+        }
+        assert!(decode_cursor("not a cursor").is_err());
+        assert!(decode_cursor(&encode_cursor(PageStart::Offset(12))[1..]).is_err());
+    }
+
+    #[test]
+    fn test_page_request() {
+        let page = PageRequest::new(None, None, None).unwrap();
+        assert_eq!(page.start, PageStart::Offset(0));
+        assert_eq!(page.limit, 10);
+        let cursor = encode_cursor(Row(5).page_after());
+        let page = PageRequest::new(Some(5), Some(20), Some(&cursor)).unwrap();
+        assert_eq!(page.start, Row(5).page_after());
+        assert_eq!(page.limit, 20);
+        assert!(PageRequest::new(None, Some(0), None).is_err());
+        let page = PageRequest::new(None, Some(MAX_LIMIT + 1), None).unwrap();
+        assert_eq!(page.limit, MAX_LIMIT);
+    }
+
+    #[test]
+    fn test_paginate_list() {
+        let link = |offset: usize| {
+            format!(
+                "/garmin/sync_status?cursor={}&limit=2",
+                encode_cursor(PageStart::Offset(offset))
+            )
+        };
+        let page = PageRequest {
+            start: PageStart::Offset(2),
+            limit: 2,
+        };
+        let (pagination, rows) = page
+            .paginate_list(vec![0, 1, 2, 3, 4], "/garmin/sync_status", &[])
+            .unwrap();
+        assert_eq!(rows, vec![2, 3]);
+        assert_eq!(pagination.total, 5);
+        assert_eq!(pagination.offset, Some(2));
+        assert_eq!(pagination.last.as_str(), link(4));
+        assert_eq!(pagination.prev.as_deref(), Some(link(0).as_str()));
+        assert_eq!(pagination.next.as_deref(), Some(link(4).as_str()));
+
+        let page = PageRequest {
+            start: PageStart::Offset(4),
+            limit: 2,
+        };
+        let (pagination, rows) = page
+            .paginate_list(vec![0, 1, 2, 3, 4], "/garmin/sync_status", &[])
+            .unwrap();
+        assert_eq!(rows, vec![4]);
+        assert_eq!(pagination.next, None);
+
+        let page = PageRequest {
+            start: Row(4).page_after(),
+            limit: 2,
+        };
+        assert!(page
+            .paginate_list(vec![0, 1], "/garmin/sync_status", &[])
+            .is_err());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let params: [(&str, StackString); 1] = [("start_date", "2024-01-01".into())];
+        let link = |start: PageStart| {
+            format!(
+                "/garmin/strava/activities_db?start_date=2024-01-01&cursor={}&limit=2",
+                encode_cursor(start)
+            )
+        };
+        let rows = [Row(3), Row(4)];
+        let page = PageRequest {
+            start: PageStart::Offset(2),
+            limit: 2,
+        };
+        let pagination = page.paginate(5, &rows, "/garmin/strava/activities_db", &params);
+        assert_eq!(pagination.offset, Some(2));
+        assert_eq!(pagination.first.as_str(), link(PageStart::Offset(0)));
+        assert_eq!(pagination.last.as_str(), link(PageStart::Offset(4)));
+        let prev = link(PageStart::Offset(0));
+        let next = link(Row(4).page_after());
+        assert_eq!(pagination.prev.as_deref(), Some(prev.as_str()));
+        assert_eq!(pagination.next.as_deref(), Some(next.as_str()));
+        assert_eq!(
+            pagination.link_header().as_str(),
+            format!(
+                "<{}>; rel=\"first\", <{prev}>; rel=\"prev\", <{next}>; rel=\"next\", <{}>; \
+                 rel=\"last\"",
+                link(PageStart::Offset(0)),
+                link(PageStart::Offset(4)),
+            )
+        );
+
+        // pages reached by keyset only link forward
+        let page = PageRequest {
+            start: Row(4).page_after(),
+            limit: 2,
+        };
+        let pagination = page.paginate(5, &rows, "/garmin/strava/activities_db", &params);
+        assert_eq!(pagination.offset, None);
+        assert_eq!(pagination.prev, None);
+        assert!(pagination.next.is_some());
+        let pagination = page.paginate(5, &rows[..1], "/garmin/strava/activities_db", &params);
+        assert_eq!(pagination.next, None);
+
+        let pagination = PageRequest {
+            start: PageStart::Offset(4),
+            limit: 2,
+        }
+        .paginate(5, &rows[..1], "/garmin/strava/activities_db", &[]);
+        assert_eq!(pagination.next, None);
+
+        let pagination = PageRequest::new(None, None, None).unwrap().paginate::<Row>(
+            0,
+            &[],
+            "/garmin/scale_measurements",
+            &[],
+        );
+        assert_eq!(pagination.prev, None);
+        assert_eq!(pagination.next, None);
+        assert_eq!(pagination.first, pagination.last);
+    }
+}
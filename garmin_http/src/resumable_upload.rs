@@ -0,0 +1,268 @@
+use anyhow::{format_err, Error};
+use parking_lot::{const_mutex, Mutex};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use stack_string::{format_sstr, StackString};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, task::spawn_blocking};
+use uuid::Uuid;
+
+/// Serializes chunk appends so the offset check and the write can't race with
+/// a retried request for the same session
+static APPEND_LOCK: Mutex<()> = const_mutex(());
+
+/// An upload sent in `Content-Range` chunks, the partial file and this
+/// metadata live in `upload_dir` so an interrupted upload can continue from
+/// the current size of the partial file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UploadSession {
+    pub session_id: Uuid,
+    pub filename: StackString,
+    pub size: u64,
+    pub sha256: StackString,
+}
+
+impl UploadSession {
+    /// # Errors
+    /// Return error if filename, size or checksum are invalid
+    pub fn new(filename: &str, size: u64, sha256: &str) -> Result<Self, Error> {
+        if filename.is_empty()
+            || filename.contains('/')
+            || filename.contains('\\')
+            || filename.starts_with('.')
+        {
+            return Err(format_err!("Invalid filename {filename}"));
+        }
+        if size == 0 {
+            return Err(format_err!("Empty File"));
+        }
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format_err!("Invalid sha256 {sha256}"));
+        }
+        Ok(Self {
+            session_id: Uuid::new_v4(),
+            filename: filename.into(),
+            size,
+            sha256: sha256.to_lowercase().into(),
+        })
+    }
+
+    fn metadata_path(upload_dir: &Path, session_id: Uuid) -> PathBuf {
+        upload_dir.join(format_sstr!("{session_id}.json"))
+    }
+
+    fn part_path(&self, upload_dir: &Path) -> PathBuf {
+        upload_dir.join(format_sstr!("{}.part", self.session_id))
+    }
+
+    /// # Errors
+    /// Return error if writing to `upload_dir` fails
+    pub async fn create(&self, upload_dir: &Path) -> Result<(), Error> {
+        fs::create_dir_all(upload_dir).await?;
+        fs::write(
+            Self::metadata_path(upload_dir, self.session_id),
+            serde_json::to_vec(self)?,
+        )
+        .await?;
+        fs::File::create(self.part_path(upload_dir)).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if the session doesn't exist
+    pub async fn get(upload_dir: &Path, session_id: Uuid) -> Result<Self, Error> {
+        let metadata_path = Self::metadata_path(upload_dir, session_id);
+        let buf = fs::read(&metadata_path)
+            .await
+            .map_err(|_| format_err!("No upload session {session_id}"))?;
+        serde_json::from_slice(&buf).map_err(Into::into)
+    }
+
+    /// Number of bytes received so far
+    /// # Errors
+    /// Return error if the partial file is missing
+    pub async fn offset(&self, upload_dir: &Path) -> Result<u64, Error> {
+        let metadata = fs::metadata(self.part_path(upload_dir)).await?;
+        Ok(metadata.len())
+    }
+
+    /// Append `data` sent with header `content_range`, the chunk has to start
+    /// at the current offset, returns the new offset
+    /// # Errors
+    /// Return error if the range is invalid, doesn't match the current offset
+    /// or writing fails
+    pub async fn append_chunk(
+        &self,
+        upload_dir: &Path,
+        content_range: &str,
+        data: &[u8],
+    ) -> Result<u64, Error> {
+        let (start, end, total) = parse_content_range(content_range)?;
+        if total != self.size || end >= total {
+            return Err(format_err!(
+                "Range {content_range} doesn't match size {}",
+                self.size
+            ));
+        }
+        if end - start + 1 != data.len() as u64 {
+            return Err(format_err!(
+                "Range {content_range} doesn't match chunk of {} bytes",
+                data.len()
+            ));
+        }
+        let part_path = self.part_path(upload_dir);
+        let data = data.to_vec();
+        spawn_blocking(move || -> Result<u64, Error> {
+            let _guard = APPEND_LOCK.lock();
+            let mut f = OpenOptions::new().append(true).open(part_path)?;
+            let offset = f.metadata()?.len();
+            if start != offset {
+                return Err(format_err!("Expected chunk starting at {offset}"));
+            }
+            f.write_all(&data)?;
+            f.flush()?;
+            Ok(offset + data.len() as u64)
+        })
+        .await?
+    }
+
+    /// Verify size and checksum of the complete upload and move it to
+    /// `output_dir`, returns the path of the uploaded file
+    /// # Errors
+    /// Return error if the upload is incomplete, the checksum doesn't match
+    /// or moving the file fails
+    pub async fn finish(&self, upload_dir: &Path, output_dir: &Path) -> Result<PathBuf, Error> {
+        let offset = self.offset(upload_dir).await?;
+        if offset != self.size {
+            return Err(format_err!(
+                "Upload incomplete, {offset} of {} bytes",
+                self.size
+            ));
+        }
+        let part_path = self.part_path(upload_dir);
+        let checksum = {
+            let part_path = part_path.clone();
+            spawn_blocking(move || -> Result<StackString, Error> {
+                let buf = std::fs::read(part_path)?;
+                Ok(format_sstr!("{:x}", Sha256::digest(&buf)))
+            })
+            .await??
+        };
+        if checksum != self.sha256 {
+            self.delete(upload_dir).await?;
+            return Err(format_err!(
+                "Checksum mismatch, expected {} got {checksum}",
+                self.sha256
+            ));
+        }
+        let output = output_dir.join(self.filename.as_str());
+        if fs::rename(&part_path, &output).await.is_err() {
+            fs::copy(&part_path, &output).await?;
+            fs::remove_file(&part_path).await?;
+        }
+        fs::remove_file(Self::metadata_path(upload_dir, self.session_id)).await?;
+        Ok(output)
+    }
+
+    /// # Errors
+    /// Return error if removing the files fails
+    pub async fn delete(&self, upload_dir: &Path) -> Result<(), Error> {
+        let part_path = self.part_path(upload_dir);
+        if part_path.exists() {
+            fs::remove_file(part_path).await?;
+        }
+        fs::remove_file(Self::metadata_path(upload_dir, self.session_id)).await?;
+        Ok(())
+    }
+}
+
+/// Parse `bytes {start}-{end}/{total}`
+/// # Errors
+/// Return error if `s` is not a valid byte range
+pub fn parse_content_range(s: &str) -> Result<(u64, u64, u64), Error> {
+    let err = || format_err!("Invalid Content-Range {s}");
+    let range = s.trim().strip_prefix("bytes ").ok_or_else(err)?;
+    let (range, total) = range.split_once('/').ok_or_else(err)?;
+    let (start, end) = range.split_once('-').ok_or_else(err)?;
+    let start: u64 = start.trim().parse().map_err(|_| err())?;
+    let end: u64 = end.trim().parse().map_err(|_| err())?;
+    let total: u64 = total.trim().parse().map_err(|_| err())?;
+    if end < start {
+        return Err(err());
+    }
+    Ok((start, end, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use sha2::{Digest, Sha256};
+    use stack_string::format_sstr;
+    use tempfile::TempDir;
+
+    use crate::resumable_upload::{parse_content_range, UploadSession};
+
+    #[test]
+    fn test_parse_content_range() -> Result<(), Error> {
+        assert_eq!(parse_content_range("bytes 0-99/1000")?, (0, 99, 1000));
+        assert!(parse_content_range("bytes 10-5/1000").is_err());
+        assert!(parse_content_range("0-99/1000").is_err());
+        assert!(parse_content_range("bytes 0-99/*").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resumable_upload() -> Result<(), Error> {
+        let upload_dir = TempDir::with_prefix("garmin_upload")?;
+        let output_dir = TempDir::with_prefix("garmin_upload_out")?;
+        let data = b"0123456789abcdef";
+        let sha256 = format_sstr!("{:x}", Sha256::digest(data));
+
+        assert!(UploadSession::new("../test.fit", 16, &sha256).is_err());
+        let session = UploadSession::new("test.fit", 16, &sha256)?;
+        session.create(upload_dir.path()).await?;
+
+        let session = UploadSession::get(upload_dir.path(), session.session_id).await?;
+        let offset = session
+            .append_chunk(upload_dir.path(), "bytes 0-7/16", &data[..8])
+            .await?;
+        assert_eq!(offset, 8);
+        // a repeated chunk is rejected, the client resumes from the offset
+        assert!(session
+            .append_chunk(upload_dir.path(), "bytes 0-7/16", &data[..8])
+            .await
+            .is_err());
+        assert!(session
+            .finish(upload_dir.path(), output_dir.path())
+            .await
+            .is_err());
+        session
+            .append_chunk(upload_dir.path(), "bytes 8-15/16", &data[8..])
+            .await?;
+        let output = session.finish(upload_dir.path(), output_dir.path()).await?;
+        assert_eq!(std::fs::read(output)?, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_append_chunk() -> Result<(), Error> {
+        let upload_dir = TempDir::with_prefix("garmin_upload")?;
+        let data = b"0123456789abcdef";
+        let sha256 = format_sstr!("{:x}", Sha256::digest(data));
+        let session = UploadSession::new("test.fit", 16, &sha256)?;
+        session.create(upload_dir.path()).await?;
+
+        // a retried request racing the original only gets written once
+        let (a, b) = tokio::join!(
+            session.append_chunk(upload_dir.path(), "bytes 0-7/16", &data[..8]),
+            session.append_chunk(upload_dir.path(), "bytes 0-7/16", &data[..8]),
+        );
+        assert!(a.is_ok() ^ b.is_ok());
+        assert_eq!(session.offset(upload_dir.path()).await?, 8);
+        Ok(())
+    }
+}
@@ -1,79 +1,30 @@
 use anyhow::Error;
-use rweb::Schema;
+use derive_more::{From, Into};
+use rweb::openapi::{self, ComponentDescriptor, ComponentOrInlineSchema, Entity};
 use serde::{de::Deserializer, ser::Serializer, Deserialize, Serialize};
 use stack_string::StackString;
-use std::{convert::TryFrom, fmt, str::FromStr};
+use std::{borrow::Cow, convert::TryFrom, fmt, str::FromStr};
 
 use garmin_utils::sport_types::SportTypes;
 
-#[derive(Serialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Schema, Deserialize)]
+#[derive(Serialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Deserialize, Into, From)]
 #[serde(into = "StackString", try_from = "StackString")]
-pub enum SportTypesWrapper {
-    #[serde(rename = "running")]
-    Running,
-    #[serde(rename = "biking")]
-    Biking,
-    #[serde(rename = "walking")]
-    Walking,
-    #[serde(rename = "hiking")]
-    Hiking,
-    #[serde(rename = "ultimate")]
-    Ultimate,
-    #[serde(rename = "elliptical")]
-    Elliptical,
-    #[serde(rename = "stairs")]
-    Stairs,
-    #[serde(rename = "lifting")]
-    Lifting,
-    #[serde(rename = "swimming")]
-    Swimming,
-    #[serde(rename = "other")]
-    Other,
-    #[serde(rename = "snowshoeing")]
-    Snowshoeing,
-    #[serde(rename = "skiing")]
-    Skiing,
-    #[serde(rename = "none")]
-    None,
-}
+pub struct SportTypesWrapper(SportTypes);
 
-impl From<SportTypes> for SportTypesWrapper {
-    fn from(item: SportTypes) -> Self {
-        match item {
-            SportTypes::Running => Self::Running,
-            SportTypes::Biking => Self::Biking,
-            SportTypes::Walking => Self::Walking,
-            SportTypes::Hiking => Self::Hiking,
-            SportTypes::Ultimate => Self::Ultimate,
-            SportTypes::Elliptical => Self::Elliptical,
-            SportTypes::Stairs => Self::Stairs,
-            SportTypes::Lifting => Self::Lifting,
-            SportTypes::Swimming => Self::Swimming,
-            SportTypes::Other => Self::Other,
-            SportTypes::Snowshoeing => Self::Snowshoeing,
-            SportTypes::Skiing => Self::Skiing,
-            SportTypes::None => Self::None,
-        }
+impl Entity for SportTypesWrapper {
+    fn type_name() -> Cow<'static, str> {
+        "sport".into()
     }
-}
-
-impl From<SportTypesWrapper> for SportTypes {
-    fn from(item: SportTypesWrapper) -> Self {
-        match item {
-            SportTypesWrapper::Running => Self::Running,
-            SportTypesWrapper::Biking => Self::Biking,
-            SportTypesWrapper::Walking => Self::Walking,
-            SportTypesWrapper::Hiking => Self::Hiking,
-            SportTypesWrapper::Ultimate => Self::Ultimate,
-            SportTypesWrapper::Elliptical => Self::Elliptical,
-            SportTypesWrapper::Stairs => Self::Stairs,
-            SportTypesWrapper::Lifting => Self::Lifting,
-            SportTypesWrapper::Swimming => Self::Swimming,
-            SportTypesWrapper::Other => Self::Other,
-            SportTypesWrapper::Snowshoeing => Self::Snowshoeing,
-            SportTypesWrapper::Skiing => Self::Skiing,
-            SportTypesWrapper::None => Self::None,
-        }
+    fn describe(_: &mut ComponentDescriptor) -> ComponentOrInlineSchema {
+        use rweb::openapi::Schema;
+        ComponentOrInlineSchema::Inline(Schema {
+            schema_type: Some(openapi::Type::String),
+            format: "sport".into(),
+            description: "Sport name, builtin (e.g. running, biking) or user defined, valid \
+                          values are listed by /garmin/meta/sports"
+                .into(),
+            ..Schema::default()
+        })
     }
 }
 
@@ -84,8 +35,7 @@ pub fn serialize<S>(sport: &SportTypesWrapper, serializer: S) -> Result<S::Ok, S
 where
     S: Serializer,
 {
-    let sport: SportTypes = (*sport).into();
-    serializer.serialize_str(&sport.to_strava_activity())
+    serializer.serialize_str(&sport.0.to_strava_activity())
 }
 
 /// # Errors
@@ -103,15 +53,13 @@ where
 
 impl fmt::Display for SportTypesWrapper {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s: SportTypes = (*self).into();
-        f.write_str(s.to_str())
+        f.write_str(self.0.to_str())
     }
 }
 
 impl From<SportTypesWrapper> for StackString {
     fn from(item: SportTypesWrapper) -> StackString {
-        let s: SportTypes = item.into();
-        s.to_str().into()
+        item.0.to_str().into()
     }
 }
 
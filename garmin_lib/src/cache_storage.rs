@@ -0,0 +1,45 @@
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Where avro and parquet caches live, with `S3` the local cache directories
+/// only keep the most recently used files
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStorage {
+    Local,
+    S3,
+}
+
+impl Default for CacheStorage {
+    fn default() -> Self {
+        Self::Local
+    }
+}
+
+impl CacheStorage {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::S3 => "s3",
+        }
+    }
+}
+
+impl fmt::Display for CacheStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for CacheStorage {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "local" => Ok(Self::Local),
+            "s3" => Ok(Self::S3),
+            _ => Err(format_err!("{s} is not a valid cache storage")),
+        }
+    }
+}
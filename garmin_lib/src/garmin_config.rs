@@ -13,7 +13,11 @@ use std::{
 };
 use url::Url;
 
-use super::strava_timezone::StravaTz;
+use super::{
+    cache_storage::CacheStorage, geocode_provider::GeocodeProvider,
+    heart_rate_profile::HeartRateProfile, notification::NotifyMatrix,
+    split_distance::SplitDistance, strava_timezone::StravaTz, week_start::WeekStart,
+};
 
 /// `GarminConfig` holds configuration information which can be set either
 /// through environment variables or the config.env file, see the dotenv crate
@@ -64,6 +68,10 @@ pub struct GarminConfigInner {
     pub strava_password: Option<StackString>,
     #[serde(default = "default_strava_endpoint")]
     pub strava_endpoint: Option<UrlWrapper>,
+    /// When set, synced Strava activities still carrying the default name
+    /// (e.g. "Morning Run") are renamed from this template, see
+    /// `garmin_models::strava_title` for the placeholders
+    pub strava_title_template: Option<StackString>,
     #[serde(default = "default_gps_bucket")]
     pub garmin_connect_email: StackString,
     #[serde(default = "default_gps_bucket")]
@@ -93,6 +101,133 @@ pub struct GarminConfigInner {
     pub fitbit_archivedir: PathBuf,
     #[serde(default = "default_fitbit_archive_bucket")]
     pub fitbit_archive_bucket: StackString,
+    /// Bucket the heart rate archive partitions are backed up to along with
+    /// a manifest of their checksums, no backup is made when unset
+    pub fitbit_archive_backup_bucket: Option<StackString>,
+    /// Bucket holding race result certificates and photos
+    #[serde(default = "default_race_attachment_bucket")]
+    pub race_attachment_bucket: StackString,
+    #[serde(default = "default_week_start")]
+    pub week_start: WeekStart,
+    /// Split distance for file reports when none is requested, e.g. `1km`
+    #[serde(default)]
+    pub split_distance: SplitDistance,
+    #[serde(default = "default_max_heart_rate")]
+    pub max_heart_rate: f64,
+    #[serde(default = "default_resting_heart_rate")]
+    pub resting_heart_rate: f64,
+    #[serde(default = "default_quarantine_dir")]
+    pub quarantine_dir: PathBuf,
+    #[serde(default = "default_upload_dir")]
+    pub upload_dir: PathBuf,
+    /// Days to keep imported raw json in `garmin_connect_import_directory`,
+    /// 0 (the default) keeps them forever
+    #[serde(default)]
+    pub raw_json_retention_days: u32,
+    /// Days to keep quarantined files, 0 (the default) keeps them forever
+    #[serde(default)]
+    pub quarantine_retention_days: u32,
+    /// Copies of quarantined files with malformed input are added to the
+    /// fuzz corpus under this directory, e.g. `garmin_parser/fuzz/corpus`
+    pub fuzz_corpus_dir: Option<PathBuf>,
+    /// Days to keep abandoned upload sessions, 0 (the default) keeps them
+    /// forever
+    #[serde(default)]
+    pub upload_retention_days: u32,
+    /// Scale measurements within this many minutes and
+    /// `scale_duplicate_lbs` of each other are considered duplicates
+    #[serde(default = "default_scale_duplicate_minutes")]
+    pub scale_duplicate_minutes: u32,
+    #[serde(default = "default_scale_duplicate_lbs")]
+    pub scale_duplicate_lbs: f64,
+    /// Goal lines drawn on the scale measurement plots
+    pub goal_mass: Option<f64>,
+    pub goal_fat_pct: Option<f64>,
+    pub goal_ffmi: Option<f64>,
+    /// Notifications are sent through each provider which is configured
+    pub smtp_host: Option<StackString>,
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    pub smtp_username: Option<StackString>,
+    pub smtp_password: Option<StackString>,
+    pub notify_email_from: Option<StackString>,
+    pub notify_email_to: Option<StackString>,
+    pub notify_telegram_chat_id: Option<i64>,
+    pub ntfy_topic: Option<StackString>,
+    #[serde(default = "default_ntfy_endpoint")]
+    pub ntfy_endpoint: Option<UrlWrapper>,
+    #[serde(default)]
+    pub notify_matrix: NotifyMatrix,
+    /// Alert when a sync job hasn't succeeded for this many hours
+    #[serde(default = "default_sync_alert_hours")]
+    pub sync_alert_hours: u32,
+    /// Warn when a week's running mileage is more than this many times the
+    /// average of the last four weeks (acute:chronic workload ratio)
+    #[serde(default = "default_ramp_rate_threshold")]
+    pub ramp_rate_threshold: f64,
+    /// Overpass api used to look up the surface of ways near track points,
+    /// point it at a local overpass instance loaded from an offline extract
+    /// to avoid the public server
+    #[serde(default = "default_overpass_endpoint")]
+    pub overpass_endpoint: Option<UrlWrapper>,
+    /// Number of track points per activity checked against osm
+    #[serde(default = "default_surface_sample_points")]
+    pub surface_sample_points: usize,
+    /// Reverse geocoding of activity start points, results are cached in
+    /// `geocode_cache` so each area is only looked up once
+    #[serde(default)]
+    pub geocode_provider: GeocodeProvider,
+    /// Overrides the provider's public endpoint
+    pub geocode_endpoint: Option<UrlWrapper>,
+    /// Email address or url added to the user agent of geocoding and
+    /// overpass requests, the public osm instances ask for a way to reach
+    /// heavy users
+    pub osm_contact: Option<StackString>,
+    /// With `s3` activity avro files and the heart rate parquet archive are
+    /// read from and written to `cache_bucket` / `fitbit_archive_bucket`
+    /// directly, `cache_dir` and `fitbit_archivedir` then only hold the
+    /// `cache_local_max_files` most recently used files
+    #[serde(default)]
+    pub cache_storage: CacheStorage,
+    #[serde(default = "default_cache_local_max_files")]
+    pub cache_local_max_files: usize,
+    /// OTLP grpc collector (e.g. `http://localhost:4317` for Jaeger or
+    /// Tempo) http request, db and external api spans are exported to, see
+    /// `telemetry::Telemetry`
+    pub otlp_endpoint: Option<UrlWrapper>,
+    /// Open-meteo compatible hourly forecast api upcoming races' weather is
+    /// fetched from (e.g. `https://api.open-meteo.com/v1/forecast`), race
+    /// locations are sent to it so forecasts are off unless it is set
+    pub forecast_endpoint: Option<UrlWrapper>,
+    /// Activities with at least this training impulse (TRIMP) count as hard
+    /// efforts in the rest day statistics
+    #[serde(default = "default_hard_effort_trimp")]
+    pub hard_effort_trimp: f64,
+    /// Training days per week of the training plan, consistency is measured
+    /// against every day of the week when unset
+    pub planned_training_days: Option<u8>,
+    /// Leave activities flagged as commutes out of training load (rest days,
+    /// ramp rate) and weekly summaries, they still count in monthly, yearly
+    /// and lifetime totals
+    #[serde(default = "default_exclude_commutes")]
+    pub exclude_commutes: bool,
+    /// Demo pages move gps tracks, shift dates and hide activity, race and
+    /// route names unless this is false
+    #[serde(default = "default_demo_anonymize")]
+    pub demo_anonymize: bool,
+    /// Tracks on demo pages are moved between half and all of this distance
+    /// in a direction fixed per activity
+    #[serde(default = "default_demo_jitter_meters")]
+    pub demo_jitter_meters: f64,
+    /// Dates on demo pages are moved back this many days, a multiple of 7
+    /// keeps the day of the week
+    #[serde(default = "default_demo_date_shift_days")]
+    pub demo_date_shift_days: i64,
+    /// Set to false to run without AWS: local directories aren't synced
+    /// with the buckets, `cache_storage` falls back to local and features
+    /// which need s3 (e.g. race result attachments) are turned off
+    #[serde(default = "default_s3_enabled")]
+    pub s3_enabled: bool,
 }
 
 fn default_height() -> f64 {
@@ -131,6 +266,57 @@ fn default_gps_dir() -> PathBuf {
 fn default_cache_dir() -> PathBuf {
     cache_dir().join("cache")
 }
+fn default_quarantine_dir() -> PathBuf {
+    cache_dir().join("quarantine")
+}
+fn default_upload_dir() -> PathBuf {
+    cache_dir().join("uploads")
+}
+fn default_scale_duplicate_minutes() -> u32 {
+    10
+}
+fn default_scale_duplicate_lbs() -> f64 {
+    0.5
+}
+fn default_sync_alert_hours() -> u32 {
+    36
+}
+fn default_ramp_rate_threshold() -> f64 {
+    1.5
+}
+fn default_overpass_endpoint() -> Option<UrlWrapper> {
+    "https://overpass-api.de/api/interpreter".try_into().ok()
+}
+fn default_hard_effort_trimp() -> f64 {
+    100.0
+}
+fn default_demo_anonymize() -> bool {
+    true
+}
+fn default_exclude_commutes() -> bool {
+    true
+}
+fn default_s3_enabled() -> bool {
+    true
+}
+fn default_demo_jitter_meters() -> f64 {
+    500.0
+}
+fn default_demo_date_shift_days() -> i64 {
+    364
+}
+fn default_surface_sample_points() -> usize {
+    10
+}
+fn default_cache_local_max_files() -> usize {
+    500
+}
+fn default_smtp_port() -> u16 {
+    587
+}
+fn default_ntfy_endpoint() -> Option<UrlWrapper> {
+    "https://ntfy.sh/".try_into().ok()
+}
 fn default_fitbit_cachedir() -> PathBuf {
     cache_dir().join("fitbit_cache")
 }
@@ -179,6 +365,18 @@ fn default_connect_import_directory() -> PathBuf {
 fn default_fitbit_archive_bucket() -> StackString {
     "fitbit-archive-ddboline".into()
 }
+fn default_race_attachment_bucket() -> StackString {
+    "race-attachments-ddboline".into()
+}
+fn default_week_start() -> WeekStart {
+    WeekStart::Monday
+}
+fn default_max_heart_rate() -> f64 {
+    HeartRateProfile::default().max_heart_rate
+}
+fn default_resting_heart_rate() -> f64 {
+    HeartRateProfile::default().resting_heart_rate
+}
 
 impl Default for GarminConfigInner {
     fn default() -> Self {
@@ -227,6 +425,43 @@ impl GarminConfig {
             Ok(Self(Arc::new(conf)))
         }
     }
+
+    #[must_use]
+    pub fn heart_rate_profile(&self) -> HeartRateProfile {
+        HeartRateProfile {
+            max_heart_rate: self.max_heart_rate,
+            resting_heart_rate: self.resting_heart_rate,
+            threshold_heart_rate: None,
+        }
+    }
+
+    /// Directories the apps read from and write to, `garmin-rust-http
+    /// --init` creates them
+    #[must_use]
+    pub fn data_directories(&self) -> Vec<&Path> {
+        let mut directories = vec![
+            self.gps_dir.as_path(),
+            self.cache_dir.as_path(),
+            self.fitbit_cachedir.as_path(),
+            self.fitbit_archivedir.as_path(),
+            self.quarantine_dir.as_path(),
+            self.upload_dir.as_path(),
+            self.download_directory.as_path(),
+            self.garmin_connect_import_directory.as_path(),
+        ];
+        directories.extend(self.secret_path.parent());
+        directories.extend(self.jwt_secret_path.parent());
+        directories.sort();
+        directories.dedup();
+        directories
+    }
+
+    /// Strava api calls need the client id and secret stored in
+    /// `strava_tokenfile`
+    #[must_use]
+    pub fn strava_configured(&self) -> bool {
+        self.strava_tokenfile.exists()
+    }
 }
 
 impl ops::Deref for GarminConfig {
@@ -266,7 +501,10 @@ impl TryFrom<&str> for UrlWrapper {
 mod tests {
     use std::{env, path::Path};
 
-    use crate::garmin_config;
+    use crate::{
+        cache_storage::CacheStorage, garmin_config, geocode_provider::GeocodeProvider,
+        heart_rate_profile::HeartRateProfile, split_distance::SplitDistance, week_start::WeekStart,
+    };
 
     #[test]
     fn test_garmin_config_new() {
@@ -281,6 +519,37 @@ mod tests {
         assert_eq!(gc.port, 8000);
         assert_eq!(&gc.pgurl, "");
         assert_eq!(gc.gps_dir, default_gps_dir);
+        assert_eq!(
+            gc.quarantine_dir,
+            home_dir
+                .join(".garmin_cache")
+                .join("run")
+                .join("quarantine")
+        );
+        assert_eq!(gc.week_start, WeekStart::Monday);
+        assert_eq!(gc.split_distance, SplitDistance::MILE);
+        assert_eq!(gc.raw_json_retention_days, 0);
+        assert_eq!(gc.quarantine_retention_days, 0);
+        assert_eq!(gc.upload_retention_days, 0);
+        assert_eq!(gc.smtp_port, 587);
+        assert_eq!(gc.sync_alert_hours, 36);
+        assert!((gc.ramp_rate_threshold - 1.5).abs() < 1e-6);
+        assert!((gc.hard_effort_trimp - 100.0).abs() < 1e-6);
+        assert!(gc.planned_training_days.is_none());
+        assert!(gc.exclude_commutes);
+        assert_eq!(gc.surface_sample_points, 10);
+        assert_eq!(gc.geocode_provider, GeocodeProvider::Nominatim);
+        assert_eq!(gc.cache_storage, CacheStorage::Local);
+        assert_eq!(gc.cache_local_max_files, 500);
+        assert!(gc.ntfy_topic.is_none());
+        assert!(gc.demo_anonymize);
+        assert!((gc.demo_jitter_meters - 500.0).abs() < 1e-6);
+        assert_eq!(gc.demo_date_shift_days, 364);
+        assert!(gc.s3_enabled);
+        let directories = gc.data_directories();
+        assert!(directories.contains(&default_gps_dir.as_path()));
+        assert!(directories.contains(&gc.upload_dir.as_path()));
+        assert_eq!(gc.heart_rate_profile(), HeartRateProfile::default());
     }
 
     #[test]
@@ -0,0 +1,54 @@
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Reverse geocoding service used to find the city of an activity's start
+/// point
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeocodeProvider {
+    Nominatim,
+    Photon,
+}
+
+impl Default for GeocodeProvider {
+    fn default() -> Self {
+        Self::Nominatim
+    }
+}
+
+impl GeocodeProvider {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Nominatim => "nominatim",
+            Self::Photon => "photon",
+        }
+    }
+
+    /// Public endpoint used when `geocode_endpoint` isn't set
+    #[must_use]
+    pub fn default_endpoint(self) -> &'static str {
+        match self {
+            Self::Nominatim => "https://nominatim.openstreetmap.org/",
+            Self::Photon => "https://photon.komoot.io/",
+        }
+    }
+}
+
+impl fmt::Display for GeocodeProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for GeocodeProvider {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nominatim" | "osm" => Ok(Self::Nominatim),
+            "photon" => Ok(Self::Photon),
+            _ => Err(format_err!("{s} is not a valid geocode provider")),
+        }
+    }
+}
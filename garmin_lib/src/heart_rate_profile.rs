@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+
+/// Weighting factor of Banister's training impulse.
+pub const TRIMP_WEIGHT: f64 = 1.92;
+/// Heart rate reserve fractions bounding the five training zones.
+pub const ZONE_RESERVE_FRACTIONS: [f64; 6] = [0.5, 0.6, 0.7, 0.8, 0.9, 1.0];
+/// Lactate threshold heart rate fractions bounding zones 2 through 5.
+pub const ZONE_THRESHOLD_FRACTIONS: [f64; 4] = [0.85, 0.9, 0.95, 1.0];
+/// Highest rating of perceived exertion, on the CR-10 scale.
+pub const MAX_RPE: f64 = 10.0;
+/// Fraction of heart rate reserve the training impulse weighting assumes the
+/// lactate threshold to be at.
+pub const REFERENCE_THRESHOLD_RESERVE: f64 = 0.85;
+
+/// Heart rate bounds used to compute the heart rate reserve, which in turn
+/// normalizes effort across sports.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct HeartRateProfile {
+    pub max_heart_rate: f64,
+    pub resting_heart_rate: f64,
+    /// Estimated lactate threshold heart rate, zones and training impulse
+    /// are based on it when known.
+    #[serde(default)]
+    pub threshold_heart_rate: Option<f64>,
+}
+
+impl Default for HeartRateProfile {
+    fn default() -> Self {
+        Self {
+            max_heart_rate: 185.0,
+            resting_heart_rate: 60.0,
+            threshold_heart_rate: None,
+        }
+    }
+}
+
+impl HeartRateProfile {
+    /// Fraction of heart rate reserve, clamped to [0, 1].
+    #[must_use]
+    pub fn reserve_fraction(&self, heart_rate: f64) -> f64 {
+        let reserve = self.max_heart_rate - self.resting_heart_rate;
+        if reserve <= 0.0 {
+            return 0.0;
+        }
+        ((heart_rate - self.resting_heart_rate) / reserve).clamp(0.0, 1.0)
+    }
+
+    /// Heart rate at `fraction` of heart rate reserve.
+    #[must_use]
+    pub fn heart_rate_at(&self, fraction: f64) -> f64 {
+        self.resting_heart_rate + fraction * (self.max_heart_rate - self.resting_heart_rate)
+    }
+
+    /// (lower, upper) heart rate of zones 1 through 5, from fractions of
+    /// lactate threshold when it's known, else of heart rate reserve.
+    #[must_use]
+    pub fn zones(&self) -> Vec<(f64, f64)> {
+        let bounds: Vec<f64> = match self.threshold_heart_rate {
+            Some(threshold) => std::iter::once(self.heart_rate_at(ZONE_RESERVE_FRACTIONS[0]))
+                .chain(ZONE_THRESHOLD_FRACTIONS.iter().map(|f| f * threshold))
+                .chain(std::iter::once(self.max_heart_rate))
+                .collect(),
+            None => ZONE_RESERVE_FRACTIONS
+                .iter()
+                .map(|f| self.heart_rate_at(*f))
+                .collect(),
+        };
+        bounds.windows(2).map(|w| (w[0], w[1])).collect()
+    }
+
+    fn reserve_trimp(&self, heart_rate: f64, duration: f64) -> f64 {
+        let hrr = self.reserve_fraction(heart_rate);
+        duration / 60.0 * hrr * 0.64 * (TRIMP_WEIGHT * hrr).exp()
+    }
+
+    /// Factor scaling the training impulse so an effort at the estimated
+    /// lactate threshold loads as much as one at
+    /// `REFERENCE_THRESHOLD_RESERVE`, 1 when the threshold isn't known.
+    #[must_use]
+    pub fn threshold_scale(&self) -> f64 {
+        let at_threshold = match self.threshold_heart_rate {
+            Some(threshold) => self.reserve_trimp(threshold, 3600.0),
+            None => return 1.0,
+        };
+        if at_threshold > 0.0 {
+            self.reserve_trimp(self.heart_rate_at(REFERENCE_THRESHOLD_RESERVE), 3600.0)
+                / at_threshold
+        } else {
+            1.0
+        }
+    }
+
+    /// Banister TRIMP for `duration` seconds at an average `heart_rate`,
+    /// scaled by `threshold_scale`.
+    #[must_use]
+    pub fn trimp(&self, heart_rate: f64, duration: f64) -> f64 {
+        self.reserve_trimp(heart_rate, duration) * self.threshold_scale()
+    }
+
+    /// Sql expression equivalent to `trimp` for a single `garmin_summary` row,
+    /// where `hr_dur` is the heart rate weighted duration and `hr_dis` the
+    /// duration with heart rate data.
+    #[must_use]
+    pub fn trimp_sql(&self, hr_dur: &str, hr_dis: &str) -> StackString {
+        let rest = self.resting_heart_rate;
+        let reserve = (self.max_heart_rate - self.resting_heart_rate).max(1.0);
+        let scale = self.threshold_scale();
+        let hrr =
+            format_sstr!("LEAST(GREATEST(({hr_dur} / {hr_dis} - {rest}) / {reserve}, 0.0), 1.0)");
+        format_sstr!(
+            "CASE WHEN {hr_dis} > 0.0 THEN {scale} * {hr_dis} / 60.0 * {hrr} * 0.64 * \
+             EXP({TRIMP_WEIGHT} * {hrr}) ELSE 0.0 END"
+        )
+    }
+
+    /// Training impulse of `duration` seconds rated `rpe` out of `MAX_RPE`,
+    /// used for activities without heart rate.  The rating stands in for the
+    /// fraction of heart rate reserve, which keeps it on the same scale as
+    /// `trimp`.
+    #[must_use]
+    pub fn rpe_trimp(rpe: f64, duration: f64) -> f64 {
+        let hrr = (rpe / MAX_RPE).clamp(0.0, 1.0);
+        duration / 60.0 * hrr * 0.64 * (TRIMP_WEIGHT * hrr).exp()
+    }
+
+    /// Sql expression equivalent to `rpe_trimp`, null when `rpe` is.
+    #[must_use]
+    pub fn rpe_trimp_sql(rpe: &str, duration: &str) -> StackString {
+        let hrr = format_sstr!("LEAST(GREATEST({rpe} / {MAX_RPE:.1}, 0.0), 1.0)");
+        format_sstr!("{duration} / 60.0 * {hrr} * 0.64 * EXP({TRIMP_WEIGHT} * {hrr})")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heart_rate_profile::HeartRateProfile;
+
+    #[test]
+    fn test_trimp() {
+        let profile = HeartRateProfile::default();
+        assert_eq!(profile.trimp(60.0, 3600.0), 0.0);
+        assert_eq!(profile.trimp(40.0, 3600.0), 0.0);
+        let easy = profile.trimp(130.0, 3600.0);
+        let hard = profile.trimp(170.0, 3600.0);
+        assert!((easy - 63.0).abs() < 0.5, "{easy}");
+        assert!((hard - 183.0).abs() < 0.5, "{hard}");
+        assert_eq!(profile.trimp(200.0, 3600.0), profile.trimp(185.0, 3600.0));
+    }
+
+    #[test]
+    fn test_threshold_trimp() {
+        let mut profile = HeartRateProfile {
+            max_heart_rate: 190.0,
+            resting_heart_rate: 50.0,
+            threshold_heart_rate: None,
+        };
+        assert_eq!(profile.threshold_scale(), 1.0);
+        let reference = profile.trimp(169.0, 3600.0);
+
+        // a threshold at the reference fraction of reserve changes nothing
+        profile.threshold_heart_rate = Some(169.0);
+        assert!((profile.threshold_scale() - 1.0).abs() < 1e-9);
+
+        // with a higher threshold the same heart rate is less of a load, an
+        // hour at threshold loads as much as an hour at the reference
+        profile.threshold_heart_rate = Some(176.0);
+        assert!(profile.trimp(169.0, 3600.0) < reference);
+        assert!((profile.trimp(176.0, 3600.0) - reference).abs() < 1e-9);
+
+        profile.threshold_heart_rate = Some(162.0);
+        assert!(profile.trimp(169.0, 3600.0) > reference);
+        let scale = format!("{} * b", profile.threshold_scale());
+        assert!(profile.trimp_sql("a", "b").contains(&scale));
+    }
+
+    #[test]
+    fn test_rpe_trimp() {
+        let easy = HeartRateProfile::rpe_trimp(5.0, 3600.0);
+        let hard = HeartRateProfile::rpe_trimp(8.0, 3600.0);
+        assert!((easy - 50.2).abs() < 0.5, "{easy}");
+        assert!((hard - 142.7).abs() < 0.5, "{hard}");
+        assert_eq!(
+            HeartRateProfile::rpe_trimp(12.0, 3600.0),
+            HeartRateProfile::rpe_trimp(10.0, 3600.0)
+        );
+        // rated all out matches an hour at max heart rate
+        let profile = HeartRateProfile::default();
+        let max = profile.trimp(profile.max_heart_rate, 3600.0);
+        assert!((HeartRateProfile::rpe_trimp(10.0, 3600.0) - max).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_zones() {
+        let mut profile = HeartRateProfile {
+            max_heart_rate: 190.0,
+            resting_heart_rate: 50.0,
+            threshold_heart_rate: None,
+        };
+        let zones = profile.zones();
+        assert_eq!(zones.len(), 5);
+        assert!((zones[0].0 - 120.0).abs() < 1e-9);
+        assert!((zones[0].1 - 134.0).abs() < 1e-9);
+        assert!((zones[4].0 - 176.0).abs() < 1e-9);
+        assert!((zones[4].1 - 190.0).abs() < 1e-9);
+
+        profile.threshold_heart_rate = Some(170.0);
+        let zones = profile.zones();
+        assert_eq!(zones.len(), 5);
+        assert!((zones[0].0 - 120.0).abs() < 1e-9);
+        assert!((zones[0].1 - 144.5).abs() < 1e-9);
+        assert!((zones[3].1 - 170.0).abs() < 1e-9);
+        assert!((zones[4].1 - 190.0).abs() < 1e-9);
+    }
+}
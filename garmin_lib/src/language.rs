@@ -0,0 +1,95 @@
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// Language the web UI is shown in, stored as its ISO 639-1 code.
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Language {
+    English,
+    German,
+    Spanish,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Self::English
+    }
+}
+
+impl Language {
+    #[must_use]
+    pub fn all() -> [Self; 3] {
+        [Self::English, Self::German, Self::Spanish]
+    }
+
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::German => "de",
+            Self::Spanish => "es",
+        }
+    }
+
+    /// Name of the language in the language itself, for the language picker
+    #[must_use]
+    pub fn native_name(self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::German => "Deutsch",
+            Self::Spanish => "Español",
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for Language {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "en" | "english" => Ok(Self::English),
+            "de" | "german" | "deutsch" => Ok(Self::German),
+            "es" | "spanish" | "español" => Ok(Self::Spanish),
+            _ => Err(format_err!("{s} is not a supported language")),
+        }
+    }
+}
+
+impl From<Language> for String {
+    fn from(item: Language) -> Self {
+        item.to_str().into()
+    }
+}
+
+impl TryFrom<String> for Language {
+    type Error = Error;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::language::Language;
+
+    #[test]
+    fn test_language() -> Result<(), Error> {
+        for language in Language::all() {
+            assert_eq!(language.to_str().parse::<Language>()?, language);
+        }
+        assert_eq!("German".parse::<Language>()?, Language::German);
+        assert!("klingon".parse::<Language>().is_err());
+        assert_eq!(serde_json::to_string(&Language::Spanish)?, r#""es""#);
+        let language: Language = serde_json::from_str(r#""de""#)?;
+        assert_eq!(language, Language::German);
+        Ok(())
+    }
+}
@@ -7,6 +7,14 @@
 #![allow(clippy::similar_names)]
 #![allow(clippy::unsafe_derive_deserialize)]
 
+pub mod cache_storage;
 pub mod date_time_wrapper;
 pub mod garmin_config;
+pub mod geocode_provider;
+pub mod heart_rate_profile;
+pub mod language;
+pub mod notification;
+pub mod split_distance;
 pub mod strava_timezone;
+pub mod telemetry;
+pub mod week_start;
@@ -0,0 +1,149 @@
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::TryFrom, fmt, str::FromStr};
+
+/// Kind of event a notification is sent for
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEvent {
+    Milestone,
+    SyncFailure,
+    MissingSync,
+    RampRate,
+}
+
+impl NotifyEvent {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Milestone => "milestone",
+            Self::SyncFailure => "sync_failure",
+            Self::MissingSync => "missing_sync",
+            Self::RampRate => "ramp_rate",
+        }
+    }
+}
+
+impl fmt::Display for NotifyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for NotifyEvent {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "milestone" => Ok(Self::Milestone),
+            "sync_failure" => Ok(Self::SyncFailure),
+            "missing_sync" => Ok(Self::MissingSync),
+            "ramp_rate" => Ok(Self::RampRate),
+            _ => Err(format_err!("{s} is not a valid notification event")),
+        }
+    }
+}
+
+/// Channel a notification is delivered through
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyProvider {
+    Email,
+    Telegram,
+    Ntfy,
+}
+
+impl NotifyProvider {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Telegram => "telegram",
+            Self::Ntfy => "ntfy",
+        }
+    }
+}
+
+impl fmt::Display for NotifyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for NotifyProvider {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "email" | "smtp" => Ok(Self::Email),
+            "telegram" => Ok(Self::Telegram),
+            "ntfy" => Ok(Self::Ntfy),
+            _ => Err(format_err!("{s} is not a valid notification provider")),
+        }
+    }
+}
+
+/// Which providers are enabled for each event type, written as e.g.
+/// `milestone=telegram,ntfy;sync_failure=email;ramp_rate=none`, events which
+/// aren't listed go to every configured provider
+#[derive(Debug, PartialEq, Clone, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub struct NotifyMatrix(HashMap<NotifyEvent, Vec<NotifyProvider>>);
+
+impl NotifyMatrix {
+    #[must_use]
+    pub fn is_enabled(&self, event: NotifyEvent, provider: NotifyProvider) -> bool {
+        self.0
+            .get(&event)
+            .is_none_or(|providers| providers.contains(&provider))
+    }
+}
+
+impl FromStr for NotifyMatrix {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut matrix = HashMap::new();
+        for entry in s.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let mut iter = entry.splitn(2, '=');
+            let event: NotifyEvent = iter.next().unwrap_or("").trim().parse()?;
+            let providers = iter
+                .next()
+                .ok_or_else(|| format_err!("No providers for {event}"))?
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty() && *p != "none")
+                .map(str::parse)
+                .collect::<Result<Vec<NotifyProvider>, Error>>()?;
+            matrix.insert(event, providers);
+        }
+        Ok(Self(matrix))
+    }
+}
+
+impl TryFrom<String> for NotifyMatrix {
+    type Error = Error;
+    fn try_from(item: String) -> Result<Self, Self::Error> {
+        item.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::notification::{NotifyEvent, NotifyMatrix, NotifyProvider};
+
+    #[test]
+    fn test_notify_matrix() -> Result<(), Error> {
+        let matrix: NotifyMatrix =
+            "milestone=telegram, ntfy; sync_failure=email;ramp_rate=none".parse()?;
+        assert!(matrix.is_enabled(NotifyEvent::Milestone, NotifyProvider::Ntfy));
+        assert!(!matrix.is_enabled(NotifyEvent::Milestone, NotifyProvider::Email));
+        assert!(matrix.is_enabled(NotifyEvent::SyncFailure, NotifyProvider::Email));
+        assert!(!matrix.is_enabled(NotifyEvent::RampRate, NotifyProvider::Telegram));
+        assert!(matrix.is_enabled(NotifyEvent::MissingSync, NotifyProvider::Telegram));
+        assert!(NotifyMatrix::default().is_enabled(NotifyEvent::RampRate, NotifyProvider::Ntfy));
+        assert!("anomaly=email".parse::<NotifyMatrix>().is_err());
+        assert!("milestone=pager".parse::<NotifyMatrix>().is_err());
+        assert!("birthday=email".parse::<NotifyMatrix>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+const METERS_PER_MILE: f64 = 1609.344;
+
+/// Shortest split accepted, anything less gives a table with a row every few
+/// gps points
+const MIN_SPLIT_METERS: f64 = 100.0;
+
+#[derive(Debug, PartialEq, Copy, Clone, Eq)]
+pub enum DistanceUnit {
+    Meter,
+    Kilometer,
+    Mile,
+}
+
+impl DistanceUnit {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Meter => "m",
+            Self::Kilometer => "km",
+            Self::Mile => "mi",
+        }
+    }
+
+    #[must_use]
+    pub fn meters(self) -> f64 {
+        match self {
+            Self::Meter => 1.0,
+            Self::Kilometer => 1000.0,
+            Self::Mile => METERS_PER_MILE,
+        }
+    }
+}
+
+impl fmt::Display for DistanceUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for DistanceUnit {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "m" | "meter" | "meters" => Ok(Self::Meter),
+            "k" | "km" | "kilometer" | "kilometers" => Ok(Self::Kilometer),
+            "mi" | "mile" | "miles" => Ok(Self::Mile),
+            _ => Err(format_err!("{s} is not a valid distance unit")),
+        }
+    }
+}
+
+/// Distance between splits in the file reports, written as `1mi`, `1km`,
+/// `5k` or `400m`
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(into = "StackString", try_from = "StackString")]
+pub struct SplitDistance {
+    value: f64,
+    unit: DistanceUnit,
+}
+
+impl Default for SplitDistance {
+    fn default() -> Self {
+        Self::MILE
+    }
+}
+
+impl SplitDistance {
+    pub const MILE: Self = Self {
+        value: 1.0,
+        unit: DistanceUnit::Mile,
+    };
+    pub const FIVE_K: Self = Self {
+        value: 5.0,
+        unit: DistanceUnit::Kilometer,
+    };
+
+    /// # Errors
+    /// Return error if the split is shorter than 100m
+    pub fn new(value: f64, unit: DistanceUnit) -> Result<Self, Error> {
+        if !value.is_finite() || value * unit.meters() < MIN_SPLIT_METERS {
+            return Err(format_err!("Split distance {value}{unit} is too short"));
+        }
+        Ok(Self { value, unit })
+    }
+
+    #[must_use]
+    pub fn value(self) -> f64 {
+        self.value
+    }
+
+    #[must_use]
+    pub fn unit(self) -> DistanceUnit {
+        self.unit
+    }
+
+    #[must_use]
+    pub fn meters(self) -> f64 {
+        self.value * self.unit.meters()
+    }
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        self.unit.to_str()
+    }
+
+    /// Distance marker for the end of split number `count` (starting at 1),
+    /// e.g. `800 m` or `1.5 mi`
+    #[must_use]
+    pub fn marker(self, count: usize) -> StackString {
+        format_sstr!("{} {}", format_value(count as f64 * self.value), self.unit)
+    }
+}
+
+fn format_value(value: f64) -> StackString {
+    if (value - value.round()).abs() < 1e-6 {
+        format_sstr!("{value:.0}")
+    } else {
+        let s = format_sstr!("{value:.3}");
+        s.trim_end_matches('0').into()
+    }
+}
+
+impl fmt::Display for SplitDistance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", format_value(self.value), self.unit)
+    }
+}
+
+impl FromStr for SplitDistance {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let idx = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (value, unit) = s.split_at(idx);
+        let unit: DistanceUnit = unit.trim().parse()?;
+        let value = if value.is_empty() {
+            1.0
+        } else {
+            value
+                .parse()
+                .map_err(|e| format_err!("{s} is not a valid split distance {e}"))?
+        };
+        Self::new(value, unit)
+    }
+}
+
+impl From<SplitDistance> for StackString {
+    fn from(item: SplitDistance) -> StackString {
+        StackString::from_display(item)
+    }
+}
+
+impl TryFrom<StackString> for SplitDistance {
+    type Error = Error;
+    fn try_from(item: StackString) -> Result<Self, Self::Error> {
+        item.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::split_distance::{DistanceUnit, SplitDistance};
+
+    #[test]
+    fn test_split_distance() -> Result<(), Error> {
+        let split: SplitDistance = "400m".parse()?;
+        assert_eq!(split.unit(), DistanceUnit::Meter);
+        assert!((split.meters() - 400.0).abs() < 1e-6);
+        assert_eq!(split.marker(3).as_str(), "1200 m");
+
+        let split: SplitDistance = "5k".parse()?;
+        assert_eq!(split, SplitDistance::FIVE_K);
+        assert_eq!(split.to_string(), "5km");
+
+        let split: SplitDistance = "mile".parse()?;
+        assert_eq!(split, SplitDistance::MILE);
+        let split: SplitDistance = "0.5mi".parse()?;
+        assert_eq!(split.marker(3).as_str(), "1.5 mi");
+        assert_eq!(split.to_string(), "0.5mi");
+
+        assert!("50m".parse::<SplitDistance>().is_err());
+        assert!("0km".parse::<SplitDistance>().is_err());
+        assert!("1 furlong".parse::<SplitDistance>().is_err());
+
+        let split: SplitDistance = serde_json::from_str(r#""1 km""#)?;
+        assert_eq!(serde_json::to_string(&split)?, r#""1km""#);
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::Error;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing_subscriber::{
+    filter::EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt, Layer,
+};
+
+use crate::garmin_config::GarminConfig;
+
+/// Spans exported over OTLP, the exporter's own http/2 client is left out so
+/// exporting doesn't trace itself
+const OTLP_FILTER: &str = "info,h2=off,hyper=off,hyper_util=off,tonic=off,tower=off";
+
+/// Logging and tracing for the binaries: `log` records and `tracing` events
+/// go to stderr filtered by `RUST_LOG` as with `env_logger`, and when
+/// `otlp_endpoint` is configured spans are also sent to that collector
+/// (Jaeger, Tempo, ...). Must be created inside the tokio runtime and kept
+/// alive until exit, dropping it flushes the pending spans.
+pub struct Telemetry {
+    provider: Option<TracerProvider>,
+}
+
+impl Telemetry {
+    /// # Errors
+    /// Return error if the exporter can't be built or a global subscriber
+    /// is already set
+    pub fn init(config: &GarminConfig, service_name: &'static str) -> Result<Self, Error> {
+        let stderr = fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_filter(EnvFilter::from_default_env());
+
+        let provider = match &config.otlp_endpoint {
+            Some(endpoint) => {
+                let exporter = SpanExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(endpoint.as_str())
+                    .build()?;
+                Some(
+                    TracerProvider::builder()
+                        .with_batch_exporter(exporter, runtime::Tokio)
+                        .with_resource(Resource::new([KeyValue::new("service.name", service_name)]))
+                        .build(),
+                )
+            }
+            None => None,
+        };
+        let otlp = provider.as_ref().map(|provider| {
+            tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer(service_name))
+                .with_filter(EnvFilter::new(OTLP_FILTER))
+        });
+
+        tracing_subscriber::registry()
+            .with(stderr)
+            .with(otlp)
+            .try_init()?;
+        Ok(Self { provider })
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("failed to flush spans {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use time::{Date, Duration};
+
+/// First day of the week used when aggregating weekly reports.
+#[derive(Debug, PartialEq, Copy, Clone, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    Monday,
+    Sunday,
+}
+
+impl Default for WeekStart {
+    fn default() -> Self {
+        Self::Monday
+    }
+}
+
+impl WeekStart {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Monday => "monday",
+            Self::Sunday => "sunday",
+        }
+    }
+
+    /// Interval added to a local timestamp in sql so that `EXTRACT(week ...)`
+    /// and `EXTRACT(isoyear ...)` bucket by this week start.
+    #[must_use]
+    pub fn sql_shift(self) -> &'static str {
+        match self {
+            Self::Monday => "",
+            Self::Sunday => " + interval '1 day'",
+        }
+    }
+
+    /// Returns (year, week number) of `date` for weeks starting on this day.
+    #[must_use]
+    pub fn year_week(self, date: Date) -> (i32, u8) {
+        let date = match self {
+            Self::Monday => date,
+            Self::Sunday => date + Duration::days(1),
+        };
+        let (year, week, _) = date.to_iso_week_date();
+        (year, week)
+    }
+
+    /// First day of the week containing `date`.
+    #[must_use]
+    pub fn week_start_date(self, date: Date) -> Date {
+        let days = match self {
+            Self::Monday => date.weekday().number_days_from_monday(),
+            Self::Sunday => date.weekday().number_days_from_sunday(),
+        };
+        date - Duration::days(days.into())
+    }
+}
+
+impl fmt::Display for WeekStart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for WeekStart {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "monday" | "mon" => Ok(Self::Monday),
+            "sunday" | "sun" => Ok(Self::Sunday),
+            _ => Err(format_err!("{s} is not a valid week start")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::date;
+
+    use crate::week_start::WeekStart;
+
+    #[test]
+    fn test_week_start_year_week() -> Result<(), Error> {
+        let saturday = date!(2023 - 01 - 07);
+        let sunday = date!(2023 - 01 - 08);
+        assert_eq!(WeekStart::Monday.year_week(saturday), (2023, 1));
+        assert_eq!(WeekStart::Monday.year_week(sunday), (2023, 1));
+        assert_eq!(WeekStart::Sunday.year_week(saturday), (2023, 1));
+        assert_eq!(WeekStart::Sunday.year_week(sunday), (2023, 2));
+        assert_eq!(
+            WeekStart::Monday.week_start_date(sunday),
+            date!(2023 - 01 - 02)
+        );
+        assert_eq!(
+            WeekStart::Sunday.week_start_date(saturday),
+            date!(2023 - 01 - 01)
+        );
+        assert_eq!(WeekStart::Sunday.week_start_date(sunday), sunday);
+        let week_start: WeekStart = "Sunday".parse()?;
+        assert_eq!(week_start, WeekStart::Sunday);
+        assert!("funday".parse::<WeekStart>().is_err());
+        Ok(())
+    }
+}
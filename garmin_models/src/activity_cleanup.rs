@@ -0,0 +1,187 @@
+use anyhow::{format_err, Error};
+use log::debug;
+use postgres_query::{query_dyn, Parameter};
+use stack_string::{format_sstr, StackString};
+use std::path::Path;
+use time::Date;
+use tokio::fs::remove_file;
+use uuid::Uuid;
+
+use garmin_lib::garmin_config::GarminConfig;
+use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
+
+use crate::{
+    garmin_summary::GarminSummary,
+    garmin_sync::{GarminSync, KeyItemCache},
+};
+
+/// Selects activities to bulk delete, every set field has to match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CleanupFilter {
+    pub start_date: Option<Date>,
+    pub end_date: Option<Date>,
+    pub sport: Option<SportTypes>,
+    /// Only activities without any recorded distance
+    pub zero_distance: bool,
+}
+
+impl CleanupFilter {
+    /// An empty filter would match every activity
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.start_date.is_none()
+            && self.end_date.is_none()
+            && self.sport.is_none()
+            && !self.zero_distance
+    }
+
+    /// Activities matching the filter, this is the dry-run listing
+    /// # Errors
+    /// Return error if the filter is empty or db query fails
+    pub async fn get_matching(&self, pool: &PgPool) -> Result<Vec<GarminSummary>, Error> {
+        if self.is_empty() {
+            return Err(format_err!("Refusing to match every activity"));
+        }
+        let mut conditions = Vec::new();
+        let mut bindings = Vec::new();
+        if let Some(d) = &self.start_date {
+            conditions.push("date(begin_datetime) >= $start_date");
+            bindings.push(("start_date", d as Parameter));
+        }
+        if let Some(d) = &self.end_date {
+            conditions.push("date(begin_datetime) <= $end_date");
+            bindings.push(("end_date", d as Parameter));
+        }
+        if let Some(sport) = &self.sport {
+            conditions.push("sport = $sport");
+            bindings.push(("sport", sport as Parameter));
+        }
+        if self.zero_distance {
+            conditions.push("COALESCE(total_distance, 0.0) <= 0.0");
+        }
+        let query = format_sstr!(
+            "
+                SELECT id,
+                    filename,
+                    begin_datetime,
+                    sport,
+                    total_calories,
+                    total_distance,
+                    total_duration,
+                    total_hr_dur,
+                    total_hr_dis,
+                    md5sum,
+                    course_difficulty,
+                    split_differential,
+                    avg_power,
+                    normalized_power,
+                    avg_temperature,
+                    calories_estimated
+                FROM garmin_summary
+                WHERE {}
+                ORDER BY begin_datetime
+            ",
+            conditions.join(" AND ")
+        );
+        let query = query_dyn!(&query, ..bindings)?;
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Synced activities stay, they're only unlinked; everything derived from
+/// the file goes with the summary
+const DELETE_SUMMARY_QUERIES: [&str; 6] = [
+    "UPDATE strava_activities SET summary_id = NULL WHERE summary_id = ANY($1)",
+    "UPDATE fitbit_activities SET summary_id = NULL WHERE summary_id = ANY($1)",
+    "UPDATE garmin_connect_activities SET summary_id = NULL WHERE summary_id = ANY($1)",
+    "DELETE FROM garmin_corrections_laps WHERE summary_id = ANY($1)",
+    "DELETE FROM race_results_garmin_summary WHERE summary_id = ANY($1)",
+    "DELETE FROM garmin_summary WHERE id = ANY($1)",
+];
+
+async fn delete_summaries(pool: &PgPool, summary_ids: &[Uuid]) -> Result<(), Error> {
+    let mut conn = pool.get().await?;
+    let tran = conn.transaction().await?;
+    for query in DELETE_SUMMARY_QUERIES {
+        tran.execute(query, &[&summary_ids]).await?;
+    }
+    tran.commit().await?;
+    Ok(())
+}
+
+async fn delete_local_file(path: &Path) -> Result<bool, Error> {
+    if path.exists() {
+        remove_file(path).await?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Delete `summaries` from the db along with their link table rows in one
+/// transaction, then the original and cached files and their s3 copies, the
+/// s3 copies are left alone without `gsync`
+/// # Errors
+/// Return error if db queries, file removal or s3 api calls fail
+pub async fn delete_activities(
+    pool: &PgPool,
+    config: &GarminConfig,
+    gsync: Option<&GarminSync>,
+    summaries: &[GarminSummary],
+) -> Result<Vec<StackString>, Error> {
+    let summary_ids: Vec<_> = summaries.iter().map(|s| s.id).collect();
+    delete_summaries(pool, &summary_ids).await?;
+    let mut output = Vec::new();
+    for summary in summaries {
+        let filename = &summary.filename;
+        let cache_key = format_sstr!("{filename}.avro");
+        for (local_dir, s3_bucket, s3_key) in [
+            (&config.gps_dir, &config.gps_bucket, filename),
+            (&config.cache_dir, &config.cache_bucket, &cache_key),
+        ] {
+            if delete_local_file(&local_dir.join(s3_key)).await? {
+                debug!("removed {s3_key} from {local_dir:?}");
+            }
+            if let Some(gsync) = gsync {
+                gsync.delete_file(s3_bucket, s3_key).await?;
+            }
+            KeyItemCache::delete(pool, s3_key, s3_bucket).await?;
+        }
+        output.push(format_sstr!(
+            "deleted {filename} {} {}",
+            summary.begin_datetime,
+            summary.sport
+        ));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::activity_cleanup::CleanupFilter;
+
+    #[test]
+    fn test_cleanup_filter_is_empty() {
+        assert!(CleanupFilter::default().is_empty());
+        let filter = CleanupFilter {
+            start_date: Some(date!(2024 - 01 - 01)),
+            ..CleanupFilter::default()
+        };
+        assert!(!filter.is_empty());
+        let filter = CleanupFilter {
+            sport: Some(SportTypes::Running),
+            ..CleanupFilter::default()
+        };
+        assert!(!filter.is_empty());
+        let filter = CleanupFilter {
+            zero_distance: true,
+            ..CleanupFilter::default()
+        };
+        assert!(!filter.is_empty());
+    }
+}
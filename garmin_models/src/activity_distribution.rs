@@ -0,0 +1,401 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
+
+use crate::garmin_file::GarminFile;
+
+/// Longest gap in seconds between points which is counted towards a bucket,
+/// longer gaps are pauses
+pub const MAX_SAMPLE_GAP: f64 = 30.0;
+
+/// `cadence` as recorded by the device in the units shown for `sport`,
+/// devices count strides when running so it's doubled to steps per minute
+#[must_use]
+pub fn cadence_per_minute(cadence: f64, sport: SportTypes) -> f64 {
+    if sport == SportTypes::Running {
+        2.0 * cadence
+    } else {
+        cadence
+    }
+}
+
+/// Quantity binned in a distribution, `Cadence` is steps per minute for
+/// running and revolutions per minute otherwise, `StrideLength` is meters per
+/// step and only recorded for running
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DistributionMetric {
+    Cadence,
+    StrideLength,
+}
+
+impl Default for DistributionMetric {
+    fn default() -> Self {
+        Self::Cadence
+    }
+}
+
+impl DistributionMetric {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Cadence => "cadence",
+            Self::StrideLength => "stride_length",
+        }
+    }
+
+    /// Width of each bucket, 5 spm (or rpm) and 10 cm
+    #[must_use]
+    pub fn bucket_width(self) -> f64 {
+        match self {
+            Self::Cadence => 5.0,
+            Self::StrideLength => 0.1,
+        }
+    }
+
+    /// Values outside this range are treated as sensor noise
+    fn valid_range(self) -> (f64, f64) {
+        match self {
+            Self::Cadence => (20.0, 250.0),
+            Self::StrideLength => (0.3, 3.0),
+        }
+    }
+
+    #[must_use]
+    pub fn units(self, sport: SportTypes) -> &'static str {
+        match self {
+            Self::Cadence if sport == SportTypes::Running => "spm",
+            Self::Cadence => "rpm",
+            Self::StrideLength => "m",
+        }
+    }
+
+    /// Lower edge of the bucket containing `value`
+    #[must_use]
+    pub fn bucket(self, value: f64) -> f64 {
+        let width = self.bucket_width();
+        // round so buckets compare equal when read back from the db
+        ((value / width).floor() * width * 100.0).round() / 100.0
+    }
+}
+
+impl fmt::Display for DistributionMetric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for DistributionMetric {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "cadence" => Ok(Self::Cadence),
+            "stride_length" | "stride" => Ok(Self::StrideLength),
+            _ => Err(format_err!("{s} is not a valid distribution metric")),
+        }
+    }
+}
+
+#[derive(FromSqlRow)]
+struct BucketRow {
+    bucket: f64,
+    seconds: f64,
+}
+
+#[derive(FromSqlRow)]
+struct MissingDistributionRow {
+    id: Uuid,
+    filename: StackString,
+}
+
+/// Time spent in each cadence or stride length bucket of an activity,
+/// stored in `activity_distributions`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActivityDistribution {
+    pub summary_id: Uuid,
+    pub metric: DistributionMetric,
+    /// (bucket lower edge, seconds)
+    pub buckets: Vec<(f64, f64)>,
+}
+
+impl ActivityDistribution {
+    /// Distributions for every metric with data in `gfile`
+    #[must_use]
+    pub fn from_gfile(summary_id: Uuid, gfile: &GarminFile) -> Vec<Self> {
+        let is_running = gfile.sport == SportTypes::Running;
+        let mut cadence = Vec::new();
+        let mut stride_length = Vec::new();
+        for point in &gfile.points {
+            if point.duration_from_last <= 0.0 || point.duration_from_last > MAX_SAMPLE_GAP {
+                continue;
+            }
+            let raw_cadence = match point.cadence {
+                Some(c) if c > 0.0 => c,
+                _ => continue,
+            };
+            let value = cadence_per_minute(raw_cadence, gfile.sport);
+            cadence.push((value, point.duration_from_last));
+            if is_running && point.speed_mps > 0.0 {
+                let stride = point.speed_mps * 60.0 / value;
+                stride_length.push((stride, point.duration_from_last));
+            }
+        }
+        [
+            (DistributionMetric::Cadence, cadence),
+            (DistributionMetric::StrideLength, stride_length),
+        ]
+        .into_iter()
+        .filter_map(|(metric, samples)| {
+            let buckets = histogram(metric, &samples);
+            if buckets.is_empty() {
+                None
+            } else {
+                Some(Self {
+                    summary_id,
+                    metric,
+                    buckets,
+                })
+            }
+        })
+        .collect()
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let conn = pool.get().await?;
+        let metric = self.metric.to_str();
+        for (bucket, seconds) in &self.buckets {
+            let query = query!(
+                "
+                    INSERT INTO activity_distributions (summary_id, metric, bucket, seconds)
+                    VALUES ($summary_id, $metric, $bucket, $seconds)
+                    ON CONFLICT (summary_id, metric, bucket) DO UPDATE
+                    SET seconds=EXCLUDED.seconds
+                ",
+                summary_id = self.summary_id,
+                metric = metric,
+                bucket = bucket,
+                seconds = seconds,
+            );
+            query.execute(&conn).await?;
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_by_summary_id(
+        pool: &PgPool,
+        summary_id: Uuid,
+        metric: DistributionMetric,
+    ) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT bucket, seconds FROM activity_distributions
+                WHERE summary_id = $summary_id AND metric = $metric
+                ORDER BY bucket
+            ",
+            summary_id = summary_id,
+            metric = metric.to_str(),
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<BucketRow> = query.fetch(&conn).await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            summary_id,
+            metric,
+            buckets: rows.into_iter().map(|r| (r.bucket, r.seconds)).collect(),
+        }))
+    }
+
+    /// Total seconds in each bucket over all `sport` activities starting
+    /// between `start` and `end`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_totals(
+        pool: &PgPool,
+        metric: DistributionMetric,
+        sport: SportTypes,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Result<Vec<(f64, f64)>, Error> {
+        let query = query!(
+            "
+                SELECT a.bucket, sum(a.seconds) as seconds
+                FROM activity_distributions a
+                JOIN garmin_summary b ON a.summary_id = b.id
+                WHERE a.metric = $metric
+                  AND b.sport = $sport
+                  AND b.begin_datetime >= $start
+                  AND b.begin_datetime < $end
+                GROUP BY a.bucket
+                ORDER BY a.bucket
+            ",
+            metric = metric.to_str(),
+            sport = sport,
+            start = start,
+            end = end,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<BucketRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.bucket, r.seconds)).collect())
+    }
+
+    /// (id, filename) of running and biking activities which haven't been
+    /// analyzed yet, activities without cadence or stride samples are only
+    /// read once
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_summaries(pool: &PgPool) -> Result<Vec<(Uuid, StackString)>, Error> {
+        let query = query!(
+            "
+                SELECT a.id, a.filename
+                FROM garmin_summary a
+                WHERE a.sport IN ('running', 'biking')
+                  AND NOT EXISTS (
+                    SELECT 1 FROM activity_distributions_analyzed b
+                    WHERE b.summary_id = a.id
+                  )
+                ORDER BY a.begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingDistributionRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+
+    /// Record that the distributions of `summary_id` are up to date, whether
+    /// or not any were found
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn mark_analyzed(pool: &PgPool, summary_id: Uuid) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_distributions_analyzed (summary_id)
+                VALUES ($summary_id)
+                ON CONFLICT (summary_id) DO UPDATE SET analyzed_at=now()
+            ",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Drop the stored distributions of re-imported activities so that the
+    /// next sync recomputes them from the new files
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn clear_analyzed(pool: &PgPool, summary_ids: &[Uuid]) -> Result<(), Error> {
+        if summary_ids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        tran.execute(
+            "DELETE FROM activity_distributions WHERE summary_id = ANY($1)",
+            &[&summary_ids],
+        )
+        .await?;
+        tran.execute(
+            "DELETE FROM activity_distributions_analyzed WHERE summary_id = ANY($1)",
+            &[&summary_ids],
+        )
+        .await?;
+        tran.commit().await?;
+        Ok(())
+    }
+}
+
+/// Seconds spent in each bucket of `metric` from (value, seconds) samples,
+/// sorted by bucket
+#[must_use]
+pub fn histogram(metric: DistributionMetric, samples: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let (min, max) = metric.valid_range();
+    let mut buckets: BTreeMap<i64, f64> = BTreeMap::new();
+    for (value, seconds) in samples {
+        if *value < min || *value > max {
+            continue;
+        }
+        let key = (value / metric.bucket_width()).floor() as i64;
+        *buckets.entry(key).or_default() += seconds;
+    }
+    buckets
+        .into_iter()
+        .map(|(key, seconds)| (metric.bucket(key as f64 * metric.bucket_width()), seconds))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use uuid::Uuid;
+
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::{
+        activity_distribution::{histogram, ActivityDistribution, DistributionMetric},
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+    };
+
+    #[test]
+    fn test_histogram() {
+        let samples = [(171.0, 1.0), (174.9, 2.0), (175.0, 1.0), (400.0, 5.0)];
+        let buckets = histogram(DistributionMetric::Cadence, &samples);
+        assert_eq!(buckets, vec![(170.0, 3.0), (175.0, 1.0)]);
+
+        let buckets = histogram(
+            DistributionMetric::StrideLength,
+            &[(1.05, 2.0), (1.15, 1.0)],
+        );
+        assert_eq!(buckets, vec![(1.0, 2.0), (1.1, 1.0)]);
+        assert_eq!(
+            "stride".parse::<DistributionMetric>().unwrap(),
+            DistributionMetric::StrideLength
+        );
+    }
+
+    #[test]
+    fn test_distribution_from_gfile() {
+        // 90 strides per minute at 3 m/s is 180 spm with a 1m stride
+        let points: Vec<_> = (0..10)
+            .map(|idx| GarminPoint {
+                duration_from_last: if idx == 0 { 0.0 } else { 1.0 },
+                cadence: Some(90.0),
+                speed_mps: 3.0,
+                ..GarminPoint::default()
+            })
+            .collect();
+        let gfile = GarminFile {
+            sport: SportTypes::Running,
+            points,
+            ..GarminFile::default()
+        };
+        let distributions = ActivityDistribution::from_gfile(Uuid::new_v4(), &gfile);
+        assert_eq!(distributions.len(), 2);
+        assert_eq!(distributions[0].metric, DistributionMetric::Cadence);
+        assert_eq!(distributions[0].buckets.len(), 1);
+        assert_abs_diff_eq!(distributions[0].buckets[0].0, 180.0);
+        assert_abs_diff_eq!(distributions[0].buckets[0].1, 9.0);
+        assert_eq!(distributions[1].metric, DistributionMetric::StrideLength);
+        assert_abs_diff_eq!(distributions[1].buckets[0].0, 1.0);
+
+        let gfile = GarminFile {
+            sport: SportTypes::Biking,
+            ..gfile
+        };
+        let distributions = ActivityDistribution::from_gfile(Uuid::new_v4(), &gfile);
+        assert_eq!(distributions.len(), 1);
+        assert_abs_diff_eq!(distributions[0].buckets[0].0, 90.0);
+    }
+}
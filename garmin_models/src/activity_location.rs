@@ -0,0 +1,366 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use url::Url;
+use uuid::Uuid;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, geocode_provider::GeocodeProvider};
+use garmin_utils::pgpool::PgPool;
+
+use crate::{garmin_file::GarminFile, osm_client::OsmClient};
+
+/// Start points within this many degrees share a `geocode_cache` entry,
+/// roughly a kilometer
+pub const GEOCODE_CACHE_PRECISION: f64 = 0.01;
+
+/// City, region and country of a point
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Location {
+    pub city: Option<StackString>,
+    pub region: Option<StackString>,
+    pub country: Option<StackString>,
+}
+
+impl Location {
+    /// Look up `(lat, lon)` in `geocode_cache`, falling back on `provider`
+    /// and caching the result
+    /// # Errors
+    /// Return error if db query or the geocoding request fails
+    pub async fn get_location(
+        pool: &PgPool,
+        client: &OsmClient,
+        provider: GeocodeProvider,
+        endpoint: &Url,
+        lat: f64,
+        lon: f64,
+    ) -> Result<Self, Error> {
+        let (lat_key, lon_key) = cache_key(lat, lon);
+        let query = query!(
+            "
+                SELECT city, region, country
+                FROM geocode_cache
+                WHERE lat_key = $lat_key AND lon_key = $lon_key
+            ",
+            lat_key = lat_key,
+            lon_key = lon_key,
+        );
+        let conn = pool.get().await?;
+        if let Some(location) = query.fetch_opt(&conn).await? {
+            return Ok(location);
+        }
+        let location = reverse_geocode(client, provider, endpoint, lat, lon).await?;
+        let query = query!(
+            "
+                INSERT INTO geocode_cache (lat_key, lon_key, city, region, country)
+                VALUES ($lat_key, $lon_key, $city, $region, $country)
+                ON CONFLICT (lat_key, lon_key) DO NOTHING
+            ",
+            lat_key = lat_key,
+            lon_key = lon_key,
+            city = location.city,
+            region = location.region,
+            country = location.country,
+        );
+        query.execute(&conn).await?;
+        Ok(location)
+    }
+}
+
+#[derive(Deserialize)]
+struct NominatimAddress {
+    city: Option<StackString>,
+    town: Option<StackString>,
+    village: Option<StackString>,
+    hamlet: Option<StackString>,
+    state: Option<StackString>,
+    country: Option<StackString>,
+}
+
+#[derive(Deserialize)]
+struct NominatimResponse {
+    address: Option<NominatimAddress>,
+}
+
+#[derive(Deserialize)]
+struct PhotonProperties {
+    city: Option<StackString>,
+    name: Option<StackString>,
+    state: Option<StackString>,
+    country: Option<StackString>,
+}
+
+#[derive(Deserialize)]
+struct PhotonFeature {
+    properties: PhotonProperties,
+}
+
+#[derive(Deserialize)]
+struct PhotonResponse {
+    features: Vec<PhotonFeature>,
+}
+
+/// Rounded coordinates used as the `geocode_cache` key
+#[must_use]
+pub fn cache_key(lat: f64, lon: f64) -> (i32, i32) {
+    (
+        (lat / GEOCODE_CACHE_PRECISION).round() as i32,
+        (lon / GEOCODE_CACHE_PRECISION).round() as i32,
+    )
+}
+
+/// # Errors
+/// Return error if the request fails or the response can't be parsed
+pub async fn reverse_geocode(
+    client: &OsmClient,
+    provider: GeocodeProvider,
+    endpoint: &Url,
+    lat: f64,
+    lon: f64,
+) -> Result<Location, Error> {
+    let lat = StackString::from_display(lat);
+    let lon = StackString::from_display(lon);
+    let url = endpoint.join("reverse")?;
+    match provider {
+        GeocodeProvider::Nominatim => {
+            let response: NominatimResponse = client
+                .get()
+                .await
+                .get(url)
+                .query(&[
+                    ("format", "jsonv2"),
+                    ("lat", lat.as_str()),
+                    ("lon", lon.as_str()),
+                    ("zoom", "10"),
+                    ("accept-language", "en"),
+                ])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            Ok(response
+                .address
+                .map_or_else(Location::default, |a| Location {
+                    city: a.city.or(a.town).or(a.village).or(a.hamlet),
+                    region: a.state,
+                    country: a.country,
+                }))
+        }
+        GeocodeProvider::Photon => {
+            let response: PhotonResponse = client
+                .get()
+                .await
+                .get(url)
+                .query(&[("lat", lat.as_str()), ("lon", lon.as_str()), ("lang", "en")])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            Ok(response
+                .features
+                .into_iter()
+                .next()
+                .map_or_else(Location::default, |f| Location {
+                    city: f.properties.city.or(f.properties.name),
+                    region: f.properties.state,
+                    country: f.properties.country,
+                }))
+        }
+    }
+}
+
+/// First point of `gfile` with a gps fix
+#[must_use]
+pub fn start_point(gfile: &GarminFile) -> Option<(f64, f64)> {
+    gfile
+        .points
+        .iter()
+        .find_map(|p| match (p.latitude, p.longitude) {
+            (Some(lat), Some(lon)) => Some((lat, lon)),
+            _ => None,
+        })
+}
+
+#[derive(FromSqlRow)]
+struct MissingLocationRow {
+    id: Uuid,
+    filename: StackString,
+}
+
+/// Start point and location stored on `garmin_summary`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityLocation {
+    pub summary_id: Uuid,
+    pub start: Option<(f64, f64)>,
+    pub location: Location,
+}
+
+impl ActivityLocation {
+    /// Activities without a gps fix are stored with an empty location so
+    /// they aren't checked again
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn update_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let start_latitude = self.start.map(|(lat, _)| lat);
+        let start_longitude = self.start.map(|(_, lon)| lon);
+        let query = query!(
+            "
+                UPDATE garmin_summary
+                SET start_latitude = $start_latitude,
+                    start_longitude = $start_longitude,
+                    city = $city,
+                    region = $region,
+                    country = $country,
+                    geocoded_at = now()
+                WHERE id = $id
+            ",
+            id = self.summary_id,
+            start_latitude = start_latitude,
+            start_longitude = start_longitude,
+            city = self.location.city,
+            region = self.location.region,
+            country = self.location.country,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// (id, filename) of activities which haven't been geocoded
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_summaries(pool: &PgPool) -> Result<Vec<(Uuid, StackString)>, Error> {
+        let query = query!(
+            "
+                SELECT id, filename
+                FROM garmin_summary
+                WHERE geocoded_at IS NULL
+                ORDER BY begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingLocationRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+}
+
+/// A city with the number of activities started there, positioned at the
+/// average start point
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CityVisit {
+    pub city: StackString,
+    pub region: Option<StackString>,
+    pub country: Option<StackString>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub activities: i64,
+    pub first_visit: DateTimeWrapper,
+    pub last_visit: DateTimeWrapper,
+}
+
+impl CityVisit {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT city,
+                       region,
+                       country,
+                       avg(start_latitude) as latitude,
+                       avg(start_longitude) as longitude,
+                       count(*) as activities,
+                       min(begin_datetime) as first_visit,
+                       max(begin_datetime) as last_visit
+                FROM garmin_summary
+                WHERE city IS NOT NULL
+                  AND start_latitude IS NOT NULL
+                  AND start_longitude IS NOT NULL
+                GROUP BY city, region, country
+                ORDER BY activities DESC, city
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Number of activities and first visit in a country, or in a US state
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegionVisit {
+    pub name: StackString,
+    pub activities: i64,
+    pub first_visit: DateTimeWrapper,
+    pub last_visit: DateTimeWrapper,
+}
+
+impl RegionVisit {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_countries(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT country as name,
+                       count(*) as activities,
+                       min(begin_datetime) as first_visit,
+                       max(begin_datetime) as last_visit
+                FROM garmin_summary
+                WHERE country IS NOT NULL
+                GROUP BY country
+                ORDER BY activities DESC, country
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// States of activities in the US, the geocoders name it either way
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_us_states(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT region as name,
+                       count(*) as activities,
+                       min(begin_datetime) as first_visit,
+                       max(begin_datetime) as last_visit
+                FROM garmin_summary
+                WHERE region IS NOT NULL
+                  AND country IN ('United States', 'United States of America')
+                GROUP BY region
+                ORDER BY activities DESC, region
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        activity_location::{cache_key, start_point},
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+    };
+
+    #[test]
+    fn test_activity_location() {
+        assert_eq!(cache_key(42.3601, -71.0589), (4236, -7106));
+        assert_eq!(cache_key(42.3649, -71.0551), (4236, -7106));
+
+        let mut gfile = GarminFile::default();
+        assert_eq!(start_point(&gfile), None);
+        gfile.points = vec![
+            GarminPoint::default(),
+            GarminPoint {
+                latitude: Some(42.36),
+                longitude: Some(-71.06),
+                ..GarminPoint::default()
+            },
+        ];
+        assert_eq!(start_point(&gfile), Some((42.36, -71.06)));
+    }
+}
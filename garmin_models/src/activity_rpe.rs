@@ -0,0 +1,89 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use garmin_utils::pgpool::PgPool;
+
+/// Lowest and highest rating of perceived exertion
+pub const RPE_RANGE: (i16, i16) = (1, 10);
+
+/// Rating of perceived exertion of an activity, entered after the fact and
+/// stored in `activity_rpe`, training load falls back on it for activities
+/// recorded without heart rate
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, FromSqlRow)]
+pub struct ActivityRpe {
+    pub summary_id: Uuid,
+    pub rpe: i16,
+}
+
+impl ActivityRpe {
+    /// # Errors
+    /// Return error if `rpe` is outside of `RPE_RANGE`
+    pub fn new(summary_id: Uuid, rpe: i16) -> Result<Self, Error> {
+        let (low, high) = RPE_RANGE;
+        if rpe < low || rpe > high {
+            return Err(format_err!("RPE must be between {low} and {high}"));
+        }
+        Ok(Self { summary_id, rpe })
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_summary_id(pool: &PgPool, summary_id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "SELECT summary_id, rpe FROM activity_rpe WHERE summary_id = $summary_id",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_rpe (summary_id, rpe)
+                VALUES ($summary_id, $rpe)
+                ON CONFLICT (summary_id) DO UPDATE
+                    SET rpe=EXCLUDED.rpe,
+                        updated_at=now()
+            ",
+            summary_id = self.summary_id,
+            rpe = self.rpe,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete_by_summary_id(pool: &PgPool, summary_id: Uuid) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM activity_rpe WHERE summary_id = $summary_id",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::activity_rpe::ActivityRpe;
+
+    #[test]
+    fn test_activity_rpe_range() {
+        let id = Uuid::new_v4();
+        assert_eq!(ActivityRpe::new(id, 7).unwrap().rpe, 7);
+        assert!(ActivityRpe::new(id, 1).is_ok());
+        assert!(ActivityRpe::new(id, 10).is_ok());
+        assert!(ActivityRpe::new(id, 0).is_err());
+        assert!(ActivityRpe::new(id, 11).is_err());
+    }
+}
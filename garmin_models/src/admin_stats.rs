@@ -0,0 +1,255 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{fs::read_dir, path::Path};
+use time::{Date, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+use tokio::task::spawn_blocking;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
+use garmin_utils::pgpool::PgPool;
+
+use crate::processing_lock::LockHolder;
+
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TableRowCount {
+    pub table_name: StackString,
+    pub row_count: i64,
+}
+
+impl TableRowCount {
+    /// Estimated row count of every table in the public schema from the
+    /// planner statistics, tables which were never analyzed fall back to
+    /// the live tuple count of the stats collector
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT c.relname::text AS table_name,
+                       CASE WHEN c.reltuples >= 0 THEN c.reltuples::bigint
+                            ELSE coalesce(s.n_live_tup, 0)
+                       END AS row_count
+                FROM pg_class c
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                LEFT JOIN pg_stat_user_tables s ON s.relid = c.oid
+                WHERE n.nspname = 'public' AND c.relkind IN ('r', 'p')
+                ORDER BY c.relname
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Number and size of files tracked per bucket in `key_item_cache`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BucketSize {
+    pub s3_bucket: StackString,
+    pub number_of_files: i64,
+    pub total_size: i64,
+}
+
+impl BucketSize {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT s3_bucket,
+                       count(*) AS number_of_files,
+                       coalesce(sum(s3_size), 0)::bigint AS total_size
+                FROM key_item_cache
+                GROUP BY s3_bucket
+                ORDER BY s3_bucket
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DirectorySize {
+    pub name: StackString,
+    pub number_of_files: u64,
+    pub total_size: u64,
+}
+
+impl DirectorySize {
+    /// Files directly in `path`, a missing directory counts as empty
+    /// # Errors
+    /// Return error if `path` can't be read
+    pub fn from_path(name: &str, path: &Path) -> Result<Self, Error> {
+        let mut number_of_files = 0;
+        let mut total_size = 0;
+        if path.exists() {
+            for entry in read_dir(path)? {
+                let metadata = entry?.metadata()?;
+                if metadata.is_file() {
+                    number_of_files += 1;
+                    total_size += metadata.len();
+                }
+            }
+        }
+        Ok(Self {
+            name: name.into(),
+            number_of_files,
+            total_size,
+        })
+    }
+}
+
+/// Consecutive days without a `heartrate_statistics_summary` entry
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartrateGap {
+    pub gap_start: Date,
+    pub gap_end: Date,
+}
+
+impl HeartrateGap {
+    #[must_use]
+    pub fn days(&self) -> i64 {
+        (self.gap_end - self.gap_start).whole_days() + 1
+    }
+
+    /// Gaps between entries and the trailing gap from the last entry up to
+    /// yesterday (local time), newest first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let today = OffsetDateTime::now_utc()
+            .to_timezone(DateTimeWrapper::local_tz())
+            .date();
+        let query = query!(
+            "
+                SELECT gap_start, gap_end FROM (
+                    SELECT date + 1 AS gap_start,
+                           coalesce(
+                               lead(date) OVER (ORDER BY date) - 1,
+                               $today::date - 1
+                           ) AS gap_end
+                    FROM heartrate_statistics_summary
+                ) gaps
+                WHERE gap_end >= gap_start
+                ORDER BY gap_start DESC
+            ",
+            today = today,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Activities in `garmin_summary` without a linked strava or garmin connect
+/// activity
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingLinks {
+    pub total_activities: i64,
+    pub missing_strava: i64,
+    pub missing_connect: i64,
+}
+
+impl MissingLinks {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Self, Error> {
+        let query = query!(
+            "
+                SELECT count(*) AS total_activities,
+                       count(*) FILTER (
+                           WHERE NOT EXISTS (
+                               SELECT 1 FROM strava_activities a WHERE a.summary_id = s.id
+                           )
+                       ) AS missing_strava,
+                       count(*) FILTER (
+                           WHERE NOT EXISTS (
+                               SELECT 1 FROM garmin_connect_activities a
+                               WHERE a.summary_id = s.id
+                           )
+                       ) AS missing_connect
+                FROM garmin_summary s
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch_one(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Storage and data coverage summary for the admin stats page
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AdminStats {
+    pub table_counts: Vec<TableRowCount>,
+    pub directory_sizes: Vec<DirectorySize>,
+    pub bucket_sizes: Vec<BucketSize>,
+    pub heartrate_gaps: Vec<HeartrateGap>,
+    pub missing_links: MissingLinks,
+    pub sync_lock: Option<LockHolder>,
+}
+
+impl AdminStats {
+    /// # Errors
+    /// Return error if db queries fail or reading a directory fails
+    pub async fn get(config: &GarminConfig, pool: &PgPool) -> Result<Self, Error> {
+        let directory_sizes = {
+            let config = config.clone();
+            spawn_blocking(move || {
+                [
+                    ("gps_dir", &config.gps_dir),
+                    ("cache_dir", &config.cache_dir),
+                    ("fitbit_cachedir", &config.fitbit_cachedir),
+                    ("fitbit_archivedir", &config.fitbit_archivedir),
+                    ("quarantine_dir", &config.quarantine_dir),
+                    ("upload_dir", &config.upload_dir),
+                ]
+                .into_iter()
+                .map(|(name, path)| DirectorySize::from_path(name, path))
+                .collect::<Result<Vec<_>, Error>>()
+            })
+        };
+        let table_counts = TableRowCount::read_from_db(pool).await?;
+        let bucket_sizes = BucketSize::read_from_db(pool).await?;
+        let heartrate_gaps = HeartrateGap::read_from_db(pool).await?;
+        let missing_links = MissingLinks::read_from_db(pool).await?;
+        let sync_lock = LockHolder::get(pool).await?;
+        Ok(Self {
+            table_counts,
+            directory_sizes: directory_sizes.await??,
+            bucket_sizes,
+            heartrate_gaps,
+            missing_links,
+            sync_lock,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use std::fs::{create_dir_all, write};
+    use tempfile::TempDir;
+    use time::macros::date;
+
+    use crate::admin_stats::{DirectorySize, HeartrateGap};
+
+    #[test]
+    fn test_directory_size() -> Result<(), Error> {
+        let dir = TempDir::with_prefix("admin_stats")?;
+        write(dir.path().join("a.avro"), b"0123")?;
+        write(dir.path().join("b.avro"), b"456")?;
+        create_dir_all(dir.path().join("subdir"))?;
+        let size = DirectorySize::from_path("cache_dir", dir.path())?;
+        assert_eq!(size.number_of_files, 2);
+        assert_eq!(size.total_size, 7);
+        let size = DirectorySize::from_path("missing", &dir.path().join("missing"))?;
+        assert_eq!(size.number_of_files, 0);
+
+        let gap = HeartrateGap {
+            gap_start: date!(2024 - 02 - 27),
+            gap_end: date!(2024 - 03 - 01),
+        };
+        assert_eq!(gap.days(), 4);
+        Ok(())
+    }
+}
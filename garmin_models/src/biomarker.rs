@@ -0,0 +1,301 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use time::{macros::format_description, Date, OffsetDateTime};
+use time_tz::PrimitiveDateTimeExt;
+
+use garmin_lib::date_time_wrapper::{iso8601::convert_str_to_datetime, DateTimeWrapper};
+use garmin_utils::pgpool::PgPool;
+
+use crate::provenance::Provenance;
+
+/// A user defined series (e.g. HRV from another device, blood pressure,
+/// cycle phase), stored in `biomarker_series`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BiomarkerSeries {
+    pub name: StackString,
+    pub units: StackString,
+    pub description: Option<StackString>,
+}
+
+impl BiomarkerSeries {
+    #[must_use]
+    pub fn new(name: &str, units: &str) -> Self {
+        Self {
+            name: name.into(),
+            units: units.into(),
+            description: None,
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_name(pool: &PgPool, name: &str) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "SELECT name, units, description FROM biomarker_series WHERE name = $name",
+            name = name,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT name, units, description FROM biomarker_series ORDER BY name");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Create the series, or update units / description if it already exists
+    /// # Errors
+    /// Return error if name is empty or db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        if self.name.is_empty() || self.name.contains(',') {
+            return Err(format_err!("Invalid series name {}", self.name));
+        }
+        let query = query!(
+            "
+                INSERT INTO biomarker_series (name, units, description)
+                VALUES ($name, $units, $description)
+                ON CONFLICT (name) DO UPDATE
+                SET units=EXCLUDED.units,
+                    description=COALESCE(EXCLUDED.description, biomarker_series.description)
+            ",
+            name = self.name,
+            units = self.units,
+            description = self.description,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete_from_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM biomarker_series WHERE name = $name",
+            name = self.name,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Single value of a `BiomarkerSeries`, stored in `biomarker_measurements`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BiomarkerMeasurement {
+    pub series: StackString,
+    pub datetime: DateTimeWrapper,
+    pub value: f64,
+}
+
+impl BiomarkerMeasurement {
+    /// Parse `datetime,value` lines, where datetime is either rfc3339 or a
+    /// plain date (taken as local midnight).  Blank lines, `#` comments and a
+    /// leading header line are skipped.
+    /// # Errors
+    /// Return error if a line can't be parsed
+    pub fn from_csv(series: &str, text: &str) -> Result<Vec<Self>, Error> {
+        let mut measurements = Vec::new();
+        let mut first_line = true;
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let is_header = first_line;
+            first_line = false;
+            let mut entries = line.split(',').map(str::trim);
+            let datetime = entries.next().unwrap_or("");
+            let value = entries.next().unwrap_or("");
+            let value: f64 = match value.parse() {
+                Ok(v) => v,
+                Err(_) if is_header => continue,
+                Err(e) => return Err(format_err!("Invalid value on line {}: {e}", idx + 1)),
+            };
+            let datetime = parse_datetime(datetime)
+                .map_err(|e| format_err!("Invalid datetime on line {}: {e}", idx + 1))?;
+            measurements.push(Self {
+                series: series.into(),
+                datetime: datetime.into(),
+                value,
+            });
+        }
+        measurements.shrink_to_fit();
+        Ok(measurements)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(
+        pool: &PgPool,
+        series: &str,
+        start_date: Option<Date>,
+        end_date: Option<Date>,
+    ) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT series, datetime, value
+                FROM biomarker_measurements
+                WHERE series = $series
+                  AND ($start_date::date IS NULL OR datetime >= $start_date)
+                  AND ($end_date::date IS NULL OR datetime < $end_date::date + 1)
+                ORDER BY datetime
+            ",
+            series = series,
+            start_date = start_date,
+            end_date = end_date,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Mean value per local calendar day
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn daily_averages(
+        pool: &PgPool,
+        series: &str,
+        start_date: Option<Date>,
+        end_date: Option<Date>,
+    ) -> Result<Vec<(Date, f64)>, Error> {
+        #[derive(FromSqlRow)]
+        struct DailyAverage {
+            date: Date,
+            value: f64,
+        }
+
+        let query = query!(
+            "
+                SELECT CAST(datetime at time zone 'localtime' as date) as date,
+                       avg(value) as value
+                FROM biomarker_measurements
+                WHERE series = $series
+                  AND ($start_date::date IS NULL OR datetime >= $start_date)
+                  AND ($end_date::date IS NULL OR datetime < $end_date::date + 1)
+                GROUP BY 1
+                ORDER BY 1
+            ",
+            series = series,
+            start_date = start_date,
+            end_date = end_date,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<DailyAverage> = query.fetch(&conn).await?;
+        let mut averages: Vec<_> = rows.into_iter().map(|r| (r.date, r.value)).collect();
+        averages.shrink_to_fit();
+        Ok(averages)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool, provenance: &Provenance) -> Result<(), Error> {
+        let provider = provenance.provider.to_str();
+        let conn = pool.get().await?;
+        conn.execute(
+            UPSERT_MEASUREMENT,
+            &[
+                &self.series,
+                &self.datetime,
+                &self.value,
+                &provider,
+                &provenance.import_path,
+                &provenance.raw_file,
+                &provenance.imported_at,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Upsert `measurements` one after the other in a single transaction, so
+    /// a failed import leaves nothing behind
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn merge_updates(
+        measurements: &[Self],
+        pool: &PgPool,
+        provenance: &Provenance,
+    ) -> Result<usize, Error> {
+        let provider = provenance.provider.to_str();
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        for m in measurements {
+            tran.execute(
+                UPSERT_MEASUREMENT,
+                &[
+                    &m.series,
+                    &m.datetime,
+                    &m.value,
+                    &provider,
+                    &provenance.import_path,
+                    &provenance.raw_file,
+                    &provenance.imported_at,
+                ],
+            )
+            .await?;
+        }
+        tran.commit().await?;
+        Ok(measurements.len())
+    }
+}
+
+const UPSERT_MEASUREMENT: &str = "
+    INSERT INTO biomarker_measurements (
+        series, datetime, value, provider, import_path, raw_file, imported_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+    ON CONFLICT (series, datetime) DO UPDATE
+        SET value=EXCLUDED.value,
+            provider=EXCLUDED.provider,
+            import_path=EXCLUDED.import_path,
+            raw_file=EXCLUDED.raw_file,
+            imported_at=EXCLUDED.imported_at
+";
+
+/// Parse rfc3339 timestamps or plain dates (taken as local midnight)
+/// # Errors
+/// Return error if `s` is neither
+pub fn parse_datetime(s: &str) -> Result<OffsetDateTime, Error> {
+    if let Ok(dt) = convert_str_to_datetime(s) {
+        return Ok(dt);
+    }
+    let date = Date::parse(s, format_description!("[year]-[month]-[day]"))?;
+    date.midnight()
+        .assume_timezone(DateTimeWrapper::local_tz())
+        .take_first()
+        .ok_or_else(|| format_err!("Invalid local date {s}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::datetime;
+
+    use crate::biomarker::BiomarkerMeasurement;
+
+    #[test]
+    fn test_from_csv() -> Result<(), Error> {
+        let text = "
+            datetime,value
+            2024-03-01T07:00:00Z, 52.5
+            # missed a day
+            2024-03-03T06:30:00-05:00,48
+        ";
+        let measurements = BiomarkerMeasurement::from_csv("hrv", text)?;
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(&measurements[0].series, "hrv");
+        assert_eq!(measurements[0].value, 52.5);
+        assert_eq!(
+            measurements[1].datetime.to_offsetdatetime(),
+            datetime!(2024-03-03 11:30:00 +00:00)
+        );
+        assert!(BiomarkerMeasurement::from_csv("hrv", "2024-03-01,52\nbad,1").is_err());
+        assert!(BiomarkerMeasurement::from_csv("hrv", "2024-03-01,52\n2024-03-02,x").is_err());
+        Ok(())
+    }
+}
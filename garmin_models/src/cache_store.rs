@@ -0,0 +1,272 @@
+use anyhow::Error;
+use log::debug;
+use stack_string::StackString;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tokio::task::spawn_blocking;
+
+use garmin_lib::{cache_storage::CacheStorage, garmin_config::GarminConfig};
+
+use crate::garmin_sync::GarminSync;
+
+/// Files fetched or stored this recently are never evicted, another task
+/// may be about to read them
+const EVICT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+/// A cache directory, with `CacheStorage::S3` the bucket holds every file
+/// and the directory is a least recently used cache of at most
+/// `max_local_files`
+#[derive(Clone)]
+pub struct CacheStore {
+    local_dir: PathBuf,
+    s3_bucket: StackString,
+    max_local_files: usize,
+    gsync: Option<GarminSync>,
+    /// Number of local files, `None` until the directory is first listed
+    local_files: Arc<Mutex<Option<usize>>>,
+}
+
+impl CacheStore {
+    pub async fn new(config: &GarminConfig, local_dir: &Path, s3_bucket: &str) -> Self {
+        let gsync = match config.cache_storage {
+            CacheStorage::Local => None,
+            CacheStorage::S3 => GarminSync::from_config(config).await,
+        };
+        Self {
+            local_dir: local_dir.to_path_buf(),
+            s3_bucket: s3_bucket.into(),
+            max_local_files: config.cache_local_max_files,
+            gsync,
+            local_files: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Activity avro files, `cache_dir` / `cache_bucket`
+    pub async fn avro_cache(config: &GarminConfig) -> Self {
+        Self::new(config, &config.cache_dir, &config.cache_bucket).await
+    }
+
+    /// Monthly heart rate parquet files, `fitbit_archivedir` /
+    /// `fitbit_archive_bucket`
+    pub async fn fitbit_archive(config: &GarminConfig) -> Self {
+        Self::new(
+            config,
+            &config.fitbit_archivedir,
+            &config.fitbit_archive_bucket,
+        )
+        .await
+    }
+
+    #[must_use]
+    pub fn is_remote(&self) -> bool {
+        self.gsync.is_some()
+    }
+
+    #[must_use]
+    pub fn local_path(&self, key: &str) -> PathBuf {
+        self.local_dir.join(key)
+    }
+
+    /// Local path of `key`, downloading it if it's only in s3, `None` if it
+    /// doesn't exist anywhere
+    /// # Errors
+    /// Return error if s3 api calls or file operations fail
+    pub async fn fetch(&self, key: &str) -> Result<Option<PathBuf>, Error> {
+        let path = self.local_path(key);
+        let gsync = match &self.gsync {
+            Some(gsync) => gsync,
+            None => return Ok(path.exists().then_some(path)),
+        };
+        if path.exists() {
+            let touched = path.clone();
+            match spawn_blocking(move || touch(&touched)).await? {
+                Ok(()) => return Ok(Some(path)),
+                // evicted since, download it again
+                Err(e) if is_not_found(&e) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !gsync.file_exists(&self.s3_bucket, key).await? {
+            return Ok(None);
+        }
+        if !self.local_dir.exists() {
+            tokio::fs::create_dir_all(&self.local_dir).await?;
+        }
+        gsync.download_file(&path, &self.s3_bucket, key).await?;
+        // the download carries the s3 modification time
+        let touched = path.clone();
+        spawn_blocking(move || touch(&touched)).await??;
+        self.evict(Some(key)).await?;
+        Ok(Some(path))
+    }
+
+    /// Upload `key` once it's been written to the local directory, a no-op
+    /// for local storage
+    /// # Errors
+    /// Return error if the upload fails
+    pub async fn store(&self, key: &str) -> Result<(), Error> {
+        if let Some(gsync) = &self.gsync {
+            gsync
+                .upload_file(&self.local_path(key), &self.s3_bucket, key)
+                .await?;
+            self.evict(Some(key)).await?;
+        }
+        Ok(())
+    }
+
+    /// Count a file added to the local directory, once there are more than
+    /// `max_local_files` remove the least recently used down to 90% of the
+    /// limit, so the directory is only listed again after that many more
+    /// files are added rather than on every fetch.  `in_use` (the key just
+    /// fetched or stored) and files used within `EVICT_GRACE_PERIOD` are
+    /// never removed, nothing is removed for local storage
+    /// # Errors
+    /// Return error if listing or removing files fails
+    pub async fn evict(&self, in_use: Option<&str>) -> Result<usize, Error> {
+        if self.gsync.is_none() {
+            return Ok(0);
+        }
+        let local_files = {
+            let mut local_files = self
+                .local_files
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            *local_files = local_files.map(|n| n + 1);
+            *local_files
+        };
+        if local_files.is_some_and(|n| n <= self.max_local_files) {
+            return Ok(0);
+        }
+        let local_dir = self.local_dir.clone();
+        let keep = self.max_local_files - self.max_local_files / 10;
+        let in_use = in_use.map(|key| self.local_path(key));
+        let recent = SystemTime::now() - EVICT_GRACE_PERIOD;
+        let (listed, evicted) = spawn_blocking(move || {
+            least_recently_used(&local_dir, keep, in_use.as_deref(), recent)
+        })
+        .await??;
+        let mut removed = 0;
+        for path in &evicted {
+            debug!("evict {path:?}");
+            match tokio::fs::remove_file(path).await {
+                Ok(()) => removed += 1,
+                // already evicted by another task
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        *self
+            .local_files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(listed - removed);
+        Ok(removed)
+    }
+}
+
+fn is_not_found(error: &Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+fn touch(path: &Path) -> Result<(), Error> {
+    fs::File::options()
+        .append(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+        .map_err(Into::into)
+}
+
+/// Number of files in `dir` and the least recently modified of them beyond
+/// `keep`.  In progress downloads (`.tmp_*`) aren't counted, `in_use` and
+/// files modified after `recent` are counted but left alone
+fn least_recently_used(
+    dir: &Path,
+    keep: usize,
+    in_use: Option<&Path>,
+    recent: SystemTime,
+) -> Result<(usize, Vec<PathBuf>), Error> {
+    if !dir.exists() {
+        return Ok((0, Vec::new()));
+    }
+    let mut listed = 0;
+    let mut files = Vec::new();
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            // removed by another task since the directory was listed
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if !metadata.is_file() || entry.file_name().to_string_lossy().starts_with(".tmp_") {
+            continue;
+        }
+        listed += 1;
+        let modified = metadata.modified()?;
+        if in_use == Some(entry.path().as_path()) || modified > recent {
+            continue;
+        }
+        files.push((modified, entry.path()));
+    }
+    files.sort();
+    files.truncate(listed.saturating_sub(keep));
+    Ok((listed, files.into_iter().map(|(_, p)| p).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+    use tempfile::TempDir;
+
+    use crate::cache_store::least_recently_used;
+
+    #[test]
+    fn test_least_recently_used() -> Result<(), Error> {
+        let dir = TempDir::new()?;
+        let now = SystemTime::now();
+        for (name, age) in [
+            ("a.avro", 30),
+            ("b.avro", 10),
+            ("c.avro", 20),
+            (".tmp_x", 40),
+        ] {
+            let path = dir.path().join(name);
+            fs::write(&path, name)?;
+            fs::File::options()
+                .append(true)
+                .open(&path)?
+                .set_modified(now - Duration::from_secs(age))?;
+        }
+        let (listed, evicted) = least_recently_used(dir.path(), 3, None, now)?;
+        assert_eq!(listed, 3);
+        assert!(evicted.is_empty());
+        let (_, evicted) = least_recently_used(dir.path(), 1, None, now)?;
+        assert_eq!(
+            evicted,
+            vec![dir.path().join("a.avro"), dir.path().join("c.avro")]
+        );
+        let in_use = dir.path().join("a.avro");
+        let (_, evicted) = least_recently_used(dir.path(), 0, Some(&in_use), now)?;
+        assert_eq!(
+            evicted,
+            vec![dir.path().join("c.avro"), dir.path().join("b.avro")]
+        );
+        // b.avro was used within the grace period
+        let recent = now - Duration::from_secs(15);
+        let (_, evicted) = least_recently_used(dir.path(), 0, None, recent)?;
+        assert_eq!(
+            evicted,
+            vec![dir.path().join("a.avro"), dir.path().join("c.avro")]
+        );
+        Ok(())
+    }
+}
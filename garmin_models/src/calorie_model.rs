@@ -0,0 +1,109 @@
+use garmin_lib::heart_rate_profile::HeartRateProfile;
+use garmin_utils::sport_types::SportTypes;
+
+/// Oxygen uptake at rest (one MET) in ml/kg/min
+pub const RESTING_VO2: f64 = 3.5;
+/// VO2max in ml/kg/min assumed when converting heart rate reserve to oxygen
+/// uptake, a moderately fit adult
+pub const ASSUMED_VO2MAX: f64 = 45.0;
+/// Energy released per liter of oxygen consumed
+pub const KCAL_PER_LITER_O2: f64 = 5.0;
+
+/// Typical MET of `sport` (Compendium of Physical Activities)
+#[must_use]
+pub fn sport_met(sport: SportTypes) -> f64 {
+    match sport {
+        SportTypes::Running => 9.8,
+        SportTypes::Biking | SportTypes::Ultimate | SportTypes::Snowshoeing => 8.0,
+        SportTypes::Stairs => 9.0,
+        SportTypes::Swimming | SportTypes::Skiing => 7.0,
+        SportTypes::Hiking => 6.0,
+        SportTypes::Elliptical | SportTypes::Other | SportTypes::None | SportTypes::Custom(_) => {
+            5.0
+        }
+        SportTypes::Walking | SportTypes::Lifting => 3.5,
+    }
+}
+
+/// Oxygen uptake in ml/kg/min, from the ACSM equations for running and
+/// walking when the average `speed` (m/s) is known, else from `sport_met`
+#[must_use]
+pub fn sport_vo2(sport: SportTypes, speed: f64) -> f64 {
+    let meters_per_minute = speed * 60.0;
+    match sport {
+        SportTypes::Running if speed > 0.0 => RESTING_VO2 + 0.2 * meters_per_minute,
+        SportTypes::Walking | SportTypes::Hiking if speed > 0.0 => {
+            RESTING_VO2 + 0.1 * meters_per_minute
+        }
+        _ => sport_met(sport) * RESTING_VO2,
+    }
+}
+
+/// Estimated kcal burned over `duration` seconds by someone weighing
+/// `weight_kg`. With an average heart rate the fraction of oxygen uptake
+/// reserve is taken to match the fraction of heart rate reserve (Swain and
+/// Leutholtz), otherwise the sport and speed determine it.
+#[must_use]
+pub fn estimate_calories(
+    sport: SportTypes,
+    duration: f64,
+    distance: f64,
+    avg_heart_rate: Option<f64>,
+    weight_kg: f64,
+    profile: &HeartRateProfile,
+) -> Option<i32> {
+    if duration <= 0.0 || weight_kg <= 0.0 {
+        return None;
+    }
+    let vo2 = match avg_heart_rate {
+        Some(heart_rate) if heart_rate > 0.0 => {
+            RESTING_VO2 + profile.reserve_fraction(heart_rate) * (ASSUMED_VO2MAX - RESTING_VO2)
+        }
+        _ => sport_vo2(sport, distance / duration),
+    };
+    let liters_o2 = vo2 * weight_kg * duration / 60.0 / 1000.0;
+    Some((liters_o2 * KCAL_PER_LITER_O2).round() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use garmin_lib::heart_rate_profile::HeartRateProfile;
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::calorie_model::{estimate_calories, sport_vo2, RESTING_VO2};
+
+    #[test]
+    fn test_estimate_calories() {
+        let profile = HeartRateProfile::default();
+        // an hour at 10 km/h, about 1 kcal per kg per km
+        let running =
+            estimate_calories(SportTypes::Running, 3600.0, 10_000.0, None, 70.0, &profile);
+        assert_eq!(running, Some(774));
+
+        // heart rate takes precedence over speed
+        let hr = profile.heart_rate_at(0.7);
+        let running_hr = estimate_calories(
+            SportTypes::Running,
+            3600.0,
+            10_000.0,
+            Some(hr),
+            70.0,
+            &profile,
+        );
+        assert_eq!(running_hr, Some(684));
+
+        // no distance falls back to the MET of the sport
+        let biking = estimate_calories(SportTypes::Biking, 3600.0, 0.0, None, 70.0, &profile);
+        assert_eq!(biking, Some(588));
+        assert_eq!(sport_vo2(SportTypes::Lifting, 0.0), RESTING_VO2);
+
+        assert_eq!(
+            estimate_calories(SportTypes::Running, 0.0, 0.0, None, 70.0, &profile),
+            None
+        );
+        assert_eq!(
+            estimate_calories(SportTypes::Running, 3600.0, 0.0, None, 0.0, &profile),
+            None
+        );
+    }
+}
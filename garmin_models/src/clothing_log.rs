@@ -0,0 +1,296 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{collections::HashMap, fmt, str::FromStr};
+use uuid::Uuid;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
+
+/// Activities logged within this many degrees C of the forecast are used for
+/// a recommendation
+pub const CLOTHING_TEMPERATURE_RANGE: f64 = 3.0;
+
+/// How comfortable the clothing worn for an activity was
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ComfortRating {
+    TooCold,
+    JustRight,
+    TooHot,
+}
+
+impl Default for ComfortRating {
+    fn default() -> Self {
+        Self::JustRight
+    }
+}
+
+impl ComfortRating {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::TooCold => "too_cold",
+            Self::JustRight => "just_right",
+            Self::TooHot => "too_hot",
+        }
+    }
+
+    #[must_use]
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::TooCold => "Too Cold",
+            Self::JustRight => "Just Right",
+            Self::TooHot => "Too Hot",
+        }
+    }
+}
+
+impl fmt::Display for ComfortRating {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for ComfortRating {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(' ', "_").as_str() {
+            "too_cold" | "cold" => Ok(Self::TooCold),
+            "just_right" | "ok" => Ok(Self::JustRight),
+            "too_hot" | "hot" => Ok(Self::TooHot),
+            _ => Err(format_err!("{s} is not a valid comfort rating")),
+        }
+    }
+}
+
+#[derive(FromSqlRow)]
+struct ClothingLogRow {
+    summary_id: Uuid,
+    filename: StackString,
+    begin_datetime: DateTimeWrapper,
+    sport: SportTypes,
+    clothing: StackString,
+    comfort: StackString,
+    temperature: Option<f64>,
+    device_temperature: bool,
+}
+
+/// What was worn for an activity and how it felt, stored in
+/// `activity_clothing`, `temperature` is in degrees C and comes from the
+/// reported weather unless `device_temperature` is set, in which case it is
+/// the watch sensor reading (which runs warm from body heat)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClothingLogEntry {
+    pub summary_id: Uuid,
+    pub filename: StackString,
+    pub begin_datetime: DateTimeWrapper,
+    pub sport: SportTypes,
+    pub clothing: StackString,
+    pub comfort: ComfortRating,
+    pub temperature: Option<f64>,
+    pub device_temperature: bool,
+}
+
+impl TryFrom<ClothingLogRow> for ClothingLogEntry {
+    type Error = Error;
+    fn try_from(row: ClothingLogRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            summary_id: row.summary_id,
+            filename: row.filename,
+            begin_datetime: row.begin_datetime,
+            sport: row.sport,
+            clothing: row.clothing,
+            comfort: row.comfort.parse()?,
+            temperature: row.temperature,
+            device_temperature: row.device_temperature,
+        })
+    }
+}
+
+impl ClothingLogEntry {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_clothing (
+                    summary_id, clothing, comfort, temperature, device_temperature
+                )
+                VALUES (
+                    $summary_id, $clothing, $comfort, $temperature, $device_temperature
+                )
+                ON CONFLICT (summary_id) DO UPDATE
+                    SET clothing=EXCLUDED.clothing,
+                        comfort=EXCLUDED.comfort,
+                        temperature=EXCLUDED.temperature,
+                        device_temperature=EXCLUDED.device_temperature
+            ",
+            summary_id = self.summary_id,
+            clothing = self.clothing,
+            comfort = self.comfort.to_str(),
+            temperature = self.temperature,
+            device_temperature = self.device_temperature,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Every logged activity, most recent first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_entries(pool: &PgPool, sport: Option<SportTypes>) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT c.summary_id,
+                       a.filename,
+                       a.begin_datetime,
+                       a.sport,
+                       c.clothing,
+                       c.comfort,
+                       c.temperature,
+                       c.device_temperature
+                FROM activity_clothing c
+                JOIN garmin_summary a ON a.id = c.summary_id
+                WHERE ($sport::text IS NULL OR a.sport = $sport)
+                ORDER BY a.begin_datetime DESC
+            ",
+            sport = sport.map(SportTypes::to_str),
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<ClothingLogRow> = query.fetch(&conn).await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+}
+
+/// How one outfit felt on the logged activities near a temperature
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct OutfitComfort {
+    pub clothing: StackString,
+    pub too_cold: usize,
+    pub just_right: usize,
+    pub too_hot: usize,
+}
+
+impl OutfitComfort {
+    /// Times it was just right less the times it wasn't
+    #[must_use]
+    pub fn score(&self) -> i64 {
+        self.just_right as i64 - self.too_cold as i64 - self.too_hot as i64
+    }
+}
+
+/// Outfits worn within `CLOTHING_TEMPERATURE_RANGE` of a forecast
+/// temperature, best first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ClothingRecommendation {
+    pub temperature: f64,
+    pub outfits: Vec<OutfitComfort>,
+}
+
+impl ClothingRecommendation {
+    /// Outfits are compared ignoring case and surrounding whitespace, the
+    /// most recent spelling is shown
+    #[must_use]
+    pub fn from_entries(entries: &[ClothingLogEntry], temperature: f64) -> Self {
+        let mut outfits: HashMap<StackString, OutfitComfort> = HashMap::new();
+        let mut nearby: Vec<_> = entries
+            .iter()
+            .filter(|e| {
+                e.temperature
+                    .is_some_and(|t| (t - temperature).abs() <= CLOTHING_TEMPERATURE_RANGE)
+            })
+            .collect();
+        nearby.sort_by_key(|e| e.begin_datetime);
+        for entry in nearby.into_iter().rev() {
+            let key: StackString = entry.clothing.trim().to_lowercase().into();
+            let outfit = outfits.entry(key).or_insert_with(|| OutfitComfort {
+                clothing: entry.clothing.trim().into(),
+                ..OutfitComfort::default()
+            });
+            match entry.comfort {
+                ComfortRating::TooCold => outfit.too_cold += 1,
+                ComfortRating::JustRight => outfit.just_right += 1,
+                ComfortRating::TooHot => outfit.too_hot += 1,
+            }
+        }
+        let mut outfits: Vec<_> = outfits.into_values().collect();
+        outfits.sort_by(|a, b| {
+            b.score()
+                .cmp(&a.score())
+                .then_with(|| b.just_right.cmp(&a.just_right))
+                .then_with(|| a.clothing.cmp(&b.clothing))
+        });
+        Self {
+            temperature,
+            outfits,
+        }
+    }
+
+    /// Outfit which was just right more often than not, if any
+    #[must_use]
+    pub fn best(&self) -> Option<&OutfitComfort> {
+        self.outfits.first().filter(|o| o.score() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+    use uuid::Uuid;
+
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::clothing_log::{ClothingLogEntry, ClothingRecommendation, ComfortRating};
+
+    #[test]
+    fn test_clothing_recommendation() {
+        let start = datetime!(2024-01-01 12:00:00 UTC);
+        let entry = |days, clothing: &str, comfort, temperature| ClothingLogEntry {
+            summary_id: Uuid::new_v4(),
+            filename: "".into(),
+            begin_datetime: (start + Duration::days(days)).into(),
+            sport: SportTypes::Running,
+            clothing: clothing.into(),
+            comfort,
+            temperature,
+            device_temperature: false,
+        };
+        let entries = [
+            entry(0, "shorts, t-shirt", ComfortRating::TooCold, Some(4.0)),
+            entry(
+                1,
+                "tights, long sleeve",
+                ComfortRating::JustRight,
+                Some(5.0),
+            ),
+            entry(
+                2,
+                "Tights, Long Sleeve ",
+                ComfortRating::JustRight,
+                Some(3.0),
+            ),
+            entry(3, "tights, jacket", ComfortRating::TooHot, Some(6.0)),
+            // too far from the forecast
+            entry(4, "shorts, t-shirt", ComfortRating::JustRight, Some(20.0)),
+            entry(5, "shorts, t-shirt", ComfortRating::JustRight, None),
+        ];
+        let recommendation = ClothingRecommendation::from_entries(&entries, 4.5);
+        assert_eq!(recommendation.outfits.len(), 3);
+        let best = recommendation.best().unwrap();
+        assert_eq!(best.clothing.as_str(), "Tights, Long Sleeve");
+        assert_eq!(best.just_right, 2);
+        assert_eq!(recommendation.outfits[2].score(), -1);
+
+        let recommendation = ClothingRecommendation::from_entries(&entries, -10.0);
+        assert!(recommendation.best().is_none());
+
+        assert_eq!(
+            "Too Hot".parse::<ComfortRating>().unwrap(),
+            ComfortRating::TooHot
+        );
+        assert!("warm".parse::<ComfortRating>().is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::Error;
+use postgres_query::query;
+use stack_string::StackString;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use garmin_utils::{garmin_util::haversine_distance, pgpool::PgPool, sport_types::SportTypes};
+
+use crate::route_match::MatchedRoute;
+
+/// Longest activity in meters still considered a commute
+pub const COMMUTE_MAX_DISTANCE: f64 = 25_000.0;
+/// Least distance in meters between start and finish of a one way trip,
+/// loops starting and ending at home aren't commutes
+pub const COMMUTE_MIN_SEPARATION: f64 = 1_000.0;
+/// Least number of attempts of a route, counting the way back, before it's
+/// considered a commute
+pub const COMMUTE_MIN_ATTEMPTS: i64 = 3;
+
+/// Condition on `garmin_summary a` leaving out activities flagged as commutes
+pub const NOT_COMMUTE_SQL: &str = "NOT EXISTS (
+    SELECT 1 FROM activity_commutes x WHERE x.summary_id = a.id AND x.is_commute
+)";
+
+/// Whether start and finish of `trace` are far enough apart to be a trip
+/// from A to B
+#[must_use]
+pub fn is_one_way(trace: &[(f64, f64)]) -> bool {
+    match (trace.first(), trace.last()) {
+        (Some((lat0, lon0)), Some((lat1, lon1))) => {
+            haversine_distance(*lat0, *lon0, *lat1, *lon1) >= COMMUTE_MIN_SEPARATION
+        }
+        _ => false,
+    }
+}
+
+/// Ids of the routes which look like commutes: short one way trips of a
+/// sport people commute by, done at least `COMMUTE_MIN_ATTEMPTS` times
+/// counting the attempts of the route in the opposite direction
+#[must_use]
+pub fn commute_routes(routes: &[(MatchedRoute, i64)]) -> HashSet<Uuid> {
+    routes
+        .iter()
+        .filter(|(route, _)| {
+            matches!(
+                route.sport,
+                SportTypes::Biking | SportTypes::Running | SportTypes::Walking
+            ) && route.distance <= COMMUTE_MAX_DISTANCE
+                && is_one_way(&route.trace())
+        })
+        .filter_map(|(route, attempts)| {
+            let mut reversed = route.trace();
+            reversed.reverse();
+            let return_attempts: i64 = routes
+                .iter()
+                .filter(|(r, _)| {
+                    r.id != route.id && r.matches(route.sport, route.distance, &reversed)
+                })
+                .map(|(_, a)| *a)
+                .sum();
+            if attempts + return_attempts >= COMMUTE_MIN_ATTEMPTS {
+                Some(route.id)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Commute flag of an activity, stored in `activity_commutes`, `source` is
+/// `strava` (tagged commute there), `route` (detected from repeated trips)
+/// or `manual`, manual flags are never replaced by detection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivityCommute {
+    pub summary_id: Uuid,
+    pub is_commute: bool,
+    pub source: StackString,
+}
+
+impl ActivityCommute {
+    /// Flag activities tagged commute on Strava or following a commute
+    /// route, clearing earlier detections which no longer apply, returns
+    /// the number of activities flagged. An activity synced from several
+    /// Strava accounts is grouped to a single row, the upsert can't touch
+    /// the same row twice
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn detect(pool: &PgPool) -> Result<u64, Error> {
+        let routes = MatchedRoute::get_with_attempts(pool, 1).await?;
+        let route_ids: Vec<Uuid> = commute_routes(&routes).into_iter().collect();
+        let query = query!(
+            "
+                WITH detected AS (
+                    SELECT a.id AS summary_id,
+                           CASE WHEN bool_or(s.commute) THEN 'strava' ELSE 'route' END AS source
+                    FROM garmin_summary a
+                    LEFT JOIN strava_activities s ON s.summary_id = a.id
+                    LEFT JOIN activity_routes r ON r.summary_id = a.id
+                    WHERE s.commute OR r.route_id = ANY($route_ids)
+                    GROUP BY a.id
+                ), removed AS (
+                    DELETE FROM activity_commutes
+                    WHERE source <> 'manual'
+                      AND summary_id NOT IN (SELECT summary_id FROM detected)
+                )
+                INSERT INTO activity_commutes (summary_id, is_commute, source)
+                SELECT summary_id, true, source FROM detected
+                ON CONFLICT (summary_id) DO UPDATE SET source = EXCLUDED.source
+                WHERE activity_commutes.source <> 'manual'
+            ",
+            route_ids = route_ids,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+
+    /// Flag (or unflag) an activity by hand, overriding detection
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn set_manual(
+        pool: &PgPool,
+        summary_id: Uuid,
+        is_commute: bool,
+    ) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_commutes (summary_id, is_commute, source)
+                VALUES ($summary_id, $is_commute, 'manual')
+                ON CONFLICT (summary_id) DO UPDATE
+                SET is_commute = EXCLUDED.is_commute, source = EXCLUDED.source
+            ",
+            summary_id = summary_id,
+            is_commute = is_commute,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::{
+        commute::{commute_routes, is_one_way},
+        route_match::MatchedRoute,
+    };
+
+    fn trace(lat0: f64, lat1: f64) -> Vec<(f64, f64)> {
+        (0..10)
+            .map(|i| (lat0 + (lat1 - lat0) * f64::from(i) / 9.0, -74.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_commute_routes() {
+        // 0.045 degrees of latitude is about 5 km
+        assert!(is_one_way(&trace(41.0, 41.045)));
+        assert!(!is_one_way(&trace(41.0, 41.0)));
+
+        let to_work =
+            MatchedRoute::new("to work", SportTypes::Biking, 5000.0, &trace(41.0, 41.045));
+        let from_work = MatchedRoute::new(
+            "from work",
+            SportTypes::Biking,
+            5000.0,
+            &trace(41.045, 41.0),
+        );
+        let loop_route = MatchedRoute::new("loop", SportTypes::Biking, 5000.0, &trace(41.0, 41.0));
+        let long_ride = MatchedRoute::new(
+            "long ride",
+            SportTypes::Biking,
+            80_000.0,
+            &trace(41.0, 41.5),
+        );
+        let swim = MatchedRoute::new("swim", SportTypes::Swimming, 5000.0, &trace(41.0, 41.045));
+
+        // two trips each way make four attempts
+        let routes = vec![
+            (to_work.clone(), 2),
+            (from_work.clone(), 2),
+            (loop_route, 10),
+            (long_ride, 10),
+            (swim, 10),
+        ];
+        let commutes = commute_routes(&routes);
+        assert_eq!(commutes.len(), 2);
+        assert!(commutes.contains(&to_work.id));
+        assert!(commutes.contains(&from_work.id));
+
+        // a single trip there and back isn't a habit yet
+        let routes = vec![(to_work, 1), (from_work, 1)];
+        assert!(commute_routes(&routes).is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::garmin_file::GarminFile;
+
+/// Altitude change in meters which has to be exceeded before a climb is
+/// counted, filters out gps and barometer noise
+pub const CLIMB_THRESHOLD: f64 = 3.0;
+/// Extra effort per meter climbed per km, roughly 3.3% per 1% of average
+/// grade
+pub const CLIMB_FACTOR: f64 = 0.0033;
+/// Altitude in meters above which thinner air starts to slow things down
+pub const ALTITUDE_THRESHOLD: f64 = 1000.0;
+/// Extra effort per meter of mean altitude above `ALTITUDE_THRESHOLD`
+pub const ALTITUDE_FACTOR: f64 = 0.00003;
+
+/// Total climb in meters, changes smaller than `CLIMB_THRESHOLD` are ignored
+#[must_use]
+pub fn total_climb(altitudes: &[f64]) -> f64 {
+    let mut climb = 0.0;
+    let mut reference = match altitudes.first() {
+        Some(a) => *a,
+        None => return climb,
+    };
+    for altitude in altitudes {
+        if altitude - reference > CLIMB_THRESHOLD {
+            climb += altitude - reference;
+            reference = *altitude;
+        } else if reference - altitude > CLIMB_THRESHOLD {
+            reference = *altitude;
+        }
+    }
+    climb
+}
+
+/// Relative effort of a course compared to a flat one at sea level, 1.0 is
+/// flat, 1.1 means an even effort is expected to be about 10% slower.
+/// Combines climb per km with the mean altitude, none of the parsers record
+/// the running surface so it doesn't contribute.
+#[must_use]
+pub fn course_difficulty(gfile: &GarminFile) -> Option<f64> {
+    if gfile.total_distance <= 0.0 {
+        return None;
+    }
+    let altitudes: Vec<_> = gfile
+        .points
+        .iter()
+        .filter_map(|p| p.altitude)
+        .filter(|a| *a > -500.0 && *a < 10000.0)
+        .collect();
+    if altitudes.len() < 2 {
+        return None;
+    }
+    let climb_per_km = total_climb(&altitudes) / (gfile.total_distance / 1000.0);
+    let mean_altitude = altitudes.iter().sum::<f64>() / altitudes.len() as f64;
+    let altitude_effort = (mean_altitude - ALTITUDE_THRESHOLD).max(0.0) * ALTITUDE_FACTOR;
+    Some(1.0 + climb_per_km * CLIMB_FACTOR + altitude_effort)
+}
+
+/// Pace (seconds per unit distance) the same effort would give on a flat
+/// course at sea level
+#[must_use]
+pub fn adjusted_pace(pace: f64, difficulty: f64) -> f64 {
+    if difficulty > 0.0 {
+        pace / difficulty
+    } else {
+        pace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{
+        course_difficulty::{adjusted_pace, course_difficulty, total_climb},
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+    };
+
+    #[test]
+    fn test_course_difficulty() {
+        // noise below the threshold doesn't count as climbing
+        let altitudes = [100.0, 102.0, 99.0, 101.0, 110.0, 105.0, 120.0];
+        assert_abs_diff_eq!(total_climb(&altitudes), 25.0);
+        assert_abs_diff_eq!(total_climb(&[]), 0.0);
+
+        let mut gfile = GarminFile::default();
+        gfile.total_distance = 5000.0;
+        assert!(course_difficulty(&gfile).is_none());
+
+        gfile.points = [100.0, 150.0, 100.0]
+            .iter()
+            .map(|a| GarminPoint {
+                altitude: Some(*a),
+                ..GarminPoint::default()
+            })
+            .collect();
+        // 50m climb over 5km
+        let difficulty = course_difficulty(&gfile).unwrap();
+        assert_abs_diff_eq!(difficulty, 1.033, epsilon = 1e-9);
+        assert_abs_diff_eq!(
+            adjusted_pace(413.1, difficulty),
+            399.903_194,
+            epsilon = 1e-6
+        );
+
+        gfile.points.iter_mut().for_each(|p| {
+            p.altitude = p.altitude.map(|a| a + 2000.0);
+        });
+        let difficulty = course_difficulty(&gfile).unwrap();
+        assert!(difficulty > 1.033);
+    }
+}
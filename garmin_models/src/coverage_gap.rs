@@ -0,0 +1,163 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use time::{Date, Duration};
+
+use garmin_utils::pgpool::PgPool;
+
+use crate::admin_stats::HeartrateGap;
+
+/// Days of consecutive activities before a missing day counts as a gap
+pub const DEFAULT_STREAK_DAYS: usize = 5;
+/// Longer breaks are assumed to be intentional
+pub const DEFAULT_MAX_GAP_DAYS: i64 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GapKind {
+    Heartrate,
+    Activity,
+}
+
+impl GapKind {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Heartrate => "heartrate",
+            Self::Activity => "activity",
+        }
+    }
+}
+
+impl fmt::Display for GapKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for GapKind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "heartrate" => Ok(Self::Heartrate),
+            "activity" => Ok(Self::Activity),
+            _ => Err(format_err!("Invalid gap kind {s}")),
+        }
+    }
+}
+
+/// Range of days (inclusive) missing heart rate data, or without activities
+/// in the middle of a streak
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoverageGap {
+    pub kind: GapKind,
+    pub gap_start: Date,
+    pub gap_end: Date,
+}
+
+impl CoverageGap {
+    #[must_use]
+    pub fn days(&self) -> i64 {
+        (self.gap_end - self.gap_start).whole_days() + 1
+    }
+
+    /// Heart rate and activity gaps ending on or after `start_date`, newest
+    /// first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(
+        pool: &PgPool,
+        start_date: Date,
+        streak_days: usize,
+        max_gap_days: i64,
+    ) -> Result<Vec<Self>, Error> {
+        #[derive(FromSqlRow)]
+        struct ActivityDate {
+            date: Date,
+        }
+
+        let mut gaps: Vec<_> = HeartrateGap::read_from_db(pool)
+            .await?
+            .into_iter()
+            .filter(|g| g.gap_end >= start_date)
+            .map(|g| Self {
+                kind: GapKind::Heartrate,
+                gap_start: g.gap_start,
+                gap_end: g.gap_end,
+            })
+            .collect();
+
+        let query = query!(
+            "
+                SELECT DISTINCT date(begin_datetime at time zone 'utc') AS date
+                FROM garmin_summary
+                WHERE date(begin_datetime at time zone 'utc') >= $start_date
+                ORDER BY date
+            ",
+            start_date = start_date,
+        );
+        let conn = pool.get().await?;
+        let dates: Vec<ActivityDate> = query.fetch(&conn).await?;
+        let dates: Vec<_> = dates.into_iter().map(|d| d.date).collect();
+        gaps.extend(find_activity_gaps(&dates, streak_days, max_gap_days));
+        gaps.sort_by(|a, b| b.gap_start.cmp(&a.gap_start));
+        gaps.shrink_to_fit();
+        Ok(gaps)
+    }
+}
+
+/// Gaps of at most `max_gap_days` in sorted, deduplicated `dates` which
+/// follow a run of at least `streak_days` consecutive days with activities
+#[must_use]
+pub fn find_activity_gaps(
+    dates: &[Date],
+    streak_days: usize,
+    max_gap_days: i64,
+) -> Vec<CoverageGap> {
+    let mut gaps = Vec::new();
+    let mut streak = 1;
+    for pair in dates.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        let gap_days = (next - prev).whole_days() - 1;
+        if gap_days <= 0 {
+            streak += 1;
+            continue;
+        }
+        if streak >= streak_days && gap_days <= max_gap_days {
+            gaps.push(CoverageGap {
+                kind: GapKind::Activity,
+                gap_start: prev + Duration::days(1),
+                gap_end: next - Duration::days(1),
+            });
+        }
+        streak = 1;
+    }
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::date, Duration};
+
+    use crate::coverage_gap::{find_activity_gaps, GapKind};
+
+    #[test]
+    fn test_find_activity_gaps() {
+        let start = date!(2024 - 03 - 01);
+        let mut dates: Vec<_> = (0..5).map(|i| start + Duration::days(i)).collect();
+        // one missing day after a five day streak
+        dates.push(date!(2024 - 03 - 07));
+        // a two week break isn't a gap in the streak
+        dates.push(date!(2024 - 03 - 22));
+        let gaps = find_activity_gaps(&dates, 5, 3);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].kind, GapKind::Activity);
+        assert_eq!(gaps[0].gap_start, date!(2024 - 03 - 06));
+        assert_eq!(gaps[0].days(), 1);
+        assert!(find_activity_gaps(&dates, 6, 3).is_empty());
+        assert_eq!(
+            "heartrate".parse::<GapKind>().ok(),
+            Some(GapKind::Heartrate)
+        );
+    }
+}
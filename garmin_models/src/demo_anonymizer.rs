@@ -0,0 +1,360 @@
+use stack_string::{format_sstr, StackString};
+use std::f64::consts::PI;
+use time::{Date, Duration, Month, OffsetDateTime, Weekday};
+
+use garmin_lib::garmin_config::GarminConfig;
+
+use crate::{garmin_file::GarminFile, route_match::MatchedRoute};
+
+/// Meters per degree of latitude (and of longitude at the equator)
+const METERS_PER_DEGREE: f64 = 6_371_000.0 * PI / 180.0;
+
+/// Largest per point jitter as a fraction of `jitter_meters`
+const POINT_JITTER_FRACTION: f64 = 0.02;
+
+/// Salts of the values drawn for an activity, points and route points are
+/// salted by their index on top
+const ROTATION_SALT: u64 = 1;
+const BEARING_SALT: u64 = 2;
+const DISTANCE_SALT: u64 = 3;
+const NAME_SALT: u64 = 4;
+const POINT_SALT: u64 = 1 << 32;
+const ROUTE_SALT: u64 = 1 << 48;
+
+/// Hides where and when demo activities happened: tracks are rotated about
+/// their start, moved by an offset fixed per activity and jittered point by
+/// point, dates are shifted back and names are replaced
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemoAnonymizer {
+    jitter_meters: f64,
+    date_shift: Duration,
+    secret: StackString,
+}
+
+/// Rotation and offset drawn for one activity, applied in meters about the
+/// start of its track
+struct TrackTransform {
+    origin: (f64, f64),
+    lon_scale: f64,
+    rotation: f64,
+    offset: (f64, f64),
+    jitter: f64,
+    hash: u64,
+}
+
+impl TrackTransform {
+    fn apply(&self, salt: u64, latitude: f64, longitude: f64) -> (f64, f64) {
+        let (lat0, lon0) = self.origin;
+        let north = (latitude - lat0) * METERS_PER_DEGREE;
+        let east = (longitude - lon0) * METERS_PER_DEGREE * self.lon_scale;
+        let (sin, cos) = self.rotation.sin_cos();
+        let point_hash = mix(self.hash, salt);
+        let bearing = unit(point_hash) * 2.0 * PI;
+        let radius = unit(mix(point_hash, 0)) * self.jitter;
+        let rotated_north = north * cos - east * sin + self.offset.0 + radius * bearing.cos();
+        let rotated_east = north * sin + east * cos + self.offset.1 + radius * bearing.sin();
+        (
+            lat0 + rotated_north / METERS_PER_DEGREE,
+            lon0 + rotated_east / (METERS_PER_DEGREE * self.lon_scale),
+        )
+    }
+}
+
+/// splitmix64 finalizer of `hash` and `salt`
+fn mix(hash: u64, salt: u64) -> u64 {
+    let mut z = hash ^ salt.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// `hash` mapped onto [0, 1)
+fn unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+impl DemoAnonymizer {
+    /// `None` when `demo_anonymize` is turned off
+    #[must_use]
+    pub fn from_config(config: &GarminConfig) -> Option<Self> {
+        if !config.demo_anonymize {
+            return None;
+        }
+        Some(Self {
+            jitter_meters: config.demo_jitter_meters,
+            date_shift: Duration::days(config.demo_date_shift_days),
+            secret: config.secret_key.clone(),
+        })
+    }
+
+    #[must_use]
+    pub fn shift_datetime(&self, datetime: OffsetDateTime) -> OffsetDateTime {
+        datetime - self.date_shift
+    }
+
+    /// Real time of the demo `datetime`
+    #[must_use]
+    pub fn unshift_datetime(&self, datetime: OffsetDateTime) -> OffsetDateTime {
+        datetime + self.date_shift
+    }
+
+    #[must_use]
+    pub fn shift_date(&self, date: Date) -> Date {
+        date - self.date_shift
+    }
+
+    /// Real date of the demo `date`
+    #[must_use]
+    pub fn unshift_date(&self, date: Date) -> Date {
+        date + self.date_shift
+    }
+
+    /// Demo iso week of the real one, weeks are moved by their thursday so
+    /// shifting back and forth gives the same week
+    #[must_use]
+    pub fn shift_year_week(&self, year: i32, week: u8) -> (i32, u8) {
+        Self::move_year_week(year, week, -self.date_shift)
+    }
+
+    #[must_use]
+    pub fn unshift_year_week(&self, year: i32, week: u8) -> (i32, u8) {
+        Self::move_year_week(year, week, self.date_shift)
+    }
+
+    /// Demo month of the real one, months are moved by their middle
+    #[must_use]
+    pub fn shift_year_month(&self, year: i32, month: u8) -> (i32, u8) {
+        Self::move_year_month(year, month, -self.date_shift)
+    }
+
+    #[must_use]
+    pub fn unshift_year_month(&self, year: i32, month: u8) -> (i32, u8) {
+        Self::move_year_month(year, month, self.date_shift)
+    }
+
+    /// Demo year of the real one, years are moved by their middle
+    #[must_use]
+    pub fn shift_year(&self, year: i32) -> i32 {
+        Self::move_year_month(year, 7, -self.date_shift).0
+    }
+
+    #[must_use]
+    pub fn unshift_year(&self, year: i32) -> i32 {
+        Self::move_year_month(year, 7, self.date_shift).0
+    }
+
+    fn move_year_week(year: i32, week: u8, shift: Duration) -> (i32, u8) {
+        Date::from_iso_week_date(year, week, Weekday::Thursday)
+            .ok()
+            .and_then(|d| d.checked_add(shift))
+            .map_or((year, week), |d| {
+                let (year, week, _) = d.to_iso_week_date();
+                (year, week)
+            })
+    }
+
+    fn move_year_month(year: i32, month: u8, shift: Duration) -> (i32, u8) {
+        Month::try_from(month)
+            .ok()
+            .and_then(|m| Date::from_calendar_date(year, m, 15).ok())
+            .and_then(|d| d.checked_add(shift))
+            .map_or((year, month), |d| (d.year(), d.month().into()))
+    }
+
+    /// Stable hash of `key` keyed on the config secret, FNV-1a followed by
+    /// the splitmix64 finalizer so a demo looks the same whatever toolchain
+    /// built it, and can't be recomputed from the filename alone
+    fn hash(&self, key: &str) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+        for byte in self.secret.bytes().chain([0]).chain(key.bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        mix(hash, 0)
+    }
+
+    fn transform(&self, key: &str, origin: (f64, f64)) -> TrackTransform {
+        let hash = self.hash(key);
+        let bearing = unit(mix(hash, BEARING_SALT)) * 2.0 * PI;
+        let distance = self.jitter_meters * (0.5 + unit(mix(hash, DISTANCE_SALT)) / 2.0);
+        TrackTransform {
+            origin,
+            lon_scale: origin.0.to_radians().cos().abs().max(0.01),
+            rotation: unit(mix(hash, ROTATION_SALT)) * 2.0 * PI,
+            offset: (distance * bearing.cos(), distance * bearing.sin()),
+            jitter: self.jitter_meters * POINT_JITTER_FRACTION,
+            hash,
+        }
+    }
+
+    /// Start (latitude, longitude) of the track of `gfile`, tracks and the
+    /// routes drawn over them are rotated about it
+    #[must_use]
+    pub fn origin(gfile: &GarminFile) -> Option<(f64, f64)> {
+        gfile
+            .points
+            .iter()
+            .find_map(|p| p.latitude.zip(p.longitude))
+    }
+
+    /// Name standing in for the activity file `filename`, keeps its extension
+    #[must_use]
+    pub fn demo_filename(&self, filename: &str) -> StackString {
+        let hash = mix(self.hash(filename), NAME_SALT);
+        match filename.split_once('.') {
+            Some((_, ext)) => format_sstr!("demo_{hash:016x}.{ext}"),
+            None => format_sstr!("demo_{hash:016x}"),
+        }
+    }
+
+    /// Rotate, move and jitter the track, shift the times and rename `gfile`
+    pub fn anonymize_file(&self, gfile: &mut GarminFile) {
+        if let Some(origin) = Self::origin(gfile) {
+            let transform = self.transform(&gfile.filename, origin);
+            for (idx, point) in gfile.points.iter_mut().enumerate() {
+                if let (Some(lat), Some(lon)) = (point.latitude, point.longitude) {
+                    let (lat, lon) = transform.apply(POINT_SALT + idx as u64, lat, lon);
+                    point.latitude = Some(lat);
+                    point.longitude = Some(lon);
+                }
+            }
+        }
+        gfile.begin_datetime = self.shift_datetime(gfile.begin_datetime.into()).into();
+        for lap in &mut gfile.laps {
+            lap.lap_start = self.shift_datetime(lap.lap_start.into()).into();
+            lap.lap_start_string = None;
+        }
+        for point in &mut gfile.points {
+            point.time = self.shift_datetime(point.time.into()).into();
+        }
+        gfile.filename = self.demo_filename(&gfile.filename);
+    }
+
+    /// Move `route` along with the activity `filename` starting at `origin`
+    /// it is drawn over and hide its name, a route over an activity without
+    /// a track is rotated about its own start
+    pub fn anonymize_route(
+        &self,
+        filename: &str,
+        origin: Option<(f64, f64)>,
+        route: &mut MatchedRoute,
+    ) {
+        let route_start = route
+            .latitudes
+            .first()
+            .copied()
+            .zip(route.longitudes.first().copied());
+        if let Some(origin) = origin.or(route_start) {
+            let transform = self.transform(filename, origin);
+            for (idx, (lat, lon)) in route
+                .latitudes
+                .iter_mut()
+                .zip(route.longitudes.iter_mut())
+                .enumerate()
+            {
+                (*lat, *lon) = transform.apply(ROUTE_SALT + idx as u64, *lat, *lon);
+            }
+        }
+        route.name = "Matched route".into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use garmin_lib::garmin_config::GarminConfig;
+    use garmin_utils::garmin_util::haversine_distance;
+
+    use crate::{
+        demo_anonymizer::DemoAnonymizer, garmin_file::GarminFile, garmin_lap::GarminLap,
+        garmin_point::GarminPoint,
+    };
+
+    #[test]
+    fn test_demo_anonymizer() {
+        let config = GarminConfig::default();
+        let anonymizer = DemoAnonymizer::from_config(&config).unwrap();
+
+        let begin = datetime!(2024-05-04 13:00:00 UTC);
+        let mut gfile = GarminFile::new();
+        gfile.filename = "2024-05-04_13-00-00_1_1.fit".into();
+        gfile.begin_datetime = begin.into();
+        gfile.laps = vec![GarminLap {
+            lap_start: begin.into(),
+            lap_start_string: Some("2024-05-04T13:00:00Z".into()),
+            ..GarminLap::new()
+        }];
+        gfile.points = (0..10_i32)
+            .map(|idx| GarminPoint {
+                time: (begin + time::Duration::seconds(idx.into())).into(),
+                latitude: Some(40.0 + f64::from(idx) * 1e-3),
+                longitude: Some(-74.0),
+                ..GarminPoint::default()
+            })
+            .collect();
+        let original = gfile.clone();
+
+        anonymizer.anonymize_file(&mut gfile);
+        assert_eq!(
+            gfile.begin_datetime,
+            datetime!(2023-05-06 13:00:00 UTC).into()
+        );
+        assert_eq!(gfile.laps[0].lap_start, gfile.begin_datetime);
+        assert!(gfile.laps[0].lap_start_string.is_none());
+        assert_eq!(gfile.points[0].time, gfile.begin_datetime);
+        assert!(gfile.filename.starts_with("demo_"));
+        assert!(gfile.filename.ends_with(".fit"));
+        assert!(!gfile.filename.contains("2024"));
+
+        let distance = |p: &GarminPoint, q: &GarminPoint| {
+            haversine_distance(
+                p.latitude.unwrap(),
+                p.longitude.unwrap(),
+                q.latitude.unwrap(),
+                q.longitude.unwrap(),
+            )
+        };
+        let shift = distance(&original.points[0], &gfile.points[0]);
+        assert!((240.0..=510.0).contains(&shift), "{shift}");
+        // the track is rotated, so it no longer runs due north
+        let (first, last) = (&gfile.points[0], &gfile.points[9]);
+        let east = (last.longitude.unwrap() - first.longitude.unwrap()).abs();
+        assert!(east > 1e-3, "{east}");
+        // and jittered, so distances along it change a little
+        let steps: Vec<f64> = gfile
+            .points
+            .windows(2)
+            .map(|w| distance(&w[0], &w[1]))
+            .collect();
+        assert!(steps.iter().all(|s| (s - 111.2).abs() < 25.0), "{steps:?}");
+        assert!(steps.iter().any(|s| (s - 111.2).abs() > 0.1), "{steps:?}");
+
+        let mut again = original.clone();
+        anonymizer.anonymize_file(&mut again);
+        assert_eq!(again, gfile);
+        // the hash is fixed, so the name doesn't change with the toolchain
+        assert_eq!(
+            anonymizer.demo_filename(&original.filename),
+            DemoAnonymizer::from_config(&config)
+                .unwrap()
+                .demo_filename(&original.filename)
+        );
+    }
+
+    #[test]
+    fn test_demo_periods() {
+        let config = GarminConfig::default();
+        let anonymizer = DemoAnonymizer::from_config(&config).unwrap();
+
+        let day = date!(2024 - 05 - 04);
+        assert_eq!(anonymizer.shift_date(day), date!(2023 - 05 - 06));
+        assert_eq!(anonymizer.unshift_date(anonymizer.shift_date(day)), day);
+        assert_eq!(anonymizer.shift_year_week(2024, 18), (2023, 18));
+        assert_eq!(anonymizer.unshift_year_week(2023, 18), (2024, 18));
+        assert_eq!(anonymizer.shift_year_month(2024, 5), (2023, 5));
+        assert_eq!(anonymizer.unshift_year_month(2023, 5), (2024, 5));
+        assert_eq!(anonymizer.shift_year(2024), 2023);
+        assert_eq!(anonymizer.unshift_year(2023), 2024);
+    }
+}
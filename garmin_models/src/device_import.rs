@@ -0,0 +1,58 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+/// Modification time of the newest activity file imported from the watch
+/// with serial number `serial`, stored in `device_imports`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeviceImport {
+    pub serial: StackString,
+    pub last_modified: DateTimeWrapper,
+    pub last_imported_at: DateTimeWrapper,
+}
+
+impl DeviceImport {
+    #[must_use]
+    pub fn new(serial: &str, last_modified: DateTimeWrapper) -> Self {
+        Self {
+            serial: serial.into(),
+            last_modified,
+            last_imported_at: DateTimeWrapper::now(),
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_serial(pool: &PgPool, serial: &str) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM device_imports WHERE serial = $serial",
+            serial = serial,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO device_imports (serial, last_modified, last_imported_at)
+                VALUES ($serial, $last_modified, $last_imported_at)
+                ON CONFLICT (serial) DO UPDATE
+                SET last_modified=EXCLUDED.last_modified,
+                    last_imported_at=EXCLUDED.last_imported_at
+            ",
+            serial = self.serial,
+            last_modified = self.last_modified,
+            last_imported_at = self.last_imported_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
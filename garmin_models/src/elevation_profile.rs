@@ -0,0 +1,231 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use garmin_utils::pgpool::PgPool;
+
+use crate::garmin_file::GarminFile;
+
+/// Size of the sparkline in the summary table, the stored path is drawn in
+/// these coordinates
+pub const PROFILE_WIDTH: f64 = 100.0;
+pub const PROFILE_HEIGHT: f64 = 20.0;
+/// Number of points in the downsampled profile
+pub const PROFILE_POINTS: usize = 50;
+
+#[derive(FromSqlRow)]
+struct MissingProfileRow {
+    id: Uuid,
+    filename: StackString,
+}
+
+/// Downsampled altitude against distance of an activity, stored as an svg
+/// path in `elevation_profiles` so summary rows can show it without reading
+/// the file
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ElevationProfile {
+    pub summary_id: Uuid,
+    pub svg_path: StackString,
+    pub min_altitude: f64,
+    pub max_altitude: f64,
+}
+
+impl ElevationProfile {
+    /// Placeholder for activities without altitude, so they aren't read
+    /// again on every sync
+    #[must_use]
+    pub fn empty(summary_id: Uuid) -> Self {
+        Self {
+            summary_id,
+            svg_path: StackString::new(),
+            min_altitude: 0.0,
+            max_altitude: 0.0,
+        }
+    }
+
+    /// Profile of the points with altitude in `gfile`, positioned by distance
+    /// where available and by elapsed time otherwise
+    #[must_use]
+    pub fn from_gfile(summary_id: Uuid, gfile: &GarminFile) -> Option<Self> {
+        let use_distance = gfile.points.iter().any(|p| p.distance.is_some());
+        let samples: Vec<_> = gfile
+            .points
+            .iter()
+            .filter_map(|p| {
+                let x = if use_distance {
+                    p.distance?
+                } else {
+                    p.duration_from_begin
+                };
+                p.altitude.map(|a| (x, a))
+            })
+            .collect();
+        let altitudes = downsample(&samples, PROFILE_POINTS);
+        if altitudes.len() < 2 {
+            return None;
+        }
+        let min_altitude = altitudes.iter().copied().fold(f64::MAX, f64::min);
+        let max_altitude = altitudes.iter().copied().fold(f64::MIN, f64::max);
+        let range = max_altitude - min_altitude;
+        let step = PROFILE_WIDTH / (altitudes.len() - 1) as f64;
+
+        let mut svg_path = StackString::new();
+        for (idx, altitude) in altitudes.iter().enumerate() {
+            let x = step * idx as f64;
+            // flat activities are drawn along the middle
+            let y = if range > 0.0 {
+                PROFILE_HEIGHT * (1.0 - (altitude - min_altitude) / range)
+            } else {
+                PROFILE_HEIGHT / 2.0
+            };
+            let cmd = if idx == 0 { 'M' } else { 'L' };
+            write!(svg_path, "{cmd}{x:.1},{y:.1} ").ok()?;
+        }
+        let svg_path = svg_path.trim_end().into();
+        Some(Self {
+            summary_id,
+            svg_path,
+            min_altitude,
+            max_altitude,
+        })
+    }
+
+    /// Inline svg sparkline, the tooltip gives the altitude range in meters
+    #[must_use]
+    pub fn to_svg(&self) -> Option<StackString> {
+        if self.svg_path.is_empty() {
+            return None;
+        }
+        Some(format_sstr!(
+            r#"<svg width="{w}" height="{h}" viewBox="0 0 {w} {h}"><title>{min:.0}m - {max:.0}m</title><path d="{path}" fill="none" stroke="steelblue" stroke-width="1"/></svg>"#,
+            w = PROFILE_WIDTH,
+            h = PROFILE_HEIGHT,
+            min = self.min_altitude,
+            max = self.max_altitude,
+            path = self.svg_path,
+        ))
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO elevation_profiles
+                    (summary_id, svg_path, min_altitude, max_altitude)
+                VALUES ($summary_id, $svg_path, $min_altitude, $max_altitude)
+                ON CONFLICT (summary_id) DO UPDATE
+                SET svg_path=EXCLUDED.svg_path,
+                    min_altitude=EXCLUDED.min_altitude,
+                    max_altitude=EXCLUDED.max_altitude
+            ",
+            summary_id = self.summary_id,
+            svg_path = self.svg_path,
+            min_altitude = self.min_altitude,
+            max_altitude = self.max_altitude,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_summary_id(pool: &PgPool, summary_id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT summary_id, svg_path, min_altitude, max_altitude
+                FROM elevation_profiles
+                WHERE summary_id = $summary_id
+            ",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// (id, filename) of activities without a stored profile
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_summaries(pool: &PgPool) -> Result<Vec<(Uuid, StackString)>, Error> {
+        let query = query!(
+            "
+                SELECT a.id, a.filename
+                FROM garmin_summary a
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM elevation_profiles b WHERE b.summary_id = a.id
+                )
+                ORDER BY a.begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingProfileRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+}
+
+/// Average altitude in each of `buckets` equal spans of x, empty spans are
+/// skipped
+#[must_use]
+pub fn downsample(samples: &[(f64, f64)], buckets: usize) -> Vec<f64> {
+    let (first, last) = match (samples.first(), samples.last()) {
+        (Some((first, _)), Some((last, _))) if last > first && buckets > 0 => (*first, *last),
+        _ => return Vec::new(),
+    };
+    let width = (last - first) / buckets as f64;
+    let mut sums = vec![(0.0, 0usize); buckets];
+    for (x, altitude) in samples {
+        let idx = (((x - first) / width) as usize).min(buckets - 1);
+        sums[idx].0 += altitude;
+        sums[idx].1 += 1;
+    }
+    sums.into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(sum, count)| sum / count as f64)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use uuid::Uuid;
+
+    use crate::{
+        elevation_profile::{downsample, ElevationProfile},
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+    };
+
+    #[test]
+    fn test_elevation_profile() {
+        let samples = [(0.0, 10.0), (1.0, 20.0), (2.0, 30.0), (4.0, 50.0)];
+        let altitudes = downsample(&samples, 2);
+        assert_eq!(altitudes.len(), 2);
+        assert_abs_diff_eq!(altitudes[0], 15.0);
+        assert_abs_diff_eq!(altitudes[1], 40.0);
+        assert!(downsample(&samples[..1], 2).is_empty());
+
+        let mut gfile = GarminFile::default();
+        gfile.points = (0..3)
+            .map(|i| GarminPoint {
+                distance: Some(f64::from(i) * 100.0),
+                altitude: Some(f64::from(i % 2) * 10.0 + 100.0),
+                ..GarminPoint::default()
+            })
+            .collect();
+        let profile = ElevationProfile::from_gfile(Uuid::new_v4(), &gfile).unwrap();
+        assert_abs_diff_eq!(profile.min_altitude, 100.0);
+        assert_abs_diff_eq!(profile.max_altitude, 110.0);
+        assert!(profile.svg_path.starts_with("M0.0,20.0 L"));
+        assert!(profile.to_svg().unwrap().contains("100m - 110m"));
+        assert!(ElevationProfile::empty(profile.summary_id)
+            .to_svg()
+            .is_none());
+
+        gfile.points.iter_mut().for_each(|p| p.altitude = None);
+        assert!(ElevationProfile::from_gfile(Uuid::new_v4(), &gfile).is_none());
+    }
+}
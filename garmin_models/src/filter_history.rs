@@ -0,0 +1,112 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+/// Number of unpinned filters kept per user, pinned filters are never pruned
+pub const MAX_UNPINNED_HISTORY: i64 = 10;
+
+/// Filter submitted on the index page, persisted per user in `filter_history`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FilterHistory {
+    pub email: StackString,
+    pub filter: StackString,
+    pub pinned: bool,
+    pub last_used: DateTimeWrapper,
+}
+
+impl FilterHistory {
+    /// Pinned entries first, then most recently used
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_email(pool: &PgPool, email: &str) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT * FROM filter_history
+                WHERE email = $email
+                ORDER BY pinned DESC, last_used DESC
+            ",
+            email = email,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Insert `filter` or bump its `last_used`, then prune old unpinned
+    /// entries
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn add(pool: &PgPool, email: &str, filter: &str) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO filter_history (email, filter)
+                VALUES ($email, $filter)
+                ON CONFLICT (email, filter) DO UPDATE SET last_used = now()
+            ",
+            email = email,
+            filter = filter,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Self::prune(pool, email).await
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn set_pinned(
+        pool: &PgPool,
+        email: &str,
+        filter: &str,
+        pinned: bool,
+    ) -> Result<bool, Error> {
+        let query = query!(
+            "
+                UPDATE filter_history SET pinned = $pinned
+                WHERE email = $email AND filter = $filter
+            ",
+            email = email,
+            filter = filter,
+            pinned = pinned,
+        );
+        let conn = pool.get().await?;
+        let n = query.execute(&conn).await?;
+        Ok(n > 0)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete(pool: &PgPool, email: &str, filter: &str) -> Result<bool, Error> {
+        let query = query!(
+            "DELETE FROM filter_history WHERE email = $email AND filter = $filter",
+            email = email,
+            filter = filter,
+        );
+        let conn = pool.get().await?;
+        let n = query.execute(&conn).await?;
+        Ok(n > 0)
+    }
+
+    async fn prune(pool: &PgPool, email: &str) -> Result<(), Error> {
+        let query = query!(
+            "
+                DELETE FROM filter_history
+                WHERE email = $email
+                  AND NOT pinned
+                  AND filter NOT IN (
+                    SELECT filter FROM filter_history
+                    WHERE email = $email AND NOT pinned
+                    ORDER BY last_used DESC
+                    LIMIT $limit
+                  )
+            ",
+            email = email,
+            limit = MAX_UNPINNED_HISTORY,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
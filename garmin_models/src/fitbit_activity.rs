@@ -12,6 +12,8 @@ use garmin_lib::date_time_wrapper::DateTimeWrapper;
 
 use garmin_utils::pgpool::PgPool;
 
+use crate::keyset::{KeysetRow, PageStart};
+
 #[derive(Serialize, Deserialize, Clone, Debug, FromSqlRow, PartialEq)]
 pub struct FitbitActivity {
     #[serde(alias = "logType")]
@@ -33,12 +35,21 @@ pub struct FitbitActivity {
     pub log_id: i64,
 }
 
+impl KeysetRow for FitbitActivity {
+    fn page_after(&self) -> PageStart {
+        PageStart::After {
+            datetime: self.start_time.into(),
+            id: self.log_id,
+        }
+    }
+}
+
 impl FitbitActivity {
     fn get_fitbit_activity_query<'a>(
         select_str: &'a str,
         start_date: Option<&'a Date>,
         end_date: Option<&'a Date>,
-        offset: Option<usize>,
+        start: Option<&'a PageStart>,
         limit: Option<usize>,
         order_str: &'a str,
     ) -> Result<Query<'a>, PqError> {
@@ -52,6 +63,11 @@ impl FitbitActivity {
             conditions.push("date(start_time) <= $end_date");
             query_bindings.push(("end_date", d as Parameter));
         }
+        if let Some(PageStart::After { datetime, id }) = start {
+            conditions.push("(start_time, log_id) > ($after_datetime, $after_id)");
+            query_bindings.push(("after_datetime", datetime as Parameter));
+            query_bindings.push(("after_id", id as Parameter));
+        }
         let mut query = format_sstr!(
             "SELECT {select_str} FROM fitbit_activities {} {order_str}",
             if conditions.is_empty() {
@@ -60,7 +76,7 @@ impl FitbitActivity {
                 format_sstr!("WHERE {}", conditions.join(" AND "))
             }
         );
-        if let Some(offset) = &offset {
+        if let Some(offset) = start.and_then(PageStart::offset) {
             query.push_str(&format_sstr!(" OFFSET {offset}"));
         }
         if let Some(limit) = &limit {
@@ -76,16 +92,16 @@ impl FitbitActivity {
         pool: &PgPool,
         start_date: Option<Date>,
         end_date: Option<Date>,
-        offset: Option<usize>,
+        start: Option<PageStart>,
         limit: Option<usize>,
     ) -> Result<Vec<Self>, Error> {
         let query = Self::get_fitbit_activity_query(
             "*",
             start_date.as_ref(),
             end_date.as_ref(),
-            offset,
+            start.as_ref(),
             limit,
-            "ORDER BY start_time",
+            "ORDER BY start_time, log_id",
         )?;
         let conn = pool.get().await?;
         query.fetch(&conn).await.map_err(Into::into)
@@ -9,6 +9,7 @@ use time::{
     format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime,
     PrimitiveDateTime,
 };
+use tracing::instrument;
 use uuid::Uuid;
 
 use garmin_lib::date_time_wrapper::DateTimeWrapper;
@@ -17,6 +18,11 @@ use garmin_utils::pgpool::PgPool;
 
 use garmin_lib::garmin_config::GarminConfig;
 
+use crate::{
+    keyset::{KeysetRow, PageStart},
+    provenance::{DataProvider, Provenance},
+};
+
 #[derive(Serialize, Deserialize, Debug, FromSqlRow, PartialEq, Clone)]
 pub struct GarminConnectActivity {
     #[serde(alias = "activityId")]
@@ -40,12 +46,21 @@ pub struct GarminConnectActivity {
     pub max_hr: Option<f64>,
 }
 
+impl KeysetRow for GarminConnectActivity {
+    fn page_after(&self) -> PageStart {
+        PageStart::After {
+            datetime: self.start_time_gmt.into(),
+            id: self.activity_id,
+        }
+    }
+}
+
 impl GarminConnectActivity {
     fn garmin_connect_activity_query<'a>(
         select_str: &'a str,
         start_date: Option<&'a Date>,
         end_date: Option<&'a Date>,
-        offset: Option<usize>,
+        start: Option<&'a PageStart>,
         limit: Option<usize>,
         order_str: &'a str,
     ) -> Result<Query<'a>, PqError> {
@@ -59,6 +74,11 @@ impl GarminConnectActivity {
             conditions.push("date(start_time_gmt) <= $end_date");
             query_bindings.push(("end_date", d as Parameter));
         }
+        if let Some(PageStart::After { datetime, id }) = start {
+            conditions.push("(start_time_gmt, activity_id) > ($after_datetime, $after_id)");
+            query_bindings.push(("after_datetime", datetime as Parameter));
+            query_bindings.push(("after_id", id as Parameter));
+        }
         let mut query = format_sstr!(
             "SELECT {select_str} FROM garmin_connect_activities {cond} {order_str}",
             cond = if conditions.is_empty() {
@@ -67,7 +87,7 @@ impl GarminConnectActivity {
                 format_sstr!("WHERE {}", conditions.join(" AND "))
             }
         );
-        if let Some(offset) = offset {
+        if let Some(offset) = start.and_then(PageStart::offset) {
             query.push_str(&format_sstr!(" OFFSET {offset}"));
         }
         if let Some(limit) = limit {
@@ -83,16 +103,16 @@ impl GarminConnectActivity {
         pool: &PgPool,
         start_date: Option<Date>,
         end_date: Option<Date>,
-        offset: Option<usize>,
+        start: Option<PageStart>,
         limit: Option<usize>,
     ) -> Result<impl Stream<Item = Result<Self, PqError>>, Error> {
         let query = Self::garmin_connect_activity_query(
             "*",
             start_date.as_ref(),
             end_date.as_ref(),
-            offset,
+            start.as_ref(),
             limit,
-            "ORDER BY start_time_gmt",
+            "ORDER BY start_time_gmt, activity_id",
         )?;
         let conn = pool.get().await?;
         query.fetch_streaming(&conn).await.map_err(Into::into)
@@ -152,18 +172,26 @@ impl GarminConnectActivity {
         query.fetch_opt(&conn).await.map_err(Into::into)
     }
 
+    /// Insert the activity along with where it came from, provenance is
+    /// only recorded when the activity is first stored
     /// # Errors
     /// Return error if db query fails
-    pub async fn insert_into_db(&self, pool: &PgPool) -> Result<(), Error> {
+    pub async fn insert_into_db(
+        &self,
+        pool: &PgPool,
+        provenance: &Provenance,
+    ) -> Result<(), Error> {
         let query = query!(
             "
                 INSERT INTO garmin_connect_activities (
                     activity_id,activity_name,description,start_time_gmt,distance,duration,
-                    elapsed_duration,moving_duration,steps,calories,average_hr,max_hr
+                    elapsed_duration,moving_duration,steps,calories,average_hr,max_hr,
+                    provider,import_path,raw_file,imported_at
                 )
                 VALUES (
                     $activity_id,$activity_name,$description,$start_time_gmt,$distance,$duration,
-                    $elapsed_duration,$moving_duration,$steps,$calories,$average_hr,$max_hr
+                    $elapsed_duration,$moving_duration,$steps,$calories,$average_hr,$max_hr,
+                    $provider,$import_path,$raw_file,$imported_at
                 )",
             activity_id = self.activity_id,
             activity_name = self.activity_name,
@@ -177,6 +205,10 @@ impl GarminConnectActivity {
             calories = self.calories,
             average_hr = self.average_hr,
             max_hr = self.max_hr,
+            provider = provenance.provider.to_str(),
+            import_path = provenance.import_path,
+            raw_file = provenance.raw_file,
+            imported_at = provenance.imported_at,
         );
         let conn = pool.get().await?;
         query.execute(&conn).await.map(|_| ()).map_err(Into::into)
@@ -214,9 +246,11 @@ impl GarminConnectActivity {
 
     /// # Errors
     /// Return error if db query fails
+    #[instrument(skip_all, fields(db.system = "postgresql"))]
     pub async fn upsert_activities(
         activities: &[Self],
         pool: &PgPool,
+        provenance: &Provenance,
     ) -> Result<Vec<StackString>, Error> {
         let mut output = Vec::new();
         let mut existing_activities: HashMap<_, _> =
@@ -245,7 +279,7 @@ impl GarminConnectActivity {
         let futures = insert_items.into_iter().map(|activity| {
             let pool = pool.clone();
             async move {
-                activity.insert_into_db(&pool).await?;
+                activity.insert_into_db(&pool, provenance).await?;
                 let activity_str = StackString::from_display(activity.activity_id);
                 Ok(activity_str)
             }
@@ -260,6 +294,7 @@ impl GarminConnectActivity {
     pub async fn merge_new_activities(
         new_activities: Vec<Self>,
         pool: &PgPool,
+        provenance: &Provenance,
     ) -> Result<Vec<Self>, Error> {
         let mut activities: HashMap<_, _> =
             GarminConnectActivity::read_from_db(pool, None, None, None, None)
@@ -274,7 +309,7 @@ impl GarminConnectActivity {
             .into_iter()
             .filter(|activity| !activities.contains_key(&activity.activity_id))
             .map(|activity| async move {
-                activity.insert_into_db(pool).await?;
+                activity.insert_into_db(pool, provenance).await?;
                 Ok(activity)
             });
         try_join_all(futures).await
@@ -304,7 +339,13 @@ pub async fn import_garmin_connect_activity_json_file(filename: &Path) -> Result
         return Err(format_err!("file {filename:?} does not exist"));
     }
     let activities = serde_json::from_reader(File::open(filename)?)?;
-    GarminConnectActivity::merge_new_activities(activities, &pool).await?;
+    let raw_file = filename.to_string_lossy();
+    let provenance = Provenance::new(
+        DataProvider::GarminConnect,
+        "import-garmin-connect-data activities",
+        Some(&raw_file),
+    );
+    GarminConnectActivity::merge_new_activities(activities, &pool, &provenance).await?;
     Ok(())
 }
 
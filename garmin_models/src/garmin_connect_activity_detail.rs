@@ -0,0 +1,474 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow, Query};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+use crate::garmin_connect_activity::{deserialize_start_time, GarminConnectActivity};
+
+const MPH_TO_METERS_PER_SECOND: f64 = 0.447_04;
+
+/// Weather, device and gear of a Garmin Connect activity, these only come
+/// from the activity detail endpoints and not from the activity list, so
+/// they're filled in from whichever of them were captured
+#[derive(Serialize, Deserialize, Debug, FromSqlRow, PartialEq, Clone, Default)]
+pub struct GarminConnectActivityDetail {
+    pub activity_id: i64,
+    /// Degrees Celsius
+    pub temperature: Option<f64>,
+    /// Degrees Celsius
+    pub apparent_temperature: Option<f64>,
+    /// Percent
+    pub relative_humidity: Option<f64>,
+    /// Meters per second
+    pub wind_speed: Option<f64>,
+    pub wind_direction: Option<StackString>,
+    pub weather: Option<StackString>,
+    pub device_manufacturer: Option<StackString>,
+    pub device_id: Option<StackString>,
+    pub gear: Option<StackString>,
+}
+
+/// Split (lap) of a Garmin Connect activity as returned by
+/// `activity-service/activity/{id}/splits`, cadence is in steps per minute
+/// for runs and revolutions per minute for rides
+#[derive(Serialize, Deserialize, Debug, FromSqlRow, PartialEq, Clone)]
+pub struct GarminConnectSplit {
+    #[serde(default)]
+    pub activity_id: i64,
+    #[serde(alias = "lapIndex")]
+    pub split_index: i32,
+    #[serde(alias = "startTimeGMT", deserialize_with = "deserialize_start_time")]
+    pub start_time_gmt: DateTimeWrapper,
+    #[serde(default)]
+    pub distance: f64,
+    #[serde(default)]
+    pub duration: f64,
+    #[serde(alias = "movingDuration")]
+    pub moving_duration: Option<f64>,
+    #[serde(alias = "averageHR")]
+    pub average_hr: Option<f64>,
+    #[serde(alias = "maxHR")]
+    pub max_hr: Option<f64>,
+    pub calories: Option<f64>,
+    #[serde(alias = "averageRunCadence", alias = "averageBikeCadence")]
+    pub average_cadence: Option<f64>,
+    #[serde(alias = "averagePower")]
+    pub average_power: Option<f64>,
+    #[serde(alias = "elevationGain")]
+    pub elevation_gain: Option<f64>,
+}
+
+/// Response of `activity-service/activity/{id}`, only the device metadata is
+/// kept
+#[derive(Deserialize, Debug)]
+struct ConnectActivityResponse {
+    #[serde(rename = "metadataDTO")]
+    metadata: Option<ConnectActivityMetadata>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ConnectActivityMetadata {
+    manufacturer: Option<StackString>,
+    #[serde(rename = "deviceMetaDataDTO")]
+    device: Option<ConnectDeviceMetadata>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ConnectDeviceMetadata {
+    #[serde(rename = "deviceId")]
+    device_id: Option<serde_json::Value>,
+}
+
+/// Response of `activity-service/activity/{id}/weather`, temperatures are in
+/// Fahrenheit and wind speed in miles per hour
+#[derive(Deserialize, Debug)]
+struct ConnectWeatherResponse {
+    temp: Option<f64>,
+    #[serde(rename = "apparentTemp")]
+    apparent_temp: Option<f64>,
+    #[serde(rename = "relativeHumidity")]
+    relative_humidity: Option<f64>,
+    #[serde(rename = "windSpeed")]
+    wind_speed: Option<f64>,
+    #[serde(rename = "windDirectionCompassPoint")]
+    wind_direction: Option<StackString>,
+    #[serde(rename = "weatherTypeDTO")]
+    weather_type: Option<ConnectWeatherType>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ConnectWeatherType {
+    desc: Option<StackString>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ConnectSplitsResponse {
+    #[serde(rename = "lapDTOs", default)]
+    laps: Vec<GarminConnectSplit>,
+}
+
+/// Response of `gear-service/gear/filterGear?activityId={id}`
+#[derive(Deserialize, Debug)]
+struct ConnectGear {
+    #[serde(rename = "displayName")]
+    display_name: Option<StackString>,
+    #[serde(rename = "customMakeModel")]
+    custom_make_model: Option<StackString>,
+}
+
+fn fahrenheit_to_celsius(temperature: f64) -> f64 {
+    (temperature - 32.0) * 5.0 / 9.0
+}
+
+impl GarminConnectActivityDetail {
+    #[must_use]
+    pub fn new(activity_id: i64) -> Self {
+        Self {
+            activity_id,
+            ..Self::default()
+        }
+    }
+
+    /// Fill in the device from an `activity-service/activity/{id}` response
+    /// # Errors
+    /// Return error if serde fails
+    pub fn set_activity(&mut self, buf: &str) -> Result<(), Error> {
+        let response: ConnectActivityResponse = serde_json::from_str(buf)?;
+        if let Some(metadata) = response.metadata {
+            self.device_manufacturer = metadata.manufacturer;
+            self.device_id = metadata
+                .device
+                .and_then(|d| d.device_id)
+                .and_then(|id| match id {
+                    serde_json::Value::String(s) => Some(s.into()),
+                    serde_json::Value::Number(n) => Some(format_sstr!("{n}")),
+                    _ => None,
+                });
+        }
+        Ok(())
+    }
+
+    /// Fill in the weather from an `activity-service/activity/{id}/weather`
+    /// response, indoor activities come back empty
+    /// # Errors
+    /// Return error if serde fails
+    pub fn set_weather(&mut self, buf: &str) -> Result<(), Error> {
+        if buf.trim().is_empty() {
+            return Ok(());
+        }
+        let response: Option<ConnectWeatherResponse> = serde_json::from_str(buf)?;
+        if let Some(response) = response {
+            self.temperature = response.temp.map(fahrenheit_to_celsius);
+            self.apparent_temperature = response.apparent_temp.map(fahrenheit_to_celsius);
+            self.relative_humidity = response.relative_humidity;
+            self.wind_speed = response.wind_speed.map(|s| s * MPH_TO_METERS_PER_SECOND);
+            self.wind_direction = response.wind_direction;
+            self.weather = response.weather_type.and_then(|w| w.desc);
+        }
+        Ok(())
+    }
+
+    /// Fill in the gear from a `gear-service/gear/filterGear` response
+    /// # Errors
+    /// Return error if serde fails
+    pub fn set_gear(&mut self, buf: &str) -> Result<(), Error> {
+        let gear: Vec<ConnectGear> = serde_json::from_str(buf)?;
+        let names: Vec<_> = gear
+            .into_iter()
+            .filter_map(|g| g.display_name.or(g.custom_make_model))
+            .collect();
+        if !names.is_empty() {
+            self.gear = Some(names.join(", ").into());
+        }
+        Ok(())
+    }
+
+    /// Parse an `activity-service/activity/{id}/splits` response
+    /// # Errors
+    /// Return error if serde fails
+    pub fn parse_splits(&self, buf: &str) -> Result<Vec<GarminConnectSplit>, Error> {
+        let response: ConnectSplitsResponse = serde_json::from_str(buf)?;
+        let mut splits = response.laps;
+        for split in &mut splits {
+            split.activity_id = self.activity_id;
+        }
+        splits.sort_by_key(|s| s.split_index);
+        Ok(splits)
+    }
+
+    /// Weather summary, e.g. `Cloudy 12.0 C (feels 10.5 C) 80% wind 3.1 m/s
+    /// NW`
+    #[must_use]
+    pub fn weather_string(&self) -> Option<StackString> {
+        let mut values = Vec::new();
+        if let Some(weather) = &self.weather {
+            values.push(weather.clone());
+        }
+        if let Some(temperature) = self.temperature {
+            values.push(format_sstr!("{temperature:.1} C"));
+        }
+        if let Some(apparent_temperature) = self.apparent_temperature {
+            values.push(format_sstr!("(feels {apparent_temperature:.1} C)"));
+        }
+        if let Some(relative_humidity) = self.relative_humidity {
+            values.push(format_sstr!("{relative_humidity:.0}%"));
+        }
+        if let Some(wind_speed) = self.wind_speed {
+            let direction = self.wind_direction.as_ref().map_or("", StackString::as_str);
+            values.push(format_sstr!("wind {wind_speed:.1} m/s {direction}"));
+        }
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.join(" ").trim().into())
+        }
+    }
+
+    #[must_use]
+    pub fn device_string(&self) -> Option<StackString> {
+        match (&self.device_manufacturer, &self.device_id) {
+            (Some(manufacturer), Some(device_id)) => {
+                Some(format_sstr!("{manufacturer} {device_id}"))
+            }
+            (Some(s), None) | (None, Some(s)) => Some(s.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_activity_id(
+        pool: &PgPool,
+        activity_id: i64,
+    ) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT activity_id, temperature, apparent_temperature, relative_humidity,
+                       wind_speed, wind_direction, weather, device_manufacturer, device_id, gear
+                FROM garmin_connect_activity_details
+                WHERE activity_id = $activity_id
+            ",
+            activity_id = activity_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Store the details and replace the splits when any were captured, in
+    /// one transaction so a failed split insert keeps the old splits,
+    /// fields missing from this capture keep their stored value.  Returns
+    /// false without storing anything if the activity isn't in
+    /// `garmin_connect_activities` yet
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(
+        &self,
+        pool: &PgPool,
+        splits: &[GarminConnectSplit],
+    ) -> Result<bool, Error> {
+        let query = query!(
+            "
+                INSERT INTO garmin_connect_activity_details AS d (
+                    activity_id, temperature, apparent_temperature, relative_humidity,
+                    wind_speed, wind_direction, weather, device_manufacturer, device_id, gear
+                )
+                SELECT $activity_id, $temperature, $apparent_temperature, $relative_humidity,
+                       $wind_speed, $wind_direction, $weather, $device_manufacturer,
+                       $device_id, $gear
+                WHERE EXISTS (
+                    SELECT 1 FROM garmin_connect_activities WHERE activity_id = $activity_id
+                )
+                ON CONFLICT (activity_id) DO UPDATE
+                    SET temperature=COALESCE(EXCLUDED.temperature, d.temperature),
+                        apparent_temperature=COALESCE(
+                            EXCLUDED.apparent_temperature, d.apparent_temperature
+                        ),
+                        relative_humidity=COALESCE(
+                            EXCLUDED.relative_humidity, d.relative_humidity
+                        ),
+                        wind_speed=COALESCE(EXCLUDED.wind_speed, d.wind_speed),
+                        wind_direction=COALESCE(EXCLUDED.wind_direction, d.wind_direction),
+                        weather=COALESCE(EXCLUDED.weather, d.weather),
+                        device_manufacturer=COALESCE(
+                            EXCLUDED.device_manufacturer, d.device_manufacturer
+                        ),
+                        device_id=COALESCE(EXCLUDED.device_id, d.device_id),
+                        gear=COALESCE(EXCLUDED.gear, d.gear),
+                        last_modified=now()
+            ",
+            activity_id = self.activity_id,
+            temperature = self.temperature,
+            apparent_temperature = self.apparent_temperature,
+            relative_humidity = self.relative_humidity,
+            wind_speed = self.wind_speed,
+            wind_direction = self.wind_direction,
+            weather = self.weather,
+            device_manufacturer = self.device_manufacturer,
+            device_id = self.device_id,
+            gear = self.gear,
+        );
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        if tran.execute(query.sql(), query.parameters()).await? == 0 {
+            return Ok(false);
+        }
+        if !splits.is_empty() {
+            let query = query!(
+                "DELETE FROM garmin_connect_activity_splits WHERE activity_id = $activity_id",
+                activity_id = self.activity_id,
+            );
+            tran.execute(query.sql(), query.parameters()).await?;
+            for split in splits {
+                let query = split.insert_query();
+                tran.execute(query.sql(), query.parameters()).await?;
+            }
+        }
+        tran.commit().await?;
+        Ok(true)
+    }
+
+    /// Connect activities linked to a file which don't have details yet,
+    /// most recent first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn activities_missing_details(
+        pool: &PgPool,
+    ) -> Result<Vec<GarminConnectActivity>, Error> {
+        let query = query!(
+            "
+                SELECT a.*
+                FROM garmin_connect_activities a
+                LEFT JOIN garmin_connect_activity_details d
+                    ON d.activity_id = a.activity_id
+                WHERE a.summary_id IS NOT NULL
+                  AND d.activity_id IS NULL
+                ORDER BY a.start_time_gmt DESC
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+impl GarminConnectSplit {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool, activity_id: i64) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT activity_id, split_index, start_time_gmt, distance, duration,
+                       moving_duration, average_hr, max_hr, calories, average_cadence,
+                       average_power, elevation_gain
+                FROM garmin_connect_activity_splits
+                WHERE activity_id = $activity_id
+                ORDER BY split_index
+            ",
+            activity_id = activity_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    fn insert_query(&self) -> Query<'_> {
+        query!(
+            "
+                INSERT INTO garmin_connect_activity_splits (
+                    activity_id, split_index, start_time_gmt, distance, duration,
+                    moving_duration, average_hr, max_hr, calories, average_cadence,
+                    average_power, elevation_gain
+                )
+                VALUES (
+                    $activity_id, $split_index, $start_time_gmt, $distance, $duration,
+                    $moving_duration, $average_hr, $max_hr, $calories, $average_cadence,
+                    $average_power, $elevation_gain
+                )
+            ",
+            activity_id = self.activity_id,
+            split_index = self.split_index,
+            start_time_gmt = self.start_time_gmt,
+            distance = self.distance,
+            duration = self.duration,
+            moving_duration = self.moving_duration,
+            average_hr = self.average_hr,
+            max_hr = self.max_hr,
+            calories = self.calories,
+            average_cadence = self.average_cadence,
+            average_power = self.average_power,
+            elevation_gain = self.elevation_gain,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use approx::assert_abs_diff_eq;
+    use time::macros::datetime;
+
+    use crate::garmin_connect_activity_detail::GarminConnectActivityDetail;
+
+    #[test]
+    fn test_connect_activity_detail() -> Result<(), Error> {
+        let mut detail = GarminConnectActivityDetail::new(12_345_678_901);
+        detail.set_activity(
+            r#"{
+                "activityId": 12345678901,
+                "metadataDTO": {
+                    "manufacturer": "GARMIN",
+                    "deviceMetaDataDTO": {"deviceId": "3412345678", "deviceTypePk": 27}
+                }
+            }"#,
+        )?;
+        detail.set_weather(
+            r#"{
+                "issueDate": "2024-05-04T13:00:00",
+                "temp": 50,
+                "apparentTemp": 46,
+                "relativeHumidity": 80,
+                "windDirectionCompassPoint": "nw",
+                "windSpeed": 10,
+                "weatherTypeDTO": {"weatherTypePk": null, "desc": "Cloudy", "image": null}
+            }"#,
+        )?;
+        detail.set_gear(r#"[{"displayName": "Pegasus", "customMakeModel": "Nike Pegasus 40"}]"#)?;
+        assert_eq!(detail.device_string().as_deref(), Some("GARMIN 3412345678"));
+        assert_abs_diff_eq!(detail.temperature.unwrap(), 10.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(detail.wind_speed.unwrap(), 4.4704, epsilon = 1e-9);
+        assert_eq!(detail.gear.as_deref(), Some("Pegasus"));
+        assert_eq!(
+            detail.weather_string().as_deref(),
+            Some("Cloudy 10.0 C (feels 7.8 C) 80% wind 4.5 m/s nw")
+        );
+
+        let splits = detail.parse_splits(
+            r#"{
+                "activityId": 12345678901,
+                "lapDTOs": [
+                    {"lapIndex": 2, "startTimeGMT": "2024-05-04T13:08:00.0", "distance": 1609.34,
+                     "duration": 470.5, "averageHR": 152.0, "averageRunCadence": 172.0},
+                    {"lapIndex": 1, "startTimeGMT": "2024-05-04T13:00:00.0", "distance": 1609.34,
+                     "duration": 480.0, "movingDuration": 478.0, "averageHR": 145.0,
+                     "maxHR": 155.0, "calories": 110.0, "averagePower": 250.0}
+                ]
+            }"#,
+        )?;
+        assert_eq!(splits.len(), 2);
+        assert_eq!(splits[0].split_index, 1);
+        assert_eq!(splits[0].activity_id, 12_345_678_901);
+        assert_eq!(
+            splits[0].start_time_gmt.to_offsetdatetime(),
+            datetime!(2024-05-04 13:00:00 UTC)
+        );
+        assert_eq!(splits[0].average_power, Some(250.0));
+        assert_eq!(splits[1].average_cadence, Some(172.0));
+
+        let mut indoor = GarminConnectActivityDetail::new(1);
+        indoor.set_weather("")?;
+        indoor.set_weather("null")?;
+        assert_eq!(indoor.weather_string(), None);
+        Ok(())
+    }
+}
@@ -1,12 +1,26 @@
 use anyhow::Error;
+use log::error;
 use serde::Deserialize;
 use stack_string::StackString;
+use std::collections::BTreeMap;
+use url::Url;
 
-use crate::garmin_connect_activity::GarminConnectActivity;
+use crate::{
+    garmin_connect_activity::GarminConnectActivity,
+    garmin_connect_activity_detail::{GarminConnectActivityDetail, GarminConnectSplit},
+};
 
 const ACTIVITY_URL: &str =
     "https://connect.garmin.com/activitylist-service/activities/search/activities";
 const HEARTRATE_URL: &str = "https://connect.garmin.com/wellness-service/wellness/dailyHeartRate";
+const SPO2_URL: &str = "https://connect.garmin.com/wellness-service/wellness/daily/spo2";
+const BLOOD_PRESSURE_URL: &str =
+    "https://connect.garmin.com/bloodpressure-service/bloodpressure/range";
+const RESPIRATION_URL: &str =
+    "https://connect.garmin.com/wellness-service/wellness/daily/respiration";
+const WEIGHT_URL: &str = "https://connect.garmin.com/weight-service/weight/dateRange";
+const ACTIVITY_DETAIL_URL: &str = "https://connect.garmin.com/activity-service/activity/";
+const GEAR_URL: &str = "https://connect.garmin.com/gear-service/gear/filterGear";
 
 #[derive(Deserialize)]
 pub struct GarminConnectHarFile {
@@ -28,11 +42,92 @@ impl GarminConnectHarFile {
 
     #[must_use]
     pub fn get_heartrates(&self) -> Vec<&str> {
+        self.get_responses(HEARTRATE_URL)
+    }
+
+    #[must_use]
+    pub fn get_spo2(&self) -> Vec<&str> {
+        self.get_responses(SPO2_URL)
+    }
+
+    #[must_use]
+    pub fn get_blood_pressures(&self) -> Vec<&str> {
+        self.get_responses(BLOOD_PRESSURE_URL)
+    }
+
+    #[must_use]
+    pub fn get_respiration(&self) -> Vec<&str> {
+        self.get_responses(RESPIRATION_URL)
+    }
+
+    #[must_use]
+    pub fn get_weights(&self) -> Vec<&str> {
+        self.get_responses(WEIGHT_URL)
+    }
+
+    /// Details and splits of every activity whose detail, weather, splits or
+    /// gear page was captured, keyed by the activity id in the request url,
+    /// responses which can't be read (error pages, encoded content) are
+    /// logged and skipped so they don't hold up the rest of the import
+    #[must_use]
+    pub fn get_activity_details(
+        &self,
+    ) -> Vec<(GarminConnectActivityDetail, Vec<GarminConnectSplit>)> {
+        let mut details = BTreeMap::new();
+        for entry in &self.log.entries {
+            let buf = match entry.response.content.text.as_ref() {
+                Some(buf) => buf.as_str(),
+                None => continue,
+            };
+            let url = entry.request.url.as_str();
+            if let Some((_, path)) = url.split_once(ACTIVITY_DETAIL_URL) {
+                let path = path.split('?').next().unwrap_or(path);
+                let mut segments = path.split('/');
+                let activity_id: i64 = match segments.next().and_then(|s| s.parse().ok()) {
+                    Some(activity_id) => activity_id,
+                    None => continue,
+                };
+                let (detail, splits) = details
+                    .entry(activity_id)
+                    .or_insert_with(|| (GarminConnectActivityDetail::new(activity_id), Vec::new()));
+                let result = match segments.next() {
+                    None | Some("") => detail.set_activity(buf),
+                    Some("weather") => detail.set_weather(buf),
+                    Some("splits") => detail.parse_splits(buf).map(|s| *splits = s),
+                    Some(_) => Ok(()),
+                };
+                if let Err(e) = result {
+                    error!("skipping {url}: {e}");
+                }
+            } else if url.starts_with(GEAR_URL) {
+                let activity_id = Url::parse(url).ok().and_then(|url| {
+                    url.query_pairs()
+                        .find(|(k, _)| k == "activityId")
+                        .and_then(|(_, v)| v.parse().ok())
+                });
+                if let Some(activity_id) = activity_id {
+                    let result = details
+                        .entry(activity_id)
+                        .or_insert_with(|| {
+                            (GarminConnectActivityDetail::new(activity_id), Vec::new())
+                        })
+                        .0
+                        .set_gear(buf);
+                    if let Err(e) = result {
+                        error!("skipping {url}: {e}");
+                    }
+                }
+            }
+        }
+        details.into_values().collect()
+    }
+
+    fn get_responses(&self, url: &str) -> Vec<&str> {
         self.log
             .entries
             .iter()
             .filter_map(|entry| {
-                if entry.request.url.contains(HEARTRATE_URL) {
+                if entry.request.url.contains(url) {
                     Some(entry.response.content.text.as_ref()?.as_str())
                 } else {
                     None
@@ -67,3 +162,52 @@ struct GarminConnectResponse {
 struct GarminConnectContent {
     text: Option<StackString>,
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::garmin_connect_har_file::GarminConnectHarFile;
+
+    #[test]
+    fn test_get_activity_details() -> Result<(), Error> {
+        let har = serde_json::json!({"log": {"entries": [
+            {
+                "request": {"url": "https://connect.garmin.com/activity-service/activity/101/weather"},
+                "response": {"content": {"text": r#"{"temp": 68, "weatherTypeDTO": {"desc": "Sunny"}}"#}},
+            },
+            {
+                "request": {"url": "https://connect.garmin.com/activity-service/activity/101/splits?_=1"},
+                "response": {"content": {"text": r#"{"lapDTOs": [{"lapIndex": 1, "startTimeGMT": "2024-05-04T13:00:00.0", "distance": 1000.0, "duration": 300.0}]}"#}},
+            },
+            {
+                "request": {"url": "https://connect.garmin.com/activity-service/activity/101/hrTimeInZones"},
+                "response": {"content": {"text": "[]"}},
+            },
+            {
+                "request": {"url": "https://connect.garmin.com/gear-service/gear/filterGear?activityId=102"},
+                "response": {"content": {"text": r#"[{"displayName": "Bike"}]"#}},
+            },
+            {
+                "request": {"url": "https://connect.garmin.com/activity-service/activity/activityTypes"},
+                "response": {"content": {"text": "[]"}},
+            },
+            {
+                "request": {"url": "https://connect.garmin.com/activity-service/activity/102/weather"},
+                "response": {"content": {"text": "eyJ0ZW1wIjogNjh9"}},
+            },
+        ]}});
+        let har: GarminConnectHarFile = serde_json::from_value(har)?;
+        let details = har.get_activity_details();
+        assert_eq!(details.len(), 2);
+        let (detail, splits) = &details[0];
+        assert_eq!(detail.activity_id, 101);
+        assert_eq!(detail.weather.as_deref(), Some("Sunny"));
+        assert_eq!(detail.temperature, Some(20.0));
+        assert_eq!(splits.len(), 1);
+        assert_eq!(details[1].0.gear.as_deref(), Some("Bike"));
+        assert_eq!(details[1].0.weather, None);
+        assert!(details[1].1.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{format_err, Error};
+use log::warn;
+use serde::Deserialize;
+use stack_string::StackString;
+use time::{macros::format_description, Date, OffsetDateTime, PrimitiveDateTime};
+use time_tz::PrimitiveDateTimeExt;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+use crate::{
+    biomarker::{BiomarkerMeasurement, BiomarkerSeries},
+    provenance::Provenance,
+};
+
+/// Biomarker series names used for values imported from Garmin Connect
+pub const SPO2_SERIES: &str = "spo2";
+pub const SYSTOLIC_SERIES: &str = "systolic";
+pub const DIASTOLIC_SERIES: &str = "diastolic";
+pub const SLEEP_RESPIRATION_SERIES: &str = "sleep_respiration";
+
+/// Response of `wellness-service/wellness/daily/spo2/{date}`
+#[derive(Deserialize, Debug)]
+pub struct GarminConnectSpO2 {
+    #[serde(rename = "calendarDate")]
+    pub calendar_date: Date,
+    #[serde(rename = "averageSpO2")]
+    pub average_spo2: Option<f64>,
+    #[serde(rename = "lowestSpO2")]
+    pub lowest_spo2: Option<f64>,
+    #[serde(rename = "spO2HourlyAverages")]
+    pub hourly_averages: Option<Vec<(i64, Option<f64>)>>,
+}
+
+impl GarminConnectSpO2 {
+    /// Hourly averages as `spo2` measurements
+    #[must_use]
+    pub fn to_measurements(&self) -> Vec<BiomarkerMeasurement> {
+        let mut measurements: Vec<_> = self
+            .hourly_averages
+            .iter()
+            .flatten()
+            .filter_map(|(timestamp_ms, value)| {
+                let value = (*value)?;
+                let datetime = OffsetDateTime::from_unix_timestamp_nanos(
+                    i128::from(*timestamp_ms) * 1_000_000,
+                )
+                .ok()?;
+                Some(BiomarkerMeasurement {
+                    series: SPO2_SERIES.into(),
+                    datetime: datetime.into(),
+                    value,
+                })
+            })
+            .collect();
+        measurements.shrink_to_fit();
+        measurements
+    }
+}
+
+/// Response of `bloodpressure-service/bloodpressure/range/{start}/{end}`
+#[derive(Deserialize, Debug)]
+pub struct GarminConnectBloodPressure {
+    #[serde(rename = "measurementSummaries", default)]
+    pub summaries: Vec<GarminConnectBloodPressureSummary>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GarminConnectBloodPressureSummary {
+    #[serde(default)]
+    pub measurements: Vec<GarminConnectBloodPressureEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GarminConnectBloodPressureEntry {
+    pub systolic: f64,
+    pub diastolic: f64,
+    pub pulse: Option<f64>,
+    #[serde(rename = "measurementTimestampGMT")]
+    pub timestamp_gmt: StackString,
+}
+
+impl GarminConnectBloodPressure {
+    /// Each reading becomes a `systolic` and a `diastolic` measurement,
+    /// readings with an invalid timestamp are skipped
+    #[must_use]
+    pub fn to_measurements(&self) -> Vec<BiomarkerMeasurement> {
+        let mut measurements = Vec::new();
+        for entry in self.summaries.iter().flat_map(|s| s.measurements.iter()) {
+            let datetime = match parse_connect_gmt(&entry.timestamp_gmt) {
+                Ok(datetime) => datetime,
+                Err(e) => {
+                    warn!("skipping blood pressure reading: {e}");
+                    continue;
+                }
+            };
+            measurements.push(BiomarkerMeasurement {
+                series: SYSTOLIC_SERIES.into(),
+                datetime: datetime.into(),
+                value: entry.systolic,
+            });
+            measurements.push(BiomarkerMeasurement {
+                series: DIASTOLIC_SERIES.into(),
+                datetime: datetime.into(),
+                value: entry.diastolic,
+            });
+        }
+        measurements.shrink_to_fit();
+        measurements
+    }
+}
+
+/// Response of `wellness-service/wellness/daily/respiration/{date}`
+#[derive(Deserialize, Debug)]
+pub struct GarminConnectRespiration {
+    #[serde(rename = "calendarDate")]
+    pub calendar_date: Date,
+    #[serde(rename = "avgSleepRespirationValue")]
+    pub avg_sleep_respiration: Option<f64>,
+    #[serde(rename = "lowestRespirationValue")]
+    pub lowest_respiration: Option<f64>,
+    #[serde(rename = "highestRespirationValue")]
+    pub highest_respiration: Option<f64>,
+}
+
+impl GarminConnectRespiration {
+    /// Nightly average as a `sleep_respiration` measurement at local midnight
+    /// of the calendar date
+    /// # Errors
+    /// Return error if the calendar date can't be converted to local time
+    pub fn to_measurement(&self) -> Result<Option<BiomarkerMeasurement>, Error> {
+        let value = match self.avg_sleep_respiration {
+            Some(v) if v > 0.0 => v,
+            _ => return Ok(None),
+        };
+        let datetime = self
+            .calendar_date
+            .midnight()
+            .assume_timezone(DateTimeWrapper::local_tz())
+            .take_first()
+            .ok_or_else(|| format_err!("Invalid local date {}", self.calendar_date))?;
+        Ok(Some(BiomarkerMeasurement {
+            series: SLEEP_RESPIRATION_SERIES.into(),
+            datetime: datetime.into(),
+            value,
+        }))
+    }
+}
+
+/// Connect reports GMT timestamps without an offset, e.g.
+/// `2024-03-01T12:34:56.0`
+fn parse_connect_gmt(s: &str) -> Result<OffsetDateTime, Error> {
+    let s = s.split('.').next().unwrap_or(s);
+    PrimitiveDateTime::parse(
+        s,
+        format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]"),
+    )
+    .map(PrimitiveDateTime::assume_utc)
+    .map_err(|e| format_err!("Invalid timestamp {s}: {e}"))
+}
+
+/// Store connect measurements, creating the spo2 / blood pressure /
+/// respiration series if needed, returns the number of values stored
+/// # Errors
+/// Return error if db queries fail
+pub async fn merge_connect_measurements(
+    pool: &PgPool,
+    measurements: &[BiomarkerMeasurement],
+    provenance: &Provenance,
+) -> Result<usize, Error> {
+    for (name, units) in [
+        (SPO2_SERIES, "%"),
+        (SYSTOLIC_SERIES, "mmHg"),
+        (DIASTOLIC_SERIES, "mmHg"),
+        (SLEEP_RESPIRATION_SERIES, "brpm"),
+    ] {
+        if measurements.iter().any(|m| m.series == name)
+            && BiomarkerSeries::get_by_name(pool, name).await?.is_none()
+        {
+            BiomarkerSeries::new(name, units).upsert_db(pool).await?;
+        }
+    }
+    BiomarkerMeasurement::merge_updates(measurements, pool, provenance).await
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::{date, datetime};
+
+    use crate::garmin_connect_wellness::{
+        GarminConnectBloodPressure, GarminConnectRespiration, GarminConnectSpO2, DIASTOLIC_SERIES,
+        SLEEP_RESPIRATION_SERIES, SPO2_SERIES, SYSTOLIC_SERIES,
+    };
+
+    #[test]
+    fn test_connect_spo2() -> Result<(), Error> {
+        let buf = r#"{
+            "userProfilePK": 1234,
+            "calendarDate": "2024-03-01",
+            "averageSpO2": 95.0,
+            "lowestSpO2": 88,
+            "spO2HourlyAverages": [[1709251200000, 96], [1709254800000, null], [1709258400000, 94]]
+        }"#;
+        let spo2: GarminConnectSpO2 = serde_json::from_str(buf)?;
+        assert_eq!(spo2.calendar_date, date!(2024 - 03 - 01));
+        assert_eq!(spo2.average_spo2, Some(95.0));
+        let measurements = spo2.to_measurements();
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(&measurements[0].series, SPO2_SERIES);
+        assert_eq!(
+            measurements[0].datetime.to_offsetdatetime(),
+            datetime!(2024-03-01 00:00:00 +00:00)
+        );
+        assert_eq!(measurements[1].value, 94.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_connect_blood_pressure() -> Result<(), Error> {
+        let buf = r#"{
+            "from": "2024-03-01",
+            "until": "2024-03-07",
+            "measurementSummaries": [{
+                "startDate": "2024-03-02",
+                "measurements": [{
+                    "systolic": 121,
+                    "diastolic": 79,
+                    "pulse": 58,
+                    "sourceType": "MANUAL",
+                    "measurementTimestampGMT": "2024-03-02T13:15:00.0",
+                    "measurementTimestampLocal": "2024-03-02T08:15:00.0"
+                }, {
+                    "systolic": 180,
+                    "diastolic": 95,
+                    "measurementTimestampGMT": "not a timestamp"
+                }]
+            }]
+        }"#;
+        let bp: GarminConnectBloodPressure = serde_json::from_str(buf)?;
+        // the reading with a broken timestamp is dropped
+        let measurements = bp.to_measurements();
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(&measurements[0].series, SYSTOLIC_SERIES);
+        assert_eq!(measurements[0].value, 121.0);
+        assert_eq!(&measurements[1].series, DIASTOLIC_SERIES);
+        assert_eq!(
+            measurements[1].datetime.to_offsetdatetime(),
+            datetime!(2024-03-02 13:15:00 +00:00)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_connect_respiration() -> Result<(), Error> {
+        let buf = r#"{
+            "userProfilePK": 1234,
+            "calendarDate": "2024-03-01",
+            "lowestRespirationValue": 11.0,
+            "highestRespirationValue": 19.0,
+            "avgWakingRespirationValue": 15.0,
+            "avgSleepRespirationValue": 13.5
+        }"#;
+        let resp: GarminConnectRespiration = serde_json::from_str(buf)?;
+        assert_eq!(resp.calendar_date, date!(2024 - 03 - 01));
+        let measurement = resp.to_measurement()?.expect("missing measurement");
+        assert_eq!(&measurement.series, SLEEP_RESPIRATION_SERIES);
+        assert_eq!(measurement.value, 13.5);
+
+        let buf = r#"{"calendarDate": "2024-03-02", "avgSleepRespirationValue": null}"#;
+        let resp: GarminConnectRespiration = serde_json::from_str(buf)?;
+        assert!(resp.to_measurement()?.is_none());
+        Ok(())
+    }
+}
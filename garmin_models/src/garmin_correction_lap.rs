@@ -7,6 +7,7 @@ use log::debug;
 use postgres_query::{query, Error as PqError, FromSqlRow};
 use stack_string::StackString;
 use std::{collections::HashMap, fs, hash::BuildHasher, path::Path, str};
+use tracing::instrument;
 use uuid::Uuid;
 
 use garmin_lib::date_time_wrapper::{iso8601::convert_str_to_datetime, DateTimeWrapper};
@@ -24,6 +25,11 @@ pub struct GarminCorrectionLap {
     pub distance: Option<f64>,
     pub duration: Option<f64>,
     pub summary_id: Option<Uuid>,
+    /// Actual length of the pool in meters
+    pub pool_length: Option<f64>,
+    /// Pool length in meters configured on the watch when the swim was
+    /// recorded
+    pub recorded_pool_length: Option<f64>,
 }
 
 pub type GarminCorrectionMap = HashMap<(DateTimeWrapper, i32), GarminCorrectionLap>;
@@ -45,6 +51,8 @@ impl GarminCorrectionLap {
             distance: None,
             duration: None,
             summary_id: None,
+            pool_length: None,
+            recorded_pool_length: None,
         }
     }
 
@@ -84,6 +92,33 @@ impl GarminCorrectionLap {
         self
     }
 
+    /// Swim recorded with the watch set to `recorded_pool_length` meters in a
+    /// pool of `pool_length` meters
+    #[must_use]
+    pub fn with_pool_length(mut self, pool_length: f64, recorded_pool_length: f64) -> Self {
+        self.pool_length = Some(pool_length);
+        self.recorded_pool_length = Some(recorded_pool_length);
+        self
+    }
+
+    #[must_use]
+    pub fn without_pool_length(mut self) -> Self {
+        self.pool_length = None;
+        self.recorded_pool_length = None;
+        self
+    }
+
+    /// Factor every lap distance is multiplied by to correct the pool length
+    #[must_use]
+    pub fn pool_length_scale(&self) -> Option<f64> {
+        match (self.pool_length, self.recorded_pool_length) {
+            (Some(actual), Some(recorded)) if actual > 0.0 && recorded > 0.0 => {
+                Some(actual / recorded)
+            }
+            _ => None,
+        }
+    }
+
     pub fn map_from_vec<T: IntoIterator<Item = Self>>(corr_list: T) -> GarminCorrectionMap {
         let mut h: HashMap<_, _> = corr_list
             .into_iter()
@@ -255,6 +290,7 @@ impl GarminCorrectionLap {
 
     /// # Errors
     /// Return error if db query fails
+    #[instrument(skip_all, fields(db.system = "postgresql"))]
     pub async fn dump_corrections_to_db(
         corr_list_map: &GarminCorrectionMap,
         pool: &PgPool,
@@ -266,13 +302,14 @@ impl GarminCorrectionLap {
         ";
         let query_insert = "
             INSERT INTO garmin_corrections_laps
-            (start_time, lap_number, distance, duration, sport)
+            (start_time, lap_number, distance, duration, sport, pool_length,
+             recorded_pool_length)
             VALUES
-            ($1, $2, $3, $4, $5)
+            ($1, $2, $3, $4, $5, $6, $7)
         ";
         let query_update = "
             UPDATE garmin_corrections_laps
-            SET distance=$3,duration=$4,sport=$5
+            SET distance=$3,duration=$4,sport=$5,pool_length=$6,recorded_pool_length=$7
             WHERE start_time=$1 AND lap_number=$2
         ";
         let conn = pool.get().await?;
@@ -297,6 +334,8 @@ impl GarminCorrectionLap {
                         &corr.distance,
                         &corr.duration,
                         &sport,
+                        &corr.pool_length,
+                        &corr.recorded_pool_length,
                     ],
                 )
                 .await?;
@@ -309,6 +348,8 @@ impl GarminCorrectionLap {
                         &corr.distance,
                         &corr.duration,
                         &sport,
+                        &corr.pool_length,
+                        &corr.recorded_pool_length,
                     ],
                 )
                 .await?;
@@ -319,6 +360,7 @@ impl GarminCorrectionLap {
 
     /// # Errors
     /// Return error if db query fails
+    #[instrument(skip_all, fields(db.system = "postgresql"))]
     pub async fn read_corrections_from_db(pool: &PgPool) -> Result<GarminCorrectionMap, Error> {
         Self::_read_corrections_from_db(pool)
             .await?
@@ -333,7 +375,8 @@ impl GarminCorrectionLap {
     ) -> Result<impl Stream<Item = Result<GarminCorrectionLap, PqError>>, Error> {
         let query = query!(
             r#"
-                SELECT id, start_time, lap_number, sport, distance, duration, summary_id
+                SELECT id, start_time, lap_number, sport, distance, duration, summary_id,
+                       pool_length, recorded_pool_length
                 FROM garmin_corrections_laps
             "#
         );
@@ -343,6 +386,7 @@ impl GarminCorrectionLap {
 
     /// # Errors
     /// Return error if db query fails
+    #[instrument(skip_all, fields(db.system = "postgresql"))]
     pub async fn fix_corrections_in_db(pool: &PgPool) -> Result<(), Error> {
         let query = query!(
             "
@@ -357,6 +401,10 @@ impl GarminCorrectionLap {
     }
 }
 
+/// Apply the corrections of each lap, keyed by the start of the first lap
+/// and the lap number.  A pool length correction is stored on lap 0 and
+/// rescales the distance of every lap of a swim without its own distance
+/// correction.
 pub fn apply_lap_corrections<S: BuildHasher + Sync>(
     lap_list: &[GarminLap],
     sport: SportTypes,
@@ -411,6 +459,25 @@ pub fn apply_lap_corrections<S: BuildHasher + Sync>(
                     }
                 })
                 .collect();
+            let pool_length_scale = corr_map
+                .get(&(lap_start, 0))
+                .and_then(GarminCorrectionLap::pool_length_scale);
+            if let Some(scale) = pool_length_scale {
+                if new_sport == SportTypes::Swimming {
+                    for lap in &mut new_lap_list {
+                        let has_distance = corr_map
+                            .get(&(lap_start, lap.lap_number))
+                            .is_some_and(|corr| corr.distance.is_some());
+                        if !has_distance {
+                            debug!(
+                                "change pool length {} {} {}",
+                                lap_start, lap.lap_number, scale
+                            );
+                            lap.lap_distance *= scale;
+                        }
+                    }
+                }
+            }
             new_lap_list.shrink_to_fit();
             for lap in &new_lap_list {
                 debug!("lap {} dis {}", lap.lap_number, lap.lap_distance);
@@ -429,9 +496,12 @@ mod tests {
 
     use garmin_lib::date_time_wrapper::iso8601::convert_str_to_datetime;
 
-    use garmin_utils::sport_types::SportTypes;
+    use garmin_utils::{garmin_util::METERS_PER_MILE, sport_types::SportTypes};
 
-    use crate::garmin_correction_lap::GarminCorrectionLap;
+    use crate::{
+        garmin_correction_lap::{apply_lap_corrections, GarminCorrectionLap},
+        garmin_lap::GarminLap,
+    };
 
     #[test]
     fn test_garmin_correction_lap_new() {
@@ -457,6 +527,45 @@ mod tests {
         assert_eq!(gc.duration, Some(6.2));
     }
 
+    #[test]
+    fn test_apply_pool_length_correction() -> Result<(), Error> {
+        let lap_start = convert_str_to_datetime("2024-05-04T12:00:00Z")?.into();
+        let lap_list: Vec<_> = (0..3)
+            .map(|lap_number| GarminLap {
+                lap_start,
+                lap_number,
+                lap_distance: 500.0,
+                lap_duration: 600.0,
+                ..GarminLap::default()
+            })
+            .collect();
+        // swum in a 25 yard pool with the watch set to 25 meters
+        let corr_map = GarminCorrectionLap::map_from_vec(vec![
+            GarminCorrectionLap::new()
+                .with_start_time(lap_start)
+                .with_lap_number(0)
+                .with_pool_length(22.86, 25.0),
+            GarminCorrectionLap::new()
+                .with_start_time(lap_start)
+                .with_lap_number(2)
+                .with_distance(0.25),
+        ]);
+        assert!((corr_map[&(lap_start, 0)].pool_length_scale().unwrap() - 0.9144).abs() < 1e-9);
+        assert_eq!(corr_map[&(lap_start, 2)].pool_length_scale(), None);
+
+        let (laps, sport) = apply_lap_corrections(&lap_list, SportTypes::Swimming, &corr_map);
+        assert_eq!(sport, SportTypes::Swimming);
+        assert!((laps[0].lap_distance - 457.2).abs() < 1e-9);
+        assert!((laps[1].lap_distance - 457.2).abs() < 1e-9);
+        assert!((laps[2].lap_distance - 0.25 * METERS_PER_MILE).abs() < 1e-9);
+        assert!((laps[0].pace_per_100m().unwrap() - 600.0 / 4.572).abs() < 1e-9);
+
+        // only swims are rescaled
+        let (laps, _) = apply_lap_corrections(&lap_list, SportTypes::Running, &corr_map);
+        assert!((laps[0].lap_distance - 500.0).abs() < 1e-9);
+        Ok(())
+    }
+
     #[test]
     fn test_corr_list_from_json() -> Result<(), Error> {
         let mut corr_list: Vec<_> =
@@ -3,7 +3,11 @@ use apache_avro::{from_value, Codec, Reader, Schema, Writer};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use stack_string::{format_sstr, StackString};
-use std::{collections::HashMap, fs::File, path::Path};
+use std::{
+    collections::HashMap,
+    fs::{rename, File},
+    path::{Path, PathBuf},
+};
 use time::macros::format_description;
 use tokio::task::spawn_blocking;
 
@@ -12,6 +16,7 @@ use garmin_lib::date_time_wrapper::DateTimeWrapper;
 use garmin_utils::sport_types::SportTypes;
 
 use crate::{
+    cache_store::CacheStore,
     garmin_lap::{GarminLap, GARMIN_LAP_AVRO_SCHEMA},
     garmin_point::{GarminPoint, GARMIN_POINT_AVRO_SCHEMA},
 };
@@ -96,17 +101,23 @@ impl GarminFile {
         )
     }
 
+    /// The avro is written to a temporary file next to `output_filename`
+    /// and renamed into place, so a crash never leaves a truncated cache file
     /// # Errors
     /// Return error if `parse_str` fails, or opening file fails, or writing
     /// codec fails
     pub fn dump_avro(&self, output_filename: &Path) -> Result<(), Error> {
         let schema = Schema::parse_str(&GARMIN_FILE_AVRO_SCHEMA)?;
 
-        let output_file = File::create(output_filename)?;
+        let mut temp_filename = output_filename.as_os_str().to_owned();
+        temp_filename.push(".tmp");
+        let temp_filename = PathBuf::from(temp_filename);
+        let output_file = File::create(&temp_filename)?;
 
         let mut writer = Writer::with_codec(&schema, output_file, Codec::Snappy);
         writer.append_ser(self)?;
-        writer.flush()?;
+        writer.into_inner()?.sync_all()?;
+        rename(&temp_filename, output_filename)?;
         Ok(())
     }
 
@@ -117,6 +128,26 @@ impl GarminFile {
         spawn_blocking(move || Self::read_avro(&input_filename)).await?
     }
 
+    /// Read the avro of `filename` from `store`, downloading it first when
+    /// the cache is kept in s3, and again if another task evicts it before
+    /// it's read
+    /// # Errors
+    /// Return error if the file isn't cached or `read_avro` fails
+    pub async fn read_cached_avro(store: &CacheStore, filename: &str) -> Result<Self, Error> {
+        let key = format_sstr!("{filename}.avro");
+        for _ in 0..2 {
+            let path = store
+                .fetch(&key)
+                .await?
+                .ok_or_else(|| format_err!("{key} is not cached"))?;
+            match Self::read_avro_async(&path).await {
+                Err(_) if !path.exists() => {}
+                result => return result,
+            }
+        }
+        Err(format_err!("{key} was evicted before it could be read"))
+    }
+
     /// # Errors
     /// Return error if open file fails, or reader fails
     pub fn read_avro(input_filename: &Path) -> Result<Self, Error> {
@@ -145,6 +176,22 @@ impl GarminFile {
                 .unwrap_or_else(|_| String::new())
         )
     }
+
+    /// Mean of the recorded point temperatures in degrees Celsius, `None` if
+    /// the device didn't log temperature
+    #[must_use]
+    pub fn avg_temperature(&self) -> Option<f64> {
+        let (sum, count) = self
+            .points
+            .iter()
+            .filter_map(|p| p.temperature)
+            .fold((0.0, 0), |(sum, count), t| (sum + t, count + 1));
+        if count > 0 {
+            Some(sum / f64::from(count))
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
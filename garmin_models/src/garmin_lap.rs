@@ -4,6 +4,7 @@ use roxmltree::{Node, NodeType};
 use serde::{Deserialize, Serialize};
 use stack_string::{format_sstr, StackString};
 use std::fmt;
+use time::OffsetDateTime;
 use time_tz::{timezones::db::UTC, OffsetDateTimeExt};
 
 use garmin_lib::date_time_wrapper::{iso8601::convert_datetime_to_str, DateTimeWrapper};
@@ -12,6 +13,12 @@ use garmin_utils::{
     sport_types::SportTypes,
 };
 
+use crate::{
+    activity_distribution::{cadence_per_minute, DistributionMetric, MAX_SAMPLE_GAP},
+    garmin_point::GarminPoint,
+    power_curve::{average_power, cumulative_power, normalized_power},
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct GarminLap {
     pub lap_type: Option<StackString>,
@@ -27,6 +34,17 @@ pub struct GarminLap {
     pub lap_intensity: Option<StackString>,
     pub lap_number: i32,
     pub lap_start_string: Option<StackString>,
+    /// Average watts, stops included
+    #[serde(default)]
+    pub lap_avg_power: Option<f64>,
+    /// See `power_curve::normalized_power`
+    #[serde(default)]
+    pub lap_normalized_power: Option<f64>,
+    /// Average cadence while moving, in the units of `GarminPoint::cadence`
+    #[serde(default)]
+    pub lap_avg_cadence: Option<f64>,
+    #[serde(default)]
+    pub lap_max_cadence: Option<f64>,
 }
 
 impl Default for GarminLap {
@@ -52,6 +70,10 @@ impl GarminLap {
             lap_intensity: None,
             lap_number: -1,
             lap_start_string: None,
+            lap_avg_power: None,
+            lap_normalized_power: None,
+            lap_avg_cadence: None,
+            lap_max_cadence: None,
         }
     }
 
@@ -69,6 +91,10 @@ impl GarminLap {
         self.lap_intensity = None;
         self.lap_number = -1;
         self.lap_start_string = None;
+        self.lap_avg_power = None;
+        self.lap_normalized_power = None;
+        self.lap_avg_cadence = None;
+        self.lap_max_cadence = None;
     }
 
     /// # Errors
@@ -127,6 +153,7 @@ impl GarminLap {
                         new_lap.lap_calories = d.text().and_then(|x| x.parse().ok()).unwrap_or(0);
                     }
                     "Intensity" => new_lap.lap_intensity = d.text().map(Into::into),
+                    "Cadence" => new_lap.lap_avg_cadence = d.text().and_then(|x| x.parse().ok()),
                     "AverageHeartRateBpm" => {
                         for entry in d.descendants() {
                             if entry.node_type() == NodeType::Element
@@ -145,6 +172,19 @@ impl GarminLap {
                             }
                         }
                     }
+                    "Extensions" => {
+                        for entry in d.descendants().filter(Node::is_element) {
+                            let value = entry.text().and_then(|x| x.trim().parse().ok());
+                            match entry.tag_name().name() {
+                                "AvgWatts" => new_lap.lap_avg_power = value,
+                                "AvgRunCadence" => new_lap.lap_avg_cadence = value,
+                                "MaxRunCadence" | "MaxBikeCadence" => {
+                                    new_lap.lap_max_cadence = value;
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
                     _ => (),
                 }
             }
@@ -202,6 +242,22 @@ impl GarminLap {
                         new_lap.lap_max_hr = Some(i as i32);
                     }
                 }
+                "avg_power" => {
+                    new_lap.lap_avg_power = get_f64(field.value());
+                }
+                "normalized_power" => {
+                    new_lap.lap_normalized_power = get_f64(field.value());
+                }
+                "avg_cadence" | "avg_running_cadence" | "avg_fractional_cadence" => {
+                    if let Some(c) = get_f64(field.value()) {
+                        new_lap.lap_avg_cadence = Some(new_lap.lap_avg_cadence.unwrap_or(0.0) + c);
+                    }
+                }
+                "max_cadence" | "max_running_cadence" | "max_fractional_cadence" => {
+                    if let Some(c) = get_f64(field.value()) {
+                        new_lap.lap_max_cadence = Some(new_lap.lap_max_cadence.unwrap_or(0.0) + c);
+                    }
+                }
                 "sport" => {
                     if let Value::String(s) = field.value() {
                         if let Ok(sport) = s.parse() {
@@ -215,6 +271,102 @@ impl GarminLap {
         (new_lap, lap_sport)
     }
 
+    /// Seconds per 100 meters, the usual swim pace, `None` without any
+    /// distance
+    #[must_use]
+    pub fn pace_per_100m(&self) -> Option<f64> {
+        if self.lap_distance > 0.0 {
+            Some(self.lap_duration / (self.lap_distance / 100.0))
+        } else {
+            None
+        }
+    }
+
+    /// Average cadence in the units shown for `sport`, e.g. `172 spm`
+    #[must_use]
+    pub fn avg_cadence_string(&self, sport: SportTypes) -> Option<StackString> {
+        let cadence = cadence_per_minute(self.lap_avg_cadence?, sport);
+        let units = DistributionMetric::Cadence.units(sport);
+        Some(format_sstr!("{cadence:.0} {units}"))
+    }
+
+    /// Points of `points` recorded during the lap with the seconds elapsed
+    /// since its start
+    fn lap_points<'a>(
+        &self,
+        points: &'a [GarminPoint],
+    ) -> impl Iterator<Item = (f64, &'a GarminPoint)> {
+        let start: OffsetDateTime = self.lap_start.into();
+        let duration = self.lap_duration;
+        points.iter().filter_map(move |p| {
+            let elapsed = (p.time.to_offsetdatetime() - start).as_seconds_f64();
+            if elapsed < 0.0 || elapsed > duration {
+                None
+            } else {
+                Some((elapsed, p))
+            }
+        })
+    }
+
+    /// Fill in the power of laps whose file doesn't record it from the
+    /// power samples of `points` recorded during the lap
+    pub fn fill_power_from_points(lap_list: &mut [Self], points: &[GarminPoint]) {
+        for lap in lap_list {
+            if lap.lap_avg_power.is_some() && lap.lap_normalized_power.is_some() {
+                continue;
+            }
+            let samples: Vec<_> = lap
+                .lap_points(points)
+                .filter_map(|(elapsed, p)| p.power.map(|watts| (elapsed, watts)))
+                .collect();
+            if samples.is_empty() {
+                continue;
+            }
+            let cumulative = cumulative_power(&samples);
+            if lap.lap_avg_power.is_none() {
+                lap.lap_avg_power = average_power(&cumulative);
+            }
+            if lap.lap_normalized_power.is_none() {
+                lap.lap_normalized_power = normalized_power(&cumulative);
+            }
+        }
+    }
+
+    /// Fill in the cadence of laps whose file doesn't record it from the
+    /// cadence samples of `points` recorded during the lap, the average
+    /// leaves out stops and is weighted by the time of each sample
+    pub fn fill_cadence_from_points(lap_list: &mut [Self], points: &[GarminPoint]) {
+        for lap in lap_list {
+            if lap.lap_avg_cadence.is_some() && lap.lap_max_cadence.is_some() {
+                continue;
+            }
+            let samples: Vec<_> = lap
+                .lap_points(points)
+                .filter_map(|(_, p)| p.cadence.filter(|c| *c > 0.0).map(|c| (c, p)))
+                .collect();
+            if samples.is_empty() {
+                continue;
+            }
+            if lap.lap_avg_cadence.is_none() {
+                let (total, weight) = samples
+                    .iter()
+                    .map(|(c, p)| (c, p.duration_from_last))
+                    .filter(|(_, dt)| *dt > 0.0 && *dt <= MAX_SAMPLE_GAP)
+                    .fold((0.0, 0.0), |(total, weight), (c, dt)| {
+                        (total + c * dt, weight + dt)
+                    });
+                lap.lap_avg_cadence = if weight > 0.0 {
+                    Some(total / weight)
+                } else {
+                    Some(samples.iter().map(|(c, _)| c).sum::<f64>() / samples.len() as f64)
+                };
+            }
+            if lap.lap_max_cadence.is_none() {
+                lap.lap_max_cadence = samples.iter().map(|(c, _)| *c).reduce(f64::max);
+            }
+        }
+    }
+
     pub fn fix_lap_number(lap_list: &mut [Self]) {
         for (i, lap) in lap_list.iter_mut().enumerate() {
             lap.lap_index = i as i32;
@@ -243,6 +395,10 @@ impl fmt::Display for GarminLap {
         let lap_max_speed = self.lap_max_speed.unwrap_or(-1.0);
         let lap_avg_hr = self.lap_avg_hr.unwrap_or(-1.0);
         let lap_max_hr = self.lap_max_hr.unwrap_or(-1);
+        let lap_avg_power = self.lap_avg_power.unwrap_or(-1.0);
+        let lap_normalized_power = self.lap_normalized_power.unwrap_or(-1.0);
+        let lap_avg_cadence = self.lap_avg_cadence.unwrap_or(-1.0);
+        let lap_max_cadence = self.lap_max_cadence.unwrap_or(-1.0);
         let keys = vec![
             "lap_type",
             "lap_index",
@@ -257,6 +413,10 @@ impl fmt::Display for GarminLap {
             "lap_intensity",
             "lap_number",
             "lap_start_string",
+            "lap_avg_power",
+            "lap_normalized_power",
+            "lap_avg_cadence",
+            "lap_max_cadence",
         ];
         let vals: Vec<&dyn fmt::Display> = vec![
             &lap_type,
@@ -272,6 +432,10 @@ impl fmt::Display for GarminLap {
             &lap_intensity,
             &self.lap_number,
             &lap_start_string,
+            &lap_avg_power,
+            &lap_normalized_power,
+            &lap_avg_cadence,
+            &lap_max_cadence,
         ];
         write!(
             f,
@@ -303,7 +467,11 @@ pub const GARMIN_LAP_AVRO_SCHEMA: &str = r#"
             {"name": "lap_max_hr", "type": ["null", "int"]},
             {"name": "lap_intensity", "type": ["null", "string"]},
             {"name": "lap_number", "type": "int"},
-            {"name": "lap_start_string", "type": ["null", "string"]}
+            {"name": "lap_start_string", "type": ["null", "string"]},
+            {"name": "lap_avg_power", "type": ["null", "double"], "default": null},
+            {"name": "lap_normalized_power", "type": ["null", "double"], "default": null},
+            {"name": "lap_avg_cadence", "type": ["null", "double"], "default": null},
+            {"name": "lap_max_cadence", "type": ["null", "double"], "default": null}
         ]
     }
 "#;
@@ -13,6 +13,62 @@ use garmin_utils::garmin_util::{
     convert_xml_local_time_to_utc, get_degrees_from_semicircles, get_f64, METERS_PER_MILE,
 };
 
+/// Garmin's tcx `TPX` extension, also written by Polar and Suunto
+const ACTIVITY_EXTENSION_NS: &str = "http://www.garmin.com/xmlschemas/ActivityExtension/v2";
+const TRACKPOINT_EXTENSION_V1_NS: &str = "http://www.garmin.com/xmlschemas/TrackPointExtension/v1";
+const TRACKPOINT_EXTENSION_V2_NS: &str = "http://www.garmin.com/xmlschemas/TrackPointExtension/v2";
+const POWER_EXTENSION_NS: &str = "http://www.garmin.com/xmlschemas/PowerExtension/v1";
+/// Cluetrust gpxdata, used by Suunto (Movescount) and Polar exports
+const GPXDATA_NS: &str = "http://www.cluetrust.com/XML/GPXDATA/1/0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionField {
+    HeartRate,
+    Cadence,
+    Power,
+    Speed,
+    Temperature,
+}
+
+/// Meaning of the extension element `name` in `namespace`, elements without
+/// a namespace or from one we don't know are matched on common local names
+#[must_use]
+pub fn extension_field(namespace: Option<&str>, name: &str) -> Option<ExtensionField> {
+    match namespace {
+        Some(ACTIVITY_EXTENSION_NS) => match name {
+            "Watts" => Some(ExtensionField::Power),
+            "RunCadence" | "Cadence" => Some(ExtensionField::Cadence),
+            "Speed" => Some(ExtensionField::Speed),
+            _ => None,
+        },
+        Some(TRACKPOINT_EXTENSION_V1_NS | TRACKPOINT_EXTENSION_V2_NS) => match name {
+            "hr" => Some(ExtensionField::HeartRate),
+            "cad" => Some(ExtensionField::Cadence),
+            "speed" => Some(ExtensionField::Speed),
+            "atemp" => Some(ExtensionField::Temperature),
+            _ => None,
+        },
+        Some(POWER_EXTENSION_NS) => match name {
+            "PowerInWatts" => Some(ExtensionField::Power),
+            _ => None,
+        },
+        Some(GPXDATA_NS) => match name {
+            "hr" => Some(ExtensionField::HeartRate),
+            "cadence" => Some(ExtensionField::Cadence),
+            "temp" => Some(ExtensionField::Temperature),
+            _ => None,
+        },
+        _ => match name.to_lowercase().as_str() {
+            "hr" | "heartrate" | "heartratebpm" => Some(ExtensionField::HeartRate),
+            "cad" | "cadence" | "runcadence" => Some(ExtensionField::Cadence),
+            "power" | "watts" => Some(ExtensionField::Power),
+            "speed" => Some(ExtensionField::Speed),
+            "atemp" | "temperature" => Some(ExtensionField::Temperature),
+            _ => None,
+        },
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub struct GarminPoint {
     pub time: DateTimeWrapper,
@@ -28,6 +84,18 @@ pub struct GarminPoint {
     pub speed_mph: f64,
     pub avg_speed_value_permi: f64,
     pub avg_speed_value_mph: f64,
+    #[serde(default)]
+    pub power: Option<f64>,
+    /// Breaths per minute
+    #[serde(default)]
+    pub respiration_rate: Option<f64>,
+    /// Ambient temperature in degrees Celsius
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// Cadence as recorded by the device, revolutions per minute when
+    /// biking and strides (one per pair of steps) per minute when running
+    #[serde(default)]
+    pub cadence: Option<f64>,
 }
 
 impl Default for GarminPoint {
@@ -53,6 +121,10 @@ impl GarminPoint {
             speed_mph: 0.0,
             avg_speed_value_permi: 0.0,
             avg_speed_value_mph: 0.0,
+            power: None,
+            respiration_rate: None,
+            temperature: None,
+            cadence: None,
         }
     }
 
@@ -109,6 +181,7 @@ impl GarminPoint {
                         new_point.longitude = d.text().and_then(|x| x.parse().ok());
                     }
                     "DistanceMeters" => new_point.distance = d.text().and_then(|x| x.parse().ok()),
+                    "Cadence" => new_point.cadence = d.text().and_then(|x| x.parse().ok()),
                     "HeartRateBpm" => {
                         for entry in d.descendants() {
                             if entry.node_type() == NodeType::Element
@@ -118,22 +191,7 @@ impl GarminPoint {
                             }
                         }
                     }
-                    "Extensions" => {
-                        for entry in d.descendants() {
-                            if entry.node_type() == NodeType::Element
-                                && entry.tag_name().name() == "Speed"
-                            {
-                                new_point.speed_mps =
-                                    entry.text().and_then(|x| x.parse().ok()).unwrap_or(0.0);
-                                new_point.speed_mph =
-                                    new_point.speed_mps * 3600.0 / METERS_PER_MILE;
-                                if new_point.speed_mps > 0.0 {
-                                    new_point.speed_permi =
-                                        METERS_PER_MILE / new_point.speed_mps / 60.0;
-                                }
-                            }
-                        }
-                    }
+                    "Extensions" => new_point.read_extensions(&d),
                     _ => (),
                 }
             }
@@ -141,6 +199,74 @@ impl GarminPoint {
         Ok(new_point)
     }
 
+    /// Read a gpx `trkpt` (or `rtept`), distance is left for the caller to
+    /// fill in since gpx only records positions
+    /// # Errors
+    /// Return error if `convert_xml_local_time_to_utc` fails
+    pub fn read_point_gpx(entries: &Node) -> Result<Self, Error> {
+        let mut new_point = Self::new();
+        new_point.latitude = entries.attribute("lat").and_then(|x| x.parse().ok());
+        new_point.longitude = entries.attribute("lon").and_then(|x| x.parse().ok());
+        for d in entries.children().filter(Node::is_element) {
+            match d.tag_name().name() {
+                "time" => {
+                    new_point.time = convert_xml_local_time_to_utc(
+                        d.text()
+                            .ok_or_else(|| format_err!("Malformed time"))?
+                            .trim(),
+                    )?
+                    .into();
+                }
+                "ele" => new_point.altitude = d.text().and_then(|x| x.trim().parse().ok()),
+                // gpx 1.0 only
+                "speed" => {
+                    new_point.speed_mps =
+                        d.text().and_then(|x| x.trim().parse().ok()).unwrap_or(0.0);
+                    new_point.speed_mph = new_point.speed_mps * 3600.0 / METERS_PER_MILE;
+                    if new_point.speed_mps > 0.0 {
+                        new_point.speed_permi = METERS_PER_MILE / new_point.speed_mps / 60.0;
+                    }
+                }
+                "extensions" => new_point.read_extensions(&d),
+                _ => (),
+            }
+        }
+        Ok(new_point)
+    }
+
+    /// Pick heart rate, cadence, power, speed and temperature out of the
+    /// vendor extensions of a tcx `Trackpoint` or gpx `trkpt`, matching
+    /// elements by namespace since devices reuse the same local names
+    pub fn read_extensions(&mut self, extensions: &Node) {
+        for entry in extensions.descendants().filter(Node::is_element) {
+            let field = match extension_field(entry.tag_name().namespace(), entry.tag_name().name())
+            {
+                Some(field) => field,
+                None => continue,
+            };
+            let value: Option<f64> = entry
+                .descendants()
+                .filter(Node::is_text)
+                .find_map(|t| t.text().and_then(|x| x.trim().parse().ok()));
+            if value.is_none() {
+                continue;
+            }
+            match field {
+                ExtensionField::HeartRate => self.heart_rate = value,
+                ExtensionField::Cadence => self.cadence = value,
+                ExtensionField::Power => self.power = value,
+                ExtensionField::Temperature => self.temperature = value,
+                ExtensionField::Speed => {
+                    self.speed_mps = value.unwrap_or(0.0);
+                    self.speed_mph = self.speed_mps * 3600.0 / METERS_PER_MILE;
+                    if self.speed_mps > 0.0 {
+                        self.speed_permi = METERS_PER_MILE / self.speed_mps / 60.0;
+                    }
+                }
+            }
+        }
+    }
+
     #[must_use]
     pub fn read_point_fit(fields: &[FitDataField]) -> Self {
         let mut new_point = Self::new();
@@ -166,6 +292,30 @@ impl GarminPoint {
                 "heart_rate" => {
                     new_point.heart_rate = get_f64(field.value());
                 }
+                "power" => {
+                    new_point.power = get_f64(field.value());
+                }
+                "enhanced_respiration_rate" => {
+                    new_point.respiration_rate = get_f64(field.value());
+                }
+                "respiration_rate" => {
+                    if new_point.respiration_rate.is_none() {
+                        new_point.respiration_rate = get_f64(field.value());
+                    }
+                }
+                "temperature" => {
+                    new_point.temperature = get_f64(field.value());
+                }
+                "cadence" => {
+                    if let Some(c) = get_f64(field.value()) {
+                        new_point.cadence = Some(new_point.cadence.unwrap_or(0.0) + c);
+                    }
+                }
+                "fractional_cadence" => {
+                    if let Some(c) = get_f64(field.value()) {
+                        new_point.cadence = Some(new_point.cadence.unwrap_or(0.0) + c);
+                    }
+                }
                 "enhanced_speed" => {
                     if let Some(f) = get_f64(field.value()) {
                         new_point.speed_mps = f;
@@ -181,6 +331,24 @@ impl GarminPoint {
         new_point
     }
 
+    /// Whether the point has a GPS fix past the start of the activity
+    #[must_use]
+    pub fn has_position(&self) -> bool {
+        self.latitude.is_some() && self.longitude.is_some() && self.distance > Some(0.0)
+    }
+
+    /// Drop points without usable data. When the file has a GPS fix only
+    /// positioned points are kept, so points recorded before the fix don't
+    /// change outdoor activities. Indoor rides and treadmill runs never get a
+    /// fix, their points with power or cadence are kept instead
+    pub fn retain_recorded(point_list: &mut Vec<Self>) {
+        if point_list.iter().any(Self::has_position) {
+            point_list.retain(Self::has_position);
+        } else {
+            point_list.retain(|p| p.power.is_some() || p.cadence.is_some());
+        }
+    }
+
     pub fn calculate_durations(point_list: &mut [Self]) {
         let mut time_from_begin = 0.0;
         let mut last_time = None;
@@ -257,7 +425,126 @@ pub const GARMIN_POINT_AVRO_SCHEMA: &str = r#"
             {"name": "speed_permi", "type": "double"},
             {"name": "speed_mph", "type": "double"},
             {"name": "avg_speed_value_permi", "type": "double"},
-            {"name": "avg_speed_value_mph", "type": "double"}
+            {"name": "avg_speed_value_mph", "type": "double"},
+            {"name": "power", "type": ["null", "double"], "default": null},
+            {"name": "respiration_rate", "type": ["null", "double"], "default": null},
+            {"name": "temperature", "type": ["null", "double"], "default": null},
+            {"name": "cadence", "type": ["null", "double"], "default": null}
         ]
     }
 "#;
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use roxmltree::Document;
+
+    use crate::garmin_point::{extension_field, ExtensionField, GarminPoint};
+
+    #[test]
+    fn test_extension_field() {
+        let trackpoint = Some("http://www.garmin.com/xmlschemas/TrackPointExtension/v1");
+        let gpxdata = Some("http://www.cluetrust.com/XML/GPXDATA/1/0");
+        assert_eq!(
+            extension_field(trackpoint, "hr"),
+            Some(ExtensionField::HeartRate)
+        );
+        assert_eq!(
+            extension_field(gpxdata, "cadence"),
+            Some(ExtensionField::Cadence)
+        );
+        assert_eq!(extension_field(gpxdata, "cad"), None);
+        assert_eq!(extension_field(None, "Watts"), Some(ExtensionField::Power));
+        assert_eq!(
+            extension_field(Some("http://example.com/vendor"), "HeartRate"),
+            Some(ExtensionField::HeartRate)
+        );
+    }
+
+    #[test]
+    fn test_read_point_gpx_extensions() -> Result<(), Error> {
+        let xml = r#"<gpx xmlns="http://www.topografix.com/GPX/1/1"
+            xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1"
+            xmlns:gpxdata="http://www.cluetrust.com/XML/GPXDATA/1/0">
+            <trkpt lat="40.5" lon="-73.5">
+                <ele>12.5</ele>
+                <time>2023-05-01T10:00:00Z</time>
+                <extensions>
+                    <gpxtpx:TrackPointExtension>
+                        <gpxtpx:hr>142</gpxtpx:hr>
+                        <gpxtpx:atemp>21.0</gpxtpx:atemp>
+                    </gpxtpx:TrackPointExtension>
+                    <gpxdata:cadence>88</gpxdata:cadence>
+                </extensions>
+            </trkpt>
+        </gpx>"#;
+        let doc = Document::parse(xml)?;
+        let node = doc
+            .descendants()
+            .find(|n| n.tag_name().name() == "trkpt")
+            .unwrap();
+        let point = GarminPoint::read_point_gpx(&node)?;
+        assert_eq!(point.latitude, Some(40.5));
+        assert_eq!(point.longitude, Some(-73.5));
+        assert_eq!(point.altitude, Some(12.5));
+        assert_eq!(point.heart_rate, Some(142.0));
+        assert_eq!(point.temperature, Some(21.0));
+        assert_eq!(point.cadence, Some(88.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_point_tcx_extensions() -> Result<(), Error> {
+        let xml = r#"<TrainingCenterDatabase
+            xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2"
+            xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2">
+            <Trackpoint>
+                <Time>2023-05-01T10:00:00Z</Time>
+                <Extensions>
+                    <ns3:TPX>
+                        <ns3:Speed>2.5</ns3:Speed>
+                        <ns3:RunCadence>84</ns3:RunCadence>
+                    </ns3:TPX>
+                    <HeartRate xmlns="http://www.suunto.com/xmlschemas/Extensions">
+                        <Value>150</Value>
+                    </HeartRate>
+                </Extensions>
+            </Trackpoint>
+        </TrainingCenterDatabase>"#;
+        let doc = Document::parse(xml)?;
+        let node = doc
+            .descendants()
+            .find(|n| n.tag_name().name() == "Trackpoint")
+            .unwrap();
+        let point = GarminPoint::read_point_tcx(&node)?;
+        assert_eq!(point.speed_mps, 2.5);
+        assert_eq!(point.cadence, Some(84.0));
+        assert_eq!(point.heart_rate, Some(150.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_retain_recorded() {
+        let positioned = GarminPoint {
+            latitude: Some(40.5),
+            longitude: Some(-73.5),
+            distance: Some(10.0),
+            power: Some(200.0),
+            ..GarminPoint::default()
+        };
+        let before_fix = GarminPoint {
+            power: Some(180.0),
+            cadence: Some(85.0),
+            ..GarminPoint::default()
+        };
+        let empty = GarminPoint::default();
+
+        let mut outdoor = vec![before_fix, positioned, empty];
+        GarminPoint::retain_recorded(&mut outdoor);
+        assert_eq!(outdoor, vec![positioned]);
+
+        let mut indoor = vec![before_fix, empty];
+        GarminPoint::retain_recorded(&mut indoor);
+        assert_eq!(indoor, vec![before_fix]);
+    }
+}
@@ -1,21 +1,25 @@
 use anyhow::Error;
-use futures::{future::try_join_all, Stream, TryStreamExt};
+use futures::{Stream, TryStreamExt};
 use itertools::Itertools;
-use log::debug;
+use log::{debug, error};
 use postgres_query::{query, query_dyn, Error as PqError, FromSqlRow};
 use serde::{Deserialize, Serialize};
 use stack_string::{format_sstr, StackString};
-use std::{fmt, sync::Arc};
+use std::fmt;
 use time::OffsetDateTime;
+use tracing::instrument;
 use uuid::Uuid;
 
 use garmin_lib::date_time_wrapper::{iso8601::convert_datetime_to_str, DateTimeWrapper};
 
-use garmin_utils::{garmin_util::generate_random_string, sport_types::SportTypes};
+use garmin_utils::sport_types::SportTypes;
 
 use garmin_utils::pgpool::PgPool;
 
-use crate::garmin_file::GarminFile;
+use crate::{
+    course_difficulty::course_difficulty, garmin_file::GarminFile, power_curve::activity_power,
+    split_differential::split_differential, stat_source::APPLY_STAT_CHOICES,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromSqlRow, PartialEq)]
 pub struct GarminSummary {
@@ -29,11 +33,57 @@ pub struct GarminSummary {
     pub total_hr_dur: f64,
     pub total_hr_dis: f64,
     pub md5sum: StackString,
+    /// Relative effort of the course, see `course_difficulty`
+    #[serde(default)]
+    pub course_difficulty: Option<f64>,
+    /// Second half time relative to the first, see `split_differential`
+    #[serde(default)]
+    pub split_differential: Option<f64>,
+    /// Average watts, stops included
+    #[serde(default)]
+    pub avg_power: Option<f64>,
+    /// See `power_curve::normalized_power`
+    #[serde(default)]
+    pub normalized_power: Option<f64>,
+    /// Mean device temperature in degrees Celsius, see
+    /// `GarminFile::avg_temperature`
+    #[serde(default)]
+    pub avg_temperature: Option<f64>,
+    /// `total_calories` were estimated, see `calorie_model`
+    #[serde(default)]
+    pub calories_estimated: bool,
+}
+
+/// Summary of an activity recorded without calories, see
+/// `GarminSummary::get_missing_calories`
+#[derive(FromSqlRow, Debug, Clone)]
+pub struct MissingCalories {
+    pub id: Uuid,
+    pub filename: StackString,
+    pub begin_datetime: DateTimeWrapper,
+    pub sport: SportTypes,
+    pub total_distance: f64,
+    pub total_duration: f64,
+    pub total_hr_dur: f64,
+    pub total_hr_dis: f64,
+}
+
+impl MissingCalories {
+    /// Average heart rate over the part of the activity with heart rate data
+    #[must_use]
+    pub fn avg_heart_rate(&self) -> Option<f64> {
+        if self.total_hr_dis > 0.0 && self.total_hr_dur > 0.0 {
+            Some(self.total_hr_dur / self.total_hr_dis)
+        } else {
+            None
+        }
+    }
 }
 
 impl GarminSummary {
     #[must_use]
     pub fn new(gfile: &GarminFile, md5sum: &str) -> Self {
+        let (avg_power, normalized_power) = activity_power(gfile);
         Self {
             id: Uuid::new_v4(),
             filename: gfile.filename.clone(),
@@ -45,11 +95,18 @@ impl GarminSummary {
             total_hr_dur: gfile.total_hr_dur,
             total_hr_dis: gfile.total_hr_dis,
             md5sum: md5sum.into(),
+            course_difficulty: course_difficulty(gfile),
+            split_differential: split_differential(gfile),
+            avg_power,
+            normalized_power,
+            avg_temperature: gfile.avg_temperature(),
+            calories_estimated: false,
         }
     }
 
     /// # Errors
     /// Return error if db query fails
+    #[instrument(skip(pool), fields(db.system = "postgresql"))]
     pub async fn read_summary_from_postgres(
         pool: &PgPool,
         pattern: &str,
@@ -71,7 +128,13 @@ impl GarminSummary {
                     total_duration,
                     total_hr_dur,
                     total_hr_dis,
-                    md5sum
+                    md5sum,
+                    course_difficulty,
+                    split_differential,
+                    avg_power,
+                    normalized_power,
+                    avg_temperature,
+                    calories_estimated
                 FROM garmin_summary
                 {where_str}
                 ORDER BY begin_datetime DESC
@@ -97,7 +160,13 @@ impl GarminSummary {
                    total_duration,
                    total_hr_dur,
                    total_hr_dis,
-                   md5sum
+                   md5sum,
+                   course_difficulty,
+                   split_differential,
+                   avg_power,
+                   normalized_power,
+                   avg_temperature,
+                   calories_estimated
             FROM garmin_summary WHERE filename = $filename",
             filename = filename,
         );
@@ -119,7 +188,13 @@ impl GarminSummary {
                    total_duration,
                    total_hr_dur,
                    total_hr_dis,
-                   md5sum
+                   md5sum,
+                   course_difficulty,
+                   split_differential,
+                   avg_power,
+                   normalized_power,
+                   avg_temperature,
+                   calories_estimated
             FROM garmin_summary WHERE id = $id",
             id = id,
         );
@@ -129,49 +204,316 @@ impl GarminSummary {
 
     /// # Errors
     /// Return error if db query fails
-    pub async fn write_summary_to_postgres(
-        summary_list: &[Self],
+    pub async fn get_by_ids(pool: &PgPool, ids: &[Uuid]) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+            SELECT id,
+                   filename,
+                   begin_datetime,
+                   sport,
+                   total_calories,
+                   total_distance,
+                   total_duration,
+                   total_hr_dur,
+                   total_hr_dis,
+                   md5sum,
+                   course_difficulty,
+                   split_differential,
+                   avg_power,
+                   normalized_power,
+                   avg_temperature,
+                   calories_estimated
+            FROM garmin_summary WHERE id = ANY($ids)
+            ORDER BY begin_datetime",
+            ids = ids,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Activity whose time range contains `datetime`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_datetime(
         pool: &PgPool,
+        datetime: OffsetDateTime,
+    ) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+            SELECT id,
+                   filename,
+                   begin_datetime,
+                   sport,
+                   total_calories,
+                   total_distance,
+                   total_duration,
+                   total_hr_dur,
+                   total_hr_dis,
+                   md5sum,
+                   course_difficulty,
+                   split_differential,
+                   avg_power,
+                   normalized_power,
+                   avg_temperature,
+                   calories_estimated
+            FROM garmin_summary
+            WHERE begin_datetime <= $datetime
+              AND begin_datetime + total_duration * interval '1 second' >= $datetime
+            ORDER BY begin_datetime DESC
+            LIMIT 1",
+            datetime = datetime,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// (id, filename) of activities stored before course difficulty was
+    /// computed
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_course_difficulty(
+        pool: &PgPool,
+    ) -> Result<Vec<(Uuid, StackString)>, Error> {
+        #[derive(FromSqlRow)]
+        struct MissingRow {
+            id: Uuid,
+            filename: StackString,
+        }
+
+        let query = query!(
+            "
+                SELECT id, filename
+                FROM garmin_summary
+                WHERE course_difficulty IS NULL
+                ORDER BY begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+
+    /// Filenames of the `limit` most recent activities of `sport` with a
+    /// distance
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_recent_filenames(
+        pool: &PgPool,
+        sport: SportTypes,
+        limit: i64,
+    ) -> Result<Vec<StackString>, Error> {
+        #[derive(FromSqlRow)]
+        struct FilenameRow {
+            filename: StackString,
+        }
+
+        let query = query!(
+            "
+                SELECT filename
+                FROM garmin_summary
+                WHERE sport = $sport AND total_distance > 0
+                ORDER BY begin_datetime DESC
+                LIMIT $limit
+            ",
+            sport = sport.to_str(),
+            limit = limit,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<FilenameRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| r.filename).collect())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn update_course_difficulty(
+        pool: &PgPool,
+        id: Uuid,
+        course_difficulty: f64,
     ) -> Result<(), Error> {
-        let rand_str = generate_random_string(8);
-
-        let temp_table_name = format_sstr!("garmin_summary_{rand_str}");
-
-        let create_table_query = format_sstr!(
-            "CREATE TABLE {temp_table_name} (
-                filename text NOT NULL PRIMARY KEY,
-                begin_datetime TIMESTAMP WITH TIME ZONE NOT NULL,
-                sport varchar(12),
-                total_calories integer,
-                total_distance double precision,
-                total_duration double precision,
-                total_hr_dur double precision,
-                total_hr_dis double precision,
-                md5sum varchar(32)
-            );"
+        let query = query!(
+            "UPDATE garmin_summary SET course_difficulty = $course_difficulty WHERE id = $id",
+            id = id,
+            course_difficulty = course_difficulty,
         );
         let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
 
-        conn.execute(create_table_query.as_str(), &[]).await?;
+    /// (id, filename) of activities stored before the split differential was
+    /// computed
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_split_differential(
+        pool: &PgPool,
+    ) -> Result<Vec<(Uuid, StackString)>, Error> {
+        #[derive(FromSqlRow)]
+        struct MissingRow {
+            id: Uuid,
+            filename: StackString,
+        }
 
-        let insert_query = Arc::new(format_sstr!(
+        let query = query!(
+            "
+                SELECT id, filename
+                FROM garmin_summary
+                WHERE split_differential IS NULL
+                  AND total_distance > 0
+                ORDER BY begin_datetime
             "
-            INSERT INTO {temp_table_name} (
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn update_split_differential(
+        pool: &PgPool,
+        id: Uuid,
+        split_differential: f64,
+    ) -> Result<(), Error> {
+        let query = query!(
+            "UPDATE garmin_summary SET split_differential = $split_differential WHERE id = $id",
+            id = id,
+            split_differential = split_differential,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Activities recorded without calories which haven't been estimated yet
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_calories(pool: &PgPool) -> Result<Vec<MissingCalories>, Error> {
+        let query = query!(
+            "
+                SELECT id, filename, begin_datetime, sport, total_distance, total_duration,
+                       total_hr_dur, total_hr_dis
+                FROM garmin_summary
+                WHERE total_calories = 0
+                  AND NOT calories_estimated
+                  AND NOT calories_attempted
+                  AND total_duration > 0
+                ORDER BY begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingCalories> = query.fetch(&conn).await?;
+        Ok(rows)
+    }
+
+    /// Fill in estimated `total_calories`, flagged so reports can tell them
+    /// apart from calories recorded by the device
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn update_estimated_calories(
+        pool: &PgPool,
+        id: Uuid,
+        total_calories: i32,
+    ) -> Result<(), Error> {
+        let query = query!(
+            "
+                UPDATE garmin_summary
+                SET total_calories = $total_calories, calories_estimated = true
+                WHERE id = $id
+            ",
+            id = id,
+            total_calories = total_calories,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Flag an activity whose calories couldn't be estimated so
+    /// `get_missing_calories` skips it from now on
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn mark_calories_attempted(pool: &PgPool, id: Uuid) -> Result<(), Error> {
+        let query = query!(
+            "UPDATE garmin_summary SET calories_attempted = true WHERE id = $id",
+            id = id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Insert or update the summaries and link them to synced activities and
+    /// lap corrections, one transaction per summary so a half processed file
+    /// never shows up in reports and one bad file doesn't hold back the
+    /// rest. Returns the ids the saved summaries have in the db, an updated
+    /// summary keeps the id of the existing row
+    /// # Errors
+    /// Return error if no summary could be saved
+    #[instrument(skip_all, fields(db.system = "postgresql", summaries = summary_list.len()))]
+    pub async fn write_summary_to_postgres(
+        summary_list: &[Self],
+        pool: &PgPool,
+    ) -> Result<Vec<Uuid>, Error> {
+        let upsert_query = "
+            INSERT INTO garmin_summary (
                 filename, begin_datetime, sport, total_calories, total_distance, total_duration,
-                total_hr_dur, total_hr_dis, md5sum
+                total_hr_dur, total_hr_dis, md5sum, course_difficulty, split_differential,
+                avg_power, normalized_power, avg_temperature
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-        "
-        ));
-
-        let futures = summary_list.iter().map(|gsum| {
-            let pool = pool.clone();
-            let insert_query = insert_query.clone();
-            async move {
-                let conn = pool.get().await?;
-                let sport_str = StackString::from_display(gsum.sport);
-                conn.execute(
-                    insert_query.as_str(),
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            ON CONFLICT (filename) DO UPDATE
+            SET (
+                begin_datetime,sport,total_calories,total_distance,total_duration,total_hr_dur,
+                total_hr_dis,md5sum,course_difficulty,split_differential,avg_power,
+                normalized_power,avg_temperature,calories_estimated,calories_attempted
+            ) = (EXCLUDED.begin_datetime,EXCLUDED.sport,EXCLUDED.total_calories,
+                 EXCLUDED.total_distance,EXCLUDED.total_duration,EXCLUDED.total_hr_dur,
+                 EXCLUDED.total_hr_dis,EXCLUDED.md5sum,EXCLUDED.course_difficulty,
+                 EXCLUDED.split_differential,EXCLUDED.avg_power,EXCLUDED.normalized_power,
+                 EXCLUDED.avg_temperature,false,false
+            )
+            RETURNING id
+        ";
+        let link_queries = [
+            "
+                UPDATE strava_activities SET summary_id = a.id
+                FROM garmin_summary a
+                WHERE a.filename = $1
+                  AND a.begin_datetime = start_date
+                  AND summary_id IS NULL
+            ",
+            "
+                UPDATE garmin_connect_activities SET summary_id = a.id
+                FROM garmin_summary a
+                WHERE a.filename = $1
+                  AND a.begin_datetime = start_time_gmt
+                  AND summary_id IS NULL
+            ",
+            "
+                UPDATE fitbit_activities SET summary_id = a.id
+                FROM garmin_summary a
+                WHERE a.filename = $1
+                  AND to_char(a.begin_datetime, 'YYYY-MM-DD HH24:MI')
+                        = to_char(start_time, 'YYYY-MM-DD HH24:MI')
+                  AND summary_id IS NULL
+            ",
+            "
+                UPDATE garmin_corrections_laps SET summary_id = a.id
+                FROM garmin_summary a
+                WHERE a.filename = $1
+                  AND a.begin_datetime = start_time
+                  AND summary_id IS NULL
+            ",
+        ];
+        let mut summary_ids = Vec::with_capacity(summary_list.len());
+        let mut first_error = None;
+        let mut conn = pool.get().await?;
+        for gsum in summary_list {
+            let tran = conn.transaction().await?;
+            let sport_str = StackString::from_display(gsum.sport);
+            let result = tran
+                .query_one(
+                    upsert_query,
                     &[
                         &gsum.filename,
                         &gsum.begin_datetime,
@@ -182,50 +524,35 @@ impl GarminSummary {
                         &gsum.total_hr_dur,
                         &gsum.total_hr_dis,
                         &gsum.md5sum,
+                        &gsum.course_difficulty,
+                        &gsum.split_differential,
+                        &gsum.avg_power,
+                        &gsum.normalized_power,
+                        &gsum.avg_temperature,
                     ],
                 )
-                .await?;
-                Ok(())
+                .await;
+            let result: Result<Uuid, Error> = async {
+                let id = result?.try_get("id")?;
+                for query in link_queries.into_iter().chain(APPLY_STAT_CHOICES) {
+                    tran.execute(query, &[&gsum.filename]).await?;
+                }
+                tran.commit().await?;
+                Ok(id)
             }
-        });
-        let results: Result<Vec<()>, Error> = try_join_all(futures).await;
-        results?;
-
-        let insert_query = format_sstr!(
-            "
-            INSERT INTO garmin_summary (
-                filename, begin_datetime, sport, total_calories, total_distance, total_duration,
-                total_hr_dur, total_hr_dis, md5sum
-            )
-            SELECT b.filename, b.begin_datetime, b.sport, b.total_calories, b.total_distance,
-                   b.total_duration, b.total_hr_dur, b.total_hr_dis, b.md5sum
-            FROM {temp_table_name} b
-            WHERE b.filename not in (select filename from garmin_summary)
-        "
-        );
-
-        let update_query = format_sstr!(
-            "
-            UPDATE garmin_summary a
-            SET (
-                begin_datetime,sport,total_calories,total_distance,total_duration,total_hr_dur,
-                total_hr_dis,md5sum
-            ) = (b.begin_datetime,b.sport,b.total_calories,b.total_distance,b.total_duration,
-                 b.total_hr_dur,b.total_hr_dis,b.md5sum
-            )
-            FROM {temp_table_name} b
-            WHERE a.filename = b.filename
-        "
-        );
-
-        let drop_table_query = format_sstr!("DROP TABLE {temp_table_name}");
-
-        conn.execute(insert_query.as_str(), &[]).await?;
-        conn.execute(update_query.as_str(), &[]).await?;
-        conn.execute(drop_table_query.as_str(), &[])
-            .await
-            .map(|_| ())
-            .map_err(Into::into)
+            .await;
+            match result {
+                Ok(id) => summary_ids.push(id),
+                Err(e) => {
+                    error!("failed to write summary {}: {e}", gsum.filename);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if summary_ids.is_empty() => Err(e),
+            _ => Ok(summary_ids),
+        }
     }
 }
 
@@ -369,12 +696,20 @@ pub async fn get_maximum_begin_datetime(pool: &PgPool) -> Result<Option<OffsetDa
 
 #[cfg(test)]
 mod tests {
+    use anyhow::Error;
+    use postgres_query::query;
     use uuid::Uuid;
 
-    use garmin_lib::date_time_wrapper::iso8601::convert_str_to_datetime;
-    use garmin_utils::sport_types::SportTypes;
+    use garmin_lib::{
+        date_time_wrapper::iso8601::convert_str_to_datetime, garmin_config::GarminConfig,
+    };
+    use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
 
-    use crate::garmin_summary;
+    use crate::{
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+        garmin_summary::{self, GarminSummary},
+    };
 
     #[test]
     fn test_garmin_file_test_display() {
@@ -391,6 +726,12 @@ mod tests {
             total_hr_dur: 1234.0,
             total_hr_dis: 23456.0,
             md5sum: "asjgpqowiqwe".into(),
+            course_difficulty: None,
+            split_differential: None,
+            avg_power: None,
+            normalized_power: None,
+            avg_temperature: None,
+            calories_estimated: false,
         };
         assert_eq!(
             format!("{}", garmin_summary),
@@ -399,4 +740,54 @@ mod tests {
              total_hr_dur=1234,total_hr_dis=23456,md5sum=asjgpqowiqwe>"
         );
     }
+
+    #[test]
+    fn test_summary_avg_temperature() {
+        let mut gfile = GarminFile::new();
+        assert_eq!(GarminSummary::new(&gfile, "").avg_temperature, None);
+
+        gfile.points = [Some(20.0), None, Some(23.0)]
+            .into_iter()
+            .map(|temperature| GarminPoint {
+                temperature,
+                ..GarminPoint::new()
+            })
+            .collect();
+        // points without a reading don't pull the mean down
+        assert_eq!(GarminSummary::new(&gfile, "").avg_temperature, Some(21.5));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_write_summary_to_postgres_skips_failures() -> Result<(), Error> {
+        let config = GarminConfig::get_config(None)?;
+        let pool = PgPool::new(&config.pgurl)?;
+        let summary = |filename: &str, md5sum: &str| GarminSummary {
+            filename: filename.into(),
+            begin_datetime: convert_str_to_datetime("2001-01-01T00:00:00Z")
+                .unwrap()
+                .into(),
+            sport: SportTypes::Running,
+            md5sum: md5sum.into(),
+            ..GarminSummary::new(&GarminFile::new(), "")
+        };
+        // md5sum is varchar(32), the middle summary can't be inserted
+        let summary_list = [
+            summary("write-summary-test-a.fit", "a"),
+            summary("write-summary-test-b.fit", &"b".repeat(40)),
+            summary("write-summary-test-c.fit", "c"),
+        ];
+        let summary_ids = GarminSummary::write_summary_to_postgres(&summary_list, &pool).await?;
+        assert_eq!(summary_ids.len(), 2);
+
+        for (gsum, saved) in summary_list.iter().zip([true, false, true]) {
+            let stored = GarminSummary::get_by_filename(&pool, &gsum.filename).await?;
+            assert_eq!(stored.is_some(), saved);
+        }
+
+        let conn = pool.get().await?;
+        let query = query!("DELETE FROM garmin_summary WHERE filename LIKE 'write-summary-test-%'");
+        query.execute(&conn).await?;
+        Ok(())
+    }
 }
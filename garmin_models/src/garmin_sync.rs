@@ -1,8 +1,8 @@
 use anyhow::{format_err, Error};
 use aws_config::SdkConfig;
 use aws_sdk_s3::{
-    operation::list_objects::ListObjectsOutput, primitives::ByteStream, types::Object as S3Object,
-    Client as S3Client,
+    operation::list_objects::ListObjectsOutput, presigning::PresigningConfig,
+    primitives::ByteStream, types::Object as S3Object, Client as S3Client,
 };
 use futures::{Stream, TryStreamExt};
 use log::{debug, error};
@@ -21,13 +21,15 @@ use std::{
     fs,
     hash::{Hash, Hasher},
     path::Path,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 use tokio::{
     fs::File,
     task::{spawn, spawn_blocking, JoinHandle},
 };
+use tracing::instrument;
 
+use garmin_lib::garmin_config::GarminConfig;
 use garmin_utils::{
     garmin_util::{exponential_retry, get_md5sum},
     pgpool::PgPool,
@@ -91,6 +93,15 @@ impl GarminSync {
         }
     }
 
+    /// `None` when `s3_enabled` is turned off
+    pub async fn from_config(config: &GarminConfig) -> Option<Self> {
+        if config.s3_enabled {
+            Some(Self::new(&aws_config::load_from_env().await))
+        } else {
+            None
+        }
+    }
+
     #[must_use]
     pub fn from_client(s3client: S3Client) -> Self {
         Self {
@@ -199,10 +210,11 @@ impl GarminSync {
         s3_bucket: &str,
         pool: &PgPool,
     ) -> Result<usize, Error> {
-        let mut allowed_extensions: HashSet<_> = ["fit", "gmn", "gz", "txt", "avro", "parquet"]
-            .iter()
-            .map(OsStr::new)
-            .collect();
+        let mut allowed_extensions: HashSet<_> =
+            ["fit", "gmn", "gpx", "gz", "txt", "avro", "parquet"]
+                .iter()
+                .map(OsStr::new)
+                .collect();
         allowed_extensions.shrink_to_fit();
 
         let mut file_map: HashMap<StackString, KeyItemCache> =
@@ -293,6 +305,7 @@ impl GarminSync {
 
     /// # Errors
     /// Return error if s3 api call fails
+    #[instrument(skip(self, pool))]
     pub async fn sync_dir(
         &self,
         title: &str,
@@ -381,6 +394,7 @@ impl GarminSync {
 
     /// # Errors
     /// Return error if s3 api call fails
+    #[instrument(skip(self))]
     pub async fn download_file(
         &self,
         local_file: &Path,
@@ -418,6 +432,7 @@ impl GarminSync {
 
     /// # Errors
     /// Return error if s3 api call fails
+    #[instrument(skip(self))]
     pub async fn upload_file(
         &self,
         local_file: &Path,
@@ -442,6 +457,134 @@ impl GarminSync {
         })
         .await
     }
+
+    /// Temporary url which allows downloading `s3_key` without credentials
+    /// # Errors
+    /// Return error if presigning fails
+    pub async fn get_presigned_url(
+        &self,
+        s3_bucket: &str,
+        s3_key: &str,
+        expires_in: Duration,
+    ) -> Result<StackString, Error> {
+        let request = self
+            .s3_client
+            .get_object()
+            .bucket(s3_bucket)
+            .key(s3_key)
+            .presigned(PresigningConfig::expires_in(expires_in)?)
+            .await?;
+        Ok(request.uri().into())
+    }
+
+    /// # Errors
+    /// Return error if s3 api call fails for any reason other than a missing
+    /// key
+    pub async fn file_exists(&self, s3_bucket: &str, s3_key: &str) -> Result<bool, Error> {
+        match self
+            .s3_client
+            .head_object()
+            .bucket(s3_bucket)
+            .key(s3_key)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => match e.into_service_error() {
+                e if e.is_not_found() => Ok(false),
+                e => Err(e.into()),
+            },
+        }
+    }
+
+    /// Etag of `s3_key`, `None` if it doesn't exist
+    /// # Errors
+    /// Return error if s3 api call fails for any reason other than a missing
+    /// key
+    pub async fn get_etag(
+        &self,
+        s3_bucket: &str,
+        s3_key: &str,
+    ) -> Result<Option<StackString>, Error> {
+        match self
+            .s3_client
+            .head_object()
+            .bucket(s3_bucket)
+            .key(s3_key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(output.e_tag.map(|e| e.trim_matches('"').into())),
+            Err(e) => match e.into_service_error() {
+                e if e.is_not_found() => Ok(None),
+                e => Err(e.into()),
+            },
+        }
+    }
+
+    /// Contents of a small object such as a manifest, `None` if it doesn't
+    /// exist
+    /// # Errors
+    /// Return error if s3 api call fails for any reason other than a missing
+    /// key
+    #[instrument(skip(self))]
+    pub async fn get_object(
+        &self,
+        s3_bucket: &str,
+        s3_key: &str,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        match self
+            .s3_client
+            .get_object()
+            .bucket(s3_bucket)
+            .key(s3_key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(Some(output.body.collect().await?.into_bytes().to_vec())),
+            Err(e) => match e.into_service_error() {
+                e if e.is_no_such_key() => Ok(None),
+                e => Err(e.into()),
+            },
+        }
+    }
+
+    /// # Errors
+    /// Return error if s3 api call fails
+    #[instrument(skip(self, body))]
+    pub async fn put_object(
+        &self,
+        s3_bucket: &str,
+        s3_key: &str,
+        body: Vec<u8>,
+    ) -> Result<StackString, Error> {
+        let etag = self
+            .s3_client
+            .put_object()
+            .bucket(s3_bucket)
+            .key(s3_key)
+            .body(ByteStream::from(body))
+            .send()
+            .await?
+            .e_tag
+            .ok_or_else(|| format_err!("Missing etag"))?
+            .trim_matches('"')
+            .into();
+        Ok(etag)
+    }
+
+    /// # Errors
+    /// Return error if s3 api call fails
+    #[instrument(skip(self))]
+    pub async fn delete_file(&self, s3_bucket: &str, s3_key: &str) -> Result<(), Error> {
+        self.s3_client
+            .delete_object()
+            .bucket(s3_bucket)
+            .key(s3_key)
+            .send()
+            .await?;
+        Ok(())
+    }
 }
 
 #[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, Default)]
@@ -492,6 +635,19 @@ impl KeyItemCache {
         query.fetch_opt(&conn).await.map_err(Into::into)
     }
 
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete(pool: &PgPool, s3_key: &str, s3_bucket: &str) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM key_item_cache WHERE s3_key = $s3_key AND s3_bucket = $s3_bucket",
+            s3_key = s3_key,
+            s3_bucket = s3_bucket,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
     /// # Errors
     /// Return error if db query fails
     pub async fn get_files(
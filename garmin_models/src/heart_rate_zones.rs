@@ -0,0 +1,502 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use time::{Date, Duration, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+use uuid::Uuid;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, heart_rate_profile::HeartRateProfile};
+use garmin_utils::pgpool::PgPool;
+
+use crate::{garmin_file::GarminFile, threshold_heart_rate::current_threshold_heart_rate};
+
+/// Seconds a heart rate has to be held to count as the maximum of an
+/// activity, so single sample spikes are ignored
+pub const SUSTAINED_HEART_RATE_SECONDS: f64 = 30.0;
+/// Activities of the last year are searched for the observed maximum
+pub const OBSERVED_MAX_DAYS: i64 = 365;
+/// A lower maximum is only suggested when the observed maximum is this far
+/// below the current one ...
+pub const LOWER_MAX_MARGIN: f64 = 5.0;
+/// ... over at least this many activities with heart rate
+pub const LOWER_MAX_MIN_ACTIVITIES: i64 = 20;
+
+/// Highest heart rate held for `SUSTAINED_HEART_RATE_SECONDS`, `None` for
+/// activities without that much heart rate data
+#[must_use]
+pub fn sustained_max_heart_rate(gfile: &GarminFile) -> Option<f64> {
+    let samples: Vec<_> = gfile
+        .points
+        .iter()
+        .filter_map(|p| p.heart_rate.map(|hr| (p.duration_from_begin, hr)))
+        .filter(|(_, hr)| *hr > 0.0)
+        .collect();
+    let mut best: Option<f64> = None;
+    for (idx, (start, _)) in samples.iter().enumerate() {
+        let mut lowest = f64::MAX;
+        let mut covered = false;
+        for (time, hr) in &samples[idx..] {
+            lowest = lowest.min(*hr);
+            if time - start >= SUSTAINED_HEART_RATE_SECONDS {
+                covered = true;
+                break;
+            }
+        }
+        if covered && best.is_none_or(|b| lowest > b) {
+            best = Some(lowest);
+        }
+    }
+    best
+}
+
+#[derive(FromSqlRow)]
+struct MissingMaxRow {
+    id: Uuid,
+    filename: StackString,
+}
+
+/// Sustained maximum heart rate of an activity, stored in
+/// `activity_max_heart_rates`, `None` for activities without heart rate so
+/// they aren't read again on every sync
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ActivityMaxHeartRate {
+    pub summary_id: Uuid,
+    pub max_heart_rate: Option<f64>,
+}
+
+impl ActivityMaxHeartRate {
+    #[must_use]
+    pub fn from_gfile(summary_id: Uuid, gfile: &GarminFile) -> Self {
+        Self {
+            summary_id,
+            max_heart_rate: sustained_max_heart_rate(gfile),
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_max_heart_rates (summary_id, max_heart_rate)
+                VALUES ($summary_id, $max_heart_rate)
+                ON CONFLICT (summary_id) DO UPDATE
+                SET max_heart_rate=EXCLUDED.max_heart_rate
+            ",
+            summary_id = self.summary_id,
+            max_heart_rate = self.max_heart_rate,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// (id, filename) of activities with heart rate which haven't been
+    /// checked
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_summaries(pool: &PgPool) -> Result<Vec<(Uuid, StackString)>, Error> {
+        let query = query!(
+            "
+                SELECT a.id, a.filename
+                FROM garmin_summary a
+                WHERE a.total_hr_dis > 0
+                  AND NOT EXISTS (
+                    SELECT 1 FROM activity_max_heart_rates b WHERE b.summary_id = a.id
+                  )
+                ORDER BY a.begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingMaxRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+}
+
+/// Maximum and resting heart rate confirmed from `effective_date` onwards,
+/// stored in `heart_rate_settings`, overrides the configured values
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct HeartRateSetting {
+    pub effective_date: Date,
+    pub max_heart_rate: f64,
+    pub resting_heart_rate: f64,
+}
+
+impl HeartRateSetting {
+    #[must_use]
+    pub fn profile(&self) -> HeartRateProfile {
+        HeartRateProfile {
+            max_heart_rate: self.max_heart_rate,
+            resting_heart_rate: self.resting_heart_rate,
+            threshold_heart_rate: None,
+        }
+    }
+
+    /// # Errors
+    /// Return error if the resting heart rate isn't below the maximum
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.resting_heart_rate <= 0.0 || self.max_heart_rate <= self.resting_heart_rate {
+            Err(format_err!(
+                "Invalid max heart rate {} or resting heart rate {}",
+                self.max_heart_rate,
+                self.resting_heart_rate
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Latest confirmed setting in effect on the local date of today
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_current(pool: &PgPool) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT * FROM heart_rate_settings
+                WHERE effective_date <= $date
+                ORDER BY effective_date DESC
+                LIMIT 1
+            ",
+            date = local_today(),
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM heart_rate_settings ORDER BY effective_date");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if validation or db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        self.validate()?;
+        let query = query!(
+            "
+                INSERT INTO heart_rate_settings
+                    (effective_date, max_heart_rate, resting_heart_rate)
+                VALUES ($effective_date, $max_heart_rate, $resting_heart_rate)
+                ON CONFLICT (effective_date) DO UPDATE
+                SET max_heart_rate=EXCLUDED.max_heart_rate,
+                    resting_heart_rate=EXCLUDED.resting_heart_rate
+            ",
+            effective_date = self.effective_date,
+            max_heart_rate = self.max_heart_rate,
+            resting_heart_rate = self.resting_heart_rate,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Today in the local timezone, settings take effect on local dates
+#[must_use]
+pub fn local_today() -> Date {
+    OffsetDateTime::now_utc()
+        .to_timezone(DateTimeWrapper::local_tz())
+        .date()
+}
+
+/// Heart rate profile in effect, the latest confirmed setting or `default`
+/// (from the config) when none has been confirmed, with the lactate threshold
+/// estimated from recent races and hard efforts
+/// # Errors
+/// Return error if db query fails
+pub async fn current_heart_rate_profile(
+    pool: &PgPool,
+    default: HeartRateProfile,
+) -> Result<HeartRateProfile, Error> {
+    let mut profile = HeartRateSetting::get_current(pool)
+        .await?
+        .as_ref()
+        .map_or(default, HeartRateSetting::profile);
+    profile.threshold_heart_rate = current_threshold_heart_rate(pool, &profile).await?;
+    Ok(profile)
+}
+
+/// Every confirmed setting, so historical activities are judged with the
+/// profile in effect on their local date rather than the latest one
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeartRateProfiles {
+    /// Profile before the first confirmed setting, from the config
+    pub default: HeartRateProfile,
+    /// Ordered by `effective_date`
+    pub settings: Vec<HeartRateSetting>,
+    /// Current lactate threshold estimate, applied to every date
+    pub threshold_heart_rate: Option<f64>,
+}
+
+impl HeartRateProfiles {
+    #[must_use]
+    pub fn new(
+        default: HeartRateProfile,
+        mut settings: Vec<HeartRateSetting>,
+        threshold_heart_rate: Option<f64>,
+    ) -> Self {
+        settings.sort_by_key(|s| s.effective_date);
+        Self {
+            default,
+            settings,
+            threshold_heart_rate,
+        }
+    }
+
+    /// Profile in effect on the local `date`
+    #[must_use]
+    pub fn profile_on(&self, date: Date) -> HeartRateProfile {
+        self.with_threshold(
+            self.settings
+                .iter()
+                .rev()
+                .find(|s| s.effective_date <= date)
+                .map_or(self.default, HeartRateSetting::profile),
+        )
+    }
+
+    fn with_threshold(&self, mut profile: HeartRateProfile) -> HeartRateProfile {
+        profile.threshold_heart_rate = self.threshold_heart_rate;
+        profile
+    }
+
+    /// Sql expression equivalent to `HeartRateProfile::trimp_sql` with the
+    /// profile in effect on `date`, a sql expression of the local date of
+    /// the row
+    #[must_use]
+    pub fn trimp_sql(&self, date: &str, hr_dur: &str, hr_dis: &str) -> StackString {
+        let default = self.with_threshold(self.default).trimp_sql(hr_dur, hr_dis);
+        if self.settings.is_empty() {
+            return default;
+        }
+        let cases: Vec<_> = self
+            .settings
+            .iter()
+            .rev()
+            .map(|s| {
+                let trimp = self.with_threshold(s.profile()).trimp_sql(hr_dur, hr_dis);
+                format_sstr!("WHEN {date} >= '{}' THEN {trimp}", s.effective_date)
+            })
+            .collect();
+        format_sstr!("CASE {} ELSE {default} END", cases.join(" "))
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool, default: HeartRateProfile) -> Result<Self, Error> {
+        let mut profiles = Self::new(default, HeartRateSetting::read_from_db(pool).await?, None);
+        let current = profiles.profile_on(local_today());
+        profiles.threshold_heart_rate = current_threshold_heart_rate(pool, &current).await?;
+        Ok(profiles)
+    }
+}
+
+/// Activity with the highest sustained heart rate
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObservedMaxHeartRate {
+    pub filename: StackString,
+    pub begin_datetime: DateTimeWrapper,
+    pub max_heart_rate: f64,
+}
+
+/// Observed maximum compared with the profile in effect, `suggested` is set
+/// when the zones should be updated
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HeartRateSuggestion {
+    pub current: HeartRateProfile,
+    pub observed: Option<ObservedMaxHeartRate>,
+    /// Activities with heart rate over the last `OBSERVED_MAX_DAYS`
+    pub activities: i64,
+    pub suggested: Option<HeartRateProfile>,
+}
+
+impl HeartRateSuggestion {
+    /// A higher observed maximum is always suggested, a lower one only when
+    /// enough activities have failed to reach the current maximum
+    #[must_use]
+    pub fn from_observed(
+        current: HeartRateProfile,
+        observed: Option<ObservedMaxHeartRate>,
+        activities: i64,
+    ) -> Self {
+        let suggested = observed.as_ref().and_then(|observed| {
+            let max_heart_rate = observed.max_heart_rate.round();
+            let raise = max_heart_rate > current.max_heart_rate;
+            let lower = max_heart_rate < current.max_heart_rate - LOWER_MAX_MARGIN
+                && activities >= LOWER_MAX_MIN_ACTIVITIES;
+            if (raise || lower) && max_heart_rate > current.resting_heart_rate {
+                Some(HeartRateProfile {
+                    max_heart_rate,
+                    ..current
+                })
+            } else {
+                None
+            }
+        });
+        Self {
+            current,
+            observed,
+            activities,
+            suggested,
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn detect(pool: &PgPool, default: HeartRateProfile) -> Result<Self, Error> {
+        #[derive(FromSqlRow)]
+        struct CountRow {
+            count: i64,
+        }
+
+        let current = current_heart_rate_profile(pool, default).await?;
+        let start = OffsetDateTime::now_utc() - Duration::days(OBSERVED_MAX_DAYS);
+        let query = query!(
+            "
+                SELECT a.filename, a.begin_datetime, b.max_heart_rate
+                FROM garmin_summary a
+                JOIN activity_max_heart_rates b ON b.summary_id = a.id
+                WHERE a.begin_datetime >= $start
+                  AND b.max_heart_rate IS NOT NULL
+                ORDER BY b.max_heart_rate DESC
+                LIMIT 1
+            ",
+            start = start,
+        );
+        let conn = pool.get().await?;
+        let observed: Option<ObservedMaxHeartRate> = query.fetch_opt(&conn).await?;
+        let query = query!(
+            "
+                SELECT count(*) AS count
+                FROM garmin_summary a
+                JOIN activity_max_heart_rates b ON b.summary_id = a.id
+                WHERE a.begin_datetime >= $start
+                  AND b.max_heart_rate IS NOT NULL
+            ",
+            start = start,
+        );
+        let count: CountRow = query.fetch_one(&conn).await?;
+        Ok(Self::from_observed(current, observed, count.count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use garmin_lib::heart_rate_profile::HeartRateProfile;
+
+    use crate::{
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+        heart_rate_zones::{
+            sustained_max_heart_rate, HeartRateProfiles, HeartRateSetting, HeartRateSuggestion,
+            ObservedMaxHeartRate,
+        },
+    };
+
+    #[test]
+    fn test_sustained_max_heart_rate() {
+        let mut gfile = GarminFile::default();
+        // a one sample spike to 210 then 40 seconds around 180
+        let heart_rates = [
+            150.0, 210.0, 150.0, 178.0, 182.0, 181.0, 180.0, 180.0, 150.0,
+        ];
+        gfile.points = heart_rates
+            .iter()
+            .enumerate()
+            .map(|(idx, hr)| GarminPoint {
+                duration_from_begin: 10.0 * idx as f64,
+                heart_rate: Some(*hr),
+                ..GarminPoint::default()
+            })
+            .collect();
+        assert_eq!(sustained_max_heart_rate(&gfile), Some(180.0));
+
+        gfile.points.truncate(3);
+        assert_eq!(sustained_max_heart_rate(&gfile), None);
+    }
+
+    #[test]
+    fn test_heart_rate_suggestion() {
+        let current = HeartRateProfile {
+            max_heart_rate: 185.0,
+            resting_heart_rate: 55.0,
+            threshold_heart_rate: None,
+        };
+        let observed = |max_heart_rate| ObservedMaxHeartRate {
+            filename: "".into(),
+            begin_datetime: datetime!(2024-05-01 12:00:00 UTC).into(),
+            max_heart_rate,
+        };
+
+        let suggestion = HeartRateSuggestion::from_observed(current, Some(observed(188.4)), 3);
+        let suggested = suggestion.suggested.unwrap();
+        assert_eq!(suggested.max_heart_rate, 188.0);
+        assert_eq!(suggested.resting_heart_rate, 55.0);
+
+        let suggestion = HeartRateSuggestion::from_observed(current, Some(observed(183.0)), 50);
+        assert!(suggestion.suggested.is_none());
+        let suggestion = HeartRateSuggestion::from_observed(current, Some(observed(175.0)), 5);
+        assert!(suggestion.suggested.is_none());
+        let suggestion = HeartRateSuggestion::from_observed(current, Some(observed(175.0)), 50);
+        assert_eq!(suggestion.suggested.unwrap().max_heart_rate, 175.0);
+
+        assert!(HeartRateSuggestion::from_observed(current, None, 0)
+            .suggested
+            .is_none());
+    }
+
+    #[test]
+    fn test_heart_rate_profiles() {
+        let default = HeartRateProfile {
+            max_heart_rate: 190.0,
+            resting_heart_rate: 60.0,
+            threshold_heart_rate: None,
+        };
+        let setting = |effective_date, max_heart_rate| HeartRateSetting {
+            effective_date,
+            max_heart_rate,
+            resting_heart_rate: 50.0,
+        };
+        let profiles = HeartRateProfiles::new(
+            default,
+            vec![
+                setting(date!(2024 - 06 - 01), 180.0),
+                setting(date!(2023 - 01 - 01), 185.0),
+            ],
+            Some(165.0),
+        );
+        let profile = profiles.profile_on(date!(2022 - 12 - 31));
+        assert_eq!(profile.max_heart_rate, 190.0);
+        assert_eq!(profile.threshold_heart_rate, Some(165.0));
+        assert_eq!(
+            profiles.profile_on(date!(2023 - 01 - 01)).max_heart_rate,
+            185.0
+        );
+        assert_eq!(
+            profiles.profile_on(date!(2024 - 05 - 31)).max_heart_rate,
+            185.0
+        );
+        assert_eq!(
+            profiles.profile_on(date!(2024 - 06 - 01)).max_heart_rate,
+            180.0
+        );
+
+        let sql = profiles.trimp_sql("d", "a.total_hr_dur", "a.total_hr_dis");
+        let latest = sql.find("d >= '2024-06-01'").unwrap();
+        let earlier = sql.find("d >= '2023-01-01'").unwrap();
+        assert!(latest < earlier, "{sql}");
+        assert!(sql.ends_with(" END"), "{sql}");
+
+        let profiles = HeartRateProfiles::new(default, Vec::new(), None);
+        assert_eq!(
+            profiles.trimp_sql("d", "a.total_hr_dur", "a.total_hr_dis"),
+            default.trimp_sql("a.total_hr_dur", "a.total_hr_dis")
+        );
+    }
+}
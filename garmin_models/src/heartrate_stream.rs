@@ -0,0 +1,268 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+use crate::{garmin_file::GarminFile, provenance::Provenance};
+
+/// Heart rate samples for an activity from a single device (e.g. the watch
+/// or a chest strap), stored in `heartrate_streams`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HeartRateStream {
+    pub summary_id: Uuid,
+    pub source: StackString,
+    pub filename: StackString,
+    pub begin_datetime: DateTimeWrapper,
+    pub offsets: Vec<f64>,
+    pub heart_rates: Vec<f64>,
+    pub avg_heart_rate: f64,
+    pub preferred: bool,
+}
+
+impl HeartRateStream {
+    /// Build a stream from (timestamp, heart rate) samples, offsets are
+    /// seconds since the first sample
+    #[must_use]
+    pub fn new(
+        summary_id: Uuid,
+        source: &str,
+        filename: &str,
+        samples: &[(OffsetDateTime, f64)],
+    ) -> Self {
+        let begin_datetime = samples
+            .first()
+            .map_or_else(DateTimeWrapper::sentinel_datetime, |(t, _)| (*t).into());
+        let begin: OffsetDateTime = begin_datetime.into();
+        let mut offsets = Vec::with_capacity(samples.len());
+        let mut heart_rates = Vec::with_capacity(samples.len());
+        for (t, hr) in samples {
+            offsets.push((*t - begin).as_seconds_f64());
+            heart_rates.push(*hr);
+        }
+        let avg_heart_rate = time_weighted_average(&offsets, &heart_rates);
+        Self {
+            summary_id,
+            source: source.into(),
+            filename: filename.into(),
+            begin_datetime,
+            offsets,
+            heart_rates,
+            avg_heart_rate,
+            preferred: false,
+        }
+    }
+
+    /// Stream recorded by the device which produced `gfile`
+    #[must_use]
+    pub fn from_gfile(summary_id: Uuid, source: &str, gfile: &GarminFile) -> Self {
+        let samples: Vec<_> = gfile
+            .points
+            .iter()
+            .filter_map(|p| {
+                p.heart_rate
+                    .filter(|hr| *hr > 0.0)
+                    .map(|hr| (p.time.into(), hr))
+            })
+            .collect();
+        Self::new(summary_id, source, &gfile.filename, &samples)
+    }
+
+    /// Returns (minutes since `begin`, heart rate) pairs for plotting
+    #[must_use]
+    pub fn plot_values(&self, begin: OffsetDateTime) -> Vec<(f64, f64)> {
+        let start: OffsetDateTime = self.begin_datetime.into();
+        let shift = (start - begin).as_seconds_f64();
+        self.offsets
+            .iter()
+            .zip(self.heart_rates.iter())
+            .map(|(t, hr)| ((t + shift) / 60.0, *hr))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn end_datetime(&self) -> OffsetDateTime {
+        let begin: OffsetDateTime = self.begin_datetime.into();
+        begin + Duration::seconds_f64(self.offsets.last().copied().unwrap_or(0.0))
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_by_summary_id(pool: &PgPool, summary_id: Uuid) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT * FROM heartrate_streams
+                WHERE summary_id = $summary_id
+                ORDER BY preferred DESC, source
+            ",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Insert or replace the samples for (`summary_id`, `source`) along with
+    /// `provenance` (without one any recorded provenance is kept), the
+    /// preferred flag is left unchanged
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(
+        &self,
+        pool: &PgPool,
+        provenance: Option<&Provenance>,
+    ) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO heartrate_streams (
+                    summary_id, source, filename, begin_datetime, offsets, heart_rates,
+                    avg_heart_rate, provider, import_path, raw_file, imported_at
+                )
+                VALUES (
+                    $summary_id, $source, $filename, $begin_datetime, $offsets, $heart_rates,
+                    $avg_heart_rate, $provider, $import_path, $raw_file, $imported_at
+                )
+                ON CONFLICT (summary_id, source) DO UPDATE
+                SET filename=EXCLUDED.filename,
+                    begin_datetime=EXCLUDED.begin_datetime,
+                    offsets=EXCLUDED.offsets,
+                    heart_rates=EXCLUDED.heart_rates,
+                    avg_heart_rate=EXCLUDED.avg_heart_rate,
+                    provider=COALESCE(EXCLUDED.provider, heartrate_streams.provider),
+                    import_path=COALESCE(EXCLUDED.import_path, heartrate_streams.import_path),
+                    raw_file=CASE WHEN EXCLUDED.provider IS NULL
+                                  THEN heartrate_streams.raw_file
+                                  ELSE EXCLUDED.raw_file END,
+                    imported_at=COALESCE(EXCLUDED.imported_at, heartrate_streams.imported_at)
+            ",
+            summary_id = self.summary_id,
+            source = self.source,
+            filename = self.filename,
+            begin_datetime = self.begin_datetime,
+            offsets = self.offsets,
+            heart_rates = self.heart_rates,
+            avg_heart_rate = self.avg_heart_rate,
+            provider = provenance.map(|p| p.provider.to_str()),
+            import_path = provenance.map(|p| &p.import_path),
+            raw_file = provenance.and_then(|p| p.raw_file.as_ref()),
+            imported_at = provenance.map(|p| p.imported_at),
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Mark `source` as the stream used for the summary heart rate of
+    /// `summary_id`, returns false if no such stream exists
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn set_preferred(
+        pool: &PgPool,
+        summary_id: Uuid,
+        source: &str,
+    ) -> Result<bool, Error> {
+        let query = query!(
+            "
+                UPDATE heartrate_streams SET preferred = (source = $source)
+                WHERE summary_id = $summary_id
+                  AND EXISTS (
+                    SELECT 1 FROM heartrate_streams
+                    WHERE summary_id = $summary_id AND source = $source
+                  )
+            ",
+            summary_id = summary_id,
+            source = source,
+        );
+        let conn = pool.get().await?;
+        let n = query.execute(&conn).await?;
+        if n == 0 {
+            return Ok(false);
+        }
+        Self::apply_preferred(pool, &[summary_id]).await?;
+        Ok(true)
+    }
+
+    /// Overwrite summary heart rate metrics of `summary_ids` with those of the
+    /// preferred stream, needed after summaries are regenerated from the
+    /// watch files
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn apply_preferred(pool: &PgPool, summary_ids: &[Uuid]) -> Result<u64, Error> {
+        if summary_ids.is_empty() {
+            return Ok(0);
+        }
+        let query = query!(
+            "
+                UPDATE garmin_summary a
+                SET total_hr_dur = b.avg_heart_rate * a.total_hr_dis
+                FROM heartrate_streams b
+                WHERE a.id = b.summary_id
+                  AND a.id = ANY($summary_ids)
+                  AND b.preferred
+                  AND a.total_hr_dur != b.avg_heart_rate * a.total_hr_dis
+            ",
+            summary_ids = summary_ids,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Average weighted by the time each sample is held, a single sample is
+/// returned as is
+#[must_use]
+pub fn time_weighted_average(offsets: &[f64], values: &[f64]) -> f64 {
+    let mut total = 0.0;
+    let mut duration = 0.0;
+    for (t, v) in offsets.windows(2).zip(values.iter()) {
+        let dt = t[1] - t[0];
+        if dt > 0.0 {
+            total += v * dt;
+            duration += dt;
+        }
+    }
+    if duration > 0.0 {
+        total / duration
+    } else {
+        values.first().copied().unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::{macros::datetime, Duration};
+    use uuid::Uuid;
+
+    use crate::heartrate_stream::{time_weighted_average, HeartRateStream};
+
+    #[test]
+    fn test_time_weighted_average() {
+        assert_abs_diff_eq!(time_weighted_average(&[], &[]), 0.0);
+        assert_abs_diff_eq!(time_weighted_average(&[0.0], &[120.0]), 120.0);
+        assert_abs_diff_eq!(
+            time_weighted_average(&[0.0, 10.0, 40.0], &[100.0, 140.0, 200.0]),
+            130.0
+        );
+    }
+
+    #[test]
+    fn test_heartrate_stream_new() {
+        let begin = datetime!(2024-05-01 12:00:00 UTC);
+        let samples = [
+            (begin + Duration::seconds(5), 100.0),
+            (begin + Duration::seconds(15), 140.0),
+            (begin + Duration::seconds(45), 150.0),
+        ];
+        let stream = HeartRateStream::new(Uuid::new_v4(), "chest_strap", "hrm.fit", &samples);
+        assert_eq!(stream.offsets, vec![0.0, 10.0, 40.0]);
+        assert_abs_diff_eq!(stream.avg_heart_rate, 130.0);
+        assert_eq!(stream.end_datetime(), begin + Duration::seconds(45));
+        let values = stream.plot_values(begin);
+        assert_abs_diff_eq!(values[0].0, 5.0 / 60.0);
+        assert_abs_diff_eq!(values[2].0, 45.0 / 60.0);
+    }
+}
@@ -0,0 +1,68 @@
+use time::{OffsetDateTime, Time};
+
+/// Where a page of a table ordered by datetime starts.  Skipping rows with
+/// `Offset` gets slower the deeper the page, `After` seeks straight to the
+/// first row past the last row of the previous page using the index on the
+/// datetime column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageStart {
+    Offset(usize),
+    /// `id` breaks ties between rows sharing a datetime, it's ignored for
+    /// tables where the datetime is unique
+    After {
+        datetime: OffsetDateTime,
+        id: i64,
+    },
+}
+
+impl PageStart {
+    #[must_use]
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::Offset(offset) => Some(*offset),
+            Self::After { .. } => None,
+        }
+    }
+
+    /// Keyset of a row of a table keyed by date alone
+    #[must_use]
+    pub fn after_date(date: time::Date) -> Self {
+        Self::After {
+            datetime: date.with_time(Time::MIDNIGHT).assume_utc(),
+            id: 0,
+        }
+    }
+}
+
+impl From<usize> for PageStart {
+    fn from(offset: usize) -> Self {
+        Self::Offset(offset)
+    }
+}
+
+/// Rows of a table which can be paged through by keyset
+pub trait KeysetRow {
+    /// Start of the page following this row
+    fn page_after(&self) -> PageStart;
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use crate::keyset::PageStart;
+
+    #[test]
+    fn test_page_start() {
+        assert_eq!(PageStart::from(20).offset(), Some(20));
+        let start = PageStart::after_date(date!(2024 - 03 - 01));
+        assert_eq!(
+            start,
+            PageStart::After {
+                datetime: datetime!(2024-03-01 00:00:00 UTC),
+                id: 0
+            }
+        );
+        assert_eq!(start.offset(), None);
+    }
+}
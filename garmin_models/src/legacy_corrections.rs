@@ -0,0 +1,287 @@
+use anyhow::{format_err, Error};
+use rusqlite::{types::Value, Connection, OpenFlags};
+use stack_string::{format_sstr, StackString};
+use std::{fs, path::Path};
+
+use garmin_lib::date_time_wrapper::{iso8601::convert_str_to_datetime, DateTimeWrapper};
+use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
+
+use crate::garmin_correction_lap::{GarminCorrectionLap, GarminCorrectionMap};
+
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// Tables the python garmin-app kept its lap corrections in
+const LEGACY_TABLES: [&str; 2] = ["garmin_corrections_laps", "garmin_corrections"];
+
+/// Distances and durations closer than this are the same correction
+const TOLERANCE: f64 = 1e-6;
+
+/// A lap which already has a correction with different values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CorrectionConflict {
+    pub existing: GarminCorrectionLap,
+    pub imported: GarminCorrectionLap,
+}
+
+impl CorrectionConflict {
+    #[must_use]
+    pub fn describe(&self) -> StackString {
+        fn value(x: Option<f64>) -> StackString {
+            x.map_or_else(|| "-".into(), |x| format_sstr!("{x:.3}"))
+        }
+        fn sport(s: Option<SportTypes>) -> StackString {
+            s.map_or_else(|| "-".into(), StackString::from_display)
+        }
+        let (e, i) = (&self.existing, &self.imported);
+        format_sstr!(
+            "conflict {} lap {}: distance {} -> {}, duration {} -> {}, sport {} -> {}",
+            e.start_time,
+            e.lap_number,
+            value(e.distance),
+            value(i.distance),
+            value(e.duration),
+            value(i.duration),
+            sport(e.sport),
+            sport(i.sport),
+        )
+    }
+}
+
+/// Result of comparing legacy corrections with those in the db, `new`
+/// holds corrections to write (including laps where the legacy file only
+/// fills in missing values)
+#[derive(Debug, Default)]
+pub struct LegacyImport {
+    pub new: GarminCorrectionMap,
+    pub unchanged: usize,
+    pub conflicts: Vec<CorrectionConflict>,
+}
+
+impl LegacyImport {
+    /// Legacy values win for fields set in both, fields only set on the
+    /// existing correction are kept
+    #[must_use]
+    pub fn compare(imported: GarminCorrectionMap, existing: &GarminCorrectionMap) -> Self {
+        let mut result = Self::default();
+        for (key, corr) in imported {
+            let current = match existing.get(&key) {
+                Some(current) => current,
+                None => {
+                    result.new.insert(key, corr);
+                    continue;
+                }
+            };
+            let merged = GarminCorrectionLap {
+                id: current.id,
+                sport: corr.sport.or(current.sport),
+                distance: corr.distance.or(current.distance),
+                duration: corr.duration.or(current.duration),
+                summary_id: current.summary_id,
+                pool_length: corr.pool_length.or(current.pool_length),
+                recorded_pool_length: corr.recorded_pool_length.or(current.recorded_pool_length),
+                ..corr
+            };
+            let differs = |a: Option<f64>, b: Option<f64>| match (a, b) {
+                (Some(a), Some(b)) => (a - b).abs() > TOLERANCE,
+                _ => false,
+            };
+            if differs(current.distance, corr.distance)
+                || differs(current.duration, corr.duration)
+                || matches!((current.sport, corr.sport), (Some(a), Some(b)) if a != b)
+            {
+                result.conflicts.push(CorrectionConflict {
+                    existing: *current,
+                    imported: merged,
+                });
+            } else if merged == *current {
+                result.unchanged += 1;
+            } else {
+                result.new.insert(key, merged);
+            }
+        }
+        result
+            .conflicts
+            .sort_by_key(|c| (c.existing.start_time, c.existing.lap_number));
+        result
+    }
+
+    /// Write new corrections, and conflicting ones when `overwrite` is set,
+    /// then link them to their activities
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn apply(&self, pool: &PgPool, overwrite: bool) -> Result<usize, Error> {
+        let mut corr_map = self.new.clone();
+        if overwrite {
+            corr_map.extend(
+                self.conflicts
+                    .iter()
+                    .map(|c| ((c.imported.start_time, c.imported.lap_number), c.imported)),
+            );
+        }
+        GarminCorrectionLap::dump_corrections_to_db(&corr_map, pool).await?;
+        GarminCorrectionLap::fix_corrections_in_db(pool).await?;
+        Ok(corr_map.len())
+    }
+}
+
+/// The python tool wrote either iso8601 or sqlite `datetime` strings
+fn parse_legacy_time(s: &str) -> Result<DateTimeWrapper, Error> {
+    let s = s.trim().replacen(' ', "T", 1);
+    let has_offset = s.ends_with('Z') || s.get(19..).is_some_and(|t| t.contains(['+', '-']));
+    let s = if has_offset { s } else { format!("{s}Z") };
+    convert_str_to_datetime(&s).map(Into::into)
+}
+
+fn value_to_f64(value: Value) -> Option<f64> {
+    match value {
+        Value::Real(x) => Some(x),
+        Value::Integer(x) => Some(x as f64),
+        Value::Text(x) => x.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Read the lap corrections table of a garmin-app sqlite database, only
+/// `start_time` and `lap_number` columns are required
+/// # Errors
+/// Return error if the database can't be read or has no corrections table
+pub fn corr_map_from_sqlite(path: &Path) -> Result<GarminCorrectionMap, Error> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let table = LEGACY_TABLES
+        .iter()
+        .find(|t| {
+            conn.query_row(
+                "SELECT count(*) FROM sqlite_master WHERE type='table' AND name=?1",
+                [t],
+                |row| row.get::<_, i64>(0),
+            )
+            .is_ok_and(|c| c > 0)
+        })
+        .ok_or_else(|| format_err!("No corrections table in {path:?}"))?;
+    let columns: Vec<String> = conn
+        .prepare(&format!("PRAGMA table_info({table})"))?
+        .query_map([], |row| row.get(1))?
+        .collect::<Result<_, _>>()?;
+    let column = |name: &str| {
+        if columns.iter().any(|c| c == name) {
+            name
+        } else {
+            "NULL"
+        }
+    };
+    let query = format!(
+        "SELECT start_time, lap_number, {}, {}, {} FROM {table}",
+        column("distance"),
+        column("duration"),
+        column("sport"),
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i32>(1)?,
+            row.get::<_, Value>(2)?,
+            row.get::<_, Value>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+    let mut corr_list = Vec::new();
+    for row in rows {
+        let (start_time, lap_number, distance, duration, sport) = row?;
+        let mut corr = GarminCorrectionLap::new()
+            .with_start_time(parse_legacy_time(&start_time)?)
+            .with_lap_number(lap_number);
+        corr.distance = value_to_f64(distance);
+        corr.duration = value_to_f64(duration);
+        corr.sport = sport.and_then(|s| s.parse().ok());
+        corr_list.push(corr);
+    }
+    Ok(GarminCorrectionLap::map_from_vec(corr_list))
+}
+
+/// Read a garmin-app corrections file, either its json dump or its sqlite
+/// database (detected from the file header)
+/// # Errors
+/// Return error if the file can't be read or parsed
+pub fn read_legacy_corrections(path: &Path) -> Result<GarminCorrectionMap, Error> {
+    let buffer = fs::read(path)?;
+    if buffer.starts_with(SQLITE_HEADER) {
+        corr_map_from_sqlite(path)
+    } else {
+        GarminCorrectionLap::corr_map_from_buffer(&buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use rusqlite::Connection;
+    use tempfile::NamedTempFile;
+
+    use garmin_lib::date_time_wrapper::iso8601::convert_str_to_datetime;
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::{
+        garmin_correction_lap::GarminCorrectionLap,
+        legacy_corrections::{read_legacy_corrections, LegacyImport},
+    };
+
+    #[test]
+    fn test_legacy_json_conflicts() -> Result<(), Error> {
+        let imported = read_legacy_corrections("../tests/data/garmin_corrections.json".as_ref())?;
+        assert!(!imported.is_empty());
+        let start_time = convert_str_to_datetime("2012-03-13T18:25:14Z")?.into();
+        let mut existing = imported.clone();
+        let same = existing[&(start_time, 0)];
+        existing.insert((start_time, 0), same.with_sport(SportTypes::Running));
+        let changed = existing[&(start_time, 1)].with_distance(0.5);
+        existing.insert((start_time, 1), changed);
+
+        let result = LegacyImport::compare(imported.clone(), &existing);
+        assert!(result.new.is_empty());
+        assert_eq!(result.unchanged, imported.len() - 1);
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = &result.conflicts[0];
+        assert_eq!(conflict.existing.distance, Some(0.5));
+        assert_eq!(conflict.imported.distance, Some(0.294));
+        assert_eq!(conflict.imported.id, changed.id);
+
+        let result = LegacyImport::compare(imported.clone(), &Default::default());
+        assert_eq!(result.new.len(), imported.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_sqlite() -> Result<(), Error> {
+        let file = NamedTempFile::new()?;
+        let conn = Connection::open(file.path())?;
+        conn.execute_batch(
+            "
+            CREATE TABLE garmin_corrections_laps (
+                id INTEGER PRIMARY KEY,
+                start_time TEXT,
+                lap_number INTEGER,
+                distance REAL,
+                duration REAL
+            );
+            INSERT INTO garmin_corrections_laps (start_time, lap_number, distance, duration)
+            VALUES ('2011-07-04 08:58:27', 0, 3.10685596118667, NULL),
+                   ('2012-03-13T18:25:14Z', 1, 0.294, 120);
+        ",
+        )?;
+        drop(conn);
+
+        let corr_map = read_legacy_corrections(file.path())?;
+        assert_eq!(corr_map.len(), 2);
+        let corr: GarminCorrectionLap = corr_map
+            .values()
+            .copied()
+            .find(|c| c.lap_number == 1)
+            .unwrap();
+        assert_eq!(corr.distance, Some(0.294));
+        assert_eq!(corr.duration, Some(120.0));
+        let start_time = convert_str_to_datetime("2011-07-04T08:58:27Z")?.into();
+        assert_eq!(corr_map[&(start_time, 0)].duration, None);
+        Ok(())
+    }
+}
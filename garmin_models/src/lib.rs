@@ -7,14 +7,59 @@
 #![allow(clippy::similar_names)]
 #![allow(clippy::unsafe_derive_deserialize)]
 
+pub mod activity_cleanup;
+pub mod activity_distribution;
+pub mod activity_location;
+pub mod activity_rpe;
+pub mod admin_stats;
+pub mod biomarker;
+pub mod cache_store;
+pub mod calorie_model;
+pub mod clothing_log;
+pub mod commute;
+pub mod course_difficulty;
+pub mod coverage_gap;
+pub mod demo_anonymizer;
+pub mod device_import;
+pub mod elevation_profile;
+pub mod filter_history;
 pub mod fitbit_activity;
 pub mod garmin_connect_activity;
+pub mod garmin_connect_activity_detail;
 pub mod garmin_connect_har_file;
+pub mod garmin_connect_wellness;
 pub mod garmin_correction_lap;
 pub mod garmin_file;
 pub mod garmin_lap;
 pub mod garmin_point;
 pub mod garmin_summary;
 pub mod garmin_sync;
+pub mod heart_rate_zones;
+pub mod heartrate_stream;
+pub mod keyset;
+pub mod legacy_corrections;
+pub mod milestone;
+pub mod notifier;
+pub mod osm_client;
+pub mod power_curve;
+pub mod power_threshold;
+pub mod processing_lock;
+pub mod provenance;
+pub mod quarantined_file;
+pub mod race_forecast;
+pub mod ramp_rate;
+pub mod rest_days;
+pub mod route_match;
+pub mod split_differential;
+pub mod stat_source;
 pub mod strava_activities_har_file;
 pub mod strava_activity;
+pub mod strava_link;
+pub mod strava_title;
+pub mod surface_type;
+pub mod sync_status;
+pub mod threshold_heart_rate;
+pub mod training_pattern;
+pub mod trip;
+pub mod user_preferences;
+pub mod yearly_comparison;
@@ -0,0 +1,297 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::collections::HashMap;
+use time::{Date, OffsetDateTime};
+use uuid::Uuid;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::{garmin_util::METERS_PER_MILE, pgpool::PgPool};
+
+pub const ACTIVITY_COUNT: &str = "activity_count";
+pub const TOTAL_DISTANCE: &str = "total_distance";
+pub const ANNIVERSARY: &str = "anniversary";
+/// Sport of milestones counted over every activity
+pub const ALL_SPORTS: &str = "all";
+
+/// Round numbers used as count and distance milestones: 100, 250, 500,
+/// 1000, 2500, 5000, 10000 ...
+#[must_use]
+pub fn is_round_number(value: i64) -> bool {
+    let mut scale = 100;
+    while scale <= value {
+        if [scale, scale * 5 / 2, scale * 5].contains(&value) {
+            return true;
+        }
+        scale *= 10;
+    }
+    false
+}
+
+/// Largest round number in `(previous, current]`
+#[must_use]
+pub fn round_number_crossed(previous: f64, current: f64) -> Option<i64> {
+    let (previous, current) = (previous.floor() as i64, current.floor() as i64);
+    let mut scale = 100;
+    let mut crossed = None;
+    while scale <= current {
+        for threshold in [scale, scale * 5 / 2, scale * 5] {
+            if threshold > previous && threshold <= current {
+                crossed = Some(threshold);
+            }
+        }
+        scale *= 10;
+    }
+    crossed
+}
+
+/// Activity as seen by the milestone engine
+#[derive(FromSqlRow, Debug, Clone, PartialEq)]
+pub struct MilestoneActivity {
+    pub id: Uuid,
+    pub begin_datetime: DateTimeWrapper,
+    pub sport: StackString,
+    pub total_distance: f64,
+}
+
+impl MilestoneActivity {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, begin_datetime, sport, total_distance
+                FROM garmin_summary
+                ORDER BY begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+/// Nth activity, round number of total miles or yearly anniversary of the
+/// first activity, per sport and over all sports
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Milestone {
+    pub kind: StackString,
+    pub sport: StackString,
+    pub threshold: i64,
+    pub summary_id: Option<Uuid>,
+    pub achieved_at: DateTimeWrapper,
+}
+
+impl Milestone {
+    #[must_use]
+    pub fn description(&self) -> StackString {
+        let sport = if self.sport == ALL_SPORTS {
+            StackString::new()
+        } else {
+            format_sstr!("{} ", self.sport)
+        };
+        let threshold = self.threshold;
+        match self.kind.as_str() {
+            ACTIVITY_COUNT => format_sstr!("{threshold}th {sport}activity"),
+            TOTAL_DISTANCE => format_sstr!("{threshold} total {sport}miles"),
+            ANNIVERSARY => {
+                let s = if threshold == 1 { "" } else { "s" };
+                format_sstr!("{threshold} year{s} since the first activity")
+            }
+            kind => format_sstr!("{kind} {sport}{threshold}"),
+        }
+    }
+
+    /// Every milestone reached by `activities` (sorted by `begin_datetime`)
+    /// up to `now`
+    #[must_use]
+    pub fn find_milestones(activities: &[MilestoneActivity], now: OffsetDateTime) -> Vec<Self> {
+        let mut milestones = Vec::new();
+        let mut counts: HashMap<&str, (i64, f64)> = HashMap::new();
+        for activity in activities {
+            let miles = activity.total_distance / METERS_PER_MILE;
+            for sport in [ALL_SPORTS, activity.sport.as_str()] {
+                let (count, distance) = counts.entry(sport).or_default();
+                let previous = *distance;
+                *count += 1;
+                *distance += miles;
+                let mut push = |kind: &str, threshold: i64| {
+                    milestones.push(Self {
+                        kind: kind.into(),
+                        sport: sport.into(),
+                        threshold,
+                        summary_id: Some(activity.id),
+                        achieved_at: activity.begin_datetime,
+                    });
+                };
+                if is_round_number(*count) {
+                    push(ACTIVITY_COUNT, *count);
+                }
+                if let Some(threshold) = round_number_crossed(previous, *distance) {
+                    push(TOTAL_DISTANCE, threshold);
+                }
+            }
+        }
+        if let Some(first) = activities.first() {
+            let first = first.begin_datetime.to_offsetdatetime();
+            let mut years = 1;
+            while let Some(anniversary) = add_years(first.date(), years)
+                .map(|d| d.with_time(first.time()).assume_offset(first.offset()))
+            {
+                if anniversary > now {
+                    break;
+                }
+                milestones.push(Self {
+                    kind: ANNIVERSARY.into(),
+                    sport: ALL_SPORTS.into(),
+                    threshold: years.into(),
+                    summary_id: None,
+                    achieved_at: anniversary.into(),
+                });
+                years += 1;
+            }
+        }
+        milestones
+    }
+
+    /// Store milestones which haven't been recorded yet, returns the new
+    /// ones
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn insert_new(pool: &PgPool, milestones: &[Self]) -> Result<Vec<Self>, Error> {
+        let conn = pool.get().await?;
+        let mut inserted = Vec::new();
+        for milestone in milestones {
+            let query = query!(
+                "
+                    INSERT INTO milestones (kind, sport, threshold, summary_id, achieved_at)
+                    VALUES ($kind, $sport, $threshold, $summary_id, $achieved_at)
+                    ON CONFLICT (kind, sport, threshold) DO NOTHING
+                ",
+                kind = milestone.kind,
+                sport = milestone.sport,
+                threshold = milestone.threshold,
+                summary_id = milestone.summary_id,
+                achieved_at = milestone.achieved_at,
+            );
+            if query.execute(&conn).await? > 0 {
+                inserted.push(milestone.clone());
+            }
+        }
+        Ok(inserted)
+    }
+
+    /// Check the cumulative counters of all activities and record new
+    /// milestones, meant to run after each import
+    /// # Errors
+    /// Return error if db queries fail
+    pub async fn check_milestones(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let activities = MilestoneActivity::read_from_db(pool).await?;
+        let milestones = Self::find_milestones(&activities, OffsetDateTime::now_utc());
+        Self::insert_new(pool, &milestones).await
+    }
+
+    /// Milestones achieved on or after `since`, newest first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool, since: Option<Date>) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT kind, sport, threshold, summary_id, achieved_at
+                FROM milestones
+                WHERE $since::date IS NULL OR achieved_at >= $since
+                ORDER BY achieved_at DESC, threshold DESC
+            ",
+            since = since,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+fn add_years(date: Date, years: i32) -> Option<Date> {
+    let year = date.year() + years;
+    // Feb 29th anniversaries fall on Mar 1st in other years
+    Date::from_calendar_date(year, date.month(), date.day())
+        .ok()
+        .or_else(|| {
+            Date::from_calendar_date(year, date.month(), 28)
+                .ok()?
+                .next_day()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+    use uuid::Uuid;
+
+    use garmin_utils::garmin_util::METERS_PER_MILE;
+
+    use crate::milestone::{
+        is_round_number, round_number_crossed, Milestone, MilestoneActivity, ACTIVITY_COUNT,
+        ALL_SPORTS, ANNIVERSARY, TOTAL_DISTANCE,
+    };
+
+    #[test]
+    fn test_round_numbers() {
+        for value in [100, 250, 500, 1000, 2500, 5000, 10000] {
+            assert!(is_round_number(value));
+        }
+        for value in [1, 99, 200, 750, 2000, 9999] {
+            assert!(!is_round_number(value));
+        }
+        assert_eq!(round_number_crossed(99.5, 100.2), Some(100));
+        assert_eq!(round_number_crossed(9990.0, 10010.0), Some(10000));
+        assert_eq!(round_number_crossed(100.5, 200.0), None);
+    }
+
+    #[test]
+    fn test_find_milestones() {
+        let start = datetime!(2020-02-29 12:00:00 UTC);
+        // one 10.5 mile run a day
+        let activities: Vec<_> = (0..100)
+            .map(|i| MilestoneActivity {
+                id: Uuid::new_v4(),
+                begin_datetime: (start + Duration::days(i)).into(),
+                sport: "running".into(),
+                total_distance: 10.5 * METERS_PER_MILE,
+            })
+            .collect();
+        let now = datetime!(2022-03-02 00:00:00 UTC);
+        let milestones = Milestone::find_milestones(&activities, now);
+
+        let count: Vec<_> = milestones
+            .iter()
+            .filter(|m| m.kind == ACTIVITY_COUNT)
+            .collect();
+        assert_eq!(count.len(), 2);
+        assert_eq!(count[0].sport, ALL_SPORTS);
+        assert_eq!(count[1].description(), "100th running activity");
+        assert_eq!(count[1].summary_id, Some(activities[99].id));
+
+        // 100, 250, 500 and 1000 miles
+        let distance: Vec<_> = milestones
+            .iter()
+            .filter(|m| m.kind == TOTAL_DISTANCE && m.sport == "running")
+            .collect();
+        assert_eq!(distance.len(), 4);
+        assert_eq!(distance[1].threshold, 250);
+        assert_eq!(distance[1].summary_id, Some(activities[23].id));
+        assert_eq!(distance[3].description(), "1000 total running miles");
+
+        let anniversaries: Vec<_> = milestones
+            .iter()
+            .filter(|m| m.kind == ANNIVERSARY)
+            .collect();
+        assert_eq!(anniversaries.len(), 2);
+        assert_eq!(
+            anniversaries[0].achieved_at,
+            datetime!(2021-03-01 12:00:00 UTC).into()
+        );
+        assert_eq!(
+            anniversaries[1].description(),
+            "2 years since the first activity"
+        );
+    }
+}
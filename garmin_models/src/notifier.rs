@@ -0,0 +1,313 @@
+use anyhow::{format_err, Error};
+use lettre::{
+    transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport, Message,
+    Tokio1Executor,
+};
+use log::error;
+use postgres_query::query;
+use reqwest::Client;
+use serde::Serialize;
+use stack_string::{format_sstr, StackString};
+
+use garmin_lib::{
+    garmin_config::GarminConfig,
+    notification::{NotifyEvent, NotifyMatrix, NotifyProvider},
+};
+use garmin_utils::pgpool::PgPool;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub event: NotifyEvent,
+    pub title: StackString,
+    pub body: StackString,
+    /// Notifications with the same event and key are only sent once,
+    /// defaults to the title
+    pub dedupe_key: StackString,
+}
+
+impl Notification {
+    #[must_use]
+    pub fn new(
+        event: NotifyEvent,
+        title: impl Into<StackString>,
+        body: impl Into<StackString>,
+    ) -> Self {
+        let title = title.into();
+        Self {
+            event,
+            dedupe_key: title.clone(),
+            title,
+            body: body.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_dedupe_key(mut self, dedupe_key: impl Into<StackString>) -> Self {
+        self.dedupe_key = dedupe_key.into();
+        self
+    }
+
+    /// Record the notification as sent, returns false if it already was
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn record_sent(&self, pool: &PgPool) -> Result<bool, Error> {
+        let query = query!(
+            "
+                INSERT INTO sent_notifications (event, dedupe_key)
+                VALUES ($event, $dedupe_key)
+                ON CONFLICT (event, dedupe_key) DO NOTHING
+            ",
+            event = self.event.to_str(),
+            dedupe_key = self.dedupe_key,
+        );
+        let conn = pool.get().await?;
+        let inserted = query.execute(&conn).await?;
+        Ok(inserted > 0)
+    }
+
+    /// Forget a notification which couldn't be delivered so it's retried
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn clear_sent(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM sent_notifications WHERE event = $event AND dedupe_key = $dedupe_key",
+            event = self.event.to_str(),
+            dedupe_key = self.dedupe_key,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// A configured delivery channel
+#[derive(Debug, Clone)]
+pub enum NotificationChannel {
+    Email {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+        from: StackString,
+        to: StackString,
+    },
+    Telegram {
+        bot_token: StackString,
+        chat_id: i64,
+    },
+    Ntfy {
+        url: StackString,
+    },
+}
+
+impl NotificationChannel {
+    #[must_use]
+    pub fn provider(&self) -> NotifyProvider {
+        match self {
+            Self::Email { .. } => NotifyProvider::Email,
+            Self::Telegram { .. } => NotifyProvider::Telegram,
+            Self::Ntfy { .. } => NotifyProvider::Ntfy,
+        }
+    }
+
+    /// # Errors
+    /// Return error if delivery fails
+    pub async fn send(&self, client: &Client, notification: &Notification) -> Result<(), Error> {
+        match self {
+            Self::Email {
+                transport,
+                from,
+                to,
+            } => {
+                let message = Message::builder()
+                    .from(from.parse()?)
+                    .to(to.parse()?)
+                    .subject(notification.title.as_str())
+                    .body(notification.body.to_string())?;
+                transport.send(message).await?;
+            }
+            Self::Telegram { bot_token, chat_id } => {
+                #[derive(Serialize)]
+                struct SendMessage<'a> {
+                    chat_id: i64,
+                    text: &'a str,
+                }
+
+                let url = format_sstr!("https://api.telegram.org/bot{bot_token}/sendMessage");
+                let text = format_sstr!("{}\n{}", notification.title, notification.body);
+                client
+                    .post(url.as_str())
+                    .json(&SendMessage {
+                        chat_id: *chat_id,
+                        text: &text,
+                    })
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            Self::Ntfy { url } => {
+                client
+                    .post(url.as_str())
+                    .header("Title", notification.title.as_str())
+                    .header("Tags", notification.event.to_str())
+                    .body(notification.body.to_string())
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends notifications through every configured channel enabled for the
+/// event type in `notify_matrix`
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    pub channels: Vec<NotificationChannel>,
+    pub matrix: NotifyMatrix,
+    pub client: Client,
+}
+
+impl Notifier {
+    /// # Errors
+    /// Return error if the smtp or ntfy settings are invalid
+    pub fn from_config(config: &GarminConfig) -> Result<Self, Error> {
+        let mut channels = Vec::new();
+        if let (Some(host), Some(from), Some(to)) = (
+            &config.smtp_host,
+            &config.notify_email_from,
+            &config.notify_email_to,
+        ) {
+            let mut builder =
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?.port(config.smtp_port);
+            if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password)
+            {
+                builder = builder
+                    .credentials(Credentials::new(username.to_string(), password.to_string()));
+            }
+            channels.push(NotificationChannel::Email {
+                transport: builder.build(),
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+        if let (Some(bot_token), Some(chat_id)) =
+            (&config.telegram_bot_token, config.notify_telegram_chat_id)
+        {
+            channels.push(NotificationChannel::Telegram {
+                bot_token: bot_token.clone(),
+                chat_id,
+            });
+        }
+        if let Some(topic) = &config.ntfy_topic {
+            let endpoint = config
+                .ntfy_endpoint
+                .as_ref()
+                .ok_or_else(|| format_err!("No ntfy endpoint"))?;
+            let url = endpoint.join(topic)?;
+            channels.push(NotificationChannel::Ntfy {
+                url: url.as_str().into(),
+            });
+        }
+        Ok(Self {
+            channels,
+            matrix: config.notify_matrix.clone(),
+            client: Client::new(),
+        })
+    }
+
+    /// Channels which will receive notifications for `event`
+    pub fn get_channels(&self, event: NotifyEvent) -> impl Iterator<Item = &NotificationChannel> {
+        self.channels
+            .iter()
+            .filter(move |c| self.matrix.is_enabled(event, c.provider()))
+    }
+
+    /// Deliver `notification`, a failing channel doesn't stop delivery to
+    /// the others, returns a line for each attempt and whether any succeeded
+    pub async fn notify(&self, notification: &Notification) -> (Vec<StackString>, bool) {
+        let mut output = Vec::new();
+        let mut delivered = false;
+        for channel in self.get_channels(notification.event) {
+            let provider = channel.provider();
+            match channel.send(&self.client, notification).await {
+                Ok(()) => {
+                    delivered = true;
+                    output.push(format_sstr!(
+                        "sent {} notification via {provider}",
+                        notification.event
+                    ));
+                }
+                Err(e) => {
+                    error!("failed to send {} via {provider}: {e}", notification.event);
+                    output.push(format_sstr!(
+                        "failed to send {} notification via {provider}: {e}",
+                        notification.event
+                    ));
+                }
+            }
+        }
+        (output, delivered)
+    }
+
+    /// Deliver `notification` unless it was sent before, failures are logged
+    /// rather than returned so they never abort the sync that raised them
+    pub async fn notify_once(
+        &self,
+        pool: &PgPool,
+        notification: &Notification,
+    ) -> Vec<StackString> {
+        if self.get_channels(notification.event).next().is_none() {
+            return Vec::new();
+        }
+        match notification.record_sent(pool).await {
+            Ok(true) => {}
+            Ok(false) => return Vec::new(),
+            Err(e) => {
+                error!("failed to record {} notification: {e}", notification.event);
+                return Vec::new();
+            }
+        }
+        let (output, delivered) = self.notify(notification).await;
+        if !delivered {
+            if let Err(e) = notification.clear_sent(pool).await {
+                error!("failed to clear {} notification: {e}", notification.event);
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use garmin_lib::notification::{NotifyEvent, NotifyProvider};
+
+    use crate::notifier::{Notification, NotificationChannel, Notifier};
+
+    #[test]
+    fn test_notifier_channels() {
+        let notifier = Notifier {
+            channels: vec![
+                NotificationChannel::Telegram {
+                    bot_token: "token".into(),
+                    chat_id: 8_675_309,
+                },
+                NotificationChannel::Ntfy {
+                    url: "https://ntfy.sh/garmin".into(),
+                },
+            ],
+            matrix: "milestone=ntfy;ramp_rate=none".parse().unwrap(),
+            ..Notifier::default()
+        };
+        let providers: Vec<_> = notifier
+            .get_channels(NotifyEvent::Milestone)
+            .map(NotificationChannel::provider)
+            .collect();
+        assert_eq!(providers, vec![NotifyProvider::Ntfy]);
+        assert_eq!(notifier.get_channels(NotifyEvent::RampRate).count(), 0);
+        assert_eq!(notifier.get_channels(NotifyEvent::SyncFailure).count(), 2);
+
+        let notification = Notification::new(NotifyEvent::Milestone, "First marathon", "body");
+        assert_eq!(notification.dedupe_key, "First marathon");
+        let notification = notification.with_dedupe_key("2024-01-01");
+        assert_eq!(notification.dedupe_key, "2024-01-01");
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::Error;
+use reqwest::Client;
+use stack_string::{format_sstr, StackString};
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+/// The public nominatim and overpass instances allow at most one request
+/// per second
+pub const OSM_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Client for openstreetmap services which identifies itself as their usage
+/// policies require and spaces requests `OSM_REQUEST_INTERVAL` apart
+#[derive(Debug)]
+pub struct OsmClient {
+    client: Client,
+    next_request: Mutex<Option<Instant>>,
+}
+
+impl OsmClient {
+    /// `contact` (an email address or url) is added to the user agent
+    /// # Errors
+    /// Return error if the client can't be built
+    pub fn new(contact: Option<&str>) -> Result<Self, Error> {
+        let client = Client::builder()
+            .user_agent(Self::user_agent(contact).as_str())
+            .build()?;
+        Ok(Self {
+            client,
+            next_request: Mutex::new(None),
+        })
+    }
+
+    #[must_use]
+    pub fn user_agent(contact: Option<&str>) -> StackString {
+        let version = env!("CARGO_PKG_VERSION");
+        match contact {
+            Some(contact) => format_sstr!(
+                "garmin_rust/{version} (+https://github.com/ddboline/garmin_rust; {contact})"
+            ),
+            None => {
+                format_sstr!("garmin_rust/{version} (+https://github.com/ddboline/garmin_rust)")
+            }
+        }
+    }
+
+    /// Wait for the next request slot and return the client to send it with
+    pub async fn get(&self) -> &Client {
+        let wait = {
+            let mut next_request = self
+                .next_request
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+            let slot = next_request.map_or(now, |t| t.max(now));
+            *next_request = Some(slot + OSM_REQUEST_INTERVAL);
+            slot - now
+        };
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+        &self.client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use std::time::Instant;
+
+    use crate::osm_client::{OsmClient, OSM_REQUEST_INTERVAL};
+
+    #[tokio::test]
+    async fn test_osm_client() -> Result<(), Error> {
+        let user_agent = OsmClient::user_agent(Some("me@example.com"));
+        assert!(user_agent.starts_with("garmin_rust/"));
+        assert!(user_agent.ends_with("; me@example.com)"));
+
+        let client = OsmClient::new(None)?;
+        let start = Instant::now();
+        client.get().await;
+        client.get().await;
+        assert!(start.elapsed() >= OSM_REQUEST_INTERVAL);
+        Ok(())
+    }
+}
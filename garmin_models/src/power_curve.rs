@@ -0,0 +1,547 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{fmt, str::FromStr};
+use time::{Date, Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
+
+use crate::garmin_file::GarminFile;
+
+/// Window lengths in seconds of the mean-maximal curves, 1s to 3h
+pub const CURVE_DURATIONS: [i32; 15] = [
+    1, 5, 10, 15, 30, 60, 120, 300, 600, 1200, 1800, 3600, 5400, 7200, 10800,
+];
+
+/// Longest gap in seconds between power samples which is treated as
+/// continuous riding, longer gaps count as zero power
+const MAX_POWER_GAP: f64 = 5.0;
+
+/// Length in seconds of the rolling average normalized power is built from
+const NORMALIZED_POWER_WINDOW: usize = 30;
+
+/// Quantity of a mean-maximal curve, `Power` values are watts and `Pace`
+/// values are speeds in m/s so that larger is always better
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum CurveMetric {
+    Power,
+    Pace,
+}
+
+impl Default for CurveMetric {
+    fn default() -> Self {
+        Self::Pace
+    }
+}
+
+impl CurveMetric {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Power => "power",
+            Self::Pace => "pace",
+        }
+    }
+}
+
+impl fmt::Display for CurveMetric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for CurveMetric {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "power" => Ok(Self::Power),
+            "pace" | "speed" => Ok(Self::Pace),
+            _ => Err(format_err!("{s} is not a valid curve metric")),
+        }
+    }
+}
+
+/// Span over which curves are aggregated, compared against the span
+/// immediately before it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CurvePeriod {
+    SixWeeks,
+    Season,
+}
+
+impl Default for CurvePeriod {
+    fn default() -> Self {
+        Self::SixWeeks
+    }
+}
+
+impl CurvePeriod {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::SixWeeks => "six_weeks",
+            Self::Season => "season",
+        }
+    }
+
+    /// Returns ((start, end), (previous start, previous end)), a season is
+    /// the calendar year so far compared to the whole previous year
+    #[must_use]
+    pub fn ranges(
+        self,
+        now: OffsetDateTime,
+    ) -> (
+        (OffsetDateTime, OffsetDateTime),
+        (OffsetDateTime, OffsetDateTime),
+    ) {
+        match self {
+            Self::SixWeeks => {
+                let start = now - Duration::weeks(6);
+                ((start, now), (start - Duration::weeks(6), start))
+            }
+            Self::Season => {
+                let year = now.year();
+                let start = Date::from_ordinal_date(year, 1)
+                    .unwrap_or(Date::MIN)
+                    .midnight()
+                    .assume_offset(now.offset());
+                let prev_start = Date::from_ordinal_date(year - 1, 1)
+                    .unwrap_or(Date::MIN)
+                    .midnight()
+                    .assume_offset(now.offset());
+                ((start, now), (prev_start, start))
+            }
+        }
+    }
+}
+
+impl fmt::Display for CurvePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for CurvePeriod {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "six_weeks" | "6weeks" | "6w" => Ok(Self::SixWeeks),
+            "season" | "year" => Ok(Self::Season),
+            _ => Err(format_err!("{s} is not a valid curve period")),
+        }
+    }
+}
+
+#[derive(FromSqlRow)]
+struct CurveRow {
+    duration: i32,
+    value: f64,
+}
+
+#[derive(FromSqlRow)]
+struct MissingCurveRow {
+    id: Uuid,
+    filename: StackString,
+}
+
+/// Best average power or speed of an activity for each of
+/// `CURVE_DURATIONS`, stored in `power_curves`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PowerCurve {
+    pub summary_id: Uuid,
+    pub metric: CurveMetric,
+    pub points: Vec<(i32, f64)>,
+}
+
+impl PowerCurve {
+    /// Curves for every metric with data in `gfile`
+    #[must_use]
+    pub fn from_gfile(summary_id: Uuid, gfile: &GarminFile) -> Vec<Self> {
+        let mut curves = Vec::new();
+        let power: Vec<_> = gfile
+            .points
+            .iter()
+            .filter_map(|p| p.power.map(|w| (p.duration_from_begin, w)))
+            .collect();
+        if !power.is_empty() {
+            let points = mean_maximal(&cumulative_power(&power), &CURVE_DURATIONS);
+            if !points.is_empty() {
+                curves.push(Self {
+                    summary_id,
+                    metric: CurveMetric::Power,
+                    points,
+                });
+            }
+        }
+        let distance: Vec<_> = gfile
+            .points
+            .iter()
+            .filter_map(|p| p.distance.map(|d| (p.duration_from_begin, d)))
+            .collect();
+        if !distance.is_empty() {
+            let points = mean_maximal(&cumulative_distance(&distance), &CURVE_DURATIONS);
+            if !points.is_empty() {
+                curves.push(Self {
+                    summary_id,
+                    metric: CurveMetric::Pace,
+                    points,
+                });
+            }
+        }
+        curves
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let conn = pool.get().await?;
+        let metric = self.metric.to_str();
+        for (duration, value) in &self.points {
+            let query = query!(
+                "
+                    INSERT INTO power_curves (summary_id, metric, duration, value)
+                    VALUES ($summary_id, $metric, $duration, $value)
+                    ON CONFLICT (summary_id, metric, duration) DO UPDATE
+                    SET value=EXCLUDED.value
+                ",
+                summary_id = self.summary_id,
+                metric = metric,
+                duration = duration,
+                value = value,
+            );
+            query.execute(&conn).await?;
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_by_summary_id(pool: &PgPool, summary_id: Uuid) -> Result<Vec<Self>, Error> {
+        let mut curves = Vec::new();
+        for metric in [CurveMetric::Power, CurveMetric::Pace] {
+            let query = query!(
+                "
+                    SELECT duration, value FROM power_curves
+                    WHERE summary_id = $summary_id AND metric = $metric
+                    ORDER BY duration
+                ",
+                summary_id = summary_id,
+                metric = metric.to_str(),
+            );
+            let conn = pool.get().await?;
+            let rows: Vec<CurveRow> = query.fetch(&conn).await?;
+            if !rows.is_empty() {
+                curves.push(Self {
+                    summary_id,
+                    metric,
+                    points: rows.into_iter().map(|r| (r.duration, r.value)).collect(),
+                });
+            }
+        }
+        Ok(curves)
+    }
+
+    /// Best value for each duration over all `sport` activities starting
+    /// between `start` and `end`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_best(
+        pool: &PgPool,
+        metric: CurveMetric,
+        sport: SportTypes,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> Result<Vec<(i32, f64)>, Error> {
+        let query = query!(
+            "
+                SELECT a.duration, max(a.value) as value
+                FROM power_curves a
+                JOIN garmin_summary b ON a.summary_id = b.id
+                WHERE a.metric = $metric
+                  AND b.sport = $sport
+                  AND b.begin_datetime >= $start
+                  AND b.begin_datetime < $end
+                GROUP BY a.duration
+                ORDER BY a.duration
+            ",
+            metric = metric.to_str(),
+            sport = sport,
+            start = start,
+            end = end,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<CurveRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.duration, r.value)).collect())
+    }
+
+    /// (id, filename) of running and biking activities which haven't been
+    /// analyzed yet, activities without any power or pace samples are only
+    /// read once
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_summaries(pool: &PgPool) -> Result<Vec<(Uuid, StackString)>, Error> {
+        let query = query!(
+            "
+                SELECT a.id, a.filename
+                FROM garmin_summary a
+                WHERE a.sport IN ('running', 'biking')
+                  AND NOT EXISTS (
+                    SELECT 1 FROM power_curves_analyzed b WHERE b.summary_id = a.id
+                  )
+                ORDER BY a.begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingCurveRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+
+    /// Record that the curves of `summary_id` are up to date, whether or not
+    /// any were found
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn mark_analyzed(pool: &PgPool, summary_id: Uuid) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO power_curves_analyzed (summary_id)
+                VALUES ($summary_id)
+                ON CONFLICT (summary_id) DO UPDATE SET analyzed_at=now()
+            ",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Drop the stored curves of re-imported activities so that the next sync
+    /// recomputes them from the new files
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn clear_analyzed(pool: &PgPool, summary_ids: &[Uuid]) -> Result<(), Error> {
+        if summary_ids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        tran.execute(
+            "DELETE FROM power_curves WHERE summary_id = ANY($1)",
+            &[&summary_ids],
+        )
+        .await?;
+        tran.execute(
+            "DELETE FROM power_curves_analyzed WHERE summary_id = ANY($1)",
+            &[&summary_ids],
+        )
+        .await?;
+        tran.commit().await?;
+        Ok(())
+    }
+}
+
+/// Average and normalized power of `gfile`, `None` without power data
+#[must_use]
+pub fn activity_power(gfile: &GarminFile) -> (Option<f64>, Option<f64>) {
+    let power: Vec<_> = gfile
+        .points
+        .iter()
+        .filter_map(|p| p.power.map(|w| (p.duration_from_begin, w)))
+        .collect();
+    if power.is_empty() {
+        return (None, None);
+    }
+    let cumulative = cumulative_power(&power);
+    (average_power(&cumulative), normalized_power(&cumulative))
+}
+
+/// Best average rate over each window of `durations` seconds, `cumulative`
+/// holds a running total sampled once per second, windows longer than the
+/// activity are skipped
+#[must_use]
+pub fn mean_maximal(cumulative: &[f64], durations: &[i32]) -> Vec<(i32, f64)> {
+    durations
+        .iter()
+        .filter_map(|&duration| {
+            let window = usize::try_from(duration).ok()?;
+            if window == 0 || window >= cumulative.len() {
+                return None;
+            }
+            let best = cumulative
+                .iter()
+                .zip(cumulative[window..].iter())
+                .map(|(begin, end)| end - begin)
+                .fold(f64::MIN, f64::max);
+            Some((duration, best / f64::from(duration)))
+        })
+        .collect()
+}
+
+/// Energy in joules at each second from (seconds since start, watts)
+/// samples, each sample is held until the next one unless the gap exceeds
+/// `MAX_POWER_GAP`
+#[must_use]
+pub fn cumulative_power(samples: &[(f64, f64)]) -> Vec<f64> {
+    let end = match samples.last() {
+        Some((t, _)) => *t,
+        None => return Vec::new(),
+    };
+    let mut cumulative = Vec::with_capacity(end as usize + 1);
+    let mut total = 0.0;
+    let mut idx = 0;
+    for second in 0..=(end as usize) {
+        let t = second as f64;
+        while idx + 1 < samples.len() && samples[idx + 1].0 <= t {
+            idx += 1;
+        }
+        if second > 0 {
+            let (sample_time, watts) = samples[idx];
+            if sample_time <= t && t - sample_time <= MAX_POWER_GAP {
+                total += watts;
+            }
+        }
+        cumulative.push(total);
+    }
+    cumulative
+}
+
+/// Average watts over the seconds of `cumulative` (from
+/// `cumulative_power`), stops count as zero power
+#[must_use]
+pub fn average_power(cumulative: &[f64]) -> Option<f64> {
+    let seconds = cumulative.len().checked_sub(1).filter(|s| *s > 0)?;
+    cumulative.last().map(|total| total / seconds as f64)
+}
+
+/// Normalized power of `cumulative` (from `cumulative_power`): the fourth
+/// root of the mean fourth power of the 30 second rolling average, which
+/// weighs hard surges the way they feel rather than averaging them out with
+/// the easy parts, `None` for anything shorter than the window
+#[must_use]
+pub fn normalized_power(cumulative: &[f64]) -> Option<f64> {
+    if cumulative.len() <= NORMALIZED_POWER_WINDOW {
+        return None;
+    }
+    let window = NORMALIZED_POWER_WINDOW as f64;
+    let fourth_powers: Vec<f64> = cumulative
+        .iter()
+        .zip(cumulative[NORMALIZED_POWER_WINDOW..].iter())
+        .map(|(begin, end)| ((end - begin) / window).powi(4))
+        .collect();
+    let mean = fourth_powers.iter().sum::<f64>() / fourth_powers.len() as f64;
+    Some(mean.powf(0.25))
+}
+
+/// Distance at each second linearly interpolated from (seconds since start,
+/// meters) samples
+#[must_use]
+pub fn cumulative_distance(samples: &[(f64, f64)]) -> Vec<f64> {
+    let end = match samples.last() {
+        Some((t, _)) => *t,
+        None => return Vec::new(),
+    };
+    let mut cumulative = Vec::with_capacity(end as usize + 1);
+    let mut idx = 0;
+    for second in 0..=(end as usize) {
+        let t = second as f64;
+        while idx + 1 < samples.len() && samples[idx + 1].0 <= t {
+            idx += 1;
+        }
+        let (t0, d0) = samples[idx];
+        let value = match samples.get(idx + 1) {
+            Some((t1, d1)) if *t1 > t0 && t > t0 => d0 + (d1 - d0) * (t - t0) / (t1 - t0),
+            _ => d0,
+        };
+        cumulative.push(value);
+    }
+    cumulative
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::macros::datetime;
+
+    use crate::power_curve::{
+        average_power, cumulative_distance, cumulative_power, mean_maximal, normalized_power,
+        CurveMetric, CurvePeriod,
+    };
+
+    #[test]
+    fn test_mean_maximal() {
+        let cumulative = [0.0, 100.0, 300.0, 600.0, 700.0, 800.0];
+        let curve = mean_maximal(&cumulative, &[1, 2, 5, 10]);
+        assert_eq!(curve.len(), 3);
+        assert_eq!(curve[0], (1, 300.0));
+        assert_eq!(curve[1], (2, 250.0));
+        assert_eq!(curve[2], (5, 160.0));
+    }
+
+    #[test]
+    fn test_cumulative_power() {
+        let samples = [(0.0, 100.0), (2.0, 200.0), (20.0, 300.0)];
+        let cumulative = cumulative_power(&samples);
+        assert_eq!(cumulative.len(), 21);
+        assert_abs_diff_eq!(cumulative[1], 100.0);
+        assert_abs_diff_eq!(cumulative[2], 300.0);
+        // the 200W sample is held for 5s, the rest of the gap counts as a stop
+        assert_abs_diff_eq!(cumulative[7], 1300.0);
+        assert_abs_diff_eq!(cumulative[19], 1300.0);
+        assert_abs_diff_eq!(cumulative[20], 1600.0);
+    }
+
+    #[test]
+    fn test_normalized_power() {
+        let steady: Vec<_> = (0..=120).map(|t| (f64::from(t), 200.0)).collect();
+        let cumulative = cumulative_power(&steady);
+        assert_abs_diff_eq!(average_power(&cumulative).unwrap(), 200.0);
+        assert_abs_diff_eq!(normalized_power(&cumulative).unwrap(), 200.0);
+
+        // one minute hard, one minute easy averages 200W but normalizes higher
+        let surges: Vec<_> = (0..=120)
+            .map(|t| (f64::from(t), if t <= 60 { 300.0 } else { 100.0 }))
+            .collect();
+        let cumulative = cumulative_power(&surges);
+        assert_abs_diff_eq!(average_power(&cumulative).unwrap(), 200.0);
+        assert!(normalized_power(&cumulative).unwrap() > 230.0);
+
+        let short = cumulative_power(&steady[..20]);
+        assert!(normalized_power(&short).is_none());
+        assert!(average_power(&short).is_some());
+        assert!(average_power(&[]).is_none());
+    }
+
+    #[test]
+    fn test_cumulative_distance() {
+        let samples = [(0.0, 0.0), (4.0, 20.0), (6.0, 40.0)];
+        let cumulative = cumulative_distance(&samples);
+        assert_eq!(cumulative.len(), 7);
+        assert_abs_diff_eq!(cumulative[2], 10.0);
+        assert_abs_diff_eq!(cumulative[5], 30.0);
+        assert_abs_diff_eq!(cumulative[6], 40.0);
+        let curve = mean_maximal(&cumulative, &[1, 5]);
+        assert_eq!(curve, vec![(1, 10.0), (5, 7.0)]);
+        assert_eq!("power".parse::<CurveMetric>().unwrap(), CurveMetric::Power);
+        assert!("watts".parse::<CurveMetric>().is_err());
+    }
+
+    #[test]
+    fn test_curve_period_ranges() {
+        let now = datetime!(2024-05-01 12:00:00 UTC);
+        let ((start, end), (prev_start, prev_end)) = CurvePeriod::Season.ranges(now);
+        assert_eq!(start, datetime!(2024-01-01 00:00:00 UTC));
+        assert_eq!(end, now);
+        assert_eq!(prev_start, datetime!(2023-01-01 00:00:00 UTC));
+        assert_eq!(prev_end, start);
+        let ((start, _), (prev_start, _)) = CurvePeriod::SixWeeks.ranges(now);
+        assert_eq!(start, datetime!(2024-03-20 12:00:00 UTC));
+        assert_eq!(prev_start, datetime!(2024-02-07 12:00:00 UTC));
+        assert_eq!(
+            "season".parse::<CurvePeriod>().unwrap(),
+            CurvePeriod::Season
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+use garmin_utils::pgpool::PgPool;
+
+/// Functional threshold power (watts) and anaerobic work capacity W'
+/// (joules) in effect from `effective_date`, stored in `power_thresholds`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PowerThreshold {
+    pub effective_date: Date,
+    pub ftp: f64,
+    pub w_prime: f64,
+}
+
+impl PowerThreshold {
+    /// # Errors
+    /// Return error if ftp or w' is not positive
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.ftp <= 0.0 || self.w_prime <= 0.0 {
+            Err(format_err!(
+                "Invalid ftp {} or w' {}",
+                self.ftp,
+                self.w_prime
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Threshold in effect on `date`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_for_date(pool: &PgPool, date: Date) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT * FROM power_thresholds
+                WHERE effective_date <= $date
+                ORDER BY effective_date DESC
+                LIMIT 1
+            ",
+            date = date,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM power_thresholds ORDER BY effective_date");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if validation or db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        self.validate()?;
+        let query = query!(
+            "
+                INSERT INTO power_thresholds (effective_date, ftp, w_prime)
+                VALUES ($effective_date, $ftp, $w_prime)
+                ON CONFLICT (effective_date) DO UPDATE
+                SET ftp=EXCLUDED.ftp, w_prime=EXCLUDED.w_prime
+            ",
+            effective_date = self.effective_date,
+            ftp = self.ftp,
+            w_prime = self.w_prime,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// W' balance in joules after each of the (seconds since start, watts)
+    /// samples, using the differential model: W' is depleted at the rate
+    /// power exceeds FTP and recovers proportionally to the remaining deficit
+    /// below it
+    #[must_use]
+    pub fn w_prime_balance(&self, samples: &[(f64, f64)]) -> Vec<f64> {
+        let mut balance = self.w_prime;
+        let mut last_time = None;
+        samples
+            .iter()
+            .map(|(t, watts)| {
+                let dt = last_time
+                    .replace(*t)
+                    .map_or(0.0, |last| (t - last).max(0.0));
+                if *watts > self.ftp {
+                    balance -= (watts - self.ftp) * dt;
+                } else {
+                    balance += (self.ftp - watts) * dt * (self.w_prime - balance) / self.w_prime;
+                }
+                balance
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::macros::date;
+
+    use crate::power_threshold::PowerThreshold;
+
+    #[test]
+    fn test_w_prime_balance() {
+        let threshold = PowerThreshold {
+            effective_date: date!(2024 - 01 - 01),
+            ftp: 250.0,
+            w_prime: 20_000.0,
+        };
+        assert!(threshold.validate().is_ok());
+        let samples = [(0.0, 400.0), (10.0, 400.0), (20.0, 150.0), (30.0, 250.0)];
+        let balance = threshold.w_prime_balance(&samples);
+        assert_abs_diff_eq!(balance[0], 20_000.0);
+        assert_abs_diff_eq!(balance[1], 18_500.0);
+        // recovery at 100W below ftp for 10s with 1500J of 20000J deficit
+        assert_abs_diff_eq!(balance[2], 18_500.0 + 1000.0 * 1500.0 / 20_000.0);
+        assert_abs_diff_eq!(balance[3], balance[2]);
+        let invalid = PowerThreshold {
+            ftp: 0.0,
+            ..threshold
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::Error;
+use log::debug;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::{PgClient, PgPool};
+
+/// Advisory lock key held while syncing or processing files ("GARM")
+pub const PROCESSING_LOCK_ID: i64 = 0x4741_524d;
+
+/// Returned when another process holds the processing lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessingInProgress;
+
+impl fmt::Display for ProcessingInProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "sync already in progress")
+    }
+}
+
+impl std::error::Error for ProcessingInProgress {}
+
+/// Whether `err` means another process is already syncing
+#[must_use]
+pub fn is_processing_in_progress(err: &Error) -> bool {
+    err.downcast_ref::<ProcessingInProgress>().is_some()
+}
+
+#[derive(FromSqlRow)]
+struct BoolResult {
+    result: bool,
+}
+
+/// Postgres session advisory lock, the connection holding it is kept out of
+/// the pool until the lock is released
+pub struct ProcessingLock {
+    conn: Option<PgClient>,
+}
+
+impl ProcessingLock {
+    /// # Errors
+    /// Return `ProcessingInProgress` if another session holds the lock, or
+    /// error if db query fails
+    pub async fn try_acquire(pool: &PgPool) -> Result<Self, Error> {
+        let query = query!(
+            "SELECT pg_try_advisory_lock($id) AS result",
+            id = PROCESSING_LOCK_ID
+        );
+        let conn = pool.get().await?;
+        let result: BoolResult = query.fetch_one(&conn).await?;
+        if result.result {
+            Ok(Self { conn: Some(conn) })
+        } else {
+            Err(ProcessingInProgress.into())
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn release(mut self) -> Result<(), Error> {
+        if let Some(conn) = self.conn.take() {
+            let query = query!(
+                "SELECT pg_advisory_unlock($id) AS result",
+                id = PROCESSING_LOCK_ID
+            );
+            let _: BoolResult = query.fetch_one(&conn).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ProcessingLock {
+    fn drop(&mut self) {
+        // not released (an error or panic while holding it), closing the
+        // connection instead of returning it to the pool frees the lock
+        if let Some(conn) = self.conn.take() {
+            debug!("closing connection holding processing lock");
+            drop(PgClient::take(conn));
+        }
+    }
+}
+
+/// Backend holding the processing lock
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    pub pid: i32,
+    pub application_name: Option<StackString>,
+    pub backend_start: Option<DateTimeWrapper>,
+    pub query_start: Option<DateTimeWrapper>,
+}
+
+impl LockHolder {
+    #[must_use]
+    pub fn describe(&self) -> StackString {
+        let started = self
+            .query_start
+            .or(self.backend_start)
+            .map_or_else(|| "-".into(), StackString::from_display);
+        format_sstr!(
+            "pid {} ({}) since {started}",
+            self.pid,
+            self.application_name
+                .as_ref()
+                .map_or("-", StackString::as_str),
+        )
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get(pool: &PgPool) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT a.pid,
+                       a.application_name,
+                       a.backend_start,
+                       a.query_start
+                FROM pg_locks l
+                JOIN pg_stat_activity a ON a.pid = l.pid
+                WHERE l.locktype = 'advisory'
+                  AND l.granted
+                  AND l.objsubid = 1
+                  AND ((l.classid::bigint << 32) | l.objid::bigint) = $id
+            ",
+            id = PROCESSING_LOCK_ID
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Terminate the backend holding the processing lock, for when a sync
+    /// process died without its connection being closed
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn force_unlock(pool: &PgPool) -> Result<Option<Self>, Error> {
+        let holder = match Self::get(pool).await? {
+            Some(holder) => holder,
+            None => return Ok(None),
+        };
+        let query = query!(
+            "SELECT pg_terminate_backend($pid) AS result",
+            pid = holder.pid
+        );
+        let conn = pool.get().await?;
+        let _: BoolResult = query.fetch_one(&conn).await?;
+        Ok(Some(holder))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{format_err, Error};
+
+    use crate::processing_lock::{is_processing_in_progress, ProcessingInProgress};
+
+    #[test]
+    fn test_is_processing_in_progress() {
+        let err: Error = ProcessingInProgress.into();
+        assert!(is_processing_in_progress(&err));
+        assert_eq!(err.to_string(), "sync already in progress");
+        let err = format_err!("some other failure");
+        assert!(!is_processing_in_progress(&err));
+    }
+}
@@ -0,0 +1,376 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{fmt, str::FromStr};
+use time::Date;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+/// Service or entry point a record was imported from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DataProvider {
+    GarminConnect,
+    Strava,
+    Fitbit,
+    Device,
+    Upload,
+    FileImport,
+    Manual,
+    Telegram,
+    Demo,
+}
+
+impl DataProvider {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::GarminConnect => "garmin_connect",
+            Self::Strava => "strava",
+            Self::Fitbit => "fitbit",
+            Self::Device => "device",
+            Self::Upload => "upload",
+            Self::FileImport => "file_import",
+            Self::Manual => "manual",
+            Self::Telegram => "telegram",
+            Self::Demo => "demo",
+        }
+    }
+}
+
+impl fmt::Display for DataProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for DataProvider {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "garmin_connect" => Ok(Self::GarminConnect),
+            "strava" => Ok(Self::Strava),
+            "fitbit" => Ok(Self::Fitbit),
+            "device" => Ok(Self::Device),
+            "upload" => Ok(Self::Upload),
+            "file_import" => Ok(Self::FileImport),
+            "manual" => Ok(Self::Manual),
+            "telegram" => Ok(Self::Telegram),
+            "demo" => Ok(Self::Demo),
+            _ => Err(format_err!("{s} is not a valid provider")),
+        }
+    }
+}
+
+/// Where a record came from, `import_path` is the command or endpoint which
+/// imported it and `raw_file` the file it was read from, if any
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub provider: DataProvider,
+    pub import_path: StackString,
+    pub raw_file: Option<StackString>,
+    pub imported_at: DateTimeWrapper,
+}
+
+impl Provenance {
+    #[must_use]
+    pub fn new(provider: DataProvider, import_path: &str, raw_file: Option<&str>) -> Self {
+        Self {
+            provider,
+            import_path: import_path.into(),
+            raw_file: raw_file.map(Into::into),
+            imported_at: DateTimeWrapper::now(),
+        }
+    }
+
+    /// Activity files are imported into `gps_dir` before they're parsed, so
+    /// their provenance is kept by filename in `activity_provenance` rather
+    /// than on `garmin_summary`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn record_activity(&self, pool: &PgPool, filename: &str) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_provenance (
+                    filename, provider, import_path, raw_file, imported_at
+                )
+                VALUES ($filename, $provider, $import_path, $raw_file, $imported_at)
+                ON CONFLICT (filename) DO UPDATE
+                    SET provider=EXCLUDED.provider,
+                        import_path=EXCLUDED.import_path,
+                        raw_file=EXCLUDED.raw_file,
+                        imported_at=EXCLUDED.imported_at
+            ",
+            filename = filename,
+            provider = self.provider.to_str(),
+            import_path = self.import_path,
+            raw_file = self.raw_file,
+            imported_at = self.imported_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Heart rate batches are merged into one avro file per day, so their
+    /// provenance is kept by date in `heartrate_batch_provenance`, the latest
+    /// import touching a day wins
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn record_heartrate_dates<'a, T>(&self, pool: &PgPool, dates: T) -> Result<(), Error>
+    where
+        T: IntoIterator<Item = &'a Date>,
+    {
+        let provider = self.provider.to_str();
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        for date in dates {
+            tran.execute(
+                UPSERT_HEARTRATE_BATCH,
+                &[
+                    date,
+                    &provider,
+                    &self.import_path,
+                    &self.raw_file,
+                    &self.imported_at,
+                ],
+            )
+            .await?;
+        }
+        tran.commit().await?;
+        Ok(())
+    }
+}
+
+const UPSERT_HEARTRATE_BATCH: &str = "
+    INSERT INTO heartrate_batch_provenance (date, provider, import_path, raw_file, imported_at)
+    VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT (date) DO UPDATE
+        SET provider=EXCLUDED.provider,
+            import_path=EXCLUDED.import_path,
+            raw_file=EXCLUDED.raw_file,
+            imported_at=EXCLUDED.imported_at
+";
+
+/// Kind of record provenance is tracked for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ProvenanceKind {
+    Activity,
+    HeartRateStream,
+    HeartRateBatch,
+    StravaActivity,
+    GarminConnectActivity,
+    ScaleMeasurement,
+    Biomarker,
+}
+
+impl ProvenanceKind {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Activity => "activity",
+            Self::HeartRateStream => "heartrate_stream",
+            Self::HeartRateBatch => "heartrate_batch",
+            Self::StravaActivity => "strava_activity",
+            Self::GarminConnectActivity => "garmin_connect_activity",
+            Self::ScaleMeasurement => "scale_measurement",
+            Self::Biomarker => "biomarker",
+        }
+    }
+}
+
+impl fmt::Display for ProvenanceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for ProvenanceKind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "activity" => Ok(Self::Activity),
+            "heartrate_stream" | "heartrate" => Ok(Self::HeartRateStream),
+            "heartrate_batch" => Ok(Self::HeartRateBatch),
+            "strava_activity" | "strava" => Ok(Self::StravaActivity),
+            "garmin_connect_activity" | "connect" => Ok(Self::GarminConnectActivity),
+            "scale_measurement" | "scale" => Ok(Self::ScaleMeasurement),
+            "biomarker" => Ok(Self::Biomarker),
+            _ => Err(format_err!("{s} is not a valid record kind")),
+        }
+    }
+}
+
+#[derive(FromSqlRow)]
+struct ProvenanceRow {
+    record_key: StackString,
+    provider: StackString,
+    import_path: Option<StackString>,
+    raw_file: Option<StackString>,
+    imported_at: Option<DateTimeWrapper>,
+}
+
+/// Provenance of one record, `record_key` identifies it within `kind`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProvenanceEntry {
+    pub kind: ProvenanceKind,
+    pub record_key: StackString,
+    #[serde(flatten)]
+    pub provenance: Provenance,
+}
+
+impl ProvenanceEntry {
+    fn from_row(kind: ProvenanceKind, row: ProvenanceRow) -> Result<Self, Error> {
+        Ok(Self {
+            kind,
+            record_key: row.record_key,
+            provenance: Provenance {
+                provider: row.provider.parse()?,
+                import_path: row.import_path.unwrap_or_default(),
+                raw_file: row.raw_file,
+                imported_at: row
+                    .imported_at
+                    .unwrap_or_else(DateTimeWrapper::sentinel_datetime),
+            },
+        })
+    }
+
+    /// Provenance of the records of `kind` under `key`: the activity filename
+    /// for activities and their heart rate streams (one entry per source),
+    /// the date (YYYY-MM-DD) for heart rate batches, the strava or garmin
+    /// connect activity id, the measurement id for scale measurements and the
+    /// series name for biomarkers (one entry per measurement), records
+    /// imported before provenance was tracked are left out
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_entries(
+        pool: &PgPool,
+        kind: ProvenanceKind,
+        key: &str,
+    ) -> Result<Vec<Self>, Error> {
+        let query = match kind {
+            ProvenanceKind::Activity => query!(
+                "
+                    SELECT filename AS record_key, provider, import_path, raw_file, imported_at
+                    FROM activity_provenance
+                    WHERE filename = $key
+                ",
+                key = key,
+            ),
+            ProvenanceKind::HeartRateStream => query!(
+                "
+                    SELECT s.source AS record_key,
+                           s.provider,
+                           s.import_path,
+                           s.raw_file,
+                           s.imported_at
+                    FROM heartrate_streams s
+                    JOIN garmin_summary a ON a.id = s.summary_id
+                    WHERE a.filename = $key
+                      AND s.provider IS NOT NULL
+                    ORDER BY s.source
+                ",
+                key = key,
+            ),
+            ProvenanceKind::HeartRateBatch => query!(
+                "
+                    SELECT date::text AS record_key, provider, import_path, raw_file, imported_at
+                    FROM heartrate_batch_provenance
+                    WHERE date::text = $key
+                ",
+                key = key,
+            ),
+            ProvenanceKind::StravaActivity => query!(
+                "
+                    SELECT id::text AS record_key, provider, import_path, raw_file, imported_at
+                    FROM strava_activities
+                    WHERE id::text = $key
+                      AND provider IS NOT NULL
+                ",
+                key = key,
+            ),
+            ProvenanceKind::GarminConnectActivity => query!(
+                "
+                    SELECT activity_id::text AS record_key,
+                           provider,
+                           import_path,
+                           raw_file,
+                           imported_at
+                    FROM garmin_connect_activities
+                    WHERE activity_id::text = $key
+                      AND provider IS NOT NULL
+                ",
+                key = key,
+            ),
+            ProvenanceKind::ScaleMeasurement => query!(
+                "
+                    SELECT id::text AS record_key, provider, import_path, raw_file, imported_at
+                    FROM scale_measurements
+                    WHERE id::text = $key
+                      AND provider IS NOT NULL
+                ",
+                key = key,
+            ),
+            ProvenanceKind::Biomarker => query!(
+                "
+                    SELECT datetime::text AS record_key,
+                           provider,
+                           import_path,
+                           raw_file,
+                           imported_at
+                    FROM biomarker_measurements
+                    WHERE series = $key
+                      AND provider IS NOT NULL
+                    ORDER BY datetime
+                ",
+                key = key,
+            ),
+        };
+        let conn = pool.get().await?;
+        let rows: Vec<ProvenanceRow> = query.fetch(&conn).await?;
+        rows.into_iter()
+            .map(|row| Self::from_row(kind, row))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::provenance::{DataProvider, ProvenanceKind};
+
+    #[test]
+    fn test_provenance_names() {
+        for provider in [
+            DataProvider::GarminConnect,
+            DataProvider::Strava,
+            DataProvider::Fitbit,
+            DataProvider::Device,
+            DataProvider::Upload,
+            DataProvider::FileImport,
+            DataProvider::Manual,
+            DataProvider::Telegram,
+            DataProvider::Demo,
+        ] {
+            assert_eq!(provider.to_str().parse::<DataProvider>().unwrap(), provider);
+        }
+        assert_eq!(
+            "scale".parse::<ProvenanceKind>().unwrap(),
+            ProvenanceKind::ScaleMeasurement
+        );
+        for kind in [
+            ProvenanceKind::Activity,
+            ProvenanceKind::HeartRateStream,
+            ProvenanceKind::HeartRateBatch,
+            ProvenanceKind::StravaActivity,
+            ProvenanceKind::GarminConnectActivity,
+            ProvenanceKind::ScaleMeasurement,
+            ProvenanceKind::Biomarker,
+        ] {
+            assert_eq!(kind.to_str().parse::<ProvenanceKind>().unwrap(), kind);
+        }
+        assert!("workout".parse::<ProvenanceKind>().is_err());
+    }
+}
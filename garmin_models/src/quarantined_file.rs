@@ -0,0 +1,89 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::collections::HashSet;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+/// Activity file which failed to parse, moved out of `gps_dir` into
+/// `quarantine_dir` until it is fixed by hand and retried
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuarantinedFile {
+    pub filename: StackString,
+    pub error_message: StackString,
+    pub quarantined_at: DateTimeWrapper,
+}
+
+impl QuarantinedFile {
+    #[must_use]
+    pub fn new(filename: &str, error_message: &str) -> Self {
+        Self {
+            filename: filename.into(),
+            error_message: error_message.into(),
+            quarantined_at: DateTimeWrapper::now(),
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_filename(pool: &PgPool, filename: &str) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "SELECT * FROM quarantined_files WHERE filename = $filename",
+            filename = filename,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM quarantined_files ORDER BY quarantined_at DESC");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_filenames(pool: &PgPool) -> Result<HashSet<StackString>, Error> {
+        Ok(Self::read_from_db(pool)
+            .await?
+            .into_iter()
+            .map(|q| q.filename)
+            .collect())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO quarantined_files (filename, error_message, quarantined_at)
+                VALUES ($filename, $error_message, $quarantined_at)
+                ON CONFLICT (filename) DO UPDATE
+                SET error_message=EXCLUDED.error_message,
+                    quarantined_at=EXCLUDED.quarantined_at
+            ",
+            filename = self.filename,
+            error_message = self.error_message,
+            quarantined_at = self.quarantined_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete_from_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM quarantined_files WHERE filename = $filename",
+            filename = self.filename,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,472 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use time::{macros::format_description, Duration, OffsetDateTime, PrimitiveDateTime};
+use time_tz::PrimitiveDateTimeExt;
+use url::Url;
+use uuid::Uuid;
+
+use garmin_lib::date_time_wrapper::{iso8601::convert_str_to_datetime, DateTimeWrapper};
+use garmin_utils::pgpool::PgPool;
+
+/// Forecasts are only available this many days ahead
+pub const FORECAST_HORIZON_DAYS: i64 = 16;
+
+/// Race coming up, the forecast for its start time and place is fetched
+/// daily once it's within `FORECAST_HORIZON_DAYS`, `distance` in meters and
+/// `target_time` in seconds
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UpcomingRace {
+    pub id: Uuid,
+    pub name: StackString,
+    pub race_datetime: DateTimeWrapper,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub distance: f64,
+    pub target_time: Option<f64>,
+}
+
+impl UpcomingRace {
+    /// # Errors
+    /// Return error if the name is empty or the location or distance are
+    /// invalid
+    pub fn new(
+        name: &str,
+        race_datetime: OffsetDateTime,
+        latitude: f64,
+        longitude: f64,
+        distance: f64,
+        target_time: Option<f64>,
+    ) -> Result<Self, Error> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format_err!("Race name is empty"));
+        }
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(format_err!("Invalid location {latitude},{longitude}"));
+        }
+        if distance <= 0.0 {
+            return Err(format_err!("Invalid distance {distance}"));
+        }
+        Ok(Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            race_datetime: race_datetime.into(),
+            latitude,
+            longitude,
+            distance,
+            target_time: target_time.filter(|t| *t > 0.0),
+        })
+    }
+
+    /// Races which haven't started yet, soonest first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_upcoming(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, name, race_datetime, latitude, longitude, distance, target_time
+                FROM upcoming_races
+                WHERE race_datetime >= $now
+                ORDER BY race_datetime
+            ",
+            now = OffsetDateTime::now_utc(),
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, name, race_datetime, latitude, longitude, distance, target_time
+                FROM upcoming_races
+                WHERE id = $id
+            ",
+            id = id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn insert_into_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO upcoming_races (
+                    id, name, race_datetime, latitude, longitude, distance, target_time
+                )
+                VALUES (
+                    $id, $name, $race_datetime, $latitude, $longitude, $distance, $target_time
+                )
+            ",
+            id = self.id,
+            name = self.name,
+            race_datetime = self.race_datetime,
+            latitude = self.latitude,
+            longitude = self.longitude,
+            distance = self.distance,
+            target_time = self.target_time,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Forecast snapshots are deleted along with the race
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete_from_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!("DELETE FROM upcoming_races WHERE id = $id", id = self.id);
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Whether the race is close enough for a forecast
+    #[must_use]
+    pub fn in_forecast_range(&self, now: OffsetDateTime) -> bool {
+        let race_datetime = self.race_datetime.to_offsetdatetime();
+        race_datetime >= now && race_datetime - now <= Duration::days(FORECAST_HORIZON_DAYS)
+    }
+}
+
+/// Forecast for the start of a race as fetched on `fetched_at`, degrees C,
+/// percent and km/h
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RaceForecast {
+    pub id: Uuid,
+    pub race_id: Uuid,
+    pub fetched_at: DateTimeWrapper,
+    pub temperature: f64,
+    pub dew_point: f64,
+    pub humidity: Option<f64>,
+    pub wind_speed: Option<f64>,
+    pub precipitation_probability: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct OpenMeteoHourly {
+    pub time: Vec<StackString>,
+    pub temperature_2m: Vec<Option<f64>>,
+    pub dew_point_2m: Vec<Option<f64>>,
+    #[serde(default)]
+    pub relative_humidity_2m: Vec<Option<f64>>,
+    #[serde(default)]
+    pub wind_speed_10m: Vec<Option<f64>>,
+    #[serde(default)]
+    pub precipitation_probability: Vec<Option<f64>>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct OpenMeteoResponse {
+    pub hourly: OpenMeteoHourly,
+}
+
+impl RaceForecast {
+    /// Forecast for the hour closest to `race_datetime` in an open-meteo
+    /// hourly forecast requested in UTC, `None` if the race is more than an
+    /// hour outside the forecast
+    #[must_use]
+    pub fn from_response(
+        race_id: Uuid,
+        race_datetime: OffsetDateTime,
+        fetched_at: OffsetDateTime,
+        response: &OpenMeteoResponse,
+    ) -> Option<Self> {
+        let hourly = &response.hourly;
+        let (idx, offset) = hourly
+            .time
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, t)| {
+                let t = PrimitiveDateTime::parse(
+                    t,
+                    format_description!("[year]-[month]-[day]T[hour]:[minute]"),
+                )
+                .ok()?
+                .assume_utc();
+                Some((idx, (t - race_datetime).abs()))
+            })
+            .min_by_key(|(_, offset)| *offset)?;
+        if offset > Duration::HOUR {
+            return None;
+        }
+        let value = |values: &[Option<f64>]| values.get(idx).copied().flatten();
+        Some(Self {
+            id: Uuid::new_v4(),
+            race_id,
+            fetched_at: fetched_at.into(),
+            temperature: value(&hourly.temperature_2m)?,
+            dew_point: value(&hourly.dew_point_2m)?,
+            humidity: value(&hourly.relative_humidity_2m),
+            wind_speed: value(&hourly.wind_speed_10m),
+            precipitation_probability: value(&hourly.precipitation_probability),
+        })
+    }
+
+    /// # Errors
+    /// Return error if the request fails or the response can't be parsed
+    pub async fn fetch(
+        client: &Client,
+        endpoint: &Url,
+        race: &UpcomingRace,
+    ) -> Result<Option<Self>, Error> {
+        let race_datetime = race.race_datetime.to_offsetdatetime();
+        let date = StackString::from_display(race_datetime.date());
+        let latitude = StackString::from_display(race.latitude);
+        let longitude = StackString::from_display(race.longitude);
+        let response: OpenMeteoResponse = client
+            .get(endpoint.as_str())
+            .query(&[
+                ("latitude", latitude.as_str()),
+                ("longitude", longitude.as_str()),
+                (
+                    "hourly",
+                    "temperature_2m,dew_point_2m,relative_humidity_2m,wind_speed_10m,\
+                     precipitation_probability",
+                ),
+                ("timezone", "UTC"),
+                ("start_date", date.as_str()),
+                ("end_date", date.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(Self::from_response(
+            race.id,
+            race_datetime,
+            OffsetDateTime::now_utc(),
+            &response,
+        ))
+    }
+
+    /// Snapshots of a race's forecast, oldest first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_race(pool: &PgPool, race_id: Uuid) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, race_id, fetched_at, temperature, dew_point, humidity, wind_speed,
+                       precipitation_probability
+                FROM race_forecasts
+                WHERE race_id = $race_id
+                ORDER BY fetched_at
+            ",
+            race_id = race_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn insert_into_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO race_forecasts (
+                    id, race_id, fetched_at, temperature, dew_point, humidity, wind_speed,
+                    precipitation_probability
+                )
+                VALUES (
+                    $id, $race_id, $fetched_at, $temperature, $dew_point, $humidity,
+                    $wind_speed, $precipitation_probability
+                )
+            ",
+            id = self.id,
+            race_id = self.race_id,
+            fetched_at = self.fetched_at,
+            temperature = self.temperature,
+            dew_point = self.dew_point,
+            humidity = self.humidity,
+            wind_speed = self.wind_speed,
+            precipitation_probability = self.precipitation_probability,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Expected slowdown as a fraction of the cool weather pace
+    #[must_use]
+    pub fn pace_adjustment(&self) -> f64 {
+        heat_pace_adjustment(self.temperature, self.dew_point)
+    }
+}
+
+/// Parse rfc3339 timestamps or the `YYYY-MM-DDTHH:MM` of a datetime-local
+/// input (taken as local time)
+/// # Errors
+/// Return error if `s` is neither
+pub fn parse_race_datetime(s: &str) -> Result<OffsetDateTime, Error> {
+    if let Ok(dt) = convert_str_to_datetime(s) {
+        return Ok(dt);
+    }
+    PrimitiveDateTime::parse(
+        s,
+        format_description!("[year]-[month]-[day]T[hour]:[minute]"),
+    )?
+    .assume_timezone(DateTimeWrapper::local_tz())
+    .take_first()
+    .ok_or_else(|| format_err!("Invalid local datetime {s}"))
+}
+
+/// Slowdown as a fraction of the cool weather pace from the sum of the
+/// temperature and dew point in degrees F, the usual runner's rule of thumb:
+/// nothing up to 100, rising to 10% at 180
+#[must_use]
+pub fn heat_pace_adjustment(temperature: f64, dew_point: f64) -> f64 {
+    const STEPS: [(f64, f64); 9] = [
+        (100.0, 0.0),
+        (110.0, 0.005),
+        (120.0, 0.01),
+        (130.0, 0.02),
+        (140.0, 0.03),
+        (150.0, 0.045),
+        (160.0, 0.06),
+        (170.0, 0.08),
+        (180.0, 0.10),
+    ];
+    let to_f = |c: f64| c * 9.0 / 5.0 + 32.0;
+    let sum = to_f(temperature) + to_f(dew_point);
+    let mut previous = STEPS[0];
+    for step in STEPS {
+        if sum <= step.0 {
+            if step.0 <= previous.0 {
+                return step.1;
+            }
+            return previous.1 + (step.1 - previous.1) * (sum - previous.0) / (step.0 - previous.0);
+        }
+        previous = step;
+    }
+    previous.1
+}
+
+/// Fetch today's forecast for each race within `FORECAST_HORIZON_DAYS`
+/// which hasn't been fetched yet today, a race whose forecast fails is
+/// reported in the output and the rest are still refreshed
+/// # Errors
+/// Return error if listing the races fails
+pub async fn refresh_race_forecasts(
+    pool: &PgPool,
+    client: &Client,
+    endpoint: &Url,
+) -> Result<Vec<StackString>, Error> {
+    let now = OffsetDateTime::now_utc();
+    let mut output = Vec::new();
+    for race in UpcomingRace::get_upcoming(pool).await? {
+        if !race.in_forecast_range(now) {
+            continue;
+        }
+        match refresh_race_forecast(pool, client, endpoint, &race, now).await {
+            Ok(Some(line)) => output.push(line),
+            Ok(None) => {}
+            Err(e) => output.push(format_sstr!("{} failed {e}", race.name)),
+        }
+    }
+    Ok(output)
+}
+
+async fn refresh_race_forecast(
+    pool: &PgPool,
+    client: &Client,
+    endpoint: &Url,
+    race: &UpcomingRace,
+    now: OffsetDateTime,
+) -> Result<Option<StackString>, Error> {
+    let fetched_today = RaceForecast::get_by_race(pool, race.id)
+        .await?
+        .last()
+        .is_some_and(|f| f.fetched_at.to_offsetdatetime().date() == now.date());
+    if fetched_today {
+        return Ok(None);
+    }
+    let forecast = match RaceForecast::fetch(client, endpoint, race).await? {
+        Some(forecast) => forecast,
+        None => return Ok(None),
+    };
+    forecast.insert_into_db(pool).await?;
+    Ok(Some(format_sstr!(
+        "{} {:.1} C dew point {:.1} C, {:.1}% slower",
+        race.name,
+        forecast.temperature,
+        forecast.dew_point,
+        forecast.pace_adjustment() * 100.0
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::{macros::datetime, Duration};
+    use uuid::Uuid;
+
+    use crate::race_forecast::{
+        heat_pace_adjustment, parse_race_datetime, OpenMeteoHourly, OpenMeteoResponse,
+        RaceForecast, UpcomingRace,
+    };
+
+    #[test]
+    fn test_heat_pace_adjustment() {
+        // 10 C and 0 C are 50 F and 32 F
+        assert_abs_diff_eq!(heat_pace_adjustment(10.0, 0.0), 0.0);
+        // 25 C and 20 C are 77 F and 68 F, 145 is halfway from 3% to 4.5%
+        assert_abs_diff_eq!(heat_pace_adjustment(25.0, 20.0), 0.0375, epsilon = 1e-9);
+        assert_abs_diff_eq!(heat_pace_adjustment(45.0, 35.0), 0.10);
+    }
+
+    #[test]
+    fn test_forecast_from_response() {
+        let race_id = Uuid::new_v4();
+        let race_datetime = datetime!(2024-05-04 13:20:00 UTC);
+        let fetched_at = race_datetime - Duration::days(3);
+        let response = OpenMeteoResponse {
+            hourly: OpenMeteoHourly {
+                time: vec!["2024-05-04T12:00".into(), "2024-05-04T13:00".into()],
+                temperature_2m: vec![Some(14.0), Some(16.5)],
+                dew_point_2m: vec![Some(8.0), Some(9.0)],
+                relative_humidity_2m: vec![Some(70.0), Some(62.0)],
+                wind_speed_10m: vec![Some(10.0), None],
+                precipitation_probability: Vec::new(),
+            },
+        };
+        let forecast =
+            RaceForecast::from_response(race_id, race_datetime, fetched_at, &response).unwrap();
+        assert_eq!(forecast.race_id, race_id);
+        assert_abs_diff_eq!(forecast.temperature, 16.5);
+        assert_abs_diff_eq!(forecast.dew_point, 9.0);
+        assert_eq!(forecast.humidity, Some(62.0));
+        assert_eq!(forecast.wind_speed, None);
+        assert_eq!(forecast.precipitation_probability, None);
+
+        let too_late = race_datetime + Duration::hours(3);
+        assert!(RaceForecast::from_response(race_id, too_late, fetched_at, &response).is_none());
+    }
+
+    #[test]
+    fn test_upcoming_race() {
+        let race_datetime = datetime!(2024-05-04 13:00:00 UTC);
+        assert!(UpcomingRace::new(" ", race_datetime, 40.0, -74.0, 5000.0, None).is_err());
+        assert!(UpcomingRace::new("5k", race_datetime, 95.0, -74.0, 5000.0, None).is_err());
+        let race =
+            UpcomingRace::new("Spring 5k", race_datetime, 40.0, -74.0, 5000.0, Some(0.0)).unwrap();
+        assert_eq!(race.target_time, None);
+        assert!(race.in_forecast_range(race_datetime - Duration::days(10)));
+        assert!(!race.in_forecast_range(race_datetime - Duration::days(20)));
+        assert!(!race.in_forecast_range(race_datetime + Duration::hours(1)));
+
+        assert_eq!(
+            parse_race_datetime("2024-05-04T13:00:00Z").unwrap(),
+            race_datetime
+        );
+        assert!(parse_race_datetime("2024-05-04T07:30").is_ok());
+        assert!(parse_race_datetime("May 4th").is_err());
+    }
+}
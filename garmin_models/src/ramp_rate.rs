@@ -0,0 +1,218 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::collections::BTreeMap;
+use time::{Date, Duration, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, week_start::WeekStart};
+use garmin_utils::{garmin_util::METERS_PER_MILE, pgpool::PgPool, sport_types::SportTypes};
+
+/// Number of weeks averaged for the chronic workload, including the week
+/// itself
+pub const CHRONIC_WEEKS: i64 = 4;
+
+#[derive(FromSqlRow)]
+struct DistanceRow {
+    begin_datetime: DateTimeWrapper,
+    total_distance: f64,
+}
+
+/// Acute:chronic workload ratio of one week, the week's mileage over the
+/// average weekly mileage of the last `CHRONIC_WEEKS` weeks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WeeklyRampRate {
+    pub week_start: Date,
+    /// miles in the week
+    pub acute: f64,
+    /// average weekly miles over the last `CHRONIC_WEEKS` weeks
+    pub chronic: f64,
+    /// `None` without a full chronic window or without any mileage in it
+    pub ratio: Option<f64>,
+}
+
+impl WeeklyRampRate {
+    #[must_use]
+    pub fn is_warning(&self, threshold: f64) -> bool {
+        self.ratio.is_some_and(|r| r > threshold)
+    }
+
+    #[must_use]
+    pub fn description(&self) -> StackString {
+        let ratio = self.ratio.unwrap_or(0.0);
+        format_sstr!(
+            "week of {}: {:0.1} mi is {ratio:0.2}x the {CHRONIC_WEEKS} week average of {:0.1} mi",
+            self.week_start,
+            self.acute,
+            self.chronic,
+        )
+    }
+
+    /// Ramp rate of each week from the first week with a full chronic window
+    /// after `start` through the week containing `end`, `distances` are
+    /// (local date, meters)
+    #[must_use]
+    pub fn from_distances(
+        distances: &[(Date, f64)],
+        week_start: WeekStart,
+        start: Date,
+        end: Date,
+    ) -> Vec<Self> {
+        let first_week = week_start.week_start_date(start);
+        let last_week = week_start.week_start_date(end);
+        let mut weekly: BTreeMap<Date, f64> = BTreeMap::new();
+        let mut week = first_week;
+        while week <= last_week {
+            weekly.insert(week, 0.0);
+            week += Duration::weeks(1);
+        }
+        for (date, meters) in distances {
+            if let Some(miles) = weekly.get_mut(&week_start.week_start_date(*date)) {
+                *miles += meters / METERS_PER_MILE;
+            }
+        }
+        let weeks: Vec<_> = weekly.into_iter().collect();
+        weeks
+            .windows(CHRONIC_WEEKS as usize)
+            .filter_map(|window| {
+                let (week_start, acute) = *window.last()?;
+                let chronic =
+                    window.iter().map(|(_, miles)| miles).sum::<f64>() / CHRONIC_WEEKS as f64;
+                let ratio = if chronic > 0.0 {
+                    Some(acute / chronic)
+                } else {
+                    None
+                };
+                Some(Self {
+                    week_start,
+                    acute,
+                    chronic,
+                    ratio,
+                })
+            })
+            .collect()
+    }
+
+    /// Ramp rates of the last `weeks` weeks of `sport` activities, computed
+    /// from the stored summaries, leaving out commutes with `exclude_commutes`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(
+        pool: &PgPool,
+        sport: SportTypes,
+        week_start: WeekStart,
+        weeks: i64,
+        exclude_commutes: bool,
+    ) -> Result<Vec<Self>, Error> {
+        let local = DateTimeWrapper::local_tz();
+        let today = OffsetDateTime::now_utc().to_timezone(local).date();
+        let start = week_start.week_start_date(today) - Duration::weeks(weeks + CHRONIC_WEEKS - 2);
+        let query = query!(
+            "
+                SELECT a.begin_datetime, a.total_distance
+                FROM garmin_summary a
+                WHERE a.sport = $sport AND a.begin_datetime >= $start
+                  AND (NOT $exclude_commutes OR NOT EXISTS (
+                    SELECT 1 FROM activity_commutes x WHERE x.summary_id = a.id AND x.is_commute
+                  ))
+            ",
+            sport = sport,
+            start = start.midnight().assume_utc() - Duration::days(1),
+            exclude_commutes = exclude_commutes,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<DistanceRow> = query.fetch(&conn).await?;
+        let distances: Vec<_> = rows
+            .into_iter()
+            .map(|row| {
+                let date = row.begin_datetime.to_timezone(local).date();
+                (date, row.total_distance)
+            })
+            .collect();
+        Ok(Self::from_distances(&distances, week_start, start, today))
+    }
+
+    /// Weeks out of the last `weeks` whose ratio exceeds `threshold`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_warnings(
+        pool: &PgPool,
+        sport: SportTypes,
+        week_start: WeekStart,
+        threshold: f64,
+        weeks: i64,
+        exclude_commutes: bool,
+    ) -> Result<Vec<Self>, Error> {
+        let ramp_rates =
+            Self::read_from_db(pool, sport, week_start, weeks, exclude_commutes).await?;
+        Ok(ramp_rates
+            .into_iter()
+            .filter(|r| r.is_warning(threshold))
+            .collect())
+    }
+
+    /// Record that a warning was sent for this week, returns false if one
+    /// already was
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn record_alert(&self, pool: &PgPool, sport: SportTypes) -> Result<bool, Error> {
+        let query = query!(
+            "
+                INSERT INTO ramp_rate_alerts (week_start, sport, ratio)
+                VALUES ($week_start, $sport, $ratio)
+                ON CONFLICT (week_start, sport) DO NOTHING
+            ",
+            week_start = self.week_start,
+            sport = sport,
+            ratio = self.ratio,
+        );
+        let conn = pool.get().await?;
+        Ok(query.execute(&conn).await? > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::{macros::date, Duration};
+
+    use garmin_lib::week_start::WeekStart;
+    use garmin_utils::garmin_util::METERS_PER_MILE;
+
+    use crate::ramp_rate::WeeklyRampRate;
+
+    #[test]
+    fn test_ramp_rate_from_distances() {
+        let start = date!(2024 - 01 - 01);
+        // 10 miles on the monday of four weeks then 26 miles in the fifth
+        let mut distances: Vec<_> = (0..4)
+            .map(|week| (start + Duration::weeks(week), 10.0 * METERS_PER_MILE))
+            .collect();
+        distances.push((date!(2024 - 02 - 03), 26.0 * METERS_PER_MILE));
+        let end = date!(2024 - 02 - 04);
+
+        let ramp_rates = WeeklyRampRate::from_distances(&distances, WeekStart::Monday, start, end);
+        assert_eq!(ramp_rates.len(), 2);
+        assert_eq!(ramp_rates[0].week_start, date!(2024 - 01 - 22));
+        assert_abs_diff_eq!(ramp_rates[0].ratio.unwrap(), 1.0);
+        assert!(!ramp_rates[0].is_warning(1.5));
+        assert_eq!(ramp_rates[1].week_start, date!(2024 - 01 - 29));
+        assert_abs_diff_eq!(ramp_rates[1].acute, 26.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(ramp_rates[1].chronic, 14.0, epsilon = 1e-9);
+        assert!(ramp_rates[1].is_warning(1.5));
+        assert_eq!(
+            ramp_rates[1].description().as_str(),
+            "week of 2024-01-29: 26.0 mi is 1.86x the 4 week average of 14.0 mi"
+        );
+
+        // with weeks starting on sunday the last day starts a week of its own
+        let ramp_rates = WeeklyRampRate::from_distances(&distances, WeekStart::Sunday, start, end);
+        let last = ramp_rates.last().unwrap();
+        assert_eq!(last.week_start, date!(2024 - 02 - 04));
+        assert_abs_diff_eq!(last.acute, 0.0);
+
+        let ramp_rates = WeeklyRampRate::from_distances(&[], WeekStart::Monday, start, end);
+        assert!(ramp_rates.iter().all(|r| r.ratio.is_none()));
+    }
+}
@@ -0,0 +1,350 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{collections::BTreeMap, fmt::Write};
+use time::{Date, Duration, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+
+use garmin_lib::{
+    date_time_wrapper::DateTimeWrapper, heart_rate_profile::HeartRateProfile, week_start::WeekStart,
+};
+use garmin_utils::pgpool::PgPool;
+
+use crate::{heart_rate_zones::HeartRateProfiles, threshold_heart_rate::RACE_EFFORT_SECONDS};
+
+/// Activities of the last year are analyzed unless asked otherwise
+pub const DEFAULT_REST_DAYS: i64 = 365;
+/// Size of the weekly consistency chart
+pub const CONSISTENCY_WIDTH: f64 = 800.0;
+pub const CONSISTENCY_HEIGHT: f64 = 150.0;
+
+#[derive(FromSqlRow)]
+struct RestDayRow {
+    begin_datetime: DateTimeWrapper,
+    total_hr_dur: f64,
+    total_hr_dis: f64,
+    total_duration: f64,
+    rpe: Option<i16>,
+}
+
+/// Days trained in one week against the days planned
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WeekConsistency {
+    pub week_start: Date,
+    pub days_trained: usize,
+    pub planned_days: usize,
+}
+
+impl WeekConsistency {
+    /// Percentage of planned days trained, extra days don't count over 100
+    #[must_use]
+    pub fn consistency(&self) -> f64 {
+        if self.planned_days == 0 {
+            return 0.0;
+        }
+        100.0 * self.days_trained.min(self.planned_days) as f64 / self.planned_days as f64
+    }
+}
+
+/// Rest between activities over a span of days, an activity is a hard effort
+/// when its training impulse reaches `hard_effort_trimp`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RestDayStats {
+    pub start_date: Date,
+    pub end_date: Date,
+    pub days_trained: usize,
+    pub rest_days: usize,
+    pub hard_efforts: usize,
+    /// Average number of days without any activity between consecutive hard
+    /// efforts, `None` with fewer than two
+    pub avg_rest_between_hard: Option<f64>,
+    /// First day and length in days of the longest run of rest days
+    pub longest_break: Option<(Date, i64)>,
+    /// Training days per week of the plan, `None` when there's no plan and
+    /// every day counts
+    pub planned_training_days: Option<u8>,
+    pub weeks: Vec<WeekConsistency>,
+}
+
+impl RestDayStats {
+    /// Statistics from the local date and training impulse of each activity
+    /// between `start_date` and `end_date` inclusive
+    #[must_use]
+    pub fn from_activities(
+        activities: &[(Date, f64)],
+        start_date: Date,
+        end_date: Date,
+        hard_effort_trimp: f64,
+        planned_training_days: Option<u8>,
+        week_start: WeekStart,
+    ) -> Self {
+        // largest training impulse of each day trained
+        let mut days: BTreeMap<Date, f64> = BTreeMap::new();
+        for (date, trimp) in activities {
+            if *date < start_date || *date > end_date {
+                continue;
+            }
+            let entry = days.entry(*date).or_default();
+            *entry = entry.max(*trimp);
+        }
+        let total_days = (end_date - start_date).whole_days() + 1;
+        let days_trained = days.len();
+        let rest_days = (total_days as usize).saturating_sub(days_trained);
+
+        let hard_dates: Vec<Date> = days
+            .iter()
+            .filter(|(_, trimp)| **trimp >= hard_effort_trimp)
+            .map(|(date, _)| *date)
+            .collect();
+        let avg_rest_between_hard = if hard_dates.len() < 2 {
+            None
+        } else {
+            let rests: usize = hard_dates
+                .windows(2)
+                .map(|w| {
+                    let between = (w[1] - w[0]).whole_days() - 1;
+                    let trained = days.range(w[0]..w[1]).count() - 1;
+                    between as usize - trained
+                })
+                .sum();
+            Some(rests as f64 / (hard_dates.len() - 1) as f64)
+        };
+
+        // a break runs from the day after one trained day to the day before
+        // the next, the span's ends bound the first and last
+        let mut longest_break: Option<(Date, i64)> = None;
+        let mut previous = start_date - Duration::days(1);
+        let next_dates = days
+            .keys()
+            .copied()
+            .chain(std::iter::once(end_date + Duration::days(1)));
+        for date in next_dates {
+            let length = (date - previous).whole_days() - 1;
+            if length > 0 && longest_break.is_none_or(|(_, l)| length > l) {
+                longest_break = Some((previous + Duration::days(1), length));
+            }
+            previous = date;
+        }
+
+        let per_week = planned_training_days.map_or(7, |d| usize::from(d.min(7)));
+        let mut weeks = Vec::new();
+        let mut week = week_start.week_start_date(start_date);
+        while week <= end_date {
+            let first = week.max(start_date);
+            let last = (week + Duration::days(6)).min(end_date);
+            let days_in_span = ((last - first).whole_days() + 1) as usize;
+            weeks.push(WeekConsistency {
+                week_start: week,
+                days_trained: days.range(first..=last).count(),
+                planned_days: per_week.min(days_in_span),
+            });
+            week += Duration::weeks(1);
+        }
+
+        Self {
+            start_date,
+            end_date,
+            days_trained,
+            rest_days,
+            hard_efforts: hard_dates.len(),
+            avg_rest_between_hard,
+            longest_break,
+            planned_training_days,
+            weeks,
+        }
+    }
+
+    /// Percentage of planned days trained over the whole span
+    #[must_use]
+    pub fn consistency(&self) -> f64 {
+        let planned: usize = self.weeks.iter().map(|w| w.planned_days).sum();
+        if planned == 0 {
+            return 0.0;
+        }
+        let trained: usize = self
+            .weeks
+            .iter()
+            .map(|w| w.days_trained.min(w.planned_days))
+            .sum();
+        100.0 * trained as f64 / planned as f64
+    }
+
+    /// Bar chart of the weekly consistency, the tooltip of each bar gives the
+    /// week and days trained
+    #[must_use]
+    pub fn consistency_svg(&self) -> Option<StackString> {
+        if self.weeks.is_empty() {
+            return None;
+        }
+        let width = CONSISTENCY_WIDTH / self.weeks.len() as f64;
+        let mut bars = StackString::new();
+        for (idx, week) in self.weeks.iter().enumerate() {
+            let consistency = week.consistency();
+            let height = CONSISTENCY_HEIGHT * consistency / 100.0;
+            let x = width * idx as f64;
+            let y = CONSISTENCY_HEIGHT - height;
+            let color = if consistency >= 100.0 {
+                "seagreen"
+            } else {
+                "steelblue"
+            };
+            write!(
+                bars,
+                r#"<rect x="{x:.1}" y="{y:.1}" width="{w:.1}" height="{height:.1}" fill="{color}"><title>{start} {trained}/{planned}</title></rect>"#,
+                w = (width - 1.0).max(1.0),
+                start = week.week_start,
+                trained = week.days_trained,
+                planned = week.planned_days,
+            )
+            .ok()?;
+        }
+        Some(format_sstr!(
+            r#"<svg width="{w}" height="{h}" viewBox="0 0 {w} {h}">{bars}</svg>"#,
+            w = CONSISTENCY_WIDTH,
+            h = CONSISTENCY_HEIGHT,
+        ))
+    }
+
+    /// Statistics of the activities of the last `days` days, training impulse
+    /// is computed from the average heart rate of each activity with the
+    /// profile in effect on its local date, or from its rating of perceived
+    /// exertion when it has no heart rate, an activity averaging at least the
+    /// lactate threshold is always hard,
+    /// with `exclude_commutes` commutes don't count as training
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(
+        pool: &PgPool,
+        profiles: &HeartRateProfiles,
+        hard_effort_trimp: f64,
+        planned_training_days: Option<u8>,
+        week_start: WeekStart,
+        days: i64,
+        exclude_commutes: bool,
+    ) -> Result<Self, Error> {
+        let local = DateTimeWrapper::local_tz();
+        let now = OffsetDateTime::now_utc().to_timezone(local);
+        let end_date = now.date();
+        let start_date = end_date - Duration::days(days - 1);
+        let query = query!(
+            "
+                SELECT a.begin_datetime, a.total_hr_dur, a.total_hr_dis, a.total_duration,
+                       rp.rpe
+                FROM garmin_summary a
+                LEFT JOIN activity_rpe rp ON rp.summary_id = a.id
+                WHERE a.begin_datetime >= $start
+                  AND (NOT $exclude_commutes OR NOT EXISTS (
+                    SELECT 1 FROM activity_commutes x WHERE x.summary_id = a.id AND x.is_commute
+                  ))
+            ",
+            start = now - Duration::days(days),
+            exclude_commutes = exclude_commutes,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<RestDayRow> = query.fetch(&conn).await?;
+        let activities: Vec<_> = rows
+            .into_iter()
+            .map(|row| {
+                let date = row.begin_datetime.to_timezone(local).date();
+                let trimp = if row.total_hr_dis > 0.0 {
+                    let profile = profiles.profile_on(date);
+                    let heart_rate = row.total_hr_dur / row.total_hr_dis;
+                    let trimp = profile.trimp(heart_rate, row.total_hr_dis);
+                    // a sustained effort at threshold is hard whatever its
+                    // training impulse
+                    let at_threshold = profile
+                        .threshold_heart_rate
+                        .is_some_and(|t| heart_rate >= t)
+                        && row.total_hr_dis >= RACE_EFFORT_SECONDS;
+                    if at_threshold {
+                        trimp.max(hard_effort_trimp)
+                    } else {
+                        trimp
+                    }
+                } else if let Some(rpe) = row.rpe {
+                    HeartRateProfile::rpe_trimp(rpe.into(), row.total_duration)
+                } else {
+                    0.0
+                };
+                (date, trimp)
+            })
+            .collect();
+        Ok(Self::from_activities(
+            &activities,
+            start_date,
+            end_date,
+            hard_effort_trimp,
+            planned_training_days,
+            week_start,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::macros::date;
+
+    use garmin_lib::week_start::WeekStart;
+
+    use crate::rest_days::RestDayStats;
+
+    #[test]
+    fn test_rest_day_stats() {
+        // 2024-05-06 is a monday
+        let activities = [
+            (date!(2024 - 05 - 06), 150.0),
+            (date!(2024 - 05 - 07), 40.0),
+            (date!(2024 - 05 - 07), 60.0),
+            (date!(2024 - 05 - 10), 120.0),
+            (date!(2024 - 05 - 16), 110.0),
+            (date!(2024 - 06 - 01), 200.0),
+        ];
+        let stats = RestDayStats::from_activities(
+            &activities,
+            date!(2024 - 05 - 06),
+            date!(2024 - 05 - 19),
+            100.0,
+            None,
+            WeekStart::Monday,
+        );
+        assert_eq!(stats.days_trained, 4);
+        assert_eq!(stats.rest_days, 10);
+        assert_eq!(stats.hard_efforts, 3);
+        // 2 rest days then 5
+        assert_abs_diff_eq!(stats.avg_rest_between_hard.unwrap(), 3.5);
+        assert_eq!(stats.longest_break, Some((date!(2024 - 05 - 11), 5)));
+        assert_eq!(stats.weeks.len(), 2);
+        assert_eq!(stats.weeks[0].days_trained, 3);
+        assert_eq!(stats.weeks[0].planned_days, 7);
+        assert_abs_diff_eq!(stats.consistency(), 400.0 / 14.0);
+
+        let stats = RestDayStats::from_activities(
+            &activities,
+            date!(2024 - 05 - 08),
+            date!(2024 - 05 - 19),
+            100.0,
+            Some(3),
+            WeekStart::Monday,
+        );
+        assert_eq!(stats.weeks[0].week_start, date!(2024 - 05 - 06));
+        assert_eq!(stats.weeks[0].days_trained, 1);
+        assert_eq!(stats.weeks[0].planned_days, 3);
+        assert_eq!(stats.weeks[1].days_trained, 1);
+        assert_abs_diff_eq!(stats.consistency(), 100.0 / 3.0);
+        assert!(stats.avg_rest_between_hard.is_some());
+        assert!(stats.consistency_svg().unwrap().contains("2024-05-13 1/3"));
+
+        let stats = RestDayStats::from_activities(
+            &[],
+            date!(2024 - 05 - 06),
+            date!(2024 - 05 - 12),
+            100.0,
+            None,
+            WeekStart::Monday,
+        );
+        assert_eq!(stats.longest_break, Some((date!(2024 - 05 - 06), 7)));
+        assert!(stats.avg_rest_between_hard.is_none());
+    }
+}
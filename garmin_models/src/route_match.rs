@@ -0,0 +1,428 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use uuid::Uuid;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::{garmin_util::haversine_distance, pgpool::PgPool, sport_types::SportTypes};
+
+use crate::garmin_file::GarminFile;
+
+/// Number of points sampled along an activity to compare it with known routes
+pub const ROUTE_TRACE_POINTS: usize = 10;
+/// Largest distance in meters between corresponding trace points of two
+/// attempts of the same route
+pub const ROUTE_MATCH_RADIUS: f64 = 200.0;
+/// Largest fractional difference in total distance between two attempts of
+/// the same route
+pub const ROUTE_DISTANCE_TOLERANCE: f64 = 0.05;
+
+/// (latitude, longitude) at `ROUTE_TRACE_POINTS` evenly spaced fractions of
+/// the distance covered, `None` for activities without gps or distance
+#[must_use]
+pub fn route_trace(gfile: &GarminFile) -> Option<Vec<(f64, f64)>> {
+    let samples: Vec<_> = gfile
+        .points
+        .iter()
+        .filter_map(|p| Some((p.distance?, p.latitude?, p.longitude?)))
+        .collect();
+    let total = samples.last()?.0;
+    if total <= 0.0 {
+        return None;
+    }
+    let mut trace = Vec::with_capacity(ROUTE_TRACE_POINTS);
+    let mut samples = samples.iter().peekable();
+    for idx in 0..ROUTE_TRACE_POINTS {
+        let target = total * idx as f64 / (ROUTE_TRACE_POINTS - 1) as f64;
+        while let Some((distance, _, _)) = samples.peek() {
+            if *distance >= target {
+                break;
+            }
+            samples.next();
+        }
+        let (_, lat, lon) = samples.peek()?;
+        trace.push((*lat, *lon));
+    }
+    Some(trace)
+}
+
+/// Whether every point of one trace lies within `ROUTE_MATCH_RADIUS` of the
+/// corresponding point of the other
+#[must_use]
+pub fn traces_match(trace0: &[(f64, f64)], trace1: &[(f64, f64)]) -> bool {
+    trace0.len() == trace1.len()
+        && trace0
+            .iter()
+            .zip(trace1)
+            .all(|((lat0, lon0), (lat1, lon1))| {
+                haversine_distance(*lat0, *lon0, *lat1, *lon1) <= ROUTE_MATCH_RADIUS
+            })
+}
+
+/// Course run more than once, identified by the trace of its first attempt,
+/// stored in `matched_routes`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchedRoute {
+    pub id: Uuid,
+    pub name: StackString,
+    pub sport: SportTypes,
+    pub distance: f64,
+    pub latitudes: Vec<f64>,
+    pub longitudes: Vec<f64>,
+}
+
+impl MatchedRoute {
+    #[must_use]
+    pub fn new(name: &str, sport: SportTypes, distance: f64, trace: &[(f64, f64)]) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            sport,
+            distance,
+            latitudes: trace.iter().map(|(lat, _)| *lat).collect(),
+            longitudes: trace.iter().map(|(_, lon)| *lon).collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn trace(&self) -> Vec<(f64, f64)> {
+        self.latitudes
+            .iter()
+            .zip(&self.longitudes)
+            .map(|(lat, lon)| (*lat, *lon))
+            .collect()
+    }
+
+    /// Whether an activity of `sport` covering `distance` along `trace`
+    /// follows this route
+    #[must_use]
+    pub fn matches(&self, sport: SportTypes, distance: f64, trace: &[(f64, f64)]) -> bool {
+        sport == self.sport
+            && (distance - self.distance).abs() <= ROUTE_DISTANCE_TOLERANCE * self.distance
+            && traces_match(&self.trace(), trace)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, name, sport, distance, latitudes, longitudes
+                FROM matched_routes
+                WHERE id = $id
+            ",
+            id = id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Routes with at least two attempts, most attempted first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<(Self, i64)>, Error> {
+        Self::get_with_attempts(pool, 2).await
+    }
+
+    /// Routes with at least `min_attempts` attempts, most attempted first
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_with_attempts(
+        pool: &PgPool,
+        min_attempts: i64,
+    ) -> Result<Vec<(Self, i64)>, Error> {
+        #[derive(FromSqlRow)]
+        struct RouteCountRow {
+            id: Uuid,
+            name: StackString,
+            sport: SportTypes,
+            distance: f64,
+            latitudes: Vec<f64>,
+            longitudes: Vec<f64>,
+            attempts: i64,
+        }
+
+        let query = query!(
+            "
+                SELECT a.id, a.name, a.sport, a.distance, a.latitudes, a.longitudes,
+                       count(*) AS attempts
+                FROM matched_routes a
+                JOIN activity_routes b ON b.route_id = a.id
+                GROUP BY a.id
+                HAVING count(*) >= $min_attempts
+                ORDER BY attempts DESC, a.name
+            ",
+            min_attempts = min_attempts,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<RouteCountRow> = query.fetch(&conn).await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                let route = Self {
+                    id: r.id,
+                    name: r.name,
+                    sport: r.sport,
+                    distance: r.distance,
+                    latitudes: r.latitudes,
+                    longitudes: r.longitudes,
+                };
+                (route, r.attempts)
+            })
+            .collect())
+    }
+
+    /// Routes of `sport` whose distance is within `ROUTE_DISTANCE_TOLERANCE`
+    /// of `distance`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_candidates(
+        pool: &PgPool,
+        sport: SportTypes,
+        distance: f64,
+    ) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, name, sport, distance, latitudes, longitudes
+                FROM matched_routes
+                WHERE sport = $sport
+                  AND abs(distance - $distance) <= $tolerance * distance
+                ORDER BY created_at
+            ",
+            sport = sport,
+            distance = distance,
+            tolerance = ROUTE_DISTANCE_TOLERANCE,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_summary_id(pool: &PgPool, summary_id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT a.id, a.name, a.sport, a.distance, a.latitudes, a.longitudes
+                FROM matched_routes a
+                JOIN activity_routes b ON b.route_id = a.id
+                WHERE b.summary_id = $summary_id
+            ",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn insert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO matched_routes (id, name, sport, distance, latitudes, longitudes)
+                VALUES ($id, $name, $sport, $distance, $latitudes, $longitudes)
+            ",
+            id = self.id,
+            name = self.name,
+            sport = self.sport,
+            distance = self.distance,
+            latitudes = self.latitudes,
+            longitudes = self.longitudes,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+#[derive(FromSqlRow)]
+struct MissingRouteRow {
+    id: Uuid,
+    filename: StackString,
+}
+
+/// Route followed by an activity and the average device sensor temperature
+/// recorded along it, stored in `activity_routes`, `route_id` is `None` for activities
+/// without gps so they aren't read again on every sync
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ActivityRoute {
+    pub summary_id: Uuid,
+    pub route_id: Option<Uuid>,
+    pub temperature: Option<f64>,
+}
+
+impl ActivityRoute {
+    /// Match `gfile` against the known routes, a new route named after the
+    /// activity is created when none match
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn match_gfile(
+        pool: &PgPool,
+        summary_id: Uuid,
+        gfile: &GarminFile,
+    ) -> Result<Self, Error> {
+        let temperatures: Vec<_> = gfile.points.iter().filter_map(|p| p.temperature).collect();
+        let temperature = if temperatures.is_empty() {
+            None
+        } else {
+            Some(temperatures.iter().sum::<f64>() / temperatures.len() as f64)
+        };
+        let trace = match route_trace(gfile) {
+            Some(trace) => trace,
+            None => {
+                return Ok(Self {
+                    summary_id,
+                    route_id: None,
+                    temperature,
+                })
+            }
+        };
+        let candidates =
+            MatchedRoute::get_candidates(pool, gfile.sport, gfile.total_distance).await?;
+        let route_id = match candidates
+            .iter()
+            .find(|r| r.matches(gfile.sport, gfile.total_distance, &trace))
+        {
+            Some(route) => route.id,
+            None => {
+                let name = format_sstr!(
+                    "{} {:.1}km from {}",
+                    gfile.sport,
+                    gfile.total_distance / 1000.0,
+                    gfile.begin_datetime.to_offsetdatetime().date(),
+                );
+                let route = MatchedRoute::new(&name, gfile.sport, gfile.total_distance, &trace);
+                route.insert_db(pool).await?;
+                route.id
+            }
+        };
+        Ok(Self {
+            summary_id,
+            route_id: Some(route_id),
+            temperature,
+        })
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_routes (summary_id, route_id, temperature)
+                VALUES ($summary_id, $route_id, $temperature)
+                ON CONFLICT (summary_id) DO UPDATE
+                SET route_id=EXCLUDED.route_id,
+                    temperature=EXCLUDED.temperature
+            ",
+            summary_id = self.summary_id,
+            route_id = self.route_id,
+            temperature = self.temperature,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// (id, filename) of outdoor activities which haven't been matched
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_summaries(pool: &PgPool) -> Result<Vec<(Uuid, StackString)>, Error> {
+        let query = query!(
+            "
+                SELECT a.id, a.filename
+                FROM garmin_summary a
+                WHERE a.sport IN ('running', 'walking', 'hiking', 'biking')
+                  AND NOT EXISTS (
+                    SELECT 1 FROM activity_routes b WHERE b.summary_id = a.id
+                  )
+                ORDER BY a.begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingRouteRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+}
+
+/// One attempt of a matched route with the conditions it was done in
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RouteAttempt {
+    pub summary_id: Uuid,
+    pub filename: StackString,
+    pub begin_datetime: DateTimeWrapper,
+    pub total_duration: f64,
+    pub total_distance: f64,
+    pub temperature: Option<f64>,
+    pub surface: Option<StackString>,
+}
+
+impl RouteAttempt {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_route_id(pool: &PgPool, route_id: Uuid) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT a.id AS summary_id, a.filename, a.begin_datetime,
+                       a.total_duration, a.total_distance, b.temperature,
+                       c.surface
+                FROM garmin_summary a
+                JOIN activity_routes b ON b.summary_id = a.id
+                LEFT JOIN activity_surfaces c ON c.summary_id = a.id
+                WHERE b.route_id = $route_id
+                ORDER BY a.begin_datetime
+            ",
+            route_id = route_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::{
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+        route_match::{route_trace, MatchedRoute, ROUTE_TRACE_POINTS},
+    };
+
+    fn gfile(lat_offset: f64, length: i32) -> GarminFile {
+        let mut gfile = GarminFile::default();
+        gfile.points = (0..=length)
+            .map(|i| GarminPoint {
+                distance: Some(f64::from(i) * 100.0),
+                latitude: Some(41.0 + lat_offset + f64::from(i) * 0.0009),
+                longitude: Some(-74.0),
+                ..GarminPoint::default()
+            })
+            .collect();
+        gfile
+    }
+
+    #[test]
+    fn test_route_match() {
+        let trace = route_trace(&gfile(0.0, 50)).unwrap();
+        assert_eq!(trace.len(), ROUTE_TRACE_POINTS);
+        assert!((trace[0].0 - 41.0).abs() < 1e-9);
+        assert!((trace[ROUTE_TRACE_POINTS - 1].0 - 41.045).abs() < 1e-9);
+
+        let route = MatchedRoute::new("loop", SportTypes::Running, 5000.0, &trace);
+        assert_eq!(route.trace(), trace);
+
+        // roughly 50m north of the first attempt
+        let nearby = route_trace(&gfile(0.00045, 50)).unwrap();
+        assert!(route.matches(SportTypes::Running, 5100.0, &nearby));
+        assert!(!route.matches(SportTypes::Biking, 5100.0, &nearby));
+        assert!(!route.matches(SportTypes::Running, 6000.0, &nearby));
+
+        // roughly 1km north of the first attempt
+        let elsewhere = route_trace(&gfile(0.009, 50)).unwrap();
+        assert!(!route.matches(SportTypes::Running, 5000.0, &elsewhere));
+
+        let mut no_gps = gfile(0.0, 50);
+        no_gps.points.iter_mut().for_each(|p| p.latitude = None);
+        assert!(route_trace(&no_gps).is_none());
+    }
+}
@@ -0,0 +1,148 @@
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+
+use crate::{garmin_file::GarminFile, garmin_point::GarminPoint};
+
+/// Halves within this fraction of each other count as an even split
+pub const EVEN_SPLIT_THRESHOLD: f64 = 0.01;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SplitType {
+    Negative,
+    Even,
+    Positive,
+}
+
+impl SplitType {
+    #[must_use]
+    pub fn from_differential(differential: f64) -> Self {
+        if differential > EVEN_SPLIT_THRESHOLD {
+            Self::Positive
+        } else if differential < -EVEN_SPLIT_THRESHOLD {
+            Self::Negative
+        } else {
+            Self::Even
+        }
+    }
+
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Negative => "negative",
+            Self::Even => "even",
+            Self::Positive => "positive",
+        }
+    }
+}
+
+impl fmt::Display for SplitType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// Seconds from the start until `distance` meters was reached, interpolated
+/// between the surrounding points
+fn time_at_distance(points: &[GarminPoint], distance: f64) -> Option<f64> {
+    let mut last: Option<(f64, f64)> = None;
+    for point in points {
+        let point_distance = match point.distance {
+            Some(d) => d,
+            None => continue,
+        };
+        let point_time = point.duration_from_begin;
+        if point_distance >= distance {
+            return Some(match last {
+                Some((last_distance, last_time)) if point_distance > last_distance => {
+                    last_time
+                        + (point_time - last_time) * (distance - last_distance)
+                            / (point_distance - last_distance)
+                }
+                _ => point_time,
+            });
+        }
+        last = Some((point_distance, point_time));
+    }
+    None
+}
+
+/// Time over the second half of the distance relative to the first half,
+/// positive when the second half was slower (a positive split), e.g. 0.05
+/// means the second half took 5% longer
+#[must_use]
+pub fn split_differential(gfile: &GarminFile) -> Option<f64> {
+    if gfile.points.len() < 3 {
+        return None;
+    }
+    let total_distance = gfile
+        .points
+        .iter()
+        .filter_map(|p| p.distance)
+        .fold(0.0, f64::max);
+    if total_distance <= 0.0 {
+        return None;
+    }
+    let first_half = time_at_distance(&gfile.points, total_distance / 2.0)?;
+    let total_time = time_at_distance(&gfile.points, total_distance)?;
+    let second_half = total_time - first_half;
+    if first_half <= 0.0 || second_half <= 0.0 {
+        return None;
+    }
+    Some(second_half / first_half - 1.0)
+}
+
+/// e.g. `+3.2% (positive)`
+#[must_use]
+pub fn format_split_differential(differential: f64) -> StackString {
+    format_sstr!(
+        "{:+.1}% ({})",
+        differential * 100.0,
+        SplitType::from_differential(differential)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+        split_differential::{format_split_differential, split_differential, SplitType},
+    };
+
+    #[test]
+    fn test_split_differential() {
+        // 5 minute first half then 5:30 second half over 2km
+        let points: Vec<_> = (0..=20)
+            .map(|idx| {
+                let distance = f64::from(idx) * 100.0;
+                let duration_from_begin = if idx <= 10 {
+                    f64::from(idx) * 30.0
+                } else {
+                    300.0 + f64::from(idx - 10) * 33.0
+                };
+                GarminPoint {
+                    distance: Some(distance),
+                    duration_from_begin,
+                    ..GarminPoint::default()
+                }
+            })
+            .collect();
+        let gfile = GarminFile {
+            total_distance: 2000.0,
+            points,
+            ..GarminFile::default()
+        };
+        let differential = split_differential(&gfile).unwrap();
+        assert_abs_diff_eq!(differential, 0.1, epsilon = 1e-9);
+        assert_eq!(
+            format_split_differential(differential).as_str(),
+            "+10.0% (positive)"
+        );
+        assert_eq!(SplitType::from_differential(-0.02), SplitType::Negative);
+        assert_eq!(SplitType::from_differential(0.005), SplitType::Even);
+
+        assert_eq!(split_differential(&GarminFile::default()), None);
+    }
+}
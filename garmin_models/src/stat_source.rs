@@ -0,0 +1,343 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use std::{fmt, str::FromStr};
+use uuid::Uuid;
+
+use garmin_utils::pgpool::PgPool;
+
+use crate::{garmin_connect_activity::GarminConnectActivity, strava_activity::StravaActivity};
+
+/// Sources are shown as disagreeing when the largest value is more than this
+/// fraction above the smallest
+pub const STAT_DISCREPANCY_TOLERANCE: f64 = 0.02;
+
+/// Copy chosen values over the `garmin_summary` totals, `$1` limits it to
+/// one filename, run whenever summaries are rewritten from their files
+pub const APPLY_STAT_CHOICES: [&str; 2] = [
+    "
+        UPDATE garmin_summary a SET total_distance = c.value
+        FROM activity_stat_choices c
+        WHERE ($1::text IS NULL OR a.filename = $1)
+          AND c.summary_id = a.id
+          AND c.stat = 'distance'
+    ",
+    "
+        UPDATE garmin_summary a SET total_calories = round(c.value)::integer
+        FROM activity_stat_choices c
+        WHERE ($1::text IS NULL OR a.filename = $1)
+          AND c.summary_id = a.id
+          AND c.stat = 'calories'
+    ",
+];
+
+/// Activity total reported by more than one source
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityStat {
+    Distance,
+    Calories,
+}
+
+impl ActivityStat {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Distance => "distance",
+            Self::Calories => "calories",
+        }
+    }
+
+    #[must_use]
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::Distance => "Distance",
+            Self::Calories => "Calories",
+        }
+    }
+}
+
+impl fmt::Display for ActivityStat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for ActivityStat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "distance" => Ok(Self::Distance),
+            "calories" => Ok(Self::Calories),
+            _ => Err(format_err!("{s} is not a valid activity stat")),
+        }
+    }
+}
+
+/// Where an activity total comes from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StatSource {
+    File,
+    Strava,
+    GarminConnect,
+}
+
+impl StatSource {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Strava => "strava",
+            Self::GarminConnect => "garmin_connect",
+        }
+    }
+
+    #[must_use]
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::File => "File",
+            Self::Strava => "Strava",
+            Self::GarminConnect => "Garmin Connect",
+        }
+    }
+}
+
+impl fmt::Display for StatSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for StatSource {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" | "fit" => Ok(Self::File),
+            "strava" => Ok(Self::Strava),
+            "garmin_connect" | "connect" => Ok(Self::GarminConnect),
+            _ => Err(format_err!("{s} is not a valid stat source")),
+        }
+    }
+}
+
+/// Values of `stat` reported by each source, distance in meters, calories in
+/// kcal, `file_distance` and `file_calories` are the totals of the parsed
+/// file
+#[must_use]
+pub fn source_values(
+    stat: ActivityStat,
+    file_distance: f64,
+    file_calories: i32,
+    strava_activity: Option<&StravaActivity>,
+    connect_activity: Option<&GarminConnectActivity>,
+) -> Vec<(StatSource, f64)> {
+    let (file, strava, connect) = match stat {
+        ActivityStat::Distance => (
+            file_distance,
+            strava_activity.and_then(|a| a.distance),
+            connect_activity.and_then(|a| a.distance),
+        ),
+        ActivityStat::Calories => (
+            f64::from(file_calories),
+            None,
+            connect_activity.and_then(|a| a.calories),
+        ),
+    };
+    let mut values = vec![(StatSource::File, file)];
+    if let Some(strava) = strava {
+        values.push((StatSource::Strava, strava));
+    }
+    if let Some(connect) = connect {
+        values.push((StatSource::GarminConnect, connect));
+    }
+    values
+}
+
+#[derive(FromSqlRow)]
+struct StatChoiceRow {
+    summary_id: Uuid,
+    stat: StackString,
+    source: StackString,
+    value: f64,
+}
+
+/// Source picked as authoritative for one total of an activity, stored in
+/// `activity_stat_choices` and copied onto `garmin_summary` so report totals
+/// use it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct StatChoice {
+    pub summary_id: Uuid,
+    pub stat: ActivityStat,
+    pub source: StatSource,
+    pub value: f64,
+}
+
+impl TryFrom<StatChoiceRow> for StatChoice {
+    type Error = Error;
+    fn try_from(row: StatChoiceRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            summary_id: row.summary_id,
+            stat: row.stat.parse()?,
+            source: row.source.parse()?,
+            value: row.value,
+        })
+    }
+}
+
+impl StatChoice {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_summary_id(pool: &PgPool, summary_id: Uuid) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT summary_id, stat, source, value
+                FROM activity_stat_choices
+                WHERE summary_id = $summary_id
+                ORDER BY stat
+            ",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<StatChoiceRow> = query.fetch(&conn).await?;
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    /// Store the choice and apply it to the activity summary
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_stat_choices (summary_id, stat, source, value)
+                VALUES ($summary_id, $stat, $source, $value)
+                ON CONFLICT (summary_id, stat) DO UPDATE
+                    SET source=EXCLUDED.source,
+                        value=EXCLUDED.value,
+                        updated_at=now()
+            ",
+            summary_id = self.summary_id,
+            stat = self.stat.to_str(),
+            source = self.source.to_str(),
+            value = self.value,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        let query = match self.stat {
+            ActivityStat::Distance => query!(
+                "UPDATE garmin_summary SET total_distance = $value WHERE id = $summary_id",
+                summary_id = self.summary_id,
+                value = self.value,
+            ),
+            ActivityStat::Calories => query!(
+                "UPDATE garmin_summary SET total_calories = $value WHERE id = $summary_id",
+                summary_id = self.summary_id,
+                value = self.value.round() as i32,
+            ),
+        };
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Values of one total across sources, listed on the file report when they
+/// disagree or a source has already been chosen
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatDiscrepancy {
+    pub stat: ActivityStat,
+    pub values: Vec<(StatSource, f64)>,
+    pub chosen: Option<StatSource>,
+}
+
+impl StatDiscrepancy {
+    #[must_use]
+    pub fn from_sources(
+        file_distance: f64,
+        file_calories: i32,
+        strava_activity: Option<&StravaActivity>,
+        connect_activity: Option<&GarminConnectActivity>,
+        choices: &[StatChoice],
+    ) -> Vec<Self> {
+        [ActivityStat::Distance, ActivityStat::Calories]
+            .into_iter()
+            .filter_map(|stat| {
+                let values = source_values(
+                    stat,
+                    file_distance,
+                    file_calories,
+                    strava_activity,
+                    connect_activity,
+                );
+                let chosen = choices.iter().find(|c| c.stat == stat).map(|c| c.source);
+                if chosen.is_some() || Self::disagree(&values) {
+                    Some(Self {
+                        stat,
+                        values,
+                        chosen,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn disagree(values: &[(StatSource, f64)]) -> bool {
+        if values.len() < 2 {
+            return false;
+        }
+        let min = values.iter().map(|(_, v)| *v).fold(f64::MAX, f64::min);
+        let max = values.iter().map(|(_, v)| *v).fold(f64::MIN, f64::max);
+        max - min > STAT_DISCREPANCY_TOLERANCE * max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        garmin_connect_activity::GarminConnectActivity,
+        stat_source::{ActivityStat, StatChoice, StatDiscrepancy, StatSource},
+    };
+
+    #[test]
+    fn test_stat_discrepancy() {
+        let connect: GarminConnectActivity = serde_json::from_str(
+            r#"{"activityId": 1, "startTimeGMT": "2024-01-07 12:30:00", "distance": 5200.0,
+                "duration": 1500.0, "calories": 400.0}"#,
+        )
+        .unwrap();
+
+        // distance is off by 4%, calories by 1%
+        let discrepancies = StatDiscrepancy::from_sources(5000.0, 404, None, Some(&connect), &[]);
+        assert_eq!(discrepancies.len(), 1);
+        assert_eq!(discrepancies[0].stat, ActivityStat::Distance);
+        assert_eq!(
+            discrepancies[0].values,
+            vec![
+                (StatSource::File, 5000.0),
+                (StatSource::GarminConnect, 5200.0)
+            ]
+        );
+        assert_eq!(discrepancies[0].chosen, None);
+
+        let choice = StatChoice {
+            summary_id: Uuid::nil(),
+            stat: ActivityStat::Calories,
+            source: StatSource::GarminConnect,
+            value: 400.0,
+        };
+        let discrepancies =
+            StatDiscrepancy::from_sources(5000.0, 404, None, Some(&connect), &[choice]);
+        assert_eq!(discrepancies.len(), 2);
+        assert_eq!(discrepancies[1].chosen, Some(StatSource::GarminConnect));
+
+        assert!(StatDiscrepancy::from_sources(5000.0, 404, None, None, &[]).is_empty());
+        assert_eq!(
+            "connect".parse::<StatSource>().unwrap(),
+            StatSource::GarminConnect
+        );
+    }
+}
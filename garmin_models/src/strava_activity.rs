@@ -4,14 +4,24 @@ use log::debug;
 use postgres_query::{query, query_dyn, Error as PqError, FromSqlRow, Parameter, Query};
 use serde::{Deserialize, Deserializer, Serialize};
 use stack_string::{format_sstr, StackString};
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 use time::{macros::format_description, Date, OffsetDateTime};
+use tracing::instrument;
 use uuid::Uuid;
 
 use garmin_lib::{date_time_wrapper::DateTimeWrapper, strava_timezone::StravaTimeZone};
 use garmin_utils::{pgpool::PgPool, sport_types, sport_types::SportTypes};
 
-use crate::garmin_summary::GarminSummary;
+use crate::{
+    garmin_summary::GarminSummary,
+    keyset::{KeysetRow, PageStart},
+    provenance::Provenance,
+    strava_link::{link_tolerant, MANUAL_LINK},
+    strava_title::is_default_name,
+};
 
 #[derive(Serialize, Deserialize, FromSqlRow, Debug, Clone, PartialEq)]
 pub struct StravaActivity {
@@ -27,6 +37,23 @@ pub struct StravaActivity {
     #[serde(alias = "type", with = "sport_types")]
     pub activity_type: SportTypes,
     pub timezone: StravaTimeZone,
+    #[serde(default)]
+    pub kudos_count: i32,
+    #[serde(default)]
+    pub comment_count: i32,
+    #[serde(default)]
+    pub total_photo_count: i32,
+    /// Not part of the activity list response, filled in from the photos
+    /// endpoint during sync
+    #[serde(default)]
+    pub photo_urls: Vec<StackString>,
+    /// Linked strava account the activity was synced from, not part of the
+    /// api response
+    #[serde(default)]
+    pub strava_account: Option<StackString>,
+    /// Tagged as a commute on Strava
+    #[serde(default)]
+    pub commute: bool,
 }
 
 impl Default for StravaActivity {
@@ -43,6 +70,21 @@ impl Default for StravaActivity {
             elev_low: None,
             activity_type: SportTypes::None,
             timezone: StravaTimeZone::default(),
+            kudos_count: 0,
+            comment_count: 0,
+            total_photo_count: 0,
+            photo_urls: Vec::new(),
+            strava_account: None,
+            commute: false,
+        }
+    }
+}
+
+impl KeysetRow for StravaActivity {
+    fn page_after(&self) -> PageStart {
+        PageStart::After {
+            datetime: self.start_date.into(),
+            id: self.id,
         }
     }
 }
@@ -52,7 +94,7 @@ impl StravaActivity {
         select_str: &'a str,
         start_date: Option<&'a Date>,
         end_date: Option<&'a Date>,
-        offset: Option<usize>,
+        start: Option<&'a PageStart>,
         limit: Option<usize>,
         order_str: &'a str,
     ) -> Result<Query<'a>, PqError> {
@@ -66,6 +108,11 @@ impl StravaActivity {
             conditions.push("date(start_date) <= $end_date");
             query_bindings.push(("end_date", d as Parameter));
         }
+        if let Some(PageStart::After { datetime, id }) = start {
+            conditions.push("(start_date, id) > ($after_datetime, $after_id)");
+            query_bindings.push(("after_datetime", datetime as Parameter));
+            query_bindings.push(("after_id", id as Parameter));
+        }
         let mut query = format_sstr!(
             "SELECT {select_str} FROM strava_activities {} {order_str}",
             if conditions.is_empty() {
@@ -74,7 +121,7 @@ impl StravaActivity {
                 format_sstr!("WHERE {}", conditions.join(" AND "))
             }
         );
-        if let Some(offset) = &offset {
+        if let Some(offset) = start.and_then(PageStart::offset) {
             query.push_str(&format_sstr!(" OFFSET {offset}"));
         }
         if let Some(limit) = &limit {
@@ -91,16 +138,16 @@ impl StravaActivity {
         pool: &PgPool,
         start_date: Option<Date>,
         end_date: Option<Date>,
-        offset: Option<usize>,
+        start: Option<PageStart>,
         limit: Option<usize>,
     ) -> Result<impl Stream<Item = Result<Self, PqError>>, Error> {
         let query = Self::get_strava_activity_query(
             "*",
             start_date.as_ref(),
             end_date.as_ref(),
-            offset,
+            start.as_ref(),
             limit,
-            "ORDER BY start_date",
+            "ORDER BY start_date, id",
         )?;
         let conn = pool.get().await?;
         query.fetch_streaming(&conn).await.map_err(Into::into)
@@ -146,6 +193,14 @@ impl StravaActivity {
         query.fetch_opt(&conn).await.map_err(Into::into)
     }
 
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: i64) -> Result<Option<Self>, Error> {
+        let query = query!("SELECT * FROM strava_activities WHERE id=$id", id = id);
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
     /// # Errors
     /// Return error if db query fails
     pub async fn get_from_summary_id(
@@ -160,18 +215,73 @@ impl StravaActivity {
         query.fetch_opt(&conn).await.map_err(Into::into)
     }
 
+    /// Activities since `start_date` whose name is still the one Strava
+    /// generated when they were first imported
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_default_named(pool: &PgPool, start_date: Date) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT * FROM strava_activities
+                WHERE date(start_date) >= $start_date
+                  AND name = default_name
+                ORDER BY start_date, id
+            ",
+            start_date = start_date,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Activities started in `year` with the most kudos
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_most_kudoed(pool: &PgPool, year: i32, limit: i64) -> Result<Vec<Self>, Error> {
+        let year = f64::from(year);
+        let query = query!(
+            "
+                SELECT * FROM strava_activities
+                WHERE date_part('year', start_date) = $year
+                  AND kudos_count > 0
+                ORDER BY kudos_count DESC, comment_count DESC, start_date
+                LIMIT $limit
+            ",
+            year = year,
+            limit = limit,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Insert the activity along with where it came from, provenance is
+    /// only recorded when the activity is first stored
     /// # Errors
     /// Return error if db query fails
-    pub async fn insert_into_db(&self, pool: &PgPool) -> Result<(), Error> {
+    pub async fn insert_into_db(
+        &self,
+        pool: &PgPool,
+        provenance: &Provenance,
+    ) -> Result<(), Error> {
+        // only a name which looks generated on first import counts as the
+        // default, renames on Strava before or after that are left alone
+        let default_name = if is_default_name(&self.name) {
+            Some(&self.name)
+        } else {
+            None
+        };
         let query = query!(
             "
                 INSERT INTO strava_activities (
                     id,name,start_date,distance,moving_time,elapsed_time,
-                    total_elevation_gain,elev_high,elev_low,activity_type,timezone
+                    total_elevation_gain,elev_high,elev_low,activity_type,timezone,
+                    kudos_count,comment_count,total_photo_count,photo_urls,strava_account,
+                    commute,default_name,provider,import_path,raw_file,imported_at
                 )
                 VALUES (
                     $id,$name,$start_date,$distance,$moving_time,$elapsed_time,
-                    $total_elevation_gain,$elev_high,$elev_low,$activity_type,$timezone
+                    $total_elevation_gain,$elev_high,$elev_low,$activity_type,$timezone,
+                    $kudos_count,$comment_count,$total_photo_count,$photo_urls,$strava_account,
+                    $commute,$default_name,$provider,$import_path,$raw_file,$imported_at
                 )",
             id = self.id,
             name = self.name,
@@ -184,11 +294,24 @@ impl StravaActivity {
             elev_low = self.elev_low,
             activity_type = self.activity_type,
             timezone = self.timezone,
+            kudos_count = self.kudos_count,
+            comment_count = self.comment_count,
+            total_photo_count = self.total_photo_count,
+            photo_urls = self.photo_urls,
+            strava_account = self.strava_account,
+            commute = self.commute,
+            default_name = default_name,
+            provider = provenance.provider.to_str(),
+            import_path = provenance.import_path,
+            raw_file = provenance.raw_file,
+            imported_at = provenance.imported_at,
         );
         let conn = pool.get().await?;
         query.execute(&conn).await.map(|_| ()).map_err(Into::into)
     }
 
+    /// Photos stay as they are when the activity has photos but none could
+    /// be fetched
     /// # Errors
     /// Return error if db query fails
     pub async fn update_db(&self, pool: &PgPool) -> Result<(), Error> {
@@ -198,7 +321,15 @@ impl StravaActivity {
                     name=$name,start_date=$start_date,distance=$distance,moving_time=$moving_time,
                     elapsed_time=$elapsed_time,total_elevation_gain=$total_elevation_gain,
                     elev_high=$elev_high,elev_low=$elev_low,activity_type=$activity_type,
-                    timezone=$timezone
+                    timezone=$timezone,kudos_count=$kudos_count,comment_count=$comment_count,
+                    total_photo_count=$total_photo_count,
+                    photo_urls=CASE
+                        WHEN $total_photo_count > 0 AND cardinality($photo_urls::text[]) = 0
+                            THEN photo_urls
+                        ELSE $photo_urls
+                    END,
+                    strava_account=COALESCE($strava_account, strava_account),
+                    commute=$commute
                 WHERE id=$id
             ",
             id = self.id,
@@ -212,6 +343,12 @@ impl StravaActivity {
             elev_low = self.elev_low,
             activity_type = self.activity_type,
             timezone = self.timezone,
+            kudos_count = self.kudos_count,
+            comment_count = self.comment_count,
+            total_photo_count = self.total_photo_count,
+            photo_urls = self.photo_urls,
+            strava_account = self.strava_account,
+            commute = self.commute,
         );
         let conn = pool.get().await?;
         query.execute(&conn).await?;
@@ -221,9 +358,11 @@ impl StravaActivity {
     /// # Errors
     /// Return error if db query fails
     #[allow(clippy::manual_filter_map)]
+    #[instrument(skip_all, fields(db.system = "postgresql"))]
     pub async fn upsert_activities(
         activities: &[Self],
         pool: &PgPool,
+        provenance: &Provenance,
     ) -> Result<Vec<StackString>, Error> {
         let mut output = Vec::new();
         let mut existing_activities: HashMap<_, _> =
@@ -262,7 +401,7 @@ impl StravaActivity {
         let futures = insert_items.into_iter().map(|activity| {
             let pool = pool.clone();
             async move {
-                activity.insert_into_db(&pool).await?;
+                activity.insert_into_db(&pool, provenance).await?;
                 let id_str = StackString::from_display(activity.id);
                 Ok(id_str)
             }
@@ -274,21 +413,356 @@ impl StravaActivity {
         Ok(output)
     }
 
+    /// Upsert activities from the training activities HAR export, which
+    /// lacks the timezone, commute flag, kudos, comments and photos, so
+    /// those keep the values of a previous api sync, `provenance` is recorded
+    /// for activities not stored before
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_har_activities(
+        activities: &[Self],
+        pool: &PgPool,
+        provenance: &Provenance,
+    ) -> Result<Vec<StackString>, Error> {
+        let query = "
+            INSERT INTO strava_activities AS t (
+                id,name,start_date,distance,moving_time,elapsed_time,
+                total_elevation_gain,activity_type,timezone,default_name,
+                provider,import_path,raw_file,imported_at
+            )
+            VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14)
+            ON CONFLICT (id) DO UPDATE SET
+                name=EXCLUDED.name,
+                start_date=EXCLUDED.start_date,
+                distance=COALESCE(EXCLUDED.distance, t.distance),
+                moving_time=COALESCE(EXCLUDED.moving_time, t.moving_time),
+                elapsed_time=EXCLUDED.elapsed_time,
+                total_elevation_gain=COALESCE(EXCLUDED.total_elevation_gain, t.total_elevation_gain),
+                activity_type=EXCLUDED.activity_type
+        ";
+        let provider = provenance.provider.to_str();
+        let mut output = Vec::new();
+        let mut conn = pool.get().await?;
+        let tran = conn.transaction().await?;
+        for activity in activities {
+            let default_name = if is_default_name(&activity.name) {
+                Some(&activity.name)
+            } else {
+                None
+            };
+            tran.execute(
+                query,
+                &[
+                    &activity.id,
+                    &activity.name,
+                    &activity.start_date,
+                    &activity.distance,
+                    &activity.moving_time,
+                    &activity.elapsed_time,
+                    &activity.total_elevation_gain,
+                    &activity.activity_type,
+                    &activity.timezone,
+                    &default_name,
+                    &provider,
+                    &provenance.import_path,
+                    &provenance.raw_file,
+                    &provenance.imported_at,
+                ],
+            )
+            .await?;
+            output.push(StackString::from_display(activity.id));
+        }
+        tran.commit().await?;
+        Ok(output)
+    }
+
+    /// Fields which differ between `self` and `new`
+    #[must_use]
+    pub fn diff(&self, new: &Self) -> Vec<StravaFieldDiff> {
+        [
+            field_diff("name", &self.name, &new.name, format_display),
+            field_diff(
+                "start_date",
+                &self.start_date,
+                &new.start_date,
+                format_display,
+            ),
+            field_diff("distance", &self.distance, &new.distance, format_option),
+            field_diff(
+                "moving_time",
+                &self.moving_time,
+                &new.moving_time,
+                format_option,
+            ),
+            field_diff(
+                "elapsed_time",
+                &self.elapsed_time,
+                &new.elapsed_time,
+                format_display,
+            ),
+            field_diff(
+                "total_elevation_gain",
+                &self.total_elevation_gain,
+                &new.total_elevation_gain,
+                format_option,
+            ),
+            field_diff("elev_high", &self.elev_high, &new.elev_high, format_option),
+            field_diff("elev_low", &self.elev_low, &new.elev_low, format_option),
+            field_diff(
+                "activity_type",
+                &self.activity_type,
+                &new.activity_type,
+                format_display,
+            ),
+            field_diff("timezone", &self.timezone, &new.timezone, format_display),
+            field_diff(
+                "kudos_count",
+                &self.kudos_count,
+                &new.kudos_count,
+                format_display,
+            ),
+            field_diff(
+                "comment_count",
+                &self.comment_count,
+                &new.comment_count,
+                format_display,
+            ),
+            field_diff(
+                "total_photo_count",
+                &self.total_photo_count,
+                &new.total_photo_count,
+                format_display,
+            ),
+            field_diff("photo_urls", &self.photo_urls, &new.photo_urls, |v| {
+                v.join(",").into()
+            }),
+            field_diff(
+                "strava_account",
+                &self.strava_account,
+                &new.strava_account,
+                format_option,
+            ),
+            field_diff("commute", &self.commute, &new.commute, format_display),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Changes `upsert_activities` followed by `fix_summary_id_in_db` would
+    /// make for `activities`, without writing anything
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn plan_upsert(
+        activities: &[Self],
+        pool: &PgPool,
+    ) -> Result<Vec<StravaSyncChange>, Error> {
+        #[derive(FromSqlRow)]
+        struct IdRow {
+            id: i64,
+        }
+        #[derive(FromSqlRow)]
+        struct BeginRow {
+            begin_datetime: DateTimeWrapper,
+        }
+
+        // only the incoming activities are compared, not the whole table
+        let ids: Vec<i64> = activities.iter().map(|a| a.id).collect();
+        let conn = pool.get().await?;
+        let query = query!(
+            "SELECT * FROM strava_activities WHERE id = ANY($ids)",
+            ids = ids,
+        );
+        let existing: Vec<Self> = query.fetch(&conn).await?;
+        let existing_activities: HashMap<_, _> = existing
+            .into_iter()
+            .map(|activity| (activity.id, activity))
+            .collect();
+        let query = query!(
+            "
+                SELECT a.id
+                FROM strava_activities a
+                WHERE a.id = ANY($ids)
+                  AND a.summary_id IS NULL
+                  AND a.link_source IS NULL
+                  AND EXISTS (
+                    SELECT 1 FROM garmin_summary b WHERE b.begin_datetime = a.start_date
+                  )
+            ",
+            ids = ids,
+        );
+        let rows: Vec<IdRow> = query.fetch(&conn).await?;
+        let unlinked: HashSet<i64> = rows.into_iter().map(|row| row.id).collect();
+        let start_dates: Vec<OffsetDateTime> = activities
+            .iter()
+            .filter(|a| !existing_activities.contains_key(&a.id))
+            .map(|a| a.start_date.to_offsetdatetime())
+            .collect();
+        let query = query!(
+            "
+                SELECT begin_datetime FROM garmin_summary
+                WHERE begin_datetime = ANY($start_dates)
+            ",
+            start_dates = start_dates,
+        );
+        let rows: Vec<BeginRow> = query.fetch(&conn).await?;
+        let summary_starts: HashSet<OffsetDateTime> = rows
+            .into_iter()
+            .map(|row| row.begin_datetime.to_offsetdatetime())
+            .collect();
+
+        let mut changes = Vec::new();
+        for activity in activities {
+            let (action, diffs, is_linkable) = match existing_activities.get(&activity.id) {
+                Some(existing) => {
+                    let diffs = existing.diff(activity);
+                    if diffs.is_empty() && !unlinked.contains(&activity.id) {
+                        continue;
+                    }
+                    let action = (!diffs.is_empty()).then_some(StravaSyncAction::Update);
+                    (action, diffs, unlinked.contains(&activity.id))
+                }
+                None => (
+                    Some(StravaSyncAction::Create),
+                    Vec::new(),
+                    summary_starts.contains(&activity.start_date.to_offsetdatetime()),
+                ),
+            };
+            if let Some(action) = action {
+                changes.push(StravaSyncChange {
+                    id: activity.id,
+                    name: activity.name.clone(),
+                    action,
+                    diffs,
+                });
+            }
+            if is_linkable {
+                changes.push(StravaSyncChange {
+                    id: activity.id,
+                    name: activity.name.clone(),
+                    action: StravaSyncAction::Link,
+                    diffs: Vec::new(),
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Link activities to the summary with the same start time, then those
+    /// still unlinked to a summary within the tolerances of
+    /// `strava_link::link_tolerant`, links made by hand are kept
     /// # Errors
     /// Return error if db query fails
     pub async fn fix_summary_id_in_db(pool: &PgPool) -> Result<(), Error> {
         let query = "
-            UPDATE strava_activities SET summary_id = (
-                SELECT id FROM garmin_summary a WHERE a.begin_datetime = start_date
+            UPDATE strava_activities s SET summary_id = (
+                SELECT a.id FROM garmin_summary a
+                WHERE a.begin_datetime = s.start_date
+                  AND NOT EXISTS (
+                    SELECT 1 FROM strava_activities o
+                    WHERE o.summary_id = a.id AND o.link_source = $1
+                  )
             )
-            WHERE summary_id IS NULL
+            WHERE s.summary_id IS NULL AND s.link_source IS NULL
         ";
         let conn = pool.get().await?;
-        conn.execute(query, &[]).await?;
+        conn.execute(query, &[&MANUAL_LINK]).await?;
+        link_tolerant(pool).await?;
         Ok(())
     }
 }
 
+/// What a strava sync does with an activity
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StravaSyncAction {
+    Create,
+    Update,
+    /// Attach to the garmin summary with the same start time
+    Link,
+}
+
+impl StravaSyncAction {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Link => "link",
+        }
+    }
+}
+
+impl fmt::Display for StravaSyncAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StravaFieldDiff {
+    pub field: &'static str,
+    pub old: StackString,
+    pub new: StackString,
+}
+
+/// Change a strava sync makes (or would make in a dry run) to one activity,
+/// `diffs` lists the changed fields of an update
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StravaSyncChange {
+    pub id: i64,
+    pub name: StackString,
+    pub action: StravaSyncAction,
+    pub diffs: Vec<StravaFieldDiff>,
+}
+
+impl StravaSyncChange {
+    /// e.g. `update 1234 Morning Run`, with `verbose` followed by a
+    /// `    field: old -> new` line for each changed field
+    #[must_use]
+    pub fn describe(&self, verbose: bool) -> StackString {
+        let mut output = format_sstr!("{} {} {}", self.action, self.id, self.name);
+        if verbose {
+            for diff in &self.diffs {
+                output.push_str(&format_sstr!(
+                    "\n    {}: {} -> {}",
+                    diff.field,
+                    diff.old,
+                    diff.new
+                ));
+            }
+        }
+        output
+    }
+}
+
+fn field_diff<T: PartialEq>(
+    field: &'static str,
+    old: &T,
+    new: &T,
+    format: impl Fn(&T) -> StackString,
+) -> Option<StravaFieldDiff> {
+    if old == new {
+        None
+    } else {
+        Some(StravaFieldDiff {
+            field,
+            old: format(old),
+            new: format(new),
+        })
+    }
+}
+
+fn format_display<T: fmt::Display>(value: &T) -> StackString {
+    StackString::from_display(value)
+}
+
+fn format_option<T: fmt::Display>(value: &Option<T>) -> StackString {
+    value
+        .as_ref()
+        .map_or_else(|| "null".into(), StackString::from_display)
+}
+
 impl From<GarminSummary> for StravaActivity {
     fn from(item: GarminSummary) -> Self {
         Self {
@@ -361,7 +835,9 @@ where
 mod tests {
     use anyhow::Error;
 
-    use crate::strava_activity::{StravaActivity, StravaActivityHarJson};
+    use crate::strava_activity::{
+        StravaActivity, StravaActivityHarJson, StravaSyncAction, StravaSyncChange,
+    };
 
     #[test]
     fn test_strava_activity_har_activity_model() -> Result<(), Error> {
@@ -371,4 +847,39 @@ mod tests {
         assert_eq!(activities.len(), 20);
         Ok(())
     }
+
+    #[test]
+    fn test_strava_activity_diff() {
+        let old = StravaActivity {
+            name: "Morning Run".into(),
+            id: 1234,
+            distance: Some(5000.0),
+            kudos_count: 2,
+            ..StravaActivity::default()
+        };
+        let new = StravaActivity {
+            name: "Tempo Run".into(),
+            distance: None,
+            kudos_count: 5,
+            ..old.clone()
+        };
+        assert!(old.diff(&old).is_empty());
+        let diffs = old.diff(&new);
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diffs[1].field, "distance");
+        assert_eq!(diffs[1].new.as_str(), "null");
+
+        let change = StravaSyncChange {
+            id: new.id,
+            name: new.name.clone(),
+            action: StravaSyncAction::Update,
+            diffs,
+        };
+        assert_eq!(change.describe(false).as_str(), "update 1234 Tempo Run");
+        assert_eq!(
+            change.describe(true).as_str(),
+            "update 1234 Tempo Run\n    name: Morning Run -> Tempo Run\n    distance: 5000 -> \
+             null\n    kudos_count: 2 -> 5"
+        );
+    }
 }
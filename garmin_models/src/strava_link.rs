@@ -0,0 +1,306 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+use crate::{garmin_summary::GarminSummary, strava_activity::StravaActivity};
+
+/// Largest difference in start time of a strava activity and the garmin
+/// summary it is linked to when the start times don't match exactly
+pub const LINK_TIME_TOLERANCE: Duration = Duration::minutes(2);
+/// Largest relative difference in distance for a tolerant link
+pub const LINK_DISTANCE_TOLERANCE: f64 = 0.005;
+/// `link_source` of strava activities linked (or unlinked) by hand, the
+/// automatic passes leave them alone
+pub const MANUAL_LINK: &str = "manual";
+
+/// Whether a strava activity and garmin summary are close enough in start
+/// time and distance to be the same activity
+#[must_use]
+pub fn is_tolerant_match(
+    strava_start: OffsetDateTime,
+    strava_distance: f64,
+    summary_start: OffsetDateTime,
+    summary_distance: f64,
+) -> bool {
+    if (strava_start - summary_start).abs() > LINK_TIME_TOLERANCE {
+        return false;
+    }
+    let largest = strava_distance.max(summary_distance);
+    largest <= 0.0
+        || (strava_distance - summary_distance).abs() <= LINK_DISTANCE_TOLERANCE * largest
+}
+
+#[derive(FromSqlRow, Debug, Clone, PartialEq)]
+struct LinkCandidate {
+    strava_id: i64,
+    summary_id: Uuid,
+    start_date: DateTimeWrapper,
+    distance: Option<f64>,
+    begin_datetime: DateTimeWrapper,
+    total_distance: f64,
+}
+
+/// Pair each strava activity with its closest matching summary, each
+/// summary is used at most once
+fn choose_links(candidates: &[LinkCandidate]) -> Vec<(i64, Uuid)> {
+    let mut matches: Vec<_> = candidates
+        .iter()
+        .filter(|c| {
+            is_tolerant_match(
+                c.start_date.to_offsetdatetime(),
+                c.distance.unwrap_or(0.0),
+                c.begin_datetime.to_offsetdatetime(),
+                c.total_distance,
+            )
+        })
+        .map(|c| {
+            let offset =
+                (c.start_date.to_offsetdatetime() - c.begin_datetime.to_offsetdatetime()).abs();
+            (offset, c.strava_id, c.summary_id)
+        })
+        .collect();
+    matches.sort();
+    let mut linked_strava = HashSet::new();
+    let mut linked_summaries = HashSet::new();
+    matches
+        .into_iter()
+        .filter_map(|(_, strava_id, summary_id)| {
+            if linked_strava.contains(&strava_id) || linked_summaries.contains(&summary_id) {
+                return None;
+            }
+            linked_strava.insert(strava_id);
+            linked_summaries.insert(summary_id);
+            Some((strava_id, summary_id))
+        })
+        .collect()
+}
+
+/// Link strava activities without a summary to a summary starting within
+/// `LINK_TIME_TOLERANCE` with a distance within `LINK_DISTANCE_TOLERANCE`,
+/// returns the (strava id, summary id) pairs linked. Only unlinked
+/// activities are considered, matched by a range on the indexed
+/// `begin_datetime`, and activities or summaries linked by hand are skipped
+/// # Errors
+/// Return error if db query fails
+pub async fn link_tolerant(pool: &PgPool) -> Result<Vec<(i64, Uuid)>, Error> {
+    let seconds = LINK_TIME_TOLERANCE.as_seconds_f64();
+    let query = query!(
+        "
+            SELECT s.id as strava_id,
+                   a.id as summary_id,
+                   s.start_date,
+                   s.distance,
+                   a.begin_datetime,
+                   a.total_distance
+            FROM strava_activities s
+            JOIN garmin_summary a
+              ON a.begin_datetime BETWEEN s.start_date - make_interval(secs => $seconds)
+                                      AND s.start_date + make_interval(secs => $seconds)
+            WHERE s.summary_id IS NULL
+              AND s.link_source IS NULL
+              AND NOT EXISTS (
+                SELECT 1 FROM strava_activities o WHERE o.summary_id = a.id
+              )
+        ",
+        seconds = seconds,
+    );
+    let mut conn = pool.get().await?;
+    let tran = conn.transaction().await?;
+    let candidates: Vec<LinkCandidate> = tran
+        .query(query.sql(), query.parameters())
+        .await?
+        .iter()
+        .map(LinkCandidate::from_row)
+        .collect::<Result<_, _>>()?;
+    let links = choose_links(&candidates);
+    for (strava_id, summary_id) in &links {
+        tran.execute(
+            "UPDATE strava_activities SET summary_id = $1 WHERE id = $2 AND summary_id IS NULL",
+            &[summary_id, strava_id],
+        )
+        .await?;
+    }
+    tran.commit().await?;
+    Ok(links)
+}
+
+/// Link a strava activity to a summary by hand, replacing any existing link
+/// of either. Both the new link and the activities unlinked from the summary
+/// are marked as `MANUAL_LINK` so later automatic passes keep them
+/// # Errors
+/// Return error if either activity doesn't exist or db query fails
+pub async fn link_manual(pool: &PgPool, summary_id: Uuid, strava_id: i64) -> Result<(), Error> {
+    let mut conn = pool.get().await?;
+    let tran = conn.transaction().await?;
+    let row = tran
+        .query_one(
+            "SELECT count(*) FROM garmin_summary WHERE id = $1",
+            &[&summary_id],
+        )
+        .await?;
+    let count: i64 = row.try_get(0)?;
+    if count == 0 {
+        return Err(format_err!("No activity {summary_id}"));
+    }
+    let row = tran
+        .query_one(
+            "SELECT count(*) FROM strava_activities WHERE id = $1",
+            &[&strava_id],
+        )
+        .await?;
+    let count: i64 = row.try_get(0)?;
+    if count == 0 {
+        return Err(format_err!("No strava activity {strava_id}"));
+    }
+    tran.execute(
+        "
+            UPDATE strava_activities SET summary_id = NULL, link_source = $3
+            WHERE summary_id = $1 AND id != $2
+        ",
+        &[&summary_id, &strava_id, &MANUAL_LINK],
+    )
+    .await?;
+    tran.execute(
+        "UPDATE strava_activities SET summary_id = $1, link_source = $3 WHERE id = $2",
+        &[&summary_id, &strava_id, &MANUAL_LINK],
+    )
+    .await?;
+    tran.commit().await?;
+    Ok(())
+}
+
+/// Strava activities and garmin summaries since `start` which aren't linked
+/// to each other
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct UnmatchedActivities {
+    pub strava_activities: Vec<StravaActivity>,
+    pub summaries: Vec<GarminSummary>,
+}
+
+impl UnmatchedActivities {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get(pool: &PgPool, start: OffsetDateTime) -> Result<Self, Error> {
+        let conn = pool.get().await?;
+        let query = query!(
+            "
+                SELECT * FROM strava_activities
+                WHERE summary_id IS NULL AND start_date >= $start
+                ORDER BY start_date DESC
+            ",
+            start = start,
+        );
+        let strava_activities: Vec<StravaActivity> = query.fetch(&conn).await?;
+        let query = query!(
+            "
+            SELECT a.id,
+                   a.filename,
+                   a.begin_datetime,
+                   a.sport,
+                   a.total_calories,
+                   a.total_distance,
+                   a.total_duration,
+                   a.total_hr_dur,
+                   a.total_hr_dis,
+                   a.md5sum,
+                   a.course_difficulty,
+                   a.split_differential,
+                   a.avg_power,
+                   a.normalized_power,
+                   a.avg_temperature,
+                   a.calories_estimated
+            FROM garmin_summary a
+            WHERE a.begin_datetime >= $start
+              AND NOT EXISTS (
+                SELECT 1 FROM strava_activities s WHERE s.summary_id = a.id
+              )
+            ORDER BY a.begin_datetime DESC",
+            start = start,
+        );
+        let summaries: Vec<GarminSummary> = query.fetch(&conn).await?;
+        Ok(Self {
+            strava_activities,
+            summaries,
+        })
+    }
+
+    /// Unlinked summaries starting within a day of each strava activity,
+    /// closest first, as candidates for a manual link
+    #[must_use]
+    pub fn candidates(&self) -> HashMap<i64, Vec<&GarminSummary>> {
+        self.strava_activities
+            .iter()
+            .map(|activity| {
+                let start = activity.start_date.to_offsetdatetime();
+                let mut nearby: Vec<_> = self
+                    .summaries
+                    .iter()
+                    .filter(|s| {
+                        (s.begin_datetime.to_offsetdatetime() - start).abs() <= Duration::DAY
+                    })
+                    .collect();
+                nearby.sort_by_key(|s| (s.begin_datetime.to_offsetdatetime() - start).abs());
+                (activity.id, nearby)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+    use uuid::Uuid;
+
+    use crate::strava_link::{choose_links, is_tolerant_match, LinkCandidate};
+
+    #[test]
+    fn test_is_tolerant_match() {
+        let start = datetime!(2024-05-04 13:00:00 UTC);
+        assert!(is_tolerant_match(start, 10_000.0, start, 10_000.0));
+        assert!(is_tolerant_match(
+            start,
+            10_000.0,
+            start + Duration::seconds(90),
+            10_040.0
+        ));
+        // more than 2 minutes apart
+        assert!(!is_tolerant_match(
+            start,
+            10_000.0,
+            start - Duration::seconds(150),
+            10_000.0
+        ));
+        // distance off by 1%
+        assert!(!is_tolerant_match(start, 10_000.0, start, 10_100.0));
+        // indoor activities without distance
+        assert!(is_tolerant_match(start, 0.0, start, 0.0));
+    }
+
+    #[test]
+    fn test_choose_links() {
+        let start = datetime!(2024-05-04 13:00:00 UTC);
+        let near = Uuid::new_v4();
+        let far = Uuid::new_v4();
+        let candidate = |strava_id, summary_id, offset| LinkCandidate {
+            strava_id,
+            summary_id,
+            start_date: start.into(),
+            distance: Some(5000.0),
+            begin_datetime: (start + Duration::seconds(offset)).into(),
+            total_distance: 5000.0,
+        };
+        let candidates = [
+            candidate(1, far, 100),
+            candidate(1, near, 10),
+            // the near summary is already taken by activity 1
+            candidate(2, near, 30),
+        ];
+        assert_eq!(choose_links(&candidates), vec![(1, near)]);
+    }
+}
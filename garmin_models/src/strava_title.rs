@@ -0,0 +1,214 @@
+use stack_string::{format_sstr, StackString};
+use time::{OffsetDateTime, Weekday};
+
+use garmin_lib::heart_rate_profile::HeartRateProfile;
+use garmin_utils::{
+    garmin_util::{print_h_m_s, METERS_PER_MILE},
+    sport_types::SportTypes,
+};
+
+use crate::garmin_file::GarminFile;
+
+/// Template used when `strava_title_template` is set to an empty string.
+/// Placeholders are `{weekday}`, `{time_of_day}`, `{workout}`, `{sport}`,
+/// `{distance}` (miles), `{duration}`, `{pace}` (per mile) and
+/// `{temperature}` (Fahrenheit), text in square brackets is dropped when a
+/// placeholder inside it has no value.
+pub const DEFAULT_TITLE_TEMPLATE: &str =
+    "{weekday} {workout} \u{2014} {distance}mi[ @ {pace}/mi][, {temperature}\u{b0}F]";
+
+/// Names Strava gives activities which weren't renamed
+const DEFAULT_NAME_PREFIXES: [&str; 5] = ["Morning", "Lunch", "Afternoon", "Evening", "Night"];
+
+/// Running at least this far is a long run regardless of effort
+pub const LONG_RUN_MILES: f64 = 10.0;
+
+/// Whether `name` looks like one Strava generated, e.g. "Morning Run"
+#[must_use]
+pub fn is_default_name(name: &str) -> bool {
+    let mut words = name.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(prefix), Some(_), None) => DEFAULT_NAME_PREFIXES.contains(&prefix),
+        _ => false,
+    }
+}
+
+/// Strava's time of day label for a local start time
+#[must_use]
+pub fn time_of_day(begin: OffsetDateTime) -> &'static str {
+    match begin.hour() {
+        4..=10 => "Morning",
+        11..=13 => "Lunch",
+        14..=17 => "Afternoon",
+        18..=21 => "Evening",
+        _ => "Night",
+    }
+}
+
+/// Effort label from distance and heart rate reserve, falls back on the
+/// Strava activity type when there's no heart rate
+#[must_use]
+pub fn workout_label(gfile: &GarminFile, profile: &HeartRateProfile) -> StackString {
+    if gfile.sport == SportTypes::Running
+        && gfile.total_distance / METERS_PER_MILE >= LONG_RUN_MILES
+    {
+        return "Long Run".into();
+    }
+    if gfile.total_hr_dis <= 0.0 {
+        return gfile.sport.to_strava_activity();
+    }
+    let reserve = profile.reserve_fraction(gfile.total_hr_dur / gfile.total_hr_dis);
+    let effort = if reserve >= 0.8 {
+        "Tempo"
+    } else if reserve >= 0.7 {
+        "Steady"
+    } else {
+        "Easy"
+    };
+    format_sstr!("{effort} {}", gfile.sport.to_strava_activity())
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Monday => "Monday",
+        Weekday::Tuesday => "Tuesday",
+        Weekday::Wednesday => "Wednesday",
+        Weekday::Thursday => "Thursday",
+        Weekday::Friday => "Friday",
+        Weekday::Saturday => "Saturday",
+        Weekday::Sunday => "Sunday",
+    }
+}
+
+/// Replace `{name}` placeholders with `lookup(name)`, a bracketed section
+/// is dropped entirely if any of its placeholders has no value while
+/// placeholders outside brackets are left empty
+#[must_use]
+pub fn render_template(
+    template: &str,
+    lookup: impl Fn(&str) -> Option<StackString>,
+) -> StackString {
+    fn render_section(
+        section: &str,
+        lookup: &impl Fn(&str) -> Option<StackString>,
+    ) -> Option<StackString> {
+        let mut output = StackString::new();
+        let mut rest = section;
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            output.push_str(&lookup(&rest[start + 1..end])?);
+            rest = &rest[end + 1..];
+        }
+        output.push_str(rest);
+        Some(output)
+    }
+
+    let optional = |name: &str| Some(lookup(name).unwrap_or_default());
+    let mut output = StackString::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('[') {
+        let end = match rest[start..].find(']') {
+            Some(end) => start + end,
+            None => break,
+        };
+        output.push_str(&render_section(&rest[..start], &optional).unwrap_or_default());
+        if let Some(section) = render_section(&rest[start + 1..end], &lookup) {
+            output.push_str(&section);
+        }
+        rest = &rest[end + 1..];
+    }
+    output.push_str(&render_section(rest, &optional).unwrap_or_default());
+    output.trim().into()
+}
+
+/// Title for `gfile` from `template`, `begin` is the start time in the
+/// activity's local timezone
+#[must_use]
+pub fn generate_title(
+    template: &str,
+    gfile: &GarminFile,
+    begin: OffsetDateTime,
+    profile: &HeartRateProfile,
+) -> StackString {
+    let template = if template.is_empty() {
+        DEFAULT_TITLE_TEMPLATE
+    } else {
+        template
+    };
+    let miles = gfile.total_distance / METERS_PER_MILE;
+    render_template(template, |name| match name {
+        "weekday" => Some(weekday_name(begin.weekday()).into()),
+        "time_of_day" => Some(time_of_day(begin).into()),
+        "workout" => Some(workout_label(gfile, profile)),
+        "sport" => Some(gfile.sport.display_name()),
+        "distance" if miles > 0.0 => Some(format_sstr!("{miles:.1}")),
+        "duration" => print_h_m_s(gfile.total_duration, false).ok(),
+        "pace" if miles > 0.0 => print_h_m_s(gfile.total_duration / miles, false)
+            .ok()
+            .map(|p| p.strip_prefix('0').unwrap_or(&p).into()),
+        "temperature" => gfile
+            .avg_temperature()
+            .map(|t| format_sstr!("{:.0}", t * 9.0 / 5.0 + 32.0)),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use garmin_lib::heart_rate_profile::HeartRateProfile;
+    use garmin_utils::{garmin_util::METERS_PER_MILE, sport_types::SportTypes};
+
+    use crate::{
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+        strava_title::{generate_title, is_default_name, render_template},
+    };
+
+    #[test]
+    fn test_is_default_name() {
+        assert!(is_default_name("Morning Run"));
+        assert!(is_default_name("Lunch Ride"));
+        assert!(!is_default_name("Boston Marathon"));
+        assert!(!is_default_name("Morning Run with Friends"));
+    }
+
+    #[test]
+    fn test_generate_title() {
+        let rendered = render_template("{a}[ and {b}][ and {c}]", |name| match name {
+            "a" => Some("x".into()),
+            "b" => Some("y".into()),
+            _ => None,
+        });
+        assert_eq!(rendered.as_str(), "x and y");
+
+        let mut gfile = GarminFile::default();
+        gfile.sport = SportTypes::Running;
+        gfile.total_distance = 6.2 * METERS_PER_MILE;
+        gfile.total_duration = 2884.0;
+        gfile.total_hr_dis = 100.0;
+        gfile.total_hr_dur = 100.0 * 165.0;
+        let begin = datetime!(2024-06-04 07:15:00 -04:00);
+        let profile = HeartRateProfile::default();
+
+        let title = generate_title("", &gfile, begin, &profile);
+        assert_eq!(title.as_str(), "Tuesday Tempo Run \u{2014} 6.2mi @ 7:45/mi");
+
+        gfile.points = vec![GarminPoint {
+            temperature: Some(12.2),
+            ..GarminPoint::default()
+        }];
+        let title = generate_title(
+            "{time_of_day} {sport}[, {temperature}F]",
+            &gfile,
+            begin,
+            &profile,
+        );
+        assert_eq!(title.as_str(), "Morning Running, 54F");
+    }
+}
@@ -0,0 +1,323 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashMap, fmt, str::FromStr};
+use url::Url;
+use uuid::Uuid;
+
+use garmin_utils::pgpool::PgPool;
+
+use crate::{garmin_file::GarminFile, osm_client::OsmClient};
+
+/// Search radius in meters around each sampled point
+pub const SURFACE_SEARCH_RADIUS: f64 = 20.0;
+
+/// Surface an activity mostly took place on, inferred from the
+/// `OpenStreetMap` ways near its track points
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SurfaceType {
+    Road,
+    Trail,
+    Track,
+    Unknown,
+}
+
+impl SurfaceType {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Road => "road",
+            Self::Trail => "trail",
+            Self::Track => "track",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Classify a way from its `highway`, `leisure` and `surface` tags
+    #[must_use]
+    pub fn from_tags(tags: &HashMap<StackString, StackString>) -> Self {
+        let tag = |k: &str| tags.get(k).map(StackString::as_str);
+        if tag("leisure") == Some("track") {
+            return Self::Track;
+        }
+        let unpaved = matches!(
+            tag("surface"),
+            Some(
+                "unpaved"
+                    | "dirt"
+                    | "earth"
+                    | "ground"
+                    | "grass"
+                    | "gravel"
+                    | "fine_gravel"
+                    | "compacted"
+                    | "mud"
+                    | "sand"
+                    | "woodchips"
+            )
+        );
+        match tag("highway") {
+            Some("path" | "track" | "bridleway") => Self::Trail,
+            Some(_) if unpaved => Self::Trail,
+            Some(_) => Self::Road,
+            None => Self::Unknown,
+        }
+    }
+
+    /// Most common known surface, ties go to the first in `Road`, `Trail`,
+    /// `Track` order
+    #[must_use]
+    pub fn majority(surfaces: impl IntoIterator<Item = Self>) -> Self {
+        let mut counts: HashMap<Self, usize> = HashMap::new();
+        for surface in surfaces {
+            if surface != Self::Unknown {
+                *counts.entry(surface).or_default() += 1;
+            }
+        }
+        [Self::Road, Self::Trail, Self::Track]
+            .into_iter()
+            .filter_map(|s| counts.get(&s).map(|c| (s, *c)))
+            .fold(None, |best: Option<(Self, usize)>, (s, c)| match best {
+                Some((_, best_count)) if best_count >= c => best,
+                _ => Some((s, c)),
+            })
+            .map_or(Self::Unknown, |(s, _)| s)
+    }
+}
+
+impl fmt::Display for SurfaceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for SurfaceType {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "road" => Ok(Self::Road),
+            "trail" => Ok(Self::Trail),
+            "track" => Ok(Self::Track),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(format_err!("{s} is not a valid surface type")),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OverpassElement {
+    #[serde(default)]
+    tags: HashMap<StackString, StackString>,
+}
+
+#[derive(Deserialize)]
+struct OverpassResponse {
+    elements: Vec<OverpassElement>,
+}
+
+#[derive(FromSqlRow)]
+struct MissingSurfaceRow {
+    id: Uuid,
+    filename: StackString,
+}
+
+#[derive(FromSqlRow)]
+struct SurfaceRow {
+    surface: StackString,
+}
+
+/// Up to `count` (latitude, longitude) points spread evenly along the track
+#[must_use]
+pub fn sample_points(gfile: &GarminFile, count: usize) -> Vec<(f64, f64)> {
+    let points: Vec<_> = gfile
+        .points
+        .iter()
+        .filter_map(|p| match (p.latitude, p.longitude) {
+            (Some(lat), Some(lon)) => Some((lat, lon)),
+            _ => None,
+        })
+        .collect();
+    if points.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let step = (points.len() as f64 / count as f64).max(1.0);
+    (0..count.min(points.len()))
+        .map(|i| points[((i as f64 + 0.5) * step) as usize % points.len()])
+        .collect()
+}
+
+/// Surface of the ways within `SURFACE_SEARCH_RADIUS` of `(lat, lon)`
+/// # Errors
+/// Return error if the overpass request fails
+pub async fn lookup_point_surface(
+    client: &OsmClient,
+    endpoint: &Url,
+    lat: f64,
+    lon: f64,
+) -> Result<SurfaceType, Error> {
+    let query = format_sstr!(
+        "[out:json][timeout:25];(way(around:{SURFACE_SEARCH_RADIUS},{lat},{lon})[highway];\
+         way(around:{SURFACE_SEARCH_RADIUS},{lat},{lon})[leisure=track];);out tags;"
+    );
+    let response: OverpassResponse = client
+        .get()
+        .await
+        .post(endpoint.as_str())
+        .form(&[("data", query.as_str())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(SurfaceType::majority(
+        response
+            .elements
+            .iter()
+            .map(|e| SurfaceType::from_tags(&e.tags)),
+    ))
+}
+
+/// Classify an activity from `sample_count` of its track points, activities
+/// without gps are `Unknown`
+/// # Errors
+/// Return error if an overpass request fails
+pub async fn infer_surface(
+    client: &OsmClient,
+    endpoint: &Url,
+    gfile: &GarminFile,
+    sample_count: usize,
+) -> Result<SurfaceType, Error> {
+    let mut surfaces = Vec::new();
+    for (lat, lon) in sample_points(gfile, sample_count) {
+        surfaces.push(lookup_point_surface(client, endpoint, lat, lon).await?);
+    }
+    Ok(SurfaceType::majority(surfaces))
+}
+
+/// Surface classification of an activity, stored in `activity_surfaces`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActivitySurface {
+    pub summary_id: Uuid,
+    pub surface: SurfaceType,
+}
+
+impl ActivitySurface {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_surfaces (summary_id, surface)
+                VALUES ($summary_id, $surface)
+                ON CONFLICT (summary_id) DO UPDATE SET surface=EXCLUDED.surface
+            ",
+            summary_id = self.summary_id,
+            surface = self.surface.to_str(),
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_summary_id(
+        pool: &PgPool,
+        summary_id: Uuid,
+    ) -> Result<Option<SurfaceType>, Error> {
+        let query = query!(
+            "SELECT surface FROM activity_surfaces WHERE summary_id = $summary_id",
+            summary_id = summary_id,
+        );
+        let conn = pool.get().await?;
+        let row: Option<SurfaceRow> = query.fetch_opt(&conn).await?;
+        row.map(|r| r.surface.parse()).transpose()
+    }
+
+    /// (id, filename) of outdoor activities which haven't been classified
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_summaries(pool: &PgPool) -> Result<Vec<(Uuid, StackString)>, Error> {
+        let query = query!(
+            "
+                SELECT a.id, a.filename
+                FROM garmin_summary a
+                WHERE a.sport IN ('running', 'walking', 'hiking', 'biking')
+                  AND NOT EXISTS (
+                    SELECT 1 FROM activity_surfaces b WHERE b.summary_id = a.id
+                  )
+                ORDER BY a.begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingSurfaceRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stack_string::StackString;
+    use std::collections::HashMap;
+
+    use crate::{
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+        surface_type::{sample_points, SurfaceType},
+    };
+
+    fn tags(items: &[(&str, &str)]) -> HashMap<StackString, StackString> {
+        items
+            .iter()
+            .map(|(k, v)| ((*k).into(), (*v).into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_surface_type_from_tags() {
+        let surface = |items| SurfaceType::from_tags(&tags(items));
+        assert_eq!(surface(&[("highway", "residential")]), SurfaceType::Road);
+        assert_eq!(
+            surface(&[("highway", "footway"), ("surface", "gravel")]),
+            SurfaceType::Trail
+        );
+        assert_eq!(surface(&[("highway", "path")]), SurfaceType::Trail);
+        assert_eq!(surface(&[("leisure", "track")]), SurfaceType::Track);
+        assert_eq!(surface(&[]), SurfaceType::Unknown);
+
+        let surfaces = [
+            SurfaceType::Trail,
+            SurfaceType::Unknown,
+            SurfaceType::Road,
+            SurfaceType::Trail,
+        ];
+        assert_eq!(SurfaceType::majority(surfaces), SurfaceType::Trail);
+        assert_eq!(
+            SurfaceType::majority([SurfaceType::Trail, SurfaceType::Road]),
+            SurfaceType::Road
+        );
+        assert_eq!(SurfaceType::majority([]), SurfaceType::Unknown);
+        assert_eq!("Trail".parse::<SurfaceType>().unwrap(), SurfaceType::Trail);
+    }
+
+    #[test]
+    fn test_sample_points() {
+        let mut gfile = GarminFile::default();
+        assert!(sample_points(&gfile, 10).is_empty());
+        gfile.points = (0..100)
+            .map(|i| GarminPoint {
+                latitude: Some(f64::from(i)),
+                longitude: Some(-f64::from(i)),
+                ..GarminPoint::default()
+            })
+            .collect();
+        let samples = sample_points(&gfile, 4);
+        assert_eq!(
+            samples,
+            vec![(12.0, -12.0), (37.0, -37.0), (62.0, -62.0), (87.0, -87.0)]
+        );
+        assert_eq!(sample_points(&gfile, 200).len(), 100);
+    }
+}
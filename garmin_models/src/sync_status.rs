@@ -0,0 +1,179 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use time::{Duration, OffsetDateTime};
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::pgpool::PgPool;
+
+/// Job recorded whenever new heart rate data has been imported
+pub const HEARTRATE_JOB: &str = "heartrate";
+/// Job recorded whenever an activity sync completes
+pub const ACTIVITY_JOB: &str = "activity";
+/// Job recorded whenever a strava sync completes
+pub const STRAVA_JOB: &str = "strava";
+
+/// Last success and failure of a scheduled sync job, updated by the cli and
+/// by the scheduler through `/garmin/sync_status`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SyncStatus {
+    pub job: StackString,
+    pub last_success: Option<DateTimeWrapper>,
+    pub last_failure: Option<DateTimeWrapper>,
+    pub message: Option<StackString>,
+    pub last_alert: Option<DateTimeWrapper>,
+    /// Failures since the last success
+    pub failure_count: i32,
+}
+
+impl SyncStatus {
+    #[must_use]
+    pub fn new(job: &str) -> Self {
+        Self {
+            job: job.into(),
+            last_success: None,
+            last_failure: None,
+            message: None,
+            last_alert: None,
+            failure_count: 0,
+        }
+    }
+
+    /// No success within `max_age` of `now`
+    #[must_use]
+    pub fn is_stale(&self, now: OffsetDateTime, max_age: Duration) -> bool {
+        self.last_success
+            .is_none_or(|t| now - t.to_offsetdatetime() > max_age)
+    }
+
+    /// Stale, and not already alerted about within the last `max_age`, so a
+    /// broken job is reported once per interval rather than on every check
+    #[must_use]
+    pub fn needs_alert(&self, now: OffsetDateTime, max_age: Duration) -> bool {
+        self.is_stale(now, max_age)
+            && self
+                .last_alert
+                .is_none_or(|t| now - t.to_offsetdatetime() > max_age)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT job, last_success, last_failure, message, last_alert, failure_count
+                FROM sync_status
+                ORDER BY job
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// Status of `HEARTRATE_JOB` and `ACTIVITY_JOB` along with any other
+    /// job reported by the scheduler, jobs which never ran have no success
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let mut statuses = Self::read_from_db(pool).await?;
+        for job in [HEARTRATE_JOB, ACTIVITY_JOB] {
+            if !statuses.iter().any(|s| s.job == job) {
+                statuses.push(Self::new(job));
+            }
+        }
+        statuses.sort_by(|a, b| a.job.cmp(&b.job));
+        Ok(statuses)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn record_success(pool: &PgPool, job: &str) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO sync_status (job, last_success)
+                VALUES ($job, now())
+                ON CONFLICT (job) DO UPDATE SET last_success = now(), failure_count = 0
+            ",
+            job = job,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn record_failure(pool: &PgPool, job: &str, message: &str) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO sync_status (job, last_failure, message, failure_count)
+                VALUES ($job, now(), $message, 1)
+                ON CONFLICT (job) DO UPDATE
+                SET last_failure = now(),
+                    message = EXCLUDED.message,
+                    failure_count = sync_status.failure_count + 1
+            ",
+            job = job,
+            message = message,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn record_alert(pool: &PgPool, job: &str) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO sync_status (job, last_alert)
+                VALUES ($job, now())
+                ON CONFLICT (job) DO UPDATE SET last_alert = now()
+            ",
+            job = job,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Jobs without a success in the last `hours` which haven't been alerted
+    /// about yet
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing(pool: &PgPool, hours: u32) -> Result<Vec<Self>, Error> {
+        let now = OffsetDateTime::now_utc();
+        let max_age = Duration::hours(hours.into());
+        let mut statuses = Self::get_all(pool).await?;
+        statuses.retain(|s| s.needs_alert(now, max_age));
+        Ok(statuses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{macros::datetime, Duration};
+
+    use crate::sync_status::{SyncStatus, HEARTRATE_JOB};
+
+    #[test]
+    fn test_sync_status_needs_alert() {
+        let now = datetime!(2024-03-10 12:00:00 UTC);
+        let max_age = Duration::hours(36);
+        let mut status = SyncStatus::new(HEARTRATE_JOB);
+        assert!(status.needs_alert(now, max_age));
+
+        status.last_success = Some(datetime!(2024-03-09 12:00:00 UTC).into());
+        assert!(!status.is_stale(now, max_age));
+        assert!(!status.needs_alert(now, max_age));
+
+        status.last_success = Some(datetime!(2024-03-08 12:00:00 UTC).into());
+        assert!(status.needs_alert(now, max_age));
+
+        // already alerted, wait another interval before alerting again
+        status.last_alert = Some(datetime!(2024-03-10 06:00:00 UTC).into());
+        assert!(!status.needs_alert(now, max_age));
+        assert!(status.needs_alert(now + Duration::days(2), max_age));
+    }
+}
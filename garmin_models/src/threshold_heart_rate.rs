@@ -0,0 +1,441 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::fmt::Write;
+use time::{Date, Duration, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+use uuid::Uuid;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, heart_rate_profile::HeartRateProfile};
+use garmin_utils::pgpool::PgPool;
+
+use crate::garmin_file::GarminFile;
+
+/// Lactate threshold is estimated as this fraction of the best average heart
+/// rate of a hard effort
+pub const THRESHOLD_FRACTION: f64 = 0.95;
+/// Length of a hard sustained effort
+pub const THRESHOLD_EFFORT_SECONDS: f64 = 3600.0;
+/// Races shorter than `THRESHOLD_EFFORT_SECONDS` count with their best 20
+/// minutes
+pub const RACE_EFFORT_SECONDS: f64 = 1200.0;
+/// Fraction of heart rate reserve an effort has to average to count as hard,
+/// races always count
+pub const HARD_EFFORT_RESERVE: f64 = 0.8;
+/// The threshold in effect on a day is the highest estimate of the efforts
+/// over this many days before
+pub const THRESHOLD_LOOKBACK_DAYS: i64 = 90;
+/// History shown on the rest days page next to the training load
+pub const THRESHOLD_HISTORY_DAYS: i64 = 730;
+/// Size of the threshold history chart
+pub const THRESHOLD_WIDTH: f64 = 800.0;
+pub const THRESHOLD_HEIGHT: f64 = 250.0;
+const THRESHOLD_MARGIN: f64 = 30.0;
+
+/// Highest average heart rate over `seconds`, each sample holding until the
+/// next one, `None` for activities without that much heart rate data
+#[must_use]
+pub fn best_average_heart_rate(gfile: &GarminFile, seconds: f64) -> Option<f64> {
+    let samples: Vec<_> = gfile
+        .points
+        .iter()
+        .filter_map(|p| p.heart_rate.map(|hr| (p.duration_from_begin, hr)))
+        .filter(|(_, hr)| *hr > 0.0)
+        .collect();
+    // heart beats up to each sample
+    let mut beats = vec![0.0];
+    let mut total = 0.0;
+    for w in samples.windows(2) {
+        total += w[0].1 * (w[1].0 - w[0].0);
+        beats.push(total);
+    }
+    let mut best: Option<f64> = None;
+    let mut end = 0;
+    for (start, (begin, _)) in samples.iter().enumerate() {
+        end = end.max(start);
+        while end < samples.len() && samples[end].0 - begin < seconds {
+            end += 1;
+        }
+        if end == samples.len() {
+            break;
+        }
+        let average = (beats[end] - beats[start]) / (samples[end].0 - begin);
+        if best.is_none_or(|b| average > b) {
+            best = Some(average);
+        }
+    }
+    best
+}
+
+#[derive(FromSqlRow)]
+struct MissingEffortRow {
+    id: Uuid,
+    filename: StackString,
+}
+
+/// Best 20 and 60 minute average heart rate of an activity, stored in
+/// `activity_threshold_efforts`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ActivityThresholdEffort {
+    pub summary_id: Uuid,
+    pub race_heart_rate: Option<f64>,
+    pub effort_heart_rate: Option<f64>,
+}
+
+impl ActivityThresholdEffort {
+    #[must_use]
+    pub fn from_gfile(summary_id: Uuid, gfile: &GarminFile) -> Self {
+        Self {
+            summary_id,
+            race_heart_rate: best_average_heart_rate(gfile, RACE_EFFORT_SECONDS),
+            effort_heart_rate: best_average_heart_rate(gfile, THRESHOLD_EFFORT_SECONDS),
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO activity_threshold_efforts
+                    (summary_id, race_heart_rate, effort_heart_rate)
+                VALUES ($summary_id, $race_heart_rate, $effort_heart_rate)
+                ON CONFLICT (summary_id) DO UPDATE
+                SET race_heart_rate=EXCLUDED.race_heart_rate,
+                    effort_heart_rate=EXCLUDED.effort_heart_rate
+            ",
+            summary_id = self.summary_id,
+            race_heart_rate = self.race_heart_rate,
+            effort_heart_rate = self.effort_heart_rate,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// (id, filename) of activities with heart rate which haven't been
+    /// checked
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_missing_summaries(pool: &PgPool) -> Result<Vec<(Uuid, StackString)>, Error> {
+        let query = query!(
+            "
+                SELECT a.id, a.filename
+                FROM garmin_summary a
+                WHERE a.total_hr_dis > 0
+                  AND NOT EXISTS (
+                    SELECT 1 FROM activity_threshold_efforts b WHERE b.summary_id = a.id
+                  )
+                ORDER BY a.begin_datetime
+            "
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<MissingEffortRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.filename)).collect())
+    }
+}
+
+#[derive(FromSqlRow)]
+struct EffortRow {
+    filename: StackString,
+    begin_datetime: DateTimeWrapper,
+    race: bool,
+    race_heart_rate: Option<f64>,
+    effort_heart_rate: Option<f64>,
+}
+
+/// Lactate threshold heart rate estimated from one race or hard effort
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ThresholdEstimate {
+    pub filename: StackString,
+    pub date: Date,
+    pub race: bool,
+    /// Best average heart rate over `effort_seconds`
+    pub heart_rate: f64,
+    pub effort_seconds: f64,
+    pub threshold_heart_rate: f64,
+}
+
+impl ThresholdEstimate {
+    /// An hour long effort counts when it's hard enough or a race, a race
+    /// shorter than an hour counts with its best 20 minutes
+    #[must_use]
+    pub fn from_effort(
+        filename: &str,
+        date: Date,
+        race: bool,
+        race_heart_rate: Option<f64>,
+        effort_heart_rate: Option<f64>,
+        profile: &HeartRateProfile,
+    ) -> Option<Self> {
+        let (heart_rate, effort_seconds) = match (effort_heart_rate, race_heart_rate) {
+            (Some(hr), _) if race || profile.reserve_fraction(hr) >= HARD_EFFORT_RESERVE => {
+                (hr, THRESHOLD_EFFORT_SECONDS)
+            }
+            (_, Some(hr)) if race => (hr, RACE_EFFORT_SECONDS),
+            _ => return None,
+        };
+        Some(Self {
+            filename: filename.into(),
+            date,
+            race,
+            heart_rate,
+            effort_seconds,
+            threshold_heart_rate: (THRESHOLD_FRACTION * heart_rate).round(),
+        })
+    }
+}
+
+/// Highest estimate of the `THRESHOLD_LOOKBACK_DAYS` up to `date`
+#[must_use]
+pub fn threshold_on(estimates: &[ThresholdEstimate], date: Date) -> Option<f64> {
+    let start = date - Duration::days(THRESHOLD_LOOKBACK_DAYS);
+    estimates
+        .iter()
+        .filter(|e| e.date > start && e.date <= date)
+        .map(|e| e.threshold_heart_rate)
+        .fold(None, |best, t| Some(best.map_or(t, |b: f64| b.max(t))))
+}
+
+/// Threshold estimates over time, `thresholds` holds the threshold in effect
+/// on the date of each estimate
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ThresholdHistory {
+    pub estimates: Vec<ThresholdEstimate>,
+    pub thresholds: Vec<(Date, f64)>,
+}
+
+impl ThresholdHistory {
+    /// `estimates` must be ordered by date
+    #[must_use]
+    pub fn from_estimates(estimates: Vec<ThresholdEstimate>) -> Self {
+        let mut thresholds: Vec<(Date, f64)> = Vec::new();
+        for estimate in &estimates {
+            if let Some(threshold) = threshold_on(&estimates, estimate.date) {
+                match thresholds.last_mut() {
+                    Some((date, t)) if *date == estimate.date => *t = threshold,
+                    _ => thresholds.push((estimate.date, threshold)),
+                }
+            }
+        }
+        Self {
+            estimates,
+            thresholds,
+        }
+    }
+
+    /// Line chart of the threshold in effect, each estimate drawn as a point,
+    /// races in red
+    /// # Errors
+    /// Return error if formatting fails
+    pub fn history_svg(&self) -> Result<Option<StackString>, Error> {
+        let (first, last) = match (self.estimates.first(), self.estimates.last()) {
+            (Some(first), Some(last)) => (first.date, last.date),
+            _ => return Ok(None),
+        };
+        let values = self.estimates.iter().map(|e| e.threshold_heart_rate);
+        let min = values.clone().fold(f64::MAX, f64::min) - 5.0;
+        let max = values.fold(f64::MIN, f64::max) + 5.0;
+        let span = (last - first).whole_days().max(1) as f64;
+        let x = |date: Date| {
+            THRESHOLD_MARGIN
+                + (THRESHOLD_WIDTH - 2.0 * THRESHOLD_MARGIN) * (date - first).whole_days() as f64
+                    / span
+        };
+        let y = |heart_rate: f64| {
+            THRESHOLD_MARGIN
+                + (THRESHOLD_HEIGHT - 2.0 * THRESHOLD_MARGIN) * (max - heart_rate) / (max - min)
+        };
+
+        let mut path = StackString::new();
+        for (idx, (date, threshold)) in self.thresholds.iter().enumerate() {
+            let cmd = if idx == 0 { 'M' } else { 'L' };
+            write!(path, "{cmd}{:.1},{:.1} ", x(*date), y(*threshold))?;
+        }
+        let mut points = StackString::new();
+        for estimate in &self.estimates {
+            let color = if estimate.race { "red" } else { "steelblue" };
+            write!(
+                points,
+                r#"<circle cx="{:.1}" cy="{:.1}" r="4" fill="{color}"><title>{} {} {:.0}</title></circle>"#,
+                x(estimate.date),
+                y(estimate.threshold_heart_rate),
+                estimate.date,
+                estimate.filename,
+                estimate.threshold_heart_rate,
+            )?;
+        }
+        Ok(Some(format_sstr!(
+            r#"<svg width="{w}" height="{h}" viewBox="0 0 {w} {h}"><text x="{m}" y="15" font-size="11">{max:.0} bpm</text><text x="{m}" y="{b}" font-size="11">{min:.0} bpm</text><path d="{path}" fill="none" stroke="steelblue" stroke-width="2"/>{points}</svg>"#,
+            w = THRESHOLD_WIDTH,
+            h = THRESHOLD_HEIGHT,
+            m = THRESHOLD_MARGIN,
+            b = THRESHOLD_HEIGHT - 5.0,
+            path = path.trim_end(),
+        )))
+    }
+
+    /// Estimates from the efforts of the last `days` days, hard efforts are
+    /// judged against `profile`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(
+        pool: &PgPool,
+        profile: &HeartRateProfile,
+        days: i64,
+    ) -> Result<Self, Error> {
+        let local = DateTimeWrapper::local_tz();
+        let start = OffsetDateTime::now_utc() - Duration::days(days);
+        let query = query!(
+            "
+                SELECT a.filename,
+                       a.begin_datetime,
+                       EXISTS (
+                         SELECT 1 FROM race_results_garmin_summary r WHERE r.summary_id = a.id
+                       ) AS race,
+                       b.race_heart_rate,
+                       b.effort_heart_rate
+                FROM garmin_summary a
+                JOIN activity_threshold_efforts b ON b.summary_id = a.id
+                WHERE a.begin_datetime >= $start
+                  AND (b.race_heart_rate IS NOT NULL OR b.effort_heart_rate IS NOT NULL)
+                ORDER BY a.begin_datetime
+            ",
+            start = start,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<EffortRow> = query.fetch(&conn).await?;
+        let estimates = rows
+            .into_iter()
+            .filter_map(|row| {
+                ThresholdEstimate::from_effort(
+                    &row.filename,
+                    row.begin_datetime.to_timezone(local).date(),
+                    row.race,
+                    row.race_heart_rate,
+                    row.effort_heart_rate,
+                    profile,
+                )
+            })
+            .collect();
+        Ok(Self::from_estimates(estimates))
+    }
+}
+
+/// Threshold in effect today, `None` without a race or hard effort over the
+/// last `THRESHOLD_LOOKBACK_DAYS`
+/// # Errors
+/// Return error if db query fails
+pub async fn current_threshold_heart_rate(
+    pool: &PgPool,
+    profile: &HeartRateProfile,
+) -> Result<Option<f64>, Error> {
+    let history = ThresholdHistory::read_from_db(pool, profile, THRESHOLD_LOOKBACK_DAYS).await?;
+    let today = OffsetDateTime::now_utc()
+        .to_timezone(DateTimeWrapper::local_tz())
+        .date();
+    Ok(threshold_on(&history.estimates, today))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::macros::date;
+
+    use garmin_lib::heart_rate_profile::HeartRateProfile;
+
+    use crate::{
+        garmin_file::GarminFile,
+        garmin_point::GarminPoint,
+        threshold_heart_rate::{
+            best_average_heart_rate, threshold_on, ThresholdEstimate, ThresholdHistory,
+            RACE_EFFORT_SECONDS, THRESHOLD_EFFORT_SECONDS,
+        },
+    };
+
+    #[test]
+    fn test_best_average_heart_rate() {
+        let mut gfile = GarminFile::default();
+        // 10 minutes at 150 then 20 at 170 and 10 at 140
+        gfile.points = (0..=40)
+            .map(|minute| GarminPoint {
+                duration_from_begin: 60.0 * f64::from(minute),
+                heart_rate: Some(match minute {
+                    0..=9 => 150.0,
+                    10..=29 => 170.0,
+                    _ => 140.0,
+                }),
+                ..GarminPoint::default()
+            })
+            .collect();
+        assert_abs_diff_eq!(
+            best_average_heart_rate(&gfile, RACE_EFFORT_SECONDS).unwrap(),
+            170.0
+        );
+        assert_abs_diff_eq!(best_average_heart_rate(&gfile, 2400.0).unwrap(), 157.5);
+        assert!(best_average_heart_rate(&gfile, THRESHOLD_EFFORT_SECONDS).is_none());
+    }
+
+    #[test]
+    fn test_threshold_history() {
+        let profile = HeartRateProfile {
+            max_heart_rate: 190.0,
+            resting_heart_rate: 50.0,
+            threshold_heart_rate: None,
+        };
+        // easy hour long run doesn't count, a short race does
+        assert!(ThresholdEstimate::from_effort(
+            "easy",
+            date!(2024 - 01 - 01),
+            false,
+            Some(150.0),
+            Some(140.0),
+            &profile
+        )
+        .is_none());
+        let race = ThresholdEstimate::from_effort(
+            "race",
+            date!(2024 - 01 - 10),
+            true,
+            Some(180.0),
+            None,
+            &profile,
+        )
+        .unwrap();
+        assert_abs_diff_eq!(race.threshold_heart_rate, 171.0);
+        assert_abs_diff_eq!(race.effort_seconds, RACE_EFFORT_SECONDS);
+        let tempo = ThresholdEstimate::from_effort(
+            "tempo",
+            date!(2024 - 03 - 01),
+            false,
+            Some(172.0),
+            Some(168.0),
+            &profile,
+        )
+        .unwrap();
+        assert_abs_diff_eq!(tempo.threshold_heart_rate, 160.0);
+        let later = ThresholdEstimate {
+            date: date!(2024 - 05 - 01),
+            ..tempo.clone()
+        };
+
+        let estimates = vec![race, tempo, later];
+        assert_eq!(threshold_on(&estimates, date!(2023 - 12 - 31)), None);
+        assert_eq!(threshold_on(&estimates, date!(2024 - 03 - 01)), Some(171.0));
+        assert_eq!(threshold_on(&estimates, date!(2024 - 05 - 01)), Some(160.0));
+
+        let history = ThresholdHistory::from_estimates(estimates);
+        assert_eq!(
+            history.thresholds,
+            vec![
+                (date!(2024 - 01 - 10), 171.0),
+                (date!(2024 - 03 - 01), 171.0),
+                (date!(2024 - 05 - 01), 160.0),
+            ]
+        );
+        let svg = history.history_svg().unwrap().unwrap();
+        assert_eq!(svg.matches(r#"fill="red""#).count(), 1);
+        assert!(ThresholdHistory::default().history_svg().unwrap().is_none());
+    }
+}
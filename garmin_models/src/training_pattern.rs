@@ -0,0 +1,184 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime, Weekday};
+use time_tz::OffsetDateTimeExt;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, week_start::WeekStart};
+use garmin_utils::{garmin_util::METERS_PER_MILE, pgpool::PgPool, sport_types::SportTypes};
+
+/// Activities of the last year are analyzed unless asked otherwise
+pub const DEFAULT_PATTERN_DAYS: i64 = 365;
+/// Longest period analyzed, 50 years as for the yearly comparison
+pub const MAX_PATTERN_DAYS: i64 = 50 * 366;
+
+#[derive(FromSqlRow)]
+struct PatternRow {
+    begin_datetime: DateTimeWrapper,
+    total_distance: f64,
+    total_duration: f64,
+}
+
+/// Activities started in one hour of one day of the week
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct TrainingPatternCell {
+    pub count: usize,
+    /// meters
+    pub total_distance: f64,
+    /// seconds
+    pub total_duration: f64,
+}
+
+impl TrainingPatternCell {
+    /// Average pace in seconds per mile, `None` without any distance
+    #[must_use]
+    pub fn pace(&self) -> Option<f64> {
+        if self.total_distance > 0.0 {
+            Some(self.total_duration / (self.total_distance / METERS_PER_MILE))
+        } else {
+            None
+        }
+    }
+}
+
+/// Day of the week by hour of the day matrix of when activities start, in
+/// local time, rows start from the configured first day of the week
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrainingPattern {
+    /// `None` counts every sport
+    pub sport: Option<SportTypes>,
+    pub week_start: WeekStart,
+    /// 7 rows of 24 hours
+    pub cells: Vec<Vec<TrainingPatternCell>>,
+}
+
+impl TrainingPattern {
+    /// Weekday of row `row`
+    #[must_use]
+    pub fn weekday(&self, row: usize) -> Weekday {
+        let first = match self.week_start {
+            WeekStart::Monday => Weekday::Monday,
+            WeekStart::Sunday => Weekday::Sunday,
+        };
+        first.nth_next(row as u8)
+    }
+
+    /// Largest number of activities in any one cell
+    #[must_use]
+    pub fn max_count(&self) -> usize {
+        self.cells
+            .iter()
+            .flatten()
+            .map(|c| c.count)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Matrix from the local start time, meters and seconds of each activity
+    #[must_use]
+    pub fn from_activities(
+        activities: &[(OffsetDateTime, f64, f64)],
+        sport: Option<SportTypes>,
+        week_start: WeekStart,
+    ) -> Self {
+        let mut cells = vec![vec![TrainingPatternCell::default(); 24]; 7];
+        for (start, distance, duration) in activities {
+            let row = match week_start {
+                WeekStart::Monday => start.weekday().number_days_from_monday(),
+                WeekStart::Sunday => start.weekday().number_days_from_sunday(),
+            };
+            let cell = &mut cells[row as usize][start.hour() as usize];
+            cell.count += 1;
+            cell.total_distance += distance;
+            cell.total_duration += duration;
+        }
+        Self {
+            sport,
+            week_start,
+            cells,
+        }
+    }
+
+    /// Pattern of the activities of the last `days` days
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(
+        pool: &PgPool,
+        sport: Option<SportTypes>,
+        week_start: WeekStart,
+        days: i64,
+    ) -> Result<Self, Error> {
+        let query = query!(
+            "
+                SELECT begin_datetime, total_distance, total_duration
+                FROM garmin_summary
+                WHERE begin_datetime >= $start
+                  AND ($sport::text IS NULL OR sport = $sport)
+            ",
+            start = OffsetDateTime::now_utc() - Duration::days(days),
+            sport = sport.map(SportTypes::to_str),
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<PatternRow> = query.fetch(&conn).await?;
+        let local = DateTimeWrapper::local_tz();
+        let activities: Vec<_> = rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.begin_datetime.to_timezone(local),
+                    row.total_distance,
+                    row.total_duration,
+                )
+            })
+            .collect();
+        Ok(Self::from_activities(&activities, sport, week_start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::{macros::datetime, Weekday};
+
+    use garmin_lib::week_start::WeekStart;
+    use garmin_utils::{garmin_util::METERS_PER_MILE, sport_types::SportTypes};
+
+    use crate::training_pattern::TrainingPattern;
+
+    #[test]
+    fn test_training_pattern() {
+        // 2024-05-04 is a saturday
+        let activities = [
+            (
+                datetime!(2024-05-04 07:15:00 -4),
+                5.0 * METERS_PER_MILE,
+                2400.0,
+            ),
+            (
+                datetime!(2024-05-11 07:45:00 -4),
+                3.0 * METERS_PER_MILE,
+                1500.0,
+            ),
+            (datetime!(2024-05-06 18:00:00 -4), 0.0, 1800.0),
+        ];
+        let pattern = TrainingPattern::from_activities(
+            &activities,
+            Some(SportTypes::Running),
+            WeekStart::Monday,
+        );
+        assert_eq!(pattern.cells.len(), 7);
+        assert_eq!(pattern.weekday(5), Weekday::Saturday);
+        let saturday = pattern.cells[5][7];
+        assert_eq!(saturday.count, 2);
+        assert_abs_diff_eq!(saturday.pace().unwrap(), 487.5);
+        let monday = pattern.cells[0][18];
+        assert_eq!(monday.count, 1);
+        assert!(monday.pace().is_none());
+        assert_eq!(pattern.max_count(), 2);
+
+        let pattern = TrainingPattern::from_activities(&activities, None, WeekStart::Sunday);
+        assert_eq!(pattern.weekday(0), Weekday::Sunday);
+        assert_eq!(pattern.cells[6][7].count, 2);
+        assert_eq!(pattern.cells[1][18].count, 1);
+    }
+}
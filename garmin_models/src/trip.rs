@@ -0,0 +1,304 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+use time::{Date, Duration, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+use uuid::Uuid;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
+
+use crate::garmin_summary::GarminSummary;
+
+#[derive(FromSqlRow)]
+struct PhotoUrlsRow {
+    photo_urls: Vec<StackString>,
+}
+
+/// Named date range, e.g. a vacation, every activity started (local time)
+/// between `start_date` and `end_date` inclusive belongs to the trip
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Trip {
+    pub id: Uuid,
+    pub name: StackString,
+    pub start_date: Date,
+    pub end_date: Date,
+    pub created_at: DateTimeWrapper,
+}
+
+impl Trip {
+    /// # Errors
+    /// Return error if the name is empty or the trip ends before it starts
+    pub fn new(name: &str, start_date: Date, end_date: Date) -> Result<Self, Error> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format_err!("Trip name is empty"));
+        }
+        if end_date < start_date {
+            return Err(format_err!(
+                "Trip {name} ends {end_date} before it starts {start_date}"
+            ));
+        }
+        Ok(Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            start_date,
+            end_date,
+            created_at: OffsetDateTime::now_utc().into(),
+        })
+    }
+
+    #[must_use]
+    pub fn contains(&self, date: Date) -> bool {
+        date >= self.start_date && date <= self.end_date
+    }
+
+    #[must_use]
+    pub fn days(&self) -> i64 {
+        (self.end_date - self.start_date).whole_days() + 1
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, name, start_date, end_date, created_at
+                FROM trips
+                ORDER BY start_date DESC
+            "
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, name, start_date, end_date, created_at
+                FROM trips
+                WHERE id = $id
+            ",
+            id = id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Insert the trip, returns false (and inserts nothing) when a trip with
+    /// the same name already exists
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn insert_into_db(&self, pool: &PgPool) -> Result<bool, Error> {
+        let query = query!(
+            "
+                INSERT INTO trips (id, name, start_date, end_date, created_at)
+                VALUES ($id, $name, $start_date, $end_date, $created_at)
+                ON CONFLICT (name) DO NOTHING
+            ",
+            id = self.id,
+            name = self.name,
+            start_date = self.start_date,
+            end_date = self.end_date,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        let inserted = query.execute(&conn).await?;
+        Ok(inserted > 0)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete_from_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!("DELETE FROM trips WHERE id = $id", id = self.id);
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// Activities of the trip, ordered by start time
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_summaries(&self, pool: &PgPool) -> Result<Vec<GarminSummary>, Error> {
+        // pad the range by a day on each side, activities are matched on
+        // their local date below
+        let start = self.start_date.midnight().assume_utc() - Duration::days(1);
+        let end = self.end_date.midnight().assume_utc() + Duration::days(2);
+        let query = query!(
+            "
+            SELECT id,
+                   filename,
+                   begin_datetime,
+                   sport,
+                   total_calories,
+                   total_distance,
+                   total_duration,
+                   total_hr_dur,
+                   total_hr_dis,
+                   md5sum,
+                   course_difficulty,
+                   split_differential,
+                   avg_power,
+                   normalized_power,
+                   avg_temperature,
+                   calories_estimated
+            FROM garmin_summary
+            WHERE begin_datetime >= $start AND begin_datetime < $end
+            ORDER BY begin_datetime",
+            start = start,
+            end = end,
+        );
+        let conn = pool.get().await?;
+        let summaries: Vec<GarminSummary> = query.fetch(&conn).await?;
+        let local = DateTimeWrapper::local_tz();
+        Ok(summaries
+            .into_iter()
+            .filter(|s| self.contains(s.begin_datetime.to_timezone(local).date()))
+            .collect())
+    }
+
+    /// Urls of the strava photos of `summaries`
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_photo_urls(
+        pool: &PgPool,
+        summaries: &[GarminSummary],
+    ) -> Result<Vec<StackString>, Error> {
+        let summary_ids: Vec<Uuid> = summaries.iter().map(|s| s.id).collect();
+        let query = query!(
+            "
+                SELECT photo_urls
+                FROM strava_activities
+                WHERE summary_id = ANY($summary_ids)
+                ORDER BY start_date
+            ",
+            summary_ids = summary_ids,
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<PhotoUrlsRow> = query.fetch(&conn).await?;
+        Ok(rows.into_iter().flat_map(|r| r.photo_urls).collect())
+    }
+}
+
+/// Totals of one sport over a trip
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TripSportStats {
+    pub sport: SportTypes,
+    pub activities: usize,
+    /// meters
+    pub total_distance: f64,
+    /// seconds
+    pub total_duration: f64,
+    pub total_calories: i64,
+}
+
+/// Totals over every activity of a trip, and per sport
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TripStats {
+    pub activities: usize,
+    pub total_distance: f64,
+    pub total_duration: f64,
+    pub total_calories: i64,
+    pub sports: Vec<TripSportStats>,
+}
+
+impl TripStats {
+    #[must_use]
+    pub fn from_summaries(summaries: &[GarminSummary]) -> Self {
+        let mut stats = Self::default();
+        for summary in summaries {
+            stats.activities += 1;
+            stats.total_distance += summary.total_distance;
+            stats.total_duration += summary.total_duration;
+            stats.total_calories += i64::from(summary.total_calories);
+            let idx = match stats.sports.iter().position(|s| s.sport == summary.sport) {
+                Some(idx) => idx,
+                None => {
+                    stats.sports.push(TripSportStats {
+                        sport: summary.sport,
+                        activities: 0,
+                        total_distance: 0.0,
+                        total_duration: 0.0,
+                        total_calories: 0,
+                    });
+                    stats.sports.len() - 1
+                }
+            };
+            let sport = &mut stats.sports[idx];
+            sport.activities += 1;
+            sport.total_distance += summary.total_distance;
+            sport.total_duration += summary.total_duration;
+            sport.total_calories += i64::from(summary.total_calories);
+        }
+        stats
+            .sports
+            .sort_by(|a, b| b.total_duration.total_cmp(&a.total_duration));
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::macros::{date, datetime};
+    use uuid::Uuid;
+
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::{
+        garmin_summary::GarminSummary,
+        trip::{Trip, TripStats},
+    };
+
+    fn summary(sport: SportTypes, total_distance: f64, total_duration: f64) -> GarminSummary {
+        GarminSummary {
+            id: Uuid::new_v4(),
+            filename: "".into(),
+            begin_datetime: datetime!(2024-07-01 08:00:00 UTC).into(),
+            sport,
+            total_calories: 100,
+            total_distance,
+            total_duration,
+            total_hr_dur: 0.0,
+            total_hr_dis: 0.0,
+            md5sum: "".into(),
+            course_difficulty: None,
+            split_differential: None,
+            avg_power: None,
+            normalized_power: None,
+            avg_temperature: None,
+            calories_estimated: false,
+        }
+    }
+
+    #[test]
+    fn test_trip_new() {
+        let trip = Trip::new(" Alps 2024 ", date!(2024 - 07 - 01), date!(2024 - 07 - 10)).unwrap();
+        assert_eq!(trip.name.as_str(), "Alps 2024");
+        assert_eq!(trip.days(), 10);
+        assert!(trip.contains(date!(2024 - 07 - 10)));
+        assert!(!trip.contains(date!(2024 - 07 - 11)));
+        assert!(Trip::new("Alps", date!(2024 - 07 - 10), date!(2024 - 07 - 01)).is_err());
+        assert!(Trip::new("", date!(2024 - 07 - 01), date!(2024 - 07 - 01)).is_err());
+    }
+
+    #[test]
+    fn test_trip_stats() {
+        let summaries = [
+            summary(SportTypes::Running, 5000.0, 1500.0),
+            summary(SportTypes::Hiking, 12000.0, 14400.0),
+            summary(SportTypes::Running, 8000.0, 2400.0),
+        ];
+        let stats = TripStats::from_summaries(&summaries);
+        assert_eq!(stats.activities, 3);
+        assert_abs_diff_eq!(stats.total_distance, 25000.0);
+        assert_eq!(stats.total_calories, 300);
+        assert_eq!(stats.sports.len(), 2);
+        assert_eq!(stats.sports[0].sport, SportTypes::Hiking);
+        assert_eq!(stats.sports[1].activities, 2);
+        assert_abs_diff_eq!(stats.sports[1].total_distance, 13000.0);
+    }
+}
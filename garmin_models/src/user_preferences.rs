@@ -0,0 +1,88 @@
+use anyhow::Error;
+use postgres_query::{query, FromSqlRow};
+use stack_string::StackString;
+
+use garmin_lib::language::Language;
+use garmin_utils::pgpool::PgPool;
+
+/// Per user settings of the web UI, persisted in `user_preferences`
+#[derive(FromSqlRow, Debug, Clone, PartialEq, Eq)]
+pub struct UserPreferences {
+    pub email: StackString,
+    pub language: StackString,
+}
+
+impl UserPreferences {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_email(pool: &PgPool, email: &str) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "SELECT email, language FROM user_preferences WHERE email = $email",
+            email = email,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Language of `email`, English when unset or no longer supported
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_language(pool: &PgPool, email: &str) -> Result<Language, Error> {
+        let preferences = Self::get_by_email(pool, email).await?;
+        Ok(preferences
+            .and_then(|p| p.language.parse().ok())
+            .unwrap_or_default())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn set_language(pool: &PgPool, email: &str, language: Language) -> Result<(), Error> {
+        let query = query!(
+            "
+                INSERT INTO user_preferences (email, language)
+                VALUES ($email, $language)
+                ON CONFLICT (email) DO UPDATE
+                SET language = $language, last_modified = now()
+            ",
+            email = email,
+            language = language.to_str(),
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use garmin_lib::{garmin_config::GarminConfig, language::Language};
+    use garmin_utils::pgpool::PgPool;
+
+    use crate::user_preferences::UserPreferences;
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_user_preferences_language() -> Result<(), Error> {
+        let config = GarminConfig::get_config(None)?;
+        let pool = PgPool::new(&config.pgurl)?;
+        let email = "language-test@localhost";
+
+        assert_eq!(
+            UserPreferences::get_language(&pool, email).await?,
+            Language::English
+        );
+        UserPreferences::set_language(&pool, email, Language::German).await?;
+        assert_eq!(
+            UserPreferences::get_language(&pool, email).await?,
+            Language::German
+        );
+        let query = postgres_query::query!(
+            "DELETE FROM user_preferences WHERE email = $email",
+            email = email
+        );
+        query.execute(&pool.get().await?).await?;
+        Ok(())
+    }
+}
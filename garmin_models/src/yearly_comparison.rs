@@ -0,0 +1,275 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use time::{Date, Duration, Month, OffsetDateTime};
+use time_tz::OffsetDateTimeExt;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::{garmin_util::METERS_PER_MILE, pgpool::PgPool, sport_types::SportTypes};
+
+/// Number of years compared unless asked otherwise, this year and the four
+/// before it
+pub const DEFAULT_COMPARISON_YEARS: i32 = 5;
+
+/// Quantity accumulated over each year, `Distance` in miles and `Duration`
+/// in hours
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonMetric {
+    Distance,
+    Duration,
+}
+
+impl Default for ComparisonMetric {
+    fn default() -> Self {
+        Self::Distance
+    }
+}
+
+impl ComparisonMetric {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Distance => "distance",
+            Self::Duration => "duration",
+        }
+    }
+
+    #[must_use]
+    pub fn units(self) -> &'static str {
+        match self {
+            Self::Distance => "mi",
+            Self::Duration => "h",
+        }
+    }
+
+    /// Value of an activity of `total_distance` meters taking
+    /// `total_duration` seconds
+    #[must_use]
+    pub fn value(self, total_distance: f64, total_duration: f64) -> f64 {
+        match self {
+            Self::Distance => total_distance / METERS_PER_MILE,
+            Self::Duration => total_duration / 3600.0,
+        }
+    }
+}
+
+impl fmt::Display for ComparisonMetric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for ComparisonMetric {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "distance" | "miles" => Ok(Self::Distance),
+            "duration" | "time" => Ok(Self::Duration),
+            _ => Err(format_err!("{s} is not a valid comparison metric")),
+        }
+    }
+}
+
+#[derive(FromSqlRow)]
+struct YearlyActivityRow {
+    begin_datetime: DateTimeWrapper,
+    total_distance: f64,
+    total_duration: f64,
+}
+
+/// Running total of one year, (day of the year, total through that day),
+/// starting from zero on day 0 so every year lines up on Jan 1
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct YearlyCumulative {
+    pub year: i32,
+    pub values: Vec<(u16, f64)>,
+}
+
+impl YearlyCumulative {
+    /// Total through day `day` of the year
+    #[must_use]
+    pub fn value_at(&self, day: u16) -> f64 {
+        self.values
+            .iter()
+            .take_while(|(d, _)| *d <= day)
+            .last()
+            .map_or(0.0, |(_, v)| *v)
+    }
+
+    #[must_use]
+    pub fn total(&self) -> f64 {
+        self.values.last().map_or(0.0, |(_, v)| *v)
+    }
+}
+
+/// Cumulative distance or time of each of the last few years, for
+/// answering "am I ahead of last year?"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct YearlyComparison {
+    pub metric: ComparisonMetric,
+    /// `None` compares every sport together
+    pub sport: Option<SportTypes>,
+    /// Day of the year of `today`, where the current year's line ends
+    pub today: u16,
+    /// Oldest year first
+    pub years: Vec<YearlyCumulative>,
+}
+
+impl YearlyComparison {
+    /// Running totals of the `years` years up to and including the year of
+    /// `today` from (local date, meters, seconds) of each activity, past
+    /// years run through Dec 31 and the current year through `today`
+    #[must_use]
+    pub fn from_activities(
+        activities: &[(Date, f64, f64)],
+        metric: ComparisonMetric,
+        sport: Option<SportTypes>,
+        years: i32,
+        today: Date,
+    ) -> Self {
+        let first_year = today.year() - years.max(1) + 1;
+        let years = (first_year..=today.year())
+            .map(|year| {
+                let mut daily: Vec<_> = activities
+                    .iter()
+                    .filter(|(date, _, _)| date.year() == year && *date <= today)
+                    .map(|(date, distance, duration)| {
+                        (date.ordinal(), metric.value(*distance, *duration))
+                    })
+                    .collect();
+                daily.sort_by_key(|(day, _)| *day);
+                let mut values = vec![(0, 0.0)];
+                let mut total = 0.0;
+                for (day, value) in daily {
+                    total += value;
+                    match values.last_mut() {
+                        Some(last) if last.0 == day => last.1 = total,
+                        _ => values.push((day, total)),
+                    }
+                }
+                let last_day = if year == today.year() {
+                    today.ordinal()
+                } else {
+                    Date::from_calendar_date(year, Month::December, 31).map_or(365, Date::ordinal)
+                };
+                if values.last().is_none_or(|(day, _)| *day < last_day) {
+                    values.push((last_day, total));
+                }
+                YearlyCumulative { year, values }
+            })
+            .collect();
+        Self {
+            metric,
+            sport,
+            today: today.ordinal(),
+            years,
+        }
+    }
+
+    /// Totals of this year and last year through today's day of the year
+    #[must_use]
+    pub fn versus_last_year(&self) -> Option<(f64, f64)> {
+        let mut years = self.years.iter().rev();
+        let current = years.next()?;
+        let previous = years.next()?;
+        Some((current.value_at(self.today), previous.value_at(self.today)))
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(
+        pool: &PgPool,
+        metric: ComparisonMetric,
+        sport: Option<SportTypes>,
+        years: i32,
+    ) -> Result<Self, Error> {
+        let local = DateTimeWrapper::local_tz();
+        let today = OffsetDateTime::now_utc().to_timezone(local).date();
+        let first_year = today.year() - years.max(1) + 1;
+        let start = Date::from_calendar_date(first_year, Month::January, 1)?;
+        let query = query!(
+            "
+                SELECT begin_datetime, total_distance, total_duration
+                FROM garmin_summary
+                WHERE begin_datetime >= $start
+                  AND ($sport::text IS NULL OR sport = $sport)
+            ",
+            start = start.midnight().assume_utc() - Duration::days(1),
+            sport = sport.map(SportTypes::to_str),
+        );
+        let conn = pool.get().await?;
+        let rows: Vec<YearlyActivityRow> = query.fetch(&conn).await?;
+        let activities: Vec<_> = rows
+            .into_iter()
+            .map(|row| {
+                let date = row.begin_datetime.to_timezone(local).date();
+                (date, row.total_distance, row.total_duration)
+            })
+            .collect();
+        Ok(Self::from_activities(
+            &activities,
+            metric,
+            sport,
+            years,
+            today,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use time::macros::date;
+
+    use garmin_utils::garmin_util::METERS_PER_MILE;
+
+    use crate::yearly_comparison::{ComparisonMetric, YearlyComparison};
+
+    #[test]
+    fn test_yearly_comparison() {
+        let activities = [
+            (date!(2023 - 01 - 10), 5.0 * METERS_PER_MILE, 2400.0),
+            (date!(2023 - 03 - 01), 10.0 * METERS_PER_MILE, 4800.0),
+            (date!(2024 - 01 - 05), 6.0 * METERS_PER_MILE, 3000.0),
+            (date!(2024 - 01 - 05), 2.0 * METERS_PER_MILE, 1200.0),
+            (date!(2024 - 02 - 01), 4.0 * METERS_PER_MILE, 1800.0),
+            // after today, ignored
+            (date!(2024 - 04 - 01), 4.0 * METERS_PER_MILE, 1800.0),
+        ];
+        let today = date!(2024 - 02 - 15);
+        let comparison = YearlyComparison::from_activities(
+            &activities,
+            ComparisonMetric::Distance,
+            None,
+            2,
+            today,
+        );
+        assert_eq!(comparison.years.len(), 2);
+        let (y2023, y2024) = (&comparison.years[0], &comparison.years[1]);
+        assert_eq!(y2023.year, 2023);
+        assert_eq!(y2023.values.first(), Some(&(0, 0.0)));
+        assert_eq!(y2023.values.last().map(|(d, _)| *d), Some(365));
+        assert_abs_diff_eq!(y2023.total(), 15.0, epsilon = 1e-9);
+        // two activities on the same day are one point
+        assert_eq!(y2024.values.len(), 4);
+        assert_eq!(y2024.values.last().map(|(d, _)| *d), Some(46));
+        assert_abs_diff_eq!(y2024.value_at(5), 8.0, epsilon = 1e-9);
+
+        let (current, previous) = comparison.versus_last_year().unwrap();
+        assert_abs_diff_eq!(current, 12.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(previous, 5.0, epsilon = 1e-9);
+
+        let comparison = YearlyComparison::from_activities(
+            &activities,
+            ComparisonMetric::Duration,
+            None,
+            1,
+            today,
+        );
+        assert_eq!(comparison.years.len(), 1);
+        assert_abs_diff_eq!(comparison.years[0].total(), 1.666_666_666, epsilon = 1e-6);
+        assert!(comparison.versus_last_year().is_none());
+    }
+}
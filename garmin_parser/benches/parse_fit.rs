@@ -0,0 +1,101 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::{env, fs};
+use time::{macros::datetime, Duration};
+
+use garmin_models::{garmin_file::GarminFile, garmin_lap::GarminLap, garmin_point::GarminPoint};
+use garmin_parser::{
+    garmin_export_fit::garmin_file_to_fit, garmin_parse::GarminParseTrait,
+    garmin_parse_fit::GarminParseFit,
+};
+use garmin_utils::sport_types::SportTypes;
+
+/// Length of the synthetic ultramarathon, recorded every second
+const ULTRA_SECONDS: i64 = 8 * 3600 + 30 * 60;
+
+/// An 8.5h run with a point every second and a lap every mile, written with
+/// the FIT exporter so the bench doesn't depend on a private recording
+fn ultramarathon_fit() -> Vec<u8> {
+    let begin = datetime!(2024-06-01 05:00:00 UTC);
+    let speed = 2.9;
+    let points: Vec<_> = (0..ULTRA_SECONDS)
+        .map(|i| GarminPoint {
+            time: (begin + Duration::seconds(i)).into(),
+            latitude: Some(40.0 + i as f64 * 2e-5),
+            longitude: Some(-105.0 + (i as f64 / 600.0).sin() * 0.01),
+            altitude: Some(1600.0 + (i as f64 / 900.0).sin() * 150.0),
+            distance: Some((i + 1) as f64 * speed),
+            heart_rate: Some(135.0 + (i as f64 / 300.0).sin() * 10.0),
+            speed_mps: speed,
+            cadence: Some(168.0),
+            ..GarminPoint::new()
+        })
+        .collect();
+    let lap_seconds = (1609.344 / speed) as i64;
+    let laps: Vec<_> = (0..ULTRA_SECONDS / lap_seconds)
+        .map(|i| GarminLap {
+            lap_start: (begin + Duration::seconds(i * lap_seconds)).into(),
+            lap_duration: lap_seconds as f64,
+            lap_distance: 1609.344,
+            lap_calories: 110,
+            lap_avg_hr: Some(140.0),
+            lap_max_hr: Some(150),
+            ..GarminLap::new()
+        })
+        .collect();
+    let gfile = GarminFile {
+        filename: "2024-06-01_05-00-00_1_1.fit".into(),
+        filetype: "fit".into(),
+        begin_datetime: begin.into(),
+        sport: SportTypes::Running,
+        total_calories: laps.iter().map(|l| l.lap_calories).sum(),
+        total_distance: laps.iter().map(|l| l.lap_distance).sum(),
+        total_duration: ULTRA_SECONDS as f64,
+        laps,
+        points,
+        ..GarminFile::new()
+    };
+    garmin_file_to_fit(&gfile)
+}
+
+/// Parse `tests/data/test.fit`, a synthetic 8.5h ultramarathon and the file in
+/// `GARMIN_BENCH_FIT_FILE` if set, both with the streaming parser and by
+/// decoding every record up front as was done before (which is only the first
+/// half of the old work, the records were then converted to points).  Each
+/// file is also parsed from disk in chunks and after reading all of it
+fn parse_fit(c: &mut Criterion) {
+    let mut files = vec![
+        (
+            "test.fit".to_string(),
+            fs::read("../tests/data/test.fit").unwrap(),
+        ),
+        ("ultramarathon".to_string(), ultramarathon_fit()),
+    ];
+    if let Some(f) = env::var_os("GARMIN_BENCH_FIT_FILE") {
+        let name = f.to_string_lossy().to_string();
+        files.push((name, fs::read(f).unwrap()));
+    }
+    let tempdir = tempfile::TempDir::new().unwrap();
+    for (name, buf) in &files {
+        let path = tempdir.path().join("bench.fit");
+        fs::write(&path, buf).unwrap();
+        c.bench_function(&format!("stream file {name}"), |b| {
+            b.iter(|| GarminParseFit::new().parse_file(&path).unwrap());
+        });
+        c.bench_function(&format!("read file {name}"), |b| {
+            b.iter(|| GarminParseFit::parse_bytes(&fs::read(&path).unwrap()).unwrap());
+        });
+        c.bench_function(&format!("stream {name}"), |b| {
+            b.iter(|| GarminParseFit::parse_bytes(buf).unwrap());
+        });
+        c.bench_function(&format!("decode all {name}"), |b| {
+            b.iter_batched(
+                || buf.clone(),
+                |buf| fitparser::from_bytes(&buf).unwrap(),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+}
+
+criterion_group!(benches, parse_fit);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use garmin_parser::garmin_parse_fit::GarminParseFit;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = GarminParseFit::parse_bytes(data);
+});
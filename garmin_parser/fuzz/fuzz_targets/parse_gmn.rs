@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use garmin_parser::garmin_parse_gmn::GarminParseGmn;
+
+// gmn files are binary but are parsed from the xml garmin_dump writes, so
+// fuzz that stage
+fuzz_target!(|data: &str| {
+    let _ = GarminParseGmn::parse_xml(data);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use garmin_parser::garmin_parse_gpx::GarminParseGpx;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = GarminParseGpx::new().parse_bytes(data);
+});
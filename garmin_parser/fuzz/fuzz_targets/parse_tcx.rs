@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use garmin_parser::garmin_parse_tcx::GarminParseTcx;
+
+// gzipped input is detected from the magic bytes
+fuzz_target!(|data: &[u8]| {
+    let _ = GarminParseTcx::new().parse_bytes(data);
+});
@@ -0,0 +1,245 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use stack_string::{format_sstr, StackString};
+use std::{f64::consts::PI, fmt::Write};
+use time::{
+    macros::{format_description, time},
+    Duration, OffsetDateTime,
+};
+
+use garmin_lib::date_time_wrapper::iso8601::convert_datetime_to_str;
+use garmin_utils::sport_types::SportTypes;
+
+/// Center of the loop every demo activity follows
+const DEMO_CENTER: (f64, f64) = (40.7812, -73.9665);
+const METERS_PER_DEGREE: f64 = 111_320.0;
+/// Seconds between trackpoints
+const POINT_INTERVAL: i64 = 10;
+
+/// A synthetic activity for the demo site and the integration tests, nothing
+/// in it comes from a real recording so the generated files can be shared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DemoActivity {
+    pub sport: SportTypes,
+    pub begin_datetime: OffsetDateTime,
+    pub number_of_laps: u32,
+    /// meters
+    pub lap_distance: f64,
+    /// meters per second
+    pub speed: f64,
+    pub avg_heart_rate: f64,
+}
+
+impl DemoActivity {
+    /// `count` activities, one a day ending the day before `end`, the same
+    /// `seed` always gives the same activities
+    #[must_use]
+    pub fn generate(seed: u64, count: usize, end: OffsetDateTime) -> Vec<Self> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let start = end.replace_time(time!(07:00));
+        (1..=count)
+            .rev()
+            .map(|days| {
+                let begin_datetime =
+                    start - Duration::days(days as i64) + Duration::minutes(rng.gen_range(0..120));
+                if rng.gen_bool(0.75) {
+                    Self {
+                        sport: SportTypes::Running,
+                        begin_datetime,
+                        number_of_laps: rng.gen_range(3..=8),
+                        lap_distance: 1609.344,
+                        speed: rng.gen_range(2.8..3.8),
+                        avg_heart_rate: rng.gen_range(135.0..165.0),
+                    }
+                } else {
+                    Self {
+                        sport: SportTypes::Biking,
+                        begin_datetime,
+                        number_of_laps: rng.gen_range(2..=5),
+                        lap_distance: 5000.0,
+                        speed: rng.gen_range(6.0..8.5),
+                        avg_heart_rate: rng.gen_range(120.0..150.0),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// When the last lap finishes
+    #[must_use]
+    pub fn end_datetime(&self) -> OffsetDateTime {
+        let duration = f64::from(self.number_of_laps) * self.lap_distance / self.speed;
+        self.begin_datetime + Duration::seconds_f64(duration)
+    }
+
+    #[must_use]
+    pub fn filename(&self) -> StackString {
+        let date = self
+            .begin_datetime
+            .format(format_description!(
+                "[year]-[month]-[day]_[hour]-[minute]-[second]"
+            ))
+            .unwrap_or_else(|_| String::new());
+        format_sstr!("demo_{date}.tcx")
+    }
+
+    fn tcx_sport(&self) -> &'static str {
+        match self.sport {
+            SportTypes::Running => "Running",
+            SportTypes::Biking => "Biking",
+            _ => "Other",
+        }
+    }
+
+    /// Laps go once around a circle of `lap_distance` meters at a constant
+    /// `speed`, heart rate drifts up over the activity
+    #[must_use]
+    pub fn to_tcx(&self) -> StackString {
+        let lap_duration = self.lap_distance / self.speed;
+        let radius = self.lap_distance / (2.0 * PI);
+        let (lat0, lon0) = DEMO_CENTER;
+        let lon_scale = METERS_PER_DEGREE * lat0.to_radians().cos();
+        let total_laps = f64::from(self.number_of_laps);
+
+        let mut buf = String::new();
+        let begin = convert_datetime_to_str(self.begin_datetime);
+        let sport = self.tcx_sport();
+        writeln!(
+            buf,
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="no" ?>"#
+        )
+        .ok();
+        writeln!(
+            buf,
+            r#"<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">"#
+        )
+        .ok();
+        writeln!(buf, "  <Activities>").ok();
+        writeln!(buf, r#"    <Activity Sport="{sport}">"#).ok();
+        writeln!(buf, "      <Id>{begin}</Id>").ok();
+        for lap in 0..self.number_of_laps {
+            let lap_offset = f64::from(lap) * lap_duration;
+            let lap_start =
+                convert_datetime_to_str(self.begin_datetime + Duration::seconds_f64(lap_offset));
+            let lap_hr = self.avg_heart_rate + 10.0 * (f64::from(lap) / total_laps - 0.5);
+            let calories = (lap_duration / 60.0 * 12.0) as i32;
+            writeln!(buf, r#"      <Lap StartTime="{lap_start}">"#).ok();
+            writeln!(
+                buf,
+                "        <TotalTimeSeconds>{lap_duration:.1}</TotalTimeSeconds>"
+            )
+            .ok();
+            writeln!(
+                buf,
+                "        <DistanceMeters>{:.1}</DistanceMeters>",
+                self.lap_distance
+            )
+            .ok();
+            writeln!(
+                buf,
+                "        <MaximumSpeed>{:.3}</MaximumSpeed>",
+                self.speed
+            )
+            .ok();
+            writeln!(buf, "        <Calories>{calories}</Calories>").ok();
+            writeln!(
+                buf,
+                "        <AverageHeartRateBpm><Value>{lap_hr:.0}</Value></AverageHeartRateBpm>"
+            )
+            .ok();
+            writeln!(buf, "        <Intensity>Active</Intensity>").ok();
+            writeln!(buf, "        <TriggerMethod>Distance</TriggerMethod>").ok();
+            writeln!(buf, "        <Track>").ok();
+            let mut t = 0;
+            while (t as f64) < lap_duration {
+                let fraction = t as f64 / lap_duration;
+                let angle = 2.0 * PI * fraction;
+                let lat = lat0 + radius * angle.sin() / METERS_PER_DEGREE;
+                let lon = lon0 + radius * (1.0 - angle.cos()) / lon_scale;
+                let altitude = 30.0 + 8.0 * angle.sin();
+                let distance = (f64::from(lap) + fraction) * self.lap_distance;
+                let time = convert_datetime_to_str(
+                    self.begin_datetime + Duration::seconds_f64(lap_offset + t as f64),
+                );
+                let heart_rate = lap_hr + 4.0 * (angle * 2.0).sin();
+                writeln!(buf, "          <Trackpoint>").ok();
+                writeln!(buf, "            <Time>{time}</Time>").ok();
+                writeln!(
+                    buf,
+                    "            <Position><LatitudeDegrees>{lat:.7}</LatitudeDegrees><LongitudeDegrees>{lon:.7}</LongitudeDegrees></Position>"
+                )
+                .ok();
+                writeln!(
+                    buf,
+                    "            <AltitudeMeters>{altitude:.1}</AltitudeMeters>"
+                )
+                .ok();
+                writeln!(
+                    buf,
+                    "            <DistanceMeters>{distance:.1}</DistanceMeters>"
+                )
+                .ok();
+                writeln!(
+                    buf,
+                    "            <HeartRateBpm><Value>{heart_rate:.0}</Value></HeartRateBpm>"
+                )
+                .ok();
+                writeln!(buf, "          </Trackpoint>").ok();
+                t += POINT_INTERVAL;
+            }
+            writeln!(buf, "        </Track>").ok();
+            writeln!(buf, "      </Lap>").ok();
+        }
+        writeln!(buf, "    </Activity>").ok();
+        writeln!(buf, "  </Activities>").ok();
+        writeln!(buf, "</TrainingCenterDatabase>").ok();
+        buf.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use approx::assert_abs_diff_eq;
+    use std::{collections::HashMap, fs::write};
+    use tempfile::TempDir;
+    use time::{macros::datetime, Duration};
+
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::{
+        demo_data::DemoActivity, garmin_parse::GarminParseTrait, garmin_parse_tcx::GarminParseTcx,
+    };
+
+    #[test]
+    fn test_demo_activity_tcx() -> Result<(), Error> {
+        let end = datetime!(2024-06-01 12:00 UTC);
+        let activities = DemoActivity::generate(42, 5, end);
+        assert_eq!(activities.len(), 5);
+        assert_eq!(activities, DemoActivity::generate(42, 5, end));
+        assert!(activities.iter().all(|a| a.begin_datetime < end));
+
+        let dir = TempDir::with_prefix("demo_data")?;
+        let activity = DemoActivity {
+            sport: SportTypes::Running,
+            begin_datetime: datetime!(2024-05-30 07:15 UTC),
+            number_of_laps: 3,
+            lap_distance: 1609.344,
+            speed: 3.2,
+            avg_heart_rate: 150.0,
+        };
+        let path = dir.path().join(activity.filename().as_str());
+        write(&path, activity.to_tcx())?;
+        let gfile = GarminParseTcx::new().with_file(&path, &HashMap::new())?;
+        assert_eq!(gfile.filename.as_str(), "demo_2024-05-30_07-15-00.tcx");
+        assert_eq!(gfile.sport, SportTypes::Running);
+        assert_eq!(gfile.laps.len(), 3);
+        assert_abs_diff_eq!(gfile.total_distance, 3.0 * 1609.344, epsilon = 1.0);
+        assert_abs_diff_eq!(gfile.total_duration, 3.0 * 1609.344 / 3.2, epsilon = 1.0);
+        assert_eq!(
+            activity.end_datetime(),
+            datetime!(2024-05-30 07:15 UTC) + Duration::seconds_f64(3.0 * 1609.344 / 3.2)
+        );
+        assert!(!gfile.points.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+use time::{Duration, OffsetDateTime};
+
+use garmin_models::{garmin_file::GarminFile, garmin_lap::GarminLap, garmin_point::GarminPoint};
+use garmin_utils::fit_encode::{
+    fit_definition, fit_file, FIT_BASE_ENUM, FIT_BASE_SINT32, FIT_BASE_SINT8, FIT_BASE_UINT16,
+    FIT_BASE_UINT32, FIT_BASE_UINT8, FIT_EPOCH_OFFSET,
+};
+
+const SEMICIRCLES_PER_DEGREE: f64 = 2_147_483_648.0 / 180.0;
+
+/// Serialize `gfile` as a FIT activity file: a record for every point, its
+/// laps (or a single lap spanning the activity if it has none), one session
+/// and the activity summary, so it can be imported back into Garmin Connect
+/// or any other tool reading FIT
+#[must_use]
+pub fn garmin_file_to_fit(gfile: &GarminFile) -> Vec<u8> {
+    let begin: OffsetDateTime = gfile.begin_datetime.into();
+    let end = gfile
+        .points
+        .last()
+        .map(|p| p.time.into())
+        .unwrap_or(begin)
+        .max(begin + Duration::seconds_f64(gfile.total_duration));
+    let sport = gfile.sport.to_fit_sport();
+    let whole_activity = [GarminLap {
+        lap_start: gfile.begin_datetime,
+        lap_duration: gfile.total_duration,
+        lap_distance: gfile.total_distance,
+        lap_calories: gfile.total_calories,
+        ..GarminLap::new()
+    }];
+    let laps = if gfile.laps.is_empty() {
+        &whole_activity[..]
+    } else {
+        &gfile.laps[..]
+    };
+
+    let mut data = Vec::new();
+
+    // file_id: type=activity, manufacturer=development
+    fit_definition(
+        &mut data,
+        0,
+        0,
+        &[
+            (0, 1, FIT_BASE_ENUM),
+            (1, 2, FIT_BASE_UINT16),
+            (2, 2, FIT_BASE_UINT16),
+            (4, 4, FIT_BASE_UINT32),
+        ],
+    );
+    data.push(0);
+    data.push(4);
+    data.extend_from_slice(&255u16.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&fit_time(begin).to_le_bytes());
+
+    // record
+    fit_definition(
+        &mut data,
+        1,
+        20,
+        &[
+            (253, 4, FIT_BASE_UINT32),
+            (0, 4, FIT_BASE_SINT32),
+            (1, 4, FIT_BASE_SINT32),
+            (5, 4, FIT_BASE_UINT32),
+            (73, 4, FIT_BASE_UINT32),
+            (78, 4, FIT_BASE_UINT32),
+            (3, 1, FIT_BASE_UINT8),
+            (4, 1, FIT_BASE_UINT8),
+            (7, 2, FIT_BASE_UINT16),
+            (13, 1, FIT_BASE_SINT8),
+        ],
+    );
+    for point in &gfile.points {
+        write_record(&mut data, point);
+    }
+
+    // lap: event=lap, event_type=stop
+    fit_definition(
+        &mut data,
+        2,
+        19,
+        &[
+            (253, 4, FIT_BASE_UINT32),
+            (254, 2, FIT_BASE_UINT16),
+            (0, 1, FIT_BASE_ENUM),
+            (1, 1, FIT_BASE_ENUM),
+            (2, 4, FIT_BASE_UINT32),
+            (7, 4, FIT_BASE_UINT32),
+            (8, 4, FIT_BASE_UINT32),
+            (9, 4, FIT_BASE_UINT32),
+            (11, 2, FIT_BASE_UINT16),
+            (15, 1, FIT_BASE_UINT8),
+            (16, 1, FIT_BASE_UINT8),
+            (19, 2, FIT_BASE_UINT16),
+            (33, 2, FIT_BASE_UINT16),
+            (17, 1, FIT_BASE_UINT8),
+            (18, 1, FIT_BASE_UINT8),
+            (25, 1, FIT_BASE_ENUM),
+        ],
+    );
+    for (idx, lap) in laps.iter().enumerate() {
+        let start: OffsetDateTime = lap.lap_start.into();
+        data.push(2);
+        data.extend_from_slice(
+            &fit_time(start + Duration::seconds_f64(lap.lap_duration)).to_le_bytes(),
+        );
+        data.extend_from_slice(&(idx as u16).to_le_bytes());
+        data.extend_from_slice(&[9, 1]);
+        data.extend_from_slice(&fit_time(start).to_le_bytes());
+        data.extend_from_slice(&scaled(Some(lap.lap_duration), 1000.0, 0.0).to_le_bytes());
+        data.extend_from_slice(&scaled(Some(lap.lap_duration), 1000.0, 0.0).to_le_bytes());
+        data.extend_from_slice(&scaled(Some(lap.lap_distance), 100.0, 0.0).to_le_bytes());
+        data.extend_from_slice(&(lap.lap_calories.clamp(0, 0xFFFE) as u16).to_le_bytes());
+        data.push(heart_rate(lap.lap_avg_hr));
+        data.push(heart_rate(lap.lap_max_hr.map(f64::from)));
+        data.extend_from_slice(&watts(lap.lap_avg_power).to_le_bytes());
+        data.extend_from_slice(&watts(lap.lap_normalized_power).to_le_bytes());
+        data.push(cadence(lap.lap_avg_cadence));
+        data.push(cadence(lap.lap_max_cadence));
+        data.push(sport);
+    }
+
+    // session: event=session, event_type=stop
+    fit_definition(
+        &mut data,
+        3,
+        18,
+        &[
+            (253, 4, FIT_BASE_UINT32),
+            (254, 2, FIT_BASE_UINT16),
+            (0, 1, FIT_BASE_ENUM),
+            (1, 1, FIT_BASE_ENUM),
+            (2, 4, FIT_BASE_UINT32),
+            (5, 1, FIT_BASE_ENUM),
+            (7, 4, FIT_BASE_UINT32),
+            (8, 4, FIT_BASE_UINT32),
+            (9, 4, FIT_BASE_UINT32),
+            (11, 2, FIT_BASE_UINT16),
+            (25, 2, FIT_BASE_UINT16),
+            (26, 2, FIT_BASE_UINT16),
+        ],
+    );
+    data.push(3);
+    data.extend_from_slice(&fit_time(end).to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&[8, 1]);
+    data.extend_from_slice(&fit_time(begin).to_le_bytes());
+    data.push(sport);
+    data.extend_from_slice(
+        &scaled(Some((end - begin).as_seconds_f64()), 1000.0, 0.0).to_le_bytes(),
+    );
+    data.extend_from_slice(&scaled(Some(gfile.total_duration), 1000.0, 0.0).to_le_bytes());
+    data.extend_from_slice(&scaled(Some(gfile.total_distance), 100.0, 0.0).to_le_bytes());
+    data.extend_from_slice(&(gfile.total_calories.clamp(0, 0xFFFE) as u16).to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&(laps.len() as u16).to_le_bytes());
+
+    // activity: type=manual, event=activity, event_type=stop
+    fit_definition(
+        &mut data,
+        4,
+        34,
+        &[
+            (253, 4, FIT_BASE_UINT32),
+            (0, 4, FIT_BASE_UINT32),
+            (1, 2, FIT_BASE_UINT16),
+            (2, 1, FIT_BASE_ENUM),
+            (3, 1, FIT_BASE_ENUM),
+            (4, 1, FIT_BASE_ENUM),
+        ],
+    );
+    data.push(4);
+    data.extend_from_slice(&fit_time(end).to_le_bytes());
+    data.extend_from_slice(&scaled(Some(gfile.total_duration), 1000.0, 0.0).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&[0, 26, 1]);
+
+    fit_file(&data)
+}
+
+fn write_record(data: &mut Vec<u8>, point: &GarminPoint) {
+    data.push(1);
+    data.extend_from_slice(&fit_time(point.time.into()).to_le_bytes());
+    data.extend_from_slice(&semicircles(point.latitude).to_le_bytes());
+    data.extend_from_slice(&semicircles(point.longitude).to_le_bytes());
+    data.extend_from_slice(&scaled(point.distance, 100.0, 0.0).to_le_bytes());
+    data.extend_from_slice(&scaled(Some(point.speed_mps), 1000.0, 0.0).to_le_bytes());
+    data.extend_from_slice(&scaled(point.altitude, 5.0, 500.0).to_le_bytes());
+    data.push(heart_rate(point.heart_rate));
+    data.push(cadence(point.cadence));
+    data.extend_from_slice(&watts(point.power).to_le_bytes());
+    let temperature = point
+        .temperature
+        .map_or(i8::MAX, |t| t.round().clamp(-127.0, 126.0) as i8);
+    data.extend_from_slice(&temperature.to_le_bytes());
+}
+
+fn fit_time(datetime: OffsetDateTime) -> u32 {
+    (datetime.unix_timestamp() - FIT_EPOCH_OFFSET).clamp(0, i64::from(u32::MAX - 1)) as u32
+}
+
+/// `(value + offset) * scale` as a FIT uint32, missing values are encoded as
+/// the invalid marker which readers skip
+fn scaled(value: Option<f64>, scale: f64, offset: f64) -> u32 {
+    value.filter(|v| v.is_finite()).map_or(u32::MAX, |v| {
+        ((v + offset) * scale)
+            .round()
+            .clamp(0.0, f64::from(u32::MAX - 1)) as u32
+    })
+}
+
+fn semicircles(degrees: Option<f64>) -> i32 {
+    degrees.filter(|d| d.is_finite()).map_or(i32::MAX, |d| {
+        (d * SEMICIRCLES_PER_DEGREE)
+            .round()
+            .clamp(f64::from(i32::MIN), f64::from(i32::MAX - 1)) as i32
+    })
+}
+
+fn watts(value: Option<f64>) -> u16 {
+    value
+        .filter(|w| w.is_finite())
+        .map_or(u16::MAX, |w| w.round().clamp(0.0, 65534.0) as u16)
+}
+
+fn cadence(value: Option<f64>) -> u8 {
+    value
+        .filter(|c| c.is_finite())
+        .map_or(u8::MAX, |c| c.round().clamp(0.0, 254.0) as u8)
+}
+
+fn heart_rate(value: Option<f64>) -> u8 {
+    value
+        .filter(|hr| *hr > 0.0)
+        .map_or(u8::MAX, |hr| hr.round().clamp(0.0, 254.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use approx::assert_abs_diff_eq;
+    use std::fs;
+    use time::{macros::datetime, Duration};
+
+    use garmin_models::{
+        garmin_file::GarminFile, garmin_lap::GarminLap, garmin_point::GarminPoint,
+    };
+    use garmin_utils::{fit_encode::fit_crc, sport_types::SportTypes};
+
+    use crate::{
+        garmin_export_fit::garmin_file_to_fit, garmin_parse::GarminParseTrait,
+        garmin_parse_fit::GarminParseFit,
+    };
+
+    fn test_file() -> GarminFile {
+        let begin = datetime!(2024-05-04 13:00:00 UTC);
+        let points = (0..10)
+            .map(|i| GarminPoint {
+                time: (begin + Duration::seconds(i * 10)).into(),
+                latitude: Some(40.7 + i as f64 * 0.0003),
+                longitude: Some(-73.95),
+                altitude: Some(12.4),
+                distance: Some((i + 1) as f64 * 33.3),
+                heart_rate: Some(140.0 + i as f64),
+                speed_mps: 3.33,
+                cadence: Some(172.0),
+                ..GarminPoint::new()
+            })
+            .collect();
+        let laps = (0..2)
+            .map(|i| GarminLap {
+                lap_start: (begin + Duration::seconds(i * 50)).into(),
+                lap_duration: 50.0,
+                lap_distance: 166.5,
+                lap_calories: 12,
+                lap_avg_hr: Some(142.0),
+                lap_max_hr: Some(149),
+                ..GarminLap::new()
+            })
+            .collect();
+        GarminFile {
+            filename: "2024-05-04_13-00-00_1_1.fit".into(),
+            filetype: "fit".into(),
+            begin_datetime: begin.into(),
+            sport: SportTypes::Running,
+            total_calories: 24,
+            total_distance: 333.0,
+            total_duration: 100.0,
+            laps,
+            points,
+            ..GarminFile::new()
+        }
+    }
+
+    #[test]
+    fn test_garmin_file_to_fit() -> Result<(), Error> {
+        let gfile = test_file();
+        let fit = garmin_file_to_fit(&gfile);
+        assert_eq!(&fit[8..12], b".FIT");
+        assert_eq!(fit_crc(&fit), 0);
+
+        let output = GarminParseFit::parse_bytes(&fit)?;
+        assert_eq!(output.sport, SportTypes::Running);
+        assert_eq!(output.lap_list.len(), 2);
+        assert_eq!(output.point_list.len(), 10);
+        for (lap, exp) in output.lap_list.iter().zip(gfile.laps.iter()) {
+            assert_eq!(lap.lap_start, exp.lap_start);
+            assert_abs_diff_eq!(lap.lap_duration, exp.lap_duration);
+            assert_abs_diff_eq!(lap.lap_distance, exp.lap_distance);
+            assert_eq!(lap.lap_calories, exp.lap_calories);
+            assert_eq!(lap.lap_max_hr, exp.lap_max_hr);
+        }
+        for (point, exp) in output.point_list.iter().zip(gfile.points.iter()) {
+            assert_eq!(point.time, exp.time);
+            assert_abs_diff_eq!(
+                point.latitude.unwrap(),
+                exp.latitude.unwrap(),
+                epsilon = 1e-6
+            );
+            assert_abs_diff_eq!(
+                point.longitude.unwrap(),
+                exp.longitude.unwrap(),
+                epsilon = 1e-6
+            );
+            assert_abs_diff_eq!(
+                point.altitude.unwrap(),
+                exp.altitude.unwrap(),
+                epsilon = 0.2
+            );
+            assert_abs_diff_eq!(
+                point.distance.unwrap(),
+                exp.distance.unwrap(),
+                epsilon = 0.01
+            );
+            assert_eq!(point.heart_rate, exp.heart_rate);
+            assert_eq!(point.cadence, exp.cadence);
+            assert_eq!(point.power, None);
+            assert_abs_diff_eq!(point.speed_mps, exp.speed_mps, epsilon = 0.001);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_garmin_file_to_fit_without_laps() -> Result<(), Error> {
+        let mut gfile = test_file();
+        gfile.laps.clear();
+        gfile.sport = SportTypes::None;
+        let output = GarminParseFit::parse_bytes(&garmin_file_to_fit(&gfile))?;
+        assert_eq!(output.lap_list.len(), 1);
+        assert_abs_diff_eq!(output.lap_list[0].lap_distance, 333.0);
+        assert_abs_diff_eq!(output.lap_list[0].lap_duration, 100.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_indoor_ride_power() -> Result<(), Error> {
+        let begin = datetime!(2024-05-04 13:00:00 UTC);
+        let gfile = GarminFile {
+            sport: SportTypes::Biking,
+            begin_datetime: begin.into(),
+            total_duration: 120.0,
+            laps: vec![GarminLap {
+                lap_start: begin.into(),
+                lap_duration: 120.0,
+                ..GarminLap::new()
+            }],
+            points: (0..120)
+                .map(|i| GarminPoint {
+                    time: (begin + Duration::seconds(i)).into(),
+                    power: Some(250.0),
+                    ..GarminPoint::new()
+                })
+                .collect(),
+            ..GarminFile::new()
+        };
+        // points without a position are kept when they have power, and the
+        // lap power missing from the file is filled in from them
+        let output = GarminParseFit::parse_bytes(&garmin_file_to_fit(&gfile))?;
+        assert_eq!(output.point_list.len(), 120);
+        let lap = &output.lap_list[0];
+        assert_abs_diff_eq!(lap.lap_avg_power.unwrap(), 250.0);
+        assert_abs_diff_eq!(lap.lap_normalized_power.unwrap(), 250.0);
+
+        let mut gfile = gfile;
+        gfile.laps[0].lap_avg_power = Some(240.0);
+        gfile.laps[0].lap_normalized_power = Some(260.0);
+        let output = GarminParseFit::parse_bytes(&garmin_file_to_fit(&gfile))?;
+        assert_eq!(output.lap_list[0].lap_avg_power, Some(240.0));
+        assert_eq!(output.lap_list[0].lap_normalized_power, Some(260.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_treadmill_cadence() -> Result<(), Error> {
+        let begin = datetime!(2024-05-04 13:00:00 UTC);
+        let gfile = GarminFile {
+            sport: SportTypes::Running,
+            begin_datetime: begin.into(),
+            total_duration: 120.0,
+            laps: vec![GarminLap {
+                lap_start: begin.into(),
+                lap_duration: 120.0,
+                ..GarminLap::new()
+            }],
+            points: (0..120)
+                .map(|i| GarminPoint {
+                    time: (begin + Duration::seconds(i)).into(),
+                    cadence: Some(if i == 60 { 95.0 } else { 84.0 }),
+                    ..GarminPoint::new()
+                })
+                .collect(),
+            ..GarminFile::new()
+        };
+        // points without a position are kept when they have cadence, and the
+        // lap cadence missing from the file is filled in from them
+        let output = GarminParseFit::parse_bytes(&garmin_file_to_fit(&gfile))?;
+        assert_eq!(output.point_list.len(), 120);
+        let lap = &output.lap_list[0];
+        assert_abs_diff_eq!(lap.lap_avg_cadence.unwrap(), 84.09, epsilon = 0.01);
+        assert_eq!(lap.lap_max_cadence, Some(95.0));
+
+        let mut gfile = gfile;
+        gfile.laps[0].lap_avg_cadence = Some(86.0);
+        gfile.laps[0].lap_max_cadence = Some(92.0);
+        let output = GarminParseFit::parse_bytes(&garmin_file_to_fit(&gfile))?;
+        assert_eq!(output.lap_list[0].lap_avg_cadence, Some(86.0));
+        assert_eq!(output.lap_list[0].lap_max_cadence, Some(92.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_file_across_read_chunks() -> Result<(), Error> {
+        let begin = datetime!(2024-05-04 13:00:00 UTC);
+        let mut gfile = test_file();
+        gfile.points = (0..10_000)
+            .map(|i| GarminPoint {
+                time: (begin + Duration::seconds(i)).into(),
+                latitude: Some(40.7 + i as f64 * 1e-5),
+                longitude: Some(-73.95),
+                distance: Some((i + 1) as f64 * 3.0),
+                heart_rate: Some(140.0),
+                speed_mps: 3.0,
+                ..GarminPoint::new()
+            })
+            .collect();
+        let fit = garmin_file_to_fit(&gfile);
+        // several of the 64KiB chunks the parser reads at a time
+        assert!(fit.len() > 3 * 64 * 1024);
+
+        let tempdir = tempfile::TempDir::new()?;
+        let filename = tempdir.path().join("long.fit");
+        fs::write(&filename, &fit)?;
+        let output = GarminParseFit::new().parse_file(&filename)?;
+        assert_eq!(output.point_list.len(), 10_000);
+        assert_eq!(output.lap_list.len(), 2);
+        assert_eq!(
+            output.point_list,
+            GarminParseFit::parse_bytes(&fit)?.point_list
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use stack_string::{format_sstr, StackString};
+use time::OffsetDateTime;
+
+use garmin_lib::date_time_wrapper::iso8601::convert_datetime_to_str;
+use garmin_models::{garmin_file::GarminFile, garmin_point::GarminPoint};
+use garmin_utils::garmin_util::xml_escape;
+
+/// Serialize `gfile` as a GPX 1.1 track for route planners, each lap becomes
+/// a track segment, points without a position are left out since gpx
+/// requires one, heart rate, cadence, temperature and power go in the Garmin
+/// extensions
+#[must_use]
+pub fn garmin_file_to_gpx(gfile: &GarminFile) -> StackString {
+    let mut gpx = String::new();
+    gpx.push_str(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<gpx version="1.1" creator="garmin_rust""#,
+        r#" xmlns="http://www.topografix.com/GPX/1/1""#,
+        r#" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1""#,
+        r#" xmlns:pwr="http://www.garmin.com/xmlschemas/PowerExtension/v1">"#,
+        "\n",
+    ));
+    gpx.push_str(&format_sstr!(
+        "  <metadata><time>{}</time></metadata>\n",
+        convert_datetime_to_str(gfile.begin_datetime.into())
+    ));
+    gpx.push_str("  <trk>\n");
+    gpx.push_str(&format_sstr!(
+        "    <name>{}</name>\n",
+        xml_escape(&gfile.filename)
+    ));
+    gpx.push_str(&format_sstr!(
+        "    <type>{}</type>\n",
+        xml_escape(gfile.sport.to_str())
+    ));
+    for segment in lap_segments(gfile) {
+        if !segment.iter().any(|p| position(p).is_some()) {
+            continue;
+        }
+        gpx.push_str("    <trkseg>\n");
+        for point in segment {
+            write_trkpt(&mut gpx, point);
+        }
+        gpx.push_str("    </trkseg>\n");
+    }
+    gpx.push_str("  </trk>\n</gpx>\n");
+    gpx.into()
+}
+
+/// Points of `gfile` split at the start of each lap
+fn lap_segments(gfile: &GarminFile) -> Vec<&[GarminPoint]> {
+    let mut segments = Vec::new();
+    let mut points = &gfile.points[..];
+    for lap in gfile.laps.iter().skip(1) {
+        let lap_start: OffsetDateTime = lap.lap_start.into();
+        let split = points.partition_point(|p| p.time.to_offsetdatetime() < lap_start);
+        let (segment, rest) = points.split_at(split);
+        segments.push(segment);
+        points = rest;
+    }
+    segments.push(points);
+    segments
+}
+
+fn position(point: &GarminPoint) -> Option<(f64, f64)> {
+    point.latitude.zip(point.longitude)
+}
+
+fn write_trkpt(gpx: &mut String, point: &GarminPoint) {
+    let (lat, lon) = match position(point) {
+        Some(position) => position,
+        None => return,
+    };
+    gpx.push_str(&format_sstr!(
+        "      <trkpt lat=\"{lat:.7}\" lon=\"{lon:.7}\">\n"
+    ));
+    if let Some(altitude) = point.altitude {
+        gpx.push_str(&format_sstr!("        <ele>{altitude:.1}</ele>\n"));
+    }
+    gpx.push_str(&format_sstr!(
+        "        <time>{}</time>\n",
+        convert_datetime_to_str(point.time.into())
+    ));
+    let mut tpx = String::new();
+    if let Some(temperature) = point.temperature {
+        tpx.push_str(&format_sstr!(
+            "<gpxtpx:atemp>{temperature:.1}</gpxtpx:atemp>"
+        ));
+    }
+    if let Some(heart_rate) = point.heart_rate {
+        tpx.push_str(&format_sstr!("<gpxtpx:hr>{heart_rate:.0}</gpxtpx:hr>"));
+    }
+    if let Some(cadence) = point.cadence {
+        tpx.push_str(&format_sstr!("<gpxtpx:cad>{cadence:.0}</gpxtpx:cad>"));
+    }
+    if !tpx.is_empty() || point.power.is_some() {
+        gpx.push_str("        <extensions>\n");
+        if let Some(power) = point.power {
+            gpx.push_str(&format_sstr!(
+                "          <pwr:PowerInWatts>{power:.0}</pwr:PowerInWatts>\n"
+            ));
+        }
+        if !tpx.is_empty() {
+            gpx.push_str(&format_sstr!(
+                "          <gpxtpx:TrackPointExtension>{tpx}</gpxtpx:TrackPointExtension>\n"
+            ));
+        }
+        gpx.push_str("        </extensions>\n");
+    }
+    gpx.push_str("      </trkpt>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use approx::assert_abs_diff_eq;
+    use std::{collections::HashMap, path::Path};
+
+    use crate::{
+        garmin_export_gpx::garmin_file_to_gpx,
+        garmin_parse::{GarminParse, GarminParseTrait},
+        garmin_parse_gpx::GarminParseGpx,
+    };
+
+    #[test]
+    fn test_garmin_file_to_gpx() -> Result<(), Error> {
+        let corr_map = HashMap::new();
+        let gfile = GarminParse::new().with_file(Path::new("../tests/data/test.fit"), &corr_map)?;
+        let gpx = garmin_file_to_gpx(&gfile);
+        let output = GarminParseGpx::new().parse_bytes(gpx.as_bytes())?;
+        let expected: Vec<_> = gfile
+            .points
+            .iter()
+            .filter(|p| p.latitude.is_some() && p.longitude.is_some())
+            .collect();
+        assert_eq!(output.point_list.len(), expected.len());
+        assert_eq!(output.sport, gfile.sport);
+        assert!(output.lap_list.len() <= gfile.laps.len());
+        for (point, exp) in output.point_list.iter().zip(expected) {
+            assert_eq!(point.time, exp.time);
+            assert_eq!(point.heart_rate, exp.heart_rate.map(f64::round));
+            assert_abs_diff_eq!(
+                point.latitude.unwrap_or(0.0),
+                exp.latitude.unwrap_or(0.0),
+                epsilon = 1e-6
+            );
+            if let Some(altitude) = exp.altitude {
+                assert_abs_diff_eq!(point.altitude.unwrap_or(0.0), altitude, epsilon = 0.05);
+            }
+        }
+        Ok(())
+    }
+}
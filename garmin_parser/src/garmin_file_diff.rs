@@ -0,0 +1,295 @@
+use anyhow::Error;
+use serde_json::Value;
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+
+use garmin_models::garmin_file::GarminFile;
+
+/// Numbers closer than this are considered equal
+pub const DEFAULT_DIFF_TOLERANCE: f64 = 1e-6;
+/// Laps or points with differences listed before the rest are only counted
+pub const DEFAULT_MAX_ENTRY_DIFFS: usize = 10;
+
+/// Value of one field on each side, `None` when the field or entry is missing
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub path: StackString,
+    pub left: Option<StackString>,
+    pub right: Option<StackString>,
+    /// right - left for numeric fields
+    pub delta: Option<f64>,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let left = self.left.as_ref().map_or("missing", StackString::as_str);
+        let right = self.right.as_ref().map_or("missing", StackString::as_str);
+        write!(f, "{}: {left} -> {right}", self.path)?;
+        if let Some(delta) = self.delta {
+            write!(f, " ({delta:+})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences of the laps or points of two parses, `omitted` counts the
+/// entries with differences past the listed ones
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EntryDiffs {
+    pub left_count: usize,
+    pub right_count: usize,
+    pub diffs: Vec<FieldDiff>,
+    pub omitted: usize,
+}
+
+impl EntryDiffs {
+    fn from_entries(
+        name: &str,
+        left: &[Value],
+        right: &[Value],
+        tolerance: f64,
+        max_entries: usize,
+    ) -> Self {
+        let mut diffs = Vec::new();
+        let mut differing = 0;
+        for idx in 0..left.len().max(right.len()) {
+            let path = format_sstr!("{name}[{idx}]");
+            let mut entry_diffs = Vec::new();
+            match (left.get(idx), right.get(idx)) {
+                (Some(l), Some(r)) => diff_values(&path, l, r, tolerance, &mut entry_diffs),
+                (l, r) => entry_diffs.push(FieldDiff {
+                    path,
+                    left: l.map(|_| "present".into()),
+                    right: r.map(|_| "present".into()),
+                    delta: None,
+                }),
+            }
+            if entry_diffs.is_empty() {
+                continue;
+            }
+            differing += 1;
+            if differing <= max_entries {
+                diffs.extend(entry_diffs);
+            }
+        }
+        Self {
+            left_count: left.len(),
+            right_count: right.len(),
+            diffs,
+            omitted: differing.saturating_sub(max_entries),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.left_count == self.right_count && self.diffs.is_empty()
+    }
+}
+
+/// Field by field differences between two parses of the same activity, e.g.
+/// the cached parse and a fresh one
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GarminFileDiff {
+    pub summary: Vec<FieldDiff>,
+    pub laps: EntryDiffs,
+    pub points: EntryDiffs,
+}
+
+impl GarminFileDiff {
+    /// Compare `left` with `right`, numbers within `tolerance` are equal and
+    /// at most `max_entries` differing laps and points are listed
+    /// # Errors
+    /// Return error if serializing either file fails
+    pub fn compare(
+        left: &GarminFile,
+        right: &GarminFile,
+        tolerance: f64,
+        max_entries: usize,
+    ) -> Result<Self, Error> {
+        let mut left = serde_json::to_value(left)?;
+        let mut right = serde_json::to_value(right)?;
+        let (left_laps, left_points) = take_entries(&mut left);
+        let (right_laps, right_points) = take_entries(&mut right);
+        let mut summary = Vec::new();
+        diff_values("", &left, &right, tolerance, &mut summary);
+        Ok(Self {
+            summary,
+            laps: EntryDiffs::from_entries("laps", &left_laps, &right_laps, tolerance, max_entries),
+            points: EntryDiffs::from_entries(
+                "points",
+                &left_points,
+                &right_points,
+                tolerance,
+                max_entries,
+            ),
+        })
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.summary.is_empty() && self.laps.is_empty() && self.points.is_empty()
+    }
+}
+
+impl fmt::Display for GarminFileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no differences");
+        }
+        writeln!(f, "summary")?;
+        for diff in &self.summary {
+            writeln!(f, "  {diff}")?;
+        }
+        for (name, entries) in [("laps", &self.laps), ("points", &self.points)] {
+            writeln!(
+                f,
+                "{name}: {} -> {}",
+                entries.left_count, entries.right_count
+            )?;
+            for diff in &entries.diffs {
+                writeln!(f, "  {diff}")?;
+            }
+            if entries.omitted > 0 {
+                writeln!(f, "  ... {} more {name} differ", entries.omitted)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn take_entries(value: &mut Value) -> (Vec<Value>, Vec<Value>) {
+    let mut take = |key: &str| match value.as_object_mut().and_then(|o| o.remove(key)) {
+        Some(Value::Array(entries)) => entries,
+        _ => Vec::new(),
+    };
+    let laps = take("laps");
+    let points = take("points");
+    (laps, points)
+}
+
+fn value_string(value: &Value) -> StackString {
+    match value {
+        Value::String(s) => s.as_str().into(),
+        v => format_sstr!("{v}"),
+    }
+}
+
+fn diff_values(
+    path: &str,
+    left: &Value,
+    right: &Value,
+    tolerance: f64,
+    diffs: &mut Vec<FieldDiff>,
+) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.into()
+        } else {
+            format_sstr!("{path}.{key}")
+        }
+    };
+    match (left, right) {
+        (Value::Object(l), Value::Object(r)) => {
+            for (key, lv) in l {
+                match r.get(key) {
+                    Some(rv) => diff_values(&join(key), lv, rv, tolerance, diffs),
+                    None => diffs.push(FieldDiff {
+                        path: join(key),
+                        left: Some(value_string(lv)),
+                        right: None,
+                        delta: None,
+                    }),
+                }
+            }
+            for (key, rv) in r.iter().filter(|(k, _)| !l.contains_key(*k)) {
+                diffs.push(FieldDiff {
+                    path: join(key),
+                    left: None,
+                    right: Some(value_string(rv)),
+                    delta: None,
+                });
+            }
+        }
+        (Value::Number(l), Value::Number(r)) => {
+            if let (Some(l), Some(r)) = (l.as_f64(), r.as_f64()) {
+                if (r - l).abs() > tolerance {
+                    diffs.push(FieldDiff {
+                        path: path.into(),
+                        left: Some(format_sstr!("{l}")),
+                        right: Some(format_sstr!("{r}")),
+                        delta: Some(r - l),
+                    });
+                }
+            }
+        }
+        (l, r) => {
+            if l != r {
+                diffs.push(FieldDiff {
+                    path: path.into(),
+                    left: Some(value_string(l)),
+                    right: Some(value_string(r)),
+                    delta: None,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use garmin_models::{
+        garmin_file::GarminFile, garmin_lap::GarminLap, garmin_point::GarminPoint,
+    };
+
+    use crate::garmin_file_diff::{GarminFileDiff, DEFAULT_DIFF_TOLERANCE};
+
+    #[test]
+    fn test_garmin_file_diff() -> Result<(), Error> {
+        let mut left = GarminFile::new();
+        left.filename = "test.fit".into();
+        left.total_distance = 5000.0;
+        left.laps = vec![GarminLap::new(); 2];
+        left.points = (0..20)
+            .map(|idx| GarminPoint {
+                distance: Some(f64::from(idx) * 10.0),
+                ..GarminPoint::default()
+            })
+            .collect();
+
+        let diff = GarminFileDiff::compare(&left, &left, DEFAULT_DIFF_TOLERANCE, 3)?;
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no differences\n");
+
+        let mut right = left.clone();
+        right.total_distance = 5001.5;
+        right.laps[1].lap_distance = 1000.0;
+        right.laps[1].lap_trigger = Some("manual".into());
+        for point in &mut right.points[5..] {
+            point.distance = point.distance.map(|d| d + 1.0);
+        }
+        right.points.pop();
+
+        let diff = GarminFileDiff::compare(&left, &right, DEFAULT_DIFF_TOLERANCE, 3)?;
+        assert_eq!(diff.summary.len(), 1);
+        assert_eq!(diff.summary[0].path.as_str(), "total_distance");
+        assert_eq!(diff.summary[0].delta, Some(1.5));
+        assert_eq!(diff.laps.diffs.len(), 2);
+        assert_eq!(diff.points.left_count, 20);
+        assert_eq!(diff.points.right_count, 19);
+        assert_eq!(diff.points.diffs.len(), 3);
+        // points 8 through 18 differ in distance, 19 is missing
+        assert_eq!(diff.points.omitted, 12);
+        let output = diff.to_string();
+        assert!(output.contains("total_distance: 5000 -> 5001.5 (+1.5)"));
+        assert!(output.contains("laps[1].lap_trigger: null -> manual"));
+        assert!(output.contains("points[5].distance: 50 -> 51 (+1)"));
+        assert!(output.contains("... 12 more points differ"));
+
+        let diff = GarminFileDiff::compare(&left, &right, 2.0, 3)?;
+        assert!(diff.summary.is_empty());
+        assert_eq!(diff.points.diffs.len(), 1);
+        Ok(())
+    }
+}
@@ -1,8 +1,13 @@
 use anyhow::{format_err, Error};
 use log::debug;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use stack_string::format_sstr;
-use std::{collections::HashMap, ffi::OsStr, path::Path};
+use stack_string::{format_sstr, StackString};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+};
 
 use garmin_lib::date_time_wrapper::DateTimeWrapper;
 use garmin_models::{
@@ -16,7 +21,8 @@ use garmin_utils::{
 
 use super::{
     garmin_parse_fit::GarminParseFit, garmin_parse_gmn::GarminParseGmn,
-    garmin_parse_tcx::GarminParseTcx, garmin_parse_txt::GarminParseTxt,
+    garmin_parse_gpx::GarminParseGpx, garmin_parse_tcx::GarminParseTcx,
+    garmin_parse_txt::GarminParseTxt, parse_error::GarminParseError,
 };
 
 #[derive(Default, Debug)]
@@ -49,10 +55,14 @@ impl GarminParse {
         let filename = &gfile.filename;
         match gfile.laps.first() {
             Some(l) if l.lap_start == DateTimeWrapper::sentinel_datetime() => {
-                return Err(format_err!("{filename} has empty lap start?"));
+                return Err(GarminParseError::Malformed {
+                    format: "lap",
+                    message: format_sstr!("{filename} has empty lap start?"),
+                }
+                .into());
             }
             Some(_) => (),
-            None => return Err(format_err!("{filename} has no laps?")),
+            None => return Err(GarminParseError::NoLaps(filename.clone()).into()),
         };
         gfile.dump_avro(&cache_file)?;
         debug!("{filepath:?} Found md5sum {md5sum} success");
@@ -88,7 +98,7 @@ impl GarminParse {
                     }
                     Some(_) => (),
                     None => {
-                        return Err(format_err!("{input_file:?} {filename:?} has no laps?"));
+                        return Err(GarminParseError::NoLaps(filename.clone()).into());
                     }
                 };
                 gfile.dump_avro(&cache_file)?;
@@ -106,20 +116,28 @@ impl GarminParseTrait for GarminParse {
         filename: &Path,
         corr_map: &HashMap<(DateTimeWrapper, i32), GarminCorrectionLap>,
     ) -> Result<GarminFile, Error> {
-        match filename.extension().and_then(OsStr::to_str) {
+        let parse = || match filename.extension().and_then(OsStr::to_str) {
             Some("txt") => GarminParseTxt::new().with_file(filename, corr_map),
             Some("fit") => GarminParseFit::new().with_file(filename, corr_map),
             Some("tcx" | "TCX") => GarminParseTcx::new().with_file(filename, corr_map),
             Some("gmn") => GarminParseGmn::new().with_file(filename, corr_map),
-            Some("gz") => {
-                if filename.to_string_lossy().ends_with("tcx.gz") {
-                    GarminParseTcx::new().with_file(filename, corr_map)
-                } else {
-                    Err(format_err!("Invalid extension"))
-                }
+            Some("gpx" | "GPX") => GarminParseGpx::new().with_file(filename, corr_map),
+            Some("gz") if filename.to_string_lossy().ends_with("tcx.gz") => {
+                GarminParseTcx::new().with_file(filename, corr_map)
             }
-            _ => Err(format_err!("Invalid extension")),
-        }
+            _ => Err(GarminParseError::InvalidExtension(filename.to_path_buf()).into()),
+        };
+        // a malformed file must not take down the whole sync
+        catch_unwind(AssertUnwindSafe(parse)).unwrap_or_else(|payload| {
+            let message: StackString = if let Some(s) = payload.downcast_ref::<&str>() {
+                (*s).into()
+            } else if let Some(s) = payload.downcast_ref::<String>() {
+                s.as_str().into()
+            } else {
+                "unknown panic".into()
+            };
+            Err(GarminParseError::Panic(format_sstr!("{filename:?}: {message}")).into())
+        })
     }
 
     fn parse_file(&self, _: &Path) -> Result<ParseOutput, Error> {
@@ -156,6 +174,7 @@ mod tests {
     use anyhow::Error;
     use approx::assert_abs_diff_eq;
     use std::{
+        collections::HashMap,
         io::{stdout, Write},
         path::Path,
     };
@@ -167,6 +186,11 @@ mod tests {
     use crate::{
         garmin_parse::{GarminParse, GarminParseTrait},
         garmin_parse_fit,
+        garmin_parse_fit::GarminParseFit,
+        garmin_parse_gmn::GarminParseGmn,
+        garmin_parse_gpx::GarminParseGpx,
+        garmin_parse_tcx::GarminParseTcx,
+        parse_error::GarminParseError,
     };
 
     #[test]
@@ -178,9 +202,62 @@ mod tests {
             .with_file(&Path::new("invalid.invalid"), &corr_map)
             .unwrap_err();
         assert_eq!(format!("{}", err), "Invalid extension".to_string());
+        assert!(matches!(
+            err.downcast_ref::<GarminParseError>(),
+            Some(GarminParseError::InvalidExtension(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_input() -> Result<(), Error> {
+        let garbage = b"garbage that is not a gps file";
+        for err in [
+            GarminParseFit::parse_bytes(garbage).unwrap_err(),
+            GarminParseTcx::new().parse_bytes(garbage).unwrap_err(),
+            GarminParseTcx { is_gzip: true }
+                .parse_bytes(garbage)
+                .unwrap_err(),
+            GarminParseGmn::parse_xml("<root>").unwrap_err(),
+            GarminParseGpx::new().parse_bytes(garbage).unwrap_err(),
+        ] {
+            let err = err.downcast_ref::<GarminParseError>().unwrap();
+            assert!(err.is_malformed_input());
+        }
+        let corr_map = HashMap::new();
+        let err = GarminParse::new()
+            .with_file(Path::new("missing.fit"), &corr_map)
+            .unwrap_err();
+        let err = err.downcast_ref::<GarminParseError>().unwrap();
+        assert!(matches!(err, GarminParseError::MissingFile(_)));
+        assert!(!err.is_malformed_input());
         Ok(())
     }
 
+    #[test]
+    fn test_should_quarantine() {
+        let corr_map = HashMap::new();
+        let garbage = GarminParseFit::parse_bytes(b"garbage").unwrap_err();
+        assert!(GarminParseError::should_quarantine(&garbage));
+        let invalid_ext = GarminParse::new()
+            .with_file(Path::new("invalid.invalid"), &corr_map)
+            .unwrap_err();
+        assert!(GarminParseError::should_quarantine(&invalid_ext));
+
+        // the file may be fine, it just couldn't be read or written right now
+        let missing = GarminParse::new()
+            .with_file(Path::new("missing.fit"), &corr_map)
+            .unwrap_err();
+        assert!(!GarminParseError::should_quarantine(&missing));
+        let io_error = Error::from(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "cache dir not writable",
+        ));
+        assert!(!GarminParseError::should_quarantine(&io_error));
+        let missing_tool = Error::from(GarminParseError::MissingTool("garmin_dump"));
+        assert!(!GarminParseError::should_quarantine(&missing_tool));
+    }
+
     #[test]
     #[ignore]
     fn test_garmin_parse_parse_gmn() -> Result<(), Error> {
@@ -259,6 +336,21 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_garmin_parse_gpx() -> Result<(), Error> {
+        let gfile =
+            GarminParse::new().with_file(&Path::new("../tests/data/test.gpx"), &HashMap::new())?;
+        assert_eq!(gfile.filename.as_str(), "test.gpx");
+        assert_eq!(gfile.filetype.as_str(), "gpx");
+        assert_eq!(
+            convert_datetime_to_str(gfile.begin_datetime.into()),
+            "2014-01-12T16:00:05Z"
+        );
+        assert_eq!(gfile.laps.len(), 1);
+        assert_eq!(gfile.points.len(), 308);
+        Ok(())
+    }
+
     #[test]
     #[ignore]
     fn test_garmin_parse_fit() -> Result<(), Error> {
@@ -1,7 +1,18 @@
 use anyhow::{format_err, Error};
-use fitparser::{profile::field_types::MesgNum, Value};
+use fitparser::{
+    de::{FitObject, FitStreamProcessor},
+    profile::field_types::MesgNum,
+    FitDataField, FitDataRecord, Value,
+};
 use log::debug;
-use std::{collections::HashMap, fs::File, path::Path};
+use stack_string::{format_sstr, StackString};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+use time::OffsetDateTime;
 
 use garmin_lib::date_time_wrapper::DateTimeWrapper;
 use garmin_utils::sport_types::SportTypes;
@@ -13,7 +24,10 @@ use garmin_models::{
     garmin_point::GarminPoint,
 };
 
-use crate::garmin_parse::{GarminParseTrait, ParseOutput};
+use crate::{
+    garmin_parse::{GarminParseTrait, ParseOutput},
+    parse_error::GarminParseError,
+};
 
 #[derive(Debug, Default)]
 pub struct GarminParseFit {}
@@ -23,6 +37,185 @@ impl GarminParseFit {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Read every heart rate sample in `filename`, including records without
+    /// gps, along with the source of the samples: `chest_strap` if the file
+    /// was created by a heart rate monitor, `watch` otherwise
+    /// # Errors
+    /// Return error if the file cannot be read or parsed
+    pub fn read_heartrate_samples(
+        filename: &Path,
+    ) -> Result<(StackString, Vec<(OffsetDateTime, f64)>), Error> {
+        let mut source: StackString = "watch".into();
+        let mut samples = Vec::new();
+        for_each_record(filename, |record| match record.kind() {
+            MesgNum::Record => {
+                let point = GarminPoint::read_point_fit(record.fields());
+                if let Some(heart_rate) = point.heart_rate.filter(|hr| *hr > 0.0) {
+                    samples.push((point.time.into(), heart_rate));
+                }
+            }
+            MesgNum::DeviceInfo => {
+                if is_heartrate_monitor_creator(record.fields()) {
+                    source = "chest_strap".into();
+                }
+            }
+            _ => {}
+        })?;
+        samples.sort_by_key(|(t, _)| *t);
+        samples.shrink_to_fit();
+        Ok((source, samples))
+    }
+
+    /// Laps, points and sport of the contents of a fit file
+    /// # Errors
+    /// Return error if `buf` isn't valid fit data
+    pub fn parse_bytes(buf: &[u8]) -> Result<ParseOutput, Error> {
+        Self::parse_reader(buf)
+    }
+
+    fn parse_reader(reader: impl Read) -> Result<ParseOutput, Error> {
+        let mut lap_list = Vec::new();
+        let mut point_list = Vec::new();
+        let mut sport = SportTypes::None;
+
+        for_each_record_in(reader, |record| match record.kind() {
+            MesgNum::Record => {
+                let new_point = GarminPoint::read_point_fit(record.fields());
+                point_list.push(new_point);
+            }
+            MesgNum::Lap => {
+                let (new_lap, lap_sport) = GarminLap::read_lap_fit(record.fields());
+                if let Some(sp) = lap_sport {
+                    sport = sp;
+                }
+                lap_list.push(new_lap);
+            }
+            MesgNum::Session => {
+                for field in record.fields() {
+                    if field.name() == "sport" {
+                        if let Value::String(s) = field.value() {
+                            if let Ok(sp) = s.parse() {
+                                sport = sp;
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {
+                debug!("{:?}", record.kind());
+            }
+        })?;
+
+        GarminLap::fix_lap_number(&mut lap_list);
+        GarminPoint::retain_recorded(&mut point_list);
+        GarminPoint::calculate_durations(&mut point_list);
+        GarminLap::fill_power_from_points(&mut lap_list, &point_list);
+        GarminLap::fill_cadence_from_points(&mut lap_list, &point_list);
+        lap_list.shrink_to_fit();
+        point_list.shrink_to_fit();
+
+        Ok(ParseOutput {
+            lap_list,
+            point_list,
+            sport,
+        })
+    }
+}
+
+/// Bytes of a fit file read at a time
+const FIT_READ_CHUNK: usize = 64 * 1024;
+
+/// Decode `filename` one record at a time, see `for_each_record_in`
+fn for_each_record(filename: &Path, handle_record: impl FnMut(FitDataRecord)) -> Result<(), Error> {
+    if !filename.exists() {
+        return Err(GarminParseError::MissingFile(filename.to_path_buf()).into());
+    }
+    let reader = BufReader::new(File::open(filename)?);
+    for_each_record_in(reader, handle_record)
+}
+
+/// Decode `reader` one record at a time, only a chunk of the raw bytes and
+/// the record currently being handled are held in memory rather than the
+/// whole file and every decoded record of it (which for a multi-hour
+/// activity recorded every second is many times the size of the file)
+fn for_each_record_in(
+    mut reader: impl Read,
+    mut handle_record: impl FnMut(FitDataRecord),
+) -> Result<(), Error> {
+    let malformed = |message| GarminParseError::Malformed {
+        format: "fit",
+        message,
+    };
+    let mut processor = FitStreamProcessor::new();
+    let mut buf = Vec::with_capacity(FIT_READ_CHUNK);
+    // start of the bytes not decoded yet
+    let mut start = 0;
+    let mut eof = false;
+    loop {
+        if start == buf.len() {
+            if eof {
+                break;
+            }
+            buf.clear();
+            start = 0;
+            eof = read_chunk(&mut reader, &mut buf)?;
+            continue;
+        }
+        let decoded = processor
+            .deserialize_next(&buf[start..])
+            .map(|(rest, object)| (rest.len(), object));
+        let (consumed, object) = match decoded {
+            Ok((rest, object)) => (buf.len() - start - rest, object),
+            Err(e) if eof => return Err(malformed(format_sstr!("{e:?}")).into()),
+            // the object may continue past the bytes read so far
+            Err(_) => {
+                buf.drain(..start);
+                start = 0;
+                eof = read_chunk(&mut reader, &mut buf)?;
+                continue;
+            }
+        };
+        // truncated input could otherwise be read forever
+        if consumed == 0 {
+            return Err(malformed("no progress decoding".into()).into());
+        }
+        start += consumed;
+        match object {
+            // chained fit files restart with a new header after the crc
+            FitObject::Crc(_) => processor.reset(),
+            FitObject::DataMessage(message) => {
+                let record = processor
+                    .decode_message(message)
+                    .map_err(|e| malformed(format_sstr!("{e:?}")))?;
+                handle_record(record);
+            }
+            FitObject::Header(_) | FitObject::DefinitionMessage(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Append up to `FIT_READ_CHUNK` bytes of `reader` to `buf`, returns true
+/// once the end of `reader` is reached
+fn read_chunk(reader: &mut impl Read, buf: &mut Vec<u8>) -> Result<bool, Error> {
+    let read = reader
+        .by_ref()
+        .take(FIT_READ_CHUNK as u64)
+        .read_to_end(buf)?;
+    Ok(read < FIT_READ_CHUNK)
+}
+
+fn is_heartrate_monitor_creator(fields: &[FitDataField]) -> bool {
+    let is_creator = fields.iter().any(|field| {
+        field.name() == "device_index"
+            && matches!(field.value(), Value::String(s) if s == "creator")
+    });
+    let is_hrm = fields.iter().any(|field| {
+        field.name().ends_with("device_type")
+            && matches!(field.value(), Value::String(s) if s == "heart_rate")
+    });
+    is_creator && is_hrm
 }
 
 impl GarminParseTrait for GarminParseFit {
@@ -34,13 +227,15 @@ impl GarminParseTrait for GarminParseFit {
         let fit_output = self.parse_file(filename)?;
         let (lap_list, sport) =
             apply_lap_corrections(&fit_output.lap_list, fit_output.sport, corr_map);
-        let first_lap = lap_list.first().ok_or_else(|| format_err!("No laps"))?;
-        let filename = filename
+        let filename: StackString = filename
             .file_name()
             .ok_or_else(|| format_err!("filename {filename:?} has no path"))?
             .to_string_lossy()
             .to_string()
             .into();
+        let first_lap = lap_list
+            .first()
+            .ok_or_else(|| GarminParseError::NoLaps(filename.clone()))?;
         let gfile = GarminFile {
             filename,
             filetype: "fit".into(),
@@ -62,58 +257,9 @@ impl GarminParseTrait for GarminParseFit {
 
     fn parse_file(&self, filename: &Path) -> Result<ParseOutput, Error> {
         if !filename.exists() {
-            return Err(format_err!("file {filename:?} does not exist"));
+            return Err(GarminParseError::MissingFile(filename.to_path_buf()).into());
         }
-        let mut f = File::open(filename)?;
-        let records = fitparser::from_reader(&mut f).map_err(|e| format_err!("{e:?}"))?;
-
-        let mut lap_list = Vec::new();
-        let mut point_list = Vec::new();
-        let mut sport = SportTypes::None;
-
-        for record in records {
-            match record.kind() {
-                MesgNum::Record => {
-                    let new_point = GarminPoint::read_point_fit(record.fields());
-                    if new_point.latitude.is_some()
-                        && new_point.longitude.is_some()
-                        && new_point.distance > Some(0.0)
-                    {
-                        point_list.push(new_point);
-                    }
-                }
-                MesgNum::Lap => {
-                    let (new_lap, lap_sport) = GarminLap::read_lap_fit(record.fields());
-                    if let Some(sp) = lap_sport {
-                        sport = sp;
-                    }
-                    lap_list.push(new_lap);
-                }
-                MesgNum::Session => {
-                    for field in record.fields() {
-                        if field.name() == "sport" {
-                            if let Value::String(s) = field.value() {
-                                if let Ok(sp) = s.parse() {
-                                    sport = sp;
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => {
-                    debug!("{:?}", record.kind());
-                }
-            }
-        }
-
-        GarminLap::fix_lap_number(&mut lap_list);
-        GarminPoint::calculate_durations(&mut point_list);
-
-        Ok(ParseOutput {
-            lap_list,
-            point_list,
-            sport,
-        })
+        Self::parse_reader(BufReader::new(File::open(filename)?))
     }
 }
 
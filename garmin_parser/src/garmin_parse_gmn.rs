@@ -1,6 +1,6 @@
 use anyhow::{format_err, Error};
 use roxmltree::{Document, NodeType};
-use stack_string::format_sstr;
+use stack_string::{format_sstr, StackString};
 use std::{collections::HashMap, path::Path};
 use subprocess::{Exec, Redirection};
 
@@ -13,7 +13,12 @@ use garmin_models::{
 };
 use garmin_utils::sport_types::SportTypes;
 
-use super::garmin_parse::{GarminParseTrait, ParseOutput};
+use super::{
+    garmin_parse::{GarminParseTrait, ParseOutput},
+    parse_error::GarminParseError,
+};
+
+const GARMIN_DUMP: &str = "/usr/bin/garmin_dump";
 
 #[derive(Debug, Default)]
 pub struct GarminParseGmn {}
@@ -32,7 +37,7 @@ impl GarminParseTrait for GarminParseGmn {
         corr_map: &HashMap<(DateTimeWrapper, i32), GarminCorrectionLap>,
     ) -> Result<GarminFile, Error> {
         let gmn_output = self.parse_file(filename)?;
-        let filename = filename
+        let filename: StackString = filename
             .file_name()
             .ok_or_else(|| format_err!("filename {filename:?} has no path"))?
             .to_string_lossy()
@@ -40,7 +45,9 @@ impl GarminParseTrait for GarminParseGmn {
             .into();
         let (lap_list, sport) =
             apply_lap_corrections(&gmn_output.lap_list, gmn_output.sport, corr_map);
-        let first_lap = lap_list.first().ok_or_else(|| format_err!("No laps"))?;
+        let first_lap = lap_list
+            .first()
+            .ok_or_else(|| GarminParseError::NoLaps(filename.clone()))?;
         let gfile = GarminFile {
             filename,
             filetype: "gmn".into(),
@@ -61,20 +68,31 @@ impl GarminParseTrait for GarminParseGmn {
     }
 
     fn parse_file(&self, filename: &Path) -> Result<ParseOutput, Error> {
-        let filename = filename.to_string_lossy().to_string();
-        assert!(Path::new("/usr/bin/garmin_dump").exists());
-        let command = format_sstr!(
-            "echo \"{}\" `garmin_dump {}` \"{}\"",
-            "<root>",
-            filename,
-            "</root>"
-        );
-
-        let output = Exec::shell(command)
+        if !filename.exists() {
+            return Err(GarminParseError::MissingFile(filename.to_path_buf()).into());
+        }
+        if !Path::new(GARMIN_DUMP).exists() {
+            return Err(GarminParseError::MissingTool(GARMIN_DUMP).into());
+        }
+        let dump = Exec::cmd(GARMIN_DUMP)
+            .arg(filename)
             .stdout(Redirection::Pipe)
             .capture()?
             .stdout_str();
-        let doc = Document::parse(&output).map_err(|e| format_err!("{e}"))?;
+        Self::parse_xml(&format_sstr!("<root>{dump}</root>"))
+    }
+}
+
+impl GarminParseGmn {
+    /// Laps, points and sport of the xml written by `garmin_dump`, wrapped in
+    /// a single root element
+    /// # Errors
+    /// Return error if `output` isn't valid xml
+    pub fn parse_xml(output: &str) -> Result<ParseOutput, Error> {
+        let doc = Document::parse(output).map_err(|e| GarminParseError::Malformed {
+            format: "gmn",
+            message: format_sstr!("{e}"),
+        })?;
 
         let mut lap_list = Vec::new();
         let mut point_list = Vec::new();
@@ -0,0 +1,232 @@
+use anyhow::{format_err, Error};
+use roxmltree::{Document, Node};
+use stack_string::{format_sstr, StackString};
+use std::{collections::HashMap, fs::read, path::Path};
+use time::OffsetDateTime;
+
+use garmin_lib::date_time_wrapper::{iso8601::convert_datetime_to_str, DateTimeWrapper};
+use garmin_models::{
+    garmin_correction_lap::{apply_lap_corrections, GarminCorrectionLap},
+    garmin_file::GarminFile,
+    garmin_lap::GarminLap,
+    garmin_point::GarminPoint,
+};
+use garmin_utils::{garmin_util::haversine_distance, sport_types::SportTypes};
+
+use super::{
+    garmin_parse::{GarminParseTrait, ParseOutput},
+    parse_error::GarminParseError,
+};
+
+#[derive(Debug, Default)]
+pub struct GarminParseGpx {}
+
+impl GarminParseGpx {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl GarminParseTrait for GarminParseGpx {
+    fn with_file(
+        self,
+        filename: &Path,
+        corr_map: &HashMap<(DateTimeWrapper, i32), GarminCorrectionLap>,
+    ) -> Result<GarminFile, Error> {
+        let gpx_output = self.parse_file(filename)?;
+        let (lap_list, sport) =
+            apply_lap_corrections(&gpx_output.lap_list, gpx_output.sport, corr_map);
+        let filename: StackString = filename
+            .file_name()
+            .ok_or_else(|| format_err!("filename {filename:?} has no path"))?
+            .to_string_lossy()
+            .to_string()
+            .into();
+        let first_lap = lap_list
+            .first()
+            .ok_or_else(|| GarminParseError::NoLaps(filename.clone()))?;
+        let gfile = GarminFile {
+            filename,
+            filetype: "gpx".into(),
+            begin_datetime: first_lap.lap_start,
+            sport,
+            total_calories: lap_list.iter().map(|lap| lap.lap_calories).sum(),
+            total_distance: lap_list.iter().map(|lap| lap.lap_distance).sum(),
+            total_duration: lap_list.iter().map(|lap| lap.lap_duration).sum(),
+            total_hr_dur: lap_list
+                .iter()
+                .map(|lap| lap.lap_avg_hr.unwrap_or(0.0) * lap.lap_duration)
+                .sum(),
+            total_hr_dis: lap_list.iter().map(|lap| lap.lap_duration).sum(),
+            laps: lap_list,
+            points: gpx_output.point_list,
+        };
+        Ok(gfile)
+    }
+
+    fn parse_file(&self, filename: &Path) -> Result<ParseOutput, Error> {
+        if !filename.exists() {
+            return Err(GarminParseError::MissingFile(filename.to_path_buf()).into());
+        }
+        self.parse_bytes(&read(filename)?)
+    }
+}
+
+impl GarminParseGpx {
+    /// Points of every track of a gpx file, gpx has no laps so each track
+    /// segment becomes a lap, the sport comes from the track `type`
+    /// # Errors
+    /// Return error if `buf` isn't valid gpx xml
+    pub fn parse_bytes(&self, buf: &[u8]) -> Result<ParseOutput, Error> {
+        let malformed = |message| GarminParseError::Malformed {
+            format: "gpx",
+            message,
+        };
+        let output = std::str::from_utf8(buf).map_err(|e| malformed(format_sstr!("{e}")))?;
+        let doc = Document::parse(output).map_err(|e| malformed(format_sstr!("{e}")))?;
+
+        let mut lap_list = Vec::new();
+        let mut point_list = Vec::new();
+        let mut sport = SportTypes::None;
+        let mut distance = 0.0;
+        let mut last_position: Option<(f64, f64)> = None;
+
+        for trk in doc.descendants().filter(|d| d.has_tag_name("trk")) {
+            if let Some(s) = child_text(&trk, "type").and_then(|t| t.parse().ok()) {
+                sport = s;
+            }
+            for trkseg in trk.children().filter(|d| d.has_tag_name("trkseg")) {
+                let mut segment = Vec::new();
+                for trkpt in trkseg.children().filter(|d| d.has_tag_name("trkpt")) {
+                    let mut point = GarminPoint::read_point_gpx(&trkpt)?;
+                    if let (Some(lat), Some(lon)) = (point.latitude, point.longitude) {
+                        if let Some((last_lat, last_lon)) = last_position.replace((lat, lon)) {
+                            distance += haversine_distance(last_lat, last_lon, lat, lon);
+                        }
+                        point.distance = Some(distance);
+                    }
+                    segment.push(point);
+                }
+                if let Some(lap) = lap_from_points(&segment) {
+                    lap_list.push(lap);
+                }
+                point_list.extend(segment);
+            }
+        }
+
+        GarminLap::fix_lap_number(&mut lap_list);
+        GarminPoint::calculate_durations(&mut point_list);
+
+        Ok(ParseOutput {
+            lap_list,
+            point_list,
+            sport,
+        })
+    }
+}
+
+fn child_text<'a>(node: &Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|c| c.has_tag_name(name))
+        .and_then(|c| c.text())
+        .map(str::trim)
+}
+
+fn lap_from_points(points: &[GarminPoint]) -> Option<GarminLap> {
+    let first = points.first()?;
+    let last = points.last()?;
+    let start: OffsetDateTime = first.time.into();
+    let end: OffsetDateTime = last.time.into();
+    let heart_rates: Vec<f64> = points.iter().filter_map(|p| p.heart_rate).collect();
+    let mut lap = GarminLap::new();
+    lap.lap_start = first.time;
+    lap.lap_start_string = Some(convert_datetime_to_str(start));
+    lap.lap_duration = (end - start).as_seconds_f64();
+    lap.lap_distance = last.distance.unwrap_or(0.0) - first.distance.unwrap_or(0.0);
+    lap.lap_max_speed = points
+        .iter()
+        .map(|p| p.speed_mps)
+        .filter(|s| *s > 0.0)
+        .reduce(f64::max);
+    if !heart_rates.is_empty() {
+        lap.lap_avg_hr = Some(heart_rates.iter().sum::<f64>() / heart_rates.len() as f64);
+        lap.lap_max_hr = heart_rates
+            .iter()
+            .copied()
+            .reduce(f64::max)
+            .map(|hr| hr as i32);
+    }
+    Some(lap)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use approx::assert_abs_diff_eq;
+    use std::{collections::HashMap, path::Path};
+
+    use garmin_lib::date_time_wrapper::iso8601::convert_datetime_to_str;
+    use garmin_utils::sport_types::SportTypes;
+
+    use crate::{garmin_parse::GarminParseTrait, garmin_parse_gpx::GarminParseGpx};
+
+    #[test]
+    fn test_garmin_parse_gpx() -> Result<(), Error> {
+        let gfile = GarminParseGpx::new()
+            .with_file(Path::new("../tests/data/test.gpx"), &HashMap::new())?;
+        assert_eq!(gfile.filename, "test.gpx");
+        assert_eq!(gfile.filetype, "gpx");
+        assert_eq!(gfile.sport, SportTypes::None);
+        assert_eq!(
+            convert_datetime_to_str(gfile.begin_datetime.into()),
+            "2014-01-12T16:00:05Z"
+        );
+        assert_eq!(gfile.laps.len(), 1);
+        assert_eq!(gfile.points.len(), 308);
+        assert!(gfile.total_distance > 0.0);
+        assert_abs_diff_eq!(
+            gfile.total_distance,
+            gfile.points.last().and_then(|p| p.distance).unwrap_or(0.0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_polar_gpx() -> Result<(), Error> {
+        let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="Polar Flow" xmlns="http://www.topografix.com/GPX/1/1"
+  xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1"
+  xmlns:gpxdata="http://www.cluetrust.com/XML/GPXDATA/1/0">
+  <trk>
+    <type>running</type>
+    <trkseg>
+      <trkpt lat="40.7000" lon="-73.1500">
+        <time>2023-05-01T10:00:00Z</time>
+        <extensions>
+          <gpxtpx:TrackPointExtension><gpxtpx:hr>140</gpxtpx:hr></gpxtpx:TrackPointExtension>
+        </extensions>
+      </trkpt>
+      <trkpt lat="40.7010" lon="-73.1500">
+        <time>2023-05-01T10:00:30Z</time>
+        <extensions>
+          <gpxdata:hr>150</gpxdata:hr>
+          <gpxdata:cadence>86</gpxdata:cadence>
+        </extensions>
+      </trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+        let output = GarminParseGpx::new().parse_bytes(gpx.as_bytes())?;
+        assert_eq!(output.sport, SportTypes::Running);
+        assert_eq!(output.lap_list.len(), 1);
+        let lap = &output.lap_list[0];
+        assert_abs_diff_eq!(lap.lap_duration, 30.0);
+        assert_abs_diff_eq!(lap.lap_distance, 111.19, epsilon = 0.01);
+        assert_eq!(lap.lap_avg_hr, Some(145.0));
+        assert_eq!(lap.lap_max_hr, Some(150));
+        assert_eq!(output.point_list[0].heart_rate, Some(140.0));
+        assert_eq!(output.point_list[1].cadence, Some(86.0));
+        Ok(())
+    }
+}
@@ -1,12 +1,13 @@
 use anyhow::{format_err, Error};
 use flate2::read::GzDecoder;
 use roxmltree::{Document, NodeType};
+use stack_string::{format_sstr, StackString};
 use std::{
     collections::HashMap,
     ffi::OsStr,
-    fs::{read_to_string, File},
+    fs::{read, write},
     io::Read,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use garmin_lib::date_time_wrapper::DateTimeWrapper;
@@ -18,7 +19,12 @@ use garmin_models::{
 };
 use garmin_utils::sport_types::SportTypes;
 
-use super::garmin_parse::{GarminParseTrait, ParseOutput};
+use super::{
+    garmin_parse::{GarminParseTrait, ParseOutput},
+    parse_error::GarminParseError,
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
 #[derive(Debug, Default)]
 pub struct GarminParseTcx {
@@ -42,13 +48,15 @@ impl GarminParseTrait for GarminParseTcx {
         let tcx_output = self.parse_file(filename)?;
         let (lap_list, sport) =
             apply_lap_corrections(&tcx_output.lap_list, tcx_output.sport, corr_map);
-        let first_lap = lap_list.first().ok_or_else(|| format_err!("No laps"))?;
-        let filename = filename
+        let filename: StackString = filename
             .file_name()
             .ok_or_else(|| format_err!("filename {filename:?} has no path"))?
             .to_string_lossy()
             .to_string()
             .into();
+        let first_lap = lap_list
+            .first()
+            .ok_or_else(|| GarminParseError::NoLaps(filename.clone()))?;
         let gfile = GarminFile {
             filename,
             filetype: "tcx".into(),
@@ -70,16 +78,20 @@ impl GarminParseTrait for GarminParseTcx {
 
     fn parse_file(&self, filename: &Path) -> Result<ParseOutput, Error> {
         if !filename.exists() {
-            return Err(format_err!("file {filename:?} does not exist"));
+            return Err(GarminParseError::MissingFile(filename.to_path_buf()).into());
         }
-        let output = if self.is_gzip {
-            let mut buf = String::new();
-            GzDecoder::new(File::open(filename)?).read_to_string(&mut buf)?;
-            buf
-        } else {
-            read_to_string(filename)?
-        };
-        let doc = Document::parse(&output).map_err(|e| format_err!("{e}"))?;
+        self.parse_bytes(&read(filename)?)
+    }
+}
+
+impl GarminParseTcx {
+    /// Laps, points and sport of the contents of a tcx file, gzipped when
+    /// `is_gzip` is set or `buf` starts with the gzip magic bytes
+    /// # Errors
+    /// Return error if `buf` isn't valid (gzipped) tcx xml
+    pub fn parse_bytes(&self, buf: &[u8]) -> Result<ParseOutput, Error> {
+        let output = self.read_xml(buf)?;
+        let doc = Document::parse(&output).map_err(|e| malformed(format_sstr!("{e}")))?;
 
         let mut lap_list = Vec::new();
         let mut point_list = Vec::new();
@@ -99,17 +111,15 @@ impl GarminParseTrait for GarminParseTcx {
             }
             if d.node_type() == NodeType::Element && d.tag_name().name() == "Trackpoint" {
                 let new_point = GarminPoint::read_point_tcx(&d)?;
-                if new_point.latitude.is_some()
-                    && new_point.longitude.is_some()
-                    && new_point.distance > Some(0.0)
-                {
-                    point_list.push(new_point);
-                }
+                point_list.push(new_point);
             }
         }
 
         GarminLap::fix_lap_number(&mut lap_list);
+        GarminPoint::retain_recorded(&mut point_list);
         GarminPoint::calculate_durations(&mut point_list);
+        GarminLap::fill_power_from_points(&mut lap_list, &point_list);
+        GarminLap::fill_cadence_from_points(&mut lap_list, &point_list);
 
         Ok(ParseOutput {
             lap_list,
@@ -117,6 +127,100 @@ impl GarminParseTrait for GarminParseTcx {
             sport,
         })
     }
+
+    /// Standalone tcx documents, one per `Activity` element of `buf`, each
+    /// keeping everything outside of the activities (namespaces, `Author`,
+    /// ...) of the original
+    /// # Errors
+    /// Return error if `buf` isn't valid (gzipped) tcx xml
+    pub fn split_activities(&self, buf: &[u8]) -> Result<Vec<String>, Error> {
+        let output = self.read_xml(buf)?;
+        let doc = Document::parse(&output).map_err(|e| malformed(format_sstr!("{e}")))?;
+        let ranges: Vec<_> = doc
+            .descendants()
+            .filter(|d| {
+                d.is_element()
+                    && d.tag_name().name() == "Activity"
+                    && d.parent_element().map(|p| p.tag_name().name()) == Some("Activities")
+            })
+            .map(|d| d.range())
+            .collect();
+        match (ranges.first(), ranges.last()) {
+            (Some(first), Some(last)) if ranges.len() > 1 => {
+                let prefix = &output[..first.start];
+                let suffix = &output[last.end..];
+                Ok(ranges
+                    .iter()
+                    .map(|r| {
+                        let activity = &output[r.clone()];
+                        format!("{prefix}{activity}{suffix}")
+                    })
+                    .collect())
+            }
+            _ => Ok(vec![output]),
+        }
+    }
+
+    /// Split a tcx file holding several activities into one file per
+    /// activity in `outdir`, a file with a single activity is returned as is
+    /// # Errors
+    /// Return error if reading, parsing or writing the files fails
+    pub fn split_file(filename: &Path, outdir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let is_gzip = filename.extension().and_then(OsStr::to_str) == Some("gz");
+        let activities = Self { is_gzip }.split_activities(&read(filename)?)?;
+        if activities.len() < 2 {
+            return Ok(vec![filename.to_path_buf()]);
+        }
+        let name = filename
+            .file_name()
+            .ok_or_else(|| format_err!("filename {filename:?} has no path"))?
+            .to_string_lossy();
+        activities
+            .into_iter()
+            .enumerate()
+            .map(|(index, activity)| {
+                let outfile = outdir.join(activity_filename(&name, index).as_str());
+                write(&outfile, activity)?;
+                Ok(outfile)
+            })
+            .collect()
+    }
+
+    fn read_xml(&self, buf: &[u8]) -> Result<String, Error> {
+        let output = if self.is_gzip || buf.starts_with(&GZIP_MAGIC) {
+            let mut output = String::new();
+            GzDecoder::new(buf)
+                .read_to_string(&mut output)
+                .map_err(|e| malformed(format_sstr!("{e}")))?;
+            output
+        } else {
+            String::from_utf8(buf.to_vec()).map_err(|e| malformed(format_sstr!("{e}")))?
+        };
+        Ok(output)
+    }
+}
+
+fn malformed(message: StackString) -> GarminParseError {
+    GarminParseError::Malformed {
+        format: "tcx",
+        message,
+    }
+}
+
+/// Name of the `index`th (zero based) activity split out of `filename`,
+/// e.g. `export.tcx.gz` gives `export_1.tcx`, `export_2.tcx`, ...
+#[must_use]
+pub fn activity_filename(filename: &str, index: usize) -> StackString {
+    let lower = filename.to_lowercase();
+    let stem = if lower.ends_with(".tcx.gz") {
+        &filename[..filename.len() - 7]
+    } else if lower.ends_with(".tcx") {
+        &filename[..filename.len() - 4]
+    } else {
+        filename
+    };
+    let index = index + 1;
+    format_sstr!("{stem}_{index}.tcx")
 }
 
 #[cfg(test)]
@@ -129,7 +233,38 @@ mod tests {
     use garmin_models::garmin_correction_lap::GarminCorrectionLap;
     use garmin_utils::sport_types::SportTypes;
 
-    use crate::{garmin_parse::GarminParseTrait, garmin_parse_tcx};
+    use crate::{
+        garmin_parse::GarminParseTrait,
+        garmin_parse_tcx::{self, activity_filename, GarminParseTcx},
+    };
+
+    const TWO_ACTIVITIES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+  <Activities>
+    <Activity Sport="Running">
+      <Id>2012-11-05T11:52:21Z</Id>
+      <Lap StartTime="2012-11-05T11:52:21Z">
+        <TotalTimeSeconds>600.0</TotalTimeSeconds>
+        <DistanceMeters>2000.0</DistanceMeters>
+        <Calories>150</Calories>
+      </Lap>
+    </Activity>
+    <Activity Sport="Biking">
+      <Id>2012-11-05T14:00:00Z</Id>
+      <Lap StartTime="2012-11-05T14:00:00Z">
+        <TotalTimeSeconds>1200.0</TotalTimeSeconds>
+        <DistanceMeters>8000.0</DistanceMeters>
+        <Calories>250</Calories>
+      </Lap>
+      <Lap StartTime="2012-11-05T14:20:00Z">
+        <TotalTimeSeconds>1200.0</TotalTimeSeconds>
+        <DistanceMeters>8000.0</DistanceMeters>
+        <Calories>250</Calories>
+      </Lap>
+    </Activity>
+  </Activities>
+  <Author><Name>test</Name></Author>
+</TrainingCenterDatabase>"#;
 
     #[test]
     #[ignore]
@@ -184,4 +319,52 @@ mod tests {
         assert_abs_diff_eq!(gfile.total_hr_dis, 1037.53);
         Ok(())
     }
+
+    #[test]
+    fn test_split_activities() -> Result<(), Error> {
+        let parser = GarminParseTcx::new();
+        let activities = parser.split_activities(TWO_ACTIVITIES.as_bytes())?;
+        assert_eq!(activities.len(), 2);
+        for activity in &activities {
+            assert!(activity.contains("<Author><Name>test</Name></Author>"));
+        }
+
+        let first = parser.parse_bytes(activities[0].as_bytes())?;
+        assert_eq!(first.sport, SportTypes::Running);
+        assert_eq!(first.lap_list.len(), 1);
+        assert_abs_diff_eq!(first.lap_list[0].lap_distance, 2000.0);
+
+        let second = parser.parse_bytes(activities[1].as_bytes())?;
+        assert_eq!(second.sport, SportTypes::Biking);
+        assert_eq!(second.lap_list.len(), 2);
+        assert_eq!(
+            convert_datetime_to_str(second.lap_list[0].lap_start.into()),
+            "2012-11-05T14:00:00Z"
+        );
+
+        let single = parser.split_activities(activities[0].as_bytes())?;
+        assert_eq!(single, vec![activities[0].clone()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_file() -> Result<(), Error> {
+        let tempdir = tempfile::TempDir::new()?;
+        let filename = tempdir.path().join("export.tcx");
+        std::fs::write(&filename, TWO_ACTIVITIES)?;
+        let outdir = tempdir.path().join("split");
+        std::fs::create_dir(&outdir)?;
+
+        let files = GarminParseTcx::split_file(&filename, &outdir)?;
+        assert_eq!(
+            files,
+            vec![outdir.join("export_1.tcx"), outdir.join("export_2.tcx")]
+        );
+        let again = GarminParseTcx::split_file(&files[0], &outdir)?;
+        assert_eq!(again, vec![files[0].clone()]);
+
+        assert_eq!(activity_filename("export.TCX", 0), "export_1.tcx");
+        assert_eq!(activity_filename("export.tcx.gz", 2), "export_3.tcx");
+        Ok(())
+    }
 }
@@ -21,7 +21,7 @@ use garmin_models::{
 };
 use garmin_utils::{
     garmin_util::{convert_time_string, METERS_PER_MILE},
-    sport_types::{get_sport_type_map, SportTypes},
+    sport_types::SportTypes,
 };
 
 use super::garmin_parse::{GarminParseTrait, ParseOutput};
@@ -174,6 +174,10 @@ impl GarminParseTrait for GarminParseTxt {
                     speed_mph: *speed_mph,
                     avg_speed_value_permi: *avg_speed_value_permi,
                     avg_speed_value_mph: *avg_speed_value_mph,
+                    power: None,
+                    respiration_rate: None,
+                    temperature: None,
+                    cadence: None,
                 },
             )
             .collect();
@@ -209,8 +213,6 @@ impl GarminParseTxt {
     }
 
     fn parse_line(line: &str) -> Result<GarminLap, Error> {
-        let sport_type_map = get_sport_type_map();
-
         let mut entry_dict: HashMap<_, _> = line
             .split_whitespace()
             .filter_map(|x| {
@@ -241,7 +243,7 @@ impl GarminParseTxt {
         let lap_start = PrimitiveDateTime::new(date, time).assume_utc().into();
 
         let lap_type = match entry_dict.get("type") {
-            Some(val) => sport_type_map.get(val.as_str()).map(|_| val.into()),
+            Some(val) => val.parse::<SportTypes>().ok().map(|_| val.into()),
             None => None,
         };
 
@@ -300,6 +302,10 @@ impl GarminParseTxt {
             lap_intensity: None,
             lap_number,
             lap_start_string: None,
+            lap_avg_power: None,
+            lap_normalized_power: None,
+            lap_avg_cadence: None,
+            lap_max_cadence: None,
         })
     }
 }
@@ -7,8 +7,14 @@
 #![allow(clippy::similar_names)]
 #![allow(clippy::unsafe_derive_deserialize)]
 
+pub mod demo_data;
+pub mod garmin_export_fit;
+pub mod garmin_export_gpx;
+pub mod garmin_file_diff;
 pub mod garmin_parse;
 pub mod garmin_parse_fit;
 pub mod garmin_parse_gmn;
+pub mod garmin_parse_gpx;
 pub mod garmin_parse_tcx;
 pub mod garmin_parse_txt;
+pub mod parse_error;
@@ -0,0 +1,51 @@
+use stack_string::StackString;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Reason a GPS file couldn't be parsed, returned inside `anyhow::Error` so
+/// callers can downcast it to tell bad input apart from other failures
+#[derive(Error, Debug)]
+pub enum GarminParseError {
+    #[error("file {0:?} does not exist")]
+    MissingFile(PathBuf),
+    #[error("Invalid extension")]
+    InvalidExtension(PathBuf),
+    #[error("{0} is required to parse gmn files")]
+    MissingTool(&'static str),
+    #[error("malformed {format} input: {message}")]
+    Malformed {
+        format: &'static str,
+        message: StackString,
+    },
+    #[error("{0} has no laps")]
+    NoLaps(StackString),
+    #[error("parser panicked: {0}")]
+    Panic(StackString),
+}
+
+impl GarminParseError {
+    /// The input itself is bad (or crashes a parser), such files are worth
+    /// adding to the fuzz corpus
+    #[must_use]
+    pub fn is_malformed_input(&self) -> bool {
+        matches!(
+            self,
+            Self::Malformed { .. } | Self::NoLaps(_) | Self::Panic(_)
+        )
+    }
+
+    /// The file can't ever be parsed as it is, as opposed to a missing tool
+    /// or file which may work on retry, only such files are quarantined
+    #[must_use]
+    pub fn is_unparseable_file(&self) -> bool {
+        self.is_malformed_input() || matches!(self, Self::InvalidExtension(_))
+    }
+
+    /// Whether `error` is a `GarminParseError` for an unparseable file
+    #[must_use]
+    pub fn should_quarantine(error: &anyhow::Error) -> bool {
+        error
+            .downcast_ref::<Self>()
+            .is_some_and(Self::is_unparseable_file)
+    }
+}
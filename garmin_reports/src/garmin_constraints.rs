@@ -3,11 +3,17 @@ use itertools::Itertools;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use stack_string::{format_sstr, StackString};
-use time::{format_description::well_known::Rfc3339, macros::format_description, OffsetDateTime};
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, Month,
+    OffsetDateTime,
+};
 use time_tz::{timezones::db::UTC, OffsetDateTimeExt};
 
-use garmin_lib::garmin_config::GarminConfig;
-use garmin_utils::sport_types::get_sport_type_map;
+use garmin_lib::{
+    garmin_config::GarminConfig, split_distance::SplitDistance, week_start::WeekStart,
+};
+use garmin_models::{demo_anonymizer::DemoAnonymizer, surface_type::SurfaceType};
+use garmin_utils::{plot_opts::PlotXAxis, sport_types::SportTypes};
 
 use crate::garmin_report_options::{GarminReportAgg, GarminReportOptions};
 
@@ -39,15 +45,19 @@ impl Default for GarminConstraint {
 }
 
 impl GarminConstraint {
-    fn to_query_string(&self) -> StackString {
+    fn to_query_string(&self, week_start: WeekStart) -> StackString {
         match self {
             Self::Latest => {
                 "a.begin_datetime=(select max(begin_datetime) from garmin_summary)".into()
             }
             Self::IsoWeek { year, week } => {
+                let dt = format_sstr!(
+                    "a.begin_datetime at time zone 'localtime'{}",
+                    week_start.sql_shift()
+                );
                 format_sstr!(
-                    "(EXTRACT(isoyear from a.begin_datetime at time zone 'localtime') = {year} AND
-                      EXTRACT(week from a.begin_datetime at time zone 'localtime') = {week})"
+                    "(EXTRACT(isoyear from {dt}) = {year} AND
+                      EXTRACT(week from {dt}) = {week})"
                 )
             }
             Self::Filename(filename) => format_sstr!("filename = '{}'", filename),
@@ -151,11 +161,47 @@ impl GarminConstraint {
             Self::Query(pat.into())
         }
     }
+
+    /// Real period of a constraint on the shifted demo pages
+    #[must_use]
+    pub fn unshift(self, anonymizer: &DemoAnonymizer) -> Self {
+        match self {
+            Self::IsoWeek { year, week } => {
+                let (year, week) = anonymizer.unshift_year_week(year, week);
+                Self::IsoWeek { year, week }
+            }
+            Self::DateTime(dt) => Self::DateTime(anonymizer.unshift_datetime(dt)),
+            Self::YearMonthDay { year, month, day } => {
+                match Month::try_from(month)
+                    .ok()
+                    .and_then(|m| Date::from_calendar_date(year, m, day).ok())
+                {
+                    Some(date) => {
+                        let date = anonymizer.unshift_date(date);
+                        Self::YearMonthDay {
+                            year: date.year(),
+                            month: date.month().into(),
+                            day: date.day(),
+                        }
+                    }
+                    None => Self::YearMonthDay { year, month, day },
+                }
+            }
+            Self::YearMonth { year, month } => {
+                let (year, month) = anonymizer.unshift_year_month(year, month);
+                Self::YearMonth { year, month }
+            }
+            Self::Year(year) => Self::Year(anonymizer.unshift_year(year)),
+            constraint => constraint,
+        }
+    }
 }
 
 #[derive(Default, Debug, Deref)]
 pub struct GarminConstraints {
+    #[deref]
     pub constraints: Vec<GarminConstraint>,
+    pub week_start: WeekStart,
 }
 
 impl GarminConstraints {
@@ -163,7 +209,7 @@ impl GarminConstraints {
     pub fn to_query_string(&self) -> String {
         self.constraints
             .iter()
-            .map(GarminConstraint::to_query_string)
+            .map(|c| c.to_query_string(self.week_start))
             .join(" OR ")
     }
 
@@ -177,8 +223,10 @@ impl GarminConstraints {
         U: AsRef<str>,
     {
         let mut options = GarminReportOptions::new();
-
-        let sport_type_map = get_sport_type_map();
+        options.week_start = config.week_start;
+        options.heart_rate_profile = config.heart_rate_profile();
+        options.split_distance = config.split_distance;
+        options.exclude_commutes = config.exclude_commutes;
 
         for pattern in patterns {
             match pattern.as_ref() {
@@ -189,9 +237,24 @@ impl GarminConstraints {
                 "file" => options.agg = Some(GarminReportAgg::File),
                 "sport" => options.do_sport = None,
                 "latest" => self.constraints.push(GarminConstraint::default()),
+                "monday" => options.week_start = WeekStart::Monday,
+                "sunday" => options.week_start = WeekStart::Sunday,
+                "distance" => options.xaxis = Some(PlotXAxis::Distance),
+                "time" => options.xaxis = Some(PlotXAxis::Time),
+                "difficulty" => options.sort_by_difficulty = true,
+                "commutes" => options.exclude_commutes = false,
                 pat => {
-                    if let Some(x) = sport_type_map.get(pat) {
-                        options.do_sport = Some(*x);
+                    if let Some(location) = pat.strip_prefix("location:") {
+                        options.location = Some(location.into());
+                    } else if let Some(split) = pat
+                        .strip_prefix("split:")
+                        .and_then(|s| s.parse::<SplitDistance>().ok())
+                    {
+                        options.split_distance = split;
+                    } else if let Ok(x) = pat.parse::<SportTypes>() {
+                        options.do_sport = Some(x);
+                    } else if let Ok(x) = pat.parse::<SurfaceType>() {
+                        options.surface = Some(x);
                     } else {
                         self.constraints
                             .push(GarminConstraint::match_pattern(config, pat));
@@ -199,8 +262,16 @@ impl GarminConstraints {
                 }
             };
         }
+        self.week_start = options.week_start;
         options
     }
+
+    /// Move the periods of a filter on the demo pages back to the real ones
+    pub fn unshift_demo(&mut self, anonymizer: &DemoAnonymizer) {
+        for constraint in &mut self.constraints {
+            *constraint = constraint.clone().unshift(anonymizer);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -208,15 +279,19 @@ mod tests {
     use anyhow::Error;
     use time::macros::datetime;
 
-    use garmin_lib::garmin_config::GarminConfig;
+    use garmin_lib::{
+        garmin_config::GarminConfig, split_distance::SplitDistance, week_start::WeekStart,
+    };
+    use garmin_models::{demo_anonymizer::DemoAnonymizer, surface_type::SurfaceType};
+    use garmin_utils::plot_opts::PlotXAxis;
 
-    use crate::garmin_constraints::GarminConstraint;
+    use crate::garmin_constraints::{GarminConstraint, GarminConstraints};
 
     #[test]
     fn test_garmin_constraints() -> Result<(), Error> {
         let dt = datetime!(2019-02-09 13:06:13 +00:00);
         let cs = GarminConstraint::DateTime(dt);
-        let obs = cs.to_query_string();
+        let obs = cs.to_query_string(WeekStart::Monday);
         println!("{}", obs);
         let exp = "replace(to_char(a.begin_datetime at time zone 'utc', \
                    'YYYY-MM-DD%HH24:MI:SSZ'), '%', 'T') = '2019-02-09T13:06:13Z'";
@@ -224,6 +299,58 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_week_start_constraint() -> Result<(), Error> {
+        let cs = GarminConstraint::IsoWeek {
+            year: 2023,
+            week: 2,
+        };
+        let obs = cs.to_query_string(WeekStart::Sunday);
+        assert!(obs.contains("at time zone 'localtime' + interval '1 day') = 2023"));
+        let obs = cs.to_query_string(WeekStart::Monday);
+        assert!(!obs.contains("interval"));
+
+        let config = GarminConfig::default();
+        let mut constraints = GarminConstraints::default();
+        let options = constraints.process_pattern(&config, ["week", "sunday"]);
+        assert_eq!(options.week_start, WeekStart::Sunday);
+        assert_eq!(constraints.week_start, WeekStart::Sunday);
+        assert_eq!(options.xaxis, None);
+        assert!(options.exclude_commutes);
+
+        let options = constraints.process_pattern(&config, ["week", "commutes"]);
+        assert!(!options.exclude_commutes);
+        Ok(())
+    }
+
+    #[test]
+    fn test_xaxis_keyword() -> Result<(), Error> {
+        let config = GarminConfig::default();
+        let mut constraints = GarminConstraints::default();
+        let options = constraints.process_pattern(&config, ["2023-01-07", "time"]);
+        assert_eq!(options.xaxis, Some(PlotXAxis::Time));
+        assert_eq!(constraints.len(), 1);
+        assert!(!options.sort_by_difficulty);
+
+        let mut constraints = GarminConstraints::default();
+        let options = constraints.process_pattern(&config, ["file", "difficulty", "trail"]);
+        assert!(options.sort_by_difficulty);
+        assert_eq!(options.surface, Some(SurfaceType::Trail));
+        assert!(constraints.is_empty());
+
+        let mut constraints = GarminConstraints::default();
+        let options = constraints.process_pattern(&config, ["location:Boston", "2024"]);
+        assert_eq!(options.location.as_deref(), Some("Boston"));
+        assert_eq!(constraints.len(), 1);
+        assert_eq!(options.split_distance, SplitDistance::MILE);
+
+        let mut constraints = GarminConstraints::default();
+        let options = constraints.process_pattern(&config, ["split:400m", "2024-01-07"]);
+        assert_eq!(options.split_distance, "400m".parse()?);
+        assert_eq!(constraints.len(), 1);
+        Ok(())
+    }
+
     #[test]
     fn test_patterns() -> Result<(), Error> {
         let config = GarminConfig::get_config(None)?;
@@ -258,4 +385,42 @@ mod tests {
         assert_eq!(result, GarminConstraint::DateTime(expected));
         Ok(())
     }
+
+    #[test]
+    fn test_unshift_demo() -> Result<(), Error> {
+        let config = GarminConfig::default();
+        let anonymizer = DemoAnonymizer::from_config(&config).unwrap();
+        let mut constraints = GarminConstraints::default();
+        constraints.process_pattern(
+            &config,
+            ["2023w18", "2023-05-06", "2023-05", "2023", "latest"],
+        );
+        constraints.unshift_demo(&anonymizer);
+        assert_eq!(
+            constraints.constraints,
+            vec![
+                GarminConstraint::IsoWeek {
+                    year: 2024,
+                    week: 18
+                },
+                GarminConstraint::YearMonthDay {
+                    year: 2024,
+                    month: 5,
+                    day: 4
+                },
+                GarminConstraint::YearMonth {
+                    year: 2024,
+                    month: 5
+                },
+                GarminConstraint::Year(2024),
+                GarminConstraint::Latest,
+            ]
+        );
+        let result = GarminConstraint::match_pattern(&config, "2023-05-06T13:00:00Z");
+        assert_eq!(
+            result.unshift(&anonymizer),
+            GarminConstraint::DateTime(datetime!(2024-05-04 13:00:00 +00:00))
+        );
+        Ok(())
+    }
 }
@@ -3,15 +3,28 @@ use itertools::Itertools;
 use log::debug;
 use stack_string::{format_sstr, StackString};
 
-use garmin_models::{garmin_file::GarminFile, garmin_lap::GarminLap};
+use garmin_lib::split_distance::SplitDistance;
+use garmin_models::{
+    garmin_file::GarminFile,
+    garmin_lap::GarminLap,
+    power_curve::activity_power,
+    split_differential::{format_split_differential, split_differential},
+};
 use garmin_utils::{
     garmin_util::{print_h_m_s, MARATHON_DISTANCE_MI, METERS_PER_MILE},
     sport_types::SportTypes,
 };
 
+use crate::interval_workout::print_intervals;
+
+/// Text report of `gfile` with splits every `split_distance` as well as
+/// every 5k
 /// # Errors
 /// Returns error if we try to parse a negative duration or time
-pub fn generate_txt_report(gfile: &GarminFile) -> Result<Vec<StackString>, Error> {
+pub fn generate_txt_report(
+    gfile: &GarminFile,
+    split_distance: SplitDistance,
+) -> Result<Vec<StackString>, Error> {
     let mut return_vec = vec![format_sstr!("Start time {}", gfile.filename)];
 
     let sport_type = gfile.sport;
@@ -55,11 +68,34 @@ pub fn generate_txt_report(gfile: &GarminFile) -> Result<Vec<StackString>, Error
             (gfile.total_hr_dur / gfile.total_hr_dis) as i32
         ));
     };
+    if let Some(avg_temperature) = gfile.avg_temperature() {
+        tmp_str.push(format_sstr!("{avg_temperature:.1} C"));
+    }
+    let (avg_power, normalized_power) = activity_power(gfile);
+    if let Some(avg_power) = avg_power {
+        tmp_str.push(format_sstr!("{avg_power:.0} W"));
+    }
+    if let Some(normalized_power) = normalized_power {
+        tmp_str.push(format_sstr!("{normalized_power:.0} W NP"));
+    }
     return_vec.push(tmp_str.join(" ").into());
+    if let Some(differential) = split_differential(gfile) {
+        return_vec.push(format_sstr!(
+            "second half vs first half {}",
+            format_split_differential(differential)
+        ));
+    }
     return_vec.push("".into());
-    return_vec.push(print_splits(gfile, METERS_PER_MILE, "mi"));
-    return_vec.push("".into());
-    return_vec.push(print_splits(gfile, 5000.0, "km"));
+    return_vec.push(print_splits(gfile, split_distance));
+    if split_distance != SplitDistance::FIVE_K {
+        return_vec.push("".into());
+        return_vec.push(print_splits(gfile, SplitDistance::FIVE_K));
+    }
+    let intervals = print_intervals(gfile)?;
+    if !intervals.is_empty() {
+        return_vec.push("".into());
+        return_vec.extend(intervals);
+    }
 
     let avg_hr: f64 = gfile
         .points
@@ -181,34 +217,45 @@ fn print_lap_string(glap: &GarminLap, sport: SportTypes) -> Result<StackString,
             false,
         )?);
         outstr.push("/ km".into());
+    } else if sport == SportTypes::Swimming {
+        if let Some(pace) = glap.pace_per_100m() {
+            outstr.push(print_h_m_s(pace, false)?);
+            outstr.push("/ 100m".into());
+        }
     };
     if let Some(x) = glap.lap_avg_hr {
         if x > 0.0 {
             outstr.push(format_sstr!("{x} bpm"));
         }
     }
+    if let Some(cadence) = glap.avg_cadence_string(sport) {
+        outstr.push(cadence);
+    }
+    if let Some(avg_power) = glap.lap_avg_power {
+        outstr.push(format_sstr!("{avg_power:.0} W"));
+    }
+    if let Some(normalized_power) = glap.lap_normalized_power {
+        outstr.push(format_sstr!("{normalized_power:.0} W NP"));
+    }
 
     Ok(outstr.join(" ").into())
 }
 
-/// # Errors
-/// Returns error if we try to parse a negative duration or time
-fn print_splits(gfile: &GarminFile, split_distance_in_meters: f64, label: &str) -> StackString {
+fn print_splits(gfile: &GarminFile, split_distance: SplitDistance) -> StackString {
     if gfile.points.is_empty() {
         return "".into();
     }
+    let split_distance_in_meters = split_distance.meters();
 
-    get_splits(gfile, split_distance_in_meters, label, true)
+    get_distance_splits(gfile, split_distance, true)
         .into_iter()
         .map(|val| {
-            let dis = val.split_distance as i32;
             let tim = val.time_value;
             let hrt = val.avg_heart_rate.unwrap_or(0.0);
 
             format_sstr!(
-                "{} {} \t {} \t {} / mi \t {} / km \t {} \t {:.2} bpm avg",
-                dis,
-                label,
+                "{} \t {} \t {} / mi \t {} / km \t {} \t {:.2} bpm avg",
+                split_distance.marker(val.split_distance.round() as usize),
                 print_h_m_s(tim, true).unwrap_or_else(|_| "".into()),
                 print_h_m_s(tim / (split_distance_in_meters / METERS_PER_MILE), false)
                     .unwrap_or_else(|_| "".into()),
@@ -233,6 +280,24 @@ pub struct SplitValue {
     pub avg_heart_rate: Option<f64>,
 }
 
+/// Splits every `split_distance`, with `split_distance` of each value set to
+/// the number of splits completed so `SplitDistance::marker` gives its label
+#[must_use]
+pub fn get_distance_splits(
+    gfile: &GarminFile,
+    split_distance: SplitDistance,
+    do_heart_rate: bool,
+) -> Vec<SplitValue> {
+    // any label other than "km" gives the distance in splits
+    get_splits(gfile, split_distance.meters(), "split", do_heart_rate)
+        .into_iter()
+        .map(|val| SplitValue {
+            split_distance: val.split_distance.floor(),
+            ..val
+        })
+        .collect()
+}
+
 #[must_use]
 pub fn get_splits(
     gfile: &GarminFile,
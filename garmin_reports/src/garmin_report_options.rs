@@ -1,4 +1,10 @@
-use garmin_utils::sport_types::SportTypes;
+use stack_string::StackString;
+
+use garmin_lib::{
+    heart_rate_profile::HeartRateProfile, split_distance::SplitDistance, week_start::WeekStart,
+};
+use garmin_models::{demo_anonymizer::DemoAnonymizer, surface_type::SurfaceType};
+use garmin_utils::{plot_opts::PlotXAxis, sport_types::SportTypes};
 
 #[derive(Debug, Clone, Copy)]
 pub enum GarminReportAgg {
@@ -13,6 +19,21 @@ pub enum GarminReportAgg {
 pub struct GarminReportOptions {
     pub agg: Option<GarminReportAgg>,
     pub do_sport: Option<SportTypes>,
+    pub week_start: WeekStart,
+    pub xaxis: Option<PlotXAxis>,
+    pub heart_rate_profile: HeartRateProfile,
+    /// Order file reports by course difficulty, hardest first
+    pub sort_by_difficulty: bool,
+    /// Only include activities classified with this surface
+    pub surface: Option<SurfaceType>,
+    /// Only include activities whose city, region or country contains this
+    pub location: Option<StackString>,
+    /// Distance between splits in file reports
+    pub split_distance: SplitDistance,
+    /// Leave activities flagged as commutes out of weekly summaries
+    pub exclude_commutes: bool,
+    /// Shift the dates and hide the linked activities of the demo reports
+    pub anonymizer: Option<DemoAnonymizer>,
 }
 
 impl GarminReportOptions {
@@ -21,6 +42,15 @@ impl GarminReportOptions {
         Self {
             agg: None,
             do_sport: None,
+            week_start: WeekStart::Monday,
+            xaxis: None,
+            heart_rate_profile: HeartRateProfile::default(),
+            sort_by_difficulty: false,
+            surface: None,
+            location: None,
+            split_distance: SplitDistance::default(),
+            exclude_commutes: false,
+            anonymizer: None,
         }
     }
 }
@@ -1,15 +1,26 @@
 use anyhow::Error;
-use futures::future::try_join_all;
+use futures::{
+    channel::mpsc,
+    future::try_join_all,
+    stream::{self, BoxStream},
+    SinkExt, StreamExt, TryStreamExt,
+};
 use log::debug;
-use postgres_query::{query_dyn, FromSqlRow};
+use postgres_query::{query_dyn, FromSqlRow, Parameter};
 use stack_string::{format_sstr, StackString};
-use time::OffsetDateTime;
+use time::{macros::format_description, Date, OffsetDateTime};
+use tokio::spawn;
 use url::Url;
 use uuid::Uuid;
 
-use garmin_lib::date_time_wrapper::iso8601::convert_datetime_to_str;
+use garmin_lib::{
+    date_time_wrapper::iso8601::convert_datetime_to_str, heart_rate_profile::HeartRateProfile,
+    week_start::WeekStart,
+};
 use garmin_models::{
-    fitbit_activity::FitbitActivity, garmin_connect_activity::GarminConnectActivity,
+    commute::NOT_COMMUTE_SQL, course_difficulty::adjusted_pace, demo_anonymizer::DemoAnonymizer,
+    elevation_profile::ElevationProfile, fitbit_activity::FitbitActivity,
+    garmin_connect_activity::GarminConnectActivity, heart_rate_zones::HeartRateProfiles,
     strava_activity::StravaActivity,
 };
 use garmin_utils::{
@@ -32,6 +43,12 @@ pub struct HtmlResult {
 
 type GarminTextEntry = (StackString, Option<HtmlResult>);
 
+/// Text entries of a report row and the filter it links to
+pub type GarminReportRow = (Vec<GarminTextEntry>, StackString);
+
+/// File report rows read ahead of the consumer
+const FILE_REPORT_BUFFER: usize = 100;
+
 pub trait GarminReportTrait {
     /// # Errors
     /// Returns error if getting text entry fails
@@ -101,6 +118,131 @@ impl GarminReportQuery {
             Self::Empty => Vec::new(),
         }
     }
+
+    /// Move the rows to the shifted demo periods, they then link to the
+    /// shifted filters the demo page moves back
+    pub fn anonymize(&mut self, anonymizer: &DemoAnonymizer, week_start: WeekStart) {
+        match self {
+            Self::Year(x) => x.iter_mut().for_each(|r| r.anonymize(anonymizer)),
+            Self::Month(x) => x.iter_mut().for_each(|r| r.anonymize(anonymizer)),
+            Self::Week(x) => x.iter_mut().for_each(|r| r.anonymize(anonymizer)),
+            Self::Day(x) => x
+                .iter_mut()
+                .for_each(|r| r.anonymize(anonymizer, week_start)),
+            Self::File(x) => x
+                .iter_mut()
+                .for_each(|r| r.anonymize(anonymizer, week_start)),
+            Self::Sport(_) | Self::Empty => {}
+        }
+    }
+}
+
+/// `LIKE` pattern matching `location` anywhere in a lowercased column
+fn location_like_pattern(location: &str) -> StackString {
+    let mut pattern = StackString::from("%");
+    for c in location.to_lowercase().chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Where clauses of the report queries built from the request
+struct ReportConstraints {
+    /// Restricts the reported activities
+    constr: StackString,
+    /// Only the date constraints, the combined effort of weekly and monthly
+    /// rows covers every sport of the period
+    period_constr: StackString,
+    /// Which activities of the period are reported
+    report_filter: StackString,
+    sport: Option<StackString>,
+    location_pattern: Option<StackString>,
+}
+
+impl ReportConstraints {
+    fn new(options: &GarminReportOptions, constraints: &GarminConstraints) -> Self {
+        let sport = options.do_sport.map(|x| format_sstr!("{x}"));
+        let mut sport_constr = if sport.is_some() {
+            "sport = $sport".into()
+        } else {
+            StackString::new()
+        };
+        if let Some(surface) = options.surface {
+            if !sport_constr.is_empty() {
+                sport_constr.push_str(" AND ");
+            }
+            sport_constr.push_str(&format_sstr!(
+                "a.id IN (SELECT summary_id FROM activity_surfaces WHERE surface = '{surface}')"
+            ));
+        }
+        let location_pattern = options
+            .location
+            .as_ref()
+            .map(|location| location_like_pattern(location));
+        if location_pattern.is_some() {
+            if !sport_constr.is_empty() {
+                sport_constr.push_str(" AND ");
+            }
+            sport_constr.push_str(
+                "(lower(a.city) like $location_pattern OR lower(a.region) like $location_pattern \
+                 OR lower(a.country) like $location_pattern)",
+            );
+        }
+        let constraints_str = if constraints.constraints.is_empty() {
+            StackString::new()
+        } else {
+            constraints.to_query_string().into()
+        };
+
+        let mut constr = StackString::new();
+        if constraints.is_empty() {
+            if !sport_constr.is_empty() {
+                constr = format_sstr!("WHERE {sport_constr}");
+            }
+        } else if sport_constr.is_empty() {
+            constr = format_sstr!("WHERE {constraints_str}");
+        } else {
+            constr = format_sstr!("WHERE ({sport_constr}) AND ({constraints_str})",);
+        }
+        let mut period_constr = if constraints.is_empty() {
+            StackString::new()
+        } else {
+            format_sstr!("WHERE {constraints_str}")
+        };
+        if options.exclude_commutes && matches!(options.agg, Some(GarminReportAgg::Week)) {
+            period_constr = match period_constr.strip_prefix("WHERE ") {
+                Some(c) => format_sstr!("WHERE ({c}) AND {NOT_COMMUTE_SQL}"),
+                None => format_sstr!("WHERE {NOT_COMMUTE_SQL}"),
+            };
+        }
+        let report_filter = if sport_constr.is_empty() {
+            "true".into()
+        } else {
+            sport_constr
+        };
+        Self {
+            constr,
+            period_constr,
+            report_filter,
+            sport,
+            location_pattern,
+        }
+    }
+
+    fn bindings(&self) -> Vec<(&str, Parameter<'_>)> {
+        let mut bindings = Vec::new();
+        if let Some(sport) = &self.sport {
+            bindings.push(("sport", sport as Parameter));
+        }
+        if let Some(location_pattern) = &self.location_pattern {
+            bindings.push(("location_pattern", location_pattern as Parameter));
+        }
+        bindings
+    }
 }
 
 /// # Errors
@@ -110,58 +252,112 @@ pub async fn create_report_query(
     options: &GarminReportOptions,
     constraints: &GarminConstraints,
 ) -> Result<GarminReportQuery, Error> {
-    let sport_constr = if let Some(x) = options.do_sport {
-        format_sstr!("sport = '{x}'")
-    } else {
-        StackString::new()
-    };
-    let constraints_str = if constraints.constraints.is_empty() {
-        StackString::new()
-    } else {
-        constraints.to_query_string().into()
-    };
-
-    let mut constr = StackString::new();
-    if constraints.is_empty() {
-        if !sport_constr.is_empty() {
-            constr = format_sstr!("WHERE {sport_constr}");
-        }
-    } else if sport_constr.is_empty() {
-        constr = format_sstr!("WHERE {constraints_str}");
-    } else {
-        constr = format_sstr!("WHERE ({sport_constr}) AND ({constraints_str})",);
-    }
+    let report_constraints = ReportConstraints::new(options, constraints);
+    let ReportConstraints {
+        constr,
+        period_constr,
+        report_filter,
+        ..
+    } = &report_constraints;
+    let bindings = report_constraints.bindings();
 
     let agg = &options.agg;
-    debug!("agg: {agg:?}, constr: {constr}");
+    let week_start = options.week_start;
+    let profiles = HeartRateProfiles::read_from_db(pool, options.heart_rate_profile).await?;
+    debug!("agg: {agg:?}, constr: {constr}, week_start: {week_start}");
 
-    let result_vec = if let Some(agg) = &options.agg {
+    let mut result_vec = if let Some(agg) = &options.agg {
         match agg {
             GarminReportAgg::Year => {
-                GarminReportQuery::Year(year_summary_report(pool, &constr).await?)
-            }
-            GarminReportAgg::Month => {
-                GarminReportQuery::Month(month_summary_report(pool, &constr).await?)
-            }
-            GarminReportAgg::Week => {
-                GarminReportQuery::Week(week_summary_report(pool, &constr).await?)
-            }
-            GarminReportAgg::Day => {
-                GarminReportQuery::Day(day_summary_report(pool, &constr).await?)
-            }
-            GarminReportAgg::File => {
-                GarminReportQuery::File(file_summary_report(pool, &constr).await?)
+                GarminReportQuery::Year(year_summary_report(pool, constr, &bindings).await?)
             }
+            GarminReportAgg::Month => GarminReportQuery::Month(
+                month_summary_report(pool, period_constr, report_filter, &bindings, &profiles)
+                    .await?,
+            ),
+            GarminReportAgg::Week => GarminReportQuery::Week(
+                week_summary_report(
+                    pool,
+                    period_constr,
+                    report_filter,
+                    &bindings,
+                    week_start,
+                    &profiles,
+                )
+                .await?,
+            ),
+            GarminReportAgg::Day => GarminReportQuery::Day(
+                day_summary_report(pool, constr, &bindings, week_start).await?,
+            ),
+            GarminReportAgg::File => GarminReportQuery::File(
+                file_summary_report(
+                    pool,
+                    constr,
+                    &bindings,
+                    week_start,
+                    options.sort_by_difficulty,
+                )
+                .await?,
+            ),
         }
     } else if options.do_sport.is_none() {
-        GarminReportQuery::Sport(sport_summary_report(pool, &constr).await?)
+        GarminReportQuery::Sport(sport_summary_report(pool, constr, &bindings).await?)
     } else {
-        GarminReportQuery::Year(year_summary_report(pool, &constr).await?)
+        GarminReportQuery::Year(year_summary_report(pool, constr, &bindings).await?)
     };
+    if let Some(anonymizer) = &options.anonymizer {
+        result_vec.anonymize(anonymizer, week_start);
+    }
 
     Ok(result_vec)
 }
 
+/// Text entries and filter url of each report row. The aggregated reports
+/// are small and read before returning, so their errors surface before
+/// anything is sent, while the per activity file report is read from a db
+/// cursor as the stream is consumed and a failure ends the stream with the
+/// error
+/// # Errors
+/// Return error if db queries fail
+pub async fn create_report_stream(
+    pool: &PgPool,
+    options: &GarminReportOptions,
+    constraints: &GarminConstraints,
+) -> Result<BoxStream<'static, Result<GarminReportRow, Error>>, Error> {
+    if !matches!(options.agg, Some(GarminReportAgg::File)) {
+        let reports = create_report_query(pool, options, constraints).await?;
+        let rows: Vec<_> = reports
+            .get_text_entries()?
+            .into_iter()
+            .zip(reports.get_url_strings())
+            .map(Ok)
+            .collect();
+        return Ok(stream::iter(rows).boxed());
+    }
+    let report_constraints = ReportConstraints::new(options, constraints);
+    let week_start = options.week_start;
+    let sort_by_difficulty = options.sort_by_difficulty;
+    let anonymizer = options.anonymizer.clone();
+    let pool = pool.clone();
+    let (mut send, recv) = mpsc::channel(FILE_REPORT_BUFFER);
+    spawn(async move {
+        if let Err(e) = send_file_summary_rows(
+            &pool,
+            &report_constraints,
+            week_start,
+            sort_by_difficulty,
+            anonymizer.as_ref(),
+            &mut send,
+        )
+        .await
+        {
+            // nothing is listening anymore if the client went away
+            let _ = send.send(Err(e)).await;
+        }
+    });
+    Ok(recv.boxed())
+}
+
 #[derive(Debug, PartialEq)]
 pub struct FileSummaryReport {
     datetime: OffsetDateTime,
@@ -169,6 +365,7 @@ pub struct FileSummaryReport {
     isodow: u32,
     sport: StackString,
     total_calories: i64,
+    calories_estimated: bool,
     total_distance: f64,
     total_duration: f64,
     total_hr_dur: f64,
@@ -179,6 +376,24 @@ pub struct FileSummaryReport {
     connect_id: Option<i64>,
     strava_title: Option<StackString>,
     strava_id: Option<i64>,
+    elevation_svg: Option<StackString>,
+    course_difficulty: Option<f64>,
+    avg_power: Option<f64>,
+    normalized_power: Option<f64>,
+    avg_temperature: Option<f64>,
+}
+
+impl FileSummaryReport {
+    fn anonymize(&mut self, anonymizer: &DemoAnonymizer, week_start: WeekStart) {
+        self.datetime = anonymizer.shift_datetime(self.datetime);
+        self.week = u32::from(week_start.year_week(self.datetime.date()).1);
+        self.isodow = u32::from(self.datetime.weekday().number_days_from_monday());
+        // titles and ids of the linked activities would give away the real ones
+        self.strava_title = None;
+        self.strava_id = None;
+        self.fitbit_id = None;
+        self.connect_id = None;
+    }
 }
 
 impl GarminReportTrait for FileSummaryReport {
@@ -200,7 +415,7 @@ impl GarminReportTrait for FileSummaryReport {
                             "{:10} {:10} {:10} {:10} {:10} {:10}",
                             self.sport,
                             format_sstr!("{:.2} mi", self.total_distance / METERS_PER_MILE),
-                            format_sstr!("{} cal", self.total_calories),
+                            format_calories(self.total_calories, self.calories_estimated),
                             format_sstr!(
                                 "{} / mi",
                                 print_h_m_s(
@@ -225,7 +440,7 @@ impl GarminReportTrait for FileSummaryReport {
                             "{:10} {:10} {:10} {:10} {:10} {:10}",
                             self.sport,
                             format_sstr!("{:.2} mi", self.total_distance / METERS_PER_MILE),
-                            format_sstr!("{} cal", self.total_calories),
+                            format_calories(self.total_calories, self.calories_estimated),
                             "",
                             "",
                             print_h_m_s(self.total_duration, true)?
@@ -242,7 +457,7 @@ impl GarminReportTrait for FileSummaryReport {
                         "{:10} {:10} {:10} {:10} {:10} {:10}",
                         self.sport,
                         format_sstr!("{:.2} mi", self.total_distance / METERS_PER_MILE),
-                        format_sstr!("{} cal", self.total_calories),
+                        format_calories(self.total_calories, self.calories_estimated),
                         format_sstr!(
                             "{:.2} mph",
                             (self.total_distance / METERS_PER_MILE) / (self.total_duration / 3600.)
@@ -261,7 +476,7 @@ impl GarminReportTrait for FileSummaryReport {
                         "{:10} {:10} {:10} {:10} {:10} {:10}",
                         self.sport,
                         format_sstr!("{:.2} mi", self.total_distance / METERS_PER_MILE),
-                        format_sstr!("{} cal", self.total_calories),
+                        format_calories(self.total_calories, self.calories_estimated),
                         "",
                         "",
                         print_h_m_s(self.total_duration, true)?
@@ -270,6 +485,23 @@ impl GarminReportTrait for FileSummaryReport {
                 ));
             }
         };
+        if let Some(difficulty) = self.course_difficulty {
+            let is_foot = matches!(self.sport.as_str(), "running" | "walking");
+            if is_foot && self.total_distance > 0.0 {
+                let pace = self.total_duration / (self.total_distance / METERS_PER_MILE);
+                tmp_vec.push((
+                    format_sstr!(
+                        " {difficulty:.2} diff {} / mi adj",
+                        print_h_m_s(adjusted_pace(pace, difficulty), false)?
+                    ),
+                    None,
+                ));
+            } else {
+                tmp_vec.push((format_sstr!(" {difficulty:.2} diff"), None));
+            }
+        } else {
+            tmp_vec.push(("".into(), None));
+        }
         if self.total_hr_dur > self.total_hr_dis {
             tmp_vec.push((
                 format_sstr!(
@@ -281,6 +513,25 @@ impl GarminReportTrait for FileSummaryReport {
         } else {
             tmp_vec.push(("".into(), None));
         }
+        if let Some(avg_power) = self.avg_power {
+            let normalized = self
+                .normalized_power
+                .map_or_else(StackString::new, |np| format_sstr!(" {np:.0} NP"));
+            tmp_vec.push((
+                format_sstr!("\t {:12}", format_sstr!("{avg_power:.0} W{normalized}")),
+                None,
+            ));
+        } else {
+            tmp_vec.push(("".into(), None));
+        }
+        if let Some(avg_temperature) = self.avg_temperature {
+            tmp_vec.push((
+                format_sstr!("\t {:7}", format_sstr!("{avg_temperature:.1} C")),
+                None,
+            ));
+        } else {
+            tmp_vec.push(("".into(), None));
+        }
         if self.total_fitbit_steps > 0 || self.total_connect_steps > 0 {
             let fitbit_url: Option<Url> = if let Some(id) = self.fitbit_id {
                 format_sstr!("https://www.fitbit.com/activities/exercise/{id}")
@@ -351,6 +602,15 @@ impl GarminReportTrait for FileSummaryReport {
         } else {
             tmp_vec.push(("".into(), None));
         }
+        if let Some(svg) = &self.elevation_svg {
+            tmp_vec.push((
+                "".into(),
+                Some(HtmlResult {
+                    text: Some(svg.clone()),
+                    url: None,
+                }),
+            ));
+        }
         Ok(tmp_vec)
     }
     fn generate_url_string(&self) -> StackString {
@@ -358,80 +618,146 @@ impl GarminReportTrait for FileSummaryReport {
     }
 }
 
-async fn file_summary_report(pool: &PgPool, constr: &str) -> Result<Vec<FileSummaryReport>, Error> {
-    #[derive(FromSqlRow, Debug)]
-    struct FileSummaryReportRow {
-        datetime: OffsetDateTime,
-        sport: StackString,
-        total_calories: i32,
-        total_distance: f64,
-        total_duration: f64,
-        total_hr_dur: f64,
-        total_hr_dis: f64,
-        summary_id: Uuid,
-    }
+#[derive(FromSqlRow, Debug)]
+struct FileSummaryReportRow {
+    datetime: OffsetDateTime,
+    sport: StackString,
+    total_calories: i32,
+    calories_estimated: bool,
+    total_distance: f64,
+    total_duration: f64,
+    total_hr_dur: f64,
+    total_hr_dis: f64,
+    summary_id: Uuid,
+    course_difficulty: Option<f64>,
+    avg_power: Option<f64>,
+    normalized_power: Option<f64>,
+    avg_temperature: Option<f64>,
+}
 
-    let query = format_sstr!(
+fn file_summary_query(constr: &str, sort_by_difficulty: bool) -> StackString {
+    let order_by = if sort_by_difficulty {
+        "a.course_difficulty DESC NULLS LAST, datetime"
+    } else {
+        "datetime, sport"
+    };
+    format_sstr!(
         "
         SELECT a.begin_datetime as datetime,
                 a.sport,
                 a.total_calories,
+                a.calories_estimated,
                 a.total_distance,
                 a.total_duration,
                 CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dur ELSE 0.0 END AS total_hr_dur,
                 CASE WHEN a.total_hr_dis > 0.0 THEN a.total_hr_dis ELSE 0.0 END AS total_hr_dis,
-                a.id as summary_id
+                a.id as summary_id,
+                a.course_difficulty,
+                a.avg_power,
+                a.normalized_power,
+                a.avg_temperature
         FROM garmin_summary a
         LEFT JOIN strava_activities b ON a.id = b.summary_id
         {constr}
-        ORDER BY datetime, sport
+        ORDER BY {order_by}
     "
-    );
-    let query = query_dyn!(&query)?;
+    )
+}
+
+async fn file_summary_row(
+    pool: &PgPool,
+    item: FileSummaryReportRow,
+    week_start: WeekStart,
+) -> Result<FileSummaryReport, Error> {
+    let strava_activity = StravaActivity::get_from_summary_id(pool, item.summary_id).await?;
+    let strava_title = strava_activity.as_ref().map(|s| s.name.clone());
+    let strava_id = strava_activity.as_ref().map(|s| s.id);
+
+    let fitbit_activity = FitbitActivity::get_from_summary_id(pool, item.summary_id).await?;
+    let total_fitbit_steps = fitbit_activity.as_ref().and_then(|a| a.steps).unwrap_or(0);
+    let fitbit_id = fitbit_activity.as_ref().map(|a| a.log_id);
+
+    let connect_activity =
+        GarminConnectActivity::get_from_summary_id(pool, item.summary_id).await?;
+    let total_connect_steps = connect_activity.as_ref().and_then(|a| a.steps).unwrap_or(0);
+    let connect_id = connect_activity.as_ref().map(|a| a.activity_id);
+
+    let elevation_svg = ElevationProfile::get_by_summary_id(pool, item.summary_id)
+        .await?
+        .and_then(|p| p.to_svg());
+
+    Ok(FileSummaryReport {
+        datetime: item.datetime,
+        week: u32::from(week_start.year_week(item.datetime.date()).1),
+        isodow: u32::from(item.datetime.weekday().number_days_from_monday()),
+        sport: item.sport,
+        total_calories: i64::from(item.total_calories),
+        calories_estimated: item.calories_estimated,
+        total_distance: item.total_distance,
+        total_duration: item.total_duration,
+        total_hr_dur: item.total_hr_dur,
+        total_hr_dis: item.total_hr_dis,
+        total_fitbit_steps,
+        fitbit_id,
+        total_connect_steps,
+        connect_id,
+        strava_title,
+        strava_id,
+        elevation_svg,
+        course_difficulty: item.course_difficulty,
+        avg_power: item.avg_power,
+        normalized_power: item.normalized_power,
+        avg_temperature: item.avg_temperature,
+    })
+}
+
+async fn file_summary_report(
+    pool: &PgPool,
+    constr: &str,
+    bindings: &[(&str, Parameter<'_>)],
+    week_start: WeekStart,
+    sort_by_difficulty: bool,
+) -> Result<Vec<FileSummaryReport>, Error> {
+    let query = file_summary_query(constr, sort_by_difficulty);
+    let query = query_dyn!(&query, ..bindings.iter().copied())?;
     let conn = pool.get().await?;
     let items: Vec<FileSummaryReportRow> = query.fetch(&conn).await?;
 
-    let futures = items.into_iter().map(|item| {
-        let pool = pool.clone();
-        async move {
-            let strava_activity =
-                StravaActivity::get_from_summary_id(&pool, item.summary_id).await?;
-            let strava_title = strava_activity.as_ref().map(|s| s.name.clone());
-            let strava_id = strava_activity.as_ref().map(|s| s.id);
-
-            let fitbit_activity =
-                FitbitActivity::get_from_summary_id(&pool, item.summary_id).await?;
-            let total_fitbit_steps = fitbit_activity.as_ref().and_then(|a| a.steps).unwrap_or(0);
-            let fitbit_id = fitbit_activity.as_ref().map(|a| a.log_id);
-
-            let connect_activity =
-                GarminConnectActivity::get_from_summary_id(&pool, item.summary_id).await?;
-            let total_connect_steps = connect_activity.as_ref().and_then(|a| a.steps).unwrap_or(0);
-            let connect_id = connect_activity.as_ref().map(|a| a.activity_id);
-
-            let result = FileSummaryReport {
-                datetime: item.datetime,
-                week: u32::from(item.datetime.iso_week()),
-                isodow: u32::from(item.datetime.weekday().number_days_from_monday()),
-                sport: item.sport,
-                total_calories: i64::from(item.total_calories),
-                total_distance: item.total_distance,
-                total_duration: item.total_duration,
-                total_hr_dur: item.total_hr_dur,
-                total_hr_dis: item.total_hr_dis,
-                total_fitbit_steps,
-                fitbit_id,
-                total_connect_steps,
-                connect_id,
-                strava_title,
-                strava_id,
-            };
-            Ok(result)
-        }
-    });
+    let futures = items
+        .into_iter()
+        .map(|item| file_summary_row(pool, item, week_start));
     try_join_all(futures).await
 }
 
+/// Send the file report rows as they're read from the db cursor, stops once
+/// the receiver is gone
+async fn send_file_summary_rows(
+    pool: &PgPool,
+    report_constraints: &ReportConstraints,
+    week_start: WeekStart,
+    sort_by_difficulty: bool,
+    anonymizer: Option<&DemoAnonymizer>,
+    send: &mut mpsc::Sender<Result<GarminReportRow, Error>>,
+) -> Result<(), Error> {
+    let query = file_summary_query(&report_constraints.constr, sort_by_difficulty);
+    let query = query_dyn!(&query, ..report_constraints.bindings())?;
+    let conn = pool.get().await?;
+    let items = query.fetch_streaming(&conn).await?;
+    futures::pin_mut!(items);
+    while let Some(item) = items.try_next().await? {
+        let item: FileSummaryReportRow = item;
+        let mut report = file_summary_row(pool, item, week_start).await?;
+        if let Some(anonymizer) = anonymizer {
+            report.anonymize(anonymizer, week_start);
+        }
+        let row = (report.get_text_entry()?, report.generate_url_string());
+        if send.send(Ok(row)).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
 #[derive(FromSqlRow, Debug, PartialEq)]
 pub struct DaySummaryReport {
     date: StackString,
@@ -439,12 +765,24 @@ pub struct DaySummaryReport {
     isodow: i32,
     sport: StackString,
     total_calories: i64,
+    calories_estimated: bool,
     total_distance: f64,
     total_duration: f64,
     total_hr_dur: f64,
     total_hr_dis: f64,
 }
 
+impl DaySummaryReport {
+    fn anonymize(&mut self, anonymizer: &DemoAnonymizer, week_start: WeekStart) {
+        if let Ok(date) = Date::parse(&self.date, format_description!("[year]-[month]-[day]")) {
+            let date = anonymizer.shift_date(date);
+            self.date = StackString::from_display(date);
+            self.week = i32::from(week_start.year_week(date).1);
+            self.isodow = i32::from(date.weekday().number_from_monday());
+        }
+    }
+}
+
 impl GarminReportTrait for DaySummaryReport {
     fn get_text_entry(&self) -> Result<Vec<GarminTextEntry>, Error> {
         let weekdayname = WEEKDAY_NAMES[self.isodow as usize - 1];
@@ -462,7 +800,7 @@ impl GarminReportTrait for DaySummaryReport {
                             format_sstr!("{:10} {:02} {:3}", self.date, self.week, weekdayname),
                             self.sport,
                             format_sstr!("{:.2} mi", self.total_distance / METERS_PER_MILE),
-                            format_sstr!("{} cal", self.total_calories),
+                            format_calories(self.total_calories, self.calories_estimated),
                             format_sstr!(
                                 "{} / mi",
                                 print_h_m_s(
@@ -488,7 +826,7 @@ impl GarminReportTrait for DaySummaryReport {
                             format_sstr!("{:10} {:02} {:3}", self.date, self.week, weekdayname),
                             self.sport,
                             format_sstr!("{:.2} mi", self.total_distance / METERS_PER_MILE),
-                            format_sstr!("{} cal", self.total_calories),
+                            format_calories(self.total_calories, self.calories_estimated),
                             "",
                             "",
                             print_h_m_s(self.total_duration, true)?
@@ -504,7 +842,7 @@ impl GarminReportTrait for DaySummaryReport {
                         format_sstr!("{:10} {:02} {:3}", self.date, self.week, weekdayname),
                         self.sport,
                         format_sstr!("{:.2} mi", self.total_distance / METERS_PER_MILE),
-                        format_sstr!("{} cal", self.total_calories),
+                        format_calories(self.total_calories, self.calories_estimated),
                         format_sstr!(
                             "{:.2} mph",
                             (self.total_distance / METERS_PER_MILE) / (self.total_duration / 3600.)
@@ -522,7 +860,7 @@ impl GarminReportTrait for DaySummaryReport {
                         format_sstr!("{:10} {:02} {:3}", self.date, self.week, weekdayname),
                         self.sport,
                         format_sstr!("{:.2} mi", self.total_distance / METERS_PER_MILE),
-                        format_sstr!("{} cal", self.total_calories),
+                        format_calories(self.total_calories, self.calories_estimated),
                         "",
                         "",
                         print_h_m_s(self.total_duration, true)?
@@ -547,13 +885,20 @@ impl GarminReportTrait for DaySummaryReport {
     }
 }
 
-async fn day_summary_report(pool: &PgPool, constr: &str) -> Result<Vec<DaySummaryReport>, Error> {
+async fn day_summary_report(
+    pool: &PgPool,
+    constr: &str,
+    bindings: &[(&str, Parameter<'_>)],
+    week_start: WeekStart,
+) -> Result<Vec<DaySummaryReport>, Error> {
+    let shift = week_start.sql_shift();
     let query = format_sstr!(
         "
         WITH c AS (
             SELECT a.begin_datetime,
                    a.sport,
                    a.total_calories,
+                   a.calories_estimated,
                    a.total_distance,
                    a.total_duration,
                    CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dur ELSE 0.0 END AS total_hr_dur,
@@ -564,10 +909,11 @@ async fn day_summary_report(pool: &PgPool, constr: &str) -> Result<Vec<DaySummar
         )
         SELECT
             CAST(CAST(begin_datetime at time zone 'localtime' as date) as text) as date,
-            CAST(EXTRACT(week from begin_datetime at time zone 'localtime') AS INT) as week,
+            CAST(EXTRACT(week from begin_datetime at time zone 'localtime'{shift}) AS INT) as week,
             CAST(EXTRACT(isodow from begin_datetime at time zone 'localtime') AS INT) as isodow,
             sport,
             sum(total_calories) as total_calories,
+            bool_or(calories_estimated) as calories_estimated,
             sum(total_distance) as total_distance,
             sum(total_duration) as total_duration,
             sum(total_hr_dur) as total_hr_dur,
@@ -578,22 +924,56 @@ async fn day_summary_report(pool: &PgPool, constr: &str) -> Result<Vec<DaySummar
     "
     );
     debug!("{}", query);
-    let query = query_dyn!(&query)?;
+    let query = query_dyn!(&query, ..bindings.iter().copied())?;
     let conn = pool.get().await?;
     query.fetch(&conn).await.map_err(Into::into)
 }
 
+/// Calories of a row, marked when they include estimates for activities
+/// recorded without them
+fn format_calories(total_calories: i64, calories_estimated: bool) -> StackString {
+    if calories_estimated {
+        format_sstr!("~{total_calories} cal")
+    } else {
+        format_sstr!("{total_calories} cal")
+    }
+}
+
+/// Effort of the row's sport followed by the effort summed over all sports in
+/// the same period.
+fn format_effort(total_effort: f64, combined_effort: f64) -> StackString {
+    if combined_effort > 0.0 {
+        format_sstr!(
+            " {:16}",
+            format_sstr!("{total_effort:.0} / {combined_effort:.0} effort")
+        )
+    } else {
+        format_sstr!(" {:16}", "")
+    }
+}
+
 #[derive(FromSqlRow, Debug, PartialEq)]
 pub struct WeekSummaryReport {
     year: i32,
     week: i32,
     sport: StackString,
     total_calories: i64,
+    calories_estimated: bool,
     total_distance: f64,
     total_duration: f64,
     total_hr_dur: f64,
     total_hr_dis: f64,
     number_of_days: i64,
+    total_effort: f64,
+    combined_effort: f64,
+}
+
+impl WeekSummaryReport {
+    fn anonymize(&mut self, anonymizer: &DemoAnonymizer) {
+        let (year, week) = anonymizer.shift_year_week(self.year, self.week as u8);
+        self.year = year;
+        self.week = i32::from(week);
+    }
 }
 
 impl GarminReportTrait for WeekSummaryReport {
@@ -608,7 +988,7 @@ impl GarminReportTrait for WeekSummaryReport {
                 format_sstr!("{} week {:02}", self.year, self.week),
                 self.sport,
                 format_sstr!("{:4.2} mi", self.total_distance / METERS_PER_MILE),
-                format_sstr!("{} cal", self.total_calories)
+                format_calories(self.total_calories, self.calories_estimated)
             ),
             None,
         )];
@@ -686,6 +1066,7 @@ impl GarminReportTrait for WeekSummaryReport {
             ),
             None,
         ));
+        tmp_vec.push((format_effort(self.total_effort, self.combined_effort), None));
 
         Ok(tmp_vec)
     }
@@ -694,38 +1075,75 @@ impl GarminReportTrait for WeekSummaryReport {
     }
 }
 
-async fn week_summary_report(pool: &PgPool, constr: &str) -> Result<Vec<WeekSummaryReport>, Error> {
+/// Rows of the activities within `period_constr` matching `report_filter`,
+/// `combined_effort` sums the effort of all activities of the week whatever
+/// their sport
+async fn week_summary_report(
+    pool: &PgPool,
+    period_constr: &str,
+    report_filter: &str,
+    bindings: &[(&str, Parameter<'_>)],
+    week_start: WeekStart,
+    profiles: &HeartRateProfiles,
+) -> Result<Vec<WeekSummaryReport>, Error> {
+    let shift = week_start.sql_shift();
+    let effort = profiles.trimp_sql(
+        "CAST(a.begin_datetime at time zone 'localtime' AS DATE)",
+        "a.total_hr_dur",
+        "a.total_hr_dis",
+    );
+    let rpe_effort = HeartRateProfile::rpe_trimp_sql("rp.rpe", "a.total_duration");
     let query = format_sstr!(
         "
         WITH c AS (
             SELECT a.begin_datetime,
+                   CAST(EXTRACT(isoyear from a.begin_datetime at time zone 'localtime'{shift}) AS INT) as year,
+                   CAST(EXTRACT(week from a.begin_datetime at time zone 'localtime'{shift}) AS INT) as week,
                    a.sport,
                    a.total_calories,
+                   a.calories_estimated,
                    a.total_distance,
                    a.total_duration,
                    CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dur ELSE 0.0 END AS total_hr_dur,
-                   CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dis ELSE 0.0 END AS total_hr_dis
+                   CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dis ELSE 0.0 END AS total_hr_dis,
+                   CASE WHEN a.total_hr_dur > 0.0 THEN {effort}
+                        ELSE COALESCE({rpe_effort}, 0.0) END AS effort,
+                   ({report_filter}) AS in_report
             FROM garmin_summary a
             LEFT JOIN strava_activities b ON a.id = b.summary_id
-            {constr}
-        )
+            LEFT JOIN activity_rpe rp ON a.id = rp.summary_id
+            {period_constr}
+        ), d AS (
         SELECT
-            CAST(EXTRACT(isoyear from begin_datetime at time zone 'localtime') AS INT) as year,
-            CAST(EXTRACT(week from begin_datetime at time zone 'localtime') AS INT) as week,
+            year,
+            week,
             sport,
             sum(total_calories) as total_calories,
+            bool_or(calories_estimated) as calories_estimated,
             sum(total_distance) as total_distance,
             sum(total_duration) as total_duration,
             sum(total_hr_dur) as total_hr_dur,
             sum(total_hr_dis) as total_hr_dis,
-            count(distinct cast(begin_datetime at time zone 'localtime' as date)) as number_of_days
+            count(distinct cast(begin_datetime at time zone 'localtime' as date)) as number_of_days,
+            sum(effort) as total_effort
         FROM c
+        WHERE in_report
         GROUP BY sport, year, week
-        ORDER BY sport, year, week
+        ), e AS (
+        SELECT year, week, sum(effort) as combined_effort
+        FROM c
+        GROUP BY year, week
+        )
+        SELECT d.year, d.week, d.sport, d.total_calories, d.calories_estimated,
+               d.total_distance, d.total_duration, d.total_hr_dur, d.total_hr_dis,
+               d.number_of_days, d.total_effort, e.combined_effort
+        FROM d
+        JOIN e ON e.year = d.year AND e.week = d.week
+        ORDER BY d.sport, d.year, d.week
     "
     );
     debug!("{}", query);
-    let query = query_dyn!(&query)?;
+    let query = query_dyn!(&query, ..bindings.iter().copied())?;
     let conn = pool.get().await?;
     query.fetch(&conn).await.map_err(Into::into)
 }
@@ -736,11 +1154,22 @@ pub struct MonthSummaryReport {
     month: i32,
     sport: StackString,
     total_calories: i64,
+    calories_estimated: bool,
     total_distance: f64,
     total_duration: f64,
     total_hr_dur: f64,
     total_hr_dis: f64,
     number_of_days: i64,
+    total_effort: f64,
+    combined_effort: f64,
+}
+
+impl MonthSummaryReport {
+    fn anonymize(&mut self, anonymizer: &DemoAnonymizer) {
+        let (year, month) = anonymizer.shift_year_month(self.year, self.month as u8);
+        self.year = year;
+        self.month = i32::from(month);
+    }
 }
 
 impl GarminReportTrait for MonthSummaryReport {
@@ -759,7 +1188,10 @@ impl GarminReportTrait for MonthSummaryReport {
             None,
         )];
         tmp_vec.push((
-            format_sstr!("{:10} \t", format_sstr!("{} cal", self.total_calories)),
+            format_sstr!(
+                "{:10} \t",
+                format_calories(self.total_calories, self.calories_estimated)
+            ),
             None,
         ));
 
@@ -834,6 +1266,7 @@ impl GarminReportTrait for MonthSummaryReport {
             ),
             None,
         ));
+        tmp_vec.push((format_effort(self.total_effort, self.combined_effort), None));
 
         Ok(tmp_vec)
     }
@@ -842,41 +1275,73 @@ impl GarminReportTrait for MonthSummaryReport {
     }
 }
 
+/// Rows of the activities within `period_constr` matching `report_filter`,
+/// `combined_effort` sums the effort of all activities of the month whatever
+/// their sport
 async fn month_summary_report(
     pool: &PgPool,
-    constr: &str,
+    period_constr: &str,
+    report_filter: &str,
+    bindings: &[(&str, Parameter<'_>)],
+    profiles: &HeartRateProfiles,
 ) -> Result<Vec<MonthSummaryReport>, Error> {
+    let effort = profiles.trimp_sql(
+        "CAST(a.begin_datetime at time zone 'localtime' AS DATE)",
+        "a.total_hr_dur",
+        "a.total_hr_dis",
+    );
+    let rpe_effort = HeartRateProfile::rpe_trimp_sql("rp.rpe", "a.total_duration");
     let query = format_sstr!(
         "
         WITH c AS (
             SELECT a.begin_datetime,
+                   CAST(EXTRACT(year from a.begin_datetime at time zone 'localtime') AS INT) as year,
+                   CAST(EXTRACT(month from a.begin_datetime at time zone 'localtime') AS INT) as month,
                    a.sport,
                    a.total_calories,
+                   a.calories_estimated,
                    a.total_distance,
                    a.total_duration,
                    CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dur ELSE 0.0 END AS total_hr_dur,
-                   CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dis ELSE 0.0 END AS total_hr_dis
+                   CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dis ELSE 0.0 END AS total_hr_dis,
+                   CASE WHEN a.total_hr_dur > 0.0 THEN {effort}
+                        ELSE COALESCE({rpe_effort}, 0.0) END AS effort,
+                   ({report_filter}) AS in_report
             FROM garmin_summary a
             LEFT JOIN strava_activities b ON a.id = b.summary_id
-            {constr}
-        )
+            LEFT JOIN activity_rpe rp ON a.id = rp.summary_id
+            {period_constr}
+        ), d AS (
         SELECT
-            CAST(EXTRACT(year from begin_datetime at time zone 'localtime') AS INT) as year,
-            CAST(EXTRACT(month from begin_datetime at time zone 'localtime') AS INT) as month,
+            year,
+            month,
             sport,
             sum(total_calories) as total_calories,
+            bool_or(calories_estimated) as calories_estimated,
             sum(total_distance) as total_distance,
             sum(total_duration) as total_duration,
             sum(total_hr_dur) as total_hr_dur,
             sum(total_hr_dis) as total_hr_dis,
-            count(distinct cast(begin_datetime at time zone 'localtime' as date)) as number_of_days
+            count(distinct cast(begin_datetime at time zone 'localtime' as date)) as number_of_days,
+            sum(effort) as total_effort
         FROM c
+        WHERE in_report
         GROUP BY sport, year, month
-        ORDER BY sport, year, month
+        ), e AS (
+        SELECT year, month, sum(effort) as combined_effort
+        FROM c
+        GROUP BY year, month
+        )
+        SELECT d.year, d.month, d.sport, d.total_calories, d.calories_estimated,
+               d.total_distance, d.total_duration, d.total_hr_dur, d.total_hr_dis,
+               d.number_of_days, d.total_effort, e.combined_effort
+        FROM d
+        JOIN e ON e.year = d.year AND e.month = d.month
+        ORDER BY d.sport, d.year, d.month
     "
     );
     debug!("{}", query);
-    let query = query_dyn!(&query)?;
+    let query = query_dyn!(&query, ..bindings.iter().copied())?;
     let conn = pool.get().await?;
     query.fetch(&conn).await.map_err(Into::into)
 }
@@ -885,6 +1350,7 @@ async fn month_summary_report(
 pub struct SportSummaryReport {
     sport: StackString,
     total_calories: i64,
+    calories_estimated: bool,
     total_distance: f64,
     total_duration: f64,
     total_hr_dur: f64,
@@ -904,7 +1370,10 @@ impl GarminReportTrait for SportSummaryReport {
                 None,
             ),
             (
-                format_sstr!("{:10} \t", format_sstr!("{} cal", self.total_calories)),
+                format_sstr!(
+                    "{:10} \t",
+                    format_calories(self.total_calories, self.calories_estimated)
+                ),
                 None,
             ),
         ];
@@ -981,6 +1450,7 @@ impl GarminReportTrait for SportSummaryReport {
 async fn sport_summary_report(
     pool: &PgPool,
     constr: &str,
+    bindings: &[(&str, Parameter<'_>)],
 ) -> Result<Vec<SportSummaryReport>, Error> {
     let query = format_sstr!(
         "
@@ -988,6 +1458,7 @@ async fn sport_summary_report(
             SELECT a.begin_datetime,
                    a.sport,
                    a.total_calories,
+                   a.calories_estimated,
                    a.total_distance,
                    a.total_duration,
                    CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dur ELSE 0.0 END AS total_hr_dur,
@@ -998,6 +1469,7 @@ async fn sport_summary_report(
         )
         SELECT sport,
                sum(total_calories) as total_calories,
+               bool_or(calories_estimated) as calories_estimated,
                sum(total_distance) as total_distance,
                sum(total_duration) as total_duration,
                sum(total_hr_dur) as total_hr_dur,
@@ -1008,7 +1480,7 @@ async fn sport_summary_report(
         "
     );
     debug!("{}", query);
-    let query = query_dyn!(&query)?;
+    let query = query_dyn!(&query, ..bindings.iter().copied())?;
     let conn = pool.get().await?;
     query.fetch(&conn).await.map_err(Into::into)
 }
@@ -1018,6 +1490,7 @@ pub struct YearSummaryReport {
     year: i32,
     sport: StackString,
     total_calories: i64,
+    calories_estimated: bool,
     total_distance: f64,
     total_duration: f64,
     total_hr_dur: f64,
@@ -1025,6 +1498,12 @@ pub struct YearSummaryReport {
     number_of_days: i64,
 }
 
+impl YearSummaryReport {
+    fn anonymize(&mut self, anonymizer: &DemoAnonymizer) {
+        self.year = anonymizer.shift_year(self.year);
+    }
+}
+
 impl GarminReportTrait for YearSummaryReport {
     fn get_text_entry(&self) -> Result<Vec<GarminTextEntry>, Error> {
         let total_days = days_in_year(self.year);
@@ -1041,7 +1520,10 @@ impl GarminReportTrait for YearSummaryReport {
                 None,
             ),
             (
-                format_sstr!("{:10} \t", format_sstr!("{} cal", self.total_calories)),
+                format_sstr!(
+                    "{:10} \t",
+                    format_calories(self.total_calories, self.calories_estimated)
+                ),
                 None,
             ),
         ];
@@ -1123,13 +1605,18 @@ impl GarminReportTrait for YearSummaryReport {
     }
 }
 
-async fn year_summary_report(pool: &PgPool, constr: &str) -> Result<Vec<YearSummaryReport>, Error> {
+async fn year_summary_report(
+    pool: &PgPool,
+    constr: &str,
+    bindings: &[(&str, Parameter<'_>)],
+) -> Result<Vec<YearSummaryReport>, Error> {
     let query = format_sstr!(
         "
         WITH c AS (
             SELECT a.begin_datetime,
                    a.sport,
                    a.total_calories,
+                   a.calories_estimated,
                    a.total_distance,
                    a.total_duration,
                    CASE WHEN a.total_hr_dur > 0.0 THEN a.total_hr_dur ELSE 0.0 END AS total_hr_dur,
@@ -1142,6 +1629,7 @@ async fn year_summary_report(pool: &PgPool, constr: &str) -> Result<Vec<YearSumm
             CAST(EXTRACT(year from begin_datetime at time zone 'localtime') AS INT) as year,
             sport,
             sum(total_calories) as total_calories,
+            bool_or(calories_estimated) as calories_estimated,
             sum(total_distance) as total_distance,
             sum(total_duration) as total_duration,
             sum(total_hr_dur) as total_hr_dur,
@@ -1153,7 +1641,18 @@ async fn year_summary_report(pool: &PgPool, constr: &str) -> Result<Vec<YearSumm
         "
     );
     debug!("{}", query);
-    let query = query_dyn!(&query)?;
+    let query = query_dyn!(&query, ..bindings.iter().copied())?;
     let conn = pool.get().await?;
     query.fetch(&conn).await.map_err(Into::into)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::garmin_summary_report_txt::location_like_pattern;
+
+    #[test]
+    fn test_location_like_pattern() {
+        assert_eq!(location_like_pattern("Boston"), "%boston%");
+        assert_eq!(location_like_pattern("O'Hare_50%"), "%o'hare\\_50\\%%");
+    }
+}
@@ -0,0 +1,412 @@
+use anyhow::Error;
+use stack_string::{format_sstr, StackString};
+use std::fmt;
+
+use garmin_models::{garmin_file::GarminFile, garmin_lap::GarminLap};
+use garmin_utils::garmin_util::{print_h_m_s, METERS_PER_MILE};
+
+/// Fewest fast segments for an activity to count as intervals
+const MIN_REPS: usize = 3;
+/// Work segments must average at least this much faster than recoveries,
+/// steady runs with auto laps vary by far less
+const MIN_SPEED_RATIO: f64 = 1.15;
+/// Window the point speed is averaged over before thresholding
+const SMOOTHING_SECONDS: f64 = 30.0;
+/// Segments detected from points shorter than this are merged into the
+/// preceding segment
+const MIN_SEGMENT_SECONDS: f64 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntervalKind {
+    Warmup,
+    Work,
+    Recovery,
+    Cooldown,
+}
+
+impl IntervalKind {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Warmup => "warmup",
+            Self::Work => "work",
+            Self::Recovery => "recovery",
+            Self::Cooldown => "cooldown",
+        }
+    }
+}
+
+impl fmt::Display for IntervalKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+/// Where the intervals came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntervalSource {
+    Laps,
+    Detected,
+}
+
+/// One segment of an interval workout, distances in meters and times in
+/// seconds, `rep` counts the work segments
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub rep: Option<usize>,
+    pub kind: IntervalKind,
+    pub distance: f64,
+    pub duration: f64,
+    pub avg_heart_rate: Option<f64>,
+}
+
+impl Interval {
+    /// Seconds per mile
+    #[must_use]
+    pub fn pace(&self) -> Option<f64> {
+        if self.distance > 0.0 {
+            Some(self.duration / (self.distance / METERS_PER_MILE))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    fast: bool,
+    distance: f64,
+    duration: f64,
+    avg_heart_rate: Option<f64>,
+}
+
+impl Segment {
+    /// Extend by `other`, keeping this segment's kind
+    fn absorb(&mut self, other: &Self) {
+        let duration = self.duration + other.duration;
+        self.avg_heart_rate = match (self.avg_heart_rate, other.avg_heart_rate) {
+            (Some(a), Some(b)) if duration > 0.0 => {
+                Some((a * self.duration + b * other.duration) / duration)
+            }
+            (a, b) => a.or(b),
+        };
+        self.distance += other.distance;
+        self.duration = duration;
+    }
+}
+
+/// Split point between slow and fast values, two cluster 1d k-means
+fn speed_threshold(speeds: &[f64]) -> Option<f64> {
+    let mut low = speeds.iter().copied().reduce(f64::min)?;
+    let mut high = speeds.iter().copied().reduce(f64::max)?;
+    if high <= low {
+        return None;
+    }
+    for _ in 0..20 {
+        let threshold = (low + high) / 2.0;
+        let (slow, fast): (Vec<f64>, Vec<f64>) = speeds.iter().partition(|s| **s < threshold);
+        if slow.is_empty() || fast.is_empty() {
+            break;
+        }
+        low = slow.iter().sum::<f64>() / slow.len() as f64;
+        high = fast.iter().sum::<f64>() / fast.len() as f64;
+    }
+    Some((low + high) / 2.0)
+}
+
+/// Work and recovery from the lap intensity when the device recorded it,
+/// otherwise from lap speed
+fn segments_from_laps(laps: &[GarminLap]) -> Vec<Segment> {
+    let is_intensity = |lap: &GarminLap, intensity: &str| {
+        lap.lap_intensity
+            .as_ref()
+            .is_some_and(|i| i.eq_ignore_ascii_case(intensity))
+    };
+    let use_intensity = laps.iter().any(|lap| is_intensity(lap, "active"))
+        && laps.iter().any(|lap| is_intensity(lap, "resting"));
+    let speeds: Vec<_> = laps
+        .iter()
+        .filter(|lap| lap.lap_duration > 0.0)
+        .map(|lap| lap.lap_distance / lap.lap_duration)
+        .collect();
+    let threshold = speed_threshold(&speeds).unwrap_or(f64::INFINITY);
+    laps.iter()
+        .filter(|lap| lap.lap_duration > 0.0)
+        .map(|lap| {
+            let fast = if use_intensity {
+                is_intensity(lap, "active")
+            } else {
+                lap.lap_distance / lap.lap_duration >= threshold
+            };
+            Segment {
+                fast,
+                distance: lap.lap_distance,
+                duration: lap.lap_duration,
+                avg_heart_rate: lap.lap_avg_hr.filter(|hr| *hr > 0.0),
+            }
+        })
+        .collect()
+}
+
+/// Fast and slow stretches of the smoothed point speed, for workouts
+/// recorded as a single lap
+fn segments_from_points(gfile: &GarminFile) -> Vec<Segment> {
+    let samples: Vec<_> = gfile
+        .points
+        .iter()
+        .filter_map(|p| Some((p.duration_from_begin, p.distance?, p.heart_rate)))
+        .collect();
+    if samples.len() < 3 {
+        return Vec::new();
+    }
+    // speed over the trailing window ending at each sample after the first
+    let mut start = 0;
+    let speeds: Vec<_> = samples
+        .iter()
+        .skip(1)
+        .map(|(t, d, _)| {
+            while t - samples[start].0 > SMOOTHING_SECONDS {
+                start += 1;
+            }
+            let (t0, d0, _) = samples[start];
+            if *t > t0 {
+                (d - d0) / (t - t0)
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let threshold = match speed_threshold(&speeds) {
+        Some(threshold) => threshold,
+        None => return Vec::new(),
+    };
+
+    let mut runs: Vec<Segment> = Vec::new();
+    for (window, speed) in samples.windows(2).zip(&speeds) {
+        let (t0, d0, _) = window[0];
+        let (t1, d1, hr) = window[1];
+        let step = Segment {
+            fast: *speed >= threshold,
+            distance: d1 - d0,
+            duration: t1 - t0,
+            avg_heart_rate: hr.filter(|hr| *hr > 0.0),
+        };
+        match runs.last_mut() {
+            Some(last) if last.fast == step.fast => last.absorb(&step),
+            _ => runs.push(step),
+        }
+    }
+    // brief surges and stops belong to the segment around them
+    let mut segments: Vec<Segment> = Vec::with_capacity(runs.len());
+    for run in runs {
+        match segments.last_mut() {
+            Some(last) if last.fast == run.fast || run.duration < MIN_SEGMENT_SECONDS => {
+                last.absorb(&run);
+            }
+            _ => segments.push(run),
+        }
+    }
+    segments
+}
+
+/// Label `segments` as warmup, work, recovery and cooldown, `None` unless
+/// there are enough work segments clearly faster than the recoveries
+fn classify(segments: &[Segment]) -> Option<Vec<Interval>> {
+    let first_work = segments.iter().position(|s| s.fast)?;
+    let last_work = segments.iter().rposition(|s| s.fast)?;
+    let work: Vec<_> = segments.iter().filter(|s| s.fast).collect();
+    let recovery: Vec<_> = segments[first_work..=last_work]
+        .iter()
+        .filter(|s| !s.fast)
+        .collect();
+    if work.len() < MIN_REPS || recovery.len() + 1 < work.len() {
+        return None;
+    }
+    let avg_speed = |segments: &[&Segment]| {
+        let distance: f64 = segments.iter().map(|s| s.distance).sum();
+        let duration: f64 = segments.iter().map(|s| s.duration).sum();
+        distance / duration
+    };
+    if avg_speed(&work) < MIN_SPEED_RATIO * avg_speed(&recovery) {
+        return None;
+    }
+    let mut rep = 0;
+    let intervals = segments
+        .iter()
+        .enumerate()
+        .map(|(idx, segment)| {
+            let kind = if segment.fast {
+                IntervalKind::Work
+            } else if idx < first_work {
+                IntervalKind::Warmup
+            } else if idx > last_work {
+                IntervalKind::Cooldown
+            } else {
+                IntervalKind::Recovery
+            };
+            let rep = if segment.fast {
+                rep += 1;
+                Some(rep)
+            } else {
+                None
+            };
+            Interval {
+                rep,
+                kind,
+                distance: segment.distance,
+                duration: segment.duration,
+                avg_heart_rate: segment.avg_heart_rate,
+            }
+        })
+        .collect();
+    Some(intervals)
+}
+
+/// Intervals of a structured workout, from the laps when the workout was
+/// lapped and otherwise detected from the pace, `None` if the activity
+/// doesn't look like an interval session
+#[must_use]
+pub fn get_intervals(gfile: &GarminFile) -> Option<(IntervalSource, Vec<Interval>)> {
+    if gfile.laps.len() >= 2 * MIN_REPS - 1 {
+        if let Some(intervals) = classify(&segments_from_laps(&gfile.laps)) {
+            return Some((IntervalSource::Laps, intervals));
+        }
+    }
+    classify(&segments_from_points(gfile)).map(|i| (IntervalSource::Detected, i))
+}
+
+/// Text table of the intervals of `gfile`, empty if it isn't an interval
+/// workout
+/// # Errors
+/// Return error if formatting times fails
+pub fn print_intervals(gfile: &GarminFile) -> Result<Vec<StackString>, Error> {
+    let intervals = match get_intervals(gfile) {
+        Some((_, intervals)) => intervals,
+        None => return Ok(Vec::new()),
+    };
+    let mut lines = vec![format_sstr!(
+        "{:>4} {:>9} {:>8} {:>9} {:>8} {:>8}",
+        "rep",
+        "type",
+        "mi",
+        "time",
+        "bpm",
+        "min/mi"
+    )];
+    for interval in &intervals {
+        let rep = interval
+            .rep
+            .map_or_else(StackString::new, StackString::from_display);
+        let hr = interval
+            .avg_heart_rate
+            .map_or_else(StackString::new, |hr| format_sstr!("{hr:.0}"));
+        let pace = match interval.pace() {
+            Some(pace) => print_h_m_s(pace, false)?,
+            None => StackString::new(),
+        };
+        lines.push(format_sstr!(
+            "{rep:>4} {:>9} {:>8.2} {:>9} {hr:>8} {pace:>8}",
+            interval.kind,
+            interval.distance / METERS_PER_MILE,
+            print_h_m_s(interval.duration, false)?,
+        ));
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use garmin_models::{
+        garmin_file::GarminFile, garmin_lap::GarminLap, garmin_point::GarminPoint,
+    };
+
+    use crate::interval_workout::{get_intervals, IntervalKind, IntervalSource};
+
+    fn lap(distance: f64, duration: f64, hr: f64) -> GarminLap {
+        GarminLap {
+            lap_distance: distance,
+            lap_duration: duration,
+            lap_avg_hr: Some(hr),
+            ..GarminLap::new()
+        }
+    }
+
+    #[test]
+    fn test_lap_intervals() {
+        let laps = vec![
+            lap(1609.0, 540.0, 130.0),
+            lap(400.0, 80.0, 170.0),
+            lap(200.0, 90.0, 140.0),
+            lap(400.0, 81.0, 172.0),
+            lap(200.0, 90.0, 142.0),
+            lap(400.0, 79.0, 175.0),
+            lap(200.0, 90.0, 143.0),
+            lap(400.0, 80.0, 176.0),
+            lap(1609.0, 560.0, 135.0),
+        ];
+        let mut gfile = GarminFile {
+            laps,
+            ..GarminFile::default()
+        };
+        let (source, intervals) = get_intervals(&gfile).unwrap();
+        assert_eq!(source, IntervalSource::Laps);
+        assert_eq!(intervals.len(), 9);
+        assert_eq!(intervals[0].kind, IntervalKind::Warmup);
+        assert_eq!(intervals[8].kind, IntervalKind::Cooldown);
+        assert_eq!(intervals[2].kind, IntervalKind::Recovery);
+        let reps: Vec<_> = intervals.iter().filter_map(|i| i.rep).collect();
+        assert_eq!(reps, vec![1, 2, 3, 4]);
+        assert_abs_diff_eq!(
+            intervals[1].pace().unwrap(),
+            80.0 / (400.0 / 1609.344),
+            epsilon = 1e-6
+        );
+
+        // steady mile laps aren't intervals
+        gfile.laps = (0..6)
+            .map(|i| lap(1609.0, 480.0 + f64::from(i) * 5.0, 150.0))
+            .collect();
+        assert!(get_intervals(&gfile).is_none());
+    }
+
+    #[test]
+    fn test_detected_intervals() {
+        // 5 minutes easy, then 4 x (2 min fast, 2 min easy)
+        let mut distance = 0.0;
+        let points = (0..=1260)
+            .step_by(5)
+            .map(|t| {
+                let fast = t > 300 && (t - 300) % 240 < 120 && t <= 300 + 4 * 240;
+                let speed = if fast { 5.0 } else { 2.5 };
+                if t > 0 {
+                    distance += speed * 5.0;
+                }
+                GarminPoint {
+                    duration_from_begin: f64::from(t),
+                    distance: Some(distance),
+                    heart_rate: Some(if fast { 170.0 } else { 140.0 }),
+                    ..GarminPoint::new()
+                }
+            })
+            .collect();
+        let gfile = GarminFile {
+            laps: vec![lap(5000.0, 1500.0, 150.0)],
+            points,
+            ..GarminFile::default()
+        };
+        let (source, intervals) = get_intervals(&gfile).unwrap();
+        assert_eq!(source, IntervalSource::Detected);
+        let work: Vec<_> = intervals
+            .iter()
+            .filter(|i| i.kind == IntervalKind::Work)
+            .collect();
+        assert_eq!(work.len(), 4);
+        for interval in work {
+            assert_abs_diff_eq!(interval.duration, 120.0, epsilon = 30.0);
+            assert!(interval.avg_heart_rate.unwrap() > 155.0);
+        }
+        assert_eq!(intervals[0].kind, IntervalKind::Warmup);
+    }
+}
@@ -9,6 +9,12 @@ pub mod garmin_constraints;
 pub mod garmin_file_report_txt;
 pub mod garmin_report_options;
 pub mod garmin_summary_report_txt;
+pub mod interval_workout;
+pub mod pace_band;
+pub mod pace_planner;
+pub mod route_profile;
+pub mod route_progression;
+pub mod share_card;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,259 @@
+use anyhow::Error;
+use stack_string::{format_sstr, StackString};
+use std::fmt::Write;
+
+use garmin_utils::garmin_util::print_h_m_s;
+
+use crate::pace_planner::{PlannedSplit, SplitUnit};
+
+/// US letter in points
+const PAGE_WIDTH: f64 = 612.0;
+const PAGE_HEIGHT: f64 = 792.0;
+const MARGIN: f64 = 36.0;
+/// Width of one band, about two inches so it wraps around a wrist
+const BAND_WIDTH: f64 = 150.0;
+const BAND_GAP: f64 = 18.0;
+const HEADER_HEIGHT: f64 = 32.0;
+const ROW_HEIGHT: f64 = 12.0;
+const FONT_SIZE: f64 = 8.0;
+/// Left edge of the distance, pace, elapsed and grade columns within a band
+const COLUMNS: [f64; 4] = [6.0, 36.0, 74.0, 118.0];
+
+/// Text for a pdf string literal, the standard fonts only cover ascii
+fn pdf_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+fn text(buf: &mut String, font: &str, size: f64, x: f64, y: f64, s: &str) {
+    writeln!(
+        buf,
+        "BT /{font} {size:.1} Tf {x:.1} {y:.1} Td ({}) Tj ET",
+        pdf_string(s)
+    )
+    .ok();
+}
+
+/// Distance marker for the end of a split, whole units unless it's the
+/// short final split
+fn split_marker(split: &PlannedSplit, unit: SplitUnit) -> StackString {
+    let distance = split.distance / unit.meters();
+    if (distance - distance.round()).abs() < 0.01 {
+        format_sstr!("{distance:.0}")
+    } else {
+        format_sstr!("{distance:.1}")
+    }
+}
+
+/// One band: a header with `title` then a row per split with the distance
+/// marker, split pace, elapsed time and (for a hilly course) the grade
+fn draw_band(
+    buf: &mut String,
+    x: f64,
+    title: &str,
+    splits: &[PlannedSplit],
+    unit: SplitUnit,
+    show_grade: bool,
+) -> Result<(), Error> {
+    let label = unit.label();
+    let height = HEADER_HEIGHT + ROW_HEIGHT * splits.len() as f64;
+    let top = PAGE_HEIGHT - MARGIN;
+    writeln!(
+        buf,
+        "0.5 w 0 G {x:.1} {:.1} {BAND_WIDTH:.1} {height:.1} re S",
+        top - height
+    )
+    .ok();
+
+    text(buf, "F2", 9.0, x + COLUMNS[0], top - 12.0, title);
+    let subtitle = if show_grade {
+        format_sstr!("min/{label}, grade adjusted")
+    } else {
+        format_sstr!("min/{label}, even effort")
+    };
+    text(buf, "F1", 7.0, x + COLUMNS[0], top - 21.0, &subtitle);
+    let headings = [label, "pace", "elapsed", "grade"];
+    let headings = if show_grade {
+        &headings[..]
+    } else {
+        &headings[..3]
+    };
+    for (column, heading) in COLUMNS.iter().zip(headings) {
+        text(
+            buf,
+            "F2",
+            7.0,
+            x + column,
+            top - HEADER_HEIGHT + 3.0,
+            heading,
+        );
+    }
+
+    for (idx, split) in splits.iter().enumerate() {
+        let row_top = top - HEADER_HEIGHT - ROW_HEIGHT * idx as f64;
+        let y = row_top - ROW_HEIGHT + 3.5;
+        // shade every other row so lines are easy to follow mid race
+        if idx % 2 == 1 {
+            writeln!(
+                buf,
+                "0.9 g {x:.1} {:.1} {BAND_WIDTH:.1} {ROW_HEIGHT:.1} re f 0 g",
+                row_top - ROW_HEIGHT
+            )
+            .ok();
+        }
+        let mut values = vec![
+            split_marker(split, unit),
+            print_h_m_s(split.pace(unit), false)?,
+            print_h_m_s(split.elapsed_time, true)?,
+        ];
+        if show_grade {
+            values.push(format_sstr!("{:+.1}%", split.grade * 100.0));
+        }
+        for (column, value) in COLUMNS.iter().zip(&values) {
+            text(buf, "F1", FONT_SIZE, x + column, y, value);
+        }
+    }
+    Ok(())
+}
+
+/// Printable pace band for `splits` as a pdf, a bordered strip per column
+/// to be cut out, long races continue onto further strips and pages
+/// # Errors
+/// Return error if formatting times fails
+pub fn splits_to_pace_band(
+    title: &str,
+    splits: &[PlannedSplit],
+    unit: SplitUnit,
+) -> Result<Vec<u8>, Error> {
+    let show_grade = splits
+        .iter()
+        .any(|s| s.elevation_gain > 0.0 || s.elevation_loss > 0.0);
+    let rows_per_band =
+        ((PAGE_HEIGHT - 2.0 * MARGIN - HEADER_HEIGHT) / ROW_HEIGHT).floor() as usize;
+    let bands_per_page =
+        ((PAGE_WIDTH - 2.0 * MARGIN + BAND_GAP) / (BAND_WIDTH + BAND_GAP)).floor() as usize;
+
+    let mut pages = Vec::new();
+    let bands: Vec<_> = splits.chunks(rows_per_band).collect();
+    for page_bands in bands.chunks(bands_per_page) {
+        let mut content = String::new();
+        for (idx, band) in page_bands.iter().enumerate() {
+            let x = MARGIN + (BAND_WIDTH + BAND_GAP) * idx as f64;
+            draw_band(&mut content, x, title, band, unit, show_grade)?;
+        }
+        pages.push(content);
+    }
+    if pages.is_empty() {
+        pages.push(String::new());
+    }
+    Ok(write_pdf(&pages))
+}
+
+/// Minimal pdf with one page per content stream, using the builtin
+/// Helvetica fonts as F1 and F2 (bold)
+fn write_pdf(pages: &[String]) -> Vec<u8> {
+    let kids: Vec<_> = (0..pages.len())
+        .map(|idx| format_sstr!("{} 0 R", 5 + 2 * idx))
+        .collect();
+    let mut objects: Vec<StackString> = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".into(),
+        format_sstr!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".into(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold >>".into(),
+    ];
+    for (idx, content) in pages.iter().enumerate() {
+        objects.push(format_sstr!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH:.0} {PAGE_HEIGHT:.0}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            6 + 2 * idx
+        ));
+        objects.push(format_sstr!(
+            "<< /Length {} >>\nstream\n{content}\nendstream",
+            content.len()
+        ));
+    }
+
+    let mut buf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (idx, object) in objects.iter().enumerate() {
+        offsets.push(buf.len());
+        buf.extend_from_slice(format_sstr!("{} 0 obj\n", idx + 1).as_bytes());
+        buf.extend_from_slice(object.as_bytes());
+        buf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_offset = buf.len();
+    let mut xref = String::new();
+    writeln!(xref, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1).ok();
+    for offset in offsets {
+        writeln!(xref, "{offset:010} 00000 n ").ok();
+    }
+    write!(
+        xref,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+        objects.len() + 1
+    )
+    .ok();
+    buf.extend_from_slice(xref.as_bytes());
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::{
+        pace_band::{pdf_string, splits_to_pace_band},
+        pace_planner::{plan_splits, SplitUnit},
+    };
+
+    #[test]
+    fn test_splits_to_pace_band() -> Result<(), Error> {
+        assert_eq!(pdf_string("a (b) \\ é").as_str(), "a \\(b\\) \\\\ ?");
+
+        let splits = plan_splits(&[], 42195.0, 3.5 * 3600.0, SplitUnit::Kilometer);
+        let pdf = splits_to_pace_band("Marathon 3:30:00", &splits, SplitUnit::Kilometer)?;
+        let text = String::from_utf8(pdf)?;
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("(Marathon 3:30:00) Tj"));
+        assert!(text.contains("(42.2) Tj"));
+        assert!(text.contains("(04:58) Tj"));
+        assert!(text.contains("(00:04:58) Tj"));
+        assert!(text.contains("even effort"));
+        assert!(text.contains("/Count 1"));
+
+        // every xref entry points at the start of its object
+        let xref = text.find("\nxref\n").unwrap() + 1;
+        let startxref: usize = text
+            .split("startxref\n")
+            .nth(1)
+            .and_then(|s| s.lines().next())
+            .unwrap()
+            .parse()?;
+        assert_eq!(startxref, xref);
+        for (idx, line) in text[xref..].lines().skip(3).take(6).enumerate() {
+            let offset: usize = line[..10].parse()?;
+            assert!(text[offset..].starts_with(&format!("{} 0 obj", idx + 1)));
+        }
+
+        // 250 km splits need more strips than fit on a page
+        let splits = plan_splits(&[], 250_000.0, 30.0 * 3600.0, SplitUnit::Kilometer);
+        let pdf = String::from_utf8(splits_to_pace_band("250k", &splits, SplitUnit::Kilometer)?)?;
+        assert!(pdf.contains("/Count 2"));
+        Ok(())
+    }
+}
@@ -0,0 +1,488 @@
+use anyhow::{format_err, Error};
+use roxmltree::Document;
+use stack_string::{format_sstr, StackString};
+use std::{fmt, str::FromStr};
+
+use garmin_models::garmin_file::GarminFile;
+use garmin_utils::{
+    fit_encode::{
+        fit_definition, fit_file, fit_string, FIT_BASE_ENUM, FIT_BASE_STRING, FIT_BASE_UINT16,
+        FIT_BASE_UINT32,
+    },
+    garmin_util::{haversine_distance, print_h_m_s, METERS_PER_MILE},
+};
+
+/// Steepest grade taken into account when adjusting splits, steeper sections
+/// are treated as this grade
+const MAX_GRADE: f64 = 0.15;
+/// Fractional slowdown per unit uphill grade
+const UPHILL_COST: f64 = 3.3;
+/// Fractional speedup per unit downhill grade
+const DOWNHILL_BENEFIT: f64 = 1.8;
+/// Width of the FIT workout speed target around the planned split speed
+const TARGET_SPEED_TOLERANCE: f64 = 0.02;
+
+/// Elevation profile sample, `distance` in meters from the start of the
+/// course and `altitude` in meters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoursePoint {
+    pub distance: f64,
+    pub altitude: f64,
+}
+
+/// Elevation profile of a recorded activity
+#[must_use]
+pub fn course_from_gfile(gfile: &GarminFile) -> Vec<CoursePoint> {
+    gfile
+        .points
+        .iter()
+        .filter_map(|p| {
+            Some(CoursePoint {
+                distance: p.distance?,
+                altitude: p.altitude?,
+            })
+        })
+        .collect()
+}
+
+/// Elevation profile of the track or route points of a GPX file
+/// # Errors
+/// Return error if the xml is malformed or has no points with elevation
+pub fn course_from_gpx(data: &str) -> Result<Vec<CoursePoint>, Error> {
+    let doc = Document::parse(data)?;
+    let mut course = Vec::new();
+    let mut distance = 0.0;
+    let mut last: Option<(f64, f64)> = None;
+    for node in doc
+        .descendants()
+        .filter(|n| matches!(n.tag_name().name(), "trkpt" | "rtept"))
+    {
+        let lat: f64 = match node.attribute("lat").and_then(|x| x.parse().ok()) {
+            Some(lat) => lat,
+            None => continue,
+        };
+        let lon: f64 = match node.attribute("lon").and_then(|x| x.parse().ok()) {
+            Some(lon) => lon,
+            None => continue,
+        };
+        let altitude: Option<f64> = node
+            .children()
+            .find(|c| c.tag_name().name() == "ele")
+            .and_then(|c| c.text())
+            .and_then(|x| x.trim().parse().ok());
+        if let Some((last_lat, last_lon)) = last.replace((lat, lon)) {
+            distance += haversine_distance(last_lat, last_lon, lat, lon);
+        }
+        if let Some(altitude) = altitude {
+            course.push(CoursePoint { distance, altitude });
+        }
+    }
+    if course.is_empty() {
+        return Err(format_err!("No points with elevation found"));
+    }
+    Ok(course)
+}
+
+/// Relative pace multiplier for running at `grade` (rise over run)
+#[must_use]
+pub fn grade_factor(grade: f64) -> f64 {
+    let grade = grade.clamp(-MAX_GRADE, MAX_GRADE);
+    if grade >= 0.0 {
+        1.0 + UPHILL_COST * grade
+    } else {
+        1.0 + DOWNHILL_BENEFIT * grade
+    }
+}
+
+/// Altitude at `distance` meters along `course`, interpolated between the
+/// surrounding points, `None` past the end of the course
+#[must_use]
+pub fn course_altitude(course: &[CoursePoint], distance: f64) -> Option<f64> {
+    let idx = course.partition_point(|p| p.distance < distance);
+    let next = course.get(idx)?;
+    match idx.checked_sub(1).and_then(|i| course.get(i)) {
+        Some(prev) if next.distance > prev.distance => Some(
+            prev.altitude
+                + (next.altitude - prev.altitude) * (distance - prev.distance)
+                    / (next.distance - prev.distance),
+        ),
+        _ => Some(next.altitude),
+    }
+}
+
+/// Flat distance in meters taking the same effort as `course`, walked in
+/// ~100m steps like the planned splits
+#[must_use]
+pub fn grade_adjusted_distance(course: &[CoursePoint]) -> f64 {
+    let (start, end) = match (course.first(), course.last()) {
+        (Some(first), Some(last)) if last.distance > first.distance => {
+            (first.distance, last.distance)
+        }
+        _ => return 0.0,
+    };
+    let steps = ((end - start) / 100.0).ceil().max(1.0) as usize;
+    let step_length = (end - start) / steps as f64;
+    (0..steps)
+        .map(|step| {
+            let d0 = start + step as f64 * step_length;
+            let rise = match (
+                course_altitude(course, d0),
+                course_altitude(course, d0 + step_length),
+            ) {
+                (Some(a0), Some(a1)) => a1 - a0,
+                _ => 0.0,
+            };
+            step_length * grade_factor(rise / step_length)
+        })
+        .sum()
+}
+
+/// Grade adjusted pace of a recorded activity in seconds per meter, `None`
+/// without distance and altitude
+#[must_use]
+pub fn grade_adjusted_pace(gfile: &GarminFile) -> Option<f64> {
+    let distance = grade_adjusted_distance(&course_from_gfile(gfile));
+    if distance > 0.0 && gfile.total_duration > 0.0 {
+        Some(gfile.total_duration / distance)
+    } else {
+        None
+    }
+}
+
+/// Length of each planned split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitUnit {
+    Mile,
+    Kilometer,
+}
+
+impl Default for SplitUnit {
+    fn default() -> Self {
+        Self::Mile
+    }
+}
+
+impl SplitUnit {
+    #[must_use]
+    pub fn meters(self) -> f64 {
+        match self {
+            Self::Mile => METERS_PER_MILE,
+            Self::Kilometer => 1000.0,
+        }
+    }
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Mile => "mi",
+            Self::Kilometer => "km",
+        }
+    }
+}
+
+impl fmt::Display for SplitUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl FromStr for SplitUnit {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mi" | "mile" | "miles" => Ok(Self::Mile),
+            "km" | "kilometer" | "kilometers" => Ok(Self::Kilometer),
+            _ => Err(format_err!("{s} is not a valid split unit")),
+        }
+    }
+}
+
+/// Target for one split of a race, distances in meters and times in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlannedSplit {
+    pub split: usize,
+    pub distance: f64,
+    pub length: f64,
+    pub elevation_gain: f64,
+    pub elevation_loss: f64,
+    pub grade: f64,
+    pub split_time: f64,
+    pub elapsed_time: f64,
+}
+
+impl PlannedSplit {
+    /// Target pace in seconds per `unit`
+    #[must_use]
+    pub fn pace(&self, unit: SplitUnit) -> f64 {
+        self.split_time * unit.meters() / self.length
+    }
+}
+
+/// Split `race_distance` into `unit` sized splits and distribute
+/// `target_time` between them in proportion to the grade adjusted effort of
+/// each split, `course` is stretched to the race distance and may be empty
+/// for a flat course
+#[must_use]
+pub fn plan_splits(
+    course: &[CoursePoint],
+    race_distance: f64,
+    target_time: f64,
+    unit: SplitUnit,
+) -> Vec<PlannedSplit> {
+    if race_distance <= 0.0 || target_time <= 0.0 {
+        return Vec::new();
+    }
+    let course_length = course.last().map_or(0.0, |p| p.distance);
+    let scale = if course_length > 0.0 {
+        race_distance / course_length
+    } else {
+        1.0
+    };
+    let altitude_at = |distance: f64| course_altitude(course, distance / scale);
+
+    let mut splits = Vec::new();
+    let mut start = 0.0;
+    while start < race_distance - 1.0 {
+        let end = (start + unit.meters()).min(race_distance);
+        let length = end - start;
+        // walk the split in ~100m steps to pick up rolling terrain
+        let steps = (length / 100.0).ceil().max(1.0) as usize;
+        let step_length = length / steps as f64;
+        let mut effort = 0.0;
+        let mut elevation_gain = 0.0;
+        let mut elevation_loss = 0.0;
+        for step in 0..steps {
+            let d0 = start + step as f64 * step_length;
+            let d1 = d0 + step_length;
+            let rise = match (altitude_at(d0), altitude_at(d1)) {
+                (Some(a0), Some(a1)) => a1 - a0,
+                _ => 0.0,
+            };
+            if rise > 0.0 {
+                elevation_gain += rise;
+            } else {
+                elevation_loss -= rise;
+            }
+            effort += step_length * grade_factor(rise / step_length);
+        }
+        splits.push(PlannedSplit {
+            split: splits.len() + 1,
+            distance: end,
+            length,
+            elevation_gain,
+            elevation_loss,
+            grade: (elevation_gain - elevation_loss) / length,
+            split_time: effort,
+            elapsed_time: 0.0,
+        });
+        start = end;
+    }
+
+    let total_effort: f64 = splits.iter().map(|s| s.split_time).sum();
+    let mut elapsed_time = 0.0;
+    for split in &mut splits {
+        split.split_time *= target_time / total_effort;
+        elapsed_time += split.split_time;
+        split.elapsed_time = elapsed_time;
+    }
+    splits
+}
+
+/// Printable table of `splits`
+/// # Errors
+/// Return error if formatting times fails
+pub fn splits_to_text(splits: &[PlannedSplit], unit: SplitUnit) -> Result<StackString, Error> {
+    let label = unit.label();
+    let mut lines = vec![format_sstr!(
+        "{:>5} {:>8} {:>8} {:>8} {:>10} {:>10} {:>10}",
+        "split",
+        label,
+        "gain m",
+        "loss m",
+        format_sstr!("min/{label}"),
+        "split",
+        "elapsed"
+    )];
+    for split in splits {
+        lines.push(format_sstr!(
+            "{:>5} {:>8.2} {:>8.0} {:>8.0} {:>10} {:>10} {:>10}",
+            split.split,
+            split.distance / unit.meters(),
+            split.elevation_gain,
+            split.elevation_loss,
+            print_h_m_s(split.pace(unit), false)?,
+            print_h_m_s(split.split_time, false)?,
+            print_h_m_s(split.elapsed_time, true)?,
+        ));
+    }
+    Ok(lines.join("\n").into())
+}
+
+/// Encode `splits` as a FIT running workout with one distance step per split
+/// targeting the planned speed
+#[must_use]
+pub fn splits_to_fit_workout(name: &str, splits: &[PlannedSplit]) -> Vec<u8> {
+    const NAME_SIZE: u8 = 16;
+    let mut data = Vec::new();
+
+    // file_id: type=workout, manufacturer=development
+    fit_definition(
+        &mut data,
+        0,
+        0,
+        &[
+            (0, 1, FIT_BASE_ENUM),
+            (1, 2, FIT_BASE_UINT16),
+            (2, 2, FIT_BASE_UINT16),
+        ],
+    );
+    data.push(0);
+    data.push(5);
+    data.extend_from_slice(&255u16.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+
+    // workout: sport=running
+    fit_definition(
+        &mut data,
+        1,
+        26,
+        &[
+            (4, 1, FIT_BASE_ENUM),
+            (6, 2, FIT_BASE_UINT16),
+            (8, NAME_SIZE, FIT_BASE_STRING),
+        ],
+    );
+    data.push(1);
+    data.push(1);
+    data.extend_from_slice(&(splits.len() as u16).to_le_bytes());
+    fit_string(&mut data, name, usize::from(NAME_SIZE));
+
+    // workout_step: duration_type=distance, target_type=speed with a custom
+    // range, intensity=active
+    fit_definition(
+        &mut data,
+        2,
+        27,
+        &[
+            (254, 2, FIT_BASE_UINT16),
+            (1, 1, FIT_BASE_ENUM),
+            (2, 4, FIT_BASE_UINT32),
+            (3, 1, FIT_BASE_ENUM),
+            (4, 4, FIT_BASE_UINT32),
+            (5, 4, FIT_BASE_UINT32),
+            (6, 4, FIT_BASE_UINT32),
+            (7, 1, FIT_BASE_ENUM),
+        ],
+    );
+    for (idx, split) in splits.iter().enumerate() {
+        let speed = split.length / split.split_time;
+        let low = (speed * (1.0 - TARGET_SPEED_TOLERANCE) * 1000.0) as u32;
+        let high = (speed * (1.0 + TARGET_SPEED_TOLERANCE) * 1000.0) as u32;
+        data.push(2);
+        data.extend_from_slice(&(idx as u16).to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&((split.length * 100.0) as u32).to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&low.to_le_bytes());
+        data.extend_from_slice(&high.to_le_bytes());
+        data.push(0);
+    }
+
+    fit_file(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use approx::assert_abs_diff_eq;
+
+    use garmin_utils::fit_encode::fit_crc;
+
+    use crate::pace_planner::{
+        course_from_gpx, grade_adjusted_distance, grade_factor, plan_splits, splits_to_fit_workout,
+        CoursePoint, SplitUnit,
+    };
+
+    #[test]
+    fn test_grade_adjusted_distance() {
+        let point = |distance, altitude| CoursePoint { distance, altitude };
+        let flat = [point(0.0, 10.0), point(1000.0, 10.0)];
+        assert_abs_diff_eq!(grade_adjusted_distance(&flat), 1000.0, epsilon = 1e-6);
+        // 5% grade up then back down
+        let hill = [point(0.0, 0.0), point(1000.0, 50.0), point(2000.0, 0.0)];
+        let expected = 1000.0 * grade_factor(0.05) + 1000.0 * grade_factor(-0.05);
+        assert_abs_diff_eq!(grade_adjusted_distance(&hill), expected, epsilon = 1e-6);
+        assert!(grade_adjusted_distance(&hill) > 2000.0);
+        assert_abs_diff_eq!(grade_adjusted_distance(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_plan_splits() {
+        let flat = plan_splits(&[], 5000.0, 1500.0, SplitUnit::Kilometer);
+        assert_eq!(flat.len(), 5);
+        for split in &flat {
+            assert_abs_diff_eq!(split.split_time, 300.0, epsilon = 1e-6);
+        }
+        assert_abs_diff_eq!(flat[4].elapsed_time, 1500.0, epsilon = 1e-6);
+
+        // climb 50m over the second km, descend it over the third
+        let course = [
+            CoursePoint {
+                distance: 0.0,
+                altitude: 100.0,
+            },
+            CoursePoint {
+                distance: 1000.0,
+                altitude: 100.0,
+            },
+            CoursePoint {
+                distance: 2000.0,
+                altitude: 150.0,
+            },
+            CoursePoint {
+                distance: 3000.0,
+                altitude: 100.0,
+            },
+        ];
+        let hilly = plan_splits(&course, 3000.0, 900.0, SplitUnit::Kilometer);
+        assert_eq!(hilly.len(), 3);
+        assert_abs_diff_eq!(hilly[1].elevation_gain, 50.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(hilly[2].elevation_loss, 50.0, epsilon = 1e-6);
+        assert!(hilly[1].split_time > hilly[0].split_time);
+        assert!(hilly[2].split_time < hilly[0].split_time);
+        assert_abs_diff_eq!(hilly[2].elapsed_time, 900.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(
+            hilly[1].split_time / hilly[0].split_time,
+            grade_factor(0.05),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn test_course_from_gpx() -> Result<(), Error> {
+        let gpx = r#"<?xml version="1.0"?>
+            <gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+              <trk><trkseg>
+                <trkpt lat="40.0" lon="-74.0"><ele>10.0</ele></trkpt>
+                <trkpt lat="40.001" lon="-74.0"><ele>12.5</ele></trkpt>
+              </trkseg></trk>
+            </gpx>"#;
+        let course = course_from_gpx(gpx)?;
+        assert_eq!(course.len(), 2);
+        assert_abs_diff_eq!(course[1].distance, 111.19, epsilon = 0.01);
+        assert_abs_diff_eq!(course[1].altitude, 12.5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_splits_to_fit_workout() {
+        let splits = plan_splits(&[], 2000.0, 600.0, SplitUnit::Kilometer);
+        let fit = splits_to_fit_workout("test plan", &splits);
+        assert_eq!(&fit[8..12], b".FIT");
+        let data_size = u32::from_le_bytes([fit[4], fit[5], fit[6], fit[7]]) as usize;
+        assert_eq!(fit.len(), 14 + data_size + 2);
+        // crc over the whole file including its trailing crc is zero
+        assert_eq!(fit_crc(&fit), 0);
+    }
+}
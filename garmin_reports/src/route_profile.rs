@@ -0,0 +1,215 @@
+use stack_string::{format_sstr, StackString};
+use std::fmt::Write;
+
+use garmin_models::elevation_profile::downsample;
+
+use crate::pace_planner::{
+    grade_adjusted_distance, plan_splits, CoursePoint, PlannedSplit, SplitUnit,
+};
+
+/// Size of the profile drawn on the route page
+pub const ROUTE_PROFILE_WIDTH: f64 = 800.0;
+pub const ROUTE_PROFILE_HEIGHT: f64 = 200.0;
+/// Number of points in the drawn profile
+pub const ROUTE_PROFILE_POINTS: usize = 200;
+/// Number of recent runs the typical grade adjusted pace is taken from
+pub const ROUTE_PACE_ACTIVITIES: usize = 10;
+/// Altitude change in meters ignored as gps / barometer noise when summing
+/// the climb
+pub const ELEVATION_THRESHOLD: f64 = 3.0;
+
+/// Typical pace in seconds per meter, the median of the grade adjusted pace
+/// of recent runs
+#[must_use]
+pub fn typical_pace(paces: &[f64]) -> Option<f64> {
+    let mut paces: Vec<_> = paces.iter().copied().filter(|p| *p > 0.0).collect();
+    if paces.is_empty() {
+        return None;
+    }
+    paces.sort_by(f64::total_cmp);
+    let mid = paces.len() / 2;
+    if paces.len() % 2 == 0 {
+        Some((paces[mid - 1] + paces[mid]) / 2.0)
+    } else {
+        Some(paces[mid])
+    }
+}
+
+/// Total climb and descent in meters, only counting changes of at least
+/// `ELEVATION_THRESHOLD` from the last counted altitude so noise in the
+/// recorded altitude doesn't add up
+#[must_use]
+pub fn elevation_change(course: &[CoursePoint]) -> (f64, f64) {
+    let mut reference = match course.first() {
+        Some(p) => p.altitude,
+        None => return (0.0, 0.0),
+    };
+    let mut gain = 0.0;
+    let mut loss = 0.0;
+    for p in course {
+        let rise = p.altitude - reference;
+        if rise >= ELEVATION_THRESHOLD {
+            gain += rise;
+            reference = p.altitude;
+        } else if rise <= -ELEVATION_THRESHOLD {
+            loss -= rise;
+            reference = p.altitude;
+        }
+    }
+    (gain, loss)
+}
+
+/// Elevation profile and time estimate of a planned route, distances and
+/// altitudes in meters, times in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteProfile {
+    pub distance: f64,
+    pub elevation_gain: f64,
+    pub elevation_loss: f64,
+    pub min_altitude: f64,
+    pub max_altitude: f64,
+    /// Flat distance taking the same effort as the route
+    pub grade_adjusted_distance: f64,
+    /// Seconds per meter on the flat
+    pub pace: f64,
+    pub estimated_time: f64,
+    pub splits: Vec<PlannedSplit>,
+    /// Downsampled altitudes, evenly spaced along the route
+    pub altitudes: Vec<f64>,
+}
+
+impl RouteProfile {
+    /// Profile of `course` run at `pace` seconds per meter on the flat, split
+    /// into `unit` sized splits, `None` for a course without length
+    #[must_use]
+    pub fn from_course(course: &[CoursePoint], pace: f64, unit: SplitUnit) -> Option<Self> {
+        let distance = course.last()?.distance;
+        if distance <= 0.0 {
+            return None;
+        }
+        let (elevation_gain, elevation_loss) = elevation_change(course);
+        let min_altitude = course.iter().map(|p| p.altitude).fold(f64::MAX, f64::min);
+        let max_altitude = course.iter().map(|p| p.altitude).fold(f64::MIN, f64::max);
+        let grade_adjusted_distance = grade_adjusted_distance(course);
+        let estimated_time = grade_adjusted_distance * pace;
+        let splits = plan_splits(course, distance, estimated_time, unit);
+        let samples: Vec<_> = course.iter().map(|p| (p.distance, p.altitude)).collect();
+        let altitudes = downsample(&samples, ROUTE_PROFILE_POINTS);
+        Some(Self {
+            distance,
+            elevation_gain,
+            elevation_loss,
+            min_altitude,
+            max_altitude,
+            grade_adjusted_distance,
+            pace,
+            estimated_time,
+            splits,
+            altitudes,
+        })
+    }
+
+    /// Filled svg of the profile, the tooltip gives the altitude range in
+    /// meters
+    #[must_use]
+    pub fn to_svg(&self) -> Option<StackString> {
+        if self.altitudes.len() < 2 {
+            return None;
+        }
+        let range = self.max_altitude - self.min_altitude;
+        let step = ROUTE_PROFILE_WIDTH / (self.altitudes.len() - 1) as f64;
+        let mut path = format_sstr!("M0,{ROUTE_PROFILE_HEIGHT} ");
+        for (idx, altitude) in self.altitudes.iter().enumerate() {
+            let x = step * idx as f64;
+            // leave a margin above the highest point, flat routes along the
+            // middle
+            let y = if range > 0.0 {
+                ROUTE_PROFILE_HEIGHT * (1.0 - 0.9 * (altitude - self.min_altitude) / range)
+            } else {
+                ROUTE_PROFILE_HEIGHT / 2.0
+            };
+            write!(path, "L{x:.1},{y:.1} ").ok()?;
+        }
+        write!(path, "L{ROUTE_PROFILE_WIDTH},{ROUTE_PROFILE_HEIGHT} Z").ok()?;
+        Some(format_sstr!(
+            r#"<svg width="{w}" height="{h}" viewBox="0 0 {w} {h}"><title>{min:.0}m - {max:.0}m</title><path d="{path}" fill="lightsteelblue" stroke="steelblue" stroke-width="1"/></svg>"#,
+            w = ROUTE_PROFILE_WIDTH,
+            h = ROUTE_PROFILE_HEIGHT,
+            min = self.min_altitude,
+            max = self.max_altitude,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{
+        pace_planner::{grade_factor, CoursePoint, SplitUnit},
+        route_profile::{elevation_change, typical_pace, RouteProfile},
+    };
+
+    #[test]
+    fn test_route_profile() {
+        let point = |distance, altitude| CoursePoint { distance, altitude };
+        let course = [
+            point(0.0, 100.0),
+            point(1000.0, 150.0),
+            point(2000.0, 100.0),
+        ];
+        // 5:00 min/km on the flat
+        let profile = RouteProfile::from_course(&course, 0.3, SplitUnit::Kilometer).unwrap();
+        assert_abs_diff_eq!(profile.distance, 2000.0);
+        assert_abs_diff_eq!(profile.elevation_gain, 50.0);
+        assert_abs_diff_eq!(profile.elevation_loss, 50.0);
+        assert_abs_diff_eq!(profile.max_altitude, 150.0);
+        let expected = 300.0 * (grade_factor(0.05) + grade_factor(-0.05));
+        assert_abs_diff_eq!(profile.estimated_time, expected, epsilon = 1e-6);
+        assert_eq!(profile.splits.len(), 2);
+        assert!(profile.splits[0].split_time > profile.splits[1].split_time);
+        assert_abs_diff_eq!(profile.splits[1].elapsed_time, expected, epsilon = 1e-6);
+        assert!(profile.to_svg().unwrap().contains("100m - 150m"));
+
+        assert!(RouteProfile::from_course(&[point(0.0, 10.0)], 0.3, SplitUnit::Mile).is_none());
+    }
+
+    #[test]
+    fn test_elevation_change() {
+        let point = |distance, altitude| CoursePoint { distance, altitude };
+        // a flat course with a meter of noise either way
+        let noisy: Vec<_> = (0..100)
+            .map(|i| point(f64::from(i) * 10.0, if i % 2 == 0 { 99.0 } else { 101.0 }))
+            .collect();
+        let (gain, loss) = elevation_change(&noisy);
+        assert_abs_diff_eq!(gain, 0.0);
+        assert_abs_diff_eq!(loss, 0.0);
+
+        // a noisy climb still counts in full
+        let climb: Vec<_> = (0..=50)
+            .map(|i| {
+                point(
+                    f64::from(i) * 10.0,
+                    f64::from(i) + if i % 2 == 0 { 1.0 } else { 0.0 },
+                )
+            })
+            .collect();
+        let (gain, loss) = elevation_change(&climb);
+        assert!((47.0..=51.0).contains(&gain));
+        assert_abs_diff_eq!(loss, 0.0);
+
+        let (gain, _) = elevation_change(&[]);
+        assert_abs_diff_eq!(gain, 0.0);
+    }
+
+    #[test]
+    fn test_typical_pace() {
+        assert_eq!(typical_pace(&[]), None);
+        assert_eq!(typical_pace(&[0.3, 0.5, 0.2]), Some(0.3));
+        assert_abs_diff_eq!(
+            typical_pace(&[0.3, 0.5, 0.2, 0.4, 0.0]).unwrap(),
+            0.35,
+            epsilon = 1e-9
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::Error;
+use stack_string::{format_sstr, StackString};
+use std::fmt::Write;
+use time_tz::OffsetDateTimeExt;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_models::route_match::RouteAttempt;
+use garmin_utils::garmin_util::print_h_m_s;
+
+/// Size of the progression chart on the route page
+pub const PROGRESSION_WIDTH: f64 = 800.0;
+pub const PROGRESSION_HEIGHT: f64 = 300.0;
+/// Space left around the plotted points for labels
+const PROGRESSION_MARGIN: f64 = 60.0;
+
+/// Fastest attempt of a route in one year, `personal_best` when it was faster
+/// than every earlier year
+#[derive(Debug, Clone, PartialEq)]
+pub struct YearBest {
+    pub year: i32,
+    pub attempt: RouteAttempt,
+    pub attempts: usize,
+    pub personal_best: bool,
+}
+
+impl YearBest {
+    /// Conditions the attempt was done in, e.g. `12C (device) road`, the
+    /// temperature is the watch's sensor reading which runs warm from body
+    /// heat rather than the weather
+    #[must_use]
+    pub fn conditions(&self) -> StackString {
+        let mut conditions = Vec::new();
+        if let Some(temperature) = self.attempt.temperature {
+            conditions.push(format_sstr!("{temperature:.0}C (device)"));
+        }
+        if let Some(surface) = &self.attempt.surface {
+            if surface.as_str() != "unknown" {
+                conditions.push(surface.clone());
+            }
+        }
+        conditions.join(" ").into()
+    }
+}
+
+/// Best attempt in each year, oldest first, `attempts` must be ordered by
+/// date
+#[must_use]
+pub fn best_by_year(attempts: &[RouteAttempt]) -> Vec<YearBest> {
+    let local = DateTimeWrapper::local_tz();
+    let mut bests: Vec<YearBest> = Vec::new();
+    for attempt in attempts.iter().filter(|a| a.total_duration > 0.0) {
+        let year = attempt.begin_datetime.to_timezone(local).year();
+        match bests.last_mut() {
+            Some(best) if best.year == year => {
+                best.attempts += 1;
+                if attempt.total_duration < best.attempt.total_duration {
+                    best.attempt = attempt.clone();
+                }
+            }
+            _ => bests.push(YearBest {
+                year,
+                attempt: attempt.clone(),
+                attempts: 1,
+                personal_best: false,
+            }),
+        }
+    }
+    let mut fastest = f64::MAX;
+    for best in &mut bests {
+        if best.attempt.total_duration < fastest {
+            fastest = best.attempt.total_duration;
+            best.personal_best = true;
+        }
+    }
+    bests
+}
+
+/// Line chart of the best time against year, each point labeled with its time
+/// and conditions, personal bests drawn in red
+/// # Errors
+/// Return error if formatting a time fails
+pub fn progression_svg(bests: &[YearBest]) -> Result<Option<StackString>, Error> {
+    let (first, last) = match (bests.first(), bests.last()) {
+        (Some(first), Some(last)) => (first.year, last.year),
+        _ => return Ok(None),
+    };
+    let durations = bests.iter().map(|b| b.attempt.total_duration);
+    let min = durations.clone().fold(f64::MAX, f64::min);
+    let max = durations.fold(f64::MIN, f64::max);
+    let plot_width = PROGRESSION_WIDTH - 2.0 * PROGRESSION_MARGIN;
+    let plot_height = PROGRESSION_HEIGHT - 2.0 * PROGRESSION_MARGIN;
+    // a single year sits in the middle, equal times along the middle
+    let x = |year: i32| {
+        if last > first {
+            PROGRESSION_MARGIN + plot_width * f64::from(year - first) / f64::from(last - first)
+        } else {
+            PROGRESSION_WIDTH / 2.0
+        }
+    };
+    let y = |duration: f64| {
+        if max > min {
+            PROGRESSION_MARGIN + plot_height * (max - duration) / (max - min)
+        } else {
+            PROGRESSION_HEIGHT / 2.0
+        }
+    };
+
+    let mut path = StackString::new();
+    let mut points = StackString::new();
+    for (idx, best) in bests.iter().enumerate() {
+        let (px, py) = (x(best.year), y(best.attempt.total_duration));
+        let cmd = if idx == 0 { 'M' } else { 'L' };
+        write!(path, "{cmd}{px:.1},{py:.1} ")?;
+        let color = if best.personal_best {
+            "red"
+        } else {
+            "steelblue"
+        };
+        let time = print_h_m_s(best.attempt.total_duration, true)?;
+        write!(
+            points,
+            r#"<circle cx="{px:.1}" cy="{py:.1}" r="4" fill="{color}"><title>{date}</title></circle><text x="{px:.1}" y="{ty:.1}" font-size="11" text-anchor="middle">{year} {time}</text><text x="{px:.1}" y="{cy:.1}" font-size="10" text-anchor="middle" fill="gray">{conditions}</text>"#,
+            date = best.attempt.begin_datetime,
+            ty = py - 20.0,
+            cy = py - 8.0,
+            year = best.year,
+            conditions = best.conditions(),
+        )?;
+    }
+    Ok(Some(format_sstr!(
+        r#"<svg width="{w}" height="{h}" viewBox="0 0 {w} {h}"><path d="{path}" fill="none" stroke="steelblue" stroke-width="2"/>{points}</svg>"#,
+        w = PROGRESSION_WIDTH,
+        h = PROGRESSION_HEIGHT,
+        path = path.trim_end(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use approx::assert_abs_diff_eq;
+    use time::macros::datetime;
+    use uuid::Uuid;
+
+    use garmin_models::route_match::RouteAttempt;
+
+    use crate::route_progression::{best_by_year, progression_svg};
+
+    #[test]
+    fn test_best_by_year() -> Result<(), Error> {
+        let attempt =
+            |begin_datetime: time::OffsetDateTime, total_duration, surface: &str| RouteAttempt {
+                summary_id: Uuid::new_v4(),
+                filename: "".into(),
+                begin_datetime: begin_datetime.into(),
+                total_duration,
+                total_distance: 5000.0,
+                temperature: Some(12.0),
+                surface: Some(surface.into()),
+            };
+        let attempts = [
+            attempt(datetime!(2021-06-01 12:00 UTC), 1500.0, "road"),
+            attempt(datetime!(2021-07-01 12:00 UTC), 1450.0, "road"),
+            attempt(datetime!(2022-06-01 12:00 UTC), 1480.0, "trail"),
+            attempt(datetime!(2023-06-01 12:00 UTC), 1400.0, "unknown"),
+        ];
+        let bests = best_by_year(&attempts);
+        assert_eq!(bests.len(), 3);
+        assert_eq!(bests[0].year, 2021);
+        assert_eq!(bests[0].attempts, 2);
+        assert_abs_diff_eq!(bests[0].attempt.total_duration, 1450.0);
+        assert!(bests[0].personal_best);
+        assert!(!bests[1].personal_best);
+        assert!(bests[2].personal_best);
+        assert_eq!(bests[1].conditions().as_str(), "12C (device) trail");
+        assert_eq!(bests[2].conditions().as_str(), "12C (device)");
+
+        let svg = progression_svg(&bests)?.unwrap();
+        assert!(svg.contains("2021 00:24:10"));
+        assert_eq!(svg.matches(r#"fill="red""#).count(), 2);
+        assert!(progression_svg(&[])?.is_none());
+        Ok(())
+    }
+}
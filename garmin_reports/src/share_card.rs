@@ -0,0 +1,255 @@
+use anyhow::{format_err, Error};
+use once_cell::sync::Lazy;
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{fontdb::Database, Options, Tree},
+};
+use stack_string::{format_sstr, StackString};
+use std::{fmt::Write, sync::Arc};
+
+use garmin_models::garmin_file::GarminFile;
+use garmin_utils::{
+    garmin_util::{print_h_m_s, xml_escape, METERS_PER_MILE},
+    sport_types::SportTypes,
+};
+
+/// Square card, the size most social sites show without cropping
+pub const CARD_SIZE: f64 = 1080.0;
+const MARGIN: f64 = 80.0;
+/// The route is drawn between the title and the stats
+const ROUTE_TOP: f64 = 220.0;
+const ROUTE_BOTTOM: f64 = 820.0;
+/// Most points in the route outline
+const ROUTE_POINTS: usize = 1000;
+const FEET_PER_METER: f64 = 3.280_84;
+
+/// Bundled so the card text renders the same everywhere, including hosts
+/// and containers without any system fonts
+static DEJAVU_SANS: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+static DEJAVU_SANS_BOLD: &[u8] = include_bytes!("../fonts/DejaVuSans-Bold.ttf");
+const FONT_FAMILY: &str = "DejaVu Sans";
+
+static FONTS: Lazy<Arc<Database>> = Lazy::new(|| {
+    let mut fonts = Database::new();
+    fonts.load_font_data(DEJAVU_SANS.to_vec());
+    fonts.load_font_data(DEJAVU_SANS_BOLD.to_vec());
+    fonts.set_sans_serif_family(FONT_FAMILY);
+    Arc::new(fonts)
+});
+
+/// Numbers printed on the share card
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareCardStats {
+    pub title: StackString,
+    pub distance: StackString,
+    /// min/mi, or mph for biking
+    pub pace: StackString,
+    pub elevation_gain: StackString,
+}
+
+impl ShareCardStats {
+    /// # Errors
+    /// Return error if formatting the pace fails
+    pub fn from_gfile(gfile: &GarminFile) -> Result<Self, Error> {
+        let date = gfile.begin_datetime.to_offsetdatetime().date();
+        let title = format_sstr!("{} {date}", gfile.sport.display_name());
+        let miles = gfile.total_distance / METERS_PER_MILE;
+        let pace = if miles <= 0.0 || gfile.total_duration <= 0.0 {
+            "-".into()
+        } else if gfile.sport == SportTypes::Biking {
+            format_sstr!("{:.1} mph", miles / (gfile.total_duration / 3600.0))
+        } else {
+            format_sstr!("{} /mi", print_h_m_s(gfile.total_duration / miles, false)?)
+        };
+        Ok(Self {
+            title,
+            distance: format_sstr!("{miles:.2} mi"),
+            pace,
+            elevation_gain: format_sstr!("{:.0} ft", elevation_gain(gfile) * FEET_PER_METER),
+        })
+    }
+}
+
+/// Total climb in meters, the sum of every rise between points
+#[must_use]
+pub fn elevation_gain(gfile: &GarminFile) -> f64 {
+    let altitudes: Vec<_> = gfile.points.iter().filter_map(|p| p.altitude).collect();
+    altitudes.windows(2).map(|w| (w[1] - w[0]).max(0.0)).sum()
+}
+
+/// Svg path of the gps track scaled to fit `width` x `height`, north up,
+/// `None` without gps data
+#[must_use]
+pub fn route_path(gfile: &GarminFile, width: f64, height: f64) -> Option<StackString> {
+    let points: Vec<_> = gfile
+        .points
+        .iter()
+        .filter_map(|p| Some((p.latitude?, p.longitude?)))
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+    let mean_lat = points.iter().map(|(lat, _)| lat).sum::<f64>() / points.len() as f64;
+    let lon_scale = mean_lat.to_radians().cos();
+    let projected: Vec<_> = points
+        .iter()
+        .map(|(lat, lon)| (lon * lon_scale, -lat))
+        .collect();
+    let (min_x, max_x, min_y, max_y) = projected.iter().fold(
+        (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+        |(min_x, max_x, min_y, max_y), (x, y)| {
+            (min_x.min(*x), max_x.max(*x), min_y.min(*y), max_y.max(*y))
+        },
+    );
+    let span = (max_x - min_x).max(max_y - min_y);
+    if span <= 0.0 {
+        return None;
+    }
+    let scale =
+        (width / (max_x - min_x).max(span * 1e-3)).min(height / (max_y - min_y).max(span * 1e-3));
+    // center the track in the box
+    let offset_x = (width - (max_x - min_x) * scale) / 2.0;
+    let offset_y = (height - (max_y - min_y) * scale) / 2.0;
+    let step = (projected.len() + ROUTE_POINTS - 1) / ROUTE_POINTS;
+
+    let mut path = StackString::new();
+    for (idx, (x, y)) in projected.iter().step_by(step.max(1)).enumerate() {
+        let x = offset_x + (x - min_x) * scale;
+        let y = offset_y + (y - min_y) * scale;
+        let cmd = if idx == 0 { 'M' } else { 'L' };
+        write!(path, "{cmd}{x:.1},{y:.1} ").ok()?;
+    }
+    Some(path.trim_end().into())
+}
+
+/// Share card as svg: title, route outline in the sport color and the
+/// distance, pace and elevation gain along the bottom
+/// # Errors
+/// Return error if formatting the stats fails
+pub fn share_card_svg(gfile: &GarminFile) -> Result<StackString, Error> {
+    let stats = ShareCardStats::from_gfile(gfile)?;
+    let color = gfile.sport.color();
+    let mut svg = String::new();
+    write!(
+        svg,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{CARD_SIZE}" height="{CARD_SIZE}" viewBox="0 0 {CARD_SIZE} {CARD_SIZE}" font-family="sans-serif">"##
+    )?;
+    write!(
+        svg,
+        r##"<rect width="{CARD_SIZE}" height="{CARD_SIZE}" fill="#1b1b1f"/>"##
+    )?;
+    write!(
+        svg,
+        r##"<text x="{MARGIN}" y="150" font-size="64" font-weight="bold" fill="#ffffff">{}</text>"##,
+        xml_escape(&stats.title)
+    )?;
+    let route_width = CARD_SIZE - 2.0 * MARGIN;
+    let route_height = ROUTE_BOTTOM - ROUTE_TOP;
+    if let Some(path) = route_path(gfile, route_width, route_height) {
+        write!(
+            svg,
+            r##"<path transform="translate({MARGIN},{ROUTE_TOP})" d="{path}" fill="none" stroke="{color}" stroke-width="8" stroke-linecap="round" stroke-linejoin="round"/>"##
+        )?;
+    }
+    let column_width = route_width / 3.0;
+    let columns = [
+        ("Distance", &stats.distance),
+        ("Pace", &stats.pace),
+        ("Elevation", &stats.elevation_gain),
+    ];
+    for (idx, (label, value)) in columns.iter().enumerate() {
+        let x = MARGIN + column_width * idx as f64;
+        write!(
+            svg,
+            r##"<text x="{x:.1}" y="910" font-size="32" fill="#a0a0a8">{label}</text>"##
+        )?;
+        write!(
+            svg,
+            r##"<text x="{x:.1}" y="980" font-size="56" font-weight="bold" fill="#ffffff">{}</text>"##,
+            xml_escape(value)
+        )?;
+    }
+    svg.push_str("</svg>");
+    Ok(svg.into())
+}
+
+/// Share card rendered to png
+/// # Errors
+/// Return error if rendering or encoding the image fails
+pub fn share_card_png(gfile: &GarminFile) -> Result<Vec<u8>, Error> {
+    let svg = share_card_svg(gfile)?;
+    if FONTS.is_empty() {
+        return Err(format_err!("No fonts loaded for the share card text"));
+    }
+    let options = Options {
+        fontdb: FONTS.clone(),
+        ..Options::default()
+    };
+    let tree = Tree::from_str(&svg, &options)?;
+    let size = tree.size().to_int_size();
+    let mut pixmap = Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| format_err!("Invalid share card size"))?;
+    resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use approx::assert_abs_diff_eq;
+
+    use garmin_models::{garmin_file::GarminFile, garmin_point::GarminPoint};
+    use garmin_utils::{garmin_util::METERS_PER_MILE, sport_types::SportTypes};
+
+    use crate::share_card::{
+        elevation_gain, route_path, share_card_png, share_card_svg, ShareCardStats, CARD_SIZE,
+        FONTS,
+    };
+
+    fn test_gfile() -> GarminFile {
+        let points = (0..=10)
+            .map(|idx| GarminPoint {
+                latitude: Some(40.0 + f64::from(idx) * 0.001),
+                longitude: Some(-74.0),
+                altitude: Some(if idx % 2 == 0 { 10.0 } else { 15.0 }),
+                ..GarminPoint::default()
+            })
+            .collect();
+        GarminFile {
+            sport: SportTypes::Running,
+            total_distance: 5.0 * METERS_PER_MILE,
+            total_duration: 2400.0,
+            points,
+            ..GarminFile::default()
+        }
+    }
+
+    #[test]
+    fn test_share_card() -> Result<(), Error> {
+        let gfile = test_gfile();
+        assert_abs_diff_eq!(elevation_gain(&gfile), 25.0);
+
+        let stats = ShareCardStats::from_gfile(&gfile)?;
+        assert_eq!(stats.distance.as_str(), "5.00 mi");
+        assert_eq!(stats.pace.as_str(), "08:00 /mi");
+        assert_eq!(stats.elevation_gain.as_str(), "82 ft");
+
+        // a north-south line is drawn down the middle of the box
+        let path = route_path(&gfile, 100.0, 100.0).unwrap();
+        assert!(path.starts_with("M50.0,100.0"));
+        assert!(path.ends_with("L50.0,0.0"));
+
+        let svg = share_card_svg(&gfile)?;
+        assert!(svg.contains(&format!(r#"width="{CARD_SIZE}""#)));
+        assert!(svg.contains("5.00 mi"));
+        assert!(svg.contains("<path"));
+
+        // text is drawn with the bundled fonts, not whatever the host has
+        assert_eq!(FONTS.len(), 2);
+        let png = share_card_png(&gfile)?;
+        assert!(png.starts_with(b"\x89PNG"));
+
+        assert!(route_path(&GarminFile::default(), 100.0, 100.0).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::StackString;
+
+use crate::{
+    pgpool::PgPool,
+    sport_types::{get_sport_type_map, update_custom_sports, update_sport_styles},
+};
+
+/// User defined sport, stored in the `custom_sports` table and registered as
+/// `SportTypes::Custom` by `load_custom_sports`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CustomSport {
+    pub name: StackString,
+    pub display_name: StackString,
+    pub strava_activity: Option<StackString>,
+    pub fitbit_activity_id: Option<i64>,
+}
+
+impl CustomSport {
+    /// # Errors
+    /// Return error if name is empty, has anything other than lowercase ascii
+    /// letters, digits and underscores or shadows a builtin sport
+    pub fn validate(&self) -> Result<(), Error> {
+        let name = self.name.as_str();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            Err(format_err!("Invalid custom sport name {name}"))
+        } else if get_sport_type_map().contains_key(name) {
+            Err(format_err!("{name} is a builtin sport"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM custom_sports ORDER BY name");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if validation or db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        self.validate()?;
+        let query = query!(
+            "
+                INSERT INTO custom_sports (name, display_name, strava_activity, fitbit_activity_id)
+                VALUES ($name, $display_name, $strava_activity, $fitbit_activity_id)
+                ON CONFLICT (name) DO UPDATE
+                SET display_name=EXCLUDED.display_name,
+                    strava_activity=EXCLUDED.strava_activity,
+                    fitbit_activity_id=EXCLUDED.fitbit_activity_id
+            ",
+            name = self.name,
+            display_name = self.display_name,
+            strava_activity = self.strava_activity,
+            fitbit_activity_id = self.fitbit_activity_id,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete_from_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM custom_sports WHERE name=$name",
+            name = self.name
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Alternate name for a builtin or custom sport, stored in `sport_aliases`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SportAlias {
+    pub alias: StackString,
+    pub sport: StackString,
+}
+
+impl SportAlias {
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM sport_aliases ORDER BY alias");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let alias = self.alias.to_lowercase();
+        let query = query!(
+            "
+                INSERT INTO sport_aliases (alias, sport) VALUES ($alias, $sport)
+                ON CONFLICT (alias) DO UPDATE SET sport=EXCLUDED.sport
+            ",
+            alias = alias,
+            sport = self.sport,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete_from_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM sport_aliases WHERE alias=$alias",
+            alias = self.alias
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Per-sport color and icon used by the html reports and plots, stored in
+/// `sport_styles`
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SportStyle {
+    pub sport: StackString,
+    pub color: StackString,
+    pub icon: Option<StackString>,
+}
+
+impl SportStyle {
+    /// # Errors
+    /// Return error if color is not a hex code or css color name, or if icon
+    /// is too long or contains markup
+    pub fn validate(&self) -> Result<(), Error> {
+        let color = self.color.as_str();
+        let valid_color = if let Some(hex) = color.strip_prefix('#') {
+            (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        } else {
+            !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic())
+        };
+        if !valid_color {
+            return Err(format_err!("Invalid color {color}"));
+        }
+        if let Some(icon) = &self.icon {
+            if icon.chars().count() > 4
+                || icon
+                    .chars()
+                    .any(|c| matches!(c, '<' | '>' | '&' | '\'' | '"' | '\\'))
+            {
+                return Err(format_err!("Invalid icon {icon}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn read_from_db(pool: &PgPool) -> Result<Vec<Self>, Error> {
+        let query = query!("SELECT * FROM sport_styles ORDER BY sport");
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if validation or db query fails
+    pub async fn upsert_db(&self, pool: &PgPool) -> Result<(), Error> {
+        self.validate()?;
+        let sport = self.sport.to_lowercase();
+        let query = query!(
+            "
+                INSERT INTO sport_styles (sport, color, icon) VALUES ($sport, $color, $icon)
+                ON CONFLICT (sport) DO UPDATE SET color=EXCLUDED.color, icon=EXCLUDED.icon
+            ",
+            sport = sport,
+            color = self.color,
+            icon = self.icon,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn delete_from_db(&self, pool: &PgPool) -> Result<(), Error> {
+        let query = query!(
+            "DELETE FROM sport_styles WHERE sport=$sport",
+            sport = self.sport
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+/// Read custom sports, aliases and sport styles from the db and register them
+/// with `SportTypes`
+/// # Errors
+/// Return error if db query fails
+pub async fn load_custom_sports(pool: &PgPool) -> Result<(), Error> {
+    let sports = CustomSport::read_from_db(pool).await?;
+    let aliases = SportAlias::read_from_db(pool).await?;
+    update_custom_sports(&sports, &aliases);
+    let styles = SportStyle::read_from_db(pool).await?;
+    update_sport_styles(&styles);
+    Ok(())
+}
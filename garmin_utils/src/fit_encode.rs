@@ -0,0 +1,94 @@
+pub const FIT_BASE_ENUM: u8 = 0x00;
+pub const FIT_BASE_SINT8: u8 = 0x01;
+pub const FIT_BASE_UINT8: u8 = 0x02;
+pub const FIT_BASE_STRING: u8 = 0x07;
+pub const FIT_BASE_UINT16: u8 = 0x84;
+pub const FIT_BASE_SINT32: u8 = 0x85;
+pub const FIT_BASE_UINT32: u8 = 0x86;
+
+/// Seconds between the unix epoch and the FIT epoch (1989-12-31T00:00:00Z)
+pub const FIT_EPOCH_OFFSET: i64 = 631_065_600;
+
+const FIT_CRC_TABLE: [u16; 16] = [
+    0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800, 0xB401,
+    0x5000, 0x9C01, 0x8801, 0x4400,
+];
+
+#[must_use]
+pub fn fit_crc(data: &[u8]) -> u16 {
+    data.iter().fold(0, |mut crc, byte| {
+        let tmp = FIT_CRC_TABLE[usize::from(crc & 0xF)];
+        crc = (crc >> 4) & 0x0FFF;
+        crc = crc ^ tmp ^ FIT_CRC_TABLE[usize::from(byte & 0xF)];
+        let tmp = FIT_CRC_TABLE[usize::from(crc & 0xF)];
+        crc = (crc >> 4) & 0x0FFF;
+        crc ^ tmp ^ FIT_CRC_TABLE[usize::from((byte >> 4) & 0xF)]
+    })
+}
+
+/// Definition message for local message `local_type`, `fields` are
+/// (field number, size, base type)
+pub fn fit_definition(buf: &mut Vec<u8>, local_type: u8, global: u16, fields: &[(u8, u8, u8)]) {
+    buf.push(0x40 | local_type);
+    buf.push(0);
+    buf.push(0);
+    buf.extend_from_slice(&global.to_le_bytes());
+    buf.push(fields.len() as u8);
+    for (field_num, size, base_type) in fields {
+        buf.extend_from_slice(&[*field_num, *size, *base_type]);
+    }
+}
+
+/// Null padded string field of `size` bytes, truncated on a char boundary
+pub fn fit_string(buf: &mut Vec<u8>, s: &str, size: usize) {
+    let mut len = s.len().min(size - 1);
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    buf.extend_from_slice(&s.as_bytes()[..len]);
+    buf.resize(buf.len() + size - len, 0);
+}
+
+/// Wrap encoded messages with the file header and trailing crc
+#[must_use]
+pub fn fit_file(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len() + 16);
+    output.push(14);
+    output.push(0x10);
+    output.extend_from_slice(&2132u16.to_le_bytes());
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    output.extend_from_slice(b".FIT");
+    let header_crc = fit_crc(&output);
+    output.extend_from_slice(&header_crc.to_le_bytes());
+    output.extend_from_slice(data);
+    let crc = fit_crc(&output);
+    output.extend_from_slice(&crc.to_le_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fit_encode::fit_string;
+
+    #[test]
+    fn test_fit_string() {
+        let mut buf = Vec::new();
+        fit_string(&mut buf, "abc", 5);
+        assert_eq!(buf, b"abc\0\0");
+
+        let mut buf = Vec::new();
+        fit_string(&mut buf, "abcdef", 4);
+        assert_eq!(buf, b"abc\0");
+
+        // "é" is two bytes, the second would be cut off
+        let mut buf = Vec::new();
+        fit_string(&mut buf, "abé", 4);
+        assert_eq!(buf, b"ab\0\0");
+
+        let mut buf = Vec::new();
+        fit_string(&mut buf, "Zürich 🏃", 10);
+        assert_eq!(buf.len(), 10);
+        let end = buf.iter().position(|b| *b == 0).unwrap();
+        assert_eq!(std::str::from_utf8(&buf[..end]), Ok("Zürich "));
+    }
+}
@@ -52,6 +52,35 @@ pub fn convert_time_string(time_str: &str) -> Result<f64, Error> {
     Ok(s + 60.0 * (f64::from(m) + 60.0 * f64::from(h)))
 }
 
+/// Race distance in meters from a name (half, half marathon, marathon) or a
+/// number with a unit (e.g. 10K, 5 km, 13.1mi, 26.2 miles, 1500m), a bare
+/// number is in miles
+/// # Errors
+/// Return error if the distance can't be parsed
+pub fn parse_race_distance(s: &str) -> Result<f64, Error> {
+    let s = s.trim().to_lowercase();
+    let marathon = f64::from(MARATHON_DISTANCE_M);
+    if s == "half" || (s.contains("half") && s.contains("marathon")) {
+        return Ok(marathon / 2.0);
+    } else if s == "full" || s.contains("marathon") {
+        return Ok(marathon);
+    }
+    let idx = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (value, units) = s.split_at(idx);
+    let scale = match units.trim() {
+        "" | "mi" | "mile" | "miles" => METERS_PER_MILE,
+        "k" | "km" | "kilometer" | "kilometers" | "kilometre" | "kilometres" => 1000.0,
+        "m" | "meter" | "meters" | "metre" | "metres" => 1.0,
+        _ => return Err(format_err!("Invalid race distance {s}")),
+    };
+    match value.parse::<f64>() {
+        Ok(value) if value > 0.0 => Ok(value * scale),
+        _ => Err(format_err!("Invalid race distance {s}")),
+    }
+}
+
 /// # Errors
 /// Return error if parsing time string fails
 pub fn convert_xml_local_time_to_utc(xml_local_time: &str) -> Result<OffsetDateTime, Error> {
@@ -145,6 +174,23 @@ pub fn titlecase(input: &str) -> StackString {
     }
 }
 
+/// Escape `s` for use in xml text and attribute values
+#[must_use]
+pub fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 #[must_use]
 pub fn generate_random_string(nchar: usize) -> StackString {
     let mut rng = thread_rng();
@@ -303,6 +349,18 @@ pub fn get_i64(value: &Value) -> Option<i64> {
     }
 }
 
+/// Great circle distance in meters between two lat/lon points in degrees
+#[inline]
+#[must_use]
+pub fn haversine_distance(lat0: f64, lon0: f64, lat1: f64, lon1: f64) -> f64 {
+    const EARTH_RADIUS_M: f64 = 6_371_000.0;
+    let dlat = (lat1 - lat0).to_radians();
+    let dlon = (lon1 - lon0).to_radians();
+    let a = (dlat / 2.0).sin().powi(2)
+        + lat0.to_radians().cos() * lat1.to_radians().cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
 #[inline]
 #[must_use]
 pub fn get_degrees_from_semicircles(s: f64) -> f64 {
@@ -391,7 +449,9 @@ mod tests {
     use std::path::Path;
     use tempfile::TempDir;
 
-    use crate::garmin_util::extract_zip;
+    use crate::garmin_util::{
+        extract_zip, parse_race_distance, xml_escape, MARATHON_DISTANCE_M, METERS_PER_MILE,
+    };
 
     #[test]
     fn test_extract_zip() -> Result<(), Error> {
@@ -404,4 +464,34 @@ mod tests {
         assert!(files.len() == 2);
         Ok(())
     }
+
+    #[test]
+    fn test_parse_race_distance() -> Result<(), Error> {
+        let marathon = f64::from(MARATHON_DISTANCE_M);
+        assert_eq!(parse_race_distance("marathon")?, marathon);
+        assert_eq!(parse_race_distance("full")?, marathon);
+        assert_eq!(parse_race_distance("half")?, marathon / 2.0);
+        assert_eq!(parse_race_distance("Half Marathon")?, marathon / 2.0);
+        assert_eq!(parse_race_distance("half-marathon")?, marathon / 2.0);
+        assert_eq!(parse_race_distance("10k")?, 10_000.0);
+        assert_eq!(parse_race_distance("10K")?, 10_000.0);
+        assert_eq!(parse_race_distance("5 km")?, 5_000.0);
+        assert_eq!(parse_race_distance("1 Mile")?, METERS_PER_MILE);
+        assert_eq!(parse_race_distance("13.1mi")?, 13.1 * METERS_PER_MILE);
+        assert_eq!(parse_race_distance("13.1 mi")?, 13.1 * METERS_PER_MILE);
+        assert_eq!(parse_race_distance("26.2 miles")?, 26.2 * METERS_PER_MILE);
+        assert_eq!(parse_race_distance("3")?, 3.0 * METERS_PER_MILE);
+        assert_eq!(parse_race_distance("1500m")?, 1_500.0);
+        assert_eq!(parse_race_distance("800 meters")?, 800.0);
+        assert!(parse_race_distance("far").is_err());
+        assert!(parse_race_distance("Relay Leg").is_err());
+        assert!(parse_race_distance("0k").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(xml_escape("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+        assert_eq!(xml_escape("Bob's run"), "Bob&apos;s run");
+    }
 }
@@ -7,6 +7,8 @@
 #![allow(clippy::similar_names)]
 #![allow(clippy::unsafe_derive_deserialize)]
 
+pub mod custom_sport;
+pub mod fit_encode;
 pub mod garmin_util;
 pub mod pgpool;
 pub mod plot_graph;
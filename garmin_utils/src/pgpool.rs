@@ -2,7 +2,9 @@ use anyhow::{format_err, Error};
 use deadpool_postgres::{Client, Config, Pool};
 use std::{fmt, sync::Arc};
 use tokio_postgres::{Config as PgConfig, NoTls};
+use tracing::instrument;
 
+pub use deadpool_postgres::Client as PgClient;
 pub use tokio_postgres::Transaction as PgTransaction;
 
 use stack_string::StackString;
@@ -53,6 +55,7 @@ impl PgPool {
     /// # Errors
     /// Return error if pool doesn't exist or we cannot pull connection from
     /// pool
+    #[instrument(name = "db.pool.get", skip_all)]
     pub async fn get(&self) -> Result<Client, Error> {
         self.pool
             .as_ref()
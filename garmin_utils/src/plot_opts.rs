@@ -1,5 +1,74 @@
 use serde::Serialize;
-use stack_string::StackString;
+use stack_string::{format_sstr, StackString};
+
+/// Quantity used for the x-axis of per-activity plots
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotXAxis {
+    Distance,
+    Time,
+}
+
+impl Default for PlotXAxis {
+    fn default() -> Self {
+        Self::Distance
+    }
+}
+
+impl PlotXAxis {
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Distance => "mi",
+            Self::Time => "min",
+        }
+    }
+}
+
+/// Y-axis a series of a combined plot is drawn against, `Background` series
+/// are drawn as a shaded area without an axis of their own
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotAxis {
+    Left,
+    Right,
+    Background,
+}
+
+impl Default for PlotAxis {
+    fn default() -> Self {
+        Self::Left
+    }
+}
+
+/// One metric of a combined plot sharing the x-axis with the other series
+#[derive(Serialize, Default)]
+pub struct PlotSeries<'a> {
+    pub name: StackString,
+    pub label: StackString,
+    pub data: &'a [(f64, f64)],
+    pub axis: PlotAxis,
+    pub color: Option<StackString>,
+}
+
+impl<'a> PlotSeries<'a> {
+    #[must_use]
+    pub fn new(name: &str, label: &str, data: &'a [(f64, f64)], axis: PlotAxis) -> Self {
+        Self {
+            name: name.into(),
+            label: label.into(),
+            data,
+            axis,
+            color: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_color(mut self, color: &str) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+}
 
 #[derive(Serialize, Default)]
 pub struct PlotOpts<'a> {
@@ -10,6 +79,8 @@ pub struct PlotOpts<'a> {
     pub marker: Option<StackString>,
     pub xlabel: StackString,
     pub ylabel: StackString,
+    pub series: Vec<PlotSeries<'a>>,
+    pub markers: Option<&'a [(f64, i32)]>,
 }
 
 #[allow(clippy::similar_names)]
@@ -24,6 +95,8 @@ impl<'a> PlotOpts<'a> {
             marker: None,
             xlabel: "".into(),
             ylabel: "".into(),
+            series: Vec::new(),
+            markers: None,
         }
     }
 
@@ -63,4 +136,78 @@ impl<'a> PlotOpts<'a> {
         self.ylabel = ylabel.into();
         self
     }
+
+    #[must_use]
+    pub fn with_series(mut self, series: PlotSeries<'a>) -> PlotOpts<'a> {
+        self.series.push(series);
+        self
+    }
+
+    /// Vertical markers drawn at x position with a label, e.g. lap boundaries
+    #[must_use]
+    pub fn with_markers(mut self, markers: &'a [(f64, i32)]) -> PlotOpts<'a> {
+        self.markers = Some(markers);
+        self
+    }
+
+    #[must_use]
+    pub fn is_combined(&self) -> bool {
+        !self.series.is_empty()
+    }
+
+    /// Text alternative for the plot: the title followed by the range and
+    /// mean of each plotted quantity
+    #[must_use]
+    pub fn summary(&self) -> StackString {
+        let mut summary = self.title.clone();
+        if self.is_combined() {
+            for series in &self.series {
+                summary.push_str(&series_summary(&series.label, series.data));
+            }
+        } else if let Some(data) = self.data {
+            summary.push_str(&series_summary(&self.ylabel, data));
+        }
+        summary
+    }
+}
+
+fn series_summary(label: &str, data: &[(f64, f64)]) -> StackString {
+    if data.is_empty() {
+        return StackString::new();
+    }
+    let (min, max, sum) = data.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY, 0.0),
+        |(min, max, sum), (_, y)| (min.min(*y), max.max(*y), sum + y),
+    );
+    let mean = sum / data.len() as f64;
+    format_sstr!("; {label} from {min:0.1} to {max:0.1}, mean {mean:0.1}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plot_opts::{PlotAxis, PlotOpts, PlotSeries};
+
+    #[test]
+    fn test_plot_summary() {
+        let data = [(0.0, 140.0), (1.0, 150.0), (2.0, 160.0)];
+        let opts = PlotOpts::new()
+            .with_title("Heart Rate")
+            .with_labels("mi", "bpm")
+            .with_data(&data);
+        assert_eq!(
+            opts.summary(),
+            "Heart Rate; bpm from 140.0 to 160.0, mean 150.0"
+        );
+
+        let speed = [(0.0, 6.0), (2.0, 8.0)];
+        let opts = PlotOpts::new()
+            .with_title("Combined")
+            .with_series(PlotSeries::new("hr", "bpm", &data, PlotAxis::Left))
+            .with_series(PlotSeries::new("speed", "mph", &speed, PlotAxis::Right))
+            .with_series(PlotSeries::new("alt", "ft", &[], PlotAxis::Right));
+        assert_eq!(
+            opts.summary(),
+            "Combined; bpm from 140.0 to 160.0, mean 150.0; mph from 6.0 to 8.0, mean 7.0"
+        );
+    }
 }
@@ -1,12 +1,50 @@
 use anyhow::{format_err, Error};
 use bytes::BytesMut;
+use log::debug;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use stack_string::StackString;
-use std::{collections::HashMap, fmt, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+    sync::{Mutex, PoisonError, RwLock},
+};
 use tokio_postgres::types::{FromSql, IsNull, ToSql, Type};
 
+use crate::{
+    custom_sport::{CustomSport, SportAlias, SportStyle},
+    garmin_util::titlecase,
+};
+
 static SPORT_TYPE_MAP: Lazy<HashMap<&'static str, SportTypes>> = Lazy::new(init_sport_type_map);
+static CUSTOM_SPORTS: Lazy<RwLock<CustomSportRegistry>> =
+    Lazy::new(|| RwLock::new(CustomSportRegistry::default()));
+static SPORT_STYLES: Lazy<RwLock<HashMap<SportTypes, SportStyleInfo>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+static INTERNED_NAMES: Lazy<Mutex<HashSet<&'static str>>> =
+    Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Every builtin sport except `None`
+pub const BUILTIN_SPORTS: [SportTypes; 12] = [
+    SportTypes::Running,
+    SportTypes::Biking,
+    SportTypes::Walking,
+    SportTypes::Hiking,
+    SportTypes::Ultimate,
+    SportTypes::Elliptical,
+    SportTypes::Stairs,
+    SportTypes::Lifting,
+    SportTypes::Swimming,
+    SportTypes::Other,
+    SportTypes::Snowshoeing,
+    SportTypes::Skiing,
+];
+
+/// Older fitbit activity ids still found in the activity log, mapped to the
+/// sport whose current id `to_fitbit_activity_id` returns
+const LEGACY_FITBIT_ACTIVITY_IDS: [(u64, SportTypes); 2] =
+    [(15000, SportTypes::Walking), (1071, SportTypes::Biking)];
 
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(into = "StackString", try_from = "StackString")]
@@ -24,6 +62,7 @@ pub enum SportTypes {
     Snowshoeing,
     Skiing,
     None,
+    Custom(&'static str),
 }
 
 impl Default for SportTypes {
@@ -82,7 +121,64 @@ impl SportTypes {
             Self::Snowshoeing => "snowshoeing",
             Self::Skiing => "skiing",
             Self::None => "none",
+            Self::Custom(name) => name,
+        }
+    }
+
+    #[must_use]
+    pub fn display_name(self) -> StackString {
+        match self {
+            Self::Custom(name) => {
+                get_custom_sport(name).map_or_else(|| titlecase(name), |s| s.display_name)
+            }
+            _ => titlecase(self.to_str()),
+        }
+    }
+
+    /// Css color used for this sport in report tables and plots
+    #[must_use]
+    pub fn color(self) -> StackString {
+        if let Some(style) = get_sport_style(self) {
+            return style.color;
+        }
+        match self {
+            Self::Running => "#1f77b4",
+            Self::Biking => "#ff7f0e",
+            Self::Walking => "#2ca02c",
+            Self::Hiking => "#8c564b",
+            Self::Ultimate => "#e377c2",
+            Self::Elliptical => "#9467bd",
+            Self::Stairs => "#7f7f7f",
+            Self::Lifting => "#d62728",
+            Self::Swimming => "#17becf",
+            Self::Snowshoeing => "#aec7e8",
+            Self::Skiing => "#393b79",
+            Self::Other | Self::None | Self::Custom(_) => "steelblue",
+        }
+        .into()
+    }
+
+    /// Short icon (usually an emoji) shown next to the sport name
+    #[must_use]
+    pub fn icon(self) -> StackString {
+        if let Some(style) = get_sport_style(self) {
+            return style.icon;
+        }
+        match self {
+            Self::Running => "\u{1f3c3}",
+            Self::Biking => "\u{1f6b4}",
+            Self::Walking => "\u{1f6b6}",
+            Self::Hiking => "\u{1f97e}",
+            Self::Ultimate => "\u{1f94f}",
+            Self::Elliptical | Self::Stairs => "\u{1f45f}",
+            Self::Lifting => "\u{1f3cb}",
+            Self::Swimming => "\u{1f3ca}",
+            Self::Snowshoeing => "\u{2744}",
+            Self::Skiing => "\u{26f7}",
+            Self::Other | Self::Custom(_) => "\u{1f3c5}",
+            Self::None => "",
         }
+        .into()
     }
 
     #[must_use]
@@ -99,6 +195,11 @@ impl SportTypes {
             Self::Snowshoeing => "Snowshoe",
             Self::Skiing => "NordicSki",
             Self::None => "None",
+            Self::Custom(name) => {
+                return get_custom_sport(name)
+                    .and_then(|s| s.strava_activity)
+                    .unwrap_or_else(|| "Other".into());
+            }
             _ => "Other",
         }
         .into()
@@ -118,7 +219,8 @@ impl SportTypes {
             "Swim" => Ok(Self::Swimming),
             "Snowshoe" => Ok(Self::Snowshoeing),
             "NordicSki" => Ok(Self::Skiing),
-            _ => Err(format_err!("Invalid activity type")),
+            _ => find_custom_sport(|s| s.strava_activity.as_deref() == Some(item))
+                .ok_or_else(|| format_err!("Invalid activity type")),
         }
     }
 
@@ -136,16 +238,41 @@ impl SportTypes {
             Self::Swimming => Some(18300),
             Self::Snowshoeing => Some(19190),
             Self::Skiing => Some(90015),
+            Self::Custom(name) => get_custom_sport(name).and_then(|s| s.fitbit_activity_id),
             _ => None,
         }
     }
 
+    /// FIT profile `sport` enum value, sports without an equivalent are
+    /// `generic`
+    #[must_use]
+    pub fn to_fit_sport(self) -> u8 {
+        match self {
+            Self::Running => 1,
+            Self::Biking => 2,
+            Self::Elliptical | Self::Stairs => 4,
+            Self::Swimming => 5,
+            Self::Lifting => 10,
+            Self::Walking => 11,
+            Self::Skiing => 12,
+            Self::Hiking => 17,
+            Self::Snowshoeing => 35,
+            _ => 0,
+        }
+    }
+
     #[must_use]
     pub fn from_fitbit_activity_id(id: usize) -> Self {
+        if let Some((_, sport)) = LEGACY_FITBIT_ACTIVITY_IDS
+            .iter()
+            .find(|(legacy_id, _)| *legacy_id == id as u64)
+        {
+            return *sport;
+        }
         match id {
             90009 => Self::Running,
-            90013 | 15000 => Self::Walking,
-            90001 | 1071 => Self::Biking,
+            90013 => Self::Walking,
+            90001 => Self::Biking,
             90012 => Self::Hiking,
             15250 => Self::Ultimate,
             90017 => Self::Elliptical,
@@ -154,18 +281,42 @@ impl SportTypes {
             18300 => Self::Swimming,
             19190 => Self::Snowshoeing,
             90015 => Self::Skiing,
-            _ => Self::None,
+            _ => {
+                find_custom_sport(|s| s.fitbit_activity_id == Some(id as u64)).unwrap_or(Self::None)
+            }
         }
     }
+
+    /// The builtin sports followed by the registered custom sports, sorted
+    /// by name
+    #[must_use]
+    pub fn all() -> Vec<Self> {
+        let registry = CUSTOM_SPORTS.read().unwrap_or_else(PoisonError::into_inner);
+        let mut custom: Vec<_> = registry.sports.keys().copied().collect();
+        custom.sort_unstable();
+        BUILTIN_SPORTS
+            .iter()
+            .copied()
+            .chain(custom.into_iter().map(Self::Custom))
+            .collect()
+    }
 }
 
 impl FromStr for SportTypes {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match SPORT_TYPE_MAP.get(s.to_lowercase().as_str()) {
-            Some(sport) => Ok(*sport),
-            None => Err(format_err!("Invalid Sport Type {s}")),
+        let s = s.to_lowercase();
+        if let Some(sport) = SPORT_TYPE_MAP.get(s.as_str()) {
+            return Ok(*sport);
+        }
+        let registry = CUSTOM_SPORTS.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(custom) = registry.sports.get(s.as_str()) {
+            Ok(Self::Custom(custom.name))
+        } else if let Some(sport) = registry.aliases.get(s.as_str()) {
+            Ok(*sport)
+        } else {
+            Err(format_err!("Invalid Sport Type {s}"))
         }
     }
 }
@@ -223,6 +374,179 @@ pub fn get_sport_type_map() -> &'static HashMap<&'static str, SportTypes> {
     &SPORT_TYPE_MAP
 }
 
+/// Registered custom sport, only the name is interned (so that `SportTypes`
+/// stays `Copy`), the other fields are owned and freed when the registry is
+/// replaced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomSportInfo {
+    pub name: &'static str,
+    pub display_name: StackString,
+    pub strava_activity: Option<StackString>,
+    pub fitbit_activity_id: Option<u64>,
+}
+
+#[derive(Default)]
+struct CustomSportRegistry {
+    sports: HashMap<&'static str, CustomSportInfo>,
+    aliases: HashMap<&'static str, SportTypes>,
+}
+
+/// Leak `s` once per distinct string, only sport names and aliases go through
+/// here so the total stays bounded by the distinct names ever registered,
+/// reloading the same sports doesn't allocate
+fn intern(s: &str) -> &'static str {
+    let mut names = INTERNED_NAMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(name) = names.get(s) {
+        return name;
+    }
+    let name: &'static str = Box::leak(s.to_string().into_boxed_str());
+    names.insert(name);
+    name
+}
+
+fn get_custom_sport(name: &str) -> Option<CustomSportInfo> {
+    let registry = CUSTOM_SPORTS.read().unwrap_or_else(PoisonError::into_inner);
+    registry.sports.get(name).cloned()
+}
+
+fn find_custom_sport(f: impl Fn(&CustomSportInfo) -> bool) -> Option<SportTypes> {
+    let registry = CUSTOM_SPORTS.read().unwrap_or_else(PoisonError::into_inner);
+    registry
+        .sports
+        .values()
+        .find(|s| f(s))
+        .map(|s| SportTypes::Custom(s.name))
+}
+
+/// Replace the registered custom sports and aliases, entries which shadow a
+/// builtin sport or point to an unknown sport are skipped
+pub fn update_custom_sports(sports: &[CustomSport], aliases: &[SportAlias]) {
+    let mut registry = CustomSportRegistry::default();
+    for sport in sports {
+        if let Err(e) = sport.validate() {
+            debug!("skipping custom sport {e}");
+            continue;
+        }
+        let info = CustomSportInfo {
+            name: intern(&sport.name),
+            display_name: sport.display_name.clone(),
+            strava_activity: sport.strava_activity.clone(),
+            fitbit_activity_id: sport.fitbit_activity_id.map(|id| id as u64),
+        };
+        registry.sports.insert(info.name, info);
+    }
+    for alias in aliases {
+        let key = alias.alias.to_lowercase();
+        if SPORT_TYPE_MAP.contains_key(key.as_str()) || registry.sports.contains_key(key.as_str()) {
+            continue;
+        }
+        let target = alias.sport.to_lowercase();
+        let sport = SPORT_TYPE_MAP.get(target.as_str()).copied().or_else(|| {
+            registry
+                .sports
+                .get(target.as_str())
+                .map(|s| SportTypes::Custom(s.name))
+        });
+        if let Some(sport) = sport {
+            registry.aliases.insert(intern(&key), sport);
+        } else {
+            debug!(
+                "skipping alias {} for unknown sport {}",
+                alias.alias, alias.sport
+            );
+        }
+    }
+    *CUSTOM_SPORTS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = registry;
+}
+
+/// Color and icon overriding the builtin defaults of a sport
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SportStyleInfo {
+    pub color: StackString,
+    pub icon: StackString,
+}
+
+fn get_sport_style(sport: SportTypes) -> Option<SportStyleInfo> {
+    let styles = SPORT_STYLES.read().unwrap_or_else(PoisonError::into_inner);
+    styles.get(&sport).cloned()
+}
+
+/// Replace the registered sport styles, styles for unknown sports or with an
+/// invalid color or icon are skipped
+pub fn update_sport_styles(styles: &[SportStyle]) {
+    let mut m = HashMap::new();
+    for style in styles {
+        if let Err(e) = style.validate() {
+            debug!("skipping sport style {e}");
+            continue;
+        }
+        let sport: SportTypes = match style.sport.parse() {
+            Ok(sport) => sport,
+            Err(e) => {
+                debug!("skipping sport style {e}");
+                continue;
+            }
+        };
+        let info = SportStyleInfo {
+            color: style.color.clone(),
+            icon: style.icon.clone().unwrap_or_default(),
+        };
+        m.insert(sport, info);
+    }
+    *SPORT_STYLES.write().unwrap_or_else(PoisonError::into_inner) = m;
+}
+
+/// All sport names and aliases, builtin and custom, that parse to a
+/// `SportTypes`
+#[must_use]
+pub fn get_sport_names() -> Vec<&'static str> {
+    let registry = CUSTOM_SPORTS.read().unwrap_or_else(PoisonError::into_inner);
+    let mut names: Vec<_> = SPORT_TYPE_MAP
+        .keys()
+        .copied()
+        .chain(registry.sports.keys().copied())
+        .chain(registry.aliases.keys().copied())
+        .collect();
+    names.shrink_to_fit();
+    names.sort_unstable();
+    names
+}
+
+/// Every fitbit activity id `from_fitbit_activity_id` recognizes, sorted by
+/// id
+#[must_use]
+pub fn get_fitbit_activity_ids() -> Vec<(u64, SportTypes)> {
+    let mut ids: Vec<_> = SportTypes::all()
+        .into_iter()
+        .filter_map(|sport| sport.to_fitbit_activity_id().map(|id| (id, sport)))
+        .chain(LEGACY_FITBIT_ACTIVITY_IDS)
+        .collect();
+    ids.sort_unstable_by_key(|(id, _)| *id);
+    ids
+}
+
+/// Strava activity types which `from_strava_activity` maps to a sport,
+/// sorted by type
+#[must_use]
+pub fn get_strava_activity_types() -> Vec<(StackString, SportTypes)> {
+    let mut types: Vec<_> = SportTypes::all()
+        .into_iter()
+        .filter_map(|sport| {
+            let activity_type = sport.to_strava_activity();
+            match SportTypes::from_strava_activity(&activity_type) {
+                Ok(s) if s == sport => Some((activity_type, sport)),
+                _ => None,
+            }
+        })
+        .collect();
+    types.sort_unstable_by(|(x, _), (y, _)| x.cmp(y));
+    types
+}
+
 pub fn convert_sport_name(sport: &str) -> Option<StackString> {
     sport.parse::<SportTypes>().ok().map(Into::into)
 }
@@ -274,3 +598,133 @@ impl ToSql for SportTypes {
         s.to_sql_checked(ty, out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+
+    use crate::{
+        custom_sport::{CustomSport, SportAlias, SportStyle},
+        sport_types::{
+            get_fitbit_activity_ids, get_sport_names, get_strava_activity_types,
+            update_custom_sports, update_sport_styles, SportTypes, BUILTIN_SPORTS,
+        },
+    };
+
+    #[test]
+    fn test_activity_type_mappings() {
+        let fitbit_ids: Vec<_> = get_fitbit_activity_ids()
+            .into_iter()
+            .filter(|(_, sport)| BUILTIN_SPORTS.contains(sport))
+            .collect();
+        assert_eq!(fitbit_ids.len(), 13);
+        for (id, sport) in &fitbit_ids {
+            assert_eq!(SportTypes::from_fitbit_activity_id(*id as usize), *sport);
+        }
+        assert!(fitbit_ids.contains(&(1071, SportTypes::Biking)));
+        assert_eq!(SportTypes::Biking.to_fitbit_activity_id(), Some(90001));
+
+        let strava_types: Vec<_> = get_strava_activity_types()
+            .into_iter()
+            .filter(|(_, sport)| BUILTIN_SPORTS.contains(sport))
+            .collect();
+        assert_eq!(strava_types.len(), 10);
+        for (activity_type, sport) in &strava_types {
+            assert_eq!(&sport.to_strava_activity(), activity_type);
+        }
+        assert!(!strava_types.iter().any(|(t, _)| t.as_str() == "Other"));
+        assert_eq!(&SportTypes::all()[..12], &BUILTIN_SPORTS);
+    }
+
+    #[test]
+    fn test_custom_sports() -> Result<(), Error> {
+        let sports = [CustomSport {
+            name: "paddleboard".into(),
+            display_name: "Paddleboard".into(),
+            strava_activity: Some("StandUpPaddling".into()),
+            fitbit_activity_id: Some(20_000),
+        }];
+        let aliases = [
+            SportAlias {
+                alias: "SUP".into(),
+                sport: "paddleboard".into(),
+            },
+            SportAlias {
+                alias: "jog".into(),
+                sport: "running".into(),
+            },
+            SportAlias {
+                alias: "run".into(),
+                sport: "biking".into(),
+            },
+        ];
+        update_custom_sports(&sports, &aliases);
+
+        let sport: SportTypes = "paddleboard".parse()?;
+        assert_eq!(sport, SportTypes::Custom("paddleboard"));
+        assert_eq!(sport.to_str(), "paddleboard");
+        assert_eq!(sport.to_strava_activity(), "StandUpPaddling");
+        assert_eq!(sport.to_fitbit_activity_id(), Some(20_000));
+        assert_eq!(sport.display_name(), "Paddleboard");
+        assert_eq!(SportTypes::from_strava_activity("StandUpPaddling")?, sport);
+        assert_eq!(SportTypes::from_fitbit_activity_id(20_000), sport);
+        assert_eq!("sup".parse::<SportTypes>()?, sport);
+        assert_eq!("jog".parse::<SportTypes>()?, SportTypes::Running);
+        assert_eq!("run".parse::<SportTypes>()?, SportTypes::Running);
+        assert!(get_sport_names().contains(&"paddleboard"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_sport_validate() {
+        let mut sport = CustomSport {
+            name: "ski_erg2".into(),
+            display_name: "Ski Erg".into(),
+            strava_activity: None,
+            fitbit_activity_id: None,
+        };
+        assert!(sport.validate().is_ok());
+        for name in [
+            "x'or'1'='1",
+            "a;b",
+            "<b>",
+            "Paddle",
+            "stand up",
+            "",
+            "running",
+        ] {
+            sport.name = name.into();
+            assert!(sport.validate().is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_sport_styles() -> Result<(), Error> {
+        assert_eq!(SportTypes::Running.color(), "#1f77b4");
+        assert_ne!(SportTypes::Running.color(), SportTypes::Biking.color());
+        let styles = [
+            SportStyle {
+                sport: "ride".into(),
+                color: "#00ff00".into(),
+                icon: Some("B".into()),
+            },
+            SportStyle {
+                sport: "walking".into(),
+                color: "red;}<script>".into(),
+                icon: None,
+            },
+            SportStyle {
+                sport: "not_a_sport".into(),
+                color: "green".into(),
+                icon: None,
+            },
+        ];
+        update_sport_styles(&styles);
+        assert_eq!(SportTypes::Biking.color(), "#00ff00");
+        assert_eq!(SportTypes::Biking.icon(), "B");
+        assert_eq!(SportTypes::Walking.color(), "#2ca02c");
+        update_sport_styles(&[]);
+        assert_eq!(SportTypes::Biking.color(), "#ff7f0e");
+        Ok(())
+    }
+}
@@ -0,0 +1,11 @@
+CREATE TABLE custom_sports (
+    name TEXT NOT NULL PRIMARY KEY,
+    display_name TEXT NOT NULL,
+    strava_activity TEXT,
+    fitbit_activity_id BIGINT
+);
+CREATE TABLE sport_aliases (
+    alias TEXT NOT NULL PRIMARY KEY,
+    sport TEXT NOT NULL
+);
+ALTER TABLE garmin_summary ALTER COLUMN sport TYPE TEXT;
@@ -0,0 +1,5 @@
+CREATE TABLE sport_styles (
+    sport TEXT NOT NULL PRIMARY KEY,
+    color TEXT NOT NULL,
+    icon TEXT
+);
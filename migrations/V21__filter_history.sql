@@ -0,0 +1,7 @@
+CREATE TABLE filter_history (
+    email TEXT NOT NULL,
+    filter TEXT NOT NULL,
+    pinned BOOLEAN NOT NULL DEFAULT false,
+    last_used TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    PRIMARY KEY (email, filter)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE heartrate_streams (
+    summary_id UUID NOT NULL REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    source TEXT NOT NULL,
+    filename TEXT NOT NULL,
+    begin_datetime TIMESTAMP WITH TIME ZONE NOT NULL,
+    offsets DOUBLE PRECISION[] NOT NULL,
+    heart_rates DOUBLE PRECISION[] NOT NULL,
+    avg_heart_rate DOUBLE PRECISION NOT NULL,
+    preferred BOOLEAN NOT NULL DEFAULT false,
+    PRIMARY KEY (summary_id, source)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE power_curves (
+    summary_id UUID NOT NULL REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    metric TEXT NOT NULL,
+    duration INTEGER NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (summary_id, metric, duration)
+);
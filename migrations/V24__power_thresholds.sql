@@ -0,0 +1,5 @@
+CREATE TABLE power_thresholds (
+    effective_date DATE NOT NULL PRIMARY KEY,
+    ftp DOUBLE PRECISION NOT NULL,
+    w_prime DOUBLE PRECISION NOT NULL
+);
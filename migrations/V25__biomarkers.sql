@@ -0,0 +1,13 @@
+CREATE TABLE biomarker_series (
+    name TEXT NOT NULL PRIMARY KEY,
+    units TEXT NOT NULL DEFAULT '',
+    description TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE biomarker_measurements (
+    series TEXT NOT NULL REFERENCES biomarker_series (name) ON DELETE CASCADE,
+    datetime TIMESTAMP WITH TIME ZONE NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (series, datetime)
+);
@@ -0,0 +1,5 @@
+CREATE TABLE quarantined_files (
+    filename TEXT NOT NULL PRIMARY KEY,
+    error_message TEXT NOT NULL,
+    quarantined_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
@@ -0,0 +1,5 @@
+CREATE TABLE device_imports (
+    serial TEXT NOT NULL PRIMARY KEY,
+    last_modified TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_imported_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
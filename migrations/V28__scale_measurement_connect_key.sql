@@ -0,0 +1,2 @@
+ALTER TABLE scale_measurements ADD COLUMN connect_primary_key BIGINT;
+CREATE UNIQUE INDEX scale_measurements_connect_primary_key_idx ON scale_measurements (connect_primary_key);
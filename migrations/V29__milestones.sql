@@ -0,0 +1,9 @@
+CREATE TABLE milestones (
+    kind TEXT NOT NULL,
+    sport TEXT NOT NULL,
+    threshold BIGINT NOT NULL,
+    summary_id UUID REFERENCES garmin_summary (id) ON DELETE SET NULL,
+    achieved_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    PRIMARY KEY (kind, sport, threshold)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE sync_status (
+    job TEXT PRIMARY KEY,
+    last_success TIMESTAMP WITH TIME ZONE,
+    last_failure TIMESTAMP WITH TIME ZONE,
+    message TEXT,
+    last_alert TIMESTAMP WITH TIME ZONE
+);
@@ -0,0 +1,11 @@
+ALTER TABLE race_results ADD COLUMN bib_number TEXT;
+ALTER TABLE race_results ADD COLUMN race_report TEXT;
+
+CREATE TABLE race_result_attachments (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    race_id UUID NOT NULL REFERENCES race_results (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    filename TEXT NOT NULL,
+    s3_key TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
@@ -0,0 +1,6 @@
+CREATE TABLE elevation_profiles (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    svg_path TEXT NOT NULL,
+    min_altitude DOUBLE PRECISION NOT NULL,
+    max_altitude DOUBLE PRECISION NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE garmin_summary ADD COLUMN course_difficulty DOUBLE PRECISION;
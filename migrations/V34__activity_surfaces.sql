@@ -0,0 +1,4 @@
+CREATE TABLE activity_surfaces (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    surface TEXT NOT NULL
+);
@@ -0,0 +1,17 @@
+ALTER TABLE garmin_summary ADD COLUMN start_latitude DOUBLE PRECISION;
+ALTER TABLE garmin_summary ADD COLUMN start_longitude DOUBLE PRECISION;
+ALTER TABLE garmin_summary ADD COLUMN city TEXT;
+ALTER TABLE garmin_summary ADD COLUMN region TEXT;
+ALTER TABLE garmin_summary ADD COLUMN country TEXT;
+ALTER TABLE garmin_summary ADD COLUMN geocoded_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX garmin_summary_city_idx ON garmin_summary (city);
+
+CREATE TABLE geocode_cache (
+    lat_key INTEGER NOT NULL,
+    lon_key INTEGER NOT NULL,
+    city TEXT,
+    region TEXT,
+    country TEXT,
+    PRIMARY KEY (lat_key, lon_key)
+);
@@ -0,0 +1,4 @@
+ALTER TABLE strava_activities ADD COLUMN kudos_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE strava_activities ADD COLUMN comment_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE strava_activities ADD COLUMN total_photo_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE strava_activities ADD COLUMN photo_urls TEXT[] NOT NULL DEFAULT '{}';
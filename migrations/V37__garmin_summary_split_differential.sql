@@ -0,0 +1 @@
+ALTER TABLE garmin_summary ADD COLUMN split_differential DOUBLE PRECISION;
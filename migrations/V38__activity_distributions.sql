@@ -0,0 +1,7 @@
+CREATE TABLE activity_distributions (
+    summary_id UUID NOT NULL REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    metric TEXT NOT NULL,
+    bucket DOUBLE PRECISION NOT NULL,
+    seconds DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (summary_id, metric, bucket)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE ramp_rate_alerts (
+    week_start DATE NOT NULL,
+    sport TEXT NOT NULL,
+    ratio DOUBLE PRECISION,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    PRIMARY KEY (week_start, sport)
+);
@@ -0,0 +1 @@
+ALTER TABLE strava_activities ADD COLUMN strava_account TEXT;
@@ -0,0 +1 @@
+ALTER TABLE sync_status ADD COLUMN failure_count INTEGER NOT NULL DEFAULT 0;
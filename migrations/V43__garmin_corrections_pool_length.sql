@@ -0,0 +1,2 @@
+ALTER TABLE garmin_corrections_laps ADD COLUMN pool_length DOUBLE PRECISION;
+ALTER TABLE garmin_corrections_laps ADD COLUMN recorded_pool_length DOUBLE PRECISION;
@@ -0,0 +1,7 @@
+CREATE TABLE activity_clothing (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    clothing TEXT NOT NULL,
+    comfort TEXT NOT NULL,
+    temperature DOUBLE PRECISION,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
@@ -0,0 +1,23 @@
+CREATE TABLE upcoming_races (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    race_datetime TIMESTAMP WITH TIME ZONE NOT NULL,
+    latitude DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL,
+    distance DOUBLE PRECISION NOT NULL,
+    target_time DOUBLE PRECISION,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE race_forecasts (
+    id UUID PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    race_id UUID NOT NULL REFERENCES upcoming_races (id) ON DELETE CASCADE,
+    fetched_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    temperature DOUBLE PRECISION NOT NULL,
+    dew_point DOUBLE PRECISION NOT NULL,
+    humidity DOUBLE PRECISION,
+    wind_speed DOUBLE PRECISION,
+    precipitation_probability DOUBLE PRECISION
+);
+
+CREATE INDEX race_forecasts_race_id_idx ON race_forecasts (race_id, fetched_at);
@@ -0,0 +1,22 @@
+CREATE TABLE activity_provenance (
+    filename TEXT NOT NULL PRIMARY KEY,
+    provider TEXT NOT NULL,
+    import_path TEXT NOT NULL,
+    raw_file TEXT,
+    imported_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+ALTER TABLE scale_measurements ADD COLUMN provider TEXT;
+ALTER TABLE scale_measurements ADD COLUMN import_path TEXT;
+ALTER TABLE scale_measurements ADD COLUMN raw_file TEXT;
+ALTER TABLE scale_measurements ADD COLUMN imported_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE heartrate_streams ADD COLUMN provider TEXT;
+ALTER TABLE heartrate_streams ADD COLUMN import_path TEXT;
+ALTER TABLE heartrate_streams ADD COLUMN raw_file TEXT;
+ALTER TABLE heartrate_streams ADD COLUMN imported_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE biomarker_measurements ADD COLUMN provider TEXT;
+ALTER TABLE biomarker_measurements ADD COLUMN import_path TEXT;
+ALTER TABLE biomarker_measurements ADD COLUMN raw_file TEXT;
+ALTER TABLE biomarker_measurements ADD COLUMN imported_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,8 @@
+CREATE TABLE activity_stat_choices (
+    summary_id UUID NOT NULL REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    stat TEXT NOT NULL,
+    source TEXT NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    PRIMARY KEY (summary_id, stat)
+);
@@ -0,0 +1,17 @@
+CREATE TABLE matched_routes (
+    id UUID NOT NULL PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    sport TEXT NOT NULL,
+    distance DOUBLE PRECISION NOT NULL,
+    latitudes DOUBLE PRECISION[] NOT NULL,
+    longitudes DOUBLE PRECISION[] NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE activity_routes (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    route_id UUID REFERENCES matched_routes (id) ON DELETE CASCADE,
+    temperature DOUBLE PRECISION
+);
+
+CREATE INDEX activity_routes_route_id_idx ON activity_routes (route_id);
@@ -0,0 +1,10 @@
+CREATE TABLE activity_max_heart_rates (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    max_heart_rate DOUBLE PRECISION
+);
+
+CREATE TABLE heart_rate_settings (
+    effective_date DATE NOT NULL PRIMARY KEY,
+    max_heart_rate DOUBLE PRECISION NOT NULL,
+    resting_heart_rate DOUBLE PRECISION NOT NULL
+);
@@ -0,0 +1,5 @@
+CREATE TABLE activity_threshold_efforts (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    race_heart_rate DOUBLE PRECISION,
+    effort_heart_rate DOUBLE PRECISION
+);
@@ -0,0 +1,5 @@
+CREATE TABLE user_preferences (
+    email TEXT NOT NULL PRIMARY KEY,
+    language TEXT NOT NULL DEFAULT 'en',
+    last_modified TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
@@ -0,0 +1,7 @@
+ALTER TABLE strava_activities ADD COLUMN commute BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE activity_commutes (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    is_commute BOOLEAN NOT NULL,
+    source TEXT NOT NULL
+);
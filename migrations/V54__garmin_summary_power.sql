@@ -0,0 +1,2 @@
+ALTER TABLE garmin_summary ADD COLUMN avg_power DOUBLE PRECISION;
+ALTER TABLE garmin_summary ADD COLUMN normalized_power DOUBLE PRECISION;
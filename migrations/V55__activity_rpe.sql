@@ -0,0 +1,5 @@
+CREATE TABLE activity_rpe (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    rpe SMALLINT NOT NULL CHECK (rpe BETWEEN 1 AND 10),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
@@ -0,0 +1,29 @@
+CREATE TABLE garmin_connect_activity_details (
+    activity_id BIGINT PRIMARY KEY REFERENCES garmin_connect_activities (activity_id) ON DELETE CASCADE,
+    temperature DOUBLE PRECISION,
+    apparent_temperature DOUBLE PRECISION,
+    relative_humidity DOUBLE PRECISION,
+    wind_speed DOUBLE PRECISION,
+    wind_direction TEXT,
+    weather TEXT,
+    device_manufacturer TEXT,
+    device_id TEXT,
+    gear TEXT,
+    last_modified TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE TABLE garmin_connect_activity_splits (
+    activity_id BIGINT NOT NULL REFERENCES garmin_connect_activity_details (activity_id) ON DELETE CASCADE,
+    split_index INTEGER NOT NULL,
+    start_time_gmt TIMESTAMP WITH TIME ZONE NOT NULL,
+    distance DOUBLE PRECISION NOT NULL,
+    duration DOUBLE PRECISION NOT NULL,
+    moving_duration DOUBLE PRECISION,
+    average_hr DOUBLE PRECISION,
+    max_hr DOUBLE PRECISION,
+    calories DOUBLE PRECISION,
+    average_cadence DOUBLE PRECISION,
+    average_power DOUBLE PRECISION,
+    elevation_gain DOUBLE PRECISION,
+    PRIMARY KEY (activity_id, split_index)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE power_curves_analyzed (
+    summary_id UUID PRIMARY KEY REFERENCES garmin_summary (id) ON DELETE CASCADE,
+    analyzed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+INSERT INTO power_curves_analyzed (summary_id)
+SELECT DISTINCT summary_id FROM power_curves;
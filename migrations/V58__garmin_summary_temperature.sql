@@ -0,0 +1 @@
+ALTER TABLE garmin_summary ADD COLUMN avg_temperature DOUBLE PRECISION;
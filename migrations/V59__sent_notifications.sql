@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS sent_notifications (
+    event TEXT NOT NULL,
+    dedupe_key TEXT NOT NULL,
+    sent_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    PRIMARY KEY (event, dedupe_key)
+);
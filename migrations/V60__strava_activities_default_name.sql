@@ -0,0 +1,2 @@
+-- name Strava generated for the activity, recorded when it's first imported
+ALTER TABLE strava_activities ADD COLUMN default_name TEXT;
@@ -0,0 +1,5 @@
+ALTER TABLE strava_activities ADD COLUMN link_source TEXT;
+
+CREATE INDEX IF NOT EXISTS garmin_summary_begin_datetime_idx ON garmin_summary (begin_datetime);
+CREATE INDEX IF NOT EXISTS strava_activities_unlinked_start_date_idx
+    ON strava_activities (start_date) WHERE summary_id IS NULL;
@@ -0,0 +1,12 @@
+ALTER TABLE activity_clothing ADD COLUMN device_temperature BOOLEAN NOT NULL DEFAULT false;
+
+UPDATE activity_clothing c
+SET device_temperature = true
+WHERE c.temperature IS NOT NULL
+  AND NOT EXISTS (
+    SELECT 1
+    FROM garmin_connect_activities a
+    JOIN garmin_connect_activity_details d ON d.activity_id = a.activity_id
+    WHERE a.summary_id = c.summary_id
+      AND d.temperature = c.temperature
+  );
@@ -0,0 +1 @@
+ALTER TABLE scale_measurements ADD COLUMN connect_exported_at TIMESTAMP WITH TIME ZONE;
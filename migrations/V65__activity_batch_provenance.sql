@@ -0,0 +1,17 @@
+ALTER TABLE strava_activities ADD COLUMN provider TEXT;
+ALTER TABLE strava_activities ADD COLUMN import_path TEXT;
+ALTER TABLE strava_activities ADD COLUMN raw_file TEXT;
+ALTER TABLE strava_activities ADD COLUMN imported_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE garmin_connect_activities ADD COLUMN provider TEXT;
+ALTER TABLE garmin_connect_activities ADD COLUMN import_path TEXT;
+ALTER TABLE garmin_connect_activities ADD COLUMN raw_file TEXT;
+ALTER TABLE garmin_connect_activities ADD COLUMN imported_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE heartrate_batch_provenance (
+    date DATE NOT NULL PRIMARY KEY,
+    provider TEXT NOT NULL,
+    import_path TEXT NOT NULL,
+    raw_file TEXT,
+    imported_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
@@ -0,0 +1 @@
+ALTER TABLE garmin_summary ADD COLUMN calories_attempted BOOLEAN NOT NULL DEFAULT false;
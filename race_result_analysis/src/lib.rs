@@ -6,6 +6,8 @@
 #![allow(clippy::cast_possible_wrap)]
 #![allow(clippy::similar_names)]
 
+pub mod race_attachment;
 pub mod race_result_analysis;
+pub mod race_result_import;
 pub mod race_results;
 pub mod race_type;
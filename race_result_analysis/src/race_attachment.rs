@@ -0,0 +1,222 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use serde::{Deserialize, Serialize};
+use stack_string::{format_sstr, StackString};
+use std::{fmt, path::Path, str::FromStr, time::Duration};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use garmin_lib::{date_time_wrapper::DateTimeWrapper, garmin_config::GarminConfig};
+use garmin_models::garmin_sync::GarminSync;
+use garmin_utils::pgpool::PgPool;
+
+/// Links to attachments stay valid for this long
+pub const ATTACHMENT_URL_EXPIRY: Duration = Duration::from_secs(3600);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    Certificate,
+    Photo,
+    Other,
+}
+
+impl AttachmentKind {
+    #[must_use]
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Certificate => "certificate",
+            Self::Photo => "photo",
+            Self::Other => "other",
+        }
+    }
+
+    /// Pdfs are usually finisher certificates, images are photos
+    #[must_use]
+    pub fn from_filename(filename: &str) -> Self {
+        let extension = Path::new(filename)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("pdf") => Self::Certificate,
+            Some("jpg" | "jpeg" | "png" | "gif" | "heic" | "webp") => Self::Photo,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for AttachmentKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for AttachmentKind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "certificate" => Ok(Self::Certificate),
+            "photo" => Ok(Self::Photo),
+            "other" => Ok(Self::Other),
+            _ => Err(format_err!("Invalid attachment kind {s}")),
+        }
+    }
+}
+
+/// Finisher certificate, photo or other file stored in
+/// `race_attachment_bucket` for a race result
+#[derive(FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RaceAttachment {
+    pub id: Uuid,
+    pub race_id: Uuid,
+    pub kind: StackString,
+    pub filename: StackString,
+    pub s3_key: StackString,
+    pub created_at: DateTimeWrapper,
+}
+
+impl RaceAttachment {
+    #[must_use]
+    pub fn new(race_id: Uuid, kind: AttachmentKind, filename: &str) -> Self {
+        let id = Uuid::new_v4();
+        let filename = Path::new(filename)
+            .file_name()
+            .map_or_else(|| filename.into(), |f| f.to_string_lossy());
+        Self {
+            id,
+            race_id,
+            kind: kind.to_str().into(),
+            s3_key: format_sstr!("{race_id}/{id}_{filename}"),
+            filename: filename.as_ref().into(),
+            created_at: OffsetDateTime::now_utc().into(),
+        }
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_race_id(pool: &PgPool, race_id: Uuid) -> Result<Vec<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, race_id, kind, filename, s3_key, created_at
+                FROM race_result_attachments
+                WHERE race_id = $race_id
+                ORDER BY created_at
+            ",
+            race_id = race_id,
+        );
+        let conn = pool.get().await?;
+        query.fetch(&conn).await.map_err(Into::into)
+    }
+
+    /// # Errors
+    /// Return error if db query fails
+    pub async fn get_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, Error> {
+        let query = query!(
+            "
+                SELECT id, race_id, kind, filename, s3_key, created_at
+                FROM race_result_attachments
+                WHERE id = $id
+            ",
+            id = id,
+        );
+        let conn = pool.get().await?;
+        query.fetch_opt(&conn).await.map_err(Into::into)
+    }
+
+    /// Upload `local_file` to `race_attachment_bucket` and store the record
+    /// # Errors
+    /// Return error if the upload or db query fails
+    pub async fn upload(
+        &self,
+        pool: &PgPool,
+        gsync: &GarminSync,
+        config: &GarminConfig,
+        local_file: &Path,
+    ) -> Result<(), Error> {
+        gsync
+            .upload_file(local_file, &config.race_attachment_bucket, &self.s3_key)
+            .await?;
+        let query = query!(
+            "
+                INSERT INTO race_result_attachments
+                    (id, race_id, kind, filename, s3_key, created_at)
+                VALUES ($id, $race_id, $kind, $filename, $s3_key, $created_at)
+            ",
+            id = self.id,
+            race_id = self.race_id,
+            kind = self.kind,
+            filename = self.filename,
+            s3_key = self.s3_key,
+            created_at = self.created_at,
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// Return error if presigning fails
+    pub async fn get_url(
+        &self,
+        gsync: &GarminSync,
+        config: &GarminConfig,
+    ) -> Result<StackString, Error> {
+        gsync
+            .get_presigned_url(
+                &config.race_attachment_bucket,
+                &self.s3_key,
+                ATTACHMENT_URL_EXPIRY,
+            )
+            .await
+    }
+
+    /// Remove the record and the file in s3
+    /// # Errors
+    /// Return error if s3 or db query fails
+    pub async fn delete(
+        &self,
+        pool: &PgPool,
+        gsync: &GarminSync,
+        config: &GarminConfig,
+    ) -> Result<(), Error> {
+        gsync
+            .delete_file(&config.race_attachment_bucket, &self.s3_key)
+            .await?;
+        let query = query!(
+            "DELETE FROM race_result_attachments WHERE id = $id",
+            id = self.id
+        );
+        let conn = pool.get().await?;
+        query.execute(&conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::race_attachment::{AttachmentKind, RaceAttachment};
+
+    #[test]
+    fn test_race_attachment_new() {
+        assert_eq!(
+            AttachmentKind::from_filename("certificate.PDF"),
+            AttachmentKind::Certificate
+        );
+        assert_eq!(
+            AttachmentKind::from_filename("finish.jpeg"),
+            AttachmentKind::Photo
+        );
+        assert_eq!(
+            AttachmentKind::from_filename("notes"),
+            AttachmentKind::Other
+        );
+
+        let race_id = Uuid::new_v4();
+        let attachment = RaceAttachment::new(race_id, AttachmentKind::Photo, "/tmp/x/finish.jpg");
+        assert_eq!(attachment.filename, "finish.jpg");
+        assert_eq!(attachment.kind, "photo");
+        assert!(attachment.s3_key.starts_with(&race_id.to_string()));
+        assert!(attachment.s3_key.ends_with("_finish.jpg"));
+    }
+}
@@ -4,13 +4,13 @@ use ndarray::{array, Array1};
 use postgres_query::{query, FromSqlRow};
 use rusfun::{curve_fit::Minimizer, func1d::Func1D};
 use stack_string::StackString;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use time::{Date, OffsetDateTime};
 use time_tz::{OffsetDateTimeExt, Tz};
 use uuid::Uuid;
 
 use garmin_lib::date_time_wrapper::DateTimeWrapper;
-use garmin_models::garmin_summary::GarminSummary;
+use garmin_models::{garmin_summary::GarminSummary, split_differential::SplitType};
 use garmin_utils::{
     garmin_util::{print_h_m_s, MARATHON_DISTANCE_M, METERS_PER_MILE},
     pgpool::PgPool,
@@ -46,6 +46,47 @@ pub enum ParamType {
     Neg,
 }
 
+/// How the halves of races at one distance compared, see
+/// `garmin_models::split_differential`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitTendency {
+    pub race_distance: i32,
+    pub race_count: usize,
+    pub negative_count: usize,
+    pub positive_count: usize,
+    pub mean_differential: f64,
+}
+
+fn split_tendency_by_distance(
+    differentials: impl IntoIterator<Item = (i32, f64)>,
+) -> Vec<SplitTendency> {
+    let mut by_distance: BTreeMap<i32, Vec<f64>> = BTreeMap::new();
+    for (race_distance, differential) in differentials {
+        by_distance
+            .entry(race_distance)
+            .or_default()
+            .push(differential);
+    }
+    by_distance
+        .into_iter()
+        .map(|(race_distance, values)| {
+            let count_type = |split_type| {
+                values
+                    .iter()
+                    .filter(|d| SplitType::from_differential(**d) == split_type)
+                    .count()
+            };
+            SplitTendency {
+                race_distance,
+                race_count: values.len(),
+                negative_count: count_type(SplitType::Negative),
+                positive_count: count_type(SplitType::Positive),
+                mean_differential: values.iter().sum::<f64>() / values.len() as f64,
+            }
+        })
+        .collect()
+}
+
 pub struct PlotData {
     pub data: Vec<(i32, f64, StackString, Date, StackString)>,
     pub other_data: Vec<(i32, f64, StackString, Date, StackString)>,
@@ -113,6 +154,26 @@ impl RaceResultAnalysis {
         })
     }
 
+    /// Split differential of the activity recorded during `result`
+    #[must_use]
+    pub fn split_differential(&self, result: &RaceResults) -> Option<f64> {
+        result
+            .race_summary_ids
+            .iter()
+            .filter_map(|id| id.and_then(|i| self.summary_map.get(&i)))
+            .find_map(|s| s.split_differential)
+    }
+
+    /// Tendency to positive or negative split for each race distance with a
+    /// recorded activity
+    #[must_use]
+    pub fn split_tendency(&self) -> Vec<SplitTendency> {
+        split_tendency_by_distance(self.data.iter().filter_map(|result| {
+            self.split_differential(result)
+                .map(|d| (result.race_distance, d))
+        }))
+    }
+
     #[must_use]
     pub fn params(&self, param_type: ParamType) -> Array1<f64> {
         match param_type {
@@ -257,7 +318,30 @@ mod tests {
     use garmin_lib::garmin_config::GarminConfig;
     use garmin_utils::pgpool::PgPool;
 
-    use crate::{race_result_analysis::RaceResultAggregated, race_type::RaceType};
+    use crate::{
+        race_result_analysis::{split_tendency_by_distance, RaceResultAggregated},
+        race_type::RaceType,
+    };
+
+    #[test]
+    fn test_split_tendency_by_distance() {
+        let tendency = split_tendency_by_distance(vec![
+            (5000, -0.02),
+            (42195, 0.08),
+            (5000, 0.03),
+            (5000, 0.005),
+            (42195, 0.04),
+        ]);
+        assert_eq!(tendency.len(), 2);
+        assert_eq!(tendency[0].race_distance, 5000);
+        assert_eq!(tendency[0].race_count, 3);
+        assert_eq!(tendency[0].negative_count, 1);
+        assert_eq!(tendency[0].positive_count, 1);
+        assert!((tendency[0].mean_differential - 0.005).abs() < 1e-9);
+        assert_eq!(tendency[1].race_distance, 42195);
+        assert_eq!(tendency[1].positive_count, 2);
+        assert!((tendency[1].mean_differential - 0.06).abs() < 1e-9);
+    }
 
     #[tokio::test]
     #[ignore]
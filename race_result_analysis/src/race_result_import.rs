@@ -0,0 +1,357 @@
+use anyhow::{format_err, Error};
+use postgres_query::{query, FromSqlRow};
+use stack_string::{format_sstr, StackString};
+use std::collections::HashMap;
+use time::{macros::format_description, Date, Duration};
+use time_tz::OffsetDateTimeExt;
+use uuid::Uuid;
+
+use garmin_lib::date_time_wrapper::DateTimeWrapper;
+use garmin_utils::{garmin_util::parse_race_distance, pgpool::PgPool};
+
+use crate::{
+    race_results::{parse_time_string, RaceResults},
+    race_type::RaceType,
+};
+
+/// Activities whose distance is within this fraction of the race distance
+/// are linked to the result
+pub const DISTANCE_TOLERANCE: f64 = 0.1;
+
+/// Columns of an Athlinks results export or a timing company's results csv,
+/// matched case insensitively against these header names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Column {
+    RaceName,
+    RaceDate,
+    Distance,
+    Name,
+    FirstName,
+    LastName,
+    Bib,
+    ChipTime,
+    GunTime,
+}
+
+impl Column {
+    fn from_header(header: &str) -> Option<Self> {
+        match header.trim().to_lowercase().as_str() {
+            "event" | "event name" | "race" | "race name" => Some(Self::RaceName),
+            "date" | "event date" | "race date" | "start date" => Some(Self::RaceDate),
+            "distance" | "course" | "course name" | "race distance" => Some(Self::Distance),
+            "name" | "athlete" | "athlete name" | "full name" | "participant" => Some(Self::Name),
+            "first name" | "firstname" => Some(Self::FirstName),
+            "last name" | "lastname" => Some(Self::LastName),
+            "bib" | "bib #" | "bib number" => Some(Self::Bib),
+            "chip time" | "net time" | "time" | "finish time" | "official time" => {
+                Some(Self::ChipTime)
+            }
+            "gun time" | "clock time" => Some(Self::GunTime),
+            _ => None,
+        }
+    }
+}
+
+/// Race name, date and distance for exports which only list finishers, and
+/// the athlete to pick out of a full field
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RaceResultImportOptions {
+    pub race_name: Option<StackString>,
+    pub race_date: Option<Date>,
+    /// Distance in meters
+    pub race_distance: Option<i32>,
+    pub athlete: Option<StackString>,
+}
+
+/// One finisher record from a results export
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalRaceResult {
+    pub race_name: Option<StackString>,
+    pub race_date: Option<Date>,
+    /// Distance in meters
+    pub race_distance: Option<i32>,
+    pub name: Option<StackString>,
+    pub bib: Option<StackString>,
+    pub chip_time: Option<f64>,
+    pub gun_time: Option<f64>,
+}
+
+impl ExternalRaceResult {
+    /// Official time, chip time if the export has it, otherwise gun time
+    #[must_use]
+    pub fn official_time(&self) -> Option<f64> {
+        self.chip_time.or(self.gun_time)
+    }
+
+    /// Records without a distance or a time can't be turned into results
+    #[must_use]
+    pub fn to_race_result(&self) -> Option<RaceResults> {
+        Some(RaceResults {
+            id: Uuid::new_v4(),
+            race_type: RaceType::Personal,
+            race_date: self.race_date,
+            race_name: self.race_name.clone(),
+            race_distance: self.race_distance?,
+            race_time: self.official_time()?,
+            race_flag: false,
+            race_summary_ids: Vec::new(),
+            bib_number: self.bib.clone(),
+            race_report: None,
+        })
+    }
+}
+
+/// Parse an Athlinks csv export (one row per race of one athlete) or a
+/// chip timing csv (one row per finisher of one race), missing race fields
+/// are filled in from `options`, rows not matching `options.athlete` are
+/// skipped
+/// # Errors
+/// Return error if the csv can't be read or has no time column
+pub fn parse_race_result_csv(
+    input: &str,
+    options: &RaceResultImportOptions,
+) -> Result<Vec<ExternalRaceResult>, Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+    let mut columns: HashMap<Column, usize> = HashMap::new();
+    for (idx, header) in reader.headers()?.iter().enumerate() {
+        if let Some(column) = Column::from_header(header) {
+            columns.entry(column).or_insert(idx);
+        }
+    }
+    if !columns.contains_key(&Column::ChipTime) && !columns.contains_key(&Column::GunTime) {
+        return Err(format_err!("No time column in race results"));
+    }
+    let athlete = options.athlete.as_ref().map(|a| a.to_lowercase());
+
+    let mut results = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let get = |column: Column| {
+            columns
+                .get(&column)
+                .and_then(|idx| record.get(*idx))
+                .filter(|s| !s.is_empty())
+        };
+        let name: Option<StackString> = match (get(Column::Name), get(Column::FirstName)) {
+            (Some(name), _) => Some(name.into()),
+            (None, Some(first)) => Some(match get(Column::LastName) {
+                Some(last) => format_sstr!("{first} {last}"),
+                None => first.into(),
+            }),
+            (None, None) => None,
+        };
+        if let Some(athlete) = &athlete {
+            if name.as_ref().map(|n| n.to_lowercase()).as_ref() != Some(athlete) {
+                continue;
+            }
+        }
+        let result = ExternalRaceResult {
+            race_name: get(Column::RaceName)
+                .map(Into::into)
+                .or_else(|| options.race_name.clone()),
+            race_date: get(Column::RaceDate)
+                .and_then(parse_race_date)
+                .or(options.race_date),
+            race_distance: get(Column::Distance)
+                .and_then(|d| parse_race_distance(d).ok())
+                .map(|d| d as i32)
+                .or(options.race_distance),
+            name,
+            bib: get(Column::Bib).map(Into::into),
+            chip_time: get(Column::ChipTime).and_then(parse_time_string),
+            gun_time: get(Column::GunTime).and_then(parse_time_string),
+        };
+        if result.official_time().is_some() {
+            results.push(result);
+        }
+    }
+    results.shrink_to_fit();
+    Ok(results)
+}
+
+/// Dates as written by Athlinks (`Apr 21, 2024`, `4/21/2024`) or iso
+fn parse_race_date(s: &str) -> Option<Date> {
+    Date::parse(s, format_description!("[year]-[month]-[day]"))
+        .or_else(|_| {
+            Date::parse(
+                s,
+                format_description!("[month padding:none]/[day padding:none]/[year]"),
+            )
+        })
+        .or_else(|_| {
+            Date::parse(
+                s,
+                format_description!("[month repr:short] [day padding:none], [year]"),
+            )
+        })
+        .ok()
+}
+
+#[derive(FromSqlRow)]
+struct CandidateActivity {
+    id: Uuid,
+    begin_datetime: DateTimeWrapper,
+    total_distance: f64,
+}
+
+/// Activities on the (local) race date within `DISTANCE_TOLERANCE` of the
+/// race distance, closest first
+/// # Errors
+/// Return error if db query fails
+pub async fn find_race_activities(
+    pool: &PgPool,
+    race_date: Date,
+    race_distance: i32,
+) -> Result<Vec<Uuid>, Error> {
+    let query = query!(
+        "
+            SELECT id, begin_datetime, total_distance
+            FROM garmin_summary
+            WHERE date(begin_datetime at time zone 'utc') >= $start_date
+              AND date(begin_datetime at time zone 'utc') <= $end_date
+        ",
+        start_date = race_date - Duration::days(1),
+        end_date = race_date + Duration::days(1),
+    );
+    let conn = pool.get().await?;
+    let activities: Vec<CandidateActivity> = query.fetch(&conn).await?;
+    Ok(match_race_activities(&activities, race_date, race_distance))
+}
+
+fn match_race_activities(
+    activities: &[CandidateActivity],
+    race_date: Date,
+    race_distance: i32,
+) -> Vec<Uuid> {
+    let local = DateTimeWrapper::local_tz();
+    let race_distance = f64::from(race_distance);
+    let mut matches: Vec<_> = activities
+        .iter()
+        .filter(|a| a.begin_datetime.to_timezone(local).date() == race_date)
+        .map(|a| {
+            (
+                a.id,
+                (a.total_distance - race_distance).abs() / race_distance,
+            )
+        })
+        .filter(|(_, diff)| *diff <= DISTANCE_TOLERANCE)
+        .collect();
+    matches.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    matches.into_iter().map(|(id, _)| id).collect()
+}
+
+/// Store imported results, linking each to the gps activity of the race
+/// when one matches the date and distance, a result already stored for the
+/// same race name and date is updated
+/// # Errors
+/// Return error if db queries fail
+pub async fn import_race_results(
+    pool: &PgPool,
+    results: &[ExternalRaceResult],
+) -> Result<Vec<RaceResults>, Error> {
+    let mut output = Vec::new();
+    for external in results {
+        let mut result = match external.to_race_result() {
+            Some(result) => result,
+            None => continue,
+        };
+        if let Some(race_date) = result.race_date {
+            result.race_summary_ids = find_race_activities(pool, race_date, result.race_distance)
+                .await?
+                .into_iter()
+                .take(1)
+                .map(Some)
+                .collect();
+        }
+        if result.race_name.is_some() && result.race_date.is_some() {
+            result.set_race_id(pool).await?;
+        }
+        result.upsert_db(pool).await?;
+        output.push(result);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Error;
+    use time::macros::{date, datetime};
+    use uuid::Uuid;
+
+    use garmin_utils::garmin_util::MARATHON_DISTANCE_M;
+
+    use crate::race_result_import::{
+        match_race_activities, parse_race_result_csv, CandidateActivity, RaceResultImportOptions,
+    };
+
+    #[test]
+    fn test_parse_athlinks_csv() -> Result<(), Error> {
+        let buf = "Event Name,Event Date,Course Name,Time,Pace,Overall Rank,Bib\n\
+                   Boston Marathon,\"Apr 15, 2024\",Marathon,3:05:12,7:04,4512,12345\n\
+                   Turkey Trot,11/28/2024,5K,21:30,6:55,35,\n\
+                   Some Relay,2024-06-01,Relay Leg,,,,\n";
+        let results = parse_race_result_csv(buf, &RaceResultImportOptions::default())?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].race_date, Some(date!(2024 - 04 - 15)));
+        assert_eq!(results[0].race_distance, Some(MARATHON_DISTANCE_M));
+        assert_eq!(results[0].chip_time, Some(3.0 * 3600.0 + 5.0 * 60.0 + 12.0));
+        assert_eq!(results[0].bib.as_ref().map(|b| b.as_str()), Some("12345"));
+        assert_eq!(results[1].race_date, Some(date!(2024 - 11 - 28)));
+        assert_eq!(results[1].race_distance, Some(5000));
+        let race = results[1].to_race_result().unwrap();
+        assert_eq!(
+            race.race_name.as_ref().map(|n| n.as_str()),
+            Some("Turkey Trot")
+        );
+        assert_eq!(race.race_time, 21.0 * 60.0 + 30.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_chip_timing_csv() -> Result<(), Error> {
+        let buf = "Place,Bib,First Name,Last Name,Age,Gun Time,Chip Time\n\
+                   1,101,Jane,Doe,31,35:10,35:08\n\
+                   2,202,John,Smith,40,38:45,38:30\n";
+        let options = RaceResultImportOptions {
+            race_name: Some("Prospect Park 10K".into()),
+            race_date: Some(date!(2024 - 05 - 12)),
+            race_distance: None,
+            athlete: Some("john smith".into()),
+        };
+        let results = parse_race_result_csv(buf, &options)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].chip_time, Some(38.0 * 60.0 + 30.0));
+        assert_eq!(results[0].gun_time, Some(38.0 * 60.0 + 45.0));
+        // no distance in the file or options
+        assert!(results[0].to_race_result().is_none());
+
+        assert!(parse_race_result_csv("Place,Name\n1,Jane Doe\n", &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_race_activities() {
+        let race = Uuid::new_v4();
+        let warmup = Uuid::new_v4();
+        let activities = vec![
+            CandidateActivity {
+                id: warmup,
+                begin_datetime: datetime!(2024-05-12 12:00:00 UTC).into(),
+                total_distance: 3_000.0,
+            },
+            CandidateActivity {
+                id: race,
+                begin_datetime: datetime!(2024-05-12 13:00:00 UTC).into(),
+                total_distance: 10_150.0,
+            },
+        ];
+        assert_eq!(
+            match_race_activities(&activities, date!(2024 - 05 - 12), 10_000),
+            vec![race]
+        );
+        assert!(match_race_activities(&activities, date!(2024 - 05 - 13), 10_000).is_empty());
+    }
+}
@@ -32,6 +32,11 @@ pub struct RaceResults {
     pub race_time: f64,
     pub race_flag: bool,
     pub race_summary_ids: Vec<Option<Uuid>>,
+    #[serde(default)]
+    pub bib_number: Option<StackString>,
+    /// Free text race report
+    #[serde(default)]
+    pub race_report: Option<StackString>,
 }
 
 impl Display for RaceResults {
@@ -83,11 +88,12 @@ impl RaceResults {
     ) -> Result<impl Stream<Item = Result<Self, PqError>>, Error> {
         let query = query!(
             "SELECT a.id, a.race_type, a.race_date, a.race_name, a.race_distance, a.race_time,
-                    a.race_flag, array_agg(b.summary_id) as race_summary_ids
+                    a.race_flag, array_agg(b.summary_id) as race_summary_ids,
+                    a.bib_number, a.race_report
             FROM race_results a
             LEFT JOIN race_results_garmin_summary b ON a.id = b.race_id
             WHERE a.race_type = $race_type
-            GROUP BY 1,2,3,4,5,6,7
+            GROUP BY 1,2,3,4,5,6,7,9,10
             ORDER BY a.race_date, a.race_distance",
             race_type = race_type
         );
@@ -100,11 +106,12 @@ impl RaceResults {
     pub async fn get_result_by_id(id: Uuid, pool: &PgPool) -> Result<Option<Self>, Error> {
         let query = query!(
             "SELECT a.id, a.race_type, a.race_date, a.race_name, a.race_distance, a.race_time,
-                    a.race_flag, array_agg(b.summary_id) as race_summary_ids
+                    a.race_flag, array_agg(b.summary_id) as race_summary_ids,
+                    a.bib_number, a.race_report
             FROM race_results a
             LEFT JOIN race_results_garmin_summary b ON a.id = b.race_id
             WHERE a.id = $id
-            GROUP BY 1,2,3,4,5,6,7",
+            GROUP BY 1,2,3,4,5,6,7,9,10",
             id = id
         );
         let conn = pool.get().await?;
@@ -120,11 +127,12 @@ impl RaceResults {
     ) -> Result<Vec<Self>, Error> {
         let query = query!(
             "SELECT a.id, a.race_type, a.race_date, a.race_name, a.race_distance, a.race_time,
-                    a.race_flag, array_agg(b.summary_id) as race_summary_ids
+                    a.race_flag, array_agg(b.summary_id) as race_summary_ids,
+                    a.bib_number, a.race_report
             FROM race_results a
             LEFT JOIN race_results_garmin_summary b ON a.id = b.race_id
             WHERE a.race_date = $race_date and a.race_type = $race_type
-            GROUP BY 1,2,3,4,5,6,7",
+            GROUP BY 1,2,3,4,5,6,7,9,10",
             race_date = race_date,
             race_type = race_type,
         );
@@ -140,7 +148,8 @@ impl RaceResults {
     ) -> Result<Option<Self>, Error> {
         let query = query!(
             "SELECT a.id, a.race_type, a.race_date, a.race_name, a.race_distance, a.race_time,
-                    a.race_flag, array_agg(b.summary_id) as race_summary_ids
+                    a.race_flag, array_agg(b.summary_id) as race_summary_ids,
+                    a.bib_number, a.race_report
             FROM race_results a
             JOIN race_results_garmin_summary b ON a.id = b.race_id
             WHERE a.id = (
@@ -148,7 +157,7 @@ impl RaceResults {
                 FROM race_results_garmin_summary b
                 WHERE b.summary_id = $summary_id
             )
-            GROUP BY 1,2,3,4,5,6,7",
+            GROUP BY 1,2,3,4,5,6,7,9,10",
             summary_id = summary_id,
         );
         let conn = pool.get().await?;
@@ -164,11 +173,12 @@ impl RaceResults {
     ) -> Result<Vec<Self>, Error> {
         let query = query!(
             "SELECT a.id, a.race_type, a.race_date, a.race_name, a.race_distance, a.race_time,
-                    a.race_flag, array_agg(b.summary_id) as race_summary_ids
+                    a.race_flag, array_agg(b.summary_id) as race_summary_ids,
+                    a.bib_number, a.race_report
             FROM race_results a
             LEFT JOIN race_results_garmin_summary b ON a.id = b.race_id
             WHERE a.race_distance = $race_distance and a.race_type = $race_type
-            GROUP BY 1,2,3,4,5,6,7",
+            GROUP BY 1,2,3,4,5,6,7,9,10",
             race_distance = race_distance,
             race_type = race_type,
         );
@@ -249,10 +259,12 @@ impl RaceResults {
         let query = query!(
             "
                 INSERT INTO race_results (
-                    race_type, race_date, race_name, race_distance, race_time, race_flag
+                    race_type, race_date, race_name, race_distance, race_time, race_flag,
+                    bib_number, race_report
                 )
                 VALUES (
-                    $race_type, $race_date, $race_name, $race_distance, $race_time, $race_flag
+                    $race_type, $race_date, $race_name, $race_distance, $race_time, $race_flag,
+                    $bib_number, $race_report
                 )
              ",
             race_type = self.race_type,
@@ -261,6 +273,8 @@ impl RaceResults {
             race_distance = self.race_distance,
             race_time = self.race_time,
             race_flag = self.race_flag,
+            bib_number = self.bib_number,
+            race_report = self.race_report,
         );
         let conn = pool.get().await?;
         query.execute(&conn).await?;
@@ -273,7 +287,8 @@ impl RaceResults {
         let query = query!(
             "UPDATE race_results
             SET race_type=$race_type,race_date=$race_date,race_name=$race_name,
-                race_distance=$race_distance,race_time=$race_time,race_flag=$race_flag
+                race_distance=$race_distance,race_time=$race_time,race_flag=$race_flag,
+                bib_number=$bib_number,race_report=$race_report
             WHERE id=$id",
             id = self.id,
             race_type = self.race_type,
@@ -282,6 +297,8 @@ impl RaceResults {
             race_distance = self.race_distance,
             race_time = self.race_time,
             race_flag = self.race_flag,
+            bib_number = self.bib_number,
+            race_report = self.race_report,
         );
         let conn = pool.get().await?;
         query.execute(&conn).await?;
@@ -374,6 +391,8 @@ impl RaceResults {
                     race_time,
                     race_flag: false,
                     race_summary_ids: Vec::new(),
+                    bib_number: None,
+                    race_report: None,
                 })
             })
             .collect();
@@ -405,6 +424,8 @@ impl RaceResults {
                     race_time,
                     race_flag: false,
                     race_summary_ids: Vec::new(),
+                    bib_number: None,
+                    race_report: None,
                 })
             })
             .collect();
@@ -413,7 +434,7 @@ impl RaceResults {
     }
 }
 
-fn parse_time_string(s: &str) -> Option<f64> {
+pub(crate) fn parse_time_string(s: &str) -> Option<f64> {
     let times: SmallVec<[&str; 3]> = s.split(':').rev().take(3).collect();
 
     let seconds: f64 = match times.first().and_then(|t| t.parse().ok()) {
@@ -445,6 +466,8 @@ impl From<GarminSummary> for RaceResults {
             race_time: item.total_duration,
             race_flag: false,
             race_summary_ids: vec![Some(item.id)],
+            bib_number: None,
+            race_report: None,
         }
     }
 }
@@ -482,6 +505,8 @@ mod tests {
             race_time: 1563.0,
             race_flag: false,
             race_summary_ids: Vec::new(),
+            bib_number: None,
+            race_report: None,
         }
     }
 
@@ -0,0 +1,27 @@
+#!/bin/bash
+# Postgres for the ignored integration tests, matching tests/data/test.env
+
+CONTAINER=garmin_rust_test_db
+
+case "$1" in
+    start)
+        docker run -d --rm --name $CONTAINER \
+            -e POSTGRES_USER=test \
+            -e POSTGRES_PASSWORD=test \
+            -e POSTGRES_DB=garmin_summary_test \
+            -p 5432:5432 \
+            postgres:16-alpine
+        until docker exec $CONTAINER pg_isready -U test -d garmin_summary_test; do
+            sleep 1
+        done
+        (set -a; . tests/data/test.env; set +a; \
+            cargo run --bin garmin-rust-cli -- run-migrations)
+        ;;
+    stop)
+        docker stop $CONTAINER
+        ;;
+    *)
+        echo "usage: $0 start|stop"
+        exit 1
+        ;;
+esac
@@ -0,0 +1,152 @@
+#![allow(clippy::too_many_lines)]
+#![allow(clippy::module_name_repetitions)]
+#![allow(clippy::cast_precision_loss)]
+#![allow(clippy::cast_sign_loss)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_possible_wrap)]
+
+use anyhow::{format_err, Error};
+use btleplug::{
+    api::{bleuuid::uuid_from_u16, Central, Manager as _, Peripheral as _, ScanFilter},
+    platform::{Manager, Peripheral},
+};
+use clap::Parser;
+use futures::StreamExt;
+use log::{error, info};
+use stack_string::StackString;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::{signal::ctrl_c, task::spawn_blocking, time::interval};
+
+use fitbit_lib::ble_heartrate::{HeartRateRecorder, HEART_RATE_MEASUREMENT, HEART_RATE_SERVICE};
+use garmin_lib::garmin_config::GarminConfig;
+
+#[derive(Parser, Debug, Clone)]
+struct RecorderOpts {
+    /// Only connect to a strap whose advertised name contains this string
+    #[clap(short, long)]
+    name: Option<StackString>,
+    /// Seconds to scan for heart rate straps
+    #[clap(short, long, default_value = "10")]
+    scan_seconds: u64,
+    /// Seconds between writes to the heartrate archive
+    #[clap(short, long, default_value = "60")]
+    flush_seconds: u64,
+}
+
+async fn find_peripheral(opts: &RecorderOpts) -> Result<Peripheral, Error> {
+    let manager = Manager::new().await?;
+    let central = manager
+        .adapters()
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format_err!("No bluetooth adapter found"))?;
+    central
+        .start_scan(ScanFilter {
+            services: vec![uuid_from_u16(HEART_RATE_SERVICE)],
+        })
+        .await?;
+    tokio::time::sleep(Duration::from_secs(opts.scan_seconds)).await;
+    central.stop_scan().await?;
+    for peripheral in central.peripherals().await? {
+        let local_name = match peripheral.properties().await? {
+            Some(properties) => properties.local_name,
+            None => continue,
+        };
+        let matches = match (&opts.name, &local_name) {
+            (None, _) => true,
+            (Some(name), Some(local_name)) => local_name.contains(name.as_str()),
+            (Some(_), None) => false,
+        };
+        if matches {
+            info!("found {local_name:?} {}", peripheral.address());
+            return Ok(peripheral);
+        }
+    }
+    Err(format_err!("No heart rate strap found"))
+}
+
+/// Write buffered samples on a blocking thread, the recorder is handed back
+/// with its samples intact when writing fails so the next flush retries them
+async fn flush(
+    config: &GarminConfig,
+    recorder: HeartRateRecorder,
+) -> (HeartRateRecorder, Result<(), Error>) {
+    let config = config.clone();
+    let (recorder, result) = match spawn_blocking(move || {
+        let mut recorder = recorder;
+        let result = recorder.flush(&config);
+        (recorder, result)
+    })
+    .await
+    {
+        Ok(output) => output,
+        Err(e) => return (HeartRateRecorder::new(), Err(e.into())),
+    };
+    match result {
+        Ok(dates) => {
+            info!("wrote {dates:?}");
+            (recorder, Ok(()))
+        }
+        Err(e) => (recorder, Err(e)),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    env_logger::init();
+    let config = GarminConfig::get_config(None)?;
+    let opts = RecorderOpts::parse();
+
+    let peripheral = find_peripheral(&opts).await?;
+    peripheral.connect().await?;
+    peripheral.discover_services().await?;
+    let measurement_uuid = uuid_from_u16(HEART_RATE_MEASUREMENT);
+    let characteristic = peripheral
+        .characteristics()
+        .into_iter()
+        .find(|c| c.uuid == measurement_uuid)
+        .ok_or_else(|| format_err!("Heart Rate Measurement characteristic not found"))?;
+    peripheral.subscribe(&characteristic).await?;
+    let mut notifications = peripheral.notifications().await?;
+
+    let mut recorder = HeartRateRecorder::new();
+    let mut flush_interval = interval(Duration::from_secs(opts.flush_seconds));
+    let shutdown = ctrl_c();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            notification = notifications.next() => {
+                match notification {
+                    Some(notification) if notification.uuid == measurement_uuid => {
+                        if let Some(value) = recorder
+                            .add_measurement(OffsetDateTime::now_utc(), &notification.value)
+                        {
+                            info!("heartrate {value}");
+                        }
+                    }
+                    Some(_) => {}
+                    None => {
+                        info!("strap disconnected");
+                        break;
+                    }
+                }
+            }
+            _ = flush_interval.tick() => {
+                if !recorder.is_empty() {
+                    let (r, result) = flush(&config, std::mem::take(&mut recorder)).await;
+                    recorder = r;
+                    if let Err(e) = result {
+                        error!("flush failed, keeping {} samples: {e}", recorder.len());
+                    }
+                }
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+    let (_, result) = flush(&config, recorder).await;
+    peripheral.disconnect().await.ok();
+    result
+}
@@ -11,8 +11,6 @@ use garmin_cli::garmin_cli_opts::GarminCliOpts;
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
-
     match GarminCliOpts::process_args().await {
         Ok(()) => (),
         Err(e) => {
@@ -5,11 +5,30 @@
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_possible_wrap)]
 
-use garmin_http::garmin_rust_app::start_app;
+use anyhow::Error;
+use clap::Parser;
+
+use garmin_http::garmin_rust_app::{init_app, start_app};
+use garmin_lib::{garmin_config::GarminConfig, telemetry::Telemetry};
+
+#[derive(Parser, Debug)]
+struct HttpOpts {
+    /// Create missing data directories and secret keys and apply the db
+    /// migrations before starting
+    #[clap(long)]
+    init: bool,
+}
 
 /// Start tokio and add our app to it
 #[tokio::main]
-async fn main() {
-    env_logger::init();
-    start_app().await.unwrap();
+async fn main() -> Result<(), Error> {
+    let opts = HttpOpts::parse();
+    let config = GarminConfig::get_config(None)?;
+    let _telemetry = Telemetry::init(&config, "garmin-rust-http")?;
+    if opts.init {
+        for line in init_app(&config).await? {
+            println!("{line}");
+        }
+    }
+    start_app().await
 }
@@ -6,21 +6,33 @@
 #![allow(clippy::cast_possible_wrap)]
 #![type_length_limit = "1059389"]
 
+use anyhow::Error;
 use clap::Parser;
-use stack_string::StackString;
+use std::path::PathBuf;
 
-use fitbit_lib::fitbit_heartrate::import_fitbit_json_files;
+use fitbit_lib::fitbit_export::import_fitbit_export;
 use garmin_lib::garmin_config::GarminConfig;
+use garmin_utils::pgpool::PgPool;
 
 #[derive(Parser, Debug, Clone)]
 pub struct JsonImportOpts {
+    /// Fitbit data export, either the zip or the extracted directory
     #[clap(short = 'd', long = "directory")]
-    pub directory: StackString,
+    pub directory: PathBuf,
 }
 
-fn main() {
+#[tokio::main]
+async fn main() -> Result<(), Error> {
     env_logger::init();
-    let config = GarminConfig::get_config(None).unwrap();
+    let config = GarminConfig::get_config(None)?;
     let opts = JsonImportOpts::parse();
-    import_fitbit_json_files(&config, opts.directory.as_str()).unwrap();
+    let pool = PgPool::new(&config.pgurl)?;
+    let output = import_fitbit_export(&config, &pool, &opts.directory, |line| {
+        println!("{line}");
+    })
+    .await?;
+    for line in output {
+        println!("{line}");
+    }
+    Ok(())
 }
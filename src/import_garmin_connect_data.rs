@@ -14,7 +14,10 @@ use fitbit_lib::fitbit_heartrate::{
     import_garmin_heartrate_file, import_garmin_json_file, FitbitHeartRate,
 };
 use garmin_lib::garmin_config::GarminConfig;
-use garmin_models::garmin_connect_activity::import_garmin_connect_activity_json_file;
+use garmin_models::{
+    garmin_connect_activity::import_garmin_connect_activity_json_file,
+    provenance::{DataProvider, Provenance},
+};
 use garmin_utils::{garmin_util::extract_zip_from_garmin_connect_multiple, pgpool::PgPool};
 
 #[derive(Parser, Debug, Clone)]
@@ -52,7 +55,16 @@ async fn main() -> Result<(), Error> {
             for file in files {
                 import_garmin_json_file(&config, &file)?;
                 if import_garmin_json_file(&config, &file).is_err() {
-                    dates.extend(import_garmin_heartrate_file(&config, &file)?.into_iter());
+                    let new_dates = import_garmin_heartrate_file(&config, &file)?;
+                    let raw_file = file.to_string_lossy();
+                    Provenance::new(
+                        DataProvider::GarminConnect,
+                        "import-garmin-connect-data heartrate",
+                        Some(&raw_file),
+                    )
+                    .record_heartrate_dates(&pool, &new_dates)
+                    .await?;
+                    dates.extend(new_dates);
                 }
             }
         }
@@ -60,8 +72,16 @@ async fn main() -> Result<(), Error> {
             let tempdir = TempDir::with_prefix("garmin_connect")?;
             let ziptmpdir = tempdir.path();
             let files = extract_zip_from_garmin_connect_multiple(&file, ziptmpdir)?;
+            let raw_file = file.to_string_lossy();
+            let provenance = Provenance::new(
+                DataProvider::GarminConnect,
+                "import-garmin-connect-data heartrates",
+                Some(&raw_file),
+            );
             for file in files {
-                dates.extend(import_garmin_heartrate_file(&config, &file)?);
+                let new_dates = import_garmin_heartrate_file(&config, &file)?;
+                provenance.record_heartrate_dates(&pool, &new_dates).await?;
+                dates.extend(new_dates);
                 println!("processed {}", file.to_string_lossy());
             }
         }
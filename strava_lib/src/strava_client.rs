@@ -11,22 +11,22 @@ use reqwest::{
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use stack_string::{format_sstr, StackString};
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 use tempfile::Builder;
 use time::{macros::format_description, OffsetDateTime};
 use time_tz::{OffsetDateTimeExt, Tz};
-use tokio::{
-    fs::File,
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    task::spawn_blocking,
-    time::sleep,
-};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, task::spawn_blocking, time::sleep};
+use tracing::instrument;
 
 use garmin_lib::{
     date_time_wrapper::{iso8601::convert_datetime_to_str, DateTimeWrapper},
     garmin_config::GarminConfig,
 };
-use garmin_models::strava_activity::StravaActivity;
+use garmin_models::{
+    provenance::{DataProvider, Provenance},
+    strava_activity::{StravaActivity, StravaSyncChange},
+    sync_status::{SyncStatus, STRAVA_JOB},
+};
 use garmin_utils::{
     garmin_util::{get_random_string, gzip_file},
     pgpool::PgPool,
@@ -34,7 +34,16 @@ use garmin_utils::{
     sport_types::SportTypes,
 };
 
-static CSRF_TOKEN: Lazy<AtomicCell<Option<StackString>>> = Lazy::new(|| AtomicCell::new(None));
+/// Pixel size requested for activity photos
+const PHOTO_SIZE: usize = 600;
+
+/// Account stored in the `[API]` section of the token file
+pub const DEFAULT_ACCOUNT: &str = "default";
+const DEFAULT_SECTION: &str = "API";
+
+/// (csrf state, account being authorized)
+static CSRF_TOKEN: Lazy<AtomicCell<Option<(StackString, StackString)>>> =
+    Lazy::new(|| AtomicCell::new(None));
 
 #[derive(Debug, Copy, Clone)]
 pub enum StravaAuthType {
@@ -48,13 +57,242 @@ impl Default for StravaAuthType {
     }
 }
 
+/// Credentials of one linked strava account, a `[name]` section of the token
+/// file, the `[API]` section is the default account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StravaAccount {
+    pub name: StackString,
+    pub client_id: StackString,
+    pub client_secret: StackString,
+    pub access_token: Option<StackString>,
+    pub refresh_token: Option<StackString>,
+    /// Unix time at which the access token expires
+    pub expires_at: Option<i64>,
+    /// Whether activities of this account are pulled in by the sync
+    pub sync_enabled: bool,
+}
+
+impl StravaAccount {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            client_id: StackString::new(),
+            client_secret: StackString::new(),
+            access_token: None,
+            refresh_token: None,
+            expires_at: None,
+            sync_enabled: true,
+        }
+    }
+
+    #[must_use]
+    pub fn expires_at(&self) -> Option<OffsetDateTime> {
+        self.expires_at
+            .and_then(|t| OffsetDateTime::from_unix_timestamp(t).ok())
+    }
+
+    /// # Errors
+    /// Return error if the name can't be used as a token file section
+    pub fn validate_name(name: &str) -> Result<(), Error> {
+        if name.is_empty()
+            || name == DEFAULT_SECTION
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format_err!("{name} is not a valid strava account name"));
+        }
+        Ok(())
+    }
+
+    /// Accounts in the token file, accounts without their own client id and
+    /// secret use those of the default account
+    #[must_use]
+    pub fn parse_tokenfile(contents: &str) -> Vec<Self> {
+        let mut accounts: Vec<Self> = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let name = if section == DEFAULT_SECTION {
+                    DEFAULT_ACCOUNT
+                } else {
+                    section
+                };
+                accounts.push(Self::new(name));
+                continue;
+            }
+            let items: SmallVec<[&str; 2]> = line.splitn(2, '=').collect();
+            if let (Some(key), Some(val)) = (items.first(), items.get(1)) {
+                if accounts.is_empty() {
+                    accounts.push(Self::new(DEFAULT_ACCOUNT));
+                }
+                if let Some(account) = accounts.last_mut() {
+                    let val = val.trim();
+                    match key.trim() {
+                        "client_id" => account.client_id = val.into(),
+                        "client_secret" => account.client_secret = val.into(),
+                        "access_token" => account.access_token = Some(val.into()),
+                        "refresh_token" => account.refresh_token = Some(val.into()),
+                        "expires_at" => account.expires_at = val.parse().ok(),
+                        "sync_enabled" => account.sync_enabled = val != "false",
+                        _ => {}
+                    }
+                }
+            }
+        }
+        let default = accounts
+            .iter()
+            .find(|a| a.name == DEFAULT_ACCOUNT)
+            .map(|a| (a.client_id.clone(), a.client_secret.clone()));
+        if let Some((client_id, client_secret)) = default {
+            for account in &mut accounts {
+                if account.client_id.is_empty() {
+                    account.client_id.clone_from(&client_id);
+                }
+                if account.client_secret.is_empty() {
+                    account.client_secret.clone_from(&client_secret);
+                }
+            }
+        }
+        accounts
+    }
+
+    #[must_use]
+    pub fn to_tokenfile(accounts: &[Self]) -> StackString {
+        let mut output = StackString::new();
+        for account in accounts {
+            let section = if account.name == DEFAULT_ACCOUNT {
+                DEFAULT_SECTION
+            } else {
+                account.name.as_str()
+            };
+            output.push_str(&format_sstr!("[{section}]\n"));
+            output.push_str(&format_sstr!("client_id = {}\n", account.client_id));
+            output.push_str(&format_sstr!("client_secret = {}\n", account.client_secret));
+            if let Some(token) = &account.access_token {
+                output.push_str(&format_sstr!("access_token = {token}\n"));
+            }
+            if let Some(token) = &account.refresh_token {
+                output.push_str(&format_sstr!("refresh_token = {token}\n"));
+            }
+            if let Some(expires_at) = account.expires_at {
+                output.push_str(&format_sstr!("expires_at = {expires_at}\n"));
+            }
+            if !account.sync_enabled {
+                output.push_str("sync_enabled = false\n");
+            }
+        }
+        output
+    }
+
+    /// # Errors
+    /// Return error if reading the token file fails
+    pub async fn read_tokenfile(config: &GarminConfig) -> Result<Vec<Self>, Error> {
+        let filename = &config.strava_tokenfile;
+        if !filename.exists() {
+            return Err(format_err!("file {filename:?} does not exist"));
+        }
+        let contents = tokio::fs::read_to_string(filename).await?;
+        Ok(Self::parse_tokenfile(&contents))
+    }
+
+    /// The accounts are written to a temporary file next to the token file
+    /// which then replaces it, so a failed write never leaves a truncated
+    /// token file behind
+    /// # Errors
+    /// Return error if writing the token file fails
+    pub async fn write_tokenfile(config: &GarminConfig, accounts: &[Self]) -> Result<(), Error> {
+        let filename = &config.strava_tokenfile;
+        let tmp_path = filename.with_file_name(format_sstr!(
+            ".{}.{}",
+            filename
+                .file_name()
+                .ok_or_else(|| format_err!("Invalid token file {filename:?}"))?
+                .to_string_lossy(),
+            get_random_string(),
+        ));
+        let result: Result<(), Error> = async {
+            let mut f = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&tmp_path)
+                .await?;
+            f.write_all(Self::to_tokenfile(accounts).as_bytes()).await?;
+            f.sync_all().await?;
+            tokio::fs::rename(&tmp_path, filename).await?;
+            Ok(())
+        }
+        .await;
+        if result.is_err() {
+            tokio::fs::remove_file(&tmp_path).await.ok();
+        }
+        result
+    }
+
+    /// # Errors
+    /// Return error if the account doesn't exist or the token file can't be
+    /// updated
+    pub async fn set_sync_enabled(
+        config: &GarminConfig,
+        name: &str,
+        sync_enabled: bool,
+    ) -> Result<(), Error> {
+        let mut accounts = Self::read_tokenfile(config).await?;
+        let account = accounts
+            .iter_mut()
+            .find(|a| a.name == name)
+            .ok_or_else(|| format_err!("No strava account {name}"))?;
+        account.sync_enabled = sync_enabled;
+        Self::write_tokenfile(config, &accounts).await
+    }
+
+    /// Forget the tokens of an account, linked accounts are removed while
+    /// the default account keeps its client id and secret so it can be
+    /// authorized again
+    /// # Errors
+    /// Return error if the account doesn't exist or the token file can't be
+    /// updated
+    pub async fn disconnect(config: &GarminConfig, name: &str) -> Result<(), Error> {
+        let mut accounts = Self::read_tokenfile(config).await?;
+        let idx = accounts
+            .iter()
+            .position(|a| a.name == name)
+            .ok_or_else(|| format_err!("No strava account {name}"))?;
+        if name == DEFAULT_ACCOUNT {
+            let account = &mut accounts[idx];
+            account.access_token = None;
+            account.refresh_token = None;
+            account.expires_at = None;
+        } else {
+            accounts.remove(idx);
+        }
+        Self::write_tokenfile(config, &accounts).await
+    }
+}
+
+/// Activities pulled from strava and the changes made to the db for them,
+/// or only planned in a dry run
+#[derive(Default, Debug)]
+pub struct StravaSyncResult {
+    pub activities: Vec<StravaActivity>,
+    pub changes: Vec<StravaSyncChange>,
+    /// `account: error` for each account which couldn't be synced
+    pub errors: Vec<StackString>,
+}
+
 #[derive(Default, Debug)]
 pub struct StravaClient {
     pub config: GarminConfig,
+    /// Name of the linked account, `DEFAULT_ACCOUNT` unless set otherwise
+    pub account: StackString,
     pub client_id: StackString,
     pub client_secret: StackString,
     pub access_token: Option<StackString>,
     pub refresh_token: Option<StackString>,
+    pub expires_at: Option<i64>,
+    pub sync_enabled: bool,
     pub client: Client,
 }
 
@@ -67,68 +305,98 @@ impl StravaClient {
     /// # Errors
     /// Return error if client init fails or `refresh_access_token` fails
     pub async fn with_auth(config: GarminConfig) -> Result<Self, Error> {
-        let mut client = Self::from_file(config).await?;
-        if client.get_strava_athlete().await.is_err() {
-            client.refresh_access_token().await?;
-            client.to_file().await?;
-        }
+        Self::with_auth_account(config, DEFAULT_ACCOUNT).await
+    }
+
+    /// # Errors
+    /// Return error if client init fails or `refresh_access_token` fails
+    pub async fn with_auth_account(config: GarminConfig, account: &str) -> Result<Self, Error> {
+        let mut client = Self::from_file_account(config, account).await?;
+        client.check_auth().await?;
         Ok(client)
     }
 
+    async fn check_auth(&mut self) -> Result<(), Error> {
+        if self.get_strava_athlete().await.is_err() {
+            self.refresh_access_token().await?;
+            self.to_file().await?;
+        }
+        Ok(())
+    }
+
+    fn from_account(config: GarminConfig, account: StravaAccount) -> Result<Self, Error> {
+        Ok(Self {
+            config,
+            account: account.name,
+            client_id: account.client_id,
+            client_secret: account.client_secret,
+            access_token: account.access_token,
+            refresh_token: account.refresh_token,
+            expires_at: account.expires_at,
+            sync_enabled: account.sync_enabled,
+            client: Client::builder().cookie_store(true).build()?,
+        })
+    }
+
+    /// Client for the default account
     /// # Errors
     /// Return error if loading info from file fails
     pub async fn from_file(config: GarminConfig) -> Result<Self, Error> {
-        let mut client = Self {
-            config,
-            client: Client::builder().cookie_store(true).build()?,
-            ..Self::default()
-        };
-        let filename = &client.config.strava_tokenfile;
-        if !filename.exists() {
-            return Err(format_err!("file {filename:?} does not exist"));
-        }
-        let f = File::open(filename).await?;
-        let mut b = BufReader::new(f);
-        let mut line = String::new();
-        loop {
-            line.clear();
-            if b.read_line(&mut line).await? == 0 {
-                break;
-            }
-            let items: SmallVec<[&str; 2]> = line.split('=').take(2).collect();
-            if let Some(key) = items.first() {
-                if let Some(val) = items.get(1) {
-                    match key.trim() {
-                        "client_id" => client.client_id = val.trim().into(),
-                        "client_secret" => client.client_secret = val.trim().into(),
-                        "access_token" => client.access_token = Some(val.trim().into()),
-                        "refresh_token" => client.refresh_token = Some(val.trim().into()),
-                        _ => {}
-                    }
-                }
-            }
+        Self::from_file_account(config, DEFAULT_ACCOUNT).await
+    }
+
+    /// # Errors
+    /// Return error if loading info from file fails or there is no such
+    /// account
+    pub async fn from_file_account(config: GarminConfig, account: &str) -> Result<Self, Error> {
+        let account = StravaAccount::read_tokenfile(&config)
+            .await?
+            .into_iter()
+            .find(|a| a.name == account)
+            .ok_or_else(|| format_err!("No strava account {account}"))?;
+        Self::from_account(config, account)
+    }
+
+    /// Clients for every linked account
+    /// # Errors
+    /// Return error if loading info from file fails
+    pub async fn all_from_file(config: GarminConfig) -> Result<Vec<Self>, Error> {
+        StravaAccount::read_tokenfile(&config)
+            .await?
+            .into_iter()
+            .map(|account| Self::from_account(config.clone(), account))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn to_account(&self) -> StravaAccount {
+        StravaAccount {
+            name: self.account.clone(),
+            client_id: self.client_id.clone(),
+            client_secret: self.client_secret.clone(),
+            access_token: self.access_token.clone(),
+            refresh_token: self.refresh_token.clone(),
+            expires_at: self.expires_at,
+            sync_enabled: self.sync_enabled,
         }
-        Ok(client)
     }
 
+    /// Write this account's credentials to the token file, other accounts
+    /// are kept as they are
     /// # Errors
     /// Return error if writing config to file fails
     pub async fn to_file(&self) -> Result<(), Error> {
-        let mut f = File::create(&self.config.strava_tokenfile).await?;
-        f.write_all(b"[API]\n").await?;
-        let s = format_sstr!("client_id = {}\n", self.client_id);
-        f.write_all(s.as_bytes()).await?;
-        let s = format_sstr!("client_secret = {}\n", self.client_secret);
-        f.write_all(s.as_bytes()).await?;
-        if let Some(token) = self.access_token.as_ref() {
-            let s = format_sstr!("access_token = {token}\n");
-            f.write_all(s.as_bytes()).await?;
-        }
-        if let Some(token) = self.refresh_token.as_ref() {
-            let s = format_sstr!("refresh_token = {token}\n");
-            f.write_all(s.as_bytes()).await?;
+        let mut accounts = if self.config.strava_tokenfile.exists() {
+            StravaAccount::read_tokenfile(&self.config).await?
+        } else {
+            Vec::new()
+        };
+        let account = self.to_account();
+        match accounts.iter_mut().find(|a| a.name == account.name) {
+            Some(existing) => *existing = account,
+            None => accounts.push(account),
         }
-        Ok(())
+        StravaAccount::write_tokenfile(&self.config, &accounts).await
     }
 
     /// # Errors
@@ -153,10 +421,13 @@ impl StravaClient {
                 ("state", state.as_str()),
             ],
         )?;
-        CSRF_TOKEN.store(Some(state));
+        CSRF_TOKEN.store(Some((state, self.account.clone())));
         Ok(url)
     }
 
+    /// Exchange the authorization code for tokens of the account the
+    /// authorization url was generated for, a newly linked account is synced
+    /// by default
     /// # Errors
     /// Return error if api calls fail
     pub async fn process_callback(&mut self, code: &str, state: &str) -> Result<(), Error> {
@@ -164,9 +435,10 @@ impl StravaClient {
         struct TokenResponse {
             access_token: StackString,
             refresh_token: StackString,
+            expires_at: Option<i64>,
         }
 
-        if let Some(current_state) = CSRF_TOKEN.swap(None) {
+        if let Some((current_state, account)) = CSRF_TOKEN.swap(None) {
             if state != current_state.as_str() {
                 return Err(format_err!("Incorrect state"));
             }
@@ -191,8 +463,13 @@ impl StravaClient {
                 .error_for_status()?
                 .json()
                 .await?;
+            if account != self.account {
+                self.account = account;
+                self.sync_enabled = true;
+            }
             self.access_token.replace(resp.access_token);
             self.refresh_token.replace(resp.refresh_token);
+            self.expires_at = resp.expires_at;
             Ok(())
         } else {
             Err(format_err!("No state"))
@@ -201,11 +478,13 @@ impl StravaClient {
 
     /// # Errors
     /// Return error if api calls fail
+    #[instrument(skip_all)]
     pub async fn refresh_access_token(&mut self) -> Result<(), Error> {
         #[derive(Deserialize)]
         struct TokenResponse {
             access_token: StackString,
             refresh_token: StackString,
+            expires_at: Option<i64>,
         }
 
         if let Some(refresh_token) = self.refresh_token.as_ref() {
@@ -232,6 +511,7 @@ impl StravaClient {
                 .await?;
             self.access_token.replace(resp.access_token);
             self.refresh_token.replace(resp.refresh_token);
+            self.expires_at = resp.expires_at;
             Ok(())
         } else {
             Err(format_err!("No refresh token"))
@@ -274,6 +554,7 @@ impl StravaClient {
 
     /// # Errors
     /// Return error if api calls fail
+    #[instrument(skip(self))]
     pub async fn get_strava_activities(
         &self,
         start_date: Option<OffsetDateTime>,
@@ -311,6 +592,42 @@ impl StravaClient {
             .map_err(Into::into)
     }
 
+    /// Urls of the photos attached to an activity, at `PHOTO_SIZE` pixels
+    /// # Errors
+    /// Return error if api calls fail
+    pub async fn get_strava_activity_photos(
+        &self,
+        activity_id: i64,
+    ) -> Result<Vec<StackString>, Error> {
+        #[derive(Deserialize)]
+        struct StravaPhoto {
+            urls: Option<HashMap<StackString, StackString>>,
+        }
+
+        let headers = self.get_auth_headers()?;
+        let url = self
+            .config
+            .strava_endpoint
+            .as_ref()
+            .ok_or_else(|| format_err!("Bad URL"))?
+            .join(&format_sstr!("api/v3/activities/{activity_id}/photos"))?;
+        let size = StackString::from_display(PHOTO_SIZE);
+        let url = Url::parse_with_params(url.as_str(), &[("size", size.as_str())])?;
+        let photos: Vec<StravaPhoto> = self
+            .client
+            .get(url)
+            .headers(headers)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(photos
+            .into_iter()
+            .filter_map(|p| p.urls.and_then(|urls| urls.into_values().next()))
+            .collect())
+    }
+
     /// # Errors
     /// Return error if api calls fail
     pub async fn get_all_strava_activites(
@@ -335,6 +652,7 @@ impl StravaClient {
 
     /// # Errors
     /// Return error if api calls fail
+    #[instrument(skip_all, fields(name = %activity.name))]
     pub async fn create_strava_activity(&self, activity: &StravaActivity) -> Result<i64, Error> {
         #[derive(Serialize, Deserialize)]
         struct CreateActivityForm {
@@ -402,6 +720,7 @@ impl StravaClient {
     /// # Errors
     /// Return error if api calls fail
     #[allow(clippy::similar_names)]
+    #[instrument(skip(self, description))]
     pub async fn upload_strava_activity(
         &self,
         filepath: &Path,
@@ -500,6 +819,7 @@ impl StravaClient {
 
     /// # Errors
     /// Return error if api calls fail
+    #[instrument(skip(self, description))]
     pub async fn update_strava_activity(
         &self,
         activity_id: u64,
@@ -513,6 +833,7 @@ impl StravaClient {
             id: u64,
             commute: bool,
             trainer: bool,
+            #[serde(skip_serializing_if = "Option::is_none")]
             description: Option<StackString>,
             name: StackString,
             #[serde(alias = "type")]
@@ -560,23 +881,98 @@ impl StravaClient {
         Ok(url)
     }
 
+    /// Activities started between `start_datetime` and `end_datetime`, with
+    /// their photos and the account filled in, activities whose photos can't
+    /// be fetched are kept without them
     /// # Errors
     /// Return error if api calls fail
-    pub async fn sync_with_client(
+    pub async fn fetch_activities(
         &self,
         start_datetime: Option<OffsetDateTime>,
         end_datetime: Option<OffsetDateTime>,
-        pool: &PgPool,
     ) -> Result<Vec<StravaActivity>, Error> {
-        let new_activities: Vec<_> = self
+        let mut new_activities: Vec<_> = self
             .get_all_strava_activites(start_datetime, end_datetime)
             .await?;
-
-        StravaActivity::upsert_activities(&new_activities, pool).await?;
-        StravaActivity::fix_summary_id_in_db(pool).await?;
-
+        for activity in &mut new_activities {
+            activity.strava_account = Some(self.account.clone());
+            if activity.total_photo_count > 0 {
+                // photos are a nice to have, don't lose the rest of the sync
+                match self.get_strava_activity_photos(activity.id).await {
+                    Ok(photo_urls) => activity.photo_urls = photo_urls,
+                    Err(e) => warn!("Failed to fetch photos of {} {e}", activity.id),
+                }
+            }
+        }
         Ok(new_activities)
     }
+
+    /// Sync every account with sync enabled, an account which fails is
+    /// reported in `errors` and the others are still synced. The outcome is
+    /// recorded as the `STRAVA_JOB` sync status, with `dry_run` the changes
+    /// are only planned and nothing is written
+    /// # Errors
+    /// Return error if the token file can't be read or the sync status can't
+    /// be recorded
+    pub async fn sync_all_accounts(
+        config: GarminConfig,
+        start_datetime: Option<OffsetDateTime>,
+        end_datetime: Option<OffsetDateTime>,
+        pool: &PgPool,
+        dry_run: bool,
+    ) -> Result<StravaSyncResult, Error> {
+        let mut output = StravaSyncResult::default();
+        if !config.strava_configured() {
+            return Ok(output);
+        }
+        let clients = match Self::all_from_file(config).await {
+            Ok(clients) => clients,
+            Err(e) => {
+                if !dry_run {
+                    SyncStatus::record_failure(pool, STRAVA_JOB, &format_sstr!("{e}")).await?;
+                }
+                return Err(e);
+            }
+        };
+        for mut client in clients {
+            if !client.sync_enabled {
+                continue;
+            }
+            let result: Result<_, Error> = async {
+                client.check_auth().await?;
+                let activities = client
+                    .fetch_activities(start_datetime, end_datetime)
+                    .await?;
+                let changes = StravaActivity::plan_upsert(&activities, pool).await?;
+                if !dry_run {
+                    let provenance = Provenance::new(DataProvider::Strava, "strava sync", None);
+                    StravaActivity::upsert_activities(&activities, pool, &provenance).await?;
+                    StravaActivity::fix_summary_id_in_db(pool).await?;
+                }
+                Ok((activities, changes))
+            }
+            .await;
+            match result {
+                Ok((activities, changes)) => {
+                    output.activities.extend(activities);
+                    output.changes.extend(changes);
+                }
+                Err(e) => {
+                    warn!("Failed to sync strava account {} {e}", client.account);
+                    output.errors.push(format_sstr!("{}: {e}", client.account));
+                }
+            }
+        }
+        if dry_run {
+            return Ok(output);
+        }
+        if output.errors.is_empty() {
+            SyncStatus::record_success(pool, STRAVA_JOB).await?;
+        } else {
+            SyncStatus::record_failure(pool, STRAVA_JOB, &output.errors.join("; ")).await?;
+        }
+        Ok(output)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -628,12 +1024,48 @@ mod tests {
     use futures::{future::try_join_all, TryStreamExt};
     use log::debug;
     use std::collections::HashMap;
-    use time::{Duration, OffsetDateTime};
+    use time::{macros::datetime, Duration, OffsetDateTime};
 
     use garmin_lib::garmin_config::GarminConfig;
     use garmin_utils::{pgpool::PgPool, sport_types::SportTypes};
 
-    use crate::strava_client::{StravaActivity, StravaClient};
+    use crate::strava_client::{StravaAccount, StravaActivity, StravaClient, DEFAULT_ACCOUNT};
+
+    #[test]
+    fn test_parse_tokenfile() {
+        let contents = "
+            [API]
+            client_id = 1234
+            client_secret = abc=
+            access_token = tok
+            refresh_token = ref
+            expires_at = 1700000000
+            [cycling_team]
+            access_token = tok2
+            sync_enabled = false
+        ";
+        let accounts = StravaAccount::parse_tokenfile(contents);
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].name.as_str(), DEFAULT_ACCOUNT);
+        assert_eq!(accounts[0].client_secret.as_str(), "abc=");
+        assert!(accounts[0].sync_enabled);
+        assert_eq!(
+            accounts[0].expires_at(),
+            Some(datetime!(2023-11-14 22:13:20 UTC))
+        );
+        assert_eq!(accounts[1].name.as_str(), "cycling_team");
+        assert_eq!(accounts[1].client_id.as_str(), "1234");
+        assert_eq!(accounts[1].refresh_token, None);
+        assert!(!accounts[1].sync_enabled);
+
+        let output = StravaAccount::to_tokenfile(&accounts);
+        assert!(output.starts_with("[API]\n"));
+        assert_eq!(StravaAccount::parse_tokenfile(&output), accounts);
+
+        assert!(StravaAccount::validate_name("cycling_team").is_ok());
+        assert!(StravaAccount::validate_name("API").is_err());
+        assert!(StravaAccount::validate_name("a b").is_err());
+    }
 
     #[tokio::test]
     #[ignore]
@@ -719,10 +1151,12 @@ mod tests {
             .collect();
         new_activities.shrink_to_fit();
         debug!("{:?}", new_activities);
+        let provenance = Provenance::new(DataProvider::Strava, "strava test", None);
         let futures = new_activities.iter().map(|activity| {
             let pool = pool.clone();
+            let provenance = &provenance;
             async move {
-                activity.insert_into_db(&pool).await?;
+                activity.insert_into_db(&pool, provenance).await?;
                 Ok(())
             }
         });
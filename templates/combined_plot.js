@@ -0,0 +1,154 @@
+function combined_plot(series, title, xlabel, color, markers, description=null) {
+    // Set the dimensions of the canvas / graph
+    let margin = {top: 30, right: 60, bottom: 30, left: 60};
+    let width = 900 - margin.left - margin.right;
+    let height = 400 - margin.top - margin.bottom;
+
+    let all_data = [].concat.apply([], series.map(function(s) {return s.data;}));
+    let x = d3.scaleLinear()
+        .range([0, width])
+        .domain(d3.extent(all_data, function(d) {return d[0];}));
+
+    series.forEach(function(s) {
+        let ymin = d3.min(s.data, function(d) {return d[1]});
+        let ymax = d3.max(s.data, function(d) {return d[1]});
+        let pad = 0.1 * Math.abs(ymax - ymin) + 0.1;
+        s.y = d3.scaleLinear().range([height, 0]).domain([ymin - pad, ymax + pad]);
+        s.color = s.color || color || "steelblue";
+        s.bisect = d3.bisector(function(d) {return d[0];}).left;
+    });
+
+    let svg = d3.select("body")
+        .append("svg")
+            .attr("role", "img")
+            .attr("aria-label", description || title)
+            .attr("width", width + margin.left + margin.right)
+            .attr("height", height + margin.top + margin.bottom)
+        .append("g")
+            .attr("transform",
+                "translate(" + margin.left + "," + margin.top + ")");
+
+    svg.append("text")      // text label for chart Title
+            .attr("x", width / 2 )
+            .attr("y", 0 - (margin.top/2))
+            .style("text-anchor", "middle")
+            .style("font-size", "16px")
+            .style("text-decoration", "underline")
+            .text(title);
+
+    svg.append("text")      // text label for the x-axis
+            .attr("x", width / 2 )
+            .attr("y",  height + margin.bottom)
+            .style("text-anchor", "middle")
+            .text(xlabel);
+
+    // Background series are drawn first so that the lines stay on top
+    series.filter(function(s) {return s.axis == "background";}).forEach(function(s) {
+        let area = d3.area()
+            .x(function(d) { return x(d[0]); })
+            .y0(height)
+            .y1(function(d) { return s.y(d[1]); });
+        svg.append("path")
+            .attr("d", area(s.data))
+            .style("stroke", "none")
+            .style("fill", s.color)
+            .style("fill-opacity", 0.4);
+    });
+
+    series.filter(function(s) {return s.axis != "background";}).forEach(function(s) {
+        let valueline = d3.line()
+            .x(function(d) { return x(d[0]); })
+            .y(function(d) { return s.y(d[1]); });
+        svg.append("path")
+            .attr("class", "line")
+            .style("stroke", s.color)
+            .attr("d", valueline(s.data));
+    });
+
+    svg.append("g")
+        .attr("class", "xaxis")
+        .attr("transform", "translate(0," + height + ")")
+        .call(d3.axisBottom(x).ticks(10));
+
+    let left = series.find(function(s) {return s.axis == "left";});
+    if (left) {
+        svg.append("g")
+            .attr("class", "yaxis")
+            .call(d3.axisLeft(left.y).ticks(5));
+        svg.append("text")
+            .attr("y", 15 - margin.left)
+            .attr("x", 0 - (height / 2))
+            .attr("transform", "rotate(-90)")
+            .style("text-anchor", "middle")
+            .style("fill", left.color)
+            .text(left.name + " [" + left.label + "]");
+    }
+    let right = series.find(function(s) {return s.axis == "right";});
+    if (right) {
+        svg.append("g")
+            .attr("class", "yaxis")
+            .attr("transform", "translate(" + width + ",0)")
+            .call(d3.axisRight(right.y).ticks(5));
+        svg.append("text")
+            .attr("y", width + margin.right - 15)
+            .attr("x", 0 - (height / 2))
+            .attr("transform", "rotate(-90)")
+            .style("text-anchor", "middle")
+            .style("fill", right.color)
+            .text(right.name + " [" + right.label + "]");
+    }
+
+    // Vertical markers with a label, e.g. lap boundaries
+    (markers || []).forEach(function(m) {
+        svg.append("line")
+            .attr("x1", x(m[0]))
+            .attr("x2", x(m[0]))
+            .attr("y1", 0)
+            .attr("y2", height)
+            .attr("stroke", "gray")
+            .style("stroke-dasharray", ("3, 3"));
+        svg.append("text")
+            .attr("x", x(m[0]) + 2)
+            .attr("y", 10)
+            .style("font-size", "10px")
+            .text(m[1]);
+    });
+
+    let rule = svg.append("line")
+        .attr("y1", 0)
+        .attr("y2", height)
+        .attr("stroke", "black");
+
+    let tooltip = svg.append("g");
+
+    svg.append("rect")
+        .attr("width", width)
+        .attr("height", height)
+        .style("fill", "none")
+        .style("pointer-events", "all")
+        .on("mousemove touchmove", handleMouseOverData);
+
+    function handleMouseOverData() {
+        let m = d3.mouse(this);
+        let xval = x.invert(m[0]);
+        rule.attr("transform", `translate(${m[0]}, 0)`);
+        d3.event.preventDefault();
+
+        tooltip.selectAll("text").remove();
+        tooltip.append("text")
+            .attr("x", 10)
+            .attr("y", 15)
+            .text(xval.toFixed(2) + " " + xlabel);
+        series.forEach(function(s, i) {
+            let idx = Math.min(s.bisect(s.data, xval), s.data.length - 1);
+            if (idx < 0) {
+                return;
+            }
+            tooltip.append("text")
+                .attr("x", 10)
+                .attr("y", 30 + 15 * i)
+                .style("fill", s.color)
+                .text(s.name + ": " + s.data[idx][1].toFixed(1) + " " + s.label);
+        });
+    }
+}
@@ -6,6 +6,26 @@ function processFormData() {
     let garmin_filter = document.getElementById( 'garmin_filter' );
     send_command( 'filter=' + garmin_filter.value );
 }
+function pinHistory(filter, pinned) {
+    let url = '/garmin/history/pin';
+    let data = JSON.stringify({"filter": filter, "pinned": pinned});
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        location.reload();
+    }
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
+}
+function deleteHistory(filter) {
+    let url = '/garmin/history?filter=' + encodeURIComponent(filter);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        location.reload();
+    }
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.send(null);
+}
 function processStravaData(filename, activity_type) {
     let strava_title = document.getElementById( 'strava_upload' );
     let url = '/garmin/strava/upload';
@@ -111,6 +131,332 @@ function stravaAthlete() {
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "syncing";
 }
+const UPLOAD_CHUNK_SIZE = 1024 * 1024;
+function uploadRequest(method, url, body, headers) {
+    return new Promise((resolve, reject) => {
+        let xmlhttp = new XMLHttpRequest();
+        xmlhttp.open(method, url, true);
+        for (const [key, value] of Object.entries(headers || {})) {
+            xmlhttp.setRequestHeader(key, value);
+        }
+        xmlhttp.onload = () => {
+            if (xmlhttp.status >= 200 && xmlhttp.status < 300) {
+                resolve(xmlhttp.responseText);
+            } else {
+                reject(new Error(xmlhttp.status + " " + xmlhttp.responseText));
+            }
+        };
+        xmlhttp.onerror = () => reject(new Error("network error"));
+        xmlhttp.send(body);
+    });
+}
+async function uploadSession(file) {
+    let key = "garmin_upload:" + file.name + ":" + file.size + ":" + file.lastModified;
+    let session_id = localStorage.getItem(key);
+    if (session_id) {
+        try {
+            let status = JSON.parse(await uploadRequest(
+                "GET", "/garmin/upload_session?session_id=" + session_id, null));
+            return [key, status];
+        } catch (e) {
+            localStorage.removeItem(key);
+        }
+    }
+    let digest = await crypto.subtle.digest("SHA-256", await file.arrayBuffer());
+    let sha256 = Array.from(new Uint8Array(digest))
+        .map(b => b.toString(16).padStart(2, "0")).join("");
+    let data = JSON.stringify({"filename": file.name, "size": file.size, "sha256": sha256});
+    let status = JSON.parse(await uploadRequest(
+        "POST", "/garmin/upload_session", data, {"Content-Type": "application/json"}));
+    localStorage.setItem(key, status.session_id);
+    return [key, status];
+}
+async function resumableUpload(file) {
+    const sleep = ms => new Promise(r => setTimeout(r, ms));
+    let output = document.getElementById("garminconnectoutput");
+    let [key, status] = await uploadSession(file);
+    let offset = status.offset;
+    let failures = 0;
+    while (offset < file.size) {
+        let end = Math.min(offset + UPLOAD_CHUNK_SIZE, file.size);
+        let url = "/garmin/upload_session/chunk?session_id=" + status.session_id;
+        let range = "bytes " + offset + "-" + (end - 1) + "/" + file.size;
+        try {
+            let chunk = await file.slice(offset, end).arrayBuffer();
+            status = JSON.parse(await uploadRequest("PUT", url, chunk, {"Content-Range": range}));
+            offset = status.offset;
+            failures = 0;
+            output.innerHTML = "uploading " + Math.floor(100 * offset / file.size) + "%";
+        } catch (e) {
+            failures += 1;
+            if (failures > 5) {
+                output.innerHTML = "upload interrupted, submit again to resume";
+                return;
+            }
+            await sleep(1000 * failures);
+            status = JSON.parse(await uploadRequest(
+                "GET", "/garmin/upload_session?session_id=" + status.session_id, null));
+            offset = status.offset;
+        }
+    }
+    output.innerHTML = "processing";
+    let body = await uploadRequest(
+        "POST", "/garmin/upload_session/complete?session_id=" + status.session_id, null);
+    localStorage.removeItem(key);
+    document.open();
+    document.write(body);
+    document.close();
+}
+function uploadFileSubmit(form) {
+    let file = form.elements["filename"].files[0];
+    if (!file || !window.crypto || !crypto.subtle) {
+        return true;
+    }
+    resumableUpload(file).catch(e => {
+        document.getElementById("garminconnectoutput").innerHTML = "upload failed";
+        document.getElementById("garmin_text_box").innerHTML = e.message;
+    });
+    return false;
+}
+function quarantinedFiles() {
+    let url = "/garmin/quarantine";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function adminStats() {
+    let url = "/garmin/admin/stats";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function syncLockRelease() {
+    if (!confirm("Terminate the connection holding the sync lock?")) {
+        return;
+    }
+    let url = "/garmin/admin/sync_lock";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "unlocking";
+}
+function coverageGaps() {
+    let url = "/garmin/admin/gaps";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function milestones() {
+    let url = "/garmin/milestones";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function mostKudoed(year) {
+    let url = "/garmin/strava/most_kudoed";
+    if (year) {
+        url = url + "?year=" + year;
+    }
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function travelMap() {
+    let url = "/garmin/travel_map?limit=1000";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function drawTravelMap() {
+        let cities = JSON.parse(xmlhttp.responseText).data;
+        let text_box = document.getElementById("garmin_text_box");
+        text_box.innerHTML = "";
+        text_box.style.height = "600px";
+        let map = new google.maps.Map(text_box, {
+            center: new google.maps.LatLng(0, 0),
+            zoom: 2,
+            mapTypeId: google.maps.MapTypeId.ROADMAP
+        });
+        let bounds = new google.maps.LatLngBounds();
+        cities.forEach(function (c) {
+            let position = new google.maps.LatLng(c.latitude, c.longitude);
+            new google.maps.Marker({
+                position: position,
+                map: map,
+                title: c.city + " (" + c.activities + ")"
+            });
+            bounds.extend(position);
+        });
+        if (cities.length > 0) {
+            map.fitBounds(bounds);
+        }
+        document.getElementById("garminconnectoutput").innerHTML = cities.length + " cities";
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function regionMap(us_states) {
+    let url = "/garmin/region_map?us_states=" + us_states + "&limit=1000";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function drawRegionMap() {
+        let regions = JSON.parse(xmlhttp.responseText).data;
+        let text_box = document.getElementById("garmin_text_box");
+        let toggle = us_states ? "Countries" : "US States";
+        text_box.innerHTML = '<button type="submit" onclick="regionMap(' + !us_states + ');">'
+            + toggle + '</button><div id="region_map" style="width: 900px; height: 500px;"></div>'
+            + '<table border="1"><thead><th>' + (us_states ? "State" : "Country")
+            + '</th><th>Activities</th><th>First Visit</th><th>Last Visit</th></thead>'
+            + '<tbody id="region_table"></tbody></table>';
+        let tbody = document.getElementById("region_table");
+        regions.forEach(function (r) {
+            let row = tbody.insertRow();
+            [
+                r.name,
+                r.activities,
+                r.first_visit.substring(0, 10),
+                r.last_visit.substring(0, 10)
+            ].forEach(function (value) {
+                row.insertCell().textContent = value;
+            });
+        });
+        let draw = function () {
+            let data = new google.visualization.DataTable();
+            data.addColumn("string", us_states ? "State" : "Country");
+            data.addColumn("number", "Activities");
+            data.addColumn({type: "string", role: "tooltip"});
+            regions.forEach(function (r) {
+                data.addRow([
+                    r.name,
+                    r.activities,
+                    r.activities + " activities, first visit " + r.first_visit.substring(0, 10)
+                ]);
+            });
+            let options = {colorAxis: {colors: ["#c6dbef", "#08306b"]}};
+            if (us_states) {
+                options.region = "US";
+                options.resolution = "provinces";
+            }
+            let chart = new google.visualization.GeoChart(document.getElementById("region_map"));
+            chart.draw(data, options);
+            document.getElementById("garminconnectoutput").innerHTML = regions.length + " regions";
+        };
+        loadGeoChart(draw);
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function loadGeoChart(callback) {
+    if (typeof google !== "undefined" && google.charts) {
+        google.charts.load("current", {packages: ["geochart"]});
+        google.charts.setOnLoadCallback(callback);
+        return;
+    }
+    let script = document.createElement("script");
+    script.src = "https://www.gstatic.com/charts/loader.js";
+    script.onload = function () {
+        google.charts.load("current", {packages: ["geochart"]});
+        google.charts.setOnLoadCallback(callback);
+    };
+    document.head.appendChild(script);
+}
+function coverageGapBackfill(kind, start_date, end_date) {
+    let url = "/garmin/admin/gaps/backfill?kind=" + kind + "&start_date=" + start_date + "&end_date=" + end_date;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "backfilling";
+}
+function cleanupQuery() {
+    let params = [];
+    let start_date = document.getElementById("cleanup_start_date");
+    if (start_date && start_date.value) {
+        params.push("start_date=" + start_date.value);
+    }
+    let end_date = document.getElementById("cleanup_end_date");
+    if (end_date && end_date.value) {
+        params.push("end_date=" + end_date.value);
+    }
+    let sport = document.getElementById("cleanup_sport");
+    if (sport && sport.value) {
+        params.push("sport=" + encodeURIComponent(sport.value));
+    }
+    let zero_distance = document.getElementById("cleanup_zero_distance");
+    if (zero_distance && zero_distance.checked) {
+        params.push("zero_distance=true");
+    }
+    return params;
+}
+function cleanupDryRun() {
+    let url = "/garmin/admin/cleanup?" + cleanupQuery().join("&");
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function cleanupDelete(button) {
+    let ids = button.dataset.ids.split(",");
+    if (!confirm("Delete " + ids.length + " activities?")) {
+        return;
+    }
+    let url = "/garmin/admin/cleanup";
+    let data = JSON.stringify({"ids": ids});
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "deleting";
+}
+function quarantineRetry(filename) {
+    let url = "/garmin/quarantine/retry?filename=" + encodeURIComponent(filename);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "processing";
+}
 function strava_sync() {
     let url = '/garmin/strava_sync';
     let xmlhttp = new XMLHttpRequest();
@@ -172,6 +518,10 @@ function scale_measurement_plots(offset, start_date=null, end_date=null) {
     if(end_date) {
         url = url + "&end_date=" + end_date;
     }
+    let overlay = document.getElementById("overlay_selector");
+    if(overlay && overlay.value) {
+        url = url + "&overlay=" + encodeURIComponent(overlay.value);
+    }
     location.replace(url)
 }
 function heartrate_plot() {
@@ -196,6 +546,10 @@ function heartrate_stat_plot(offset, start_date=null, end_date=null) {
     if(end_date) {
         url = url + "&end_date=" + end_date;
     }
+    let overlay = document.getElementById("overlay_selector");
+    if(overlay && overlay.value) {
+        url = url + "&overlay=" + encodeURIComponent(overlay.value);
+    }
     location.replace(url)
 }
 function heartrateSync() {
@@ -245,6 +599,14 @@ function race_result_plot_world_record_women() {
     let url = "/garmin/race_result_plot?race_type=world_record_women"
     location.replace(url)
 }
+function power_curve_plot(metric, sport, period) {
+    let url = "/garmin/power_curve?metric=" + metric + "&sport=" + sport + "&period=" + period
+    location.replace(url)
+}
+function yearly_comparison_plot(metric, sport) {
+    let url = "/garmin/stats/yearly_comparison?metric=" + metric + "&sport=" + sport
+    location.replace(url)
+}
 function flipRaceResultFlag(id) {
     let url = '/garmin/race_result_flag?id=' + id;
     let xmlhttp = new XMLHttpRequest();
@@ -256,6 +618,99 @@ function flipRaceResultFlag(id) {
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "updating";
 }
+function trips() {
+    let url = "/garmin/trips";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function tripCreate() {
+    let url = "/garmin/trips";
+    let data = JSON.stringify({
+        "name": document.getElementById("trip_name").value,
+        "start_date": document.getElementById("trip_start_date").value,
+        "end_date": document.getElementById("trip_end_date").value,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "saving";
+}
+function tripDelete(button) {
+    if (!confirm("Delete trip " + button.dataset.name + "?")) {
+        return;
+    }
+    let url = "/garmin/trips?id=" + encodeURIComponent(button.dataset.id);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "deleting";
+}
+function raceResultNotes(id) {
+    let url = '/garmin/race_result_notes?id=' + id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function raceResultNotesSave(id) {
+    let url = '/garmin/race_result_notes';
+    let data = JSON.stringify({
+        "id": id,
+        "bib_number": document.getElementById("race_bib_number").value,
+        "race_report": document.getElementById("race_report").value,
+    });
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "saving";
+}
+function raceAttachmentUpload(form, id) {
+    let url = '/garmin/race_result_attachment?id=' + id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(new FormData(form));
+    document.getElementById("garminconnectoutput").innerHTML = "uploading";
+    return false;
+}
+function raceAttachmentDelete(attachment_id, id) {
+    let url = '/garmin/race_result_attachment?id=' + attachment_id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "deleting";
+}
 function garminConnectUserSummary() {
     let url = "/garmin/garmin_connect_user_summary";
     let xmlhttp = new XMLHttpRequest();
@@ -351,6 +806,28 @@ function scaleMeasurementManualInput() {
     xmlhttp.send(data);
     document.getElementById("garminconnectoutput").innerHTML = "processing";
 }
+function scaleDuplicates() {
+    let url = "/garmin/scale_measurements/duplicates";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("scale_measurement_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "processing";
+}
+function scaleDuplicateMerge(keep) {
+    let url = "/garmin/scale_measurements/duplicates/merge?keep=" + keep;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.onload = function() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("scale_measurement_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "processing";
+}
 function manualScaleMeasurement() {
     let url = "/garmin/scale_measurements/manual/input";
     let xmlhttp = new XMLHttpRequest();
@@ -361,4 +838,250 @@ function manualScaleMeasurement() {
     }
     xmlhttp.send(null);
     document.getElementById("garminconnectoutput").innerHTML = "processing";
+}
+function integrations() {
+    let url = "/garmin/integrations";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function stravaConnect(account) {
+    let url = "/garmin/strava/auth?account=" + encodeURIComponent(account);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        let win = window.open(xmlhttp.responseText, '_blank');
+        win.focus()
+    }
+    xmlhttp.open("GET", url, true);
+    xmlhttp.send(null);
+}
+function stravaAccountSync(account, sync_enabled) {
+    let url = "/garmin/strava/accounts/sync?account=" + encodeURIComponent(account) + "&sync_enabled=" + sync_enabled;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.onload = function nothing() {
+        integrations();
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "updating";
+}
+function stravaDisconnect(account) {
+    if (!confirm("Disconnect strava account " + account + "?")) {
+        return;
+    }
+    let url = "/garmin/strava/accounts?account=" + encodeURIComponent(account);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("DELETE", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "updating";
+}
+function linkReview() {
+    let url = "/garmin/link/unmatched";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function linkActivity(strava_id) {
+    let summary_id = document.getElementById("link_summary_" + strava_id).value;
+    if (!summary_id) {
+        return;
+    }
+    let url = "/garmin/link?summary_id=" + summary_id + "&strava_id=" + strava_id;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "linking";
+}
+function linkAuto() {
+    let url = "/garmin/link/auto";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("POST", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "linking";
+}
+function trainingPattern(sport) {
+    let url = "/garmin/training_pattern?sport=" + sport;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function addGarminCorrectionPoolLength(begin_datetime) {
+    let pool_length = document.getElementById( "pool_length" ).value.trim();
+    let recorded_pool_length = document.getElementById( "recorded_pool_length" ).value.trim();
+    let url = "/garmin/add_garmin_correction";
+    let correction = {"start_time": begin_datetime, "lap_number": 0};
+    if (pool_length === "") {
+        // an empty pool length removes the correction
+        correction["clear_pool_length"] = true;
+    } else {
+        // anything which isn't a finite number is sent as typed and rejected
+        let asNumber = function(value) {
+            let number = Number(value);
+            return (value !== "" && Number.isFinite(number)) ? number : value;
+        };
+        correction["pool_length"] = asNumber(pool_length);
+        correction["recorded_pool_length"] = asNumber(recorded_pool_length);
+    }
+    let data = JSON.stringify(correction);
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        if (xmlhttp.status == 200) {
+            document.getElementById("garminconnectoutput").innerHTML = "done";
+        } else {
+            document.getElementById("garminconnectoutput").textContent = xmlhttp.responseText;
+        }
+    }
+    xmlhttp.open( "POST", url , true );
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "updating";
+}
+function clothingReport(temperature) {
+    let url = "/garmin/clothing";
+    if (temperature) {
+        url = url + "?temperature=" + temperature;
+    }
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function clothingLog(filename) {
+    let clothing = document.getElementById( "clothing_text" ).value;
+    let comfort = document.getElementById( "comfort_select" ).value;
+    let url = "/garmin/clothing";
+    let data = JSON.stringify(
+        {
+            "filename": filename,
+            "clothing": clothing,
+            "comfort": comfort
+        }
+    );
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open( "POST", url , true );
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "updating";
+}
+function activityRpe(filename) {
+    let rpe = document.getElementById( "rpe_input" ).value;
+    let url = "/garmin/activity_rpe";
+    let data = JSON.stringify(
+        {
+            "filename": filename,
+            "rpe": rpe === "" ? null : parseInt(rpe, 10)
+        }
+    );
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open( "POST", url , true );
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "updating";
+}
+function chooseStatSource(filename, stat, source) {
+    let url = "/garmin/stat_source";
+    let data = JSON.stringify(
+        {
+            "filename": filename,
+            "stat": stat,
+            "source": source
+        }
+    );
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.open( "POST", url , true );
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "updating";
+}
+function heartRateZones() {
+    let url = "/garmin/heart_rate_zones";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function restDays() {
+    let url = "/garmin/rest_days";
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.open("GET", url, true);
+    xmlhttp.onload = function nothing() {
+        document.getElementById("garminconnectoutput").innerHTML = "done";
+        document.getElementById("garmin_text_box").innerHTML = xmlhttp.responseText;
+    }
+    xmlhttp.send(null);
+    document.getElementById("garminconnectoutput").innerHTML = "loading";
+}
+function heartRateZonesUpdate(max_heart_rate, resting_heart_rate) {
+    let url = "/garmin/heart_rate_zones";
+    let data = JSON.stringify(
+        {
+            "max_heart_rate": max_heart_rate,
+            "resting_heart_rate": resting_heart_rate
+        }
+    );
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        document.getElementById("garminconnectoutput").innerHTML = xmlhttp.responseText;
+        heartRateZones();
+    }
+    xmlhttp.open( "POST", url , true );
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
+    document.getElementById("garminconnectoutput").innerHTML = "updating";
+}
+function setLanguage(language) {
+    let url = "/garmin/language";
+    let data = JSON.stringify({"language": language});
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        location.reload();
+    }
+    xmlhttp.open( "POST", url , true );
+    xmlhttp.setRequestHeader("Content-Type", "application/json");
+    xmlhttp.send(data);
 }
\ No newline at end of file
@@ -24,6 +24,10 @@ function scale_measurement_plots(offset, start_date=null, end_date=null) {
     if(end_date) {
         url = url + "&end_date=" + end_date;
     }
+    let overlay = document.getElementById("overlay_selector");
+    if(overlay && overlay.value) {
+        url = url + "&overlay=" + encodeURIComponent(overlay.value);
+    }
     location.replace(url)
 }
 function heartrate_stat_plot(offset, start_date=null, end_date=null) {
@@ -45,6 +49,10 @@ function heartrate_stat_plot(offset, start_date=null, end_date=null) {
         url = url + "&end_date=" + end_date;
     }
 
+    let overlay = document.getElementById("overlay_selector");
+    if(overlay && overlay.value) {
+        url = url + "&overlay=" + encodeURIComponent(overlay.value);
+    }
     location.replace(url)
 }
 function heartrate_plot() {
@@ -63,6 +71,10 @@ function race_result_plot_world_record_women() {
     let url = "/garmin/race_result_plot_demo?race_type=world_record_women"
     location.replace(url)
 }
+function power_curve_plot(metric, sport, period) {
+    let url = "/garmin/power_curve_demo?metric=" + metric + "&sport=" + sport + "&period=" + period
+    location.replace(url)
+}
 function heartrate_plot_date(start_date, end_date) {
     let url = '/garmin/fitbit/heartrate_plots_demo?start_date=' + start_date + "&end_date=" + end_date;
     location.replace(url)
@@ -87,3 +99,12 @@ function heartrate_plot_button_single(date, button_date) {
     console.log(url);
     location.replace(url)
 }
+function setLanguageDemo(language) {
+    let url = "/garmin/language_demo?language=" + language;
+    let xmlhttp = new XMLHttpRequest();
+    xmlhttp.onload = function() {
+        location.reload();
+    }
+    xmlhttp.open( "GET", url , true );
+    xmlhttp.send(null);
+}
@@ -1,4 +1,4 @@
-function create_init(center_lat, center_lon, zoom_value, runningRouteCoordinates) {
+function create_init(center_lat, center_lon, zoom_value, runningRouteCoordinates, color, lapPins) {
     return function init() {
         let mapOptions = {
             center: { lat: center_lat, lng: center_lon},
@@ -11,14 +11,49 @@ function create_init(center_lat, center_lon, zoom_value, runningRouteCoordinates
         let runningRoute = new google.maps.Polyline({
             path: runningRouteCoordinates,
             geodesic: true,
-            strokeColor: '#FF0000',
+            strokeColor: color || '#FF0000',
             strokeOpacity: 1.0,
             strokeWeight: 2
         });
         runningRoute.setMap(map);
+        (lapPins || []).forEach(function(pin) {
+            new google.maps.Marker({
+                position: { lat: pin[0], lng: pin[1] },
+                label: String(pin[2]),
+                title: "Lap " + pin[2],
+                map: map
+            });
+        });
     };
 }
-function initialize(center_lat, center_lon, zoom_value, runningRouteCoordinates) {
-    let init = create_init(center_lat, center_lon, zoom_value, runningRouteCoordinates);
+function initialize(center_lat, center_lon, zoom_value, runningRouteCoordinates, color, lapPins) {
+    let init = create_init(center_lat, center_lon, zoom_value, runningRouteCoordinates, color, lapPins);
     google.maps.event.addDomListener(window, 'load', init);
 }
+function initialize_routes(routes) {
+    google.maps.event.addDomListener(window, 'load', function init() {
+        let map = new google.maps.Map(
+            document.getElementById('garmin_text_box'),
+            { mapTypeId: google.maps.MapTypeId.SATELLITE }
+        );
+        let bounds = new google.maps.LatLngBounds();
+        routes.forEach(function(route) {
+            let path = route.coordinates.map(function(c) {
+                return new google.maps.LatLng(c[0], c[1]);
+            });
+            path.forEach(function(p) { bounds.extend(p); });
+            let line = new google.maps.Polyline({
+                path: path,
+                geodesic: true,
+                strokeColor: route.color || '#FF0000',
+                strokeOpacity: 1.0,
+                strokeWeight: 2
+            });
+            line.addListener('click', function() {
+                send_command('filter=' + route.filename);
+            });
+            line.setMap(map);
+        });
+        map.fitBounds(bounds);
+    });
+}
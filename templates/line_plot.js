@@ -1,4 +1,4 @@
-function line_plot(data, title, xaxis, yaxis) {
+function line_plot(data, title, xaxis, yaxis, color, markers, description=null) {
     // Set the dimensions of the canvas / graph
     let margin = {top: 30, right: 20, bottom: 30, left: 60};
     let width = 600 - margin.left - margin.right;
@@ -21,6 +21,8 @@ function line_plot(data, title, xaxis, yaxis) {
     // Adds the svg canvas
     let svg = d3.select("body")
         .append("svg")
+            .attr("role", "img")
+            .attr("aria-label", description || title)
             .attr("width", width + margin.left + margin.right)
             .attr("height", height + margin.top + margin.bottom)
         .append("g")
@@ -65,6 +67,7 @@ function line_plot(data, title, xaxis, yaxis) {
 
     svg.append("path")
         .attr("class", "line")
+        .style("stroke", color || "steelblue")
         .attr("d", valueline(data));
     svg.append("g")
         .attr("class", "xaxis")
@@ -74,6 +77,25 @@ function line_plot(data, title, xaxis, yaxis) {
         .attr("class", "yaxis")
         .call(yAxis);
 
+    // Vertical markers with a label, e.g. lap boundaries
+    (markers || []).forEach(function(m) {
+        if ((m[0] < xmin) || (m[0] > xmax)) {
+            return;
+        }
+        svg.append("line")
+            .attr("x1", x(m[0]))
+            .attr("x2", x(m[0]))
+            .attr("y1", y(ymin))
+            .attr("y2", y(ymax))
+            .attr("stroke", "gray")
+            .style("stroke-dasharray", ("3, 3"));
+        svg.append("text")
+            .attr("x", x(m[0]) + 2)
+            .attr("y", y(ymax) + 10)
+            .style("font-size", "10px")
+            .text(m[1]);
+    });
+
     let rule = svg.append("g")
         .append("line")
         .attr("y1", y(ymin))
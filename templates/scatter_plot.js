@@ -1,16 +1,18 @@
-function scatter_plot(data, title, xlabel, ylabel, xStep, yStep) {
+function scatter_plot(data, title, xlabel, ylabel, xStep, yStep, color, description=null) {
     var margin = {top: 20, right: 90, bottom: 30, left: 50},
     width = 960 - margin.left - margin.right,
     height = 500 - margin.top - margin.bottom;
 
     var x = d3.scaleLinear().range([0, width]),
         y = d3.scaleLinear().range([height, 0]),
-        z = d3.scaleLinear().range(["white", "steelblue"]);
+        z = d3.scaleLinear().range(["white", color || "steelblue"]);
 
     // The size of the buckets in the CSV data file.
     // This could be inferred from the data if it weren't sparse.
 
     var svg = d3.select("body").append("svg")
+        .attr("role", "img")
+        .attr("aria-label", description || title)
         .attr("width", width + margin.left + margin.right)
         .attr("height", height + margin.top + margin.bottom)
     .append("g")
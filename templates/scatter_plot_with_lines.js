@@ -35,6 +35,8 @@ function scatter_plot_with_lines(
     // Adds the svg canvas
     var svg = d3.select("body")
         .append("svg")
+            .attr("role", "img")
+            .attr("aria-label", title)
             .attr("width", width + margin.left + margin.right)
             .attr("height", height + margin.top + margin.bottom)
         .append("g")
@@ -1,6 +1,6 @@
-function time_series(data, title, xaxis, yaxis, units) {
+function time_series(data, title, xaxis, yaxis, units, overlay=null, goal=null, projection=null) {
     // Set the dimensions of the canvas / graph
-    let margin = {top: 30, right: 20, bottom: 30, left: 60};
+    let margin = {top: 30, right: overlay ? 60 : 20, bottom: 30, left: 60};
     let width = 600 - margin.left - margin.right;
     let height = 270 - margin.top - margin.bottom;
 
@@ -19,6 +19,8 @@ function time_series(data, title, xaxis, yaxis, units) {
     // Adds the svg canvas
     let svg = d3.select("body")
         .append("svg")
+            .attr("role", "img")
+            .attr("aria-label", title)
             .attr("width", width + margin.left + margin.right)
             .attr("height", height + margin.top + margin.bottom)
         .append("g")
@@ -57,10 +59,24 @@ function time_series(data, title, xaxis, yaxis, units) {
     let ymax = d3.max(data, function(d) {return d[1]});
     let ymin = d3.min(data, function(d) {return d[1]});
 
+    if (goal !== null) {
+        ymax = Math.max(ymax, goal);
+        ymin = Math.min(ymin, goal);
+    }
+
+    if (projection && projection.data.length > 0) {
+        projection.data.forEach(function(d) {
+            d[0] = parseDateTime(d[0]);
+        });
+        xmax = d3.max([xmax, d3.max(projection.data, function(d) {return d[0]})]);
+        ymax = Math.max(ymax, d3.max(projection.data, function(d) {return d[3]}));
+        ymin = Math.min(ymin, d3.min(projection.data, function(d) {return d[2]}));
+    }
+
     ymax = ymax + 0.1 * Math.abs(ymax);
     ymin = ymin - 0.1 * Math.abs(ymin);
 
-    x.domain(d3.extent(data, function(d) {return d[0]; }));
+    x.domain([xmin, xmax]);
     y.domain([ymin, ymax]);
 
     // Define the line
@@ -79,6 +95,77 @@ function time_series(data, title, xaxis, yaxis, units) {
 
     svg.append("g").attr("class", "yaxis").call(yAxis);
 
+    // Optional goal drawn as a horizontal line
+    if (goal !== null) {
+        svg.append("line")
+            .attr("x1", 0)
+            .attr("x2", width)
+            .attr("y1", y(goal))
+            .attr("y2", y(goal))
+            .style("stroke", "green")
+            .style("stroke-dasharray", "6,3");
+        svg.append("text")
+            .attr("x", width - 5)
+            .attr("y", y(goal) - 4)
+            .style("text-anchor", "end")
+            .style("fill", "green")
+            .text("goal " + goal + " " + units);
+    }
+
+    // Optional projection of the trend with its uncertainty band
+    if (projection && projection.data.length > 0) {
+        let band = d3.area()
+            .x(function(d) { return x(d[0]); })
+            .y0(function(d) { return y(d[2]); })
+            .y1(function(d) { return y(d[3]); });
+        svg.append("path")
+            .attr("d", band(projection.data))
+            .style("fill", "lightgreen")
+            .style("opacity", 0.4);
+        let projectionline = d3.line()
+            .x(function(d) { return x(d[0]); })
+            .y(function(d) { return y(d[1]); });
+        svg.append("path")
+            .attr("d", projectionline(projection.data))
+            .style("fill", "none")
+            .style("stroke", "green")
+            .style("stroke-dasharray", "2,2");
+    }
+
+    // Optional biomarker series on a secondary y-axis sharing the time axis
+    if (overlay && overlay.data.length > 0) {
+        overlay.data.forEach(function(d) {
+            d[0] = parseDateTime(d[0]);
+        });
+        let y2 = d3.scaleLinear().range([height, 0]);
+        let y2max = d3.max(overlay.data, function(d) {return d[1]});
+        let y2min = d3.min(overlay.data, function(d) {return d[1]});
+        y2.domain([
+            y2min - 0.1 * Math.abs(y2min),
+            y2max + 0.1 * Math.abs(y2max)
+        ]);
+        let overlayline = d3.line()
+            .defined(function(d) { return d[0] >= xmin && d[0] <= xmax; })
+            .x(function(d) { return x(d[0]); })
+            .y(function(d) { return y2(d[1]); });
+        svg.append("path")
+            .attr("d", overlayline(overlay.data))
+            .style("fill", "none")
+            .style("stroke", "darkorange")
+            .style("stroke-dasharray", "4,2");
+        svg.append("g")
+            .attr("class", "yaxis")
+            .attr("transform", "translate(" + width + ",0)")
+            .call(d3.axisRight(y2).ticks(5));
+        svg.append("text")
+            .attr("y", width + margin.right - 10)
+            .attr("x", 0 - (height / 2))
+            .attr("transform", "rotate(-90)")
+            .style("text-anchor", "middle")
+            .style("fill", "darkorange")
+            .text(overlay.name + " [" + overlay.units + "]");
+    }
+
     function wrap(text, width) {
         text.each(function() {
             var text = d3.select(this),